@@ -190,7 +190,7 @@ router.post('/create-rental', async (req, res) => {
         // Schedule SSH user cleanup
         setTimeout(() => {
             console.log(`🧹 Cleaning up SSH user ${sshUsername} after rental period`);
-            const cleanupProcess = spawn('bash', ['-c', `echo "delete_user:${sshUsername}" | nc -U /tmp/eryzaa_ssh_service.sock`]);
+            const cleanupProcess = spawn('eryzaa-ssh-service', ['remove', sshUsername]);
             cleanupProcess.on('close', (code) => {
                 console.log(`SSH user ${sshUsername} cleanup completed with code ${code}`);
             });
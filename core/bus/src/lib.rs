@@ -0,0 +1,207 @@
+//! Control-plane event bus
+//! Subsystems publish `Event`s and subscribe to them by topic; a single node uses the
+//! in-process backend, coordinator deployments share the same events over NATS (`nats` feature).
+
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+pub use eryzaa_protocol::event::{topic_matches, Envelope, Event};
+
+#[cfg(feature = "nats")]
+pub mod nats;
+
+/// Environment variable with the NATS server to use instead of the in-process bus
+pub const NATS_URL_ENV: &str = "ERYZAA_NATS_URL";
+
+/// Events buffered per in-process subscriber before the slowest one starts missing some
+const LOCAL_CAPACITY: usize = 1024;
+
+pub enum Bus {
+    Local(LocalBus),
+    #[cfg(feature = "nats")]
+    Nats(nats::NatsBus),
+}
+
+impl Bus {
+    /// In-process bus for a single node
+    pub fn local(source: &str) -> Self {
+        Bus::Local(LocalBus::new(source))
+    }
+
+    /// NATS bus if `ERYZAA_NATS_URL` is set, otherwise the in-process bus
+    pub fn from_env(source: &str) -> Result<Self, String> {
+        match std::env::var(NATS_URL_ENV) {
+            Ok(url) => Self::connect_nats(&url, source),
+            Err(_) => Ok(Self::local(source)),
+        }
+    }
+
+    #[cfg(feature = "nats")]
+    fn connect_nats(url: &str, source: &str) -> Result<Self, String> {
+        nats::NatsBus::connect_blocking(url, source).map(Bus::Nats)
+    }
+
+    #[cfg(not(feature = "nats"))]
+    fn connect_nats(url: &str, _source: &str) -> Result<Self, String> {
+        Err(format!("Cannot use NATS at {}: built without the `nats` feature", url))
+    }
+
+    pub fn backend(&self) -> &'static str {
+        match self {
+            Bus::Local(_) => "local",
+            #[cfg(feature = "nats")]
+            Bus::Nats(_) => "nats",
+        }
+    }
+
+    /// Publish an event without waiting for delivery
+    pub fn publish(&self, event: Event) {
+        match self {
+            Bus::Local(bus) => bus.publish(event),
+            #[cfg(feature = "nats")]
+            Bus::Nats(bus) => bus.publish(event),
+        }
+    }
+
+    /// Receive every event whose topic falls under `prefix`
+    pub fn subscribe(&self, prefix: &str) -> Subscription {
+        match self {
+            Bus::Local(bus) => bus.subscribe(prefix),
+            #[cfg(feature = "nats")]
+            Bus::Nats(bus) => bus.subscribe(prefix),
+        }
+    }
+}
+
+/// In-process bus on a tokio broadcast channel; publishing needs no runtime
+pub struct LocalBus {
+    source: String,
+    sender: broadcast::Sender<Envelope>,
+}
+
+impl LocalBus {
+    pub fn new(source: &str) -> Self {
+        let (sender, _) = broadcast::channel(LOCAL_CAPACITY);
+        Self {
+            source: source.to_string(),
+            sender,
+        }
+    }
+
+    pub fn publish(&self, event: Event) {
+        // No subscribers is not an error
+        let _ = self.sender.send(envelope(&self.source, event));
+    }
+
+    pub fn subscribe(&self, prefix: &str) -> Subscription {
+        Subscription {
+            inner: Inner::Local {
+                receiver: self.sender.subscribe(),
+                prefix: prefix.to_string(),
+            },
+        }
+    }
+}
+
+pub struct Subscription {
+    inner: Inner,
+}
+
+enum Inner {
+    Local {
+        receiver: broadcast::Receiver<Envelope>,
+        prefix: String,
+    },
+    #[cfg_attr(not(feature = "nats"), allow(dead_code))]
+    Forwarded(tokio::sync::mpsc::UnboundedReceiver<Envelope>),
+}
+
+impl Subscription {
+    /// Next matching event, or None once the bus is gone
+    pub async fn next(&mut self) -> Option<Envelope> {
+        match &mut self.inner {
+            Inner::Local { receiver, prefix } => loop {
+                match receiver.recv().await {
+                    Ok(envelope) if topic_matches(prefix, envelope.event.topic()) => return Some(envelope),
+                    Ok(_) => continue,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        log::warn!("Event subscriber fell behind, missed {} events", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            },
+            Inner::Forwarded(receiver) => receiver.recv().await,
+        }
+    }
+
+    /// Blocking `next` for threads outside a tokio runtime
+    pub fn blocking_next(&mut self) -> Option<Envelope> {
+        match &mut self.inner {
+            Inner::Local { receiver, prefix } => loop {
+                match receiver.blocking_recv() {
+                    Ok(envelope) if topic_matches(prefix, envelope.event.topic()) => return Some(envelope),
+                    Ok(_) => continue,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        log::warn!("Event subscriber fell behind, missed {} events", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            },
+            Inner::Forwarded(receiver) => receiver.blocking_recv(),
+        }
+    }
+
+    /// A matching event if one is already waiting
+    pub fn try_next(&mut self) -> Option<Envelope> {
+        match &mut self.inner {
+            Inner::Local { receiver, prefix } => loop {
+                match receiver.try_recv() {
+                    Ok(envelope) if topic_matches(prefix, envelope.event.topic()) => return Some(envelope),
+                    Ok(_) => continue,
+                    Err(broadcast::error::TryRecvError::Lagged(missed)) => {
+                        log::warn!("Event subscriber fell behind, missed {} events", missed);
+                    }
+                    Err(_) => return None,
+                }
+            },
+            Inner::Forwarded(receiver) => receiver.try_recv().ok(),
+        }
+    }
+}
+
+fn envelope(source: &str, event: Event) -> Envelope {
+    Envelope {
+        source: source.to_string(),
+        timestamp_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default(),
+        event,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_bus_filters_by_topic() {
+        let bus = Bus::local("node-1");
+        let mut scheduler = bus.subscribe("scheduler");
+        let mut everything = bus.subscribe("");
+
+        bus.publish(Event::EnergyMetered { job_id: "abc".to_string(), wh: 12.5 });
+        bus.publish(Event::JobStarted { job_id: "abc".to_string() });
+
+        let started = scheduler.try_next().unwrap();
+        assert_eq!(started.source, "node-1");
+        assert_eq!(started.event, Event::JobStarted { job_id: "abc".to_string() });
+        assert!(scheduler.try_next().is_none());
+
+        assert_eq!(everything.try_next().unwrap().event.topic(), "metering.energy");
+        assert_eq!(everything.try_next().unwrap().event.topic(), "scheduler.started");
+
+        assert!(topic_matches("ssh.user_created", "ssh.user_created"));
+        assert!(!topic_matches("ssh", "sshd.started"));
+    }
+}
@@ -0,0 +1,107 @@
+//! NATS backend, built with the `nats` feature
+//! Events travel as JSON envelopes on `eryzaa.<topic>` subjects so every node and coordinator
+//! connected to the same server sees the events the single-node bus would carry.
+
+use futures::StreamExt;
+use log::warn;
+use std::sync::Arc;
+use tokio::runtime::{Handle, Runtime};
+use tokio::sync::mpsc;
+
+use crate::{envelope, Event, Inner, Subscription};
+
+const SUBJECT_ROOT: &str = "eryzaa";
+
+pub struct NatsBus {
+    source: String,
+    client: async_nats::Client,
+    handle: Handle,
+    _runtime: Option<Arc<Runtime>>, // Owned when connected from synchronous code
+}
+
+impl NatsBus {
+    /// Connect from inside a tokio runtime
+    pub async fn connect(url: &str, source: &str) -> Result<Self, String> {
+        let client = async_nats::connect(url)
+            .await
+            .map_err(|e| format!("Failed to connect to NATS at {}: {}", url, e))?;
+        Ok(Self {
+            source: source.to_string(),
+            client,
+            handle: Handle::current(),
+            _runtime: None,
+        })
+    }
+
+    /// Connect from synchronous code, starting a small runtime for the client
+    pub fn connect_blocking(url: &str, source: &str) -> Result<Self, String> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .map_err(|e| format!("Failed to start NATS runtime: {}", e))?;
+        let mut bus = runtime.block_on(Self::connect(url, source))?;
+        bus._runtime = Some(Arc::new(runtime));
+        Ok(bus)
+    }
+
+    pub fn publish(&self, event: Event) {
+        let subject = format!("{}.{}", SUBJECT_ROOT, event.topic());
+        let payload = match serde_json::to_vec(&envelope(&self.source, event)) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Failed to serialize event: {}", e);
+                return;
+            }
+        };
+
+        let client = self.client.clone();
+        self.handle.spawn(async move {
+            if let Err(e) = client.publish(subject.clone(), payload.into()).await {
+                warn!("Failed to publish {}: {}", subject, e);
+            }
+        });
+    }
+
+    pub fn subscribe(&self, prefix: &str) -> Subscription {
+        let subject = subject_for(prefix);
+        let client = self.client.clone();
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        self.handle.spawn(async move {
+            let mut messages = match client.subscribe(subject.clone()).await {
+                Ok(messages) => messages,
+                Err(e) => {
+                    warn!("Failed to subscribe to {}: {}", subject, e);
+                    return;
+                }
+            };
+            while let Some(message) = messages.next().await {
+                match serde_json::from_slice(&message.payload) {
+                    Ok(envelope) => {
+                        if sender.send(envelope).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => warn!("Ignoring malformed event on {}: {}", message.subject, e),
+                }
+            }
+        });
+
+        Subscription {
+            inner: Inner::Forwarded(receiver),
+        }
+    }
+}
+
+/// NATS subject covering a topic prefix
+fn subject_for(prefix: &str) -> String {
+    if prefix.is_empty() {
+        format!("{}.>", SUBJECT_ROOT)
+    } else if prefix.contains('.') {
+        // A full `<subsystem>.<event>` topic
+        format!("{}.{}", SUBJECT_ROOT, prefix)
+    } else {
+        format!("{}.{}.>", SUBJECT_ROOT, prefix)
+    }
+}
@@ -1,11 +1,89 @@
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
 use std::process::Command;
 use std::fs::{File, create_dir_all};
 use std::path::Path;
 use std::thread;
 use std::time::Duration;
+use eryzaa_discovery::abuse::ReportStore;
+use eryzaa_discovery::coordinators::{self, CoordinatorSet};
+use eryzaa_discovery::diagnostics;
+use eryzaa_discovery::handoffs::HandoffRelay;
+use eryzaa_discovery::market::{self, CoordinatorStores, MarketStats};
+use eryzaa_discovery::observers::ObserverStore;
+use eryzaa_discovery::push::{self, PushStore};
+use eryzaa_discovery::quota::{self, QuotaLimits, QuotaUpdate, Quotas};
+use eryzaa_discovery::registration::{self, RegistrationStore};
+use eryzaa_discovery::rendezvous::RendezvousStore;
+use eryzaa_jobs::{abuse, cache, compose, demo, exec, outputs, pipeline, requirements, rollout, schema, spec, staging, sweep, transfers};
+use eryzaa_payments::{ClientAccess, DepositClaim, ReputationAttestation};
+use eryzaa_ssh_manager::grants::{self, AccessScope, GrantRequest};
+use eryzaa_ssh_manager::SourceRestriction;
+use eryzaa_jobs::{CredentialBundle, ExecRequest, ExecutorConfig, HandoffLink, HandoffStore, JobManager, NodeBookmark, NodeCache, OperationQueue, PauseOptions, Profile, ProfileStore, QueuedAction, SshConfigWriter, WorkspaceStore};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() > 1 && args[1] == "state" {
+        return run_state_command(&args[2..]);
+    }
+    // State saved by an older build is brought up to date; a newer build's is left alone
+    if let Err(e) = schema::prepare() {
+        eprintln!("[-] {}", e);
+        std::process::exit(1);
+    }
+    if args.len() > 1 && args[1] == "job" {
+        return run_job_command(&args[2..]);
+    }
+    if args.len() > 1 && args[1] == "hosts" {
+        return run_hosts_command(&args[2..]);
+    }
+    if args.len() > 1 && args[1] == "profile" {
+        return run_profile_command(&args[2..]);
+    }
+    if args.len() > 1 && args[1] == "migrate" {
+        return run_migrate_command(&args[2..]);
+    }
+    if args.len() > 1 && args[1] == "compat" {
+        return run_compat_command(&args[2..]);
+    }
+    if args.len() > 1 && args[1] == "pipeline" {
+        return run_pipeline_command(&args[2..]);
+    }
+    if args.len() > 1 && args[1] == "sweep" {
+        return run_sweep_command(&args[2..]);
+    }
+    if args.len() > 1 && args[1] == "cache" {
+        return run_cache_command(&args[2..]);
+    }
+    if args.len() > 1 && args[1] == "workspace" {
+        return run_workspace_command(&args[2..]);
+    }
+    if args.len() > 1 && args[1] == "coordinator" {
+        return run_coordinator_command(&args[2..]);
+    }
+    if args.len() > 1 && args[1] == "abuse" {
+        return run_abuse_command(&args[2..]);
+    }
+    if args.len() > 1 && args[1] == "deposit" {
+        return run_deposit_command(&args[2..]);
+    }
+    if args.len() > 1 && args[1] == "fleet" {
+        return run_fleet_command(&args[2..]);
+    }
+    if args.len() > 1 && args[1] == "network" {
+        return run_network_command(&args[2..]);
+    }
+    if args.len() > 1 && args[1] == "demo" {
+        return run_demo_command(&args[2..]);
+    }
+    #[cfg(feature = "nats")]
+    if args.len() > 1 && args[1] == "events" {
+        return run_events_command(&args[2..]);
+    }
+    #[cfg(feature = "chaos")]
+    if args.len() > 1 && args[1] == "chaos" {
+        return run_chaos_command(&args[2..]);
+    }
+    
     println!("=== Docker-based Rental Server Client ===");
     
     // === Step 1: Check and install Docker ===
@@ -389,18 +467,1645 @@ fn get_container_zerotier_ip() -> Option<String> {
     None
 }
 
+// Pin the container's SSH host key, read over the local docker channel
+fn pin_container_host_key(ip: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let output = Command::new("docker")
+        .args(&["exec", "rental-server", "cat", "/etc/ssh/ssh_host_ed25519_key.pub"])
+        .output()?;
+    
+    if !output.status.success() {
+        return Err("Could not read the container's SSH host key".into());
+    }
+    
+    let key = String::from_utf8_lossy(&output.stdout);
+    SshConfigWriter::for_current_user().pin_host_key(&[ip.to_string()], &key)?;
+    Ok(())
+}
+
 // Connect to the server via SSH
 fn connect_to_server(ip: &str) -> Result<(), Box<dyn std::error::Error>> {
     println!("[*] Connecting to rental server...");
     
+    if let Err(e) = pin_container_host_key(ip) {
+        println!("[!] {}", e);
+    }
+    
+    let host_key_args = SshConfigWriter::for_current_user().host_key_args(ip);
     let status = Command::new("ssh")
-        .args(&["-o", "StrictHostKeyChecking=no", &format!("rental@{}", ip)])
+        .args(&host_key_args)
+        .arg(format!("rental@{}", ip))
         .status()?;
     
     if !status.success() {
         println!("[!] SSH connection failed. You can try manually:");
-        println!("    ssh -o StrictHostKeyChecking=no rental@{}", ip);
+        println!("    ssh {} rental@{}", host_key_args.join(" "), ip);
+    }
+    
+    Ok(())
+}
+
+// Handle `job` subcommands
+fn run_job_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    match args.first().map(|s| s.as_str()) {
+        Some("exec") => {
+            // eryzaa job exec <id> [-- command...]
+            let job_id = args.get(1).ok_or("Usage: job exec <id> [-- command...]")?;
+            let command: Vec<String> = match args.iter().position(|a| a == "--") {
+                Some(idx) => args[idx + 1..].to_vec(),
+                None => args[2..].to_vec(),
+            };
+            
+            let request = if command.is_empty() {
+                ExecRequest::shell(job_id)
+            } else {
+                ExecRequest::new(job_id, command)
+            };
+            
+            let manager = JobManager::load_from(&eryzaa_jobs::default_registry_path())?;
+            let code = manager.exec(&request)?;
+            if code != 0 {
+                std::process::exit(code);
+            }
+            Ok(())
+        }
+        Some("pause") => {
+            // eryzaa job pause <id> [--release-gpu]
+            let job_id = args.get(1).ok_or("Usage: job pause <id> [--release-gpu]")?;
+            let options = PauseOptions {
+                release_gpu: args.iter().any(|a| a == "--release-gpu"),
+            };
+            
+            let registry = eryzaa_jobs::default_registry_path();
+            let manager = JobManager::load_from(&registry)?;
+            manager.pause_job(job_id, &options)?;
+            manager.save_to(&registry)?;
+            println!("[+] Job {} paused", job_id);
+            Ok(())
+        }
+        Some("resume") => {
+            let job_id = args.get(1).ok_or("Usage: job resume <id>")?;
+            
+            let registry = eryzaa_jobs::default_registry_path();
+            let manager = JobManager::load_from(&registry)?;
+            manager.resume_job(job_id)?;
+            manager.save_to(&registry)?;
+            println!("[+] Job {} resumed", job_id);
+            Ok(())
+        }
+        Some("cancel") => {
+            let job_id = args.get(1).ok_or("Usage: job cancel <id>")?;
+            
+            let registry = eryzaa_jobs::default_registry_path();
+            let manager = JobManager::load_from(&registry)?;
+            let action = QueuedAction::Cancel { job_id: job_id.clone() };
+            match manager.execute_queued(&action) {
+                Ok(()) => {
+                    manager.save_to(&registry)?;
+                    println!("[+] Job {} cancelled", job_id);
+                }
+                Err(e) => {
+                    // Keep the cancellation so it goes through once the node is back
+                    let queue_path = eryzaa_jobs::offline::default_queue_path();
+                    let queue = OperationQueue::load_from(&queue_path)?;
+                    queue.enqueue(action);
+                    queue.save_to(&queue_path)?;
+                    println!("[!] {}; cancellation queued ({} pending)", e, queue.len());
+                }
+            }
+            Ok(())
+        }
+        Some("queue") => {
+            // eryzaa job queue [replay]
+            let queue_path = eryzaa_jobs::offline::default_queue_path();
+            let queue = OperationQueue::load_from(&queue_path)?;
+            
+            if args.get(1).map(|s| s.as_str()) == Some("replay") {
+                let registry = eryzaa_jobs::default_registry_path();
+                let manager = JobManager::load_from(&registry)?;
+                let report = queue.replay(|action| manager.execute_queued(action));
+                manager.save_to(&registry)?;
+                queue.save_to(&queue_path)?;
+                
+                println!("[+] Replayed {} operations, {} still queued", report.completed.len(), report.remaining);
+                if let Some(e) = report.error {
+                    println!("[!] Stopped at: {}", e);
+                }
+                return Ok(());
+            }
+            
+            for op in queue.pending() {
+                println!("{}  queued {}  attempts {}  {}",
+                         op.action, op.queued_at.format("%Y-%m-%d %H:%M"), op.attempts,
+                         op.last_error.unwrap_or_default());
+            }
+            Ok(())
+        }
+        Some("ssh-config") => {
+            // Write Host blocks for all known jobs into ~/.ssh/config.d/eryzaa
+            let manager = JobManager::load_from(&eryzaa_jobs::default_registry_path())?;
+            let writer = SshConfigWriter::for_current_user();
+            writer.sync(&manager.list_jobs())?;
+            println!("[+] Wrote {}", writer.config_path().display());
+            Ok(())
+        }
+        Some("list") => {
+            let manager = JobManager::load_from(&eryzaa_jobs::default_registry_path())?;
+            for job in manager.list_jobs() {
+                println!("{}  {:?}  {}  {}  {:.2} kWh", job.job_id, job.status, job.image,
+                         job.connect_host().unwrap_or_else(|| "local".to_string()),
+                         job.energy_wh / 1000.0);
+                if let Some(push) = &job.output_push {
+                    for url in &push.urls {
+                        println!("    -> {}", url);
+                    }
+                    if let Some(e) = &push.error {
+                        println!("    [-] Output push failed ({} attempts): {}", push.attempts, e);
+                    }
+                }
+            }
+            Ok(())
+        }
+        Some("grant") => {
+            // eryzaa job grant <id> <identity> <ssh|logs|full> <public-key-file>
+            let usage = "Usage: job grant <id> <identity> <ssh|logs|full> <public-key-file>";
+            let (job_id, identity, scope, key_path) = match args.get(1..5) {
+                Some([job_id, identity, scope, key_path]) => (job_id, identity, scope, key_path),
+                _ => return Err(usage.into()),
+            };
+            let scope = AccessScope::parse(scope)?;
+            grants::validate_identity(identity)?;
+
+            let manager = JobManager::load_from(&eryzaa_jobs::default_registry_path())?;
+            let job = manager.get_job(job_id).ok_or(format!("No job found with id '{}'", job_id))?;
+            let token = job.access_token.clone().ok_or(format!("Job '{}' has no access token to share it with", job_id))?;
+            let mut request = GrantRequest::new(job_id, identity, &token);
+            request.scope = Some(scope);
+            request.public_key = Some(std::fs::read_to_string(key_path)?.trim().to_string());
+            let grantee_token = (scope == AccessScope::Full).then(grants::new_token);
+            request.grantee_token_hash = grantee_token.as_deref().map(grants::token_hash);
+
+            exec::file_grant_request(&job, &request)?;
+            println!("[+] Asked the node to give {} {} access to job {}", identity, scope.label(), job_id);
+            if let Some(grantee_token) = grantee_token {
+                println!("[*] {} can share the job in turn with this token: {}", identity, grantee_token);
+            }
+            Ok(())
+        }
+        Some("revoke-grant") => {
+            // eryzaa job revoke-grant <id> <identity>
+            let (job_id, identity) = match args.get(1..3) {
+                Some([job_id, identity]) => (job_id, identity),
+                _ => return Err("Usage: job revoke-grant <id> <identity>".into()),
+            };
+            let manager = JobManager::load_from(&eryzaa_jobs::default_registry_path())?;
+            let job = manager.get_job(job_id).ok_or(format!("No job found with id '{}'", job_id))?;
+            let token = job.access_token.clone().ok_or(format!("Job '{}' has no access token", job_id))?;
+
+            exec::file_grant_request(&job, &GrantRequest::new(job_id, identity, &token))?;
+            println!("[+] Asked the node to revoke the access of {} to job {}", identity, job_id);
+            Ok(())
+        }
+        Some("restrict") => {
+            // eryzaa job restrict <id> [--from <ip|cidr>]... [--key <public-key-file|SHA256:...>]...
+            let usage = "Usage: job restrict <id> [--from <ip|cidr>]... [--key <public-key-file|SHA256:...>]...";
+            let job_id = args.get(1).ok_or(usage)?;
+            let mut sources = SourceRestriction::default();
+            let mut flags = args[2..].iter();
+            while let Some(flag) = flags.next() {
+                match (flag.as_str(), flags.next()) {
+                    ("--from", Some(address)) => sources.addresses.push(address.clone()),
+                    ("--key", Some(key)) if key.starts_with("SHA256:") => sources.fingerprints.push(key.clone()),
+                    ("--key", Some(key_path)) => {
+                        let key = std::fs::read_to_string(key_path)?;
+                        let fingerprint = grants::fingerprint(key.trim()).ok_or(format!("{} holds no public key", key_path))?;
+                        sources.fingerprints.push(fingerprint);
+                    }
+                    _ => return Err(usage.into()),
+                }
+            }
+            sources.validate()?;
+
+            let manager = JobManager::load_from(&eryzaa_jobs::default_registry_path())?;
+            let job = manager.get_job(job_id).ok_or(format!("No job found with id '{}'", job_id))?;
+            let token = job.access_token.clone().ok_or(format!("Job '{}' has no access token", job_id))?;
+            let mut request = GrantRequest::new(job_id, "owner", &token);
+            request.sources = Some(sources.clone());
+
+            exec::file_grant_request(&job, &request)?;
+            match sources.is_empty() {
+                true => println!("[+] Asked the node to let job {} be used from anywhere", job_id),
+                false => println!("[+] Asked the node to restrict job {}: {}", job_id, sources.summary()),
+            }
+            Ok(())
+        }
+        Some("share") => {
+            // eryzaa job share <id> [--minutes N]
+            let job_id = args.get(1).ok_or("Usage: job share <id> [--minutes N]")?;
+            let minutes: i64 = match args.iter().position(|a| a == "--minutes") {
+                Some(idx) => args.get(idx + 1).ok_or("--minutes needs a value")?.parse()?,
+                None => 15,
+            };
+
+            let manager = JobManager::load_from(&eryzaa_jobs::default_registry_path())?;
+            let job = manager.get_job(job_id).ok_or(format!("No job found with id '{}'", job_id))?;
+
+            let mut bundle = CredentialBundle::for_job(&job);
+            if let Err(e) = bundle.attach_job_key(&job) {
+                println!("[!] Sharing without an SSH key: {}", e);
+            }
+
+            let store_path = eryzaa_jobs::handoff::default_store_path();
+            let store = HandoffStore::load_from(&store_path)?;
+            let link = store.issue(&bundle, chrono::Duration::minutes(minutes))?;
+            let relayed = match CoordinatorSet::from_env() {
+                Some(coordinators) => store.relay(&link.id, &coordinators).map_err(|e| e.to_string()),
+                None => Err(format!("no coordinator configured (set {})", market::COORDINATOR_URL_ENV)),
+            };
+            store.save_to(&store_path)?;
+
+            println!("{}", link.qr_text()?);
+            println!("[+] One-time link (expires in {} min): {}", minutes, link);
+            match relayed {
+                Ok(url) => println!("    Redeemable from any machine through {}", url),
+                Err(e) => println!("[!] Only redeemable on this machine: {}", e),
+            }
+            println!("    Revoke with: job revoke {}", link.id);
+            Ok(())
+        }
+        Some(kind @ ("seal-input" | "seal-output")) => {
+            // eryzaa job seal-input|seal-output <id> <source> <target> [--minutes N], credentials JSON on stdin
+            let usage = format!("Usage: job {} <id> <source> <target> [--minutes N] < credentials.json", kind);
+            let (job_id, source, target) = match args.get(1..4) {
+                Some([job_id, source, target]) => (job_id, source, target),
+                _ => return Err(usage.into()),
+            };
+            // Outputs are pushed after the job ends, so their credentials default to the longest life
+            let default_minutes = match kind {
+                "seal-input" => 60,
+                _ => staging::MAX_CREDENTIAL_TTL_HOURS * 60,
+            };
+            let minutes: i64 = match args.iter().position(|a| a == "--minutes") {
+                Some(idx) => args.get(idx + 1).ok_or("--minutes needs a value")?.parse()?,
+                None => default_minutes,
+            };
+
+            let manager = JobManager::load_from(&eryzaa_jobs::default_registry_path())?;
+            let job = manager.get_job(job_id).ok_or(format!("No job found with id '{}'", job_id))?;
+            let token = job.access_token.ok_or(format!("Job '{}' has no access token to seal for", job_id))?;
+
+            let mut json = String::new();
+            io::stdin().read_to_string(&mut json)?;
+            let credentials: staging::SourceCredentials = serde_json::from_str(&json)?;
+            let ttl = chrono::Duration::minutes(minutes);
+            let entry = match kind {
+                "seal-input" => serde_json::to_string_pretty(&staging::JobInput {
+                    source: source.clone(),
+                    target: target.clone(),
+                    credentials: Some(staging::seal_credentials(&credentials, source, &token, ttl)?),
+                })?,
+                _ => serde_json::to_string_pretty(&outputs::JobOutput {
+                    source: source.clone(),
+                    target: target.clone(),
+                    credentials: Some(staging::seal_credentials(&credentials, target, &token, ttl)?),
+                })?,
+            };
+            println!("{}", entry);
+            let list = if kind == "seal-input" { "inputs" } else { "outputs" };
+            eprintln!("[+] Add this to the spec's {}; the node can open it for {} min", list, minutes);
+            Ok(())
+        }
+        Some("redeem") => {
+            let link = HandoffLink::parse(args.get(1).ok_or("Usage: job redeem <link>")?)?;
+
+            let store_path = eryzaa_jobs::handoff::default_store_path();
+            let store = HandoffStore::load_from(&store_path)?;
+            let coordinators = CoordinatorSet::from_env().unwrap_or_else(|| CoordinatorSet::new(vec![]));
+            let redeemed = store.redeem_anywhere(&link, &coordinators);
+            store.save_to(&store_path)?;
+            let bundle = redeemed?;
+
+            let writer = SshConfigWriter::for_current_user();
+            if let Some(key) = &bundle.private_key {
+                let path = writer.install_identity(&bundle.job_id, key, bundle.certificate.as_deref())?;
+                println!("[+] Installed key {}", path.display());
+            }
+
+            let registry = eryzaa_jobs::default_registry_path();
+            let manager = JobManager::load_from(&registry)?;
+            manager.register_job(bundle.to_job_record());
+            manager.save_to(&registry)?;
+            writer.sync(&manager.list_jobs())?;
+            println!("[+] Access to job {} added", bundle.job_id);
+            Ok(())
+        }
+        Some("revoke") => {
+            let id = args.get(1).ok_or("Usage: job revoke <handoff-id>")?;
+
+            let store_path = eryzaa_jobs::handoff::default_store_path();
+            let store = HandoffStore::load_from(&store_path)?;
+            let job_id = store.list_offers().into_iter().find(|offer| &offer.id == id).map(|offer| offer.job_id);
+            let manager = JobManager::load_from(&eryzaa_jobs::default_registry_path())?;
+            let job = job_id.and_then(|job_id| manager.get_job(&job_id));
+            let revoked = store.revoke_everywhere(id, job.as_ref());
+            store.save_to(&store_path)?;
+            revoked?;
+            println!("[+] Handoff {} revoked", id);
+            Ok(())
+        }
+        Some("handoffs") => {
+            let store_path = eryzaa_jobs::handoff::default_store_path();
+            let store = HandoffStore::load_from(&store_path)?;
+            store.refresh();
+            store.save_to(&store_path)?;
+            let now = chrono::Utc::now();
+            for offer in store.list_offers() {
+                println!("{}  job {}  {:?}  expires {}", offer.id, offer.job_id, offer.status(now),
+                         offer.expires_at.format("%Y-%m-%d %H:%M"));
+            }
+            Ok(())
+        }
+        Some("analyze") => {
+            // eryzaa job analyze <spec.yaml> [node-id]
+            let file = args.get(1).ok_or("Usage: job analyze <spec.yaml> [node-id]")?;
+            let needs = requirements::JobNeeds::measure(&spec::load_spec(Path::new(file))?);
+            let image = needs.image_gb.map(|gb| format!("{:.1} GB", gb)).unwrap_or_else(|| "unknown size".to_string());
+            println!("{}: image {} ({}), {:.1} GB of data, {} GPU(s)", file, needs.image, image, needs.dataset_gb, needs.gpus);
+            
+            let cache = NodeCache::load_from(&eryzaa_jobs::offline::default_node_cache_path());
+            if cache.nodes.is_empty() {
+                println!("[!] No cached nodes to compare against; browse nodes in the client first");
+                return Ok(());
+            }
+            if let Some(node_id) = args.get(2) {
+                let node = cache
+                    .nodes
+                    .iter()
+                    .find(|node| &node.node_id == node_id)
+                    .ok_or_else(|| format!("Node '{}' is not in the node cache", node_id))?;
+                let findings = requirements::analyze(&needs, &node.capabilities);
+                if findings.is_empty() {
+                    println!("[+] {} fits the job", node_id);
+                }
+                for finding in findings {
+                    println!("[{}] {}", if finding.severity == requirements::Severity::Blocker { "-" } else { "!" }, finding.message);
+                }
+            }
+            
+            let fitting = requirements::suggest(&needs, &cache.nodes);
+            if fitting.is_empty() {
+                println!("[-] None of the {} cached nodes fits the job", cache.nodes.len());
+            } else {
+                println!("Nodes that fit (node list {}):", cache.age_label(chrono::Utc::now()));
+                for node in fitting.iter().take(5) {
+                    let price = node.price_per_hour.map(|p| format!("{:.2} AVAX/h", p)).unwrap_or_else(|| "-".to_string());
+                    println!("    {:<24} {} GPU(s) × {} GB  {} GB disk  {}", node.node_id, node.capabilities.gpu_count, node.capabilities.gpu_memory_gb, node.capabilities.disk_space_gb, price);
+                }
+            }
+            Ok(())
+        }
+        Some("receipt") => {
+            let job_id = args.get(1).ok_or("Usage: job receipt <id>")?;
+            
+            let registry = eryzaa_jobs::default_registry_path();
+            let manager = JobManager::load_from(&registry)?;
+            // Prefer the node's figures; fall back to what was last synced
+            let receipt = match manager.fetch_receipt(job_id) {
+                Ok(Some(receipt)) => {
+                    manager.save_to(&registry)?;
+                    receipt
+                }
+                Ok(None) | Err(_) => manager
+                    .get_job(job_id)
+                    .ok_or_else(|| format!("No job found with id '{}'", job_id))?
+                    .receipt(chrono::Utc::now()),
+            };
+            println!("{}", receipt);
+            Ok(())
+        }
+        _ => {
+            println!("Usage:");
+            println!("    job list");
+            println!("    job exec <id> [-- command...]");
+            println!("    job pause <id> [--release-gpu]");
+            println!("    job resume <id>");
+            println!("    job ssh-config");
+            println!("    job cancel <id>");
+            println!("    job queue [replay]");
+            println!("    job share <id> [--minutes N]");
+            println!("    job grant <id> <identity> <ssh|logs|full> <public-key-file>");
+            println!("    job revoke-grant <id> <identity>");
+            println!("    job restrict <id> [--from <ip|cidr>]... [--key <public-key-file|SHA256:...>]...");
+            println!("    job seal-input <id> <source> <target> [--minutes N] < credentials.json");
+            println!("    job seal-output <id> <workspace-path> <bucket-prefix> [--minutes N] < credentials.json");
+            println!("    job redeem <link>");
+            println!("    job revoke <handoff-id>");
+            println!("    job handoffs");
+            println!("    job receipt <id>");
+            println!("    job analyze <spec.yaml> [node-id]");
+            Ok(())
+        }
+    }
+}
+
+/// How long `hosts sync` listens for node advertisements
+const HOSTS_SEARCH_TIME: Duration = Duration::from_secs(5);
+
+// Handle `hosts` subcommands
+fn run_hosts_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    match args.first().map(|s| s.as_str()) {
+        Some("sync") => {
+            // Publish <node-id>.eryzaa names for nodes heard on the network, then
+            // <job-id>.jobs.eryzaa and <node-id>.eryzaa names for known jobs
+            let network_id = std::env::var("ZEROTIER_NETWORK_ID").unwrap_or_default();
+            let (lan_ip, overlay_ip) = eryzaa_discovery::overlay::local_addresses(&network_id);
+            let hostname = std::fs::read_to_string("/etc/hostname").unwrap_or_default();
+            let advertisement = eryzaa_discovery::create_client_advertisement(
+                format!("client-{}", hostname.trim()),
+                lan_ip,
+                overlay_ip,
+                network_id,
+            );
+            let runtime = tokio::runtime::Runtime::new()?;
+            let _runtime = runtime.enter();
+            let mut discovery = eryzaa_discovery::DiscoveryService::new(advertisement)?;
+            discovery.keep_node_keys(&node_keys_path());
+            discovery.start()?;
+            println!("[*] Listening for nodes for {} seconds...", HOSTS_SEARCH_TIME.as_secs());
+            thread::sleep(HOSTS_SEARCH_TIME);
+            runtime.block_on(discovery.shutdown());
+            
+            let manager = JobManager::load_from(&eryzaa_jobs::default_registry_path())?;
+            let entries = eryzaa_discovery::names::merge_entries(
+                discovery.host_entries(),
+                manager.list_jobs().iter().flat_map(|job| job.host_entries()),
+            );
+            
+            let hosts_path = eryzaa_discovery::names::system_hosts_path();
+            eryzaa_discovery::names::sync_hosts_file(&hosts_path, &entries)?;
+            
+            println!("[+] Synced {} names into {}", entries.len(), hosts_path.display());
+            for entry in entries {
+                println!("    {}  {}", entry.name, entry.ip);
+            }
+            Ok(())
+        }
+        _ => {
+            println!("Usage:");
+            println!("    hosts sync");
+            Ok(())
+        }
+    }
+}
+
+// Handle `profile` subcommands
+fn run_profile_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut store = ProfileStore::load()?;
+    
+    match args.first().map(|s| s.as_str()) {
+        Some("list") | None => {
+            let active = store.active().name.clone();
+            for profile in store.list() {
+                let marker = if profile.name == active { "*" } else { " " };
+                println!("{} {}  {}  {:.2} AVAX  {} bookmarks  {}", marker, profile.name,
+                         profile.organization.as_deref().unwrap_or("personal"),
+                         profile.balance, profile.bookmarks.len(), profile.data_dir().display());
+            }
+            Ok(())
+        }
+        Some("create") => {
+            // eryzaa profile create <name> [--org ORG] [--wallet ADDRESS]
+            let name = args.get(1).ok_or("Usage: profile create <name> [--org ORG] [--wallet ADDRESS]")?;
+            let flag = |flag: &str| args.iter().position(|a| a == flag).and_then(|idx| args.get(idx + 1)).cloned();
+            
+            let mut profile = Profile::new(name);
+            profile.organization = flag("--org");
+            profile.wallet_address = flag("--wallet");
+            store.create(profile)?;
+            store.save()?;
+            println!("[+] Created profile {}", name);
+            Ok(())
+        }
+        Some("use") => {
+            let name = args.get(1).ok_or("Usage: profile use <name>")?;
+            store.switch(name)?;
+            store.save()?;
+            
+            // Host blocks in ~/.ssh/config.d/eryzaa follow the active profile's jobs
+            let manager = JobManager::load_from(&eryzaa_jobs::default_registry_path())?;
+            SshConfigWriter::for_current_user().sync(&manager.list_jobs())?;
+            println!("[+] Switched to profile {}", name);
+            Ok(())
+        }
+        Some("remove") => {
+            let name = args.get(1).ok_or("Usage: profile remove <name>")?;
+            let removed = store.remove(name)?;
+            store.save()?;
+            println!("[+] Removed profile {} (data kept in {})", name, removed.data_dir().display());
+            Ok(())
+        }
+        Some("bookmark") => {
+            // eryzaa profile bookmark <node-id> <label> [address]
+            let node_id = args.get(1).ok_or("Usage: profile bookmark <node-id> <label> [address]")?;
+            let label = args.get(2).cloned().unwrap_or_else(|| node_id.clone());
+            store.active_mut().bookmark(NodeBookmark {
+                node_id: node_id.clone(),
+                label,
+                address: args.get(3).cloned(),
+            });
+            store.save()?;
+            println!("[+] Bookmarked node {}", node_id);
+            Ok(())
+        }
+        _ => {
+            println!("Usage:");
+            println!("    profile list");
+            println!("    profile create <name> [--org ORG] [--wallet ADDRESS]");
+            println!("    profile use <name>");
+            println!("    profile remove <name>");
+            println!("    profile bookmark <node-id> <label> [address]");
+            Ok(())
+        }
     }
+}
+
+// Directory holding the compose files, `--dir DIR` or the current directory
+fn compose_dir(args: &[String]) -> Result<std::path::PathBuf, String> {
+    match args.iter().position(|a| a == "--dir") {
+        Some(idx) => args.get(idx + 1).map(std::path::PathBuf::from).ok_or_else(|| "--dir needs a value".to_string()),
+        None => Ok(std::path::PathBuf::from(".")),
+    }
+}
+
+// Handle `migrate`: convert the compose files behind manage.sh modes into executor configs
+fn run_migrate_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = compose_dir(args)?;
+    let modes: Vec<&str> = match args.iter().position(|a| a == "--mode") {
+        Some(idx) => vec![args.get(idx + 1).ok_or("--mode needs a value")?.as_str()],
+        None => compose::MANAGE_MODES.iter().map(|(mode, _)| *mode).collect(),
+    };
     
+    for mode in modes {
+        let conversion = match compose::convert_manage_mode(&dir, mode) {
+            Ok(conversion) => conversion,
+            Err(e) => {
+                println!("[-] {}: {}", mode, e);
+                continue;
+            }
+        };
+        let path = spec::default_config_path(mode);
+        conversion.config.save_to(&path)?;
+        println!("[+] {} → {} ({} containers)", mode, path.display(), conversion.config.specs.len());
+        for warning in &conversion.warnings {
+            println!("    [!] {}", warning);
+        }
+    }
     Ok(())
 }
+
+// Handle `compat`: manage.sh modes run by the native executor
+fn run_compat_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let registry = eryzaa_jobs::default_registry_path();
+    let manager = JobManager::load_from(&registry)?;
+    let dir = compose_dir(args)?;
+    
+    // Prefer configs saved by `migrate`, which users may have edited
+    let config_for = |mode: &str| -> Result<ExecutorConfig, String> {
+        let path = spec::default_config_path(mode);
+        if path.exists() {
+            ExecutorConfig::load_from(&path)
+        } else {
+            compose::convert_manage_mode(&dir, mode).map(|conversion| conversion.config)
+        }
+    };
+    
+    match args.first().map(|s| s.as_str()) {
+        Some(mode @ ("deploy" | "dev" | "fast")) => {
+            // Like manage.sh, only one mode runs at a time
+            for (other, _) in compose::MANAGE_MODES.iter().filter(|(m, _)| *m != mode) {
+                if let Ok(config) = ExecutorConfig::load_from(&spec::default_config_path(other)) {
+                    let _ = manager.stop_config(&config);
+                }
+            }
+            
+            let config = config_for(mode)?;
+            config.save_to(&spec::default_config_path(mode))?;
+            create_dir_all(dir.join("workspace/data"))?;
+            create_dir_all(dir.join("workspace/shared"))?;
+            manager.run_config(&config, eryzaa_jobs::stack::STACK_CLIENT)?;
+            manager.save_to(&registry)?;
+            println!("[+] {} started with the native executor", mode);
+        }
+        Some("stop") => {
+            for (mode, _) in compose::MANAGE_MODES.iter() {
+                if let Ok(config) = ExecutorConfig::load_from(&spec::default_config_path(mode)) {
+                    manager.stop_config(&config)?;
+                }
+            }
+            manager.save_to(&registry)?;
+            println!("[+] Stopped");
+        }
+        Some("status") => {
+            for job in manager.list_jobs().iter().filter(|job| job.client_id == eryzaa_jobs::stack::STACK_CLIENT) {
+                println!("{}  {:?}  {}", job.container_name, job.status, job.image);
+            }
+        }
+        _ => {
+            println!("Usage:");
+            println!("    migrate [--dir DIR] [--mode deploy|dev|fast]");
+            println!("    compat deploy|dev|fast|stop|status [--dir DIR]");
+        }
+    }
+    Ok(())
+}
+
+// Handle `pipeline` subcommands: DAGs of job specs run by the rental server
+fn run_pipeline_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let path = pipeline::default_store_path();
+    let mut store = pipeline::PipelineStore::load_from(&path);
+    
+    match args.first().map(|s| s.as_str()) {
+        Some("submit") => {
+            let file = args.get(1).ok_or("Usage: pipeline submit <file.yaml>")?;
+            let definition = pipeline::Pipeline::load_from(Path::new(file))?;
+            let order = definition.order()?;
+            let run = pipeline::PipelineRun::new(definition, "local");
+            println!("[+] Submitted pipeline {} as run {}", run.pipeline.name, run.run_id);
+            println!("    Stages: {}", order.join(" → "));
+            store.runs.push(run);
+            store.save_to(&path)?;
+        }
+        Some("list") | None => {
+            for run in &store.runs {
+                let done = run.states.values().filter(|state| state.is_finished()).count();
+                let status = if !run.is_finished() {
+                    "running"
+                } else if run.succeeded() {
+                    "succeeded"
+                } else {
+                    "failed"
+                };
+                println!("{}  {:<20} {}/{} stages  {}", run.run_id, run.pipeline.name, done, run.states.len(), status);
+            }
+        }
+        Some("status") => {
+            let run_id = args.get(1).ok_or("Usage: pipeline status <run-id>")?;
+            let run = store.get_mut(run_id).ok_or_else(|| format!("No pipeline run '{}'", run_id))?;
+            println!("Pipeline {} (run {})", run.pipeline.name, run.run_id);
+            for stage in run.pipeline.order()? {
+                println!("    {:<16} {:?}", stage, run.states[&stage]);
+            }
+        }
+        Some("cancel") => {
+            let run_id = args.get(1).ok_or("Usage: pipeline cancel <run-id>")?;
+            let registry = eryzaa_jobs::default_registry_path();
+            let manager = JobManager::load_from(&registry)?;
+            let run = store.get_mut(run_id).ok_or_else(|| format!("No pipeline run '{}'", run_id))?;
+            manager.cancel_pipeline(run);
+            store.save_to(&path)?;
+            manager.save_to(&registry)?;
+            println!("[+] Cancelled pipeline run {}", run_id);
+        }
+        _ => {
+            println!("Usage:");
+            println!("    pipeline submit <file.yaml>");
+            println!("    pipeline list");
+            println!("    pipeline status <run-id>");
+            println!("    pipeline cancel <run-id>");
+        }
+    }
+    Ok(())
+}
+
+// Handle `sweep` subcommands: hyperparameter sweeps over one spec, run as budgeted trials
+fn run_sweep_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let path = sweep::default_store_path();
+    let mut store = sweep::SweepStore::load_from(&path);
+    
+    match args.first().map(|s| s.as_str()) {
+        Some("submit") => {
+            let file = args.get(1).ok_or("Usage: sweep submit <file.yaml> [[user@]address[:price[:slots]]...]")?;
+            let targets = args[2..].iter().map(|target| sweep::SweepTarget::parse(target)).collect::<Result<Vec<_>, _>>()?;
+            let run = sweep::SweepRun::new(spec::load_spec(Path::new(file))?, "local", targets)?;
+            let budget = run.sweep().budget.map(|b| format!(", budget {:.2} AVAX", b)).unwrap_or_default();
+            println!("[+] Submitted sweep of {} as run {}: {} trials{}", run.spec.name, run.run_id, run.trials.len(), budget);
+            println!("    Targets: {}", run.targets.iter().map(|t| t.label()).collect::<Vec<_>>().join(", "));
+            store.runs.push(run);
+            store.save_to(&path)?;
+        }
+        Some("run") => {
+            // Drive a sweep from here, e.g. when its trials run on rented nodes
+            let run_id = args.get(1).ok_or("Usage: sweep run <run-id>")?;
+            let registry = eryzaa_jobs::default_registry_path();
+            let manager = JobManager::load_from(&registry)?;
+            loop {
+                let run = store.get_mut(run_id).ok_or_else(|| format!("No sweep run '{}'", run_id))?;
+                let before = run.trials.clone();
+                manager.advance_sweep(run, &mut |_| true)?;
+                for (trial, old) in run.trials.iter().zip(&before) {
+                    if trial.state != old.state {
+                        println!("[*] Trial {}: {:?}", trial.index, trial.state);
+                    }
+                }
+                let finished = run.is_finished();
+                store.save_to(&path)?;
+                manager.save_to(&registry)?;
+                if finished {
+                    break;
+                }
+                thread::sleep(Duration::from_secs(10));
+            }
+            print_best_trial(store.get_mut(run_id).ok_or("Sweep run disappeared")?);
+        }
+        Some("list") | None => {
+            let now = chrono::Utc::now();
+            for run in &store.runs {
+                let done = run.trials.iter().filter(|trial| trial.state.is_finished()).count();
+                let status = if run.is_finished() { "finished" } else { "running" };
+                println!("{}  {:<20} {}/{} trials  {:.2} AVAX  {}", run.run_id, run.spec.name, done, run.trials.len(), run.spent(now), status);
+            }
+        }
+        Some("status") => {
+            let run_id = args.get(1).ok_or("Usage: sweep status <run-id>")?;
+            let run = store.get_mut(run_id).ok_or_else(|| format!("No sweep run '{}'", run_id))?;
+            let metric = run.sweep().metric;
+            println!("Sweep of {} (run {}), spent {:.2} AVAX", run.spec.name, run.run_id, run.spent(chrono::Utc::now()));
+            for trial in &run.trials {
+                let value = trial.metrics.get(&metric).map(|v| format!("{} = {}", metric, v)).unwrap_or_default();
+                let target = trial.target.and_then(|t| run.targets.get(t)).map(|t| t.label()).unwrap_or_default();
+                println!("    #{:<3} {:<12} {:<24} {:?}  {:?}", trial.index, target, value, trial.state, trial.params);
+            }
+            print_best_trial(run);
+        }
+        Some("cancel") => {
+            let run_id = args.get(1).ok_or("Usage: sweep cancel <run-id>")?;
+            let registry = eryzaa_jobs::default_registry_path();
+            let manager = JobManager::load_from(&registry)?;
+            let run = store.get_mut(run_id).ok_or_else(|| format!("No sweep run '{}'", run_id))?;
+            manager.cancel_sweep(run);
+            store.save_to(&path)?;
+            manager.save_to(&registry)?;
+            println!("[+] Cancelled sweep run {}", run_id);
+        }
+        _ => {
+            println!("Usage:");
+            println!("    sweep submit <file.yaml> [[user@]address[:price[:slots]]...]");
+            println!("    sweep run <run-id>");
+            println!("    sweep list");
+            println!("    sweep status <run-id>");
+            println!("    sweep cancel <run-id>");
+        }
+    }
+    Ok(())
+}
+
+fn print_best_trial(run: &sweep::SweepRun) {
+    let metric = run.sweep().metric;
+    match run.best() {
+        Some(best) => println!("[+] Best trial #{}: {} = {} with {:?}", best.index, metric, best.metrics[&metric], best.params),
+        None => println!("[*] No successful trial reported {} yet", metric),
+    }
+}
+
+// Handle `cache` subcommands: this node's result cache for deterministic stages
+fn run_cache_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let result_cache = cache::ResultCache::open_default();
+    let mut policy = result_cache.policy.clone();
+    
+    match args.first().map(|s| s.as_str()) {
+        Some("list") | None => {
+            println!("Cache {} (fee {:.2} per cached answer)", if policy.enabled { "enabled" } else { "disabled" }, policy.cache_fee);
+            for entry in result_cache.entries() {
+                println!("{}  {:>10} bytes  from job {}  {}", &entry.hash[..12], entry.size_bytes, entry.job_id, entry.manifest.image_digest);
+            }
+            return Ok(());
+        }
+        Some("enable") => policy.enabled = true,
+        Some("disable") => policy.enabled = false,
+        Some("fee") => {
+            policy.cache_fee = args.get(1).ok_or("Usage: cache fee <amount>")?.parse()?;
+        }
+        Some("clear") => {
+            result_cache.clear()?;
+            println!("[+] Result cache cleared");
+            return Ok(());
+        }
+        _ => {
+            println!("Usage:");
+            println!("    cache list");
+            println!("    cache enable|disable");
+            println!("    cache fee <amount>");
+            println!("    cache clear");
+            return Ok(());
+        }
+    }
+    
+    policy.save_to(&cache::default_policy_path())?;
+    println!("[+] Cache {}, fee {:.2}", if policy.enabled { "enabled" } else { "disabled" }, policy.cache_fee);
+    Ok(())
+}
+
+// Handle `workspace` subcommands: snapshots of a job session's home carried between rentals
+fn run_workspace_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let store = WorkspaceStore::for_profile(&eryzaa_jobs::profiles::active_profile());
+    
+    match args.first().map(|s| s.as_str()) {
+        Some("save") => {
+            // eryzaa workspace save <job-id> [label]
+            let job_id = args.get(1).ok_or("Usage: workspace save <job-id> [label]")?;
+            let label = args.get(2).map(|s| s.as_str()).unwrap_or("workspace");
+            
+            let manager = JobManager::load_from(&eryzaa_jobs::default_registry_path())?;
+            println!("[*] Saving the workspace of job {}...", job_id);
+            let snapshot = manager.save_workspace(job_id, &store, label)?;
+            println!("[+] Saved snapshot {} ({} bytes) in {}", snapshot.id, snapshot.bytes, store.dir().display());
+        }
+        Some("restore") => {
+            let usage = "Usage: workspace restore <job-id> <snapshot-id>";
+            let job_id = args.get(1).ok_or(usage)?;
+            let snapshot_id = args.get(2).ok_or(usage)?;
+            
+            let manager = JobManager::load_from(&eryzaa_jobs::default_registry_path())?;
+            println!("[*] Restoring snapshot {} into job {}...", snapshot_id, job_id);
+            manager.restore_workspace(job_id, &store, snapshot_id)?;
+            println!("[+] Workspace restored");
+        }
+        Some("list") | None => {
+            for snapshot in store.list() {
+                println!("{}  {:>12} bytes  from job {}  {}", snapshot.id, snapshot.bytes, snapshot.job_id,
+                         snapshot.created_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"));
+            }
+        }
+        Some("remove") => {
+            let id = args.get(1).ok_or("Usage: workspace remove <snapshot-id>")?;
+            store.remove(id)?;
+            println!("[+] Snapshot {} removed", id);
+        }
+        _ => {
+            println!("Usage:");
+            println!("    workspace save <job-id> [label]");
+            println!("    workspace restore <job-id> <snapshot-id>");
+            println!("    workspace list");
+            println!("    workspace remove <snapshot-id>");
+        }
+    }
+    Ok(())
+}
+
+// Handle `coordinator` subcommands: network-wide marketplace statistics and abuse reports
+fn run_coordinator_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    match args.first().map(|s| s.as_str()) {
+        Some("serve") => {
+            // eryzaa coordinator serve [PORT]
+            let port: u16 = match args.get(1) {
+                Some(port) => port.parse()?,
+                None => market::DEFAULT_PORT,
+            };
+            let hostname = std::fs::read_to_string("/etc/hostname").unwrap_or_default();
+            let mut advertisement = eryzaa_discovery::create_client_advertisement(
+                format!("coordinator-{}", hostname.trim()),
+                "0.0.0.0".to_string(),
+                None,
+                std::env::var("ZEROTIER_NETWORK_ID").unwrap_or_default(),
+            );
+            advertisement.node_type = eryzaa_discovery::NodeType::Coordinator;
+            
+            // Discovery runs as tasks on a runtime that lives as long as the server
+            let runtime = tokio::runtime::Runtime::new()?;
+            let _runtime = runtime.enter();
+            let mut discovery = eryzaa_discovery::DiscoveryService::new(advertisement)?;
+            discovery.keep_node_keys(&node_keys_path());
+            discovery.start()?;
+            let quotas = Quotas::open(quota_path())?;
+            let listener = std::net::TcpListener::bind(("0.0.0.0", port))?;
+            println!("[+] Serving network status on http://0.0.0.0:{}/status and stats on /api/stats", port);
+            println!("[+] Accepting abuse reports on /api/reports, blocklist on /api/blocklist");
+            println!("[+] Registering nodes on /api/nodes, owner claims on /api/claims");
+            println!("[+] Sharing node status with observer tokens on /api/observe/<token> and /observe/<token>");
+            println!("[+] Watching jobs for phone alerts on /api/push/subscriptions");
+            println!("[+] Listing nodes beyond this network on /api/rendezvous");
+            println!("[+] Holding credential handoffs for their redeemers on /api/handoffs");
+            let limits = quotas.config().default;
+            println!("[*] Each caller may make {} requests/minute, bursts of {}", limits.per_minute, limits.burst);
+            let stores = CoordinatorStores {
+                reports: report_store(),
+                registrations: registration_store(),
+                observers: observer_store(),
+                pushes: push_store(),
+                rendezvous: RendezvousStore::default(),
+                handoffs: handoff_relay(),
+            };
+            // Nodes heard on this network, then those that only reach us through the rendezvous
+            let nodes = || {
+                let mut nodes = discovery.get_discovered_nodes();
+                for node in stores.rendezvous.nodes(chrono::Utc::now().timestamp() as u64) {
+                    nodes.entry(node.node_id.clone()).or_insert(node);
+                }
+                nodes
+            };
+            let node = |node_id: &str| nodes().remove(node_id);
+            std::thread::scope(|scope| {
+                scope.spawn(|| loop {
+                    std::thread::sleep(std::time::Duration::from_secs(60));
+                    let alerts = match stores.pushes.due(chrono::Utc::now().timestamp() as u64, &node) {
+                        Ok(alerts) => alerts,
+                        Err(e) => {
+                            println!("[-] {}", e);
+                            continue;
+                        }
+                    };
+                    for alert in alerts {
+                        if let Err(e) = push::publish(&alert) {
+                            println!("[-] {}", e);
+                        }
+                    }
+                });
+                market::serve(
+                    listener,
+                    || MarketStats::from_nodes(nodes().values(), chrono::Utc::now().timestamp() as u64),
+                    node,
+                    &stores,
+                    &quotas,
+                );
+            });
+        }
+        Some("stats") => {
+            let stats = coordinators(args.get(1))?.call(market::fetch_stats)?;
+            
+            println!("Nodes:        {} ({} available)", stats.total_nodes, stats.available_nodes);
+            println!("GPUs:         {}", stats.total_gpus);
+            if let Some(price) = stats.median_price_per_hour {
+                println!("Median price: {:.2} AVAX/hour", price);
+            }
+            if let Some(utilization) = stats.utilization {
+                println!("Utilization:  {:.0}%", utilization * 100.0);
+            }
+            for (model, gpus) in &stats.gpus_by_model {
+                let price = stats.median_price_by_model.get(model).map(|p| format!("{:.2} AVAX/hour", p)).unwrap_or_default();
+                println!("    {:<32} {:>4} GPUs  {}", model, gpus, price);
+            }
+        }
+        Some("reports") => {
+            let reports = report_store().reports();
+            if reports.is_empty() {
+                println!("No abuse reports");
+            }
+            for report in reports {
+                println!("{}  {:<24} {:<24} job {} from {}", report.report_id, report.client_id,
+                         report.category.label(), report.job_id, report.reporter_node_id);
+                if !report.description.is_empty() {
+                    println!("    {}", report.description);
+                }
+                for evidence in &report.evidence {
+                    println!("    [{}] {}", evidence.kind, evidence.summary);
+                }
+            }
+        }
+        Some("nodes") => {
+            let nodes = registration_store().nodes();
+            if nodes.is_empty() {
+                println!("No registered nodes");
+            }
+            for node in nodes {
+                let owner = match (&node.owner, &node.claim_code) {
+                    (Some(owner), _) => format!("owned by {}", owner),
+                    (None, Some(code)) => format!("waiting for claim code {}", code),
+                    (None, None) => "unclaimed".to_string(),
+                };
+                println!("{}  {:<24} {:<16} {}", node.registration.node_id, node.registration.hostname,
+                         node.registration.overlay_ip.as_deref().unwrap_or("-"), owner);
+            }
+        }
+        Some("observers") => {
+            let tokens = observer_store().tokens();
+            if tokens.is_empty() {
+                println!("No observer tokens");
+            }
+            for token in tokens {
+                let state = match token.revoked_at {
+                    Some(_) => "revoked",
+                    None => "active",
+                };
+                println!("{}  {:<24} {:<24} {:<8} owned by {}", token.token, token.node_id, token.label, state, token.owner);
+            }
+        }
+        Some("pushes") => {
+            let subscriptions = push_store().subscriptions();
+            if subscriptions.is_empty() {
+                println!("No jobs watched for phone alerts");
+            }
+            for subscription in subscriptions {
+                let alerts: Vec<&str> = subscription.alerts.iter().map(|kind| kind.label()).collect();
+                println!("{}  {:<24} {}/{}  {}", subscription.job_id, subscription.node_id, subscription.relay,
+                         subscription.topic, alerts.join(", "));
+            }
+        }
+        Some("flag") if args.len() >= 3 => {
+            // eryzaa coordinator flag <client_id> <reason...>
+            report_store().flag(&args[1], &args[2..].join(" "), chrono::Utc::now().timestamp() as u64)?;
+            println!("[+] Client {} is on the blocklist", args[1]);
+        }
+        Some("clear") if args.len() == 2 => {
+            match report_store().clear(&args[1])? {
+                true => println!("[+] Cleared reports and flags on {}", args[1]),
+                false => println!("Client {} had no reports or flags", args[1]),
+            }
+        }
+        Some("quota") => {
+            // eryzaa coordinator quota [URL]
+            // eryzaa coordinator quota set <address|default> <per-minute> <burst> [URL]
+            // eryzaa coordinator quota reset <address> [URL]
+            let (update, url) = match args.get(1).map(|s| s.as_str()) {
+                Some("set") if args.len() >= 5 => {
+                    let limits = QuotaLimits { per_minute: args[3].parse()?, burst: args[4].parse()? };
+                    let identity = (args[2] != "default").then(|| args[2].clone());
+                    (Some(QuotaUpdate { identity, limits: Some(limits) }), args.get(5))
+                }
+                Some("reset") if args.len() >= 3 => (Some(QuotaUpdate { identity: Some(args[2].clone()), limits: None }), args.get(3)),
+                Some("set" | "reset") => {
+                    return Err("Usage: coordinator quota set <address|default> <per-minute> <burst> [URL] | reset <address> [URL]".into());
+                }
+                _ => (None, args.get(1)),
+            };
+            let coordinators = coordinators(url)?;
+            let token = coordinator_token()?;
+            
+            match update {
+                // Mirrors keep their own quotas, so each gets the change
+                Some(update) => {
+                    for (url, answer) in coordinators.publish(|url| quota::update_quota(url, &token, &update)) {
+                        match answer {
+                            Ok(answer) => println!("[+] {}: {}", url, answer),
+                            Err(e) => println!("[-] {}", e),
+                        }
+                    }
+                }
+                None => {
+                    let config = coordinators.call(|url| quota::fetch_quotas(url, &token))?;
+                    println!("{:<24} {:>10}/min  burst {}", "default", config.default.per_minute, config.default.burst);
+                    for (address, limits) in &config.overrides {
+                        println!("{:<24} {:>10}/min  burst {}", address, limits.per_minute, limits.burst);
+                    }
+                }
+            }
+        }
+        _ => {
+            println!("Usage:");
+            println!("    coordinator serve [PORT]");
+            println!("    coordinator stats [URL]");
+            println!("    coordinator reports");
+            println!("    coordinator nodes");
+            println!("    coordinator observers");
+            println!("    coordinator pushes");
+            println!("    coordinator flag <client_id> <reason>");
+            println!("    coordinator clear <client_id>");
+            println!("    coordinator quota [URL]");
+            println!("    coordinator quota set <address|default> <per-minute> <burst> [URL]");
+            println!("    coordinator quota reset <address> [URL]");
+        }
+    }
+    Ok(())
+}
+
+/// Abuse reports received by the coordinator on this machine
+fn report_store() -> ReportStore {
+    ReportStore::new(eryzaa_jobs::default_registry_path().with_file_name("abuse-reports.json"))
+}
+
+/// Nodes registered with the coordinator on this machine
+fn registration_store() -> RegistrationStore {
+    RegistrationStore::new(eryzaa_jobs::default_registry_path().with_file_name("registered-nodes.json"))
+}
+
+/// Observer tokens issued by the coordinator on this machine
+fn observer_store() -> ObserverStore {
+    ObserverStore::new(eryzaa_jobs::default_registry_path().with_file_name("observer-tokens.json"))
+}
+
+/// Jobs the coordinator on this machine watches for phone alerts
+fn push_store() -> PushStore {
+    PushStore::new(eryzaa_jobs::default_registry_path().with_file_name("push-subscriptions.json"))
+}
+
+/// Credential handoffs this machine's coordinator holds for their redeemers
+fn handoff_relay() -> HandoffRelay {
+    HandoffRelay::new(eryzaa_jobs::default_registry_path().with_file_name("relayed-handoffs.json"))
+}
+
+/// Keys pinned for the nodes this machine has heard from
+fn node_keys_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("node_keys.json")
+}
+
+/// Request quotas of the coordinator on this machine
+fn quota_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("coordinator-quotas.json")
+}
+
+/// Admin token from the environment, or the one of the coordinator on this machine
+fn coordinator_token() -> Result<String, String> {
+    std::env::var(quota::ADMIN_TOKEN_ENV)
+        .ok()
+        .or_else(|| std::fs::read_to_string(quota_path().with_file_name(quota::TOKEN_FILE)).ok())
+        .map(|token| token.trim().to_string())
+        .ok_or(format!("No admin token (set {})", quota::ADMIN_TOKEN_ENV))
+}
+
+/// The coordinators listed in `arg`, or else in the environment, to fail over between
+fn coordinators(arg: Option<&String>) -> Result<CoordinatorSet, String> {
+    match arg {
+        Some(urls) => Some(CoordinatorSet::new(coordinators::parse_urls(urls))).filter(|set| !set.urls().is_empty()),
+        None => CoordinatorSet::from_env(),
+    }
+    .ok_or(format!("No coordinator URL given (set {})", market::COORDINATOR_URL_ENV))
+}
+
+// Handle `deposit` subcommands: refundable deposits for nodes that ask clients for one
+//
+// What is posted here travels with the next job; the node checks it and keeps the ledger.
+fn run_deposit_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let path = eryzaa_jobs::default_registry_path().with_file_name("client-access.json");
+    let mut access = ClientAccess::load_from(&path);
+    let client = ProfileStore::load()?.active().name.clone();
+    
+    match args.first().map(|s| s.as_str()) {
+        Some("requirement") => {
+            // eryzaa deposit requirement <node-id>
+            let node_id = args.get(1).ok_or("Usage: deposit requirement <node-id>")?;
+            let cache = NodeCache::load_from(&eryzaa_jobs::offline::default_node_cache_path());
+            let node = cache
+                .nodes
+                .iter()
+                .find(|node| &node.node_id == node_id)
+                .ok_or_else(|| format!("Node '{}' is not in the node cache", node_id))?;
+            let access = &node.access;
+            match (access.min_reputation, access.deposit) {
+                (None, None) => println!("{} is open to every client", node_id),
+                (Some(min), Some(deposit)) => println!("{} needs a reputation of {:.1}, or else a deposit of {:.2} AVAX per job", node_id, min, deposit),
+                (Some(min), None) => println!("{} needs a reputation of {:.1}", node_id, min),
+                (None, Some(deposit)) => println!("{} needs a deposit of {:.2} AVAX per job", node_id, deposit),
+            }
+        }
+        Some("post") => {
+            // eryzaa deposit post <amount> <tx-ref>
+            let usage = "Usage: deposit post <amount> <tx-ref>";
+            let amount: f32 = args.get(1).ok_or(usage)?.parse()?;
+            let tx_ref = args.get(2).ok_or(usage)?;
+            if amount <= 0.0 {
+                return Err("Deposit must be more than 0 AVAX".into());
+            }
+            access.deposit = Some(DepositClaim { tx_ref: tx_ref.clone(), amount });
+            access.save_to(&path)?;
+            println!("[+] Deposit of {:.2} AVAX ({}) goes with {}'s next job; the node checks the transfer", amount, tx_ref, client);
+        }
+        Some("withdraw") => {
+            // A deposit the node has recorded is refunded by its owner when the job ends
+            let claim = access.deposit.take().ok_or("No deposit waiting to go with a job")?;
+            access.save_to(&path)?;
+            println!("[+] Deposit {} ({:.2} AVAX) no longer goes with the next job", claim.tx_ref, claim.amount);
+        }
+        Some("reputation") => {
+            // eryzaa deposit reputation <attestation.json>
+            let file = args.get(1).ok_or("Usage: deposit reputation <attestation.json>")?;
+            let attestation: ReputationAttestation = serde_json::from_str(&std::fs::read_to_string(file)?)?;
+            if attestation.client_id != client {
+                return Err(format!("Attestation is for {}, not {}", attestation.client_id, client).into());
+            }
+            println!("[+] Reputation {:.1} from issuer {} goes with {}'s jobs", attestation.score, attestation.issuer, client);
+            access.reputation = Some(attestation);
+            access.save_to(&path)?;
+        }
+        Some("list") | None => {
+            match &access.deposit {
+                Some(claim) => println!("deposit     {:.2} AVAX  {}  (with the next job)", claim.amount, claim.tx_ref),
+                None => println!("deposit     -"),
+            }
+            match &access.reputation {
+                Some(attestation) => println!("reputation  {:.1}  issuer {}", attestation.score, attestation.issuer),
+                None => println!("reputation  -"),
+            }
+        }
+        _ => {
+            println!("Usage:");
+            println!("    deposit requirement <node-id>");
+            println!("    deposit post <amount> <tx-ref>");
+            println!("    deposit withdraw");
+            println!("    deposit reputation <attestation.json>");
+            println!("    deposit list");
+        }
+    }
+    Ok(())
+}
+
+// Handle `abuse report|blocklist|subscribe`: report tenants and use the shared blocklist
+fn run_abuse_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    match args.first().map(|s| s.as_str()) {
+        Some("report") if args.len() >= 3 => {
+            // eryzaa abuse report <job_id> <category> [description...]
+            let category = abuse::AbuseCategory::parse(&args[2]).ok_or_else(|| {
+                let labels: Vec<&str> = abuse::AbuseCategory::ALL.iter().map(|c| c.label()).collect();
+                format!("Unknown category '{}', expected one of: {}", args[2], labels.join(", "))
+            })?;
+            let manager = JobManager::load_from(&eryzaa_jobs::default_registry_path())?;
+            let ledger = transfers::TransferLedger::load_from(&transfers::default_ledger_path());
+            let report = manager.abuse_report(&args[1], category, &args[3..].join(" "), &ledger)?;
+            for evidence in &report.evidence {
+                println!("    [{}] {}", evidence.kind, evidence.summary);
+            }
+            // Every mirror keeps its own reports, so each hears of this one
+            let outcomes = coordinators(None)?.publish(|url| eryzaa_discovery::abuse::submit_report(url, &report));
+            for (url, answer) in &outcomes {
+                match answer {
+                    Ok(answer) => println!("[+] {}: {}", url, answer),
+                    Err(e) => println!("[-] {}", e),
+                }
+            }
+            if outcomes.iter().all(|(_, answer)| answer.is_err()) {
+                return Err("No coordinator took the report".into());
+            }
+        }
+        Some("blocklist") => {
+            let blocklist = coordinators(args.get(1))?.call(eryzaa_discovery::abuse::fetch_blocklist)?;
+            if blocklist.entries.is_empty() {
+                println!("The blocklist is empty");
+            }
+            for entry in &blocklist.entries {
+                let categories: Vec<&str> = entry.categories.iter().map(|c| c.label()).collect();
+                println!("{:<24} {:<40} {}", entry.client_id, categories.join(","), entry.reason);
+            }
+        }
+        Some("subscribe") if args.len() == 2 => {
+            let path = abuse::default_feed_path();
+            let mut feed = abuse::BlocklistFeed::load_from(&path);
+            feed.subscribed = match args[1].as_str() {
+                "on" => true,
+                "off" => false,
+                other => return Err(format!("Expected on or off, got '{}'", other).into()),
+            };
+            if feed.subscribed {
+                coordinators(None)?.call(|url| feed.refresh(url))?;
+                println!("[+] Subscribed; {} clients are blocked", feed.blocklist.entries.len());
+            } else {
+                println!("[+] Unsubscribed from the blocklist");
+            }
+            feed.save_to(&path)?;
+        }
+        _ => {
+            println!("Usage:");
+            println!("    abuse report <job_id> <cryptojacking|attacking-third-parties|other> [description]");
+            println!("    abuse blocklist [URL]");
+            println!("    abuse subscribe <on|off>");
+        }
+    }
+    Ok(())
+}
+
+// Handle `network diagnose`: ZeroTier health checks with remediation hints
+// Handle `fleet` subcommands: maintenance across rental nodes reachable over SSH
+fn run_fleet_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    const FLAGS: [&str; 5] = ["--update", "--rollback", "--max-unavailable", "--drain-timeout", "--health-timeout"];
+    let flag = |flag: &str| args.iter().position(|a| a == flag).and_then(|idx| args.get(idx + 1)).cloned();
+    let nodes = || -> Result<Vec<rollout::FleetNode>, String> {
+        args.iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(i, arg)| !FLAGS.contains(&arg.as_str()) && !FLAGS.contains(&args[i - 1].as_str()))
+            .map(|(_, arg)| rollout::FleetNode::parse(arg))
+            .collect()
+    };
+    
+    match args.first().map(|s| s.as_str()) {
+        Some("rollout") => {
+            // eryzaa fleet rollout --update CMD [--rollback CMD] [--max-unavailable N] [--drain-timeout MIN] [--health-timeout SEC] <[user@]node>...
+            let mut plan = rollout::RolloutPlan {
+                update_command: flag("--update").ok_or("Usage: fleet rollout --update CMD [--rollback CMD] [options] <[user@]node>...")?,
+                rollback_command: flag("--rollback"),
+                ..Default::default()
+            };
+            if let Some(max) = flag("--max-unavailable") {
+                plan.max_unavailable = max.parse()?;
+            }
+            if let Some(minutes) = flag("--drain-timeout") {
+                plan.drain_timeout_secs = minutes.parse::<u64>()? * 60;
+            }
+            if let Some(secs) = flag("--health-timeout") {
+                plan.health_timeout_secs = secs.parse()?;
+            }
+            let nodes = nodes()?;
+            if nodes.is_empty() {
+                return Err("No nodes to update".into());
+            }
+            if plan.rollback_command.is_none() {
+                println!("[!] No --rollback command; a node that fails its health check is left drained");
+            }
+            
+            println!("[*] Rolling {} nodes, {} at a time", nodes.len(), plan.max_unavailable);
+            let mut run = rollout::Rollout::new(plan, nodes);
+            loop {
+                let before: Vec<rollout::Phase> = run.nodes.iter().map(|n| n.phase.clone()).collect();
+                run.advance(chrono::Utc::now().timestamp() as u64, &mut rollout::ssh_control);
+                for (node, old) in run.nodes.iter().zip(&before) {
+                    if &node.phase != old {
+                        println!("[*] {}: {:?}", node.node.address, node.phase);
+                    }
+                }
+                if run.is_finished() {
+                    break;
+                }
+                thread::sleep(Duration::from_secs(10));
+            }
+            
+            let done = run.nodes.iter().filter(|n| n.phase == rollout::Phase::Done).count();
+            match &run.halted {
+                Some(reason) => return Err(format!("Rollout stopped after {} of {} nodes: {}", done, run.nodes.len(), reason).into()),
+                None => println!("[+] Updated all {} nodes", done),
+            }
+        }
+        Some("drain") if args.len() >= 3 => {
+            // eryzaa fleet drain on|off <[user@]node>...
+            let on = match args[1].as_str() {
+                "on" => true,
+                "off" => false,
+                other => return Err(format!("Expected on or off, got '{}'", other).into()),
+            };
+            for arg in &args[2..] {
+                let node = rollout::FleetNode::parse(arg)?;
+                match rollout::ssh_control(&node, &rollout::NodeCommand::Drain(on)) {
+                    Ok(output) => println!("{}: {}", node.address, output.trim()),
+                    Err(e) => println!("[-] {}: {}", node.address, e),
+                }
+            }
+        }
+        Some("health") => {
+            // eryzaa fleet health <[user@]node>...
+            for node in nodes()? {
+                match rollout::health(&node, &mut rollout::ssh_control) {
+                    Ok(health) => {
+                        let services = if health.is_healthy() { "healthy".to_string() } else { format!("down: {}", health.unhealthy_services.join(", ")) };
+                        println!("{:<24} {} running jobs  {}  {}", node.address, health.running_jobs, if health.draining { "draining" } else { "accepting" }, services);
+                    }
+                    Err(e) => println!("{:<24} unreachable: {}", node.address, e),
+                }
+            }
+        }
+        _ => {
+            println!("Usage:");
+            println!("    fleet rollout --update CMD [--rollback CMD] [--max-unavailable N] [--drain-timeout MIN] [--health-timeout SEC] <[user@]node>...");
+            println!("    fleet drain on|off <[user@]node>...");
+            println!("    fleet health <[user@]node>...");
+        }
+    }
+    Ok(())
+}
+
+fn run_network_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    match args.first().map(|s| s.as_str()) {
+        Some("diagnose") => {
+            let network_id = args.get(1).cloned().or_else(|| std::env::var("ZEROTIER_NETWORK_ID").ok());
+            let checks = diagnostics::diagnose(network_id.as_deref());
+            for check in &checks {
+                println!("{}", check);
+            }
+            
+            let problems = checks.iter().filter(|c| c.severity == diagnostics::Severity::Problem).count();
+            if problems > 0 {
+                return Err(format!("{} network problems found", problems).into());
+            }
+            println!("[+] Clients should be able to reach this node");
+        }
+        _ => {
+            println!("Usage:");
+            println!("    network diagnose [NETWORK_ID]");
+        }
+    }
+    Ok(())
+}
+
+// Handle `demo` subcommands: a coordinator and two mock rental nodes on this machine, for trying the GUIs
+fn run_demo_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    match args.first().map(|s| s.as_str()) {
+        Some("up") => {
+            // eryzaa demo up [PORT]
+            let port: u16 = match args.get(1) {
+                Some(port) => port.parse()?,
+                None => market::DEFAULT_PORT,
+            };
+            let url = format!("http://127.0.0.1:{}", port);
+            let profile = demo::ensure_profile(&eryzaa_jobs::profiles::default_store_path())?;
+            demo::seed_registry(&profile, chrono::Utc::now())?;
+            println!("[+] Seeded jobs and {:.0} AVAX into profile '{}'", profile.balance, profile.name);
+            
+            // Every part runs under the demo profile, so none of it touches real data
+            let exe = std::env::current_exe()?;
+            let spawn = |args: &[String]| {
+                Command::new(&exe)
+                    .arg("demo")
+                    .args(args)
+                    .env(eryzaa_jobs::profiles::PROFILE_ENV, demo::PROFILE)
+                    .stdin(std::process::Stdio::piped())
+                    .spawn()
+            };
+            let mut children = vec![spawn(&["coordinator".to_string(), port.to_string()])?];
+            for index in 0..demo::node_count() {
+                children.push(spawn(&["node".to_string(), index.to_string()])?);
+            }
+            
+            let coordinator = CoordinatorSet::new(vec![url.clone()]);
+            for _ in 0..20 {
+                coordinator.check_health();
+                if coordinator.health()[0].1.failures == 0 {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(250));
+            }
+            for registration in (0..demo::node_count()).filter_map(demo::mock_registration) {
+                match registration::register_node(&url, &registration) {
+                    Ok(receipt) => println!("[+] {} registered, claim code {}", registration.node_id, receipt.claim_code),
+                    Err(e) => println!("[-] {}", e),
+                }
+            }
+            
+            println!("[+] Demo coordinator on {}/status", url);
+            println!("[*] Try the GUIs against it:");
+            println!("    {}={} {}={} eryzaa-client", eryzaa_jobs::profiles::PROFILE_ENV, demo::PROFILE, market::COORDINATOR_URL_ENV, url);
+            println!("    {}={} {}={} eryzaa-rental", eryzaa_jobs::profiles::PROFILE_ENV, demo::PROFILE, market::COORDINATOR_URL_ENV, url);
+            println!("[*] Ctrl+C stops the demo");
+            
+            // Ctrl+C reaches the children too; if one of them dies, take the rest down with it
+            loop {
+                thread::sleep(Duration::from_secs(1));
+                if children.iter_mut().any(|child| child.try_wait().ok().flatten().is_some()) {
+                    for child in &mut children {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                    }
+                    return Err("A demo process exited".into());
+                }
+            }
+        }
+        Some("coordinator") => {
+            // Serves the mock nodes' stats rather than what discovery finds, so it can share this
+            // machine's discovery port with the GUIs
+            let port: u16 = args.get(1).ok_or("Usage: demo coordinator <PORT>")?.parse()?;
+            exit_with_stdin();
+            let quotas = Quotas::open(quota_path())?;
+            let listener = std::net::TcpListener::bind(("127.0.0.1", port))?;
+            let stores = CoordinatorStores {
+                reports: report_store(),
+                registrations: registration_store(),
+                observers: observer_store(),
+                pushes: push_store(),
+                rendezvous: RendezvousStore::default(),
+                handoffs: handoff_relay(),
+            };
+            let nodes = || {
+                let now = chrono::Utc::now().timestamp() as u64;
+                (0..demo::node_count()).filter_map(move |index| demo::mock_node(index, now))
+            };
+            market::serve(
+                listener,
+                || MarketStats::from_nodes(nodes().collect::<Vec<_>>().iter(), chrono::Utc::now().timestamp() as u64),
+                |node_id: &str| nodes().find(|node| node.node_id == node_id),
+                &stores,
+                &quotas,
+            );
+        }
+        Some("node") => {
+            let index: usize = args.get(1).ok_or("Usage: demo node <INDEX>")?.parse()?;
+            exit_with_stdin();
+            let now = || chrono::Utc::now().timestamp() as u64;
+            let node = demo::mock_node(index, now()).ok_or(format!("The demo has {} nodes", demo::node_count()))?;
+            // Kept across runs, as listeners pin the key a node id first signed with
+            let key_path = eryzaa_jobs::default_registry_path().with_file_name(format!("demo-node-{}.key", index));
+            let identity = eryzaa_discovery::NodeIdentity::load_or_create(&key_path)?;
+            println!("[+] Mock node {} advertising {} at {:.2} AVAX/h", node.node_id, node.capabilities.hardware.summary(), node.price_per_hour.unwrap_or_default());
+            while let Some(node) = demo::mock_node(index, now()) {
+                if let Err(e) = eryzaa_discovery::announce_locally(&identity, &node) {
+                    println!("[-] {}", e);
+                }
+                thread::sleep(Duration::from_secs(10));
+            }
+        }
+        _ => {
+            println!("Usage:");
+            println!("    demo up [PORT]");
+        }
+    }
+    Ok(())
+}
+
+// Handle `state` subcommands: the schema version of saved state, and migrating it to this build's
+fn run_state_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    match args.first().map(|s| s.as_str()) {
+        Some("migrate") => {
+            let dry_run = args.iter().any(|a| a == "--dry-run");
+            for dir in schema::data_dirs() {
+                let report = schema::migrate(&dir, dry_run)?;
+                if report.is_current() {
+                    println!("[+] {}: schema version {}, up to date", dir.display(), report.to);
+                    continue;
+                }
+                println!("[{}] {}: schema version {} → {}", if dry_run { "*" } else { "+" }, dir.display(), report.from, report.to);
+                for step in &report.steps {
+                    println!("    - {}", step);
+                }
+                for file in &report.changed_files {
+                    println!("    {} {}", if dry_run { "would rewrite" } else { "rewrote" }, file);
+                }
+            }
+            if dry_run {
+                println!("[*] Dry run, nothing was changed");
+            }
+        }
+        Some("version") => {
+            for dir in schema::data_dirs() {
+                println!("{}: schema version {}", dir.display(), schema::version_of(&dir)?);
+            }
+            println!("This build: schema version {}", schema::CURRENT_VERSION);
+        }
+        _ => {
+            println!("Usage:");
+            println!("    state version");
+            println!("    state migrate [--dry-run]");
+        }
+    }
+    Ok(())
+}
+
+/// Exit once stdin closes, so demo processes go away with `demo up` however it ends
+fn exit_with_stdin() {
+    thread::spawn(|| {
+        let _ = io::stdin().read_to_end(&mut vec![]);
+        std::process::exit(0);
+    });
+}
+
+// Follow control-plane events shared over NATS
+#[cfg(feature = "nats")]
+fn run_events_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let prefix = args.first().map(|s| s.as_str()).unwrap_or("");
+    if std::env::var(eryzaa_bus::NATS_URL_ENV).is_err() {
+        return Err(format!("Set {} to the coordinator's NATS server", eryzaa_bus::NATS_URL_ENV).into());
+    }
+    
+    let bus = eryzaa_bus::Bus::from_env("client")?;
+    let mut events = bus.subscribe(prefix);
+    println!("[*] Following {} events (Ctrl+C to stop)", if prefix.is_empty() { "all" } else { prefix });
+    while let Some(envelope) = events.blocking_next() {
+        let at = chrono::DateTime::from_timestamp_millis(envelope.timestamp_ms as i64)
+            .map(|t| t.with_timezone(&chrono::Local).format("%H:%M:%S").to_string())
+            .unwrap_or_default();
+        println!("{}  {:<26} {:<16} {:?}", at, envelope.event.topic(), envelope.source, envelope.event);
+    }
+    Ok(())
+}
+
+// Handle `chaos` subcommands (test builds only)
+#[cfg(feature = "chaos")]
+fn run_chaos_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    use eryzaa_jobs::chaos::{self, Fault};
+    
+    let number = |i: usize| -> Result<u64, Box<dyn std::error::Error>> {
+        Ok(args.get(i).ok_or("Missing value")?.parse()?)
+    };
+    
+    match args.first().map(|s| s.as_str()) {
+        Some("list") | None => {
+            for fault in chaos::active_faults() {
+                println!("{}", fault);
+            }
+            Ok(())
+        }
+        Some("drop-discovery") => {
+            chaos::inject(Fault::DropDiscoveryPackets { every: number(1)? as u32 })?;
+            Ok(())
+        }
+        Some("delay-control") => {
+            chaos::inject(Fault::DelayControl { millis: number(1)? })?;
+            Ok(())
+        }
+        Some("gpu-ecc") => {
+            chaos::inject(Fault::GpuEccErrors { gpu: number(1)? as u32, count: number(2).unwrap_or(1) })?;
+            Ok(())
+        }
+        Some("kill") => {
+            let job_id = args.get(1).ok_or("Usage: chaos kill <job-id>")?;
+            JobManager::load_from(&eryzaa_jobs::default_registry_path())?.chaos_kill(job_id)?;
+            println!("[+] Killed container of job {}", job_id);
+            Ok(())
+        }
+        Some("clear") => {
+            chaos::clear()?;
+            Ok(())
+        }
+        _ => {
+            println!("Usage:");
+            println!("    chaos list");
+            println!("    chaos drop-discovery <every-nth>");
+            println!("    chaos delay-control <millis>");
+            println!("    chaos gpu-ecc <gpu> [count]");
+            println!("    chaos kill <job-id>");
+            println!("    chaos clear");
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Abuse reports at the coordinator
+//! Reports from renters are kept with their evidence. A client reported by `FLAG_THRESHOLD`
+//! distinct nodes is flagged network-wide, so one grudge cannot block anyone; the operator can
+//! also flag or clear a client by hand.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+pub use eryzaa_protocol::{AbuseCategory, AbuseReport, Blocklist, BlocklistEntry, Evidence};
+
+pub const REPORTS_PATH: &str = "/api/reports";
+pub const BLOCKLIST_PATH: &str = "/api/blocklist";
+
+/// Distinct reporting nodes that flag a client
+pub const FLAG_THRESHOLD: usize = 2;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Ledger {
+    reports: Vec<AbuseReport>,
+    #[serde(default)]
+    flags: Vec<BlocklistEntry>, // Set by the operator
+}
+
+/// Reports and manual flags, kept in one JSON file
+#[derive(Debug, Clone)]
+pub struct ReportStore {
+    path: PathBuf,
+}
+
+impl ReportStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Keep a report; true when it put the client on the blocklist
+    pub fn submit(&self, report: AbuseReport) -> Result<bool, String> {
+        if report.client_id.is_empty() || report.reporter_node_id.is_empty() {
+            return Err("Report needs a client and a reporting node".to_string());
+        }
+        if report.evidence.is_empty() {
+            return Err("Report has no evidence".to_string());
+        }
+
+        let mut ledger = self.load();
+        if ledger.reports.iter().any(|r| r.report_id == report.report_id) {
+            return Err(format!("Report {} was already submitted", report.report_id));
+        }
+        let client_id = report.client_id.clone();
+        let was_blocked = blocklist(&ledger, 0).entry(&client_id).is_some();
+        ledger.reports.push(report);
+        self.save(&ledger)?;
+        Ok(!was_blocked && blocklist(&ledger, 0).entry(&client_id).is_some())
+    }
+
+    pub fn reports(&self) -> Vec<AbuseReport> {
+        self.load().reports
+    }
+
+    /// Flag a client by hand, regardless of how many nodes reported it
+    pub fn flag(&self, client_id: &str, reason: &str, at: u64) -> Result<(), String> {
+        let mut ledger = self.load();
+        ledger.flags.retain(|entry| entry.client_id != client_id);
+        ledger.flags.push(BlocklistEntry {
+            client_id: client_id.to_string(),
+            categories: vec![],
+            reporters: 0,
+            flagged_at: at,
+            reason: reason.to_string(),
+        });
+        self.save(&ledger)
+    }
+
+    /// Take a client off the blocklist, dropping its reports
+    pub fn clear(&self, client_id: &str) -> Result<bool, String> {
+        let mut ledger = self.load();
+        let before = ledger.reports.len() + ledger.flags.len();
+        ledger.reports.retain(|report| report.client_id != client_id);
+        ledger.flags.retain(|entry| entry.client_id != client_id);
+        let cleared = ledger.reports.len() + ledger.flags.len() < before;
+        self.save(&ledger)?;
+        Ok(cleared)
+    }
+
+    pub fn blocklist(&self, now: u64) -> Blocklist {
+        blocklist(&self.load(), now)
+    }
+
+    fn load(&self) -> Ledger {
+        std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, ledger: &Ledger) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(ledger)
+            .map_err(|e| format!("Failed to serialize abuse reports: {}", e))?;
+        std::fs::write(&self.path, content)
+            .map_err(|e| format!("Failed to write abuse reports {}: {}", self.path.display(), e))
+    }
+}
+
+fn blocklist(ledger: &Ledger, now: u64) -> Blocklist {
+    let mut by_client: BTreeMap<&str, Vec<&AbuseReport>> = BTreeMap::new();
+    for report in &ledger.reports {
+        by_client.entry(&report.client_id).or_default().push(report);
+    }
+
+    let mut entries = ledger.flags.clone();
+    for (client_id, reports) in by_client {
+        let reporters: BTreeSet<&str> = reports.iter().map(|r| r.reporter_node_id.as_str()).collect();
+        let categories: BTreeSet<AbuseCategory> = reports.iter().map(|r| r.category).collect();
+        if let Some(flag) = entries.iter_mut().find(|entry| entry.client_id == client_id) {
+            flag.categories = categories.into_iter().collect();
+            flag.reporters = reporters.len() as u32;
+        } else if reporters.len() >= FLAG_THRESHOLD {
+            entries.push(BlocklistEntry {
+                client_id: client_id.to_string(),
+                categories: categories.into_iter().collect(),
+                reporters: reporters.len() as u32,
+                flagged_at: reports.iter().map(|r| r.reported_at).max().unwrap_or_default(),
+                reason: format!("Reported by {} nodes", reporters.len()),
+            });
+        }
+    }
+    entries.sort_by(|a, b| a.client_id.cmp(&b.client_id));
+    Blocklist { generated_at: now, entries }
+}
+
+/// Handle a posted report, answering with what happened to the client
+pub(crate) fn accept(body: &str, reports: &ReportStore) -> Result<String, String> {
+    let report: AbuseReport = serde_json::from_str(body).map_err(|e| format!("Invalid report: {}", e))?;
+    let client_id = report.client_id.clone();
+    Ok(match reports.submit(report)? {
+        true => format!("Client {} is now on the blocklist", client_id),
+        false => format!("Report on {} recorded", client_id),
+    })
+}
+
+/// Send a report to the coordinator at `base_url`, returning its answer
+pub fn submit_report(base_url: &str, report: &AbuseReport) -> Result<String, String> {
+    let body = serde_json::to_string(report).map_err(|e| format!("Failed to serialize report: {}", e))?;
+    crate::market::request(base_url, "POST", REPORTS_PATH, Some(&body), None).map(|answer| answer.trim().to_string())
+}
+
+pub fn fetch_blocklist(base_url: &str) -> Result<Blocklist, String> {
+    let body = crate::market::request(base_url, "GET", BLOCKLIST_PATH, None, None)?;
+    serde_json::from_str(&body).map_err(|e| format!("Failed to parse blocklist: {}", e))
+}
+
+/// Node id a renter reports under; the machine's hostname
+pub fn reporter_id() -> String {
+    sysinfo::System::host_name().unwrap_or_else(|| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_flag_client() {
+        let store = ReportStore::new(std::env::temp_dir().join(format!("eryzaa-abuse-{}.json", std::process::id())));
+        let report = |id: &str, reporter: &str, category| AbuseReport {
+            report_id: id.to_string(),
+            reporter_node_id: reporter.to_string(),
+            client_id: "client-7".to_string(),
+            job_id: format!("job-{}", id),
+            category,
+            description: String::new(),
+            reported_at: 1_760_000_000,
+            evidence: vec![Evidence { kind: "receipt".to_string(), summary: String::new(), data: serde_json::Value::Null }],
+        };
+
+        assert!(store.submit(AbuseReport { evidence: vec![], ..report("0", "node-a", AbuseCategory::Other) }).is_err());
+        assert_eq!(store.submit(report("1", "node-a", AbuseCategory::Cryptojacking)), Ok(false));
+        assert_eq!(store.submit(report("2", "node-a", AbuseCategory::Cryptojacking)), Ok(false));
+        assert!(store.submit(report("2", "node-b", AbuseCategory::Cryptojacking)).is_err());
+        assert!(store.blocklist(0).entries.is_empty());
+
+        assert_eq!(store.submit(report("3", "node-b", AbuseCategory::AttackingThirdParties)), Ok(true));
+        let blocklist = store.blocklist(1_760_000_100);
+        let entry = blocklist.entry("client-7").unwrap();
+        assert_eq!(entry.reporters, 2);
+        assert_eq!(entry.categories, vec![AbuseCategory::Cryptojacking, AbuseCategory::AttackingThirdParties]);
+
+        store.flag("client-9", "Stolen payment method", 1_760_000_050).unwrap();
+        assert_eq!(store.blocklist(0).entries.len(), 2);
+        assert_eq!(store.clear("client-7"), Ok(true));
+        assert_eq!(store.blocklist(0).entries.iter().map(|e| e.client_id.as_str()).collect::<Vec<_>>(), vec!["client-9"]);
+        let _ = std::fs::remove_file(store.path());
+    }
+}
@@ -0,0 +1,17 @@
+//! Discovery backends
+//! Multicast and the overlay broadcasts only reach nodes in the same broadcast domain. A
+//! backend carries signed advertisement packets further, e.g. through a rendezvous server on
+//! the coordinator, see `rendezvous`. The discovery service announces through every backend it
+//! was given on each beat and feeds what they return through the same checks as packets heard
+//! on the LAN, so a backend needs no more trust than the network does.
+
+pub trait DiscoveryBackend: Send {
+    /// Where the backend reaches, for logs and the GUIs
+    fn name(&self) -> String;
+
+    /// Publish our signed advertisement packet
+    fn announce(&self, packet: &[u8]) -> Result<(), String>;
+
+    /// Advertisement packets other nodes published
+    fn lookup(&self) -> Result<Vec<Vec<u8>>, String>;
+}
@@ -0,0 +1,128 @@
+//! Fault injection for integration tests, built with the `chaos` feature
+//! Active faults live in a small JSON file so a test harness can switch them on and off
+//! in running nodes and clients; the hooks in discovery, job control and GPU monitoring read it.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Environment variable overriding where the fault file lives
+pub const CHAOS_FILE_ENV: &str = "ERYZAA_CHAOS_FILE";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum Fault {
+    DropDiscoveryPackets { every: u32 }, // Drop every Nth packet; 1 drops them all
+    DelayControl { millis: u64 },        // Delay each control command sent to a node
+    GpuEccErrors { gpu: u32, count: u64 }, // Report uncorrected ECC errors on a GPU
+}
+
+impl std::fmt::Display for Fault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fault::DropDiscoveryPackets { every } => write!(f, "drop every {} discovery packet(s)", every),
+            Fault::DelayControl { millis } => write!(f, "delay control commands by {} ms", millis),
+            Fault::GpuEccErrors { gpu, count } => write!(f, "{} ECC errors on GPU {}", count, gpu),
+        }
+    }
+}
+
+static PACKETS_SEEN: AtomicU64 = AtomicU64::new(0);
+
+pub fn chaos_file() -> PathBuf {
+    std::env::var(CHAOS_FILE_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| std::env::temp_dir().join("eryzaa-chaos.json"))
+}
+
+/// Faults currently switched on
+pub fn active_faults() -> Vec<Fault> {
+    std::fs::read_to_string(chaos_file())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Switch a fault on, replacing any fault of the same kind (and GPU)
+pub fn inject(fault: Fault) -> Result<(), String> {
+    let mut faults = active_faults();
+    faults.retain(|existing| !same_kind(existing, &fault));
+    faults.push(fault);
+    write_faults(&faults)
+}
+
+/// Switch every fault off
+pub fn clear() -> Result<(), String> {
+    write_faults(&[])
+}
+
+/// Whether the discovery packet being handled should be dropped
+pub fn should_drop_discovery_packet() -> bool {
+    let every = active_faults().into_iter().find_map(|fault| match fault {
+        Fault::DropDiscoveryPackets { every } => Some(every.max(1) as u64),
+        _ => None,
+    });
+    match every {
+        Some(every) => PACKETS_SEEN.fetch_add(1, Ordering::Relaxed) % every == every - 1,
+        None => false,
+    }
+}
+
+/// Delay to apply before a control command, if any
+pub fn control_delay() -> Option<Duration> {
+    active_faults().into_iter().find_map(|fault| match fault {
+        Fault::DelayControl { millis } => Some(Duration::from_millis(millis)),
+        _ => None,
+    })
+}
+
+/// Simulated uncorrected ECC errors on a GPU
+pub fn ecc_errors(gpu: u32) -> u64 {
+    active_faults()
+        .into_iter()
+        .filter_map(|fault| match fault {
+            Fault::GpuEccErrors { gpu: g, count } if g == gpu => Some(count),
+            _ => None,
+        })
+        .sum()
+}
+
+fn same_kind(a: &Fault, b: &Fault) -> bool {
+    match (a, b) {
+        (Fault::GpuEccErrors { gpu: x, .. }, Fault::GpuEccErrors { gpu: y, .. }) => x == y,
+        _ => std::mem::discriminant(a) == std::mem::discriminant(b),
+    }
+}
+
+fn write_faults(faults: &[Fault]) -> Result<(), String> {
+    let path = chaos_file();
+    let content = serde_json::to_string_pretty(faults)
+        .map_err(|e| format!("Failed to serialize faults: {}", e))?;
+    std::fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inject_and_clear() {
+        let path = std::env::temp_dir().join(format!("eryzaa-chaos-{}.json", std::process::id()));
+        std::env::set_var(CHAOS_FILE_ENV, &path);
+
+        inject(Fault::DelayControl { millis: 100 }).unwrap();
+        inject(Fault::DelayControl { millis: 250 }).unwrap();
+        inject(Fault::GpuEccErrors { gpu: 1, count: 3 }).unwrap();
+        inject(Fault::DropDiscoveryPackets { every: 1 }).unwrap();
+
+        assert_eq!(active_faults().len(), 3);
+        assert_eq!(control_delay(), Some(Duration::from_millis(250)));
+        assert_eq!((ecc_errors(0), ecc_errors(1)), (0, 3));
+        assert!(should_drop_discovery_packet());
+
+        clear().unwrap();
+        assert!(active_faults().is_empty());
+        assert!(!should_drop_discovery_packet());
+        let _ = std::fs::remove_file(path);
+    }
+}
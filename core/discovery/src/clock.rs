@@ -0,0 +1,149 @@
+//! Clock synchronization checks
+//! Expiry and billing compare wall clocks across machines, so each side checks its own NTP
+//! offset, advertises it, and estimates every peer's offset from the packets it receives.
+//! Run time is measured on the per-boot monotonic clock where possible.
+
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Offsets beyond this, locally or between peers, are worth a warning
+pub const DRIFT_WARNING_MS: i64 = 2000;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClockStatus {
+    pub synchronized: Option<bool>, // Whether an NTP daemon reports the clock as synced
+    pub offset_ms: Option<i64>,     // Local clock minus NTP time, positive when ahead
+}
+
+impl ClockStatus {
+    /// What is wrong with the local clock, if anything
+    pub fn warning(&self) -> Option<String> {
+        if let Some(offset) = self.offset_ms {
+            if offset.abs() > DRIFT_WARNING_MS {
+                return Some(format!("System clock is off from NTP time: {}", describe_offset(offset)));
+            }
+        }
+        match self.synchronized {
+            Some(false) => Some("System clock is not synchronized with NTP; enable systemd-timesyncd or chrony".to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// NTP state of this machine from chrony, falling back to timedatectl
+pub fn local_clock_status() -> ClockStatus {
+    let run = |program: &str, args: &[&str]| {
+        Command::new(program)
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+    };
+
+    if let Some(offset_ms) = run("chronyc", &["tracking"]).and_then(|output| parse_chrony_tracking(&output)) {
+        return ClockStatus { synchronized: Some(true), offset_ms: Some(offset_ms) };
+    }
+    ClockStatus {
+        synchronized: run("timedatectl", &["show", "-p", "NTPSynchronized"]).and_then(|output| parse_timedatectl(&output)),
+        offset_ms: None,
+    }
+}
+
+/// Offset from `chronyc tracking`: "System time : 0.000012 seconds fast of NTP time"
+pub fn parse_chrony_tracking(output: &str) -> Option<i64> {
+    let line = output.lines().find(|line| line.starts_with("System time"))?;
+    let fields: Vec<&str> = line.split(':').nth(1)?.split_whitespace().collect();
+    let seconds: f64 = fields.first()?.parse().ok()?;
+    let sign = match fields.get(2) {
+        Some(&"fast") => 1.0,
+        Some(&"slow") => -1.0,
+        _ => return None,
+    };
+    Some((sign * seconds * 1000.0).round() as i64)
+}
+
+/// `timedatectl show -p NTPSynchronized`: "NTPSynchronized=yes"
+pub fn parse_timedatectl(output: &str) -> Option<bool> {
+    match output.trim().strip_prefix("NTPSynchronized=")? {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Milliseconds since the Unix epoch on the local wall clock
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// How far a peer's clock is ahead of ours, from the send time on a packet we just received
+///
+/// Network delay makes the peer look slightly behind, which is negligible next to the
+/// drift worth warning about.
+pub fn peer_offset_ms(sent_at_ms: u64, received_at_ms: u64) -> i64 {
+    sent_at_ms as i64 - received_at_ms as i64
+}
+
+/// "1.5 s ahead", "300 ms behind"
+pub fn describe_offset(offset_ms: i64) -> String {
+    let direction = if offset_ms >= 0 { "ahead" } else { "behind" };
+    let magnitude = offset_ms.unsigned_abs();
+    if magnitude >= 1000 {
+        format!("{:.1} s {}", magnitude as f64 / 1000.0, direction)
+    } else {
+        format!("{} ms {}", magnitude, direction)
+    }
+}
+
+/// A reading of this machine's monotonic clock, which NTP steps and manual changes do not move
+///
+/// Seconds since boot only compare within the same boot, so each marker carries the boot id.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SessionMarker {
+    pub boot_id: String,
+    pub uptime_secs: u64,
+}
+
+impl SessionMarker {
+    /// The marker for right now, where the platform exposes boot time
+    pub fn now() -> Option<Self> {
+        let boot_id = std::fs::read_to_string("/proc/sys/kernel/random/boot_id").ok()?;
+        let uptime = std::fs::read_to_string("/proc/uptime").ok()?;
+        let uptime_secs: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+        Some(Self {
+            boot_id: boot_id.trim().to_string(),
+            uptime_secs: uptime_secs as u64,
+        })
+    }
+
+    /// Seconds from this marker to a later one, or None when they span a reboot
+    pub fn seconds_until(&self, later: &SessionMarker) -> Option<u64> {
+        (self.boot_id == later.boot_id).then(|| later.uptime_secs.saturating_sub(self.uptime_secs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_readings() {
+        let tracking = "Reference ID    : A29FC87B (time.cloudflare.com)\nSystem time     : 2.500000000 seconds slow of NTP time\nLast offset     : -0.000012 seconds\n";
+        assert_eq!(parse_chrony_tracking(tracking), Some(-2500));
+        assert_eq!(parse_timedatectl("NTPSynchronized=no\n"), Some(false));
+
+        let status = ClockStatus { synchronized: Some(true), offset_ms: Some(-2500) };
+        assert_eq!(status.warning().unwrap(), "System clock is off from NTP time: 2.5 s behind");
+        assert_eq!(ClockStatus { synchronized: Some(true), offset_ms: Some(12) }.warning(), None);
+        assert_eq!(peer_offset_ms(1_000_300, 1_000_000), 300);
+
+        let start = SessionMarker { boot_id: "a".to_string(), uptime_secs: 100 };
+        assert_eq!(start.seconds_until(&SessionMarker { boot_id: "a".to_string(), uptime_secs: 700 }), Some(600));
+        assert_eq!(start.seconds_until(&SessionMarker { boot_id: "b".to_string(), uptime_secs: 700 }), None);
+    }
+}
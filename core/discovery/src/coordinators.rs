@@ -0,0 +1,170 @@
+//! Mirrored coordinators
+//! `ERYZAA_COORDINATOR_URL` may list several coordinators, separated by commas, so the marketplace
+//! survives one of them going down. Requests a single coordinator can answer go to the first one
+//! that is up, in the order given, and fail over to the next when it cannot be reached; a
+//! coordinator that failed is skipped for a while, longer each time, until a health check or a
+//! request finds it back. What every coordinator should hear, like a node registering or a job
+//! finishing, is published to all of them.
+
+use std::sync::Mutex;
+
+use crate::market::{self, COORDINATOR_URL_ENV};
+
+pub const HEALTH_PATH: &str = "/api/health";
+
+/// Seconds a coordinator is skipped after its first failure, doubling with each one after
+const FIRST_BACKOFF: u64 = 15;
+const MAX_BACKOFF: u64 = 600;
+
+/// Coordinator URLs in a comma or whitespace separated list, without duplicates
+pub fn parse_urls(list: &str) -> Vec<String> {
+    let mut urls: Vec<String> = vec![];
+    for url in list.split([',', ' ', '\n', '\t']).map(|url| url.trim().trim_end_matches('/')) {
+        if !url.is_empty() && !urls.iter().any(|known| known == url) {
+            urls.push(url.to_string());
+        }
+    }
+    urls
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Health {
+    pub failures: u32,   // In a row; 0 while the coordinator answers
+    pub skip_until: u64, // Unix seconds
+}
+
+impl Health {
+    fn failed(&mut self, now: u64) {
+        self.failures += 1;
+        let backoff = FIRST_BACKOFF.saturating_mul(1 << (self.failures - 1).min(10)).min(MAX_BACKOFF);
+        self.skip_until = now + backoff;
+    }
+
+    pub fn is_up(&self, now: u64) -> bool {
+        self.skip_until <= now
+    }
+}
+
+/// Every configured coordinator and how each last fared
+#[derive(Debug, Default)]
+pub struct CoordinatorSet {
+    urls: Vec<String>,
+    health: Mutex<Vec<Health>>,
+}
+
+impl CoordinatorSet {
+    pub fn new(urls: Vec<String>) -> Self {
+        let health = Mutex::new(vec![Health::default(); urls.len()]);
+        Self { urls, health }
+    }
+
+    /// The coordinators in `ERYZAA_COORDINATOR_URL`, None when it lists none
+    pub fn from_env() -> Option<Self> {
+        let urls = parse_urls(&std::env::var(COORDINATOR_URL_ENV).unwrap_or_default());
+        (!urls.is_empty()).then(|| Self::new(urls))
+    }
+
+    pub fn urls(&self) -> &[String] {
+        &self.urls
+    }
+
+    pub fn health(&self) -> Vec<(String, Health)> {
+        self.urls.iter().cloned().zip(self.health.lock().unwrap().iter().copied()).collect()
+    }
+
+    /// Indexes to try at `now`: coordinators that are up in configured order, then the others
+    fn order(&self, now: u64) -> Vec<usize> {
+        let health = self.health.lock().unwrap();
+        let (up, down): (Vec<usize>, Vec<usize>) = (0..self.urls.len()).partition(|index| health[*index].is_up(now));
+        up.into_iter().chain(down).collect()
+    }
+
+    fn record(&self, index: usize, ok: bool, now: u64) {
+        let mut health = self.health.lock().unwrap();
+        match ok {
+            true => health[index] = Health::default(),
+            false => health[index].failed(now),
+        }
+    }
+
+    /// Ask each coordinator whether it is up
+    pub fn check_health(&self) {
+        for (index, url) in self.urls.iter().enumerate() {
+            let ok = market::request(url, "GET", HEALTH_PATH, None, None).is_ok();
+            self.record(index, ok, crate::current_timestamp());
+        }
+    }
+
+    /// The answer of the first coordinator that can give one
+    ///
+    /// Only an unreachable or overloaded coordinator is failed over; a refusal is the answer.
+    pub fn call<T>(&self, mut request: impl FnMut(&str) -> Result<T, String>) -> Result<T, String> {
+        if self.urls.is_empty() {
+            return Err(format!("No coordinator configured (set {})", COORDINATOR_URL_ENV));
+        }
+        let mut errors = vec![];
+        for index in self.order(crate::current_timestamp()) {
+            match request(&self.urls[index]) {
+                Ok(answer) => {
+                    self.record(index, true, crate::current_timestamp());
+                    return Ok(answer);
+                }
+                Err(e) if market::is_unavailable(&e) => {
+                    self.record(index, false, crate::current_timestamp());
+                    errors.push(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(format!("No coordinator answered: {}", errors.join("; ")))
+    }
+
+    /// Send the same request to every coordinator, returning each one's outcome
+    pub fn publish<T>(&self, mut request: impl FnMut(&str) -> Result<T, String>) -> Vec<(String, Result<T, String>)> {
+        self.urls
+            .iter()
+            .enumerate()
+            .map(|(index, url)| {
+                let result = request(url);
+                let reachable = !matches!(&result, Err(e) if market::is_unavailable(e));
+                self.record(index, reachable, crate::current_timestamp());
+                (url.clone(), result)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failover() {
+        assert_eq!(parse_urls(" http://a:8090/, http://b:8090 http://a:8090,,"), vec!["http://a:8090", "http://b:8090"]);
+
+        let set = CoordinatorSet::new(parse_urls("http://a:8090,http://b:8090,http://c:8090"));
+        let mut asked = vec![];
+        let answer = set.call(|url| {
+            asked.push(url.to_string());
+            match url {
+                "http://a:8090" => Err(format!("Failed to reach coordinator {}: connection refused", url)),
+                _ => Ok(url.to_string()),
+            }
+        });
+        assert_eq!(answer.unwrap(), "http://b:8090");
+        assert_eq!(asked, vec!["http://a:8090", "http://b:8090"]);
+
+        // The failed coordinator goes last until its backoff runs out
+        let now = crate::current_timestamp();
+        assert_eq!(set.order(now), vec![1, 2, 0]);
+        assert_eq!(set.order(now + FIRST_BACKOFF), vec![0, 1, 2]);
+
+        // A refusal is not failed over
+        let refused = set.call(|url| Err::<(), _>(format!("Coordinator {} answered HTTP/1.1 400 Bad Request: Unknown claim code", url)));
+        assert!(refused.unwrap_err().contains("http://b:8090"));
+
+        let outcomes = set.publish(|url| if url == "http://c:8090" { Err("Failed to reach coordinator".to_string()) } else { Ok(()) });
+        assert_eq!(outcomes.iter().filter(|(_, result)| result.is_ok()).count(), 2);
+        assert_eq!(set.health()[2].1.failures, 1);
+    }
+}
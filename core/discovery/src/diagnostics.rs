@@ -0,0 +1,321 @@
+//! ZeroTier network health checks
+//! Answers "why can't a client reach my node" from `zerotier-cli`: service state, membership
+//! authorization, routes, peer paths and MTU, each with a plain-language fix. The clock is
+//! checked too, since job expiry and billing compare timestamps across machines.
+
+use serde_json::Value;
+use std::fmt;
+use std::process::Command;
+
+use crate::clock::{self, ClockStatus};
+use crate::overlay;
+
+/// Default MTU of ZeroTier virtual networks
+pub const ZEROTIER_MTU: u64 = 2800;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Ok,
+    Warning,
+    Problem,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub name: String,
+    pub severity: Severity,
+    pub detail: String,
+    pub hint: Option<String>, // What to do about a warning or problem
+}
+
+impl Check {
+    fn ok(name: &str, detail: impl Into<String>) -> Self {
+        Self { name: name.to_string(), severity: Severity::Ok, detail: detail.into(), hint: None }
+    }
+
+    fn warning(name: &str, detail: impl Into<String>, hint: &str) -> Self {
+        Self { name: name.to_string(), severity: Severity::Warning, detail: detail.into(), hint: Some(hint.to_string()) }
+    }
+
+    fn problem(name: &str, detail: impl Into<String>, hint: &str) -> Self {
+        Self { name: name.to_string(), severity: Severity::Problem, detail: detail.into(), hint: Some(hint.to_string()) }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mark = match self.severity {
+            Severity::Ok => "[+]",
+            Severity::Warning => "[!]",
+            Severity::Problem => "[-]",
+        };
+        write!(f, "{} {}: {}", mark, self.name, self.detail)?;
+        if let Some(hint) = &self.hint {
+            write!(f, "\n    → {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run every check; `network_id` is the network clients are expected to use
+pub fn diagnose(network_id: Option<&str>) -> Vec<Check> {
+    let mut checks = zerotier_checks(network_id);
+    checks.push(check_clock(&clock::local_clock_status()));
+    checks
+}
+
+fn zerotier_checks(network_id: Option<&str>) -> Vec<Check> {
+    let info = match zerotier_cli(&["info"]) {
+        Ok(output) => output,
+        Err(e) => return vec![service_problem(&e)],
+    };
+    let mut checks = vec![check_info(&info)];
+    if checks[0].severity == Severity::Problem {
+        return checks;
+    }
+
+    match zerotier_cli(&["-j", "listnetworks"]) {
+        Ok(output) => {
+            let mtus = |device: &str| {
+                std::fs::read_to_string(format!("/sys/class/net/{}/mtu", device))
+                    .ok()
+                    .and_then(|mtu| mtu.trim().parse().ok())
+            };
+            checks.extend(check_networks(&output, network_id, mtus));
+        }
+        Err(e) => checks.push(Check::problem("Networks", e, "Run `sudo zerotier-cli listnetworks` to see the error")),
+    }
+    match zerotier_cli(&["-j", "peers"]) {
+        Ok(output) => checks.extend(check_peers(&output)),
+        Err(e) => checks.push(Check::warning("Peers", e, "Run `sudo zerotier-cli peers` to see the error")),
+    }
+    checks
+}
+
+/// Whether the local clock is NTP-synchronized and within the drift threshold
+pub fn check_clock(status: &ClockStatus) -> Check {
+    if let Some(warning) = status.warning() {
+        return Check::warning("Clock", warning, "Enable NTP with `sudo timedatectl set-ntp true`; drift skews job expiry and billing");
+    }
+    match (status.synchronized, status.offset_ms) {
+        (_, Some(offset_ms)) => Check::ok("Clock", format!("synchronized, {}", clock::describe_offset(offset_ms))),
+        (Some(true), None) => Check::ok("Clock", "synchronized"),
+        _ => Check::warning("Clock", "NTP state unknown", "Install chrony or systemd-timesyncd so drift can be detected"),
+    }
+}
+
+fn zerotier_cli(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("zerotier-cli")
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to execute zerotier-cli: {}", e))?;
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{}{}", stdout.trim(), stderr.trim()));
+    }
+    Ok(stdout)
+}
+
+fn service_problem(error: &str) -> Check {
+    if error.contains("Failed to execute") {
+        Check::problem("Service", "ZeroTier is not installed", "Install it from https://www.zerotier.com/download/ or run the setup wizard")
+    } else {
+        Check::problem(
+            "Service",
+            format!("zerotier-one is not answering ({})", error),
+            "Start it with `sudo systemctl start zerotier-one`; if it is running, the CLI may need sudo to read the auth token",
+        )
+    }
+}
+
+/// `zerotier-cli info`: "200 info <address> <version> ONLINE"
+pub fn check_info(output: &str) -> Check {
+    let fields: Vec<&str> = output.split_whitespace().collect();
+    match fields.as_slice() {
+        ["200", "info", address, version, "ONLINE", ..] => {
+            Check::ok("Service", format!("online as {} (version {})", address, version))
+        }
+        ["200", "info", _, _, "TUNNELED", ..] => Check::warning(
+            "Service",
+            "online, but only over TCP fallback",
+            "Outbound UDP is blocked; allow UDP port 9993 out, or expect high latency",
+        ),
+        ["200", "info", _, _, state, ..] => Check::problem(
+            "Service",
+            format!("running but {}", state),
+            "The node cannot reach the ZeroTier roots; check the internet connection and that UDP 9993 is not blocked",
+        ),
+        _ => service_problem(output.trim()),
+    }
+}
+
+/// `zerotier-cli -j listnetworks`; `interface_mtu` reads the MTU of a local interface
+pub fn check_networks(json: &str, network_id: Option<&str>, interface_mtu: impl Fn(&str) -> Option<u64>) -> Vec<Check> {
+    let networks: Vec<Value> = serde_json::from_str(json).unwrap_or_default();
+    let mut checks = Vec::new();
+
+    if let Some(id) = network_id {
+        if !networks.iter().any(|n| n["nwid"].as_str() == Some(id)) {
+            checks.push(Check::problem(
+                "Membership",
+                format!("not joined to network {}", id),
+                &format!("Join it with `sudo zerotier-cli join {}`", id),
+            ));
+        }
+    } else if networks.is_empty() {
+        checks.push(Check::problem("Membership", "not joined to any network", "Join the Eryzaa network from the setup wizard"));
+    }
+
+    for network in &networks {
+        let id = network["nwid"].as_str().unwrap_or("?");
+        let name = format!("Network {}", id);
+        match network["status"].as_str().unwrap_or("") {
+            "OK" => checks.push(Check::ok(&name, "authorized")),
+            "ACCESS_DENIED" => {
+                checks.push(Check::problem(&name, "membership not authorized", "Ask the network admin to authorize this node in ZeroTier Central"));
+                continue;
+            }
+            "REQUESTING_CONFIGURATION" => {
+                checks.push(Check::warning(&name, "waiting for configuration", "Wait a minute; if it persists the controller is unreachable"));
+                continue;
+            }
+            "NOT_FOUND" => {
+                checks.push(Check::problem(&name, "network does not exist", "Check the network ID for typos"));
+                continue;
+            }
+            other => checks.push(Check::warning(&name, format!("status {}", other), "Leave and re-join the network")),
+        }
+
+        let addresses: Vec<&str> = network["assignedAddresses"]
+            .as_array()
+            .map(|a| a.iter().filter_map(|v| v.as_str()).collect())
+            .unwrap_or_default();
+        if addresses.is_empty() {
+            checks.push(Check::problem(
+                "Addresses",
+                format!("no address assigned on {}", id),
+                "Enable auto-assign or give this member an IP in ZeroTier Central",
+            ));
+        } else {
+            checks.push(Check::ok("Addresses", addresses.join(", ")));
+        }
+
+        let routes: Vec<&str> = network["routes"]
+            .as_array()
+            .map(|r| r.iter().filter_map(|route| route["target"].as_str()).collect())
+            .unwrap_or_default();
+        let overlay_routes: Vec<&str> = routes
+            .iter()
+            .copied()
+            .filter(|target| overlay::DEFAULT_OVERLAY_SUBNETS.contains(target))
+            .collect();
+        if routes.is_empty() {
+            checks.push(Check::problem("Routes", format!("no managed routes on {}", id), "Add the network's subnet as a managed route in ZeroTier Central"));
+        } else if overlay_routes.is_empty() {
+            checks.push(Check::warning(
+                "Routes",
+                format!("routes {} do not include an Eryzaa overlay subnet", routes.join(", ")),
+                "Clients expect one of the Eryzaa subnets; check this is the right network",
+            ));
+        } else {
+            checks.push(Check::ok("Routes", routes.join(", ")));
+        }
+
+        let network_mtu = network["mtu"].as_u64().unwrap_or(ZEROTIER_MTU);
+        if let Some(device) = network["portDeviceName"].as_str() {
+            match interface_mtu(device) {
+                Some(mtu) if mtu < network_mtu => checks.push(Check::warning(
+                    "MTU",
+                    format!("{} has MTU {} but the network uses {}", device, mtu, network_mtu),
+                    "Large transfers may stall; restart zerotier-one or remove the MTU override on the interface",
+                )),
+                Some(mtu) => checks.push(Check::ok("MTU", format!("{} ({})", mtu, device))),
+                None => {}
+            }
+        }
+    }
+    checks
+}
+
+/// `zerotier-cli -j peers`: path and latency of every other member
+pub fn check_peers(json: &str) -> Vec<Check> {
+    let peers: Vec<Value> = serde_json::from_str(json).unwrap_or_default();
+    let mut checks = Vec::new();
+    let mut relayed = 0;
+    let mut leaves = 0;
+
+    for peer in peers.iter().filter(|p| p["role"].as_str() == Some("LEAF")) {
+        leaves += 1;
+        let address = peer["address"].as_str().unwrap_or("?");
+        let latency = peer["latency"].as_i64().unwrap_or(-1);
+        let direct = peer["paths"]
+            .as_array()
+            .map(|paths| paths.iter().any(|path| path["active"].as_bool().unwrap_or(false)))
+            .unwrap_or(false);
+        let name = format!("Peer {}", address);
+
+        if !direct {
+            relayed += 1;
+            checks.push(Check::warning(
+                &name,
+                "RELAY",
+                "Traffic goes through a ZeroTier root; forward UDP 9993 on your router or enable UPnP for a direct path",
+            ));
+        } else if latency < 0 {
+            checks.push(Check::ok(&name, "DIRECT"));
+        } else if latency > 250 {
+            checks.push(Check::warning(&name, format!("DIRECT, {} ms", latency), "High latency; interactive sessions will feel slow"));
+        } else {
+            checks.push(Check::ok(&name, format!("DIRECT, {} ms", latency)));
+        }
+    }
+
+    if leaves > 0 && relayed == leaves {
+        checks.push(Check::problem(
+            "Paths",
+            "every peer is relayed",
+            "This node's NAT or firewall blocks direct connections; allow UDP 9993 in and out",
+        ));
+    } else if leaves == 0 {
+        checks.push(Check::ok("Paths", "no other members connected yet"));
+    }
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_checks() {
+        assert_eq!(check_info("200 info 8a2f1c0d3e 1.12.2 ONLINE\n").severity, Severity::Ok);
+        assert_eq!(check_info("200 info 8a2f1c0d3e 1.12.2 OFFLINE\n").severity, Severity::Problem);
+
+        let networks = r#"[
+            {"nwid": "363c67c55ad2489d", "status": "OK", "assignedAddresses": ["10.242.1.5/16"],
+             "routes": [{"target": "10.242.0.0/16", "via": null}], "mtu": 2800, "portDeviceName": "ztabc"},
+            {"nwid": "0000000000000001", "status": "ACCESS_DENIED", "assignedAddresses": [], "routes": []}
+        ]"#;
+        let checks = check_networks(networks, Some("363c67c55ad2489d"), |_| Some(1500));
+        let worst = |name: &str| checks.iter().filter(|c| c.name == name).map(|c| c.severity).max();
+        assert_eq!(worst("Routes"), Some(Severity::Ok));
+        assert_eq!(worst("MTU"), Some(Severity::Warning));
+        assert_eq!(worst("Network 0000000000000001"), Some(Severity::Problem));
+        assert_eq!(worst("Membership"), None);
+
+        let peers = r#"[
+            {"address": "aaaaaaaaaa", "role": "LEAF", "latency": 32, "paths": [{"address": "203.0.113.4/9993", "active": true}]},
+            {"address": "bbbbbbbbbb", "role": "LEAF", "latency": -1, "paths": []},
+            {"address": "cccccccccc", "role": "PLANET", "latency": 80, "paths": [{"active": true}]}
+        ]"#;
+        let checks = check_peers(peers);
+        assert_eq!(checks.len(), 2);
+        assert_eq!(checks[0].detail, "DIRECT, 32 ms");
+        assert_eq!(checks[1].detail, "RELAY");
+
+        let drifted = ClockStatus { synchronized: Some(true), offset_ms: Some(4200) };
+        assert_eq!(check_clock(&drifted).severity, Severity::Warning);
+        assert_eq!(check_clock(&ClockStatus { synchronized: Some(true), offset_ms: Some(3) }).detail, "synchronized, 3 ms ahead");
+    }
+}
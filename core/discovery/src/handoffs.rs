@@ -0,0 +1,288 @@
+//! Relay for one-time credential handoffs
+//! A client parks a sealed handoff at the coordinator so its link can be redeemed from any
+//! machine, not just the one that issued it. The coordinator never sees the key that opens an
+//! offer, only a hash of it that the redeemer has to match; it hands each offer out once, and
+//! the issuer checks on or revokes it with the owner token it posted a hash of.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::market;
+
+pub const HANDOFFS_PATH: &str = "/api/handoffs";
+pub const REDEEM_PATH: &str = "/api/handoffs/redeem";
+pub const REVOKE_PATH: &str = "/api/handoffs/revoke";
+pub const STATUS_PATH: &str = "/api/handoffs/status";
+
+/// Offers the relay holds at once
+const MAX_OFFERS: usize = 10_000;
+
+/// A sealed bundle is a few keys and addresses, never more
+const MAX_SEALED: usize = 32 * 1024;
+
+/// Hex SHA-256 of a handoff key or owner token, which is all the relay keeps of either
+pub fn secret_hash(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+/// A sealed handoff waiting at the relay
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RelayedOffer {
+    pub id: String,
+    pub sealed: String, // Cleared once redeemed or revoked
+    pub expires_at: u64,
+    pub key_hash: String,
+    pub owner_hash: String,
+    #[serde(default)]
+    pub redeemed_at: Option<u64>,
+    #[serde(default)]
+    pub revoked: bool,
+}
+
+/// What the issuer learns about its offer
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RelayStatus {
+    pub redeemed_at: Option<u64>,
+    pub revoked: bool,
+}
+
+/// A redeemer asking for an offer, proving it holds the key
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RedeemRequest {
+    pub id: String,
+    pub key_hash: String,
+}
+
+/// The issuer revoking or checking on an offer
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OwnerRequest {
+    pub id: String,
+    pub owner_token: String,
+}
+
+/// Relayed offers, kept in one JSON file
+#[derive(Debug, Clone)]
+pub struct HandoffRelay {
+    path: PathBuf,
+    lock: Arc<Mutex<()>>,
+}
+
+impl HandoffRelay {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), lock: Arc::new(Mutex::new(())) }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Park `offer` until it expires; ids are never reused
+    pub fn post(&self, offer: RelayedOffer, now: u64) -> Result<(), String> {
+        if offer.id.is_empty() || offer.id.len() > 32 || !offer.id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err("Handoff ids are 1-32 letters or digits".to_string());
+        }
+        if offer.sealed.is_empty() || offer.sealed.len() > MAX_SEALED {
+            return Err(format!("Sealed handoffs are 1-{} bytes", MAX_SEALED));
+        }
+        if offer.expires_at <= now || offer.redeemed_at.is_some() || offer.revoked {
+            return Err("Only live offers can be relayed".to_string());
+        }
+
+        let _guard = self.lock.lock().unwrap();
+        let mut offers = self.load();
+        offers.retain(|o| o.expires_at > now);
+        if offers.iter().any(|o| o.id == offer.id) {
+            return Err(format!("Handoff {} was relayed already", offer.id));
+        }
+        if offers.len() >= MAX_OFFERS {
+            return Err("The relay is full, try again later".to_string());
+        }
+        offers.push(offer);
+        self.save(&offers)
+    }
+
+    /// The sealed bundle of an offer, handed out once to whoever holds its key
+    pub fn redeem(&self, request: &RedeemRequest, now: u64) -> Result<String, (&'static str, String)> {
+        let _guard = self.lock.lock().unwrap();
+        let mut offers = self.load();
+        let offer = offers
+            .iter_mut()
+            .find(|o| o.id == request.id)
+            .ok_or_else(|| ("404 Not Found", format!("No handoff {} at this relay", request.id)))?;
+
+        // A wrong key does not use the offer up
+        if offer.key_hash != request.key_hash {
+            return Err(("403 Forbidden", "Handoff key is not valid".to_string()));
+        }
+        if offer.revoked {
+            return Err(("410 Gone", format!("Handoff {} was revoked", request.id)));
+        }
+        if offer.redeemed_at.is_some() {
+            return Err(("410 Gone", format!("Handoff {} was already redeemed", request.id)));
+        }
+        if now >= offer.expires_at {
+            return Err(("410 Gone", format!("Handoff {} has expired", request.id)));
+        }
+
+        offer.redeemed_at = Some(now);
+        let sealed = std::mem::take(&mut offer.sealed);
+        self.save(&offers).map_err(|e| ("500 Internal Server Error", e))?;
+        Ok(sealed)
+    }
+
+    /// Revoke an offer for its issuer
+    pub fn revoke(&self, request: &OwnerRequest) -> Result<(), String> {
+        let _guard = self.lock.lock().unwrap();
+        let mut offers = self.load();
+        let offer = Self::owned(&mut offers, request)?;
+        offer.revoked = true;
+        offer.sealed.clear();
+        self.save(&offers)
+    }
+
+    /// Whether an offer was redeemed or revoked, for its issuer
+    pub fn status(&self, request: &OwnerRequest) -> Result<RelayStatus, String> {
+        let _guard = self.lock.lock().unwrap();
+        let mut offers = self.load();
+        let offer = Self::owned(&mut offers, request)?;
+        Ok(RelayStatus { redeemed_at: offer.redeemed_at, revoked: offer.revoked })
+    }
+
+    fn owned<'a>(offers: &'a mut [RelayedOffer], request: &OwnerRequest) -> Result<&'a mut RelayedOffer, String> {
+        offers
+            .iter_mut()
+            .find(|o| o.id == request.id && o.owner_hash == secret_hash(&request.owner_token))
+            .ok_or_else(|| format!("No handoff {} of yours at this relay", request.id))
+    }
+
+    fn load(&self) -> Vec<RelayedOffer> {
+        std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, offers: &[RelayedOffer]) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(offers)
+            .map_err(|e| format!("Failed to serialize relayed handoffs: {}", e))?;
+        std::fs::write(&self.path, content)
+            .map_err(|e| format!("Failed to write relayed handoffs {}: {}", self.path.display(), e))
+    }
+}
+
+/// True for the paths `route` answers
+pub fn handles(path: &str) -> bool {
+    path.split('?').next().is_some_and(|path| path == HANDOFFS_PATH || path.starts_with("/api/handoffs/"))
+}
+
+/// Status, content type and body for a handoff request
+pub fn route(method: &str, path: &str, body: &str, relay: &HandoffRelay) -> (&'static str, &'static str, String) {
+    let now = crate::current_timestamp();
+    match (method, path.split('?').next().unwrap_or(path)) {
+        ("POST", HANDOFFS_PATH) => match serde_json::from_str::<RelayedOffer>(body) {
+            Ok(offer) => {
+                let id = offer.id.clone();
+                match relay.post(offer, now) {
+                    Ok(()) => ("201 Created", "text/plain", format!("Holding handoff {}\n", id)),
+                    Err(e) => ("400 Bad Request", "text/plain", e + "\n"),
+                }
+            }
+            Err(e) => ("400 Bad Request", "text/plain", format!("Malformed handoff: {}\n", e)),
+        },
+        ("POST", REDEEM_PATH) => match serde_json::from_str::<RedeemRequest>(body) {
+            Ok(request) => match relay.redeem(&request, now) {
+                Ok(sealed) => ("200 OK", "text/plain", sealed),
+                Err((status, e)) => (status, "text/plain", e + "\n"),
+            },
+            Err(e) => ("400 Bad Request", "text/plain", format!("Malformed redemption: {}\n", e)),
+        },
+        ("POST", REVOKE_PATH) => match serde_json::from_str::<OwnerRequest>(body) {
+            Ok(request) => match relay.revoke(&request) {
+                Ok(()) => ("200 OK", "text/plain", format!("Revoked handoff {}\n", request.id)),
+                Err(e) => ("404 Not Found", "text/plain", e + "\n"),
+            },
+            Err(e) => ("400 Bad Request", "text/plain", format!("Malformed revocation: {}\n", e)),
+        },
+        ("POST", STATUS_PATH) => match serde_json::from_str::<OwnerRequest>(body) {
+            Ok(request) => match relay.status(&request) {
+                Ok(status) => ("200 OK", "application/json", serde_json::to_string(&status).unwrap_or_default()),
+                Err(e) => ("404 Not Found", "text/plain", e + "\n"),
+            },
+            Err(e) => ("400 Bad Request", "text/plain", format!("Malformed status request: {}\n", e)),
+        },
+        ("GET" | "POST", _) => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain", "Only GET and POST are supported\n".to_string()),
+    }
+}
+
+/// Park an offer at the coordinator at `base_url`
+pub fn post(base_url: &str, offer: &RelayedOffer) -> Result<String, String> {
+    let body = serde_json::to_string(offer).map_err(|e| format!("Failed to serialize handoff: {}", e))?;
+    market::request(base_url, "POST", HANDOFFS_PATH, Some(&body), None).map(|answer| answer.trim().to_string())
+}
+
+/// Fetch the sealed bundle of an offer from the coordinator at `base_url`
+pub fn redeem(base_url: &str, request: &RedeemRequest) -> Result<String, String> {
+    let body = serde_json::to_string(request).map_err(|e| format!("Failed to serialize redemption: {}", e))?;
+    market::request(base_url, "POST", REDEEM_PATH, Some(&body), None)
+}
+
+/// Revoke an offer at the coordinator at `base_url`
+pub fn revoke(base_url: &str, request: &OwnerRequest) -> Result<String, String> {
+    let body = serde_json::to_string(request).map_err(|e| format!("Failed to serialize revocation: {}", e))?;
+    market::request(base_url, "POST", REVOKE_PATH, Some(&body), None).map(|answer| answer.trim().to_string())
+}
+
+/// Whether an offer at the coordinator at `base_url` was redeemed or revoked
+pub fn status(base_url: &str, request: &OwnerRequest) -> Result<RelayStatus, String> {
+    let body = serde_json::to_string(request).map_err(|e| format!("Failed to serialize status request: {}", e))?;
+    let answer = market::request(base_url, "POST", STATUS_PATH, Some(&body), None)?;
+    serde_json::from_str(&answer).map_err(|e| format!("Failed to parse handoff status: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relay_hands_offers_out_once() {
+        let relay = HandoffRelay::new(std::env::temp_dir().join(format!("eryzaa-handoffs-{}.json", std::process::id())));
+        let offer = RelayedOffer {
+            id: "abc123".to_string(),
+            sealed: "c2VhbGVk".to_string(),
+            expires_at: 4_000_000_000, // Outlives the clock `route` reads
+            key_hash: secret_hash("key"),
+            owner_hash: secret_hash("owner"),
+            redeemed_at: None,
+            revoked: false,
+        };
+        relay.post(offer.clone(), 1000).unwrap();
+        assert!(relay.post(offer.clone(), 1000).is_err());
+        assert!(relay.post(RelayedOffer { id: "../x".to_string(), ..offer.clone() }, 1000).is_err());
+
+        let owner = OwnerRequest { id: "abc123".to_string(), owner_token: "owner".to_string() };
+        let stranger = OwnerRequest { owner_token: "guess".to_string(), ..owner.clone() };
+        assert!(relay.revoke(&stranger).is_err() && relay.status(&stranger).is_err());
+
+        let wrong = RedeemRequest { id: "abc123".to_string(), key_hash: secret_hash("guess") };
+        assert_eq!(relay.redeem(&wrong, 1500).unwrap_err().0, "403 Forbidden");
+        let request = RedeemRequest { key_hash: secret_hash("key"), ..wrong };
+        let body = serde_json::to_string(&request).unwrap();
+        assert_eq!(route("POST", REDEEM_PATH, &body, &relay), ("200 OK", "text/plain", "c2VhbGVk".to_string()));
+        assert_eq!(relay.redeem(&request, 1500).unwrap_err().0, "410 Gone");
+        assert!(relay.status(&owner).unwrap().redeemed_at.is_some());
+
+        relay.post(RelayedOffer { id: "def456".to_string(), ..offer }, 1000).unwrap();
+        relay.revoke(&OwnerRequest { id: "def456".to_string(), ..owner }).unwrap();
+        let revoked = RedeemRequest { id: "def456".to_string(), key_hash: secret_hash("key") };
+        assert_eq!(relay.redeem(&revoked, 1500).unwrap_err().1, "Handoff def456 was revoked");
+        std::fs::remove_file(relay.path()).unwrap();
+    }
+}
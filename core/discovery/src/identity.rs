@@ -0,0 +1,213 @@
+//! Node identity keys
+//! Each node signs every advertisement it sends with an ed25519 key and puts the public half
+//! in the advertisement. Listeners drop packets whose signature does not check out, and pin
+//! the first key they see for a node id, so another machine on the multicast group cannot
+//! take over a node's listing. Advertisements from nodes older than protocol v10 carry no
+//! signature; they are listed without a key, as unverified, until that node id has been seen
+//! signed, and unsigned packets from newer nodes are dropped. Pins can be kept in a file so they
+//! outlast a restart. Signatures are checked against the packet as it came, so they hold across
+//! protocol versions.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::NodeAdvertisement;
+use eryzaa_protocol::node::signed_body;
+
+pub struct NodeIdentity {
+    signing_key: SigningKey,
+}
+
+impl NodeIdentity {
+    /// A fresh key, for a node that does not keep one across restarts
+    pub fn generate() -> Self {
+        Self { signing_key: SigningKey::generate(&mut rand::rngs::OsRng) }
+    }
+
+    /// The key saved at `path`, created on first use
+    pub fn load_or_create(path: &Path) -> Result<Self, String> {
+        if let Ok(secret) = std::fs::read_to_string(path) {
+            let secret: [u8; 32] = hex::decode(secret.trim())
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| format!("{} does not hold an ed25519 key", path.display()))?;
+            return Ok(Self { signing_key: SigningKey::from_bytes(&secret) });
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let identity = Self::generate();
+        std::fs::write(path, hex::encode(identity.signing_key.to_bytes()))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+                .map_err(|e| format!("Failed to restrict {}: {}", path.display(), e))?;
+        }
+        Ok(identity)
+    }
+
+    /// Hex public key, as advertisements carry it
+    pub fn public_key(&self) -> String {
+        hex::encode(self.signing_key.verifying_key().to_bytes())
+    }
+
+    /// Put our public key in `advertisement` and sign it; sign last, after any other change
+    pub fn sign(&self, advertisement: &mut NodeAdvertisement) -> Result<(), String> {
+        advertisement.public_key = Some(self.public_key());
+        let signature = self.signing_key.sign(&advertisement.signed_bytes()?);
+        advertisement.signature = Some(hex::encode(signature.to_bytes()));
+        Ok(())
+    }
+}
+
+/// Check the signature on `advertisement`, decoded from `packet`; false when it has none
+pub fn verify(advertisement: &NodeAdvertisement, packet: &[u8]) -> Result<bool, String> {
+    let (public_key, signature) = match (&advertisement.public_key, &advertisement.signature) {
+        (None, None) => return Ok(false),
+        (Some(public_key), Some(signature)) => (public_key, signature),
+        _ => return Err(format!("Advertisement from {} is half signed", advertisement.node_id)),
+    };
+    let public_key: [u8; 32] = hex::decode(public_key)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| format!("Advertisement from {} has a malformed key", advertisement.node_id))?;
+    let signature: [u8; 64] = hex::decode(signature)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| format!("Advertisement from {} has a malformed signature", advertisement.node_id))?;
+    let public_key = VerifyingKey::from_bytes(&public_key)
+        .map_err(|e| format!("Advertisement from {} has an invalid key: {}", advertisement.node_id, e))?;
+    let signed = signed_body(packet)?
+        .ok_or_else(|| format!("Advertisement from {} does not match its packet", advertisement.node_id))?;
+    public_key
+        .verify(&signed, &Signature::from_bytes(&signature))
+        .map_err(|_| format!("Advertisement from {} has a bad signature", advertisement.node_id))?;
+    Ok(true)
+}
+
+/// Protocol version from which nodes sign every advertisement they send
+pub const SIGNED_SINCE: u32 = 10;
+
+/// Public key pinned for each node id that signed, kept in a file when given one so that a
+/// restart does not hand a node's listing to whoever signs for its id first
+#[derive(Debug, Default)]
+pub struct KeyPins {
+    pins: HashMap<String, String>,
+    path: Option<PathBuf>,
+}
+
+impl KeyPins {
+    /// Pins saved at `path`, which new pins are written back to
+    pub fn load_from(path: &Path) -> Self {
+        let pins = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self { pins, path: Some(path.to_path_buf()) }
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let content = serde_json::to_string_pretty(&self.pins)
+            .map_err(|e| format!("Failed to serialize node keys: {}", e))?;
+        std::fs::write(path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Key pinned for `node_id`, None until it has been seen signed
+    pub fn get(&self, node_id: &str) -> Option<&String> {
+        self.pins.get(node_id)
+    }
+
+    /// Verify `advertisement`, decoded from `packet`, and hold it to the key pinned for its
+    /// node id, pinning the key of a node signing for the first time
+    ///
+    /// Unsigned packets pass only from nodes older than `SIGNED_SINCE`, and only for node ids
+    /// that have never signed.
+    pub fn check(&mut self, advertisement: &NodeAdvertisement, packet: &[u8]) -> Result<(), String> {
+        if !verify(advertisement, packet)? && packet_version(packet) >= SIGNED_SINCE {
+            return Err(format!("Advertisement from {} is not signed", advertisement.node_id));
+        }
+        match (self.pins.get(&advertisement.node_id), &advertisement.public_key) {
+            (Some(pinned), Some(public_key)) if pinned == public_key => Ok(()),
+            (Some(_), _) => Err(format!("Advertisement from {} is not signed with its known key", advertisement.node_id)),
+            (None, Some(public_key)) => {
+                self.pins.insert(advertisement.node_id.clone(), public_key.clone());
+                // Still pinned for this run if the file cannot be written
+                if let Some(path) = &self.path {
+                    let _ = self.save_to(path);
+                }
+                Ok(())
+            }
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Protocol version a discovery packet was sent with
+fn packet_version(packet: &[u8]) -> u32 {
+    packet.get(..4).map_or(0, |version| u32::from_le_bytes([version[0], version[1], version[2], version[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_client_advertisement;
+    use eryzaa_protocol::node::{decode_advertisement, encode_advertisement};
+
+    #[test]
+    fn test_signed_advertisements() {
+        let path = std::env::temp_dir().join(format!("eryzaa-node-key-{}", std::process::id()));
+        let identity = NodeIdentity::load_or_create(&path).unwrap();
+        assert_eq!(NodeIdentity::load_or_create(&path).unwrap().public_key(), identity.public_key());
+        std::fs::remove_file(&path).unwrap();
+
+        let packet = |advertisement: &NodeAdvertisement| encode_advertisement(advertisement).unwrap();
+        let mut advertisement = create_client_advertisement("node-1".to_string(), "192.168.1.20".to_string(), None, String::new());
+        let pins_path = std::env::temp_dir().join(format!("eryzaa-node-pins-{}.json", std::process::id()));
+        let mut pins = KeyPins::load_from(&pins_path);
+        assert_eq!(verify(&advertisement, &packet(&advertisement)), Ok(false));
+        assert!(pins.check(&advertisement, &packet(&advertisement)).unwrap_err().contains("not signed"));
+
+        // Nodes from before signing are let through, with no key to show for them
+        let hex = include_str!("../../protocol/fixtures/v9/node_advertisement.hex");
+        let legacy = hex::decode(hex.trim()).unwrap();
+        let legacy_advertisement = decode_advertisement(&legacy).unwrap();
+        pins.check(&legacy_advertisement, &legacy).unwrap();
+        assert_eq!(pins.get(&legacy_advertisement.node_id), None);
+
+        identity.sign(&mut advertisement).unwrap();
+        assert_eq!(verify(&advertisement, &packet(&advertisement)), Ok(true));
+        pins.check(&advertisement, &packet(&advertisement)).unwrap();
+        assert_eq!(pins.get("node-1"), Some(&identity.public_key()));
+
+        // Pins outlast a restart
+        let mut pins = KeyPins::load_from(&pins_path);
+        std::fs::remove_file(&pins_path).unwrap();
+        assert_eq!(pins.get("node-1"), Some(&identity.public_key()));
+
+        // Changed in transit
+        let mut tampered = advertisement.clone();
+        tampered.price_per_hour = Some(0.01);
+        assert!(verify(&tampered, &packet(&tampered)).unwrap_err().contains("bad signature"));
+
+        // Someone else claiming the node id, signed or not
+        let mut impostor = advertisement.clone();
+        NodeIdentity::generate().sign(&mut impostor).unwrap();
+        assert_eq!(verify(&impostor, &packet(&impostor)), Ok(true));
+        assert!(pins.check(&impostor, &packet(&impostor)).unwrap_err().contains("known key"));
+        let unsigned = NodeAdvertisement { public_key: None, signature: None, ..advertisement };
+        assert!(pins.check(&unsigned, &packet(&unsigned)).is_err());
+
+        // Signed by a node a protocol version behind, before fields were added
+        let hex = include_str!("../../protocol/fixtures/v10/node_advertisement.hex");
+        let older = hex::decode(hex.trim()).unwrap();
+        assert_eq!(verify(&decode_advertisement(&older).unwrap(), &older), Ok(true));
+    }
+}
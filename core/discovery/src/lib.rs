@@ -1,73 +1,76 @@
-use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::future::Future;
 use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::path::Path;
 use std::sync::{Arc, Mutex};
-use std::thread;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::time;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
-/// Service discovery protocol for Eryzaa nodes
-/// Allows rental nodes to advertise their availability and clients to discover them
+pub mod abuse;
+pub mod backend;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+pub mod clock;
+pub mod coordinators;
+pub mod diagnostics;
+pub mod handoffs;
+pub mod identity;
+pub mod market;
+pub mod names;
+pub mod observers;
+pub mod overlay;
+pub mod privacy;
+pub mod push;
+pub mod quota;
+pub mod registration;
+pub mod rendezvous;
+pub mod timing;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NodeAdvertisement {
-    pub node_id: String,
-    pub node_type: NodeType,
-    pub ip_address: String,
-    pub zerotier_ip: Option<String>,
-    pub ssh_port: u16,
-    pub api_port: u16,
-    pub capabilities: NodeCapabilities,
-    pub status: NodeStatus,
-    pub timestamp: u64,
-    pub network_id: String, // ZeroTier network ID
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub enum NodeType {
-    Rental,
-    Client,
-    Coordinator,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NodeCapabilities {
-    pub cpu_cores: u32,
-    pub memory_gb: u32,
-    pub gpu_count: u32,
-    pub gpu_memory_gb: u32,
-    pub disk_space_gb: u32,
-    pub network_speed_mbps: u32,
-    pub supports_docker: bool,
-    pub supports_gpu: bool,
-    pub max_concurrent_jobs: u32,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub enum NodeStatus {
-    Available,
-    Busy,
-    Maintenance,
-    Offline,
-}
+pub use eryzaa_protocol::details;
+pub use eryzaa_protocol::hardware;
+use eryzaa_protocol::node::{decode_advertisement, encode_advertisement};
+pub use eryzaa_protocol::{AccessPolicy, Check, GpuStack, HardwareProfile, NodeAdvertisement, NodeCapabilities, NodeDetails, NodeStatus, NodeType, Readiness, TrialOffer};
+pub use backend::DiscoveryBackend;
+pub use identity::{KeyPins, NodeIdentity};
+pub use privacy::AdvertisementPrivacy;
+pub use timing::AdvertisementTiming;
 
 /// Discovery service for managing node advertisements
+///
+/// `start` spawns the advertising, listening, backend and cleanup tasks on the current tokio
+/// runtime; `shutdown` cancels them and returns once all of them have ended.
 pub struct DiscoveryService {
-    local_node: NodeAdvertisement,
+    local_node: Arc<Mutex<NodeAdvertisement>>,
     discovered_nodes: Arc<Mutex<HashMap<String, NodeAdvertisement>>>,
-    socket: Arc<UdpSocket>,
-    running: Arc<Mutex<bool>>,
+    clock_offsets: Arc<Mutex<HashMap<String, i64>>>, // How far each peer's clock is ahead of ours
+    socket: Arc<UdpSocket>, // Non-blocking; the tasks share an async handle on it
+    cancel: Mutex<CancellationToken>, // Cancelled on stop, replaced on the next start
+    tasks: Mutex<Vec<JoinHandle<()>>>,
     multicast_addr: SocketAddr,
+    timing: AdvertisementTiming,
+    push: Arc<Notify>, // Notified when the advertisement changed and should go out before the next beat
+    privacy: Arc<Mutex<AdvertisementPrivacy>>,
+    query_key: Option<String>, // Proves to private nodes we probe that we may see them
+    identity: Arc<Mutex<NodeIdentity>>,
+    node_keys: Arc<Mutex<KeyPins>>, // Public key pinned for each node id that signed
+    backends: Arc<Mutex<Vec<Box<dyn DiscoveryBackend>>>>, // Reach beyond the broadcast domain
 }
 
 const DISCOVERY_PORT: u16 = 9999;
 const MULTICAST_ADDR: &str = "239.255.255.250:9999"; // Local multicast address
-const ADVERTISEMENT_INTERVAL: Duration = Duration::from_secs(30);
 const NODE_TIMEOUT: Duration = Duration::from_secs(120);
 
 impl DiscoveryService {
     /// Create a new discovery service
     pub fn new(local_node: NodeAdvertisement) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::with_timing(local_node, AdvertisementTiming::default())
+    }
+    
+    /// Create a discovery service that advertises on its own schedule
+    pub fn with_timing(local_node: NodeAdvertisement, timing: AdvertisementTiming) -> Result<Self, Box<dyn std::error::Error>> {
+        timing.validate()?;
         let socket = UdpSocket::bind(format!("0.0.0.0:{}", DISCOVERY_PORT))?;
         socket.set_broadcast(true)?;
         
@@ -88,36 +91,65 @@ impl DiscoveryService {
             }
         }
         
+        socket.set_nonblocking(true)?;
         let multicast_addr = MULTICAST_ADDR.parse()?;
         
         Ok(DiscoveryService {
-            local_node,
+            local_node: Arc::new(Mutex::new(local_node)),
             discovered_nodes: Arc::new(Mutex::new(HashMap::new())),
+            clock_offsets: Arc::new(Mutex::new(HashMap::new())),
             socket: Arc::new(socket),
-            running: Arc::new(Mutex::new(false)),
+            cancel: Mutex::new(CancellationToken::new()),
+            tasks: Mutex::new(Vec::new()),
             multicast_addr,
+            timing,
+            push: Arc::new(Notify::new()),
+            privacy: Arc::new(Mutex::new(AdvertisementPrivacy::default())),
+            query_key: None,
+            identity: Arc::new(Mutex::new(NodeIdentity::generate())),
+            node_keys: Arc::new(Mutex::new(KeyPins::default())),
+            backends: Arc::new(Mutex::new(Vec::new())),
         })
     }
     
-    /// Start the discovery service
+    /// Start the discovery service on the current tokio runtime; an error outside of one
     pub fn start(&self) -> Result<(), Box<dyn std::error::Error>> {
-        *self.running.lock().unwrap() = true;
-        
-        // Start advertisement thread
-        self.start_advertisement_thread();
-        
-        // Start discovery listener thread  
-        self.start_listener_thread();
-        
-        // Start cleanup thread
-        self.start_cleanup_thread();
+        let runtime = tokio::runtime::Handle::try_current()
+            .map_err(|e| format!("Discovery has to be started on a tokio runtime: {}", e))?;
+        if self.is_running() {
+            return Ok(());
+        }
+        let socket = Arc::new(tokio::net::UdpSocket::from_std(self.socket.try_clone()?)?);
+        let cancel = CancellationToken::new();
+        *self.cancel.lock().unwrap() = cancel.clone();
         
+        let mut tasks = self.tasks.lock().unwrap();
+        tasks.clear();
+        tasks.push(runtime.spawn(self.advertise(Arc::clone(&socket), cancel.clone())));
+        tasks.push(runtime.spawn(self.listen(socket, cancel.clone())));
+        tasks.push(runtime.spawn(self.clean_up(cancel.clone())));
+        tasks.push(runtime.spawn(self.poll_backends(cancel)));
         Ok(())
     }
     
-    /// Stop the discovery service
+    /// Stop the discovery service; its tasks end at their next await
     pub fn stop(&self) {
-        *self.running.lock().unwrap() = false;
+        self.cancel.lock().unwrap().cancel();
+    }
+    
+    /// Stop the discovery service and wait for its tasks to end, letting a backend round in
+    /// progress finish first
+    pub async fn shutdown(&self) {
+        self.stop();
+        let tasks: Vec<JoinHandle<()>> = self.tasks.lock().unwrap().drain(..).collect();
+        for task in tasks {
+            let _ = task.await;
+        }
+    }
+    
+    /// Whether the tasks were started and not stopped since
+    pub fn is_running(&self) -> bool {
+        !self.cancel.lock().unwrap().is_cancelled() && !self.tasks.lock().unwrap().is_empty()
     }
     
     /// Get all discovered nodes
@@ -136,9 +168,13 @@ impl DiscoveryService {
             .collect()
     }
     
-    /// Get available rental nodes
+    /// Get available rental nodes, those that signed their advertisements first
+    ///
+    /// Nodes older than protocol v10 do not sign; they come last and carry no `public_key`,
+    /// which marks them as unverified.
     pub fn get_available_rentals(&self) -> Vec<NodeAdvertisement> {
-        self.discovered_nodes
+        let mut rentals: Vec<NodeAdvertisement> = self
+            .discovered_nodes
             .lock()
             .unwrap()
             .values()
@@ -146,19 +182,228 @@ impl DiscoveryService {
                 node.node_type == NodeType::Rental && node.status == NodeStatus::Available
             })
             .cloned()
+            .collect();
+        rentals.sort_by_key(|node| node.public_key.is_none());
+        rentals
+    }
+    
+    /// Estimated milliseconds a discovered node's clock runs ahead of ours
+    pub fn clock_offset(&self, node_id: &str) -> Option<i64> {
+        self.clock_offsets.lock().unwrap().get(node_id).copied()
+    }
+    
+    /// Discovered nodes whose clocks are further from ours than the warning threshold
+    pub fn drifting_nodes(&self) -> Vec<(String, i64)> {
+        let mut drifting: Vec<(String, i64)> = self
+            .clock_offsets
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, offset)| offset.abs() > clock::DRIFT_WARNING_MS)
+            .map(|(node_id, offset)| (node_id.clone(), *offset))
+            .collect();
+        drifting.sort();
+        drifting
+    }
+    
+    /// Overlay host names for all discovered nodes
+    pub fn host_entries(&self) -> Vec<names::HostEntry> {
+        self.discovered_nodes
+            .lock()
+            .unwrap()
+            .values()
+            .map(names::node_entry)
             .collect()
     }
     
-    /// Update local node status
+    /// Update local node status, republishing if it changed
     pub fn update_status(&mut self, status: NodeStatus) {
-        self.local_node.status = status;
-        self.local_node.timestamp = current_timestamp();
+        let mut local_node = self.local_node.lock().unwrap();
+        // Busy is set by `update_slots`, which clears it again once a slot frees up
+        let status = match (status, &local_node.status) {
+            (NodeStatus::Available, NodeStatus::Busy) => NodeStatus::Busy,
+            (status, _) => status,
+        };
+        if local_node.status == status {
+            return;
+        }
+        local_node.status = status;
+        local_node.timestamp = current_timestamp();
+        self.push();
     }
     
-    /// Update local node capabilities
+    /// Update local node capabilities and republish the advertisement right away
     pub fn update_capabilities(&mut self, capabilities: NodeCapabilities) {
-        self.local_node.capabilities = capabilities;
-        self.local_node.timestamp = current_timestamp();
+        let mut local_node = self.local_node.lock().unwrap();
+        local_node.capabilities = capabilities;
+        local_node.timestamp = current_timestamp();
+        self.push();
+    }
+    
+    /// Update the owner's description and notes, republishing if they changed
+    pub fn update_details(&mut self, details: NodeDetails) {
+        let mut local_node = self.local_node.lock().unwrap();
+        if local_node.details == details {
+            return;
+        }
+        local_node.details = details;
+        local_node.timestamp = current_timestamp();
+        self.push();
+    }
+    
+    /// Advertise the images a job can start warm from, republishing if they changed
+    pub fn update_warm_images(&mut self, warm_images: Vec<String>) {
+        let mut local_node = self.local_node.lock().unwrap();
+        if local_node.warm_images == warm_images {
+            return;
+        }
+        local_node.warm_images = warm_images;
+        local_node.timestamp = current_timestamp();
+        self.push();
+    }
+    
+    /// Advertise the reputation or deposit clients need, republishing if it changed
+    pub fn update_access(&mut self, access: AccessPolicy) {
+        let mut local_node = self.local_node.lock().unwrap();
+        if local_node.access == access {
+            return;
+        }
+        local_node.access = access;
+        local_node.timestamp = current_timestamp();
+        self.push();
+    }
+    
+    /// Advertise the node's free trial, or that it offers none, republishing if it changed
+    pub fn update_trial(&mut self, trial: Option<TrialOffer>) {
+        let mut local_node = self.local_node.lock().unwrap();
+        if local_node.trial == trial {
+            return;
+        }
+        local_node.trial = trial;
+        local_node.timestamp = current_timestamp();
+        self.push();
+    }
+    
+    /// Advertise what the last readiness check found, republishing if any check changed
+    pub fn update_readiness(&mut self, readiness: Readiness) {
+        let mut local_node = self.local_node.lock().unwrap();
+        let changed = local_node.readiness.checks().iter().zip(readiness.checks().iter()).any(|(old, new)| old != new);
+        local_node.readiness = readiness;
+        if !changed {
+            return;
+        }
+        local_node.timestamp = current_timestamp();
+        self.push();
+    }
+    
+    /// Advertise the GPU model and asking price for market statistics, republishing if they changed
+    pub fn update_listing(&mut self, gpu_model: Option<String>, price_per_hour: Option<f32>) {
+        let mut local_node = self.local_node.lock().unwrap();
+        if local_node.gpu_model == gpu_model && local_node.price_per_hour == price_per_hour {
+            return;
+        }
+        local_node.gpu_model = gpu_model;
+        local_node.price_per_hour = price_per_hour;
+        local_node.timestamp = current_timestamp();
+        self.push();
+    }
+    
+    /// Advertise free job slots; a node with none left shows as busy
+    pub fn update_slots(&mut self, free_slots: u32, max_concurrent_jobs: u32) {
+        let mut local_node = self.local_node.lock().unwrap();
+        let status = match local_node.status {
+            NodeStatus::Available | NodeStatus::Busy if free_slots == 0 => NodeStatus::Busy,
+            NodeStatus::Busy => NodeStatus::Available,
+            ref status => status.clone(),
+        };
+        if local_node.free_slots == Some(free_slots)
+            && local_node.capabilities.max_concurrent_jobs == max_concurrent_jobs
+            && local_node.status == status
+        {
+            return;
+        }
+        local_node.free_slots = Some(free_slots);
+        local_node.capabilities.max_concurrent_jobs = max_concurrent_jobs;
+        local_node.status = status;
+        local_node.timestamp = current_timestamp();
+        self.push();
+    }
+    
+    /// Hide the fields `privacy` asks for, or stop broadcasting, republishing if it changed
+    pub fn update_privacy(&mut self, privacy: AdvertisementPrivacy) {
+        let mut current = self.privacy.lock().unwrap();
+        if *current == privacy {
+            return;
+        }
+        *current = privacy;
+        self.push();
+    }
+    
+    /// Sign advertisements with `identity` rather than the key made for this run, so the node
+    /// keeps its key across restarts
+    pub fn set_identity(&mut self, identity: NodeIdentity) {
+        *self.identity.lock().unwrap() = identity;
+        self.push();
+    }
+    
+    /// Keep the keys nodes sign with in the file at `path`, so they stay pinned across restarts
+    pub fn keep_node_keys(&mut self, path: &Path) {
+        *self.node_keys.lock().unwrap() = KeyPins::load_from(path);
+    }
+    
+    /// Also announce and look up nodes through `backend`, e.g. a rendezvous on the coordinator
+    pub fn add_backend(&mut self, backend: Box<dyn DiscoveryBackend>) {
+        self.backends.lock().unwrap().push(backend);
+    }
+    
+    /// Public key a discovered node signs with, None for nodes that do not sign
+    pub fn node_key(&self, node_id: &str) -> Option<String> {
+        self.node_keys.lock().unwrap().get(node_id).cloned()
+    }
+    
+    /// Key to query nodes with that answer only those who know it
+    pub fn set_query_key(&mut self, query_key: Option<String>) {
+        self.query_key = query_key;
+    }
+    
+    /// Advertise new addresses after the overlay reassigned ours or the machine changed networks
+    ///
+    /// Besides the usual push, the new advertisement goes straight to every node we know, so
+    /// clients with sessions here learn the new endpoint even if broadcasts no longer reach them.
+    pub fn update_address(&mut self, ip_address: String, zerotier_ip: Option<String>) {
+        let mut local_node = self.local_node.lock().unwrap();
+        if local_node.ip_address == ip_address && local_node.zerotier_ip == zerotier_ip {
+            return;
+        }
+        local_node.ip_address = ip_address;
+        local_node.zerotier_ip = zerotier_ip;
+        local_node.timestamp = current_timestamp();
+        
+        let privacy = self.privacy.lock().unwrap();
+        // A node that only answers queries waits for its clients to ask again
+        if self.is_running() && privacy.broadcasts() {
+            if let Ok(data) = signed_packet(&self.identity.lock().unwrap(), &privacy.apply(&local_node)) {
+                for peer in self.discovered_nodes.lock().unwrap().values() {
+                    let ip = peer.zerotier_ip.as_deref().unwrap_or(&peer.ip_address);
+                    if let Ok(ip) = ip.parse::<IpAddr>() {
+                        let _ = self.socket.send_to(&data, SocketAddr::new(ip, DISCOVERY_PORT));
+                    }
+                }
+            }
+        }
+        drop(privacy);
+        self.push();
+    }
+    
+    /// Send the advertisement out now rather than at the next beat
+    fn push(&self) {
+        // Leaves a permit while the task is busy broadcasting, so the change is not missed
+        self.push.notify_one();
+    }
+    
+    /// The advertisement this node currently publishes, before privacy settings hide anything
+    pub fn local_advertisement(&self) -> NodeAdvertisement {
+        self.local_node.lock().unwrap().clone()
     }
     
     /// Manually discover nodes on ZeroTier network
@@ -167,7 +412,7 @@ impl DiscoveryService {
         
         // Get ZeroTier network members
         if let Ok(output) = tokio::process::Command::new("zerotier-cli")
-            .args(&["listpeers"])
+            .args(["listpeers"])
             .output()
             .await
         {
@@ -188,90 +433,191 @@ impl DiscoveryService {
         Ok(discovered)
     }
     
-    /// Start the advertisement thread
-    fn start_advertisement_thread(&self) {
-        let socket = Arc::clone(&self.socket);
-        let running = Arc::clone(&self.running);
+    /// The task that broadcasts our advertisement on every beat, and as soon as it changes
+    fn advertise(&self, socket: Arc<tokio::net::UdpSocket>, cancel: CancellationToken) -> impl Future<Output = ()> + Send + 'static {
         let multicast_addr = self.multicast_addr;
-        let mut local_node = self.local_node.clone();
+        let local_node = Arc::clone(&self.local_node);
+        let timing = self.timing;
+        let push = Arc::clone(&self.push);
+        let privacy = Arc::clone(&self.privacy);
+        let identity = Arc::clone(&self.identity);
         
-        thread::spawn(move || {
-            while *running.lock().unwrap() {
-                // Update timestamp
-                local_node.timestamp = current_timestamp();
+        async move {
+            loop {
+                // Asks chronyc, which may take a moment
+                let clock_offset_ms = tokio::task::spawn_blocking(|| clock::local_clock_status().offset_ms)
+                    .await
+                    .unwrap_or(None);
                 
-                // Serialize and broadcast advertisement
-                if let Ok(data) = bincode::serialize(&local_node) {
-                    let _ = socket.send_to(&data, multicast_addr);
-                    
-                    // Also try direct broadcast to common ZeroTier subnets
-                    for subnet in &["10.242.0.255:9999", "10.243.0.255:9999", "192.168.191.255:9999"] {
-                        if let Ok(addr) = subnet.parse::<SocketAddr>() {
-                            let _ = socket.send_to(&data, addr);
-                        }
+                // Update timestamp and broadcast the latest advertisement
+                let packet = {
+                    let mut local_node = local_node.lock().unwrap();
+                    local_node.timestamp = current_timestamp();
+                    local_node.clock_offset_ms = clock_offset_ms;
+                    let privacy = privacy.lock().unwrap();
+                    if privacy.broadcasts() {
+                        let shown = privacy.apply(&local_node);
+                        signed_packet(&identity.lock().unwrap(), &shown).ok().map(|data| (data, shown.zerotier_ip.is_some()))
+                    } else {
+                        None
                     }
+                };
+                if let Some((data, has_overlay)) = packet {
+                    broadcast_packet(&socket, multicast_addr, &data, has_overlay).await;
                 }
                 
-                thread::sleep(ADVERTISEMENT_INTERVAL);
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = tokio::time::sleep(timing.next_delay(timing::random_unit())) => {}
+                    _ = push.notified() => {
+                        // Let a burst of changes settle into one push
+                        tokio::select! {
+                            _ = cancel.cancelled() => break,
+                            _ = tokio::time::sleep(timing.min_push_gap()) => {}
+                        }
+                    }
+                }
             }
-        });
+        }
     }
     
-    /// Start the listener thread
-    fn start_listener_thread(&self) {
-        let socket = Arc::clone(&self.socket);
-        let running = Arc::clone(&self.running);
+    /// The task that lists the nodes we hear and answers probes
+    fn listen(&self, socket: Arc<tokio::net::UdpSocket>, cancel: CancellationToken) -> impl Future<Output = ()> + Send + 'static {
         let discovered_nodes = Arc::clone(&self.discovered_nodes);
-        let local_node_id = self.local_node.node_id.clone();
+        let clock_offsets = Arc::clone(&self.clock_offsets);
+        let local_node = Arc::clone(&self.local_node);
+        let local_node_id = local_node.lock().unwrap().node_id.clone();
+        let privacy = Arc::clone(&self.privacy);
+        let identity = Arc::clone(&self.identity);
+        let node_keys = Arc::clone(&self.node_keys);
         
-        thread::spawn(move || {
+        async move {
             let mut buffer = [0u8; 4096];
-            
-            // Set socket timeout for non-blocking behavior
-            socket.set_read_timeout(Some(Duration::from_millis(1000))).ok();
-            
-            while *running.lock().unwrap() {
-                match socket.recv_from(&mut buffer) {
-                    Ok((size, addr)) => {
-                        if let Ok(advertisement) = bincode::deserialize::<NodeAdvertisement>(&buffer[..size]) {
-                            // Don't add ourselves
-                            if advertisement.node_id != local_node_id {
-                                // Validate advertisement age
-                                if current_timestamp() - advertisement.timestamp < NODE_TIMEOUT.as_secs() {
-                                    discovered_nodes
-                                        .lock()
-                                        .unwrap()
-                                        .insert(advertisement.node_id.clone(), advertisement);
-                                }
-                            }
+            loop {
+                let (size, addr) = tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    received = socket.recv_from(&mut buffer) => match received {
+                        Ok(received) => received,
+                        // E.g. an ICMP error for an earlier send, continue
+                        Err(_) => continue,
+                    },
+                };
+                
+                #[cfg(feature = "chaos")]
+                if chaos::should_drop_discovery_packet() {
+                    continue;
+                }
+                
+                // Answer probes straight back to whoever asked, if they may see us
+                if buffer[..size].starts_with(privacy::QUERY_PREFIX.as_bytes()) {
+                    let reply = {
+                        let node = local_node.lock().unwrap().clone();
+                        let privacy = privacy.lock().unwrap();
+                        if privacy.answers(&buffer[..size], current_timestamp()) {
+                            signed_packet(&identity.lock().unwrap(), &privacy.apply(&node)).ok()
+                        } else {
+                            None
                         }
+                    };
+                    if let Some(data) = reply {
+                        let _ = socket.send_to(&data, addr).await;
                     }
-                    Err(_) => {
-                        // Timeout or other error, continue
+                    continue;
+                }
+                
+                if let Ok(advertisement) = decode_advertisement(&buffer[..size]) {
+                    // Don't add ourselves
+                    if advertisement.node_id != local_node_id {
+                        let offset_ms = clock::peer_offset_ms(advertisement.sent_at_ms, clock::now_ms());
+                        admit(advertisement, &buffer[..size], Some(offset_ms), &node_keys, &clock_offsets, &discovered_nodes);
                     }
                 }
             }
-        });
+        }
     }
     
-    /// Start the cleanup thread to remove stale nodes
-    fn start_cleanup_thread(&self) {
+    /// The task that announces us through the backends and lists the nodes they know
+    fn poll_backends(&self, cancel: CancellationToken) -> impl Future<Output = ()> + Send + 'static {
+        let backends = Arc::clone(&self.backends);
         let discovered_nodes = Arc::clone(&self.discovered_nodes);
-        let running = Arc::clone(&self.running);
+        let clock_offsets = Arc::clone(&self.clock_offsets);
+        let local_node = Arc::clone(&self.local_node);
+        let local_node_id = local_node.lock().unwrap().node_id.clone();
+        let privacy = Arc::clone(&self.privacy);
+        let identity = Arc::clone(&self.identity);
+        let node_keys = Arc::clone(&self.node_keys);
+        let timing = self.timing;
         
-        thread::spawn(move || {
-            while *running.lock().unwrap() {
-                let current_time = current_timestamp();
+        async move {
+            loop {
+                // Only rental nodes are listed beyond the LAN, and only if they broadcast at all
+                let packet = {
+                    let local_node = local_node.lock().unwrap();
+                    let privacy = privacy.lock().unwrap();
+                    if local_node.node_type == NodeType::Rental && privacy.broadcasts() {
+                        signed_packet(&identity.lock().unwrap(), &privacy.apply(&local_node)).ok()
+                    } else {
+                        None
+                    }
+                };
                 
-                discovered_nodes.lock().unwrap().retain(|_, node| {
-                    current_time - node.timestamp < NODE_TIMEOUT.as_secs()
-                });
+                // Backends make blocking HTTP calls
+                let round = {
+                    let (backends, discovered_nodes, clock_offsets, node_keys, local_node_id) = (
+                        Arc::clone(&backends),
+                        Arc::clone(&discovered_nodes),
+                        Arc::clone(&clock_offsets),
+                        Arc::clone(&node_keys),
+                        local_node_id.clone(),
+                    );
+                    tokio::task::spawn_blocking(move || {
+                        for backend in backends.lock().unwrap().iter() {
+                            if let Some(packet) = &packet {
+                                let _ = backend.announce(packet);
+                            }
+                            for data in backend.lookup().unwrap_or_default() {
+                                if let Ok(advertisement) = decode_advertisement(&data) {
+                                    if advertisement.node_id != local_node_id {
+                                        admit(advertisement, &data, None, &node_keys, &clock_offsets, &discovered_nodes);
+                                    }
+                                }
+                            }
+                        }
+                    })
+                };
+                let _ = round.await;
                 
-                thread::sleep(Duration::from_secs(60)); // Cleanup every minute
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = tokio::time::sleep(timing.next_delay(timing::random_unit())) => {}
+                }
             }
-        });
+        }
     }
     
+    /// The task that removes stale nodes
+    fn clean_up(&self, cancel: CancellationToken) -> impl Future<Output = ()> + Send + 'static {
+        let discovered_nodes = Arc::clone(&self.discovered_nodes);
+        let clock_offsets = Arc::clone(&self.clock_offsets);
+        
+        async move {
+            let mut every_minute = tokio::time::interval(Duration::from_secs(60));
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = every_minute.tick() => {}
+                }
+                let mut discovered_nodes = discovered_nodes.lock().unwrap();
+                let mut clock_offsets = clock_offsets.lock().unwrap();
+                discovered_nodes.retain(|node_id, node| {
+                    let offset_ms = clock_offsets.get(node_id).copied().unwrap_or(0);
+                    node_age(node, offset_ms) < NODE_TIMEOUT.as_secs()
+                });
+                clock_offsets.retain(|node_id, _| discovered_nodes.contains_key(node_id));
+            }
+        }
+    }
+
     /// Extract IP from ZeroTier line
     fn extract_ip_from_zerotier_line(&self, line: &str) -> Option<String> {
         // Parse ZeroTier CLI output to extract IP addresses
@@ -304,15 +650,16 @@ impl DiscoveryService {
         let addr = format!("{}:{}", ip, DISCOVERY_PORT);
         let socket = tokio::net::UdpSocket::bind("0.0.0.0:0").await?;
         
-        // Send discovery request
-        let request = b"DISCOVER";
-        socket.send_to(request, &addr).await?;
+        // Send discovery request, with proof of the key for nodes that only answer those who have it
+        let request = privacy::query(self.query_key.as_deref(), current_timestamp());
+        socket.send_to(request.as_bytes(), &addr).await?;
         
         // Wait for response with timeout
         let mut buffer = [0u8; 4096];
         match tokio::time::timeout(Duration::from_secs(5), socket.recv_from(&mut buffer)).await {
             Ok(Ok((size, _))) => {
-                let advertisement = bincode::deserialize::<NodeAdvertisement>(&buffer[..size])?;
+                let advertisement = decode_advertisement(&buffer[..size])?;
+                self.node_keys.lock().unwrap().check(&advertisement, &buffer[..size])?;
                 Ok(advertisement)
             }
             _ => Err("No response from node".into()),
@@ -320,6 +667,87 @@ impl DiscoveryService {
     }
 }
 
+/// Send `node`'s advertisement once, signed by `identity`, to discovery services on this
+/// machine, for nodes that run without one of their own, like the demo's
+pub fn announce_locally(identity: &NodeIdentity, node: &NodeAdvertisement) -> Result<(), String> {
+    let socket = UdpSocket::bind("127.0.0.1:0").map_err(|e| format!("Failed to open a discovery socket: {}", e))?;
+    let data = signed_packet(identity, node)?;
+    socket
+        .send_to(&data, SocketAddr::from(([127, 0, 0, 1], DISCOVERY_PORT)))
+        .map_err(|e| format!("Failed to announce {}: {}", node.node_id, e))?;
+    Ok(())
+}
+
+/// Discovery packet for `node`, stamped with the send time and signed by `identity`
+fn signed_packet(identity: &NodeIdentity, node: &NodeAdvertisement) -> Result<Vec<u8>, String> {
+    // Stamped at send time so receivers can estimate how far our clock is from theirs
+    let mut node = NodeAdvertisement { sent_at_ms: clock::now_ms(), ..node.clone() };
+    identity.sign(&mut node)?;
+    encode_advertisement(&node)
+}
+
+/// Send an advertisement packet to the multicast group and common ZeroTier subnets, or to the
+/// LAN broadcast address for a node without an overlay address
+async fn broadcast_packet(socket: &tokio::net::UdpSocket, multicast_addr: SocketAddr, data: &[u8], has_overlay: bool) {
+    let _ = socket.send_to(data, multicast_addr).await;
+    
+    // Switches that drop multicast still pass LAN broadcasts
+    if !has_overlay {
+        let _ = socket.send_to(data, SocketAddr::from(([255, 255, 255, 255], DISCOVERY_PORT))).await;
+        return;
+    }
+    
+    // Also try direct broadcast to common ZeroTier subnets
+    for subnet in &["10.242.0.255:9999", "10.243.0.255:9999", "192.168.191.255:9999"] {
+        if let Ok(addr) = subnet.parse::<SocketAddr>() {
+            let _ = socket.send_to(data, addr).await;
+        }
+    }
+}
+
+/// List `advertisement`, decoded from `packet`, if its signature holds and it is fresh;
+/// `offset_ms` is how far the sender's clock runs ahead of ours, known only for packets that
+/// came straight from it
+fn admit(
+    advertisement: NodeAdvertisement,
+    packet: &[u8],
+    offset_ms: Option<i64>,
+    node_keys: &Mutex<KeyPins>,
+    clock_offsets: &Mutex<HashMap<String, i64>>,
+    discovered_nodes: &Mutex<HashMap<String, NodeAdvertisement>>,
+) {
+    // Nor anyone posing as a node they are not
+    if node_keys.lock().unwrap().check(&advertisement, packet).is_err() {
+        return;
+    }
+    // Validate advertisement age on our clock, not the sender's
+    if node_age(&advertisement, offset_ms.unwrap_or(0)) >= NODE_TIMEOUT.as_secs() {
+        return;
+    }
+    
+    let mut discovered_nodes = discovered_nodes.lock().unwrap();
+    match offset_ms {
+        Some(offset_ms) => {
+            clock_offsets.lock().unwrap().insert(advertisement.node_id.clone(), offset_ms);
+        }
+        // A backend may hand back an older copy of what we heard directly
+        None if discovered_nodes
+            .get(&advertisement.node_id)
+            .is_some_and(|known| known.timestamp > advertisement.timestamp) =>
+        {
+            return;
+        }
+        None => {}
+    }
+    discovered_nodes.insert(advertisement.node_id.clone(), advertisement);
+}
+
+/// Seconds since a node advertised, on our clock, given how far its clock runs ahead of ours
+fn node_age(node: &NodeAdvertisement, offset_ms: i64) -> u64 {
+    let local_timestamp = node.timestamp as i64 - offset_ms / 1000;
+    (current_timestamp() as i64 - local_timestamp).max(0) as u64
+}
+
 /// Get current timestamp in seconds
 fn current_timestamp() -> u64 {
     SystemTime::now()
@@ -347,6 +775,20 @@ pub fn create_rental_advertisement(
         status: NodeStatus::Available,
         timestamp: current_timestamp(),
         network_id,
+        carbon_intensity_g_per_kwh: None,
+        avg_job_kwh: None,
+        details: NodeDetails::default(),
+        free_slots: None,
+        sent_at_ms: clock::now_ms(),
+        clock_offset_ms: None,
+        warm_images: vec![],
+        gpu_model: None,
+        price_per_hour: None,
+        access: AccessPolicy::default(),
+        readiness: Readiness::default(),
+        trial: None,
+        public_key: None,
+        signature: None,
     }
 }
 
@@ -374,10 +816,26 @@ pub fn create_client_advertisement(
             supports_docker: false,
             supports_gpu: false,
             max_concurrent_jobs: 0,
+            gpu_stack: None,
+            hardware: HardwareProfile::default(),
         },
         status: NodeStatus::Available,
         timestamp: current_timestamp(),
         network_id,
+        carbon_intensity_g_per_kwh: None,
+        avg_job_kwh: None,
+        details: NodeDetails::default(),
+        free_slots: None,
+        sent_at_ms: clock::now_ms(),
+        clock_offset_ms: None,
+        warm_images: vec![],
+        gpu_model: None,
+        price_per_hour: None,
+        access: AccessPolicy::default(),
+        readiness: Readiness::default(),
+        trial: None,
+        public_key: None,
+        signature: None,
     }
 }
 
@@ -397,6 +855,8 @@ mod tests {
             supports_docker: true,
             supports_gpu: true,
             max_concurrent_jobs: 4,
+            gpu_stack: None,
+            hardware: HardwareProfile::default(),
         };
         
         let advertisement = create_rental_advertisement(
@@ -407,11 +867,29 @@ mod tests {
             "363c67c55ad2489d".to_string(),
         );
         
-        let serialized = bincode::serialize(&advertisement).unwrap();
-        let deserialized: NodeAdvertisement = bincode::deserialize(&serialized).unwrap();
+        let serialized = encode_advertisement(&advertisement).unwrap();
+        let deserialized = decode_advertisement(&serialized).unwrap();
         
         assert_eq!(advertisement.node_id, deserialized.node_id);
         assert_eq!(advertisement.node_type, deserialized.node_type);
         assert_eq!(advertisement.ip_address, deserialized.ip_address);
     }
+    
+    #[tokio::test]
+    async fn test_shutdown() {
+        let advertisement = create_client_advertisement("client-1".to_string(), "127.0.0.1".to_string(), None, String::new());
+        let service = DiscoveryService::new(advertisement).unwrap();
+        service.start().unwrap();
+        assert!(service.is_running());
+        tokio::time::timeout(Duration::from_secs(5), service.shutdown()).await.unwrap();
+        assert!(!service.is_running());
+        
+        // And again, as the GUIs do when renting is switched off and on
+        service.start().unwrap();
+        tokio::time::timeout(Duration::from_secs(5), service.shutdown()).await.unwrap();
+        
+        // Off a runtime there is nothing to run the tasks on
+        let started = std::thread::spawn(move || service.start().map_err(|e| e.to_string())).join().unwrap();
+        assert!(started.unwrap_err().contains("tokio runtime"));
+    }
 }
@@ -0,0 +1,335 @@
+//! Coordinator HTTP endpoint
+//! A coordinator serves `MarketStats` of the rental nodes it discovers over plain HTTP: JSON at
+//! /api/stats for the GUIs and a small network status page at /status for people. Abuse reports
+//! are posted to /api/reports and the resulting blocklist is served at /api/blocklist. New nodes
+//! register at /api/nodes and owners claim them at /api/claims, see `registration`. Owners share
+//! a node's status read-only through observer tokens, see `observers`. Clients have jobs watched
+//! for alerts on their phones, see `push`. Every request counts against the caller's quota, see
+//! `quota`. Mirrors answer /api/health so clients can fail over, see `coordinators`. Nodes beyond
+//! the coordinator's LAN are found through /api/rendezvous, see `rendezvous`. Sealed credential
+//! handoffs wait at /api/handoffs for their redeemer, see `handoffs`.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::abuse::{self, ReportStore};
+use crate::coordinators;
+use crate::handoffs::{self, HandoffRelay};
+use crate::observers::{self, ObserverStore};
+use crate::push::{self, PushStore};
+use crate::quota::{self, Quotas};
+use crate::registration::{self, RegistrationStore};
+use crate::rendezvous::{self, RendezvousStore};
+use crate::NodeAdvertisement;
+
+pub use eryzaa_protocol::market::UNKNOWN_MODEL;
+pub use eryzaa_protocol::MarketStats;
+
+/// Environment variable with the coordinator's base URL, e.g. http://10.242.0.1:8090, or
+/// several mirrored ones separated by commas, see `coordinators`
+pub const COORDINATOR_URL_ENV: &str = "ERYZAA_COORDINATOR_URL";
+
+pub const DEFAULT_PORT: u16 = 8090;
+
+const STATS_PATH: &str = "/api/stats";
+const TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_BODY: usize = 1 << 20; // Evidence is a few receipts and ledgers, never megabytes
+const MAX_CONNECTIONS: usize = 64; // Answered at once; more wait to be accepted
+
+/// What the coordinator keeps on disk, one JSON file each
+#[derive(Debug, Clone)]
+pub struct CoordinatorStores {
+    pub reports: ReportStore,
+    pub registrations: RegistrationStore,
+    pub observers: ObserverStore,
+    pub pushes: PushStore,
+    pub rendezvous: RendezvousStore, // In memory only; nodes post again on every beat
+    pub handoffs: HandoffRelay,
+}
+
+/// Answer requests on `listener` with freshly computed stats; a bad request only drops its connection
+///
+/// `node` looks up the latest advertisement of a node, for its observers. Each connection is
+/// answered on its own thread, up to `MAX_CONNECTIONS` at once, so a slow client holds up no one
+/// else.
+pub fn serve(
+    listener: TcpListener,
+    stats: impl Fn() -> MarketStats + Sync,
+    node: impl Fn(&str) -> Option<NodeAdvertisement> + Sync,
+    stores: &CoordinatorStores,
+    quotas: &Quotas,
+) {
+    // The stores read, change and write back whole files, so requests that change them go one at a time
+    let writes = Mutex::new(());
+    let active = (Mutex::new(0), Condvar::new());
+    std::thread::scope(|scope| {
+        for stream in listener.incoming().flatten() {
+            let (count, freed) = &active;
+            let mut running = freed.wait_while(count.lock().unwrap(), |running| *running >= MAX_CONNECTIONS).unwrap();
+            *running += 1;
+            drop(running);
+            let (stats, node, writes, active) = (&stats, &node, &writes, &active);
+            scope.spawn(move || {
+                let _ = respond(stream, stats, node, stores, quotas, writes);
+                let (count, freed) = active;
+                *count.lock().unwrap() -= 1;
+                freed.notify_one();
+            });
+        }
+    });
+}
+
+fn respond(
+    mut stream: TcpStream,
+    stats: &impl Fn() -> MarketStats,
+    node: &impl Fn(&str) -> Option<NodeAdvertisement>,
+    stores: &CoordinatorStores,
+    quotas: &Quotas,
+    writes: &Mutex<()>,
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let caller = stream.peer_addr()?.ip().to_string();
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    let mut content_length = 0;
+    let mut authorization = String::new();
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            } else if name.eq_ignore_ascii_case("authorization") {
+                authorization = value.trim().to_string();
+            }
+        }
+    }
+
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs_f64();
+    if let Err(retry_after) = quotas.take(&caller, now) {
+        let body = format!("Too many requests, retry in {} s\n", retry_after);
+        return write!(
+            stream,
+            "HTTP/1.1 429 Too Many Requests\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nRetry-After: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            retry_after,
+            body
+        );
+    }
+
+    let mut body = vec![0; content_length.min(MAX_BODY)];
+    reader.read_exact(&mut body)?;
+    let body = String::from_utf8_lossy(&body);
+
+    let _writing = (!request_line.starts_with("GET ")).then(|| writes.lock().unwrap_or_else(PoisonError::into_inner));
+    let (status, content_type, body) = match request_line.split_whitespace().collect::<Vec<_>>()[..] {
+        [method, path, ..] if path.split('?').next() == Some(quota::ADMIN_PATH) => quota::admin(method, &authorization, &body, quotas),
+        [method, path, ..] if observers::handles(path) => {
+            observers::route(method, path, &body, &stores.observers, &stores.registrations, node)
+        }
+        [method, path, ..] if push::handles(path) => push::route(method, path, &body, &stores.pushes),
+        [method, path, ..] if rendezvous::handles(path) => {
+            rendezvous::route(method, &body, &caller, &stores.rendezvous, &stores.registrations)
+        }
+        [method, path, ..] if handoffs::handles(path) => handoffs::route(method, path, &body, &stores.handoffs),
+        _ => route(&request_line, &body, stats, &stores.reports, &stores.registrations),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// Status, content type and body for a request line such as "GET /api/stats HTTP/1.1"
+pub fn route(
+    request_line: &str,
+    body: &str,
+    stats: &impl Fn() -> MarketStats,
+    reports: &ReportStore,
+    registrations: &RegistrationStore,
+) -> (&'static str, &'static str, String) {
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next();
+    let path = parts.next().map(|target| target.split('?').next().unwrap_or(target));
+    match (method, path) {
+        (Some("GET"), Some(coordinators::HEALTH_PATH)) => ("200 OK", "text/plain", "ok\n".to_string()),
+        (Some("GET"), Some(STATS_PATH)) => (
+            "200 OK",
+            "application/json",
+            serde_json::to_string(&stats()).unwrap_or_default(),
+        ),
+        (Some("GET"), Some(abuse::BLOCKLIST_PATH)) => (
+            "200 OK",
+            "application/json",
+            serde_json::to_string(&reports.blocklist(crate::current_timestamp())).unwrap_or_default(),
+        ),
+        (Some("POST"), Some(abuse::REPORTS_PATH)) => match abuse::accept(body, reports) {
+            Ok(message) => ("201 Created", "text/plain", message + "\n"),
+            Err(e) => ("400 Bad Request", "text/plain", e + "\n"),
+        },
+        (Some("POST"), Some(registration::NODES_PATH)) => match registration::accept_registration(body, registrations) {
+            Ok(receipt) => ("201 Created", "application/json", receipt),
+            Err(e) => ("400 Bad Request", "text/plain", e + "\n"),
+        },
+        (Some("POST"), Some(registration::CLAIMS_PATH)) => match registration::accept_claim(body, registrations) {
+            Ok(node) => ("200 OK", "application/json", node),
+            Err(e) => ("400 Bad Request", "text/plain", e + "\n"),
+        },
+        (Some("GET"), Some("/" | "/status")) => ("200 OK", "text/html; charset=utf-8", status_page(&stats())),
+        (Some("GET" | "POST"), _) => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain", "Only GET and POST are supported\n".to_string()),
+    }
+}
+
+/// Public network status page
+pub fn status_page(stats: &MarketStats) -> String {
+    let price = |price: Option<f32>| price.map(|p| format!("{:.2} AVAX/h", p)).unwrap_or_else(|| "-".to_string());
+    let mut rows = String::new();
+    for (model, gpus) in &stats.gpus_by_model {
+        rows.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape(model),
+            gpus,
+            price(stats.median_price_by_model.get(model).copied())
+        ));
+    }
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><meta http-equiv=\"refresh\" content=\"60\"><title>Eryzaa network status</title></head><body>\n\
+         <h1>Eryzaa network status</h1>\n\
+         <p>{} nodes ({} available), {} GPUs, median price {}, utilization {}</p>\n\
+         <table><tr><th>GPU</th><th>Count</th><th>Median price</th></tr>\n{}</table>\n</body></html>\n",
+        stats.total_nodes,
+        stats.available_nodes,
+        stats.total_gpus,
+        price(stats.median_price_per_hour),
+        stats.utilization.map(|u| format!("{:.0}%", u * 100.0)).unwrap_or_else(|| "-".to_string()),
+        rows
+    )
+}
+
+/// Stats from a coordinator at `base_url` ("http://host:port")
+pub fn fetch_stats(base_url: &str) -> Result<MarketStats, String> {
+    let body = request(base_url, "GET", STATS_PATH, None, None)?;
+    serde_json::from_str(&body).map_err(|e| format!("Failed to parse coordinator stats: {}", e))
+}
+
+/// Body of a successful request to the coordinator at `base_url`, with an optional bearer token
+pub(crate) fn request(base_url: &str, method: &str, path: &str, body: Option<&str>, token: Option<&str>) -> Result<String, String> {
+    let authority = base_url
+        .trim()
+        .trim_start_matches("http://")
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    let address = match authority.contains(':') {
+        true => authority.to_string(),
+        false => format!("{}:80", authority),
+    };
+    let addr = address
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or_else(|| format!("Cannot resolve coordinator {}", base_url))?;
+
+    let error = |e: std::io::Error| format!("Failed to reach coordinator {}: {}", base_url, e);
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT).map_err(error)?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(error)?;
+    let body = body.unwrap_or_default();
+    let authorization = token.map(|token| format!("Authorization: Bearer {}\r\n", token)).unwrap_or_default();
+    write!(
+        stream,
+        "{} {} HTTP/1.0\r\nHost: {}\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        authority,
+        authorization,
+        body.len(),
+        body
+    )
+    .map_err(error)?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).map_err(error)?;
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| format!("Malformed response from coordinator {}", base_url))?;
+    let status = head.lines().next().unwrap_or_default();
+    if !status.contains(" 200 ") && !status.contains(" 201 ") {
+        return Err(format!("Coordinator {} answered {}: {}", base_url, status, body.trim()));
+    }
+    Ok(body.to_string())
+}
+
+/// Whether `error` from `request` means the coordinator is down or overloaded, rather than refusing
+pub(crate) fn is_unavailable(error: &str) -> bool {
+    let status = error.split_once(" answered ").map(|(_, rest)| rest.split(':').next().unwrap_or_default());
+    match status {
+        Some(status) => status.contains(" 5") || status.contains(" 429 "),
+        None => error.starts_with("Cannot resolve") || error.starts_with("Failed to reach") || error.starts_with("Malformed response"),
+    }
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_endpoint() {
+        let stats = || MarketStats {
+            total_nodes: 2,
+            gpus_by_model: [("RTX <3090>".to_string(), 3)].into(),
+            median_price_per_hour: Some(2.5),
+            ..Default::default()
+        };
+        let reports = ReportStore::new(std::env::temp_dir().join(format!("eryzaa-market-{}.json", std::process::id())));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let quota_path = std::env::temp_dir().join(format!("eryzaa-market-quotas-{}.json", std::process::id()));
+        let config = quota::QuotaConfig { default: quota::QuotaLimits { per_minute: 1.0, burst: 2.0 }, ..Default::default() };
+        let quotas = Quotas::new(&quota_path, config, "s3cret".to_string());
+        let registrations = RegistrationStore::new(std::env::temp_dir().join(format!("eryzaa-market-nodes-{}.json", std::process::id())));
+        let observers = ObserverStore::new(std::env::temp_dir().join(format!("eryzaa-market-observers-{}.json", std::process::id())));
+        let pushes = PushStore::new(std::env::temp_dir().join(format!("eryzaa-market-push-{}.json", std::process::id())));
+        let stores = CoordinatorStores {
+            reports: reports.clone(),
+            registrations: registrations.clone(),
+            observers,
+            pushes,
+            rendezvous: RendezvousStore::default(),
+            handoffs: HandoffRelay::new(std::env::temp_dir().join(format!("eryzaa-market-handoffs-{}.json", std::process::id()))),
+        };
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || serve(listener, stats, |_| None, &stores, &quotas));
+
+        // A client that connects and says nothing holds up no one else
+        let _idle = TcpStream::connect(address).unwrap();
+        let started = std::time::Instant::now();
+        assert_eq!(fetch_stats(&url).unwrap(), stats());
+        assert!(started.elapsed() < TIMEOUT);
+        assert_eq!(quota::fetch_quotas(&url, "s3cret").unwrap().default.burst, 2.0);
+        let limited = fetch_stats(&url).unwrap_err();
+        assert!(limited.contains("429 Too Many Requests") && limited.contains("retry in 60 s"), "{}", limited);
+        assert_eq!(route("GET /nope HTTP/1.1", "", &stats, &reports, &registrations).0, "404 Not Found");
+        assert_eq!(route("DELETE /api/stats HTTP/1.1", "", &stats, &reports, &registrations).0, "405 Method Not Allowed");
+        assert_eq!(route("POST /api/reports HTTP/1.1", "{}", &stats, &reports, &registrations).0, "400 Bad Request");
+        assert_eq!(route("POST /api/claims HTTP/1.1", "{}", &stats, &reports, &registrations).0, "400 Bad Request");
+        let (_, content_type, page) = route("GET /status HTTP/1.1", "", &stats, &reports, &registrations);
+        assert_eq!(content_type, "text/html; charset=utf-8");
+        assert!(page.contains("<td>RTX &lt;3090&gt;</td><td>3</td><td>-</td>"));
+        assert!(page.contains("2 nodes (0 available), 0 GPUs, median price 2.50 AVAX/h"));
+    }
+}
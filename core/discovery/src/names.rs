@@ -0,0 +1,181 @@
+//! Overlay host names for nodes and jobs
+//! `<node-id>.eryzaa` and `<job-id>.jobs.eryzaa` are kept in a managed block of the
+//! system hosts file so every tool on the machine can resolve them.
+
+use std::collections::HashMap;
+use std::net::ToSocketAddrs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::NodeAdvertisement;
+
+pub const NODE_DOMAIN: &str = "eryzaa";
+pub const JOB_DOMAIN: &str = "jobs.eryzaa";
+
+const BLOCK_BEGIN: &str = "# BEGIN eryzaa";
+const BLOCK_END: &str = "# END eryzaa";
+
+/// How long a name lookup is trusted before `preferred_host` asks the resolver again
+const LOOKUP_TTL: Duration = Duration::from_secs(60);
+
+/// Whether a (name, address) pair resolved, and when that was checked
+type Lookups = HashMap<(String, String), (bool, Instant)>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostEntry {
+    pub name: String,
+    pub ip: String,
+}
+
+/// DNS name of a node on the overlay
+pub fn node_hostname(node_id: &str) -> String {
+    format!("{}.{}", sanitize_label(node_id), NODE_DOMAIN)
+}
+
+/// DNS name of a job on the overlay
+pub fn job_hostname(job_id: &str) -> String {
+    format!("{}.{}", sanitize_label(job_id), JOB_DOMAIN)
+}
+
+/// Host entry for a discovered node, preferring its overlay address
+pub fn node_entry(node: &NodeAdvertisement) -> HostEntry {
+    HostEntry {
+        name: node_hostname(&node.node_id),
+        ip: node.zerotier_ip.clone().unwrap_or_else(|| node.ip_address.clone()),
+    }
+}
+
+/// Use `name` if it currently resolves, otherwise fall back to the raw address
+///
+/// Lookups block, so each answer is remembered for `LOOKUP_TTL`.
+pub fn preferred_host(name: &str, ip: &str) -> String {
+    static LOOKUPS: OnceLock<Mutex<Lookups>> = OnceLock::new();
+    let key = (name.to_string(), ip.to_string());
+    let cached = LOOKUPS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .get(&key)
+        .filter(|(_, at)| at.elapsed() < LOOKUP_TTL)
+        .map(|(resolves, _)| *resolves);
+
+    let resolves = cached.unwrap_or_else(|| {
+        let resolves = (name, 22)
+            .to_socket_addrs()
+            .map(|mut addrs| addrs.any(|addr| addr.ip().to_string() == ip))
+            .unwrap_or(false);
+        LOOKUPS.get_or_init(Default::default).lock().unwrap().insert(key, (resolves, Instant::now()));
+        resolves
+    });
+
+    if resolves {
+        name.to_string()
+    } else {
+        ip.to_string()
+    }
+}
+
+/// `nodes` followed by those of `jobs` whose names no node entry already has
+///
+/// Discovered nodes come first, as their addresses are fresher than those recorded with jobs.
+pub fn merge_entries(nodes: Vec<HostEntry>, jobs: impl IntoIterator<Item = HostEntry>) -> Vec<HostEntry> {
+    let mut entries = nodes;
+    for entry in jobs {
+        if !entries.iter().any(|known| known.name == entry.name) {
+            entries.push(entry);
+        }
+    }
+    entries
+}
+
+/// Location of the system hosts file
+pub fn system_hosts_path() -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from(r"C:\Windows\System32\drivers\etc\hosts")
+    } else {
+        PathBuf::from("/etc/hosts")
+    }
+}
+
+/// Replace the managed eryzaa block of a hosts file with `entries`
+pub fn render_hosts(existing: &str, entries: &[HostEntry]) -> String {
+    let mut output = String::new();
+    let mut in_block = false;
+
+    for line in existing.lines() {
+        if line.trim() == BLOCK_BEGIN {
+            in_block = true;
+            continue;
+        }
+        if line.trim() == BLOCK_END {
+            in_block = false;
+            continue;
+        }
+        if !in_block {
+            output.push_str(line);
+            output.push('\n');
+        }
+    }
+
+    if !entries.is_empty() {
+        output.push_str(BLOCK_BEGIN);
+        output.push('\n');
+        for entry in entries {
+            output.push_str(&format!("{}\t{}\n", entry.ip, entry.name));
+        }
+        output.push_str(BLOCK_END);
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Write `entries` into the managed block of the hosts file at `path`
+pub fn sync_hosts_file(path: &Path, entries: &[HostEntry]) -> Result<(), String> {
+    let existing = std::fs::read_to_string(path).unwrap_or_default();
+    let updated = render_hosts(&existing, entries);
+
+    if updated != existing {
+        std::fs::write(path, updated)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+
+    Ok(())
+}
+
+fn sanitize_label(id: &str) -> String {
+    id.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c.to_ascii_lowercase() } else { '-' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hostnames() {
+        assert_eq!(node_hostname("Node_1"), "node-1.eryzaa");
+        assert_eq!(job_hostname("abc123"), "abc123.jobs.eryzaa");
+    }
+
+    #[test]
+    fn test_render_hosts_replaces_managed_block() {
+        let existing = "127.0.0.1\tlocalhost\n# BEGIN eryzaa\n10.242.0.1\told.eryzaa\n# END eryzaa\n";
+        let entries = vec![HostEntry { name: node_hostname("n1"), ip: "10.242.0.2".to_string() }];
+
+        let rendered = render_hosts(existing, &entries);
+        assert_eq!(rendered, "127.0.0.1\tlocalhost\n# BEGIN eryzaa\n10.242.0.2\tn1.eryzaa\n# END eryzaa\n");
+        assert_eq!(render_hosts(&rendered, &[]), "127.0.0.1\tlocalhost\n");
+    }
+
+    #[test]
+    fn test_merge_prefers_discovered_nodes() {
+        let entry = |name: &str, ip: &str| HostEntry { name: name.to_string(), ip: ip.to_string() };
+        let nodes = vec![entry("n1.eryzaa", "10.242.0.9")];
+        let jobs = vec![entry("abc.jobs.eryzaa", "10.242.0.2"), entry("n1.eryzaa", "10.242.0.2")];
+
+        assert_eq!(merge_entries(nodes, jobs), vec![entry("n1.eryzaa", "10.242.0.9"), entry("abc.jobs.eryzaa", "10.242.0.2")]);
+    }
+}
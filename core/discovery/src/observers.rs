@@ -0,0 +1,385 @@
+//! Read-only observer tokens
+//! The owner of a claimed node can mint observer tokens to share the node's status with people
+//! who must not control it, e.g. a "my rig's availability" widget on their website. A token shows
+//! the node's status, utilization and pricing and nothing else: as JSON at /api/observe/<token>
+//! and as a small page to embed at /observe/<token>. Tokens last until the owner revokes them.
+//! Minting and revoking take the owner secret the claim issued, see `registration`.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::market;
+use crate::registration::RegistrationStore;
+use crate::{NodeAdvertisement, NodeStatus};
+
+pub const OBSERVERS_PATH: &str = "/api/observers";
+pub const REVOKE_PATH: &str = "/api/observers/revoke";
+pub const STATUS_PATH_PREFIX: &str = "/api/observe/";
+pub const PAGE_PATH_PREFIX: &str = "/observe/";
+
+/// Tokens a node can have at once, revoked ones not counted
+const MAX_TOKENS_PER_NODE: usize = 20;
+
+/// An observer token as the coordinator keeps it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ObserverToken {
+    pub token: String,
+    pub node_id: String,
+    pub owner: String,
+    #[serde(default)]
+    pub label: String, // Shown instead of the node's id or hostname
+    pub created_at: u64, // Unix seconds
+    #[serde(default)]
+    pub revoked_at: Option<u64>,
+}
+
+/// An owner asking for a token to share one of their nodes
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MintRequest {
+    pub node_id: String,
+    pub owner: String,
+    #[serde(default)]
+    pub owner_secret: String,
+    #[serde(default)]
+    pub label: String,
+}
+
+/// An owner taking a token back
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RevokeRequest {
+    pub token: String,
+    pub owner: String,
+    #[serde(default)]
+    pub owner_secret: String,
+}
+
+/// Everything an observer gets to see of a node
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ObservedStatus {
+    pub label: String,
+    pub status: NodeStatus, // Offline when the coordinator has not heard from the node lately
+    #[serde(default)]
+    pub utilization: Option<f32>, // Share of job slots in use, 0-1
+    #[serde(default)]
+    pub free_slots: Option<u32>,
+    #[serde(default)]
+    pub price_per_hour: Option<f32>, // AVAX
+    #[serde(default)]
+    pub gpu_model: Option<String>,
+    #[serde(default)]
+    pub gpu_count: u32,
+    #[serde(default)]
+    pub last_seen: Option<u64>, // Unix seconds of the node's last advertisement
+}
+
+impl ObservedStatus {
+    /// What `advertisement` shows an observer; without one the node counts as offline
+    pub fn from_advertisement(label: &str, advertisement: Option<&NodeAdvertisement>) -> Self {
+        let Some(node) = advertisement else {
+            return Self {
+                label: label.to_string(),
+                status: NodeStatus::Offline,
+                utilization: None,
+                free_slots: None,
+                price_per_hour: None,
+                gpu_model: None,
+                gpu_count: 0,
+                last_seen: None,
+            };
+        };
+        Self {
+            label: label.to_string(),
+            status: node.status.clone(),
+            utilization: node
+                .slots()
+                .and_then(|(free, max)| (max > 0).then(|| max.saturating_sub(free) as f32 / max as f32)),
+            free_slots: node.free_slots,
+            price_per_hour: node.price_per_hour,
+            gpu_model: node.gpu_model.clone(),
+            gpu_count: node.capabilities.gpu_count,
+            last_seen: Some(node.timestamp),
+        }
+    }
+}
+
+/// Observer tokens, kept in one JSON file
+#[derive(Debug, Clone)]
+pub struct ObserverStore {
+    path: PathBuf,
+}
+
+impl ObserverStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Issue a token for a node that `request.owner` has claimed and holds the owner secret of
+    pub fn mint(&self, request: &MintRequest, registrations: &RegistrationStore, now: u64) -> Result<ObserverToken, String> {
+        let owner = request.owner.trim();
+        let registered = registrations
+            .nodes()
+            .into_iter()
+            .find(|node| node.registration.node_id == request.node_id)
+            .ok_or_else(|| format!("No node {} is registered", request.node_id))?;
+        if owner.is_empty() || registered.owner.as_deref() != Some(owner) {
+            return Err(format!("Node {} is not claimed by {}", request.node_id, owner));
+        }
+        if !registered.owned_by(owner, &request.owner_secret) {
+            return Err(format!("Wrong owner secret for node {}", request.node_id));
+        }
+
+        let mut tokens = self.load();
+        let live = tokens
+            .iter()
+            .filter(|token| token.node_id == request.node_id && token.revoked_at.is_none())
+            .count();
+        if live >= MAX_TOKENS_PER_NODE {
+            return Err(format!("Node {} has {} observer tokens already; revoke one first", request.node_id, live));
+        }
+        let label = match request.label.trim() {
+            "" => registered.registration.hostname.clone(),
+            label => label.to_string(),
+        };
+        let token = ObserverToken {
+            token: uuid::Uuid::new_v4().simple().to_string(),
+            node_id: request.node_id.clone(),
+            owner: owner.to_string(),
+            label,
+            created_at: now,
+            revoked_at: None,
+        };
+        tokens.push(token.clone());
+        self.save(&tokens)?;
+        Ok(token)
+    }
+
+    /// Stop `request.token` from working; only the owner who minted it can
+    pub fn revoke(&self, request: &RevokeRequest, registrations: &RegistrationStore, now: u64) -> Result<ObserverToken, String> {
+        let nodes = registrations.nodes();
+        let mut tokens = self.load();
+        let token = tokens
+            .iter_mut()
+            .find(|token| token.token == request.token && token.owner == request.owner.trim())
+            .filter(|token| {
+                nodes
+                    .iter()
+                    .any(|node| node.registration.node_id == token.node_id && node.owned_by(&request.owner, &request.owner_secret))
+            })
+            .ok_or_else(|| "No such observer token for this owner".to_string())?;
+        token.revoked_at.get_or_insert(now);
+        let revoked = token.clone();
+        self.save(&tokens)?;
+        Ok(revoked)
+    }
+
+    /// The token `token`, unless it was revoked
+    pub fn find(&self, token: &str) -> Option<ObserverToken> {
+        self.load().into_iter().find(|entry| entry.token == token && entry.revoked_at.is_none())
+    }
+
+    pub fn tokens(&self) -> Vec<ObserverToken> {
+        self.load()
+    }
+
+    fn load(&self) -> Vec<ObserverToken> {
+        std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, tokens: &[ObserverToken]) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(tokens)
+            .map_err(|e| format!("Failed to serialize observer tokens: {}", e))?;
+        std::fs::write(&self.path, content)
+            .map_err(|e| format!("Failed to write observer tokens {}: {}", self.path.display(), e))
+    }
+}
+
+/// True for the paths `route` answers
+pub fn handles(path: &str) -> bool {
+    path.starts_with(OBSERVERS_PATH) || path.starts_with(STATUS_PATH_PREFIX) || path.starts_with(PAGE_PATH_PREFIX)
+}
+
+/// Status, content type and body for an observer request; `node` looks up a node's latest advertisement
+pub fn route(
+    method: &str,
+    path: &str,
+    body: &str,
+    store: &ObserverStore,
+    registrations: &RegistrationStore,
+    node: &impl Fn(&str) -> Option<NodeAdvertisement>,
+) -> (&'static str, &'static str, String) {
+    let path = path.split('?').next().unwrap_or(path);
+    let observed = |token: &str| {
+        store
+            .find(token)
+            .map(|token| ObservedStatus::from_advertisement(&token.label, node(&token.node_id).as_ref()))
+    };
+    match (method, path) {
+        ("POST", OBSERVERS_PATH) => match serde_json::from_str::<MintRequest>(body) {
+            Ok(request) => match store.mint(&request, registrations, crate::current_timestamp()) {
+                Ok(token) => ("201 Created", "application/json", serde_json::to_string(&token).unwrap_or_default()),
+                Err(e) => ("403 Forbidden", "text/plain", e + "\n"),
+            },
+            Err(e) => ("400 Bad Request", "text/plain", format!("Malformed observer token request: {}\n", e)),
+        },
+        ("POST", REVOKE_PATH) => match serde_json::from_str::<RevokeRequest>(body) {
+            Ok(request) => match store.revoke(&request, registrations, crate::current_timestamp()) {
+                Ok(token) => ("200 OK", "text/plain", format!("Revoked observer token for {}\n", token.label)),
+                Err(e) => ("404 Not Found", "text/plain", e + "\n"),
+            },
+            Err(e) => ("400 Bad Request", "text/plain", format!("Malformed revocation: {}\n", e)),
+        },
+        ("GET", _) if path.starts_with(STATUS_PATH_PREFIX) => match observed(&path[STATUS_PATH_PREFIX.len()..]) {
+            Some(status) => ("200 OK", "application/json", serde_json::to_string(&status).unwrap_or_default()),
+            None => ("404 Not Found", "text/plain", "Unknown or revoked observer token\n".to_string()),
+        },
+        ("GET", _) if path.starts_with(PAGE_PATH_PREFIX) => match observed(&path[PAGE_PATH_PREFIX.len()..]) {
+            Some(status) => ("200 OK", "text/html; charset=utf-8", widget_page(&status)),
+            None => ("404 Not Found", "text/plain", "Unknown or revoked observer token\n".to_string()),
+        },
+        ("GET" | "POST", _) => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain", "Only GET and POST are supported\n".to_string()),
+    }
+}
+
+/// Availability card small enough for an iframe on the owner's website
+pub fn widget_page(status: &ObservedStatus) -> String {
+    let (state, color) = match status.status {
+        NodeStatus::Available => ("Available", "#2e7d32"),
+        NodeStatus::Busy => ("Busy", "#ef6c00"),
+        NodeStatus::Maintenance => ("Maintenance", "#757575"),
+        NodeStatus::Offline => ("Offline", "#c62828"),
+    };
+    let gpus = match (&status.gpu_model, status.gpu_count) {
+        (_, 0) => "CPU only".to_string(),
+        (Some(model), count) => format!("{} x {}", count, market::escape(model)),
+        (None, count) => format!("{} GPUs", count),
+    };
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><meta http-equiv=\"refresh\" content=\"60\"><title>{label}</title></head>\
+         <body style=\"font-family:sans-serif;margin:8px\">\n\
+         <strong>{label}</strong> <span style=\"color:{color}\">&#9679; {state}</span><br>\n\
+         {gpus}, {price}, {utilization} in use\n</body></html>\n",
+        label = market::escape(&status.label),
+        color = color,
+        state = state,
+        gpus = gpus,
+        price = status.price_per_hour.map(|p| format!("{:.2} AVAX/h", p)).unwrap_or_else(|| "no price set".to_string()),
+        utilization = status.utilization.map(|u| format!("{:.0}%", u * 100.0)).unwrap_or_else(|| "-".to_string()),
+    )
+}
+
+/// Mint an observer token for `node_id` at the coordinator at `base_url`
+pub fn mint_token(base_url: &str, node_id: &str, owner: &str, owner_secret: &str, label: &str) -> Result<ObserverToken, String> {
+    let request = MintRequest {
+        node_id: node_id.to_string(),
+        owner: owner.to_string(),
+        owner_secret: owner_secret.to_string(),
+        label: label.to_string(),
+    };
+    let body = serde_json::to_string(&request).map_err(|e| format!("Failed to serialize observer token request: {}", e))?;
+    let answer = market::request(base_url, "POST", OBSERVERS_PATH, Some(&body), None)?;
+    serde_json::from_str(&answer).map_err(|e| format!("Failed to parse observer token: {}", e))
+}
+
+/// Revoke an observer token at the coordinator at `base_url`
+pub fn revoke_token(base_url: &str, token: &str, owner: &str, owner_secret: &str) -> Result<String, String> {
+    let request = RevokeRequest { token: token.trim().to_string(), owner: owner.to_string(), owner_secret: owner_secret.to_string() };
+    let body = serde_json::to_string(&request).map_err(|e| format!("Failed to serialize revocation: {}", e))?;
+    market::request(base_url, "POST", REVOKE_PATH, Some(&body), None).map(|answer| answer.trim().to_string())
+}
+
+/// Link to the embeddable status page of `token`
+pub fn page_url(base_url: &str, token: &str) -> String {
+    format!("{}{}{}", base_url.trim_end_matches('/'), PAGE_PATH_PREFIX, token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registration::{ClaimRequest, NodeRegistration};
+
+    #[test]
+    fn test_observer_tokens() {
+        let registrations = RegistrationStore::new(std::env::temp_dir().join(format!("eryzaa-observed-nodes-{}.json", std::process::id())));
+        let store = ObserverStore::new(std::env::temp_dir().join(format!("eryzaa-observers-{}.json", std::process::id())));
+        let registration = NodeRegistration {
+            node_id: "node-1".to_string(),
+            hostname: "desk".to_string(),
+            overlay_ip: None,
+            price_per_hour: Some(2.0),
+            cpu_score: None,
+            gpus: vec![],
+            public_key: None,
+        };
+        let receipt = registrations.register(registration, 1000).unwrap();
+        let mut request = MintRequest { node_id: "node-1".to_string(), owner: "0xabc".to_string(), owner_secret: String::new(), label: String::new() };
+        assert!(store.mint(&request, &registrations, 1000).unwrap_err().contains("not claimed"));
+        let claimed = registrations.claim(&ClaimRequest { claim_code: receipt.claim_code, owner: "0xabc".to_string() }, 1000).unwrap();
+        request.owner_secret = claimed.owner_secret.clone();
+        let token = store.mint(&request, &registrations, 1000).unwrap();
+        assert_eq!(token.label, "desk");
+
+        let mut advertisement = crate::create_client_advertisement("node-1".to_string(), "10.0.0.5".to_string(), None, String::new());
+        advertisement.status = NodeStatus::Available;
+        advertisement.capabilities.max_concurrent_jobs = 4;
+        advertisement.free_slots = Some(1);
+        advertisement.price_per_hour = Some(2.5);
+        let node = |node_id: &str| (node_id == "node-1").then(|| advertisement.clone());
+        let (status, _, body) = route("GET", &format!("{}{}", STATUS_PATH_PREFIX, token.token), "", &store, &registrations, &node);
+        assert_eq!(status, "200 OK");
+        let observed: ObservedStatus = serde_json::from_str(&body).unwrap();
+        assert_eq!((observed.status, observed.utilization, observed.price_per_hour), (NodeStatus::Available, Some(0.75), Some(2.5)));
+        assert!(!body.contains("10.0.0.5") && !body.contains("node-1"));
+        let (_, content_type, page) = route("GET", &format!("{}{}", PAGE_PATH_PREFIX, token.token), "", &store, &registrations, &node);
+        assert_eq!(content_type, "text/html; charset=utf-8");
+        assert!(page.contains("<strong>desk</strong>") && page.contains("CPU only, 2.50 AVAX/h, 75% in use"));
+
+        let revoke = |owner: &str, owner_secret: &str| RevokeRequest { token: token.token.clone(), owner: owner.to_string(), owner_secret: owner_secret.to_string() };
+        assert!(store.revoke(&revoke("0xdef", &claimed.owner_secret), &registrations, 2000).is_err());
+        assert!(store.revoke(&revoke("0xabc", "guess"), &registrations, 2000).is_err());
+        store.revoke(&revoke("0xabc", &claimed.owner_secret), &registrations, 2000).unwrap();
+        assert_eq!(route("GET", &format!("{}{}", STATUS_PATH_PREFIX, token.token), "", &store, &registrations, &node).0, "404 Not Found");
+        std::fs::remove_file(store.path()).unwrap();
+        std::fs::remove_file(registrations.path()).unwrap();
+    }
+
+    #[test]
+    fn test_forged_mint_is_refused() {
+        let registrations = RegistrationStore::new(std::env::temp_dir().join(format!("eryzaa-forged-nodes-{}.json", std::process::id())));
+        let store = ObserverStore::new(std::env::temp_dir().join(format!("eryzaa-forged-observers-{}.json", std::process::id())));
+        let registration = NodeRegistration {
+            node_id: "node-1".to_string(),
+            hostname: "desk".to_string(),
+            overlay_ip: None,
+            price_per_hour: None,
+            cpu_score: None,
+            gpus: vec![],
+            public_key: None,
+        };
+        let receipt = registrations.register(registration, 1000).unwrap();
+        registrations.claim(&ClaimRequest { claim_code: receipt.claim_code, owner: "0xabc".to_string() }, 1000).unwrap();
+
+        // The owner's account is public, e.g. a wallet address; knowing it is not enough
+        let forged = MintRequest { node_id: "node-1".to_string(), owner: "0xabc".to_string(), owner_secret: String::new(), label: "mine".to_string() };
+        let body = serde_json::to_string(&forged).unwrap();
+        let (status, _, answer) = route("POST", OBSERVERS_PATH, &body, &store, &registrations, &|_: &str| None);
+        assert_eq!(status, "403 Forbidden");
+        assert!(answer.contains("Wrong owner secret"));
+        let guessed = MintRequest { owner_secret: "0123456789abcdef".to_string(), ..forged };
+        assert!(store.mint(&guessed, &registrations, 1000).is_err());
+        assert!(store.tokens().is_empty());
+        std::fs::remove_file(registrations.path()).unwrap();
+    }
+}
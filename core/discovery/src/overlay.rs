@@ -0,0 +1,238 @@
+//! Address ranges of the Eryzaa overlay networks
+//! Also finds this machine's own addresses, which change when ZeroTier reassigns the overlay
+//! address or the machine roams to another network. Nodes on one LAN can skip the overlay and
+//! serve clients on their LAN addresses instead, with the LAN subnet standing in for the overlay.
+
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream};
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// Subnets handed out by the Eryzaa ZeroTier networks
+pub const DEFAULT_OVERLAY_SUBNETS: &[&str] = &["10.242.0.0/16", "10.243.0.0/16", "192.168.191.0/24"];
+
+/// Private ranges a LAN direct node accepts clients from when its own subnet is unknown
+pub const PRIVATE_SUBNETS: &[&str] = &["10.0.0.0/8", "172.16.0.0/12", "192.168.0.0/16"];
+
+/// How a node reaches its clients, chosen per node
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum NetworkMode {
+    #[default]
+    Overlay, // Through the ZeroTier network
+    LanDirect, // Straight over the LAN; discovery by multicast, SSH and control on LAN addresses
+}
+
+impl NetworkMode {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize network mode: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write network mode {}: {}", path.display(), e))
+    }
+
+    pub fn uses_overlay(&self) -> bool {
+        *self == NetworkMode::Overlay
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            NetworkMode::Overlay => "ZeroTier overlay",
+            NetworkMode::LanDirect => "LAN direct",
+        }
+    }
+}
+
+/// Whether `ip` falls inside the IPv4 CIDR `subnet`
+pub fn subnet_contains(subnet: &str, ip: &str) -> bool {
+    let Some((network, prefix)) = subnet.split_once('/') else {
+        return subnet == ip;
+    };
+    let (Ok(network), Ok(prefix), Ok(ip)) = (
+        network.parse::<Ipv4Addr>(),
+        prefix.parse::<u32>(),
+        ip.parse::<Ipv4Addr>(),
+    ) else {
+        return false;
+    };
+    if prefix > 32 {
+        return false;
+    }
+
+    let mask = if prefix == 0 { 0 } else { u32::MAX << (32 - prefix) };
+    u32::from(network) & mask == u32::from(ip) & mask
+}
+
+/// Whether `ip` is an address on one of the default overlay subnets
+pub fn is_overlay_ip(ip: &str) -> bool {
+    DEFAULT_OVERLAY_SUBNETS.iter().any(|subnet| subnet_contains(subnet, ip))
+}
+
+/// This machine's address on `network_id` from `zerotier-cli listnetworks` output
+///
+/// Lines look like "200 listnetworks <nwid> <name> <mac> <status> <type> <dev> <ips>", where
+/// the addresses are comma separated and may include IPv6 ones.
+pub fn overlay_ip_from_listnetworks(output: &str, network_id: &str) -> Option<String> {
+    output
+        .lines()
+        .filter(|line| line.split_whitespace().any(|field| field == network_id))
+        .flat_map(|line| line.split_whitespace().flat_map(|field| field.split(',')))
+        .filter_map(|address| address.split('/').next()?.parse::<Ipv4Addr>().ok())
+        .map(|ip| ip.to_string())
+        .next()
+}
+
+/// The IPv4 subnet `ip` is configured on, e.g. "192.168.1.0/24", from `ip -o -4 addr show` output
+///
+/// Lines look like "2: eth0    inet 192.168.1.100/24 brd 192.168.1.255 scope global eth0".
+pub fn subnet_from_ip_addr(output: &str, ip: &str) -> Option<String> {
+    output
+        .lines()
+        .flat_map(|line| line.split_whitespace())
+        .filter_map(|field| field.split_once('/'))
+        .find(|(address, _)| *address == ip)
+        .and_then(|(address, prefix)| {
+            let (Ok(address), Ok(prefix)) = (address.parse::<Ipv4Addr>(), prefix.parse::<u32>()) else {
+                return None;
+            };
+            let mask = if prefix == 0 { 0 } else { u32::MAX.checked_shl(32 - prefix)? };
+            Some(format!("{}/{}", Ipv4Addr::from(u32::from(address) & mask), prefix))
+        })
+}
+
+/// The subnet of this machine's LAN address `ip`, where `ip` can tell
+pub fn lan_subnet(ip: &str) -> Option<String> {
+    run("ip", &["-o", "-4", "addr", "show"]).and_then(|output| subnet_from_ip_addr(&output, ip))
+}
+
+/// This machine's addresses in `mode`: with LAN direct there is no overlay address to look up
+pub fn local_addresses_in(mode: NetworkMode, network_id: &str) -> (String, Option<String>) {
+    match mode {
+        NetworkMode::Overlay => local_addresses(network_id),
+        NetworkMode::LanDirect => (lan_address(None), None),
+    }
+}
+
+/// This machine's LAN address and its address on the overlay `network_id`, if it has one
+pub fn local_addresses(network_id: &str) -> (String, Option<String>) {
+    let overlay_ip = run("zerotier-cli", &["listnetworks"]).and_then(|output| overlay_ip_from_listnetworks(&output, network_id));
+    (lan_address(overlay_ip.as_deref()), overlay_ip)
+}
+
+/// Join the overlay `network_id` and wait up to `wait` for an address on it
+pub fn join(network_id: &str, wait: Duration) -> Result<String, String> {
+    let output = Command::new("zerotier-cli")
+        .args(["join", network_id])
+        .output()
+        .map_err(|e| format!("Failed to execute zerotier-cli: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("Failed to join {}: {}", network_id, String::from_utf8_lossy(&output.stdout).trim()));
+    }
+
+    let started = Instant::now();
+    loop {
+        if let (_, Some(overlay_ip)) = local_addresses(network_id) {
+            return Ok(overlay_ip);
+        }
+        if started.elapsed() >= wait {
+            return Err(format!("Joined {} but got no address yet; authorize this machine in the network", network_id));
+        }
+        std::thread::sleep(Duration::from_secs(2));
+    }
+}
+
+/// Where a client reaches a node: its LAN address when `answers` gets through to it, so a node
+/// on the client's own LAN is reached directly, else its overlay address
+///
+/// A LAN direct node has no overlay address and is reached on its LAN address either way.
+pub fn connect_address(lan_ip: &str, overlay_ip: Option<&str>, answers: impl Fn(&str) -> bool) -> String {
+    match overlay_ip {
+        Some(overlay_ip) if overlay_ip != lan_ip && !answers(lan_ip) => overlay_ip.to_string(),
+        _ => lan_ip.to_string(),
+    }
+}
+
+/// Whether `ip` takes TCP connections on `port` within `timeout`
+pub fn answers(ip: &str, port: u16, timeout: Duration) -> bool {
+    ip.parse::<IpAddr>()
+        .is_ok_and(|ip| TcpStream::connect_timeout(&SocketAddr::new(ip, port), timeout).is_ok())
+}
+
+/// The first address that is neither loopback nor `overlay_ip`
+fn lan_address(overlay_ip: Option<&str>) -> String {
+    run("hostname", &["-I"])
+        .and_then(|output| {
+            output
+                .split_whitespace()
+                .find(|ip| *ip != "127.0.0.1" && Some(*ip) != overlay_ip)
+                .map(|ip| ip.to_string())
+        })
+        .unwrap_or_else(|| "127.0.0.1".to_string())
+}
+
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    Command::new(program)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subnet_contains() {
+        assert!(subnet_contains("10.242.0.0/16", "10.242.7.1"));
+        assert!(!subnet_contains("10.242.0.0/16", "10.244.0.1"));
+        assert!(subnet_contains("203.0.113.7", "203.0.113.7"));
+        assert!(is_overlay_ip("192.168.191.20"));
+        assert!(!is_overlay_ip("8.8.8.8"));
+    }
+
+    #[test]
+    fn test_listnetworks_address() {
+        let output = "200 listnetworks <nwid> <name> <mac> <status> <type> <dev> <ZT assigned ips>\n\
+                      200 listnetworks 8056c2e21c000001 other 12:34:56:78:9a:bc OK PRIVATE ztabc 10.147.17.3/24\n\
+                      200 listnetworks 363c67c55ad2489d eryzaa 5e:11:22:33:44:55 OK PRIVATE ztxyz fd80::1/88,10.242.9.14/16\n";
+        assert_eq!(overlay_ip_from_listnetworks(output, "363c67c55ad2489d").as_deref(), Some("10.242.9.14"));
+        assert_eq!(overlay_ip_from_listnetworks(output, "deadbeef00000000"), None);
+        let waiting = "200 listnetworks 363c67c55ad2489d eryzaa 5e:11:22:33:44:55 REQUESTING_CONFIGURATION PRIVATE ztxyz -\n";
+        assert_eq!(overlay_ip_from_listnetworks(waiting, "363c67c55ad2489d"), None);
+
+        let addresses = "1: lo    inet 127.0.0.1/8 scope host lo\n\
+                         2: eth0    inet 192.168.1.100/24 brd 192.168.1.255 scope global dynamic eth0\n";
+        assert_eq!(subnet_from_ip_addr(addresses, "192.168.1.100").as_deref(), Some("192.168.1.0/24"));
+        assert_eq!(subnet_from_ip_addr(addresses, "192.168.1.10"), None);
+        assert_eq!(serde_json::from_str::<NetworkMode>("\"LanDirect\"").unwrap(), NetworkMode::LanDirect);
+    }
+
+    #[test]
+    fn test_connect_address() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let probe = |ip: &str| answers(ip, port, Duration::from_secs(1));
+
+        // Straight to the LAN address while it answers, through the overlay once it does not
+        assert_eq!(connect_address("127.0.0.1", Some("10.242.0.7"), probe), "127.0.0.1");
+        drop(listener);
+        assert_eq!(connect_address("127.0.0.1", Some("10.242.0.7"), probe), "10.242.0.7");
+        assert_eq!(connect_address("not an address", Some("10.242.0.7"), probe), "10.242.0.7");
+        // A LAN direct node has nothing to fall back to
+        assert_eq!(connect_address("127.0.0.1", None, |_| panic!("nothing to choose between")), "127.0.0.1");
+    }
+}
@@ -0,0 +1,177 @@
+//! Advertisement privacy
+//! An owner on a shared network may keep parts of the node's advertisement to themselves. GPU
+//! models go out as "unknown" with their memory kept, so clients can still size jobs; hiding
+//! the location drops the LAN address and the grid carbon intensity, which tells the region.
+//! With a query key the node stops broadcasting altogether and answers only `DISCOVER` queries
+//! that prove they know the key, which the owner hands to the clients they rent to. The same
+//! settings apply to what the node registers at the coordinator.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use crate::market::UNKNOWN_MODEL;
+use crate::registration::NodeRegistration;
+use crate::NodeAdvertisement;
+
+/// What a client sends to ask a node for its advertisement
+pub const QUERY_PREFIX: &str = "DISCOVER";
+
+/// How old an authenticated query may be, in seconds, so a recorded one soon stops working
+pub const QUERY_MAX_AGE_SECS: u64 = 60;
+
+/// Shortest query key, which is all that keeps the node from being listed
+pub const MIN_QUERY_KEY_LEN: usize = 12;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct AdvertisementPrivacy {
+    pub hide_gpu_model: bool,
+    pub hide_location: bool,
+    pub query_key: Option<String>, // Answer only queries made with this key, never broadcast
+}
+
+impl AdvertisementPrivacy {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Self>(&content).ok())
+            .filter(|privacy| privacy.validate().is_ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        self.validate()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize advertisement privacy: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write advertisement privacy {}: {}", path.display(), e))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.query_key {
+            Some(key) if key.len() < MIN_QUERY_KEY_LEN => Err(format!("Query key must be at least {} characters", MIN_QUERY_KEY_LEN)),
+            Some(key) if key.chars().any(char::is_whitespace) => Err("Query key cannot contain spaces".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the node broadcasts its advertisement for anyone to hear
+    pub fn broadcasts(&self) -> bool {
+        self.query_key.is_none()
+    }
+
+    /// `advertisement` with the hidden fields taken out, as it goes on the network
+    pub fn apply(&self, advertisement: &NodeAdvertisement) -> NodeAdvertisement {
+        let mut shown = advertisement.clone();
+        if self.hide_gpu_model {
+            shown.gpu_model = shown.gpu_model.map(|_| UNKNOWN_MODEL.to_string());
+            for gpu in &mut shown.capabilities.hardware.gpus {
+                gpu.model = UNKNOWN_MODEL.to_string();
+            }
+        }
+        if self.hide_location {
+            // Clients reach the node over the overlay; without one there is no address to give
+            shown.ip_address = shown.zerotier_ip.clone().unwrap_or_default();
+            shown.carbon_intensity_g_per_kwh = None;
+        }
+        shown
+    }
+
+    /// `registration` with the hidden fields taken out, as the coordinator gets it
+    pub fn apply_to_registration(&self, registration: &NodeRegistration) -> NodeRegistration {
+        let mut shown = registration.clone();
+        if self.hide_gpu_model {
+            shown.gpus = shown.gpus.iter().map(|_| UNKNOWN_MODEL.to_string()).collect();
+        }
+        if self.hide_location {
+            // Host names tend to say whose desk or which site the machine is at
+            shown.hostname = String::new();
+        }
+        shown
+    }
+
+    /// Whether to answer the query `packet`, received at `now` (Unix seconds)
+    pub fn answers(&self, packet: &[u8], now: u64) -> bool {
+        let Ok(packet) = std::str::from_utf8(packet) else {
+            return false;
+        };
+        let mut words = packet.split_whitespace();
+        if words.next() != Some(QUERY_PREFIX) {
+            return false;
+        }
+        let Some(key) = &self.query_key else {
+            return true;
+        };
+        match (words.next().and_then(|sent_at| sent_at.parse::<u64>().ok()), words.next()) {
+            (Some(sent_at), Some(proof)) => now.abs_diff(sent_at) <= QUERY_MAX_AGE_SECS && proof == query_proof(key, sent_at),
+            _ => false,
+        }
+    }
+}
+
+/// A `DISCOVER` query made at `now`, proving knowledge of `key` when one is given
+pub fn query(key: Option<&str>, now: u64) -> String {
+    match key {
+        Some(key) => format!("{} {} {}", QUERY_PREFIX, now, query_proof(key, now)),
+        None => QUERY_PREFIX.to_string(),
+    }
+}
+
+/// Hex SHA-256 of the key and the query time; the key itself never goes on the network
+fn query_proof(key: &str, sent_at: u64) -> String {
+    Sha256::digest(format!("{}:{}", key, sent_at).as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_client_advertisement;
+    use crate::hardware::GpuDevice;
+
+    #[test]
+    fn test_privacy() {
+        let mut advertisement =
+            create_client_advertisement("node-1".to_string(), "192.168.1.20".to_string(), Some("10.242.0.7".to_string()), String::new());
+        advertisement.capabilities.hardware.gpus.push(GpuDevice { model: "RTX 4090".to_string(), memory_mb: 24576, compute_capability: None });
+        advertisement.gpu_model = Some("RTX 4090".to_string());
+        advertisement.carbon_intensity_g_per_kwh = Some(320.0);
+
+        let open = AdvertisementPrivacy::default();
+        assert_eq!(serde_json::to_value(open.apply(&advertisement)).unwrap(), serde_json::to_value(&advertisement).unwrap());
+        assert!(open.broadcasts() && open.answers(b"DISCOVER", 0));
+
+        let private = AdvertisementPrivacy { hide_gpu_model: true, hide_location: true, query_key: Some("k3y-for-clients".to_string()) };
+        let shown = private.apply(&advertisement);
+        assert_eq!(shown.gpu_model.as_deref(), Some(UNKNOWN_MODEL));
+        assert_eq!(shown.capabilities.hardware.gpus[0].model, UNKNOWN_MODEL);
+        assert_eq!(shown.capabilities.hardware.gpus[0].memory_mb, 24576);
+        assert_eq!((shown.ip_address.as_str(), shown.carbon_intensity_g_per_kwh), ("10.242.0.7", None));
+        let registration = NodeRegistration {
+            node_id: "node-1".to_string(),
+            hostname: "alice-berlin".to_string(),
+            overlay_ip: None,
+            price_per_hour: None,
+            cpu_score: None,
+            gpus: vec!["RTX 4090".to_string()],
+            public_key: None,
+        };
+        let registered = private.apply_to_registration(&registration);
+        assert_eq!((registered.hostname.as_str(), registered.gpus[0].as_str()), ("", UNKNOWN_MODEL));
+
+        assert!(!private.broadcasts());
+        assert!(!private.answers(b"DISCOVER", 1000));
+        assert!(private.answers(query(Some("k3y-for-clients"), 1000).as_bytes(), 1030));
+        assert!(!private.answers(query(Some("k3y-for-clients"), 1000).as_bytes(), 1000 + QUERY_MAX_AGE_SECS + 1));
+        assert!(!private.answers(query(Some("wrong-key-here"), 1000).as_bytes(), 1000));
+        assert!(AdvertisementPrivacy { query_key: Some("short".to_string()), ..Default::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,437 @@
+//! Push alerts to clients' phones
+//! A client subscribes its running jobs at the coordinator with an ntfy topic, and the coordinator
+//! keeps watching them after the desktop app is closed: it publishes to the topic when the node
+//! reports the job finished, when the job has spent 80% of its budget and when the node is no
+//! longer heard from. The topic is the only secret, so clients pick a long random one. ntfy.sh
+//! works as the relay, as does a self-hosted ntfy, over plain HTTP like the coordinator itself.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::market;
+use crate::NodeAdvertisement;
+
+pub const SUBSCRIPTIONS_PATH: &str = "/api/push/subscriptions";
+pub const UNSUBSCRIBE_PATH: &str = "/api/push/unsubscribe";
+pub const FINISHED_PATH: &str = "/api/push/finished";
+
+pub const DEFAULT_RELAY: &str = "http://ntfy.sh";
+
+/// Share of its budget a job spends before the client hears about it
+pub const BUDGET_ALERT_SHARE: f32 = 0.8;
+
+/// Seconds without an advertisement before a node counts as unreachable
+pub const UNREACHABLE_AFTER: u64 = 300;
+
+/// Jobs one topic can have watched at once
+const MAX_SUBSCRIPTIONS_PER_TOPIC: usize = 100;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    Finished,
+    Budget,
+    Unreachable,
+}
+
+impl AlertKind {
+    pub const ALL: [AlertKind; 3] = [Self::Finished, Self::Budget, Self::Unreachable];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Finished => "Job finished",
+            Self::Budget => "80% of budget spent",
+            Self::Unreachable => "Node unreachable",
+        }
+    }
+}
+
+/// The client's side: where its alerts go and which it wants
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct PushSettings {
+    pub enabled: bool,
+    pub relay: String, // Base URL of the ntfy server
+    pub topic: String,
+    pub alerts: Vec<AlertKind>,
+    pub budget: Option<f32>, // AVAX per job; no budget alert without one
+}
+
+impl Default for PushSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            relay: DEFAULT_RELAY.to_string(),
+            topic: new_topic(),
+            alerts: AlertKind::ALL.to_vec(),
+            budget: None,
+        }
+    }
+}
+
+impl PushSettings {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize push settings: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write push settings {}: {}", path.display(), e))
+    }
+
+    /// What to subscribe to in the ntfy app
+    pub fn subscribe_url(&self) -> String {
+        format!("{}/{}", self.relay.trim().trim_end_matches('/'), self.topic)
+    }
+
+    /// A subscription for a job costing `price_per_hour` that has spent `spent` AVAX by `now`
+    pub fn subscription(&self, job_id: &str, node_id: &str, price_per_hour: Option<f32>, spent: f32, now: u64) -> PushSubscription {
+        PushSubscription {
+            job_id: job_id.to_string(),
+            node_id: node_id.to_string(),
+            relay: self.relay.trim().trim_end_matches('/').to_string(),
+            topic: self.topic.clone(),
+            alerts: self.alerts.clone(),
+            price_per_hour,
+            budget: self.budget,
+            spent,
+            spent_at: now,
+            sent: vec![],
+            outcome: None,
+        }
+    }
+}
+
+/// A fresh topic, long enough that nobody guesses it
+pub fn new_topic() -> String {
+    format!("eryzaa-{}", uuid::Uuid::new_v4().simple())
+}
+
+/// A job the coordinator watches for a client
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PushSubscription {
+    pub job_id: String,
+    pub node_id: String,
+    pub relay: String,
+    pub topic: String,
+    pub alerts: Vec<AlertKind>,
+    #[serde(default)]
+    pub price_per_hour: Option<f32>,
+    #[serde(default)]
+    pub budget: Option<f32>,
+    #[serde(default)]
+    pub spent: f32, // AVAX spent as of `spent_at`
+    pub spent_at: u64,
+    #[serde(default)]
+    pub sent: Vec<AlertKind>, // Alerts already published, so each goes out once
+    #[serde(default)]
+    pub outcome: Option<String>, // Set when the node reports the job finished
+}
+
+impl PushSubscription {
+    fn spent_by(&self, now: u64) -> f32 {
+        self.spent + self.price_per_hour.unwrap_or(0.0) * now.saturating_sub(self.spent_at) as f32 / 3600.0
+    }
+
+    fn wants(&self, kind: AlertKind) -> bool {
+        self.alerts.contains(&kind) && !self.sent.contains(&kind)
+    }
+
+    fn alert(&self, kind: AlertKind, message: String) -> PushAlert {
+        let (title, priority, tags) = match kind {
+            AlertKind::Finished => (format!("Job {} finished", self.job_id), 3, "checkered_flag"),
+            AlertKind::Budget => (format!("Job {} is near its budget", self.job_id), 4, "moneybag"),
+            AlertKind::Unreachable => (format!("Node of job {} unreachable", self.job_id), 5, "warning"),
+        };
+        PushAlert {
+            relay: self.relay.clone(),
+            topic: self.topic.clone(),
+            title,
+            message,
+            priority,
+            tags: vec![tags.to_string()],
+        }
+    }
+}
+
+/// A node saying one of its jobs is over
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FinishedReport {
+    pub job_id: String,
+    pub outcome: String, // e.g. "Completed" or "Failed: Exited with code 1"
+}
+
+/// A client taking a job's alerts back
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Unsubscribe {
+    pub job_id: String,
+    pub topic: String,
+}
+
+/// An alert ready to publish, in ntfy's JSON format apart from the relay
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PushAlert {
+    #[serde(skip)]
+    pub relay: String,
+    pub topic: String,
+    pub title: String,
+    pub message: String,
+    pub priority: u8, // 1-5, 5 rings through do-not-disturb
+    pub tags: Vec<String>,
+}
+
+/// Push subscriptions, kept in one JSON file
+#[derive(Debug, Clone)]
+pub struct PushStore {
+    path: PathBuf,
+    lock: Arc<Mutex<()>>, // The request loop and the publisher both rewrite the file
+}
+
+impl PushStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), lock: Arc::new(Mutex::new(())) }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Watch `subscription`'s job, replacing an earlier subscription of the same topic to it
+    pub fn subscribe(&self, subscription: PushSubscription) -> Result<(), String> {
+        if !subscription.relay.starts_with("http://") {
+            return Err(format!("Relay {} must be an http:// URL", subscription.relay));
+        }
+        if subscription.topic.len() < 16 || !subscription.topic.chars().all(|c| c.is_ascii_alphanumeric() || "-_".contains(c)) {
+            return Err("Topics need at least 16 letters, digits, '-' or '_'".to_string());
+        }
+
+        let _guard = self.lock.lock().unwrap();
+        let mut subscriptions = self.load();
+        let earlier = subscriptions
+            .iter()
+            .position(|s| s.job_id == subscription.job_id && s.topic == subscription.topic);
+        match earlier {
+            // A renewed subscription only refreshes the spending; what went out stays sent
+            Some(index) => {
+                let earlier = &mut subscriptions[index];
+                let sent = std::mem::take(&mut earlier.sent);
+                let outcome = earlier.outcome.take();
+                subscriptions[index] = PushSubscription { sent, outcome, ..subscription };
+            }
+            None => {
+                if subscriptions.iter().filter(|s| s.topic == subscription.topic).count() >= MAX_SUBSCRIPTIONS_PER_TOPIC {
+                    return Err(format!("Topic has {} jobs watched already", MAX_SUBSCRIPTIONS_PER_TOPIC));
+                }
+                subscriptions.push(subscription);
+            }
+        }
+        self.save(&subscriptions)
+    }
+
+    /// Stop watching a job for one topic; false when it was not watched
+    pub fn unsubscribe(&self, request: &Unsubscribe) -> Result<bool, String> {
+        let _guard = self.lock.lock().unwrap();
+        let mut subscriptions = self.load();
+        let before = subscriptions.len();
+        subscriptions.retain(|s| !(s.job_id == request.job_id && s.topic == request.topic));
+        let removed = subscriptions.len() < before;
+        if removed {
+            self.save(&subscriptions)?;
+        }
+        Ok(removed)
+    }
+
+    /// Note that a job is over; its alert goes out with the next `due`. Returns the subscribers.
+    ///
+    /// Job ids are random, so only the node running a job and its client can report it.
+    pub fn finished(&self, report: &FinishedReport) -> Result<usize, String> {
+        let _guard = self.lock.lock().unwrap();
+        let mut subscriptions = self.load();
+        let mut watching = 0;
+        for subscription in subscriptions.iter_mut().filter(|s| s.job_id == report.job_id) {
+            subscription.outcome.get_or_insert_with(|| report.outcome.clone());
+            watching += 1;
+        }
+        if watching > 0 {
+            self.save(&subscriptions)?;
+        }
+        Ok(watching)
+    }
+
+    /// Alerts that are due at `now`, each marked sent; `node` looks up a node's latest advertisement
+    ///
+    /// Finished jobs are dropped once their alert is out. An unreachable alert goes out again if
+    /// the node comes back and is lost once more.
+    pub fn due(&self, now: u64, node: &impl Fn(&str) -> Option<NodeAdvertisement>) -> Result<Vec<PushAlert>, String> {
+        let _guard = self.lock.lock().unwrap();
+        let mut subscriptions = self.load();
+        let mut alerts = vec![];
+        for subscription in &mut subscriptions {
+            if let Some(outcome) = subscription.outcome.clone() {
+                if subscription.wants(AlertKind::Finished) {
+                    alerts.push(subscription.alert(AlertKind::Finished, format!("{} on node {}", outcome, subscription.node_id)));
+                }
+                continue;
+            }
+
+            let spent = subscription.spent_by(now);
+            if let Some(budget) = subscription.budget.filter(|budget| *budget > 0.0) {
+                if spent >= budget * BUDGET_ALERT_SHARE && subscription.wants(AlertKind::Budget) {
+                    let message = format!("Spent {:.2} of its {:.2} AVAX budget", spent, budget);
+                    alerts.push(subscription.alert(AlertKind::Budget, message));
+                    subscription.sent.push(AlertKind::Budget);
+                }
+            }
+
+            let last_seen = node(&subscription.node_id).map(|node| node.timestamp);
+            let heard = last_seen.is_some_and(|timestamp| timestamp + UNREACHABLE_AFTER >= now);
+            if heard {
+                subscription.sent.retain(|kind| *kind != AlertKind::Unreachable);
+            } else if subscription.wants(AlertKind::Unreachable) {
+                let message = match last_seen {
+                    Some(timestamp) => format!("Node {} last heard from {} minutes ago", subscription.node_id, now.saturating_sub(timestamp) / 60),
+                    None => format!("Node {} is not advertising", subscription.node_id),
+                };
+                alerts.push(subscription.alert(AlertKind::Unreachable, message));
+                subscription.sent.push(AlertKind::Unreachable);
+            }
+        }
+        subscriptions.retain(|s| s.outcome.is_none());
+        self.save(&subscriptions)?;
+        Ok(alerts)
+    }
+
+    pub fn subscriptions(&self) -> Vec<PushSubscription> {
+        self.load()
+    }
+
+    fn load(&self) -> Vec<PushSubscription> {
+        std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, subscriptions: &[PushSubscription]) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(subscriptions)
+            .map_err(|e| format!("Failed to serialize push subscriptions: {}", e))?;
+        std::fs::write(&self.path, content)
+            .map_err(|e| format!("Failed to write push subscriptions {}: {}", self.path.display(), e))
+    }
+}
+
+/// True for the paths `route` answers
+pub fn handles(path: &str) -> bool {
+    path.starts_with("/api/push/")
+}
+
+/// Status, content type and body for a push request
+pub fn route(method: &str, path: &str, body: &str, store: &PushStore) -> (&'static str, &'static str, String) {
+    match (method, path.split('?').next().unwrap_or(path)) {
+        ("POST", SUBSCRIPTIONS_PATH) => match serde_json::from_str::<PushSubscription>(body) {
+            Ok(subscription) => {
+                let job_id = subscription.job_id.clone();
+                match store.subscribe(subscription) {
+                    Ok(()) => ("201 Created", "text/plain", format!("Watching job {}\n", job_id)),
+                    Err(e) => ("400 Bad Request", "text/plain", e + "\n"),
+                }
+            }
+            Err(e) => ("400 Bad Request", "text/plain", format!("Malformed push subscription: {}\n", e)),
+        },
+        ("POST", UNSUBSCRIBE_PATH) => match serde_json::from_str::<Unsubscribe>(body) {
+            Ok(request) => match store.unsubscribe(&request) {
+                Ok(true) => ("200 OK", "text/plain", format!("Stopped watching job {}\n", request.job_id)),
+                Ok(false) => ("404 Not Found", "text/plain", format!("Job {} was not watched for this topic\n", request.job_id)),
+                Err(e) => ("500 Internal Server Error", "text/plain", e + "\n"),
+            },
+            Err(e) => ("400 Bad Request", "text/plain", format!("Malformed unsubscription: {}\n", e)),
+        },
+        // Nodes report every job they finish; one nobody watches is not an error
+        ("POST", FINISHED_PATH) => match serde_json::from_str::<FinishedReport>(body) {
+            Ok(report) => match store.finished(&report) {
+                Ok(watching) => ("200 OK", "text/plain", format!("{} subscribers of job {}\n", watching, report.job_id)),
+                Err(e) => ("500 Internal Server Error", "text/plain", e + "\n"),
+            },
+            Err(e) => ("400 Bad Request", "text/plain", format!("Malformed job report: {}\n", e)),
+        },
+        ("GET" | "POST", _) => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain", "Only GET and POST are supported\n".to_string()),
+    }
+}
+
+/// Publish `alert` to its relay
+pub fn publish(alert: &PushAlert) -> Result<(), String> {
+    let body = serde_json::to_string(alert).map_err(|e| format!("Failed to serialize alert: {}", e))?;
+    market::request(&alert.relay, "POST", "/", Some(&body), None)
+        .map(|_| ())
+        .map_err(|e| format!("Failed to push to {}: {}", alert.relay, e))
+}
+
+/// Have the coordinator at `base_url` watch a job
+pub fn subscribe(base_url: &str, subscription: &PushSubscription) -> Result<String, String> {
+    let body = serde_json::to_string(subscription).map_err(|e| format!("Failed to serialize push subscription: {}", e))?;
+    market::request(base_url, "POST", SUBSCRIPTIONS_PATH, Some(&body), None).map(|answer| answer.trim().to_string())
+}
+
+/// Tell the coordinator at `base_url` that a job on this node is over
+pub fn report_finished(base_url: &str, report: &FinishedReport) -> Result<String, String> {
+    let body = serde_json::to_string(report).map_err(|e| format!("Failed to serialize job report: {}", e))?;
+    market::request(base_url, "POST", FINISHED_PATH, Some(&body), None).map(|answer| answer.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_alerts() {
+        let store = PushStore::new(std::env::temp_dir().join(format!("eryzaa-push-{}.json", std::process::id())));
+        let settings = PushSettings { enabled: true, budget: Some(10.0), ..Default::default() };
+        assert!(settings.subscribe_url().starts_with("http://ntfy.sh/eryzaa-"));
+        let subscription = settings.subscription("job-1", "node-1", Some(2.0), 6.0, 1000);
+        let body = serde_json::to_string(&subscription).unwrap();
+        assert_eq!(route("POST", SUBSCRIPTIONS_PATH, &body, &store).0, "201 Created");
+        let weak = PushSubscription { topic: "short".to_string(), ..subscription.clone() };
+        assert!(store.subscribe(weak).is_err());
+
+        let mut advertisement = crate::create_client_advertisement("node-1".to_string(), "10.0.0.5".to_string(), None, String::new());
+        advertisement.timestamp = 1000;
+        let node = |node_id: &str| (node_id == "node-1").then(|| advertisement.clone());
+
+        // 6 + 2 AVAX/h for an hour crosses 80% of 10; the node goes quiet after 300 s
+        assert!(store.due(1200, &node).unwrap().is_empty());
+        let alerts = store.due(1000 + 3600, &node).unwrap();
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].message, "Spent 8.00 of its 10.00 AVAX budget");
+        assert_eq!(alerts[1].priority, 5);
+        assert!(store.due(1000 + 7200, &node).unwrap().is_empty());
+        // Renewing keeps what was sent
+        store.subscribe(subscription).unwrap();
+        assert!(store.due(1000 + 7200, &node).unwrap().is_empty());
+
+        let report = serde_json::to_string(&FinishedReport { job_id: "job-1".to_string(), outcome: "Completed".to_string() }).unwrap();
+        assert_eq!(route("POST", FINISHED_PATH, &report, &store).0, "200 OK");
+        let alerts = store.due(9000, &node).unwrap();
+        assert_eq!((alerts.len(), alerts[0].message.as_str()), (1, "Completed on node node-1"));
+        let json: serde_json::Value = serde_json::from_str(&serde_json::to_string(&alerts[0]).unwrap()).unwrap();
+        assert!(json.get("relay").is_none() && json["topic"] == settings.topic.as_str());
+        assert!(store.subscriptions().is_empty());
+        std::fs::remove_file(store.path()).unwrap();
+    }
+}
@@ -0,0 +1,256 @@
+//! API quotas at the coordinator
+//! Every caller, identified by its IP address, draws from a token bucket that holds `burst`
+//! requests and refills at `per_minute`. A caller with an empty bucket gets 429 with Retry-After.
+//! The operator can give an address its own limits through the admin API, which takes a bearer
+//! token kept next to the quota file.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+pub const ADMIN_PATH: &str = "/api/admin/quotas";
+
+/// Environment variable with the admin token for `update_quota`
+pub const ADMIN_TOKEN_ENV: &str = "ERYZAA_COORDINATOR_TOKEN";
+
+/// Admin token file, beside the quota file
+pub const TOKEN_FILE: &str = "coordinator-token";
+
+/// Callers tracked before buckets that have refilled are dropped
+const MAX_BUCKETS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct QuotaLimits {
+    pub per_minute: f64, // Sustained rate
+    pub burst: f64,      // Requests a caller with a full bucket can make at once
+}
+
+impl Default for QuotaLimits {
+    fn default() -> Self {
+        Self { per_minute: 60.0, burst: 20.0 }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct QuotaConfig {
+    pub default: QuotaLimits,
+    #[serde(default)]
+    pub overrides: BTreeMap<String, QuotaLimits>, // By caller address
+}
+
+impl QuotaConfig {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize quota config: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write quota config {}: {}", path.display(), e))
+    }
+
+    pub fn limits_for(&self, identity: &str) -> QuotaLimits {
+        self.overrides.get(identity).copied().unwrap_or(self.default)
+    }
+}
+
+/// Change posted to the admin API
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuotaUpdate {
+    pub identity: Option<String>,    // None changes the default
+    pub limits: Option<QuotaLimits>, // None drops the caller's override
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: f64, // Unix seconds
+}
+
+/// Buckets of every caller, shared by the coordinator's connections
+#[derive(Debug, Clone)]
+pub struct Quotas {
+    path: PathBuf,
+    admin_token: String,
+    config: Arc<Mutex<QuotaConfig>>,
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
+}
+
+impl Quotas {
+    /// Quotas from the file at `path`, with the admin token beside it
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        let admin_token = admin_token(&path.with_file_name(TOKEN_FILE))?;
+        let config = QuotaConfig::load_from(&path);
+        Ok(Self::new(path, config, admin_token))
+    }
+
+    pub fn new(path: impl Into<PathBuf>, config: QuotaConfig, admin_token: String) -> Self {
+        Self {
+            path: path.into(),
+            admin_token,
+            config: Arc::new(Mutex::new(config)),
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn config(&self) -> QuotaConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Spend one of `identity`'s tokens at `now`; otherwise the seconds until one is back
+    pub fn take(&self, identity: &str, now: f64) -> Result<(), u64> {
+        let config = self.config();
+        let limits = config.limits_for(identity);
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_BUCKETS {
+            // A full bucket is the same as none
+            buckets.retain(|caller, bucket| {
+                let limits = config.limits_for(caller);
+                bucket.tokens + (now - bucket.updated) * limits.per_minute / 60.0 < limits.burst
+            });
+        }
+
+        let bucket = buckets
+            .entry(identity.to_string())
+            .or_insert(Bucket { tokens: limits.burst, updated: now });
+        let refill = (now - bucket.updated).max(0.0) * limits.per_minute / 60.0;
+        bucket.tokens = (bucket.tokens + refill).min(limits.burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        if limits.per_minute <= 0.0 {
+            return Err(60);
+        }
+        Err(((1.0 - bucket.tokens) * 60.0 / limits.per_minute).ceil().max(1.0) as u64)
+    }
+
+    /// Apply an admin change and keep it in the quota file
+    pub fn update(&self, update: QuotaUpdate) -> Result<String, String> {
+        if let Some(limits) = update.limits {
+            if !(limits.per_minute >= 0.0 && limits.burst >= 1.0) {
+                return Err("Quota needs per_minute of at least 0 and burst of at least 1".to_string());
+            }
+        }
+
+        let mut config = self.config.lock().unwrap();
+        let mut buckets = self.buckets.lock().unwrap();
+        let message = match (update.identity, update.limits) {
+            (None, Some(limits)) => {
+                config.default = limits;
+                buckets.clear();
+                "Default quota updated".to_string()
+            }
+            (None, None) => return Err("The default quota cannot be removed".to_string()),
+            (Some(identity), Some(limits)) => {
+                config.overrides.insert(identity.clone(), limits);
+                buckets.remove(&identity);
+                format!("Quota for {} updated", identity)
+            }
+            (Some(identity), None) => match config.overrides.remove(&identity) {
+                Some(_) => {
+                    buckets.remove(&identity);
+                    format!("{} is back on the default quota", identity)
+                }
+                None => return Err(format!("{} has no quota of its own", identity)),
+            },
+        };
+        // Callers whose limits changed start again with a full bucket
+        config.save_to(&self.path)?;
+        Ok(message)
+    }
+}
+
+/// Status, content type and body for a request to the admin API
+pub fn admin(method: &str, authorization: &str, body: &str, quotas: &Quotas) -> (&'static str, &'static str, String) {
+    if authorization.strip_prefix("Bearer ") != Some(quotas.admin_token.as_str()) {
+        return ("401 Unauthorized", "text/plain", "Missing or wrong token\n".to_string());
+    }
+    match method {
+        "GET" => ("200 OK", "application/json", serde_json::to_string(&quotas.config()).unwrap_or_default()),
+        "POST" => match serde_json::from_str::<QuotaUpdate>(body) {
+            Ok(update) => match quotas.update(update) {
+                Ok(message) => ("200 OK", "text/plain", message + "\n"),
+                Err(e) => ("400 Bad Request", "text/plain", e + "\n"),
+            },
+            Err(e) => ("400 Bad Request", "text/plain", format!("Malformed quota update: {}\n", e)),
+        },
+        _ => ("405 Method Not Allowed", "text/plain", "Only GET and POST are supported\n".to_string()),
+    }
+}
+
+/// Shared secret for the admin API, created on first use
+pub fn admin_token(path: &Path) -> Result<String, String> {
+    if let Ok(token) = std::fs::read_to_string(path) {
+        return Ok(token.trim().to_string());
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    std::fs::write(path, &token).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("Failed to restrict {}: {}", path.display(), e))?;
+    }
+    Ok(token)
+}
+
+/// Quotas set on the coordinator at `base_url`
+pub fn fetch_quotas(base_url: &str, token: &str) -> Result<QuotaConfig, String> {
+    let body = crate::market::request(base_url, "GET", ADMIN_PATH, None, Some(token))?;
+    serde_json::from_str(&body).map_err(|e| format!("Failed to parse quota config: {}", e))
+}
+
+/// Change a quota on the coordinator at `base_url`
+pub fn update_quota(base_url: &str, token: &str, update: &QuotaUpdate) -> Result<String, String> {
+    let body = serde_json::to_string(update).map_err(|e| format!("Failed to serialize quota update: {}", e))?;
+    crate::market::request(base_url, "POST", ADMIN_PATH, Some(&body), Some(token)).map(|answer| answer.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_buckets() {
+        let path = std::env::temp_dir().join(format!("eryzaa-quotas-{}.json", std::process::id()));
+        let config = QuotaConfig { default: QuotaLimits { per_minute: 30.0, burst: 3.0 }, ..Default::default() };
+        let quotas = Quotas::new(&path, config, "s3cret".to_string());
+
+        for _ in 0..3 {
+            assert_eq!(quotas.take("10.0.0.1", 100.0), Ok(()));
+        }
+        assert_eq!(quotas.take("10.0.0.1", 100.0), Err(2)); // One token every 2 s
+        assert_eq!(quotas.take("10.0.0.2", 100.0), Ok(()));
+        assert_eq!(quotas.take("10.0.0.1", 102.0), Ok(()));
+
+        let raise = r#"{"identity": "10.0.0.1", "limits": {"per_minute": 600, "burst": 50}}"#;
+        assert_eq!(admin("POST", "Bearer guess", raise, &quotas).0, "401 Unauthorized");
+        assert_eq!(admin("POST", "Bearer s3cret", raise, &quotas).0, "200 OK");
+        assert_eq!(QuotaConfig::load_from(&path).limits_for("10.0.0.1").burst, 50.0);
+        assert!((0..50).all(|_| quotas.take("10.0.0.1", 103.0).is_ok()));
+
+        let drop = r#"{"identity": "10.0.0.1", "limits": null}"#;
+        assert_eq!(admin("POST", "Bearer s3cret", drop, &quotas).0, "200 OK");
+        assert_eq!(admin("POST", "Bearer s3cret", drop, &quotas).0, "400 Bad Request");
+        let (_, _, body) = admin("GET", "Bearer s3cret", "", &quotas);
+        assert_eq!(serde_json::from_str::<QuotaConfig>(&body).unwrap(), QuotaConfig::load_from(&path));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
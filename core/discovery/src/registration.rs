@@ -0,0 +1,273 @@
+//! Node registration at the coordinator
+//! A new node registers itself during onboarding and gets a short claim code to show its owner.
+//! The owner enters the code in the rental GUI, which claims the node for their account. Codes
+//! run out after `CLAIM_CODE_TTL` and work once; registering again issues a fresh one. The claim
+//! hands the owner a secret that later owner-only requests must carry, as an account name alone
+//! proves nothing.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::handoffs::secret_hash;
+use crate::market;
+
+pub const NODES_PATH: &str = "/api/nodes";
+pub const CLAIMS_PATH: &str = "/api/claims";
+
+/// How long a claim code can be used, in seconds
+pub const CLAIM_CODE_TTL: u64 = 24 * 3600;
+
+/// Letters of a claim code, without ones that read alike (0/O, 1/I/L)
+const CLAIM_ALPHABET: &[u8] = b"23456789ABCDEFGHJKMNPQRSTUVWXYZ";
+
+/// What a node tells the coordinator about itself
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NodeRegistration {
+    pub node_id: String,
+    pub hostname: String,
+    #[serde(default)]
+    pub overlay_ip: Option<String>,
+    #[serde(default)]
+    pub price_per_hour: Option<f32>,
+    #[serde(default)]
+    pub cpu_score: Option<f64>, // Million operations per second over all cores, from the onboarding benchmark
+    #[serde(default)]
+    pub gpus: Vec<String>, // Model names
+    #[serde(default)]
+    pub public_key: Option<String>, // Hex ed25519 key the node signs its advertisements with
+}
+
+/// A registered node as the coordinator keeps it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RegisteredNode {
+    pub registration: NodeRegistration,
+    pub registered_at: u64, // Unix seconds
+    #[serde(default)]
+    pub claim_code: Option<String>, // Until the node is claimed
+    #[serde(default)]
+    pub claim_expires_at: u64,
+    #[serde(default)]
+    pub owner: Option<String>, // Account that claimed the node, e.g. a wallet address
+    #[serde(default)]
+    pub owner_secret_hash: Option<String>, // Hex SHA-256 of the secret issued with the claim
+}
+
+impl RegisteredNode {
+    /// Whether `owner` claimed this node and `secret` is the one the claim issued
+    pub fn owned_by(&self, owner: &str, secret: &str) -> bool {
+        !owner.trim().is_empty()
+            && self.owner.as_deref() == Some(owner.trim())
+            && self.owner_secret_hash.as_deref().is_some_and(|hash| hash == secret_hash(secret.trim()))
+    }
+}
+
+/// The coordinator's answer to a registration
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RegistrationReceipt {
+    pub claim_code: String,
+    pub expires_at: u64,
+    #[serde(default)]
+    pub owner: Option<String>, // Set when the node was claimed before
+}
+
+/// An owner claiming a node with the code it showed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ClaimRequest {
+    pub claim_code: String,
+    pub owner: String,
+}
+
+/// What the owner gets for claiming a node
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ClaimReceipt {
+    #[serde(flatten)]
+    pub registration: NodeRegistration,
+    pub owner_secret: String, // Proves ownership to the coordinator later; shown only this once
+}
+
+/// A fresh claim code such as "K7QM-3XPD"
+pub fn new_claim_code() -> String {
+    let bytes = uuid::Uuid::new_v4().into_bytes();
+    let letters: String = bytes[..8]
+        .iter()
+        .map(|b| CLAIM_ALPHABET[*b as usize % CLAIM_ALPHABET.len()] as char)
+        .collect();
+    format!("{}-{}", &letters[..4], &letters[4..])
+}
+
+/// A claim code as typed by someone: any case, with or without the dash or spaces
+pub fn normalize_claim_code(code: &str) -> String {
+    let letters: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    match letters.len() {
+        8 => format!("{}-{}", &letters[..4], &letters[4..]),
+        _ => letters,
+    }
+}
+
+/// Registered nodes, kept in one JSON file
+#[derive(Debug, Clone)]
+pub struct RegistrationStore {
+    path: PathBuf,
+}
+
+impl RegistrationStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Keep a node's registration and issue it a claim code, unless it is claimed already
+    pub fn register(&self, registration: NodeRegistration, now: u64) -> Result<RegistrationReceipt, String> {
+        if registration.node_id.trim().is_empty() {
+            return Err("Registration needs a node id".to_string());
+        }
+
+        let mut nodes = self.load();
+        let known = nodes.iter().find(|node| node.registration.node_id == registration.node_id);
+        // Whoever holds a claimed node's key speaks for it, at the rendezvous too
+        if let Some(public_key) = known.filter(|node| node.owner.is_some()).and_then(|node| node.registration.public_key.as_ref()) {
+            if registration.public_key.as_ref() != Some(public_key) {
+                return Err(format!("{} is claimed and registered with another key", registration.node_id));
+            }
+        }
+        let (owner, owner_secret_hash) = known
+            .map(|node| (node.owner.clone(), node.owner_secret_hash.clone()))
+            .unwrap_or_default();
+        nodes.retain(|node| node.registration.node_id != registration.node_id);
+        let claim_code = new_claim_code();
+        let expires_at = now + CLAIM_CODE_TTL;
+        nodes.push(RegisteredNode {
+            registration,
+            registered_at: now,
+            claim_code: owner.is_none().then(|| claim_code.clone()),
+            claim_expires_at: expires_at,
+            owner: owner.clone(),
+            owner_secret_hash,
+        });
+        self.save(&nodes)?;
+        Ok(RegistrationReceipt { claim_code, expires_at, owner })
+    }
+
+    /// Give the node showing `claim_code` to `owner`, with the secret that proves it
+    pub fn claim(&self, request: &ClaimRequest, now: u64) -> Result<ClaimReceipt, String> {
+        if request.owner.trim().is_empty() {
+            return Err("Claim needs an owner account".to_string());
+        }
+
+        let code = normalize_claim_code(&request.claim_code);
+        let mut nodes = self.load();
+        let node = nodes
+            .iter_mut()
+            .find(|node| node.claim_code.as_deref() == Some(code.as_str()))
+            .ok_or_else(|| format!("No node is waiting for claim code {}", code))?;
+        if node.claim_expires_at <= now {
+            return Err(format!("Claim code {} has expired; run onboarding on the node again", code));
+        }
+        let owner_secret = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+        node.claim_code = None;
+        node.owner = Some(request.owner.trim().to_string());
+        node.owner_secret_hash = Some(secret_hash(&owner_secret));
+        let registration = node.registration.clone();
+        self.save(&nodes)?;
+        Ok(ClaimReceipt { registration, owner_secret })
+    }
+
+    pub fn nodes(&self) -> Vec<RegisteredNode> {
+        self.load()
+    }
+
+    fn load(&self) -> Vec<RegisteredNode> {
+        std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, nodes: &[RegisteredNode]) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(nodes)
+            .map_err(|e| format!("Failed to serialize registered nodes: {}", e))?;
+        std::fs::write(&self.path, content)
+            .map_err(|e| format!("Failed to write registered nodes {}: {}", self.path.display(), e))
+    }
+}
+
+/// Handle a registration posted to the coordinator
+pub fn accept_registration(body: &str, store: &RegistrationStore) -> Result<String, String> {
+    let registration: NodeRegistration = serde_json::from_str(body).map_err(|e| format!("Malformed registration: {}", e))?;
+    let receipt = store.register(registration, crate::current_timestamp())?;
+    serde_json::to_string(&receipt).map_err(|e| format!("Failed to serialize receipt: {}", e))
+}
+
+/// Handle a claim posted to the coordinator
+pub fn accept_claim(body: &str, store: &RegistrationStore) -> Result<String, String> {
+    let request: ClaimRequest = serde_json::from_str(body).map_err(|e| format!("Malformed claim: {}", e))?;
+    let receipt = store.claim(&request, crate::current_timestamp())?;
+    serde_json::to_string(&receipt).map_err(|e| format!("Failed to serialize claim receipt: {}", e))
+}
+
+/// Register this node with the coordinator at `base_url`
+pub fn register_node(base_url: &str, registration: &NodeRegistration) -> Result<RegistrationReceipt, String> {
+    let body = serde_json::to_string(registration).map_err(|e| format!("Failed to serialize registration: {}", e))?;
+    let answer = market::request(base_url, "POST", NODES_PATH, Some(&body), None)?;
+    serde_json::from_str(&answer).map_err(|e| format!("Failed to parse registration receipt: {}", e))
+}
+
+/// Claim the node showing `claim_code` for `owner`, returning what it registered and the owner secret
+pub fn claim_node(base_url: &str, claim_code: &str, owner: &str) -> Result<ClaimReceipt, String> {
+    let request = ClaimRequest { claim_code: normalize_claim_code(claim_code), owner: owner.to_string() };
+    let body = serde_json::to_string(&request).map_err(|e| format!("Failed to serialize claim: {}", e))?;
+    let answer = market::request(base_url, "POST", CLAIMS_PATH, Some(&body), None)?;
+    serde_json::from_str(&answer).map_err(|e| format!("Failed to parse claim receipt: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_and_claim() {
+        let store = RegistrationStore::new(std::env::temp_dir().join(format!("eryzaa-nodes-{}.json", std::process::id())));
+        let registration = NodeRegistration {
+            node_id: "node-1".to_string(),
+            hostname: "desk".to_string(),
+            overlay_ip: Some("10.242.0.5".to_string()),
+            price_per_hour: Some(2.0),
+            cpu_score: Some(850.0),
+            gpus: vec!["RTX 3090".to_string()],
+            public_key: Some("ab".repeat(32)),
+        };
+        let receipt = store.register(registration.clone(), 1000).unwrap();
+        assert_eq!(receipt.claim_code.len(), 9);
+        assert_eq!(normalize_claim_code(&receipt.claim_code.to_lowercase().replace('-', " ")), receipt.claim_code);
+
+        let claim = |code: &str, now| store.claim(&ClaimRequest { claim_code: code.to_string(), owner: "0xabc".to_string() }, now);
+        assert!(claim(&receipt.claim_code, 1000 + CLAIM_CODE_TTL).unwrap_err().contains("expired"));
+        let claimed = claim(&receipt.claim_code, 2000).unwrap();
+        assert_eq!(claimed.registration.node_id, "node-1");
+        assert!(claim(&receipt.claim_code, 2000).is_err());
+        assert!(store.nodes()[0].owned_by("0xabc", &claimed.owner_secret));
+        assert!(!store.nodes()[0].owned_by("0xabc", "guess"));
+
+        // Registering again keeps the owner and issues no usable code, but only with the same key
+        let hijack = NodeRegistration { public_key: Some("cd".repeat(32)), ..registration.clone() };
+        assert!(store.register(hijack, 3000).unwrap_err().contains("another key"));
+        let again = store.register(registration, 3000).unwrap();
+        assert_eq!(again.owner.as_deref(), Some("0xabc"));
+        assert!(claim(&again.claim_code, 3000).is_err());
+        assert!(store.nodes()[0].owned_by("0xabc", &claimed.owner_secret));
+        assert_eq!(store.nodes().len(), 1);
+        std::fs::remove_file(store.path()).unwrap();
+    }
+}
@@ -0,0 +1,282 @@
+//! Rendezvous on the coordinator
+//! Rental nodes outside the coordinator's broadcast domain post their signed advertisement
+//! packets to /api/rendezvous on every beat, and clients anywhere fetch the packets posted
+//! within the node timeout from the same path. The coordinator lists only nodes an owner has
+//! claimed, signed with the key the node registered, and only so many from each caller; it keeps
+//! the packets in memory. Clients check every packet again, so a coordinator cannot list a node
+//! that did not sign.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::backend::DiscoveryBackend;
+use crate::coordinators::CoordinatorSet;
+use crate::identity;
+use crate::market;
+use crate::registration::RegistrationStore;
+use crate::{NodeAdvertisement, NodeType, NODE_TIMEOUT};
+use eryzaa_protocol::node::decode_advertisement;
+
+pub const RENDEZVOUS_PATH: &str = "/api/rendezvous";
+
+/// Nodes one coordinator lists at once; beyond that only nodes it already lists get through
+const MAX_NODES: usize = 10_000;
+
+/// Nodes listed at once from one address, enough for a site of machines behind one NAT
+const MAX_NODES_PER_CALLER: usize = 64;
+
+/// A packet posted by a node, or one of those a client fetches
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RendezvousPacket {
+    pub packet: String, // Hex discovery packet, exactly as the node signed it
+}
+
+/// Latest packet of a node, and when it came
+#[derive(Debug, Clone)]
+struct Listed {
+    packet: Vec<u8>,
+    caller: String, // Address the packet was posted from
+    received_at: u64,
+}
+
+impl Listed {
+    fn is_fresh(&self, now: u64) -> bool {
+        now.saturating_sub(self.received_at) < NODE_TIMEOUT.as_secs()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RendezvousStore {
+    packets: Arc<Mutex<HashMap<String, Listed>>>,
+}
+
+impl RendezvousStore {
+    /// Keep the signed rental advertisement in `packet`, posted from `caller`; the node it
+    /// came from
+    ///
+    /// The node has to be claimed at this coordinator and sign with the key it registered, so
+    /// no one can list a node id they do not own.
+    pub fn publish(&self, packet: &[u8], caller: &str, registrations: &RegistrationStore, now: u64) -> Result<String, String> {
+        let advertisement = decode_advertisement(packet)?;
+        if advertisement.node_type != NodeType::Rental {
+            return Err("Rendezvous lists rental nodes only".to_string());
+        }
+        if !identity::verify(&advertisement, packet)? {
+            return Err(format!("Advertisement from {} is not signed", advertisement.node_id));
+        }
+        let registered = registrations
+            .nodes()
+            .into_iter()
+            .find(|node| node.registration.node_id == advertisement.node_id)
+            .filter(|node| node.owner.is_some())
+            .ok_or_else(|| format!("{} is not a claimed node; register and claim it first", advertisement.node_id))?;
+        if registered.registration.public_key.is_none() || registered.registration.public_key != advertisement.public_key {
+            return Err(format!("Advertisement from {} is not signed with the key it registered", advertisement.node_id));
+        }
+
+        let mut packets = self.packets.lock().unwrap();
+        packets.retain(|_, listed| listed.is_fresh(now));
+        let from_caller = packets
+            .iter()
+            .filter(|(node_id, listed)| **node_id != advertisement.node_id && listed.caller == caller)
+            .count();
+        if from_caller >= MAX_NODES_PER_CALLER {
+            return Err(format!("{} already lists {} nodes here", caller, from_caller));
+        }
+        if packets.len() >= MAX_NODES && !packets.contains_key(&advertisement.node_id) {
+            return Err("Rendezvous is full, try another coordinator".to_string());
+        }
+        packets.insert(
+            advertisement.node_id.clone(),
+            Listed { packet: packet.to_vec(), caller: caller.to_string(), received_at: now },
+        );
+        Ok(advertisement.node_id)
+    }
+
+    /// Packets posted within the node timeout
+    pub fn packets(&self, now: u64) -> Vec<Vec<u8>> {
+        self.packets
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|listed| listed.is_fresh(now))
+            .map(|listed| listed.packet.clone())
+            .collect()
+    }
+
+    /// Advertisements posted within the node timeout, for the coordinator's own stats
+    pub fn nodes(&self, now: u64) -> Vec<NodeAdvertisement> {
+        self.packets(now).iter().filter_map(|packet| decode_advertisement(packet).ok()).collect()
+    }
+}
+
+pub fn handles(path: &str) -> bool {
+    path.split('?').next() == Some(RENDEZVOUS_PATH)
+}
+
+/// Status, content type and body for a rendezvous request from `caller`
+pub fn route(
+    method: &str,
+    body: &str,
+    caller: &str,
+    store: &RendezvousStore,
+    registrations: &RegistrationStore,
+) -> (&'static str, &'static str, String) {
+    let now = crate::current_timestamp();
+    match method {
+        "GET" => {
+            let packets: Vec<RendezvousPacket> =
+                store.packets(now).iter().map(|packet| RendezvousPacket { packet: hex::encode(packet) }).collect();
+            ("200 OK", "application/json", serde_json::to_string(&packets).unwrap_or_default())
+        }
+        "POST" => {
+            let posted = serde_json::from_str::<RendezvousPacket>(body)
+                .map_err(|e| format!("Malformed rendezvous packet: {}", e))
+                .and_then(|posted| hex::decode(posted.packet.trim()).map_err(|e| format!("Malformed rendezvous packet: {}", e)))
+                .and_then(|packet| store.publish(&packet, caller, registrations, now));
+            match posted {
+                Ok(node_id) => ("201 Created", "text/plain", format!("Listed {}\n", node_id)),
+                Err(e) => ("400 Bad Request", "text/plain", e + "\n"),
+            }
+        }
+        _ => ("405 Method Not Allowed", "text/plain", "Only GET and POST are supported\n".to_string()),
+    }
+}
+
+/// Rendezvous at the coordinator at `base_url`
+pub struct RendezvousBackend {
+    base_url: String,
+}
+
+impl RendezvousBackend {
+    pub fn new(base_url: &str) -> Self {
+        Self { base_url: base_url.trim_end_matches('/').to_string() }
+    }
+}
+
+impl DiscoveryBackend for RendezvousBackend {
+    fn name(&self) -> String {
+        format!("rendezvous at {}", self.base_url)
+    }
+
+    fn announce(&self, packet: &[u8]) -> Result<(), String> {
+        let body = serde_json::to_string(&RendezvousPacket { packet: hex::encode(packet) })
+            .map_err(|e| format!("Failed to serialize rendezvous packet: {}", e))?;
+        market::request(&self.base_url, "POST", RENDEZVOUS_PATH, Some(&body), None).map(|_| ())
+    }
+
+    fn lookup(&self) -> Result<Vec<Vec<u8>>, String> {
+        let body = market::request(&self.base_url, "GET", RENDEZVOUS_PATH, None, None)?;
+        let packets: Vec<RendezvousPacket> =
+            serde_json::from_str(&body).map_err(|e| format!("Failed to parse rendezvous packets: {}", e))?;
+        Ok(packets.iter().filter_map(|posted| hex::decode(&posted.packet).ok()).collect())
+    }
+}
+
+/// A rendezvous backend for each coordinator in `ERYZAA_COORDINATOR_URL`
+pub fn backends_from_env() -> Vec<Box<dyn DiscoveryBackend>> {
+    CoordinatorSet::from_env()
+        .map(|coordinators| {
+            coordinators
+                .urls()
+                .iter()
+                .map(|url| Box::new(RendezvousBackend::new(url)) as Box<dyn DiscoveryBackend>)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registration::{ClaimRequest, NodeRegistration};
+    use crate::{create_client_advertisement, NodeIdentity};
+    use eryzaa_protocol::node::encode_advertisement;
+
+    #[test]
+    fn test_rendezvous() {
+        let store = RendezvousStore::default();
+        let registrations = RegistrationStore::new(std::env::temp_dir().join(format!("eryzaa-rendezvous-nodes-{}.json", std::process::id())));
+        let publish = |packet: &[u8], caller: &str| store.publish(packet, caller, &registrations, 1000);
+        let identity = NodeIdentity::generate();
+        let client = create_client_advertisement("client-1".to_string(), "192.168.1.30".to_string(), None, String::new());
+        let mut rental = NodeAdvertisement { node_id: "rental-1".to_string(), node_type: NodeType::Rental, ..client.clone() };
+        assert!(publish(&encode_advertisement(&rental).unwrap(), "203.0.113.5").unwrap_err().contains("not signed"));
+        let mut signed_client = client;
+        identity.sign(&mut signed_client).unwrap();
+        assert!(publish(&encode_advertisement(&signed_client).unwrap(), "203.0.113.5").unwrap_err().contains("rental nodes only"));
+
+        // Listed once an owner has claimed the node and only with the key it registered
+        identity.sign(&mut rental).unwrap();
+        let packet = encode_advertisement(&rental).unwrap();
+        assert!(publish(&packet, "203.0.113.5").unwrap_err().contains("not a claimed node"));
+        let registration = NodeRegistration {
+            node_id: "rental-1".to_string(),
+            hostname: String::new(),
+            overlay_ip: None,
+            price_per_hour: None,
+            cpu_score: None,
+            gpus: vec![],
+            public_key: Some(identity.public_key()),
+        };
+        let receipt = registrations.register(registration, 900).unwrap();
+        assert!(publish(&packet, "203.0.113.5").unwrap_err().contains("not a claimed node"));
+        let claim = ClaimRequest { claim_code: receipt.claim_code, owner: "0xabc".to_string() };
+        registrations.claim(&claim, 950).unwrap();
+        assert_eq!(publish(&packet, "203.0.113.5").unwrap(), "rental-1");
+        let mut impostor = rental.clone();
+        NodeIdentity::generate().sign(&mut impostor).unwrap();
+        assert!(publish(&encode_advertisement(&impostor).unwrap(), "203.0.113.5").unwrap_err().contains("key it registered"));
+        assert_eq!(store.packets(1010), vec![packet.clone()]);
+        assert!(store.packets(1000 + NODE_TIMEOUT.as_secs()).is_empty());
+
+        // Over HTTP, as the coordinator serves it
+        let body = serde_json::to_string(&RendezvousPacket { packet: hex::encode(&packet) }).unwrap();
+        assert_eq!(route("POST", &body, "203.0.113.5", &store, &registrations).0, "201 Created");
+        assert_eq!(route("POST", "{\"packet\": \"zz\"}", "203.0.113.5", &store, &registrations).0, "400 Bad Request");
+        let (status, _, listed) = route("GET", "", "198.51.100.7", &store, &registrations);
+        assert_eq!(status, "200 OK");
+        let listed: Vec<RendezvousPacket> = serde_json::from_str(&listed).unwrap();
+        assert_eq!(listed, vec![RendezvousPacket { packet: hex::encode(&packet) }]);
+        assert_eq!(store.nodes(crate::current_timestamp())[0].node_id, "rental-1");
+        std::fs::remove_file(registrations.path()).unwrap();
+    }
+
+    #[test]
+    fn test_listings_per_caller_are_capped() {
+        let store = RendezvousStore::default();
+        let registrations = RegistrationStore::new(std::env::temp_dir().join(format!("eryzaa-rendezvous-cap-{}.json", std::process::id())));
+        let client = create_client_advertisement(String::new(), "192.168.1.30".to_string(), None, String::new());
+        let packets: Vec<Vec<u8>> = (0..=MAX_NODES_PER_CALLER)
+            .map(|index| {
+                let identity = NodeIdentity::generate();
+                let node_id = format!("rental-{}", index);
+                let registration = NodeRegistration {
+                    node_id: node_id.clone(),
+                    hostname: String::new(),
+                    overlay_ip: None,
+                    price_per_hour: None,
+                    cpu_score: None,
+                    gpus: vec![],
+                    public_key: Some(identity.public_key()),
+                };
+                let receipt = registrations.register(registration, 900).unwrap();
+                registrations.claim(&ClaimRequest { claim_code: receipt.claim_code, owner: "0xabc".to_string() }, 950).unwrap();
+                let mut rental = NodeAdvertisement { node_id, node_type: NodeType::Rental, ..client.clone() };
+                identity.sign(&mut rental).unwrap();
+                encode_advertisement(&rental).unwrap()
+            })
+            .collect();
+
+        for packet in &packets[..MAX_NODES_PER_CALLER] {
+            store.publish(packet, "203.0.113.5", &registrations, 1000).unwrap();
+        }
+        let last = &packets[MAX_NODES_PER_CALLER];
+        assert!(store.publish(last, "203.0.113.5", &registrations, 1000).unwrap_err().contains("already lists"));
+        // Nodes already listed still get through, as do other callers
+        store.publish(&packets[0], "203.0.113.5", &registrations, 1010).unwrap();
+        store.publish(last, "198.51.100.7", &registrations, 1010).unwrap();
+        std::fs::remove_file(registrations.path()).unwrap();
+    }
+}
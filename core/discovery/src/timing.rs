@@ -0,0 +1,99 @@
+//! Advertisement timing
+//! Nodes re-advertise every `interval_secs`, each beat moved by up to `jitter_percent` so nodes
+//! started together don't keep broadcasting in lockstep. Changes to status, capabilities or
+//! pricing are pushed at once instead of waiting for the next beat, with pushes that arrive
+//! within `min_push_gap_ms` of each other sent as one.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+/// Longest interval that still fits two beats into the time peers keep a silent node
+pub const MAX_INTERVAL_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct AdvertisementTiming {
+    pub interval_secs: u64,
+    pub jitter_percent: u8,   // Each beat lands up to this far before or after the interval
+    pub min_push_gap_ms: u64, // Pushes closer together than this are coalesced
+}
+
+impl Default for AdvertisementTiming {
+    fn default() -> Self {
+        Self { interval_secs: 30, jitter_percent: 20, min_push_gap_ms: 500 }
+    }
+}
+
+impl AdvertisementTiming {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Self>(&content).ok())
+            .filter(|timing| timing.validate().is_ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        self.validate()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize advertisement timing: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write advertisement timing {}: {}", path.display(), e))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_INTERVAL_SECS).contains(&self.interval_secs) {
+            return Err(format!("Advertisement interval must be between 1 and {} seconds", MAX_INTERVAL_SECS));
+        }
+        if self.jitter_percent > 50 {
+            return Err("Advertisement jitter can be at most 50%".to_string());
+        }
+        Ok(())
+    }
+
+    /// Wait before the next beat, for `unit` drawn uniformly from [0, 1)
+    pub fn next_delay(&self, unit: f64) -> Duration {
+        let jitter = f64::from(self.jitter_percent) / 100.0 * (2.0 * unit.clamp(0.0, 1.0) - 1.0);
+        Duration::from_secs_f64(self.interval_secs as f64 * (1.0 + jitter))
+    }
+
+    pub fn min_push_gap(&self) -> Duration {
+        Duration::from_millis(self.min_push_gap_ms)
+    }
+}
+
+/// A uniform draw from [0, 1) for `next_delay`
+pub fn random_unit() -> f64 {
+    (uuid::Uuid::new_v4().as_u128() >> 75) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jittered_interval() {
+        let timing = AdvertisementTiming { interval_secs: 10, jitter_percent: 20, ..Default::default() };
+        assert_eq!(timing.next_delay(0.0), Duration::from_secs(8));
+        assert_eq!(timing.next_delay(0.5), Duration::from_secs(10));
+        assert_eq!(timing.next_delay(1.0), Duration::from_secs(12));
+        assert!((0..100).map(|_| random_unit()).all(|unit| (0.0..1.0).contains(&unit)));
+
+        assert!(AdvertisementTiming { interval_secs: 0, ..timing }.validate().is_err());
+        assert!(AdvertisementTiming { interval_secs: 120, ..timing }.validate().is_err());
+        assert!(AdvertisementTiming { jitter_percent: 80, ..timing }.validate().is_err());
+
+        let path = std::env::temp_dir().join(format!("eryzaa-timing-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{"interval_secs": 5}"#).unwrap();
+        assert_eq!(AdvertisementTiming::load_from(&path), AdvertisementTiming { interval_secs: 5, ..Default::default() });
+        std::fs::write(&path, r#"{"interval_secs": 600}"#).unwrap();
+        assert_eq!(AdvertisementTiming::load_from(&path), AdvertisementTiming::default());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
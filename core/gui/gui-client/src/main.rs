@@ -2,16 +2,37 @@ use eframe::egui;
 use std::process::Command;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
 use tokio::runtime::Runtime;
 use std::collections::HashMap;
 use eryzaa_discovery::{
-    DiscoveryService, NodeAdvertisement, NodeType, NodeStatus,
+    AccessPolicy, DiscoveryService, GpuStack, HardwareProfile, NodeAdvertisement, NodeDetails, NodeType, NodeStatus, Readiness, TrialOffer,
     create_client_advertisement,
 };
+use eryzaa_discovery::clock::{self, ClockStatus};
+use eryzaa_discovery::coordinators::{self, CoordinatorSet};
+use eryzaa_discovery::market::{self, MarketStats, COORDINATOR_URL_ENV};
+use eryzaa_discovery::overlay;
+use eryzaa_discovery::push::{self, AlertKind, PushSettings};
+use eryzaa_discovery::rendezvous;
+use eryzaa_jobs::handoff::HandoffOffer;
+use eryzaa_jobs::onboarding::{self, ClientOnboarding, SelfTest};
+use eryzaa_jobs::scratch::{ScratchRequest, StorageClass};
+use eryzaa_jobs::{cuda, paste, receipt, requirements};
+use eryzaa_jobs::terminal::{self, Platform};
+use eryzaa_jobs::requirements::{JobNeeds, Severity};
+use eryzaa_jobs::transfers::{self, EgressAlertPolicy, TransferLedger};
+use eryzaa_jobs::{CredentialBundle, ExecRequest, FailoverPolicy, HandoffLink, HandoffStore, HistoryEntry, JobHistory, JobManager, JobRecord, JobSpec, JobStatus, JobSubmission, NodeBookmark, NodeCache, OperationQueue, PauseOptions, Profile, ProfileStore, QueuedAction, Snapshot, SshConfigWriter, WorkspaceStore};
 use uuid::Uuid;
 
+/// Image of the plain sessions started with "Deploy Job"
+const INTERACTIVE_IMAGE: &str = "ubuntu:22.04";
+
+/// How long the setup wizard listens for node advertisements
+const NODE_SEARCH_TIME: Duration = Duration::from_secs(10);
+
 pub struct EryzaaClientApp {
     // Connection state
     server_status: Arc<Mutex<ServerStatus>>,
@@ -35,9 +56,76 @@ pub struct EryzaaClientApp {
     // Edge computing state
     gpu_nodes: Vec<GpuNode>,
     active_jobs: Vec<ComputeJob>,
+    known_jobs: HashMap<String, JobRecord>,
+    last_registry_refresh: Option<Instant>,
+    job_history: JobHistory,
+    history_draft: Option<SpecDraft>, // Past job being edited before re-submission
+    
+    // Credential handoff state
+    handoff_offers: Vec<HandoffOffer>,
+    shared_link: Option<HandoffLink>,
+    
+    // Offline state
+    node_cache: NodeCache,
+    queued_operations: usize,
+    nodes_reachable: Arc<Mutex<Option<bool>>>, // None until the first probe finishes
+    replay_notes: Arc<Mutex<Vec<String>>>,
+    last_connectivity_check: Option<Instant>,
+    clock_status: Arc<Mutex<ClockStatus>>, // NTP state of this machine
+    last_clock_check: Option<Instant>,
+    market_stats: Arc<Mutex<Option<MarketStats>>>, // From the coordinator, when one is configured
+    last_market_check: Option<Instant>,
+    
+    // Transfers
+    transfer_ledger: TransferLedger,
+    transfer_status: String,
+    last_transfer_fetch: Option<Instant>,
+    sort_by_carbon: bool,
+    only_functional: bool, // Hide nodes whose readiness checks found something broken
+    expanded_node: Option<String>, // Node whose details are open
+    compare_selection: Vec<String>, // Up to four node ids side by side
+    compare_latency: Arc<Mutex<HashMap<String, Option<u32>>>>, // Connect time in ms, None when unreachable
+    required_gpus: u32,
+    required_memory_gb: u32,
+    max_price_per_hour: f32,
+    prefer_warm: bool, // Favor nodes with an idle container for interactive sessions
+    
+    // Paste channel
+    paste_job: Option<String>,
+    paste_text: String,
+    paste_status: Arc<Mutex<String>>,
+    incoming_files: Arc<Mutex<Vec<String>>>, // Files in the selected job's ~/incoming
+    
+    // Workspace snapshots
+    workspace_job: Option<String>,
+    workspace_label: String,
+    workspace_snapshots: Arc<Mutex<Vec<Snapshot>>>,
+    workspace_status: Arc<Mutex<String>>,
+    
+    // Profile state
+    profiles: ProfileStore,
+    new_profile_name: String,
     
     // Settings
     settings: Settings,
+    accessibility: Accessibility,
+    accessibility_status: String,
+    failover: FailoverPolicy, // Opt-in move of running jobs off dead nodes
+    failover_status: String,
+    push: PushSettings, // Phone alerts the coordinator sends while this app is closed
+    push_status: Arc<Mutex<String>>,
+    last_push_sync: Option<Instant>,
+    
+    // First-run setup
+    onboarding: Arc<Mutex<ClientOnboarding>>, // Of the active profile, saved after each step
+    show_onboarding: bool,
+    onboarding_busy: Arc<Mutex<bool>>, // A step is running in the background
+    onboarding_status: Arc<Mutex<String>>,
+    wallet_import: String,
+    wallet_password: String,
+    overlay_skipped: bool, // The machine reaches nodes without ZeroTier
+    echo_target: String, // host:port of the echo node
+    found_nodes: Arc<Mutex<Option<Vec<NodeAdvertisement>>>>, // Set when the node search ends
     
     // Runtime
     runtime: Arc<Runtime>,
@@ -45,6 +133,12 @@ pub struct EryzaaClientApp {
 
 impl Default for EryzaaClientApp {
     fn default() -> Self {
+        let profiles = ProfileStore::load().unwrap_or_default();
+        let mut settings = Settings::default();
+        settings.wallet_address = profiles.active().wallet_address.clone().unwrap_or_default();
+        let onboarding = ClientOnboarding::load_from(&onboarding::default_onboarding_path());
+        let coordinator_urls = coordinators::parse_urls(&std::env::var(COORDINATOR_URL_ENV).unwrap_or_default());
+        
         Self {
             server_status: Arc::new(Mutex::new(ServerStatus::default())),
             zerotier_ip: String::new(),
@@ -70,7 +164,60 @@ impl Default for EryzaaClientApp {
             selected_dataset: None,
             gpu_nodes: vec![],
             active_jobs: vec![],
-            settings: Settings::default(),
+            known_jobs: HashMap::new(),
+            last_registry_refresh: None,
+            job_history: JobHistory::load_from(&eryzaa_jobs::history::default_history_path()),
+            history_draft: None,
+            handoff_offers: vec![],
+            shared_link: None,
+            node_cache: NodeCache::load_from(&eryzaa_jobs::offline::default_node_cache_path()),
+            queued_operations: 0,
+            nodes_reachable: Arc::new(Mutex::new(None)),
+            replay_notes: Arc::new(Mutex::new(Vec::new())),
+            last_connectivity_check: None,
+            clock_status: Arc::new(Mutex::new(ClockStatus::default())),
+            last_clock_check: None,
+            market_stats: Arc::new(Mutex::new(None)),
+            last_market_check: None,
+            transfer_ledger: TransferLedger::default(),
+            transfer_status: String::new(),
+            last_transfer_fetch: None,
+            sort_by_carbon: false,
+            only_functional: false,
+            expanded_node: None,
+            compare_selection: vec![],
+            compare_latency: Arc::new(Mutex::new(HashMap::new())),
+            required_gpus: 1,
+            required_memory_gb: 0,
+            max_price_per_hour: 10.0,
+            prefer_warm: true,
+            paste_job: None,
+            paste_text: String::new(),
+            paste_status: Arc::new(Mutex::new(String::new())),
+            incoming_files: Arc::new(Mutex::new(vec![])),
+            workspace_job: None,
+            workspace_label: "workspace".to_string(),
+            workspace_snapshots: Arc::new(Mutex::new(WorkspaceStore::for_profile(profiles.active()).list())),
+            workspace_status: Arc::new(Mutex::new(String::new())),
+            profiles,
+            new_profile_name: String::new(),
+            settings,
+            accessibility: Accessibility::load_from(&accessibility_path()),
+            accessibility_status: String::new(),
+            failover: FailoverPolicy::load_from(&eryzaa_jobs::failover::default_policy_path()),
+            failover_status: String::new(),
+            push: PushSettings::load_from(&push_settings_path()),
+            push_status: Arc::new(Mutex::new(String::new())),
+            last_push_sync: None,
+            show_onboarding: onboarding.completed_at.is_none(),
+            onboarding: Arc::new(Mutex::new(onboarding)),
+            onboarding_busy: Arc::new(Mutex::new(false)),
+            onboarding_status: Arc::new(Mutex::new(String::new())),
+            wallet_import: String::new(),
+            wallet_password: String::new(),
+            overlay_skipped: false,
+            echo_target: onboarding::echo_node(&coordinator_urls).unwrap_or_default(),
+            found_nodes: Arc::new(Mutex::new(None)),
             runtime: Arc::new(Runtime::new().unwrap()),
         }
     }
@@ -97,6 +244,7 @@ pub enum Tab {
     SSH,
     ModelTraining,
     EdgeComputing,
+    History,
     Logs,
     Settings,
 }
@@ -107,6 +255,33 @@ impl Default for Tab {
     }
 }
 
+impl Tab {
+    /// Tabs in display order, which is also their Ctrl+1.. shortcut order
+    const ALL: [Tab; 8] = [
+        Tab::Dashboard,
+        Tab::AccessTypes,
+        Tab::SSH,
+        Tab::ModelTraining,
+        Tab::EdgeComputing,
+        Tab::History,
+        Tab::Logs,
+        Tab::Settings,
+    ];
+    
+    fn label(&self) -> &'static str {
+        match self {
+            Tab::Dashboard => "📊 Dashboard",
+            Tab::AccessTypes => "🚀 Access Types",
+            Tab::SSH => "💻 SSH",
+            Tab::ModelTraining => "🧠 AI Training",
+            Tab::EdgeComputing => "⚡ Edge Computing",
+            Tab::History => "🕘 History",
+            Tab::Logs => "📋 Logs",
+            Tab::Settings => "⚙️ Settings",
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum AccessType {
     SSH,
@@ -157,6 +332,61 @@ pub struct GpuNode {
     memory: String,
     status: String,
     price_per_hour: f32,
+    carbon_per_job_g: Option<f32>, // Estimated gCO2 of an average job on the node
+    details: NodeDetails,
+    slots: Option<(u32, u32)>, // Free job slots and the node's maximum
+    clock_offset_ms: Option<i64>, // Node's own offset from NTP time
+    gpu_stack: Option<GpuStack>, // Driver and CUDA versions, checked against job images
+    hardware: HardwareProfile, // GPU and CPU models, NVLink, NVMe and accelerators
+    warm_images: Vec<String>, // Images the node can start a job from in seconds
+    address: Option<(String, u16)>, // SSH endpoint, used to measure latency
+    benchmark_score: Option<f32>,
+    reputation: Option<f32>, // 0-5 stars from past renters
+    access: AccessPolicy, // Reputation or deposit the node asks of clients
+    readiness: Readiness, // Which parts of the node's stack last worked
+    trial: Option<TrialOffer>, // Free session the node gives each client once
+    public_key: Option<String>, // Key the node's advertisements were verified against, None if unsigned
+}
+
+/// Editable copy of a past job spec
+#[derive(Debug, Clone)]
+pub struct SpecDraft {
+    spec: JobSpec,
+    command: String, // Space separated
+    env: String, // One KEY=VALUE per line
+    gpus: String,
+    node_id: Option<String>, // None lets any node take it
+}
+
+impl SpecDraft {
+    fn from_entry(entry: &HistoryEntry) -> Self {
+        Self {
+            command: entry.spec.command.join(" "),
+            env: entry
+                .spec
+                .env
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            gpus: entry.spec.gpus.clone().unwrap_or_default(),
+            node_id: entry.node_id.clone(),
+            spec: entry.spec.clone(),
+        }
+    }
+    
+    fn to_spec(&self) -> JobSpec {
+        let mut spec = self.spec.clone();
+        spec.command = self.command.split_whitespace().map(str::to_string).collect();
+        spec.env = self
+            .env
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .collect();
+        spec.gpus = Some(self.gpus.trim().to_string()).filter(|gpus| !gpus.is_empty());
+        spec
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -251,13 +481,15 @@ impl Default for Settings {
 }
 
 impl EryzaaClientApp {
-    pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
+    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
         let runtime = Arc::new(Runtime::new().expect("Failed to create Tokio runtime"));
         
-        Self {
+        let app = Self {
             runtime,
             ..Default::default()
-        }
+        };
+        app.accessibility.apply(&cc.egui_ctx);
+        app
     }
     
     fn deploy_server(&mut self, mode: DeploymentMode) {
@@ -293,6 +525,16 @@ impl EryzaaClientApp {
                                     if parts.len() > 6 {
                                         let ip = parts[6].split('/').next().unwrap_or("");
                                         if !ip.is_empty() && ip != "-" {
+                                            // Pin the container's host key, read over the local docker channel
+                                            if let Ok(key_output) = Command::new("docker")
+                                                .args(&["exec", "rental-dev", "cat", "/etc/ssh/ssh_host_ed25519_key.pub"])
+                                                .output()
+                                            {
+                                                if key_output.status.success() {
+                                                    let key = String::from_utf8_lossy(&key_output.stdout);
+                                                    let _ = SshConfigWriter::for_current_user().pin_host_key(&[ip.to_string()], &key);
+                                                }
+                                            }
                                             *status.lock().unwrap() = ServerStatus::Running(ip.to_string());
                                             return;
                                         }
@@ -331,16 +573,1067 @@ impl EryzaaClientApp {
         // This function can be simplified or removed
     }
     
-    fn open_ssh_terminal(&self, ip: &str) {
-        let ssh_command = format!(
-            "gnome-terminal -- bash -c 'echo \"Connecting to Eryzaa Server...\"; ssh -o StrictHostKeyChecking=no {}@{}; exec bash'",
-            self.settings.ssh_username, ip
+    fn refresh_job_registry(&mut self) {
+        let due = self
+            .last_registry_refresh
+            .map(|t| t.elapsed() > Duration::from_secs(5))
+            .unwrap_or(true);
+        if !due {
+            return;
+        }
+        self.last_registry_refresh = Some(Instant::now());
+        
+        if let Ok(manager) = JobManager::load_from(&eryzaa_jobs::default_registry_path()) {
+            self.known_jobs = manager
+                .list_jobs()
+                .into_iter()
+                .map(|job| (job.job_id.clone(), job))
+                .collect();
+            
+            let history_path = eryzaa_jobs::history::default_history_path();
+            self.job_history = JobHistory::load_from(&history_path);
+            if self.job_history.refresh_outcomes(&manager) {
+                if let Err(e) = self.job_history.save_to(&history_path) {
+                    self.log_content.push_str(&format!("Failed to save job history: {}\n", e));
+                }
+            }
+        }
+        
+        if let Ok(store) = HandoffStore::load_from(&eryzaa_jobs::handoff::default_store_path()) {
+            self.handoff_offers = store.list_offers();
+        }
+        
+        if let Ok(queue) = OperationQueue::load_from(&eryzaa_jobs::offline::default_queue_path()) {
+            self.queued_operations = queue.len();
+        }
+        
+        self.transfer_ledger = TransferLedger::load_from(&transfers::default_ledger_path());
+        self.fetch_transfers();
+    }
+    
+    /// Pull the transfer entries each node has published for our jobs, every few minutes
+    fn fetch_transfers(&mut self) {
+        let due = self
+            .last_transfer_fetch
+            .map(|t| t.elapsed() > Duration::from_secs(300))
+            .unwrap_or(true);
+        if !due || self.is_offline() {
+            return;
+        }
+        self.last_transfer_fetch = Some(Instant::now());
+        
+        let remote_jobs: Vec<String> = self
+            .known_jobs
+            .values()
+            .filter(|job| job.node_address.is_some())
+            .map(|job| job.job_id.clone())
+            .collect();
+        if remote_jobs.is_empty() {
+            return;
+        }
+        
+        let notes = self.replay_notes.clone();
+        thread::spawn(move || {
+            let registry = eryzaa_jobs::default_registry_path();
+            let Ok(manager) = JobManager::load_from(&registry) else {
+                return;
+            };
+            let path = transfers::default_ledger_path();
+            let mut ledger = TransferLedger::load_from(&path);
+            for job_id in remote_jobs {
+                match manager.fetch_transfers(&job_id) {
+                    Ok(entries) => ledger.merge_job(&job_id, entries),
+                    Err(e) => notes.lock().unwrap().push(format!("Failed to fetch transfers for job {}: {}", job_id, e)),
+                }
+                // Energy figures come from the node's receipt
+                if let Err(e) = manager.fetch_receipt(&job_id) {
+                    notes.lock().unwrap().push(format!("Failed to fetch receipt for job {}: {}", job_id, e));
+                }
+            }
+            if let Err(e) = ledger.save_to(&path) {
+                notes.lock().unwrap().push(e);
+            }
+            if let Err(e) = manager.save_to(&registry) {
+                notes.lock().unwrap().push(e);
+            }
+        });
+    }
+    
+    fn export_transfers(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .set_file_name("eryzaa-transfers.csv")
+            .add_filter("CSV", &["csv"])
+            .save_file()
+        else {
+            return;
+        };
+        
+        self.transfer_status = match std::fs::write(&path, self.transfer_ledger.to_csv()) {
+            Ok(()) => format!("✅ Exported to {}", path.display()),
+            Err(e) => format!("❌ Failed to export: {}", e),
+        };
+    }
+    
+    fn show_transfers(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("📶 Transfers");
+            
+            let today = chrono::Local::now().date_naive();
+            for alert in self.transfer_ledger.egress_alerts(&EgressAlertPolicy::default(), today) {
+                ui.colored_label(egui::Color32::YELLOW, format!("⚠️ {}", alert));
+            }
+            
+            let days = self.transfer_ledger.daily_totals();
+            if days.is_empty() {
+                ui.label("No transfer data from your rentals yet");
+            } else {
+                egui::Grid::new("client_transfers_grid").striped(true).show(ui, |ui| {
+                    ui.strong("Day");
+                    ui.strong("Overlay ↓/↑");
+                    ui.strong("Internet ↓/↑");
+                    ui.strong("Staged ↓");
+                    ui.end_row();
+                    for day in days.iter().rev().take(7) {
+                        ui.label(day.day.map(|d| d.to_string()).unwrap_or_default());
+                        ui.label(format!("{} / {}", transfers::format_bytes(day.overlay_rx), transfers::format_bytes(day.overlay_tx)));
+                        ui.label(format!("{} / {}", transfers::format_bytes(day.internet_rx), transfers::format_bytes(day.internet_tx)));
+                        ui.label(transfers::format_bytes(day.staging_rx));
+                        ui.end_row();
+                    }
+                });
+            }
+            
+            ui.horizontal(|ui| {
+                if ui.button("📤 Export CSV").clicked() {
+                    self.export_transfers();
+                }
+                ui.label(&self.transfer_status);
+            });
+        });
+    }
+    
+    fn is_offline(&self) -> bool {
+        *self.nodes_reachable.lock().unwrap() == Some(false)
+    }
+    
+    /// Re-check NTP sync every ten minutes, off the UI thread
+    fn check_clock(&mut self) {
+        if self.last_clock_check.map(|t| t.elapsed() < Duration::from_secs(600)).unwrap_or(false) {
+            return;
+        }
+        self.last_clock_check = Some(Instant::now());
+        
+        let clock_status = self.clock_status.clone();
+        thread::spawn(move || {
+            *clock_status.lock().unwrap() = clock::local_clock_status();
+        });
+    }
+    
+    /// Refresh coordinator market stats every five minutes when ERYZAA_COORDINATOR_URL is set
+    fn sample_market(&mut self) {
+        if self.last_market_check.map(|t| t.elapsed() < Duration::from_secs(300)).unwrap_or(false) {
+            return;
+        }
+        self.last_market_check = Some(Instant::now());
+        let Some(coordinators) = CoordinatorSet::from_env() else {
+            return;
+        };
+        
+        let market_stats = self.market_stats.clone();
+        thread::spawn(move || {
+            if let Ok(stats) = coordinators.call(market::fetch_stats) {
+                *market_stats.lock().unwrap() = Some(stats);
+            }
+        });
+    }
+    
+    /// Have the coordinator watch running jobs for phone alerts, renewing every five minutes so
+    /// it knows what they have spent
+    fn sync_push_subscriptions(&mut self) {
+        if !self.push.enabled || self.last_push_sync.map(|t| t.elapsed() < Duration::from_secs(300)).unwrap_or(false) {
+            return;
+        }
+        self.last_push_sync = Some(Instant::now());
+        let Some(coordinators) = CoordinatorSet::from_env() else {
+            *self.push_status.lock().unwrap() = format!("❌ Phone alerts need {} set", market::COORDINATOR_URL_ENV);
+            return;
+        };
+        
+        let now = chrono::Utc::now();
+        let subscriptions: Vec<_> = self
+            .known_jobs
+            .values()
+            .filter(|job| job.status == JobStatus::Running)
+            .filter_map(|job| {
+                let node_id = job.node_id.as_deref()?;
+                let spent = job.offered_price.unwrap_or(0.0) * job.billable_seconds(now) as f32 / 3600.0;
+                Some(self.push.subscription(&job.job_id, node_id, job.offered_price, spent, now.timestamp() as u64))
+            })
+            .collect();
+        let push_status = self.push_status.clone();
+        thread::spawn(move || {
+            let failed: Vec<String> = subscriptions
+                .iter()
+                .filter_map(|subscription| coordinators.call(|url| push::subscribe(url, subscription)).err())
+                .collect();
+            *push_status.lock().unwrap() = match failed.first() {
+                Some(e) => format!("❌ {}", e),
+                None => format!("📱 Watching {} running jobs", subscriptions.len()),
+            };
+        });
+    }
+    
+    fn check_connectivity(&mut self) {
+        for note in self.replay_notes.lock().unwrap().drain(..) {
+            self.log_content.push_str(&note);
+            self.log_content.push('\n');
+        }
+        
+        let due = self
+            .last_connectivity_check
+            .map(|t| t.elapsed() > Duration::from_secs(15))
+            .unwrap_or(true);
+        if !due {
+            return;
+        }
+        self.last_connectivity_check = Some(Instant::now());
+        
+        // Probe the nodes we depend on: those running our jobs and those we last saw
+        let mut targets: Vec<(String, u16)> = self
+            .known_jobs
+            .values()
+            .filter_map(|job| job.node_address.clone().map(|ip| (ip, 22)))
+            .collect();
+        let nodes: Vec<(String, Option<String>, u16)> = self
+            .node_cache
+            .nodes
+            .iter()
+            .map(|node| (node.ip_address.clone(), node.zerotier_ip.clone(), node.ssh_port))
+            .collect();
+        
+        let reachable = self.nodes_reachable.clone();
+        let notes = self.replay_notes.clone();
+        let has_queue = self.queued_operations > 0;
+        let failover = self.failover.clone();
+        let workspaces = self.workspace_store();
+        thread::spawn(move || {
+            // Nodes on our own LAN directly, the others through the overlay
+            targets.extend(nodes.iter().map(|(lan_ip, overlay_ip, port)| {
+                let address = overlay::connect_address(lan_ip, overlay_ip.as_deref(), |ip| overlay::answers(ip, *port, Duration::from_secs(2)));
+                (address, *port)
+            }));
+            let online = targets.is_empty()
+                || targets
+                    .iter()
+                    .any(|(host, port)| eryzaa_jobs::offline::is_reachable(host, *port, Duration::from_secs(2)));
+            *reachable.lock().unwrap() = Some(online);
+            
+            if online && has_queue {
+                let queue_path = eryzaa_jobs::offline::default_queue_path();
+                let registry = eryzaa_jobs::default_registry_path();
+                let result = OperationQueue::load_from(&queue_path).and_then(|queue| {
+                    let manager = JobManager::load_from(&registry)?;
+                    let report = queue.replay(|action| manager.execute_queued(action));
+                    manager.save_to(&registry)?;
+                    queue.save_to(&queue_path)?;
+                    Ok(report)
+                });
+                
+                let note = match result {
+                    Ok(report) if report.completed.is_empty() => None,
+                    Ok(report) => Some(format!(
+                        "Replayed {} queued operations, {} still queued",
+                        report.completed.len(),
+                        report.remaining
+                    )),
+                    Err(e) => Some(format!("Failed to replay queued operations: {}", e)),
+                };
+                notes.lock().unwrap().extend(note);
+            }
+            
+            // Jobs whose node went silent move on, if the client opted in
+            if online && failover.enabled {
+                let registry = eryzaa_jobs::default_registry_path();
+                let history_path = eryzaa_jobs::history::default_history_path();
+                let cache = NodeCache::load_from(&eryzaa_jobs::offline::default_node_cache_path());
+                let Ok(manager) = JobManager::load_from(&registry) else {
+                    return;
+                };
+                let mut history = JobHistory::load_from(&history_path);
+                let moved = manager.fail_over_dead_nodes(&mut history, &cache, &workspaces, &failover);
+                if !moved.is_empty() {
+                    for result in [manager.save_to(&registry), history.save_to(&history_path)] {
+                        if let Err(e) = result {
+                            notes.lock().unwrap().push(e);
+                        }
+                    }
+                    notes.lock().unwrap().extend(moved);
+                }
+            }
+        });
+    }
+    
+    fn queue_action(&mut self, action: QueuedAction) {
+        let queue_path = eryzaa_jobs::offline::default_queue_path();
+        let result = OperationQueue::load_from(&queue_path).and_then(|queue| {
+            queue.enqueue(action);
+            queue.save_to(&queue_path)?;
+            Ok(queue.len())
+        });
+        
+        match result {
+            Ok(len) => self.queued_operations = len,
+            Err(e) => self.log_content.push_str(&format!("Failed to queue operation: {}\n", e)),
+        }
+    }
+    
+    fn switch_profile(&mut self, name: &str) {
+        if let Err(e) = self.profiles.switch(name).and_then(|_| self.profiles.save()) {
+            self.log_content.push_str(&format!("Failed to switch profile: {}\n", e));
+            return;
+        }
+        
+        // Everything job-related belongs to the previous profile
+        self.active_jobs.clear();
+        self.known_jobs.clear();
+        self.handoff_offers.clear();
+        self.shared_link = None;
+        self.history_draft = None;
+        self.last_registry_refresh = None;
+        self.refresh_job_registry();
+        
+        let profile = self.profiles.active().clone();
+        self.settings.wallet_address = profile.wallet_address.clone().unwrap_or_default();
+        // Each profile is set up on its own
+        let onboarding = ClientOnboarding::load_from(&onboarding::default_onboarding_path());
+        self.show_onboarding = onboarding.completed_at.is_none();
+        *self.onboarding.lock().unwrap() = onboarding;
+        let jobs: Vec<JobRecord> = self.known_jobs.values().cloned().collect();
+        if let Err(e) = SshConfigWriter::for_current_user().sync(&jobs) {
+            self.log_content.push_str(&format!("Failed to write SSH config: {}\n", e));
+        }
+        self.log_content.push_str(&format!("Switched to profile {}\n", profile.name));
+    }
+    
+    fn create_profile(&mut self) {
+        let name = self.new_profile_name.trim().to_string();
+        let result = self
+            .profiles
+            .create(Profile::new(&name))
+            .and_then(|_| self.profiles.save());
+        
+        match result {
+            Ok(()) => {
+                self.new_profile_name.clear();
+                self.switch_profile(&name);
+            }
+            Err(e) => self.log_content.push_str(&format!("Failed to create profile: {}\n", e)),
+        }
+    }
+    
+    fn toggle_bookmark(&mut self, node_id: &str, label: &str) {
+        let profile = self.profiles.active_mut();
+        if !profile.remove_bookmark(node_id) {
+            profile.bookmark(NodeBookmark {
+                node_id: node_id.to_string(),
+                label: label.to_string(),
+                address: None,
+            });
+        }
+        if let Err(e) = self.profiles.save() {
+            self.log_content.push_str(&format!("Failed to save bookmarks: {}\n", e));
+        }
+    }
+    
+    fn deploy_job_on(&mut self, node_id: &str) {
+        let spec = JobSpec {
+            name: format!("job_{}", self.active_jobs.len() + 1),
+            image: Some(INTERACTIVE_IMAGE.to_string()),
+            ..Default::default()
+        };
+        self.submit_spec(spec, Some(node_id.to_string()), None);
+    }
+    
+    /// Take a node's free trial: an interactive job without GPUs, on the node's terms
+    fn start_trial_on(&mut self, node_id: &str) {
+        let trial = self.gpu_nodes.iter().find(|node| node.id == node_id).and_then(|node| node.trial);
+        if trial.is_none() {
+            self.log_content.push_str(&format!("{} does not offer a free trial\n", node_id));
+            return;
+        }
+        let spec = JobSpec {
+            name: format!("trial_{}", self.active_jobs.len() + 1),
+            image: Some(INTERACTIVE_IMAGE.to_string()),
+            ..Default::default()
+        };
+        self.submit_spec(spec, Some(node_id.to_string()), trial);
+    }
+    
+    /// Submit a job spec, as a free trial on `trial`'s terms if given, and record it in the
+    /// profile's history
+    fn submit_spec(&mut self, spec: JobSpec, node_id: Option<String>, trial: Option<TrialOffer>) {
+        let node = node_id.as_ref().and_then(|id| self.gpu_nodes.iter().find(|node| &node.id == id));
+        let image = spec.image_tag();
+        let mut needs = JobNeeds::declared(&spec);
+        needs.cuda = cuda::local_requirement(&image);
+        let advertised = node_id.as_ref().and_then(|id| self.node_cache.nodes.iter().find(|node| &node.node_id == id));
+        if let Some(advertised) = advertised {
+            let findings = requirements::analyze(&needs, &advertised.capabilities);
+            for finding in &findings {
+                self.log_content.push_str(&format!("{} on {}\n", finding, advertised.node_id));
+            }
+            if findings.iter().any(|f| f.severity == Severity::Blocker) {
+                let fitting: Vec<&str> = requirements::suggest(&needs, &self.node_cache.nodes)
+                    .iter()
+                    .take(3)
+                    .map(|node| node.node_id.as_str())
+                    .collect();
+                let hint = if fitting.is_empty() { "no cached node fits".to_string() } else { format!("try {}", fitting.join(", ")) };
+                self.log_content.push_str(&format!("Job not submitted; {}\n", hint));
+                return;
+            }
+        } else if spec.gpus.is_some() {
+            let stack = node.and_then(|node| node.gpu_stack.as_ref());
+            if let Err(e) = cuda::check(&image, needs.cuda.as_deref(), stack) {
+                self.log_content.push_str(&format!("Job not submitted: {}\n", e));
+                return;
+            }
+        }
+        let node_name = node
+            .map(|node| node.name.clone())
+            .unwrap_or_else(|| "any node".to_string());
+        let offline = self.is_offline();
+        let job = ComputeJob {
+            id: format!("job_{}", &Uuid::new_v4().to_string()[..8]),
+            name: match trial {
+                Some(_) => format!("🎁 {} on {} (free trial)", spec.name, node_name),
+                None => format!("{} on {}", spec.name, node_name),
+            },
+            status: if offline { "Queued" } else { "Running" }.to_string(),
+            progress: 0.0,
+            estimated_time: match trial {
+                Some(trial) => format!("{}m, free", trial.minutes),
+                None => "2h 30m".to_string(),
+            },
+        };
+        if offline {
+            // A posted deposit goes with one job only; the reputation goes with every job
+            let access_path = eryzaa_jobs::default_registry_path().with_file_name("client-access.json");
+            let mut access = eryzaa_payments::ClientAccess::load_from(&access_path);
+            let deposit = access.deposit.take();
+            if deposit.is_some() {
+                if let Err(e) = access.save_to(&access_path) {
+                    self.log_content.push_str(&format!("Failed to save client access: {}\n", e));
+                }
+            }
+            let submission = JobSubmission {
+                job_id: job.id.clone(),
+                client_id: self.client_id(),
+                image: spec.image_tag(),
+                command: spec.command.clone(),
+                node_id: node_id.clone(),
+                node_address: None,
+                ssh_user: None,
+                trial,
+                deposit,
+                reputation: access.reputation,
+            };
+            self.queue_action(QueuedAction::Submit(Box::new(submission)));
+        }
+        
+        self.job_history.record(&job.id, spec, node_id);
+        if let Err(e) = self.job_history.save_to(&eryzaa_jobs::history::default_history_path()) {
+            self.log_content.push_str(&format!("Failed to save job history: {}\n", e));
+        }
+        self.active_jobs.push(job);
+    }
+    
+    fn toggle_compare(&mut self, node_id: &str) {
+        if let Some(pos) = self.compare_selection.iter().position(|id| id == node_id) {
+            self.compare_selection.remove(pos);
+            return;
+        }
+        if self.compare_selection.len() >= 4 {
+            return;
+        }
+        self.compare_selection.push(node_id.to_string());
+        
+        // Measure latency once per node as the SSH connect time
+        let Some((host, port)) = self.gpu_nodes.iter().find(|node| node.id == node_id).and_then(|node| node.address.clone()) else {
+            return;
+        };
+        if self.compare_latency.lock().unwrap().contains_key(node_id) {
+            return;
+        }
+        let latency = self.compare_latency.clone();
+        let node_id = node_id.to_string();
+        thread::spawn(move || {
+            let started = Instant::now();
+            let connected = (host.as_str(), port)
+                .to_socket_addrs()
+                .ok()
+                .and_then(|mut addrs| addrs.next())
+                .map(|addr| TcpStream::connect_timeout(&addr, Duration::from_secs(2)).is_ok())
+                .unwrap_or(false);
+            let ms = connected.then(|| started.elapsed().as_millis() as u32);
+            latency.lock().unwrap().insert(node_id, ms);
+        });
+    }
+    
+    fn show_node_comparison(&mut self, ui: &mut egui::Ui) {
+        let nodes: Vec<GpuNode> = self
+            .compare_selection
+            .iter()
+            .filter_map(|id| self.gpu_nodes.iter().find(|node| &node.id == id).cloned())
+            .collect();
+        let latency = self.compare_latency.lock().unwrap().clone();
+        let mut rent = None;
+        
+        ui.group(|ui| {
+            ui.heading("⚖ Compare Nodes");
+            ui.label("Best value in each row is highlighted");
+            
+            egui::Grid::new("node_comparison").striped(true).show(ui, |ui| {
+                ui.label("");
+                for node in &nodes {
+                    ui.strong(&node.name);
+                }
+                ui.end_row();
+                
+                ui.label("Status");
+                for node in &nodes {
+                    ui.label(&node.status);
+                }
+                ui.end_row();
+                
+                let gpus: Vec<Option<f32>> = nodes.iter().map(|n| Some(n.gpu_count as f32)).collect();
+                compare_row(ui, "GPUs", &gpus, true, |v| format!("{}", v as u32));
+                let memory: Vec<Option<f32>> = nodes.iter().map(|n| Some(memory_gb(&n.memory) as f32)).collect();
+                compare_row(ui, "GPU memory", &memory, true, |v| format!("{} GB", v as u32));
+                let price: Vec<Option<f32>> = nodes.iter().map(|n| Some(n.price_per_hour)).collect();
+                compare_row(ui, "Price", &price, false, |v| format!("{:.1} AVAX/h", v));
+                let ping: Vec<Option<f32>> = nodes
+                    .iter()
+                    .map(|n| latency.get(&n.id).copied().flatten().map(|ms| ms as f32))
+                    .collect();
+                compare_row(ui, "Latency", &ping, false, |v| format!("{} ms", v as u32));
+                let benchmark: Vec<Option<f32>> = nodes.iter().map(|n| n.benchmark_score).collect();
+                compare_row(ui, "Benchmark", &benchmark, true, |v| format!("{:.0}", v));
+                let reputation: Vec<Option<f32>> = nodes.iter().map(|n| n.reputation).collect();
+                compare_row(ui, "Reputation", &reputation, true, |v| format!("{:.1} ★", v));
+                let carbon: Vec<Option<f32>> = nodes.iter().map(|n| n.carbon_per_job_g).collect();
+                compare_row(ui, "Carbon per job", &carbon, false, |v| receipt::format_carbon(v as f64));
+                let slots: Vec<Option<f32>> = nodes.iter().map(|n| n.slots.map(|(free, _)| free as f32)).collect();
+                compare_row(ui, "Free job slots", &slots, true, |v| format!("{}", v as u32));
+            });
+            
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Requirements:");
+                ui.add(egui::DragValue::new(&mut self.required_gpus).clamp_range(0..=64).prefix("GPUs ≥ "));
+                ui.add(egui::DragValue::new(&mut self.required_memory_gb).clamp_range(0..=2048).prefix("memory ≥ ").suffix(" GB"));
+                ui.add(egui::DragValue::new(&mut self.max_price_per_hour).speed(0.1).clamp_range(0.0..=100.0).prefix("price ≤ "));
+                if let Some(median) = self.market_stats.lock().unwrap().as_ref().and_then(|stats| stats.median_price_per_hour) {
+                    ui.label(format!("Market median {:.2} AVAX/h", median))
+                        .on_hover_text("Median asking price of the rental nodes the coordinator sees");
+                }
+                ui.checkbox(&mut self.prefer_warm, "⚡ Prefer warm start")
+                    .on_hover_text("Pick a node with an idle container ready, even if it costs more, so the session opens in seconds");
+            });
+            
+            let cheapest = nodes
+                .iter()
+                .filter(|n| n.status == "Available" && n.slots.map(|(free, _)| free > 0).unwrap_or(true))
+                .filter(|n| n.gpu_count >= self.required_gpus && memory_gb(&n.memory) >= self.required_memory_gb)
+                .filter(|n| n.price_per_hour <= self.max_price_per_hour)
+                .filter(|n| n.readiness.is_functional(self.required_gpus > 0))
+                .min_by(|a, b| {
+                    let cold = |n: &GpuNode| self.prefer_warm && !n.warm_images.iter().any(|image| image == INTERACTIVE_IMAGE);
+                    cold(a).cmp(&cold(b)).then(a.price_per_hour.total_cmp(&b.price_per_hour))
+                });
+            match cheapest {
+                Some(node) => {
+                    if ui.button(format!("💸 Rent cheapest that meets requirements ({})", node.name)).clicked() {
+                        rent = Some(node.id.clone());
+                    }
+                }
+                None => {
+                    ui.weak("No compared node meets the requirements");
+                }
+            }
+        });
+        
+        if let Some(node_id) = rent {
+            self.deploy_job_on(&node_id);
+        }
+    }
+    
+    fn show_profile_menu(&mut self, ui: &mut egui::Ui) {
+        let active = self.profiles.active().name.clone();
+        let mut switch_to = None;
+        let mut create = false;
+        
+        ui.menu_button(format!("👤 {}", active), |ui| {
+            for profile in self.profiles.list() {
+                let label = match &profile.organization {
+                    Some(org) => format!("{} ({})", profile.name, org),
+                    None => profile.name.clone(),
+                };
+                if ui.selectable_label(profile.name == active, label).clicked() {
+                    switch_to = Some(profile.name.clone());
+                    ui.close_menu();
+                }
+            }
+            
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut self.new_profile_name);
+                if ui.button("➕ New").clicked() {
+                    create = true;
+                    ui.close_menu();
+                }
+            });
+        });
+        
+        if let Some(name) = switch_to {
+            if name != active {
+                self.switch_profile(&name);
+            }
+        }
+        if create {
+            self.create_profile();
+        }
+    }
+    
+    fn share_job_access(&mut self, job_id: &str) {
+        let Some(job) = self.known_jobs.get(job_id) else {
+            self.log_content.push_str(&format!("Job '{}' is not in the local job registry\n", job_id));
+            return;
+        };
+        
+        let mut bundle = CredentialBundle::for_job(job);
+        if let Err(e) = bundle.attach_job_key(job) {
+            self.log_content.push_str(&format!("Sharing job {} without an SSH key: {}\n", job_id, e));
+        }
+        
+        let store_path = eryzaa_jobs::handoff::default_store_path();
+        let mut log = String::new();
+        let result = HandoffStore::load_from(&store_path).and_then(|store| {
+            let link = store.issue(&bundle, chrono::Duration::minutes(15))?;
+            match CoordinatorSet::from_env() {
+                Some(coordinators) => {
+                    if let Err(e) = store.relay(&link.id, &coordinators) {
+                        log = format!("Link for job {} only works on this machine: {}\n", job_id, e);
+                    }
+                }
+                None => log = format!("Link for job {} only works on this machine: no coordinator configured\n", job_id),
+            }
+            store.save_to(&store_path)?;
+            self.handoff_offers = store.list_offers();
+            Ok(link)
+        });
+        
+        self.log_content.push_str(&log);
+        match result {
+            Ok(link) => self.shared_link = Some(link),
+            Err(e) => self.log_content.push_str(&format!("Failed to share job {}: {}\n", job_id, e)),
+        }
+    }
+    
+    fn revoke_handoff(&mut self, id: &str) {
+        let store_path = eryzaa_jobs::handoff::default_store_path();
+        let job = self
+            .handoff_offers
+            .iter()
+            .find(|offer| offer.id == id)
+            .and_then(|offer| self.known_jobs.get(&offer.job_id))
+            .cloned();
+        let result = HandoffStore::load_from(&store_path).and_then(|store| {
+            let revoked = store.revoke_everywhere(id, job.as_ref());
+            store.save_to(&store_path)?;
+            self.handoff_offers = store.list_offers();
+            revoked
+        });
+        
+        match result {
+            Ok(()) => {
+                if self.shared_link.as_ref().map(|l| l.id == id).unwrap_or(false) {
+                    self.shared_link = None;
+                }
+                self.log_content.push_str(&format!("Revoked handoff {}\n", id));
+            }
+            Err(e) => self.log_content.push_str(&format!("Failed to revoke handoff {}: {}\n", id, e)),
+        }
+    }
+    
+    /// Who this client is to nodes: the id made during setup, else the profile's name
+    fn client_id(&self) -> String {
+        let onboarding = self.onboarding.lock().unwrap();
+        if onboarding.client_id.is_empty() {
+            return self.profiles.active().name.clone();
+        }
+        onboarding.client_id.clone()
+    }
+    
+    /// Run a setup step in the background; the state it leaves is saved whether or not it worked
+    fn run_onboarding_step(&self, work: impl FnOnce(&mut ClientOnboarding) -> Result<(), String> + Send + 'static) {
+        let (onboarding, busy, status) = (self.onboarding.clone(), self.onboarding_busy.clone(), self.onboarding_status.clone());
+        *busy.lock().unwrap() = true;
+        thread::spawn(move || {
+            let mut state = onboarding.lock().unwrap().clone();
+            let result = work(&mut state);
+            let saved = state.save_to(&onboarding::default_onboarding_path());
+            *onboarding.lock().unwrap() = state;
+            *status.lock().unwrap() = result.and(saved).err().unwrap_or_default();
+            *busy.lock().unwrap() = false;
+        });
+    }
+    
+    fn save_onboarding(&mut self) {
+        if let Err(e) = self.onboarding.lock().unwrap().save_to(&onboarding::default_onboarding_path()) {
+            *self.onboarding_status.lock().unwrap() = e;
+        }
+    }
+    
+    /// Listen for rental nodes, which fill the node browser when setup finishes
+    fn find_nodes(&self) {
+        let state = self.onboarding.lock().unwrap().clone();
+        let network_id = self.settings.zerotier_network_id.clone();
+        let (busy, status, found) = (self.onboarding_busy.clone(), self.onboarding_status.clone(), self.found_nodes.clone());
+        *busy.lock().unwrap() = true;
+        thread::spawn(move || {
+            let (lan_ip, _) = overlay::local_addresses(&network_id);
+            let advertisement = create_client_advertisement(state.client_id, lan_ip, state.overlay_ip, network_id);
+            let nodes = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| e.into())
+                .and_then(|runtime| {
+                    let mut discovery = DiscoveryService::new(advertisement)?;
+                    discovery.keep_node_keys(&node_keys_path());
+                    // Nodes outside this network are listed at the coordinators
+                    for backend in rendezvous::backends_from_env() {
+                        discovery.add_backend(backend);
+                    }
+                    runtime.block_on(async {
+                        discovery.start()?;
+                        tokio::time::sleep(NODE_SEARCH_TIME).await;
+                        discovery.shutdown().await;
+                        Ok::<_, Box<dyn std::error::Error>>(discovery.get_available_rentals())
+                    })
+                })
+                .map_err(|e: Box<dyn std::error::Error>| format!("Failed to search for nodes: {}", e));
+            match nodes {
+                Ok(nodes) => *found.lock().unwrap() = Some(nodes),
+                Err(e) => *status.lock().unwrap() = e,
+            }
+            *busy.lock().unwrap() = false;
+        });
+    }
+    
+    /// End setup with the nodes found, showing them in the node browser
+    fn finish_onboarding(&mut self, nodes: Vec<NodeAdvertisement>) {
+        let count = nodes.len();
+        if count > 0 {
+            self.node_cache.update(nodes);
+            if let Err(e) = self.node_cache.save_to(&eryzaa_jobs::offline::default_node_cache_path()) {
+                self.log_content.push_str(&format!("{}\n", e));
+            }
+            self.gpu_nodes.clear();
+        }
+        self.onboarding.lock().unwrap().completed_at = Some(chrono::Utc::now().timestamp() as u64);
+        self.save_onboarding();
+        self.show_onboarding = false;
+        self.selected_tab = Tab::EdgeComputing;
+        self.log_content.push_str(&format!("Setup finished, {} nodes found\n", count));
+    }
+    
+    fn show_onboarding_window(&mut self, ctx: &egui::Context) {
+        if !self.show_onboarding {
+            return;
+        }
+        if let Some(nodes) = self.found_nodes.lock().unwrap().take() {
+            self.finish_onboarding(nodes);
+            return;
+        }
+        
+        let state = self.onboarding.lock().unwrap().clone();
+        if let Some(address) = &state.wallet_address {
+            if self.settings.wallet_address.is_empty() {
+                self.settings.wallet_address = address.clone();
+                self.save_settings();
+            }
+        }
+        let busy = *self.onboarding_busy.lock().unwrap();
+        let steps = [
+            ("Identity and SSH key", state.ssh_key.is_some()),
+            ("Wallet", state.wallet_done()),
+            ("Overlay network", state.overlay_ip.is_some() || self.overlay_skipped || state.self_test_passed()),
+            ("Connectivity self-test", state.self_test_passed()),
+            ("Find nodes", false),
+        ];
+        let current = steps.iter().position(|(_, done)| !done).unwrap_or(steps.len() - 1);
+        
+        let mut open = true;
+        egui::Window::new("👋 Set Up Eryzaa")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                for (i, (label, done)) in steps.iter().enumerate() {
+                    let mark = if *done { "✅" } else if i == current { "▶" } else { "⭕" };
+                    ui.label(format!("{} {}. {}", mark, i + 1, label));
+                }
+                ui.separator();
+                
+                ui.add_enabled_ui(!busy, |ui| match current {
+                    0 => {
+                        ui.label("Nodes know you by your client id; your SSH key signs you in to the jobs you rent.");
+                        if ui.button("🔑 Create Identity and Key").clicked() {
+                            self.run_onboarding_step(|state| {
+                                state.ensure_identity();
+                                let path = SshConfigWriter::for_current_user().default_identity();
+                                onboarding::generate_ssh_key(&path, &format!("eryzaa-{}", state.client_id))?;
+                                state.ssh_key = Some(path);
+                                Ok(())
+                            });
+                        }
+                    }
+                    1 => {
+                        ui.label("Jobs are paid from a wallet on the Avalanche C-Chain.");
+                        ui.horizontal(|ui| {
+                            let label = ui.label("New wallet password:");
+                            ui.add(egui::TextEdit::singleline(&mut self.wallet_password).password(true)).labelled_by(label.id);
+                            let strong = self.wallet_password.len() >= 8;
+                            let create = ui.add_enabled(strong, egui::Button::new("👛 Create Wallet"));
+                            if create.on_disabled_hover_text("Use at least 8 characters").clicked() {
+                                let password = std::mem::take(&mut self.wallet_password);
+                                let dir = self.profiles.active().data_dir().join("wallets");
+                                self.run_onboarding_step(move |state| {
+                                    state.wallet_address = Some(create_wallet(&dir, &password)?);
+                                    Ok(())
+                                });
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            let label = ui.label("Existing address:");
+                            ui.text_edit_singleline(&mut self.wallet_import).labelled_by(label.id);
+                            let valid = onboarding::is_wallet_address(self.wallet_import.trim());
+                            if ui.add_enabled(valid, egui::Button::new("📥 Use Address")).clicked() {
+                                self.onboarding.lock().unwrap().wallet_address = Some(self.wallet_import.trim().to_string());
+                                self.save_onboarding();
+                            }
+                        });
+                        if ui.button("Skip for now").clicked() {
+                            self.onboarding.lock().unwrap().wallet_skipped = true;
+                            self.save_onboarding();
+                        }
+                    }
+                    2 => {
+                        ui.label(format!("Nodes are reached over ZeroTier network {}.", self.settings.zerotier_network_id));
+                        ui.horizontal(|ui| {
+                            if ui.button("🌐 Join Network").clicked() {
+                                let network_id = self.settings.zerotier_network_id.clone();
+                                *self.onboarding_status.lock().unwrap() =
+                                    "Joining; approve this machine in the network if it waits for an address".to_string();
+                                self.run_onboarding_step(move |state| {
+                                    state.overlay_ip = Some(overlay::join(&network_id, Duration::from_secs(60))?);
+                                    Ok(())
+                                });
+                            }
+                            if ui.button("Skip, my nodes are on this LAN").clicked() {
+                                self.overlay_skipped = true;
+                            }
+                        });
+                    }
+                    3 => {
+                        ui.label("Check that traffic gets through by bouncing a message off an echo node.");
+                        ui.horizontal(|ui| {
+                            let label = ui.label("Echo node:");
+                            ui.text_edit_singleline(&mut self.echo_target).labelled_by(label.id);
+                            let target = self.echo_target.trim().to_string();
+                            if ui.add_enabled(!target.is_empty(), egui::Button::new("📡 Run Self-Test")).clicked() {
+                                self.run_onboarding_step(move |state| {
+                                    let result = onboarding::echo_test(&target, Duration::from_secs(5));
+                                    state.self_test = Some(SelfTest {
+                                        echo_node: target,
+                                        round_trip_ms: result.as_ref().ok().copied(),
+                                        error: result.as_ref().err().cloned(),
+                                        tested_at: chrono::Utc::now().timestamp() as u64,
+                                    });
+                                    result.map(|_| ())
+                                });
+                            }
+                        });
+                        if let Some(SelfTest { error: Some(error), .. }) = &state.self_test {
+                            ui.colored_label(egui::Color32::RED, format!("❌ {}", error));
+                        }
+                    }
+                    _ => {
+                        if let Some(SelfTest { echo_node, round_trip_ms: Some(ms), .. }) = &state.self_test {
+                            ui.colored_label(egui::Color32::GREEN, format!("✅ {} answered in {} ms", echo_node, ms));
+                        }
+                        ui.label(format!("Listen {} seconds for nodes advertising on the network.", NODE_SEARCH_TIME.as_secs()));
+                        ui.horizontal(|ui| {
+                            if ui.button("🔍 Find Nodes").clicked() {
+                                *self.onboarding_status.lock().unwrap() = "Listening for nodes...".to_string();
+                                self.find_nodes();
+                            }
+                            if ui.button("Finish").clicked() {
+                                *self.found_nodes.lock().unwrap() = Some(vec![]);
+                            }
+                        });
+                    }
+                });
+                
+                let status = self.onboarding_status.lock().unwrap().clone();
+                if busy || !status.is_empty() {
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        if busy {
+                            ui.spinner();
+                        }
+                        ui.label(status);
+                    });
+                }
+            });
+        // Closing puts setup off until the next start, or Settings
+        if !open {
+            self.show_onboarding = false;
+        }
+    }
+    
+    fn show_handoff_window(&mut self, ctx: &egui::Context) {
+        let Some(link) = self.shared_link.clone() else {
+            return;
+        };
+        
+        let mut open = !ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape));
+        let mut revoke = false;
+        egui::Window::new("📤 Share Job Access")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("Scan or open this link on the other device. It works once and expires in 15 minutes.");
+                ui.add_space(5.0);
+                
+                match link.qr_modules() {
+                    Ok(modules) => {
+                        let module_size = 4.0;
+                        let quiet_zone = 4.0 * module_size;
+                        let side = modules.len() as f32 * module_size + 2.0 * quiet_zone;
+                        let (rect, response) = ui.allocate_exact_size(egui::vec2(side, side), egui::Sense::hover());
+                        response.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Other, "QR code of the access link below"));
+                        let painter = ui.painter();
+                        painter.rect_filled(rect, 0.0, egui::Color32::WHITE);
+                        for (y, row) in modules.iter().enumerate() {
+                            for (x, dark) in row.iter().enumerate() {
+                                if *dark {
+                                    let min = rect.min + egui::vec2(quiet_zone + x as f32 * module_size, quiet_zone + y as f32 * module_size);
+                                    painter.rect_filled(
+                                        egui::Rect::from_min_size(min, egui::vec2(module_size, module_size)),
+                                        0.0,
+                                        egui::Color32::BLACK,
+                                    );
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        ui.colored_label(egui::Color32::RED, e);
+                    }
+                }
+                
+                ui.add_space(5.0);
+                ui.monospace(link.to_string());
+                ui.horizontal(|ui| {
+                    let copy = ui.button("📋 Copy Link");
+                    // Land keyboard users in the dialog rather than behind it
+                    if ui.memory(|m| m.focus().is_none()) {
+                        copy.request_focus();
+                    }
+                    if copy.clicked() {
+                        ui.output_mut(|o| o.copied_text = link.to_string());
+                    }
+                    if ui.button("🚫 Revoke").clicked() {
+                        revoke = true;
+                    }
+                });
+            });
+        
+        if revoke {
+            self.revoke_handoff(&link.id);
+        } else if !open {
+            self.shared_link = None;
+        }
+    }
+    
+    fn sync_host_names(&mut self) {
+        // Nodes found on the network, then the jobs we know of
+        let entries = eryzaa_discovery::names::merge_entries(
+            self.node_cache.nodes.iter().map(eryzaa_discovery::names::node_entry).collect(),
+            self.known_jobs.values().flat_map(|job| job.host_entries()),
         );
         
-        let _ = Command::new("sh")
-            .arg("-c")
-            .arg(&ssh_command)
-            .spawn();
+        let hosts_path = eryzaa_discovery::names::system_hosts_path();
+        match eryzaa_discovery::names::sync_hosts_file(&hosts_path, &entries) {
+            Ok(()) => self.log_content.push_str(&format!("Synced {} names into {}\n", entries.len(), hosts_path.display())),
+            Err(e) => self.log_content.push_str(&format!("Failed to sync DNS names: {}\n", e)),
+        }
+    }
+    
+    fn open_job_shell(&mut self, job_id: &str) {
+        let manager = match JobManager::load_from(&eryzaa_jobs::default_registry_path()) {
+            Ok(manager) => manager,
+            Err(e) => {
+                self.log_content.push_str(&format!("Failed to load job registry: {}\n", e));
+                return;
+            }
+        };
+        
+        let shell_line = match manager.get_job(job_id) {
+            Some(record) => eryzaa_jobs::exec::exec_command(&record, &ExecRequest::shell(job_id))
+                .map(|command| Platform::current().command_line(&command)),
+            None => Err(format!("Job '{}' is not in the local job registry", job_id)),
+        };
+        
+        let opened = shell_line.and_then(|line| terminal::launch(&format!("Opening shell in job {}...", job_id), &line));
+        if let Err(e) = opened {
+            self.log_content.push_str(&format!("Cannot open shell in job: {}\n", e));
+        }
+    }
+    
+    fn set_job_paused(&mut self, job_id: &str, pause: bool) {
+        let registry = eryzaa_jobs::default_registry_path();
+        let result = JobManager::load_from(&registry).and_then(|manager| {
+            if pause {
+                let options = PauseOptions { release_gpu: self.settings.enable_gpu };
+                manager.pause_job(job_id, &options)?;
+            } else {
+                manager.resume_job(job_id)?;
+            }
+            manager.save_to(&registry)
+        });
+        
+        match result {
+            Ok(()) => {
+                if let Some(job) = self.active_jobs.iter_mut().find(|j| j.id == job_id) {
+                    job.status = if pause { "Paused" } else { "Running" }.to_string();
+                }
+            }
+            Err(e) => {
+                self.log_content.push_str(&format!("Failed to {} job {}: {}\n", if pause { "pause" } else { "resume" }, job_id, e));
+            }
+        }
+    }
+    
+    fn open_ssh_terminal(&mut self, ip: &str) {
+        let host_key_args = SshConfigWriter::for_current_user().host_key_args(ip);
+        let line = Platform::current().ssh_snippet(&self.settings.ssh_username, ip, &host_key_args);
+        if let Err(e) = terminal::launch("Connecting to Eryzaa Server...", &line) {
+            self.log_content.push_str(&format!("Cannot open terminal: {}\n", e));
+        }
     }
 }
 
@@ -348,19 +1641,38 @@ impl eframe::App for EryzaaClientApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Update every second
         ctx.request_repaint_after(Duration::from_secs(1));
+        self.refresh_job_registry();
+        self.check_connectivity();
+        self.check_clock();
+        self.sample_market();
+        self.sync_push_subscriptions();
+        
+        if let Some(tab) = tab_shortcut(ctx, &Tab::ALL, &self.selected_tab) {
+            self.selected_tab = tab;
+        }
         
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
                 ui.heading("🚀 Eryzaa Client");
                 ui.separator();
                 
-                ui.selectable_value(&mut self.selected_tab, Tab::Dashboard, "📊 Dashboard");
-                ui.selectable_value(&mut self.selected_tab, Tab::AccessTypes, "🚀 Access Types");
-                ui.selectable_value(&mut self.selected_tab, Tab::SSH, "💻 SSH");
-                ui.selectable_value(&mut self.selected_tab, Tab::ModelTraining, "🧠 AI Training");
-                ui.selectable_value(&mut self.selected_tab, Tab::EdgeComputing, "⚡ Edge Computing");
-                ui.selectable_value(&mut self.selected_tab, Tab::Logs, "📋 Logs");
-                ui.selectable_value(&mut self.selected_tab, Tab::Settings, "⚙️ Settings");
+                for (i, tab) in Tab::ALL.iter().enumerate() {
+                    ui.selectable_value(&mut self.selected_tab, tab.clone(), tab.label())
+                        .on_hover_text(format!("Ctrl+{}", i + 1));
+                }
+                ui.separator();
+                self.show_profile_menu(ui);
+                if self.is_offline() {
+                    ui.colored_label(egui::Color32::YELLOW, format!("📴 Offline ({} queued)", self.queued_operations))
+                        .on_hover_text("Nodes are unreachable; submissions and cancellations are queued and replayed automatically");
+                } else if self.queued_operations > 0 {
+                    ui.label(format!("🔁 {} queued", self.queued_operations));
+                }
+                if let Some(warning) = self.clock_status.lock().unwrap().warning() {
+                    ui.colored_label(egui::Color32::YELLOW, "🕒 Clock drift")
+                        .on_hover_text(format!("{}. Job expiry and billing compare clocks across machines.", warning));
+                }
+                self.show_cost_tickers(ui);
             });
         });
         
@@ -371,14 +1683,34 @@ impl eframe::App for EryzaaClientApp {
                 Tab::SSH => self.show_ssh(ui),
                 Tab::ModelTraining => self.show_model_training(ui),
                 Tab::EdgeComputing => self.show_edge_computing(ui),
+                Tab::History => self.show_history(ui),
                 Tab::Logs => self.show_logs(ui),
                 Tab::Settings => self.show_settings(ui),
             }
         });
+        
+        self.show_handoff_window(ctx);
+        self.show_onboarding_window(ctx);
     }
 }
 
 impl EryzaaClientApp {
+    /// What each running rental or job has run up so far, at the rate agreed with its node
+    fn show_cost_tickers(&self, ui: &mut egui::Ui) {
+        let now = chrono::Utc::now();
+        let mut running: Vec<&JobRecord> = self.known_jobs.values().filter(|job| job.status == JobStatus::Running).collect();
+        running.sort_by_key(|job| job.created_at);
+        for job in running {
+            ui.separator();
+            let ticker = job.ticker(now);
+            ui.label(ticker.to_string()).on_hover_text(format!(
+                "Job {} on {}\nEnergy as the node metered it in its last receipt",
+                job.job_id,
+                job.node_id.as_deref().unwrap_or("this machine")
+            ));
+        }
+    }
+
     fn show_dashboard(&mut self, ui: &mut egui::Ui) {
         ui.heading("📊 Eryzaa Dashboard");
         ui.separator();
@@ -483,7 +1815,20 @@ impl EryzaaClientApp {
                     ui.heading(format!("{}", self.active_jobs.len()));
                 });
             });
+            let receipts: Vec<_> = self.known_jobs.values().map(|job| job.receipt(chrono::Utc::now())).collect();
+            let energy_kwh: f64 = receipts.iter().map(|r| r.energy_kwh).sum();
+            let carbon_g: f64 = receipts.iter().filter_map(|r| r.carbon_g()).sum();
+            ui.group(|ui| {
+                ui.vertical_centered(|ui| {
+                    ui.label("Energy Used");
+                    ui.heading(format!("{:.2} kWh", energy_kwh));
+                    ui.label(format!("🌱 {}", receipt::format_carbon(carbon_g)));
+                });
+            });
         });
+        
+        ui.add_space(10.0);
+        self.show_transfers(ui);
     }
     
     fn show_access_types(&mut self, ui: &mut egui::Ui) {
@@ -710,7 +2055,53 @@ impl EryzaaClientApp {
                 ui.group(|ui| {
                     ui.heading("🖥️ Available GPU Nodes");
                     
-                    if self.gpu_nodes.is_empty() {
+                    if self.is_offline() {
+                        ui.colored_label(
+                            egui::Color32::YELLOW,
+                            format!("📴 Offline - showing cached nodes ({})", self.node_cache.age_label(chrono::Utc::now())),
+                        );
+                    } else if self.node_cache.is_stale(chrono::Utc::now(), chrono::Duration::minutes(10)) {
+                        ui.label(format!("⏳ Node list {}", self.node_cache.age_label(chrono::Utc::now())));
+                    }
+                    
+                    if self.gpu_nodes.is_empty() && !self.node_cache.nodes.is_empty() {
+                        self.gpu_nodes = self
+                            .node_cache
+                            .nodes
+                            .iter()
+                            .map(|node| GpuNode {
+                                id: node.node_id.clone(),
+                                name: node.node_id.clone(),
+                                gpu_count: node.capabilities.gpu_count,
+                                memory: format!("{}GB", node.capabilities.gpu_memory_gb),
+                                status: format!("{:?}", node.status),
+                                price_per_hour: node.price_per_hour.unwrap_or(0.0),
+                                carbon_per_job_g: node.carbon_per_job_g(),
+                                details: node.details.clone(),
+                                slots: node.slots(),
+                                clock_offset_ms: node.clock_offset_ms,
+                                gpu_stack: node.capabilities.gpu_stack.clone(),
+                                hardware: node.capabilities.hardware.clone(),
+                                warm_images: node.warm_images.clone(),
+                                address: Some((
+                                    node.zerotier_ip.clone().unwrap_or_else(|| node.ip_address.clone()),
+                                    node.ssh_port,
+                                )),
+                                // Not advertised yet
+                                benchmark_score: None,
+                                reputation: None,
+                                access: node.access.clone(),
+                                readiness: node.readiness.clone(),
+                                trial: node.trial,
+                                public_key: node.public_key.clone(),
+                            })
+                            .collect();
+                    }
+                    
+                    let set_up = self.onboarding.lock().unwrap().completed_at.is_some();
+                    if self.gpu_nodes.is_empty() && set_up {
+                        ui.label("🔍 No nodes found yet. They appear here as they advertise on the network.");
+                    } else if self.gpu_nodes.is_empty() {
                         // Add some sample nodes for demo
                         self.gpu_nodes = vec![
                             GpuNode {
@@ -720,6 +2111,27 @@ impl EryzaaClientApp {
                                 memory: "320GB".to_string(),
                                 status: "Available".to_string(),
                                 price_per_hour: 4.5,
+                                carbon_per_job_g: Some(1850.0),
+                                details: NodeDetails {
+                                    description: "Datacenter rack, A100 SXM with NVSwitch".to_string(),
+                                    photo_url: None,
+                                    metadata: vec![
+                                        ("NVLink".to_string(), "yes".to_string()),
+                                        ("Internet".to_string(), "10 Gbps".to_string()),
+                                    ],
+                                },
+                                slots: Some((3, 8)),
+                                clock_offset_ms: None,
+                                gpu_stack: None,
+                                hardware: HardwareProfile::default(),
+                                warm_images: vec![],
+                                address: None,
+                                benchmark_score: Some(9120.0),
+                                reputation: Some(4.8),
+                                access: AccessPolicy::default(),
+                                readiness: Readiness::default(),
+                                trial: None,
+                                public_key: None,
                             },
                             GpuNode {
                                 id: "node2".to_string(),
@@ -728,6 +2140,24 @@ impl EryzaaClientApp {
                                 memory: "96GB".to_string(),
                                 status: "Available".to_string(),
                                 price_per_hour: 2.8,
+                                carbon_per_job_g: Some(420.0),
+                                details: NodeDetails {
+                                    description: "Home lab on solar, water cooled".to_string(),
+                                    photo_url: None,
+                                    metadata: vec![("Internet".to_string(), "1 Gbps fiber".to_string())],
+                                },
+                                slots: Some((1, 4)),
+                                clock_offset_ms: None,
+                                gpu_stack: None,
+                                hardware: HardwareProfile::default(),
+                                warm_images: vec![],
+                                address: None,
+                                benchmark_score: Some(6350.0),
+                                reputation: Some(4.5),
+                                access: AccessPolicy::default(),
+                                readiness: Readiness::default(),
+                                trial: Some(TrialOffer { minutes: 15, cpu_percent: 25 }),
+                                public_key: None,
                             },
                             GpuNode {
                                 id: "node3".to_string(),
@@ -736,42 +2166,182 @@ impl EryzaaClientApp {
                                 memory: "512GB".to_string(),
                                 status: "Busy".to_string(),
                                 price_per_hour: 6.2,
+                                carbon_per_job_g: None,
+                                details: NodeDetails::default(),
+                                slots: Some((0, 4)),
+                                clock_offset_ms: None,
+                                gpu_stack: None,
+                                hardware: HardwareProfile::default(),
+                                warm_images: vec![],
+                                address: None,
+                                benchmark_score: Some(4870.0),
+                                reputation: Some(3.9),
+                                access: AccessPolicy::default(),
+                                readiness: Readiness::default(),
+                                trial: None,
+                                public_key: None,
                             },
                         ];
                     }
                     
+                    ui.checkbox(&mut self.only_functional, "✅ Only fully functional nodes")
+                        .on_hover_text("Hide nodes that report a broken overlay, sshd, Docker, GPU runtime or payout setup");
+                    if ui.checkbox(&mut self.sort_by_carbon, "🌱 Lowest carbon per job first").changed() && self.sort_by_carbon {
+                        // Nodes that do not report carbon go last
+                        self.gpu_nodes.sort_by(|a, b| {
+                            a.carbon_per_job_g
+                                .unwrap_or(f32::MAX)
+                                .total_cmp(&b.carbon_per_job_g.unwrap_or(f32::MAX))
+                        });
+                    }
+                    
+                    let bookmarked: Vec<String> = self
+                        .profiles
+                        .active()
+                        .bookmarks
+                        .iter()
+                        .map(|b| b.node_id.clone())
+                        .collect();
+                    let mut bookmark_toggles = Vec::new();
+                    let mut toggled_details = None;
+                    let mut deploy_requests = Vec::new();
+                    let mut trial_requests = Vec::new();
+                    let mut compare_toggles = Vec::new();
+                    
                     egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
                         for node in &self.gpu_nodes {
+                            if self.only_functional && !node.readiness.is_functional(node.gpu_count > 0) {
+                                continue;
+                            }
                             ui.group(|ui| {
                                 ui.horizontal(|ui| {
+                                    let star = if bookmarked.contains(&node.id) { "⭐" } else { "☆" };
+                                    if icon_button(ui, star, "Bookmark in this profile").clicked() {
+                                        bookmark_toggles.push((node.id.clone(), node.name.clone()));
+                                    }
                                     ui.label(&node.name);
                                     match node.status.as_str() {
                                         "Available" => ui.colored_label(egui::Color32::GREEN, "🟢 Available"),
                                         "Busy" => ui.colored_label(egui::Color32::RED, "🔴 Busy"),
                                         _ => ui.colored_label(egui::Color32::YELLOW, "🟡 Unknown"),
                                     };
+                                    if let Some((free, max)) = node.slots {
+                                        ui.label(format!("{}/{} job slots free", free, max));
+                                    }
+                                    match &node.public_key {
+                                        Some(public_key) => {
+                                            ui.colored_label(egui::Color32::LIGHT_GREEN, "🔏 Signed")
+                                                .on_hover_text(format!("Advertisements verified against node key {}…", &public_key[..16.min(public_key.len())]));
+                                        }
+                                        None => {
+                                            ui.colored_label(egui::Color32::YELLOW, "⚠ Unverified")
+                                                .on_hover_text("This node runs a protocol older than v10 and does not sign its advertisements, so anyone on the network could have sent this listing");
+                                        }
+                                    }
+                                    let problems = node.readiness.problems();
+                                    if !problems.is_empty() {
+                                        ui.colored_label(egui::Color32::YELLOW, "⚠ Not fully functional")
+                                            .on_hover_text(problems.join("\n"));
+                                    }
+                                    if !node.warm_images.is_empty() {
+                                        ui.colored_label(egui::Color32::LIGHT_BLUE, "⚡ Warm start")
+                                            .on_hover_text(format!("Idle container ready for {}", node.warm_images.join(", ")));
+                                    }
+                                    if let Some(offset_ms) = node.clock_offset_ms.filter(|o| o.abs() > clock::DRIFT_WARNING_MS) {
+                                        ui.colored_label(egui::Color32::YELLOW, format!("🕒 Clock {}", clock::describe_offset(offset_ms)))
+                                            .on_hover_text("The node's clock is off from NTP time, so job expiry times it reports may be shifted");
+                                    }
+                                    let mut compared = self.compare_selection.contains(&node.id);
+                                    let full = self.compare_selection.len() >= 4;
+                                    if ui
+                                        .add_enabled(compared || !full, egui::Checkbox::new(&mut compared, "⚖ Compare"))
+                                        .changed()
+                                    {
+                                        compare_toggles.push(node.id.clone());
+                                    }
                                 });
                                 ui.label(format!("GPUs: {} | Memory: {}", node.gpu_count, node.memory));
+                                let hardware = node.hardware.summary();
+                                if !hardware.is_empty() {
+                                    ui.label(format!("🔧 {}", hardware));
+                                }
+                                if let Some(stack) = &node.gpu_stack {
+                                    ui.label(format!(
+                                        "Driver {} | CUDA {} | cuDNN {}",
+                                        stack.driver_version,
+                                        stack.cuda_version.as_deref().unwrap_or("?"),
+                                        stack.cudnn_version.as_deref().unwrap_or("none")
+                                    ));
+                                }
                                 ui.label(format!("Price: {:.1} AVAX/hour", node.price_per_hour));
+                                if let Some(trial) = &node.trial {
+                                    ui.colored_label(egui::Color32::LIGHT_GREEN, format!("🎁 {}", trial.label()))
+                                        .on_hover_text("Once per client, to check latency and the environment before paying");
+                                }
+                                match (node.access.min_reputation, node.access.deposit) {
+                                    (None, None) => {}
+                                    (Some(min), Some(deposit)) => {
+                                        ui.label(format!("🛡 Reputation {:.1}+ or a {:.2} AVAX deposit per job", min, deposit));
+                                    }
+                                    (Some(min), None) => {
+                                        ui.label(format!("🛡 Reputation {:.1}+ required", min));
+                                    }
+                                    (None, Some(deposit)) => {
+                                        ui.label(format!("🛡 {:.2} AVAX deposit per job, refunded when it ends", deposit));
+                                    }
+                                }
+                                match node.carbon_per_job_g {
+                                    Some(grams) => ui.label(format!("🌱 ~{} per job", receipt::format_carbon(grams as f64))),
+                                    None => ui.weak("🌱 Carbon not reported"),
+                                };
                                 
-                                if node.status == "Available" {
-                                    if ui.button("🚀 Deploy Job").clicked() {
-                                        // Deploy job to this node
-                                        let job = ComputeJob {
-                                            id: format!("job_{}", self.active_jobs.len() + 1),
-                                            name: format!("Job on {}", node.name),
-                                            status: "Running".to_string(),
-                                            progress: 0.0,
-                                            estimated_time: "2h 30m".to_string(),
-                                        };
-                                        self.active_jobs.push(job);
+                                let expanded = self.expanded_node.as_deref() == Some(node.id.as_str());
+                                if !node.details.is_empty() {
+                                    if ui.small_button(if expanded { "▾ Details" } else { "▸ Details" }).clicked() {
+                                        toggled_details = Some(node.id.clone());
+                                    }
+                                    if expanded {
+                                        show_node_details(ui, &node.id, &node.details);
                                     }
                                 }
+                                
+                                if node.status == "Available" {
+                                    ui.horizontal(|ui| {
+                                        if ui.button("🚀 Deploy Job").clicked() {
+                                            deploy_requests.push(node.id.clone());
+                                        }
+                                        if node.trial.is_some() && ui.button("🎁 Free Trial").clicked() {
+                                            trial_requests.push(node.id.clone());
+                                        }
+                                    });
+                                }
                             });
                             ui.add_space(5.0);
                         }
                     });
+                    
+                    for (node_id, label) in bookmark_toggles {
+                        self.toggle_bookmark(&node_id, &label);
+                    }
+                    if let Some(node_id) = toggled_details {
+                        self.expanded_node = if self.expanded_node.as_ref() == Some(&node_id) { None } else { Some(node_id) };
+                    }
+                    
+                    for node_id in compare_toggles {
+                        self.toggle_compare(&node_id);
+                    }
+                    for node_id in deploy_requests {
+                        self.deploy_job_on(&node_id);
+                    }
+                    for node_id in trial_requests {
+                        self.start_trial_on(&node_id);
+                    }
                 });
+                
+                if self.compare_selection.len() >= 2 {
+                    ui.add_space(10.0);
+                    self.show_node_comparison(ui);
+                }
             });
             
             ui.separator();
@@ -786,6 +2356,9 @@ impl EryzaaClientApp {
                     } else {
                         egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
                             let mut jobs_to_remove = Vec::new();
+                            let mut shell_job = None;
+                            let mut share_job = None;
+                            let mut pause_toggles = Vec::new();
                             
                             for (i, job) in self.active_jobs.iter_mut().enumerate() {
                                 ui.group(|ui| {
@@ -797,9 +2370,34 @@ impl EryzaaClientApp {
                                     ui.add(egui::ProgressBar::new(job.progress));
                                     ui.label(format!("ETA: {}", job.estimated_time));
                                     
+                                    if let Some(record) = self.known_jobs.get(&job.id) {
+                                        if record.node_address.is_some() {
+                                            ui.monospace(format!("🏷️ {}", eryzaa_discovery::names::job_hostname(&record.job_id)));
+                                        }
+                                        if !record.port_mappings.is_empty() {
+                                            let ports: Vec<String> = record.port_mappings.iter().map(|m| m.to_string()).collect();
+                                            ui.label(format!("🔌 Ports: {}", ports.join(", ")));
+                                        }
+                                        if let Some(left) = record.trial_seconds_left(chrono::Utc::now()) {
+                                            ui.colored_label(egui::Color32::LIGHT_GREEN, format!("🎁 Free trial, {}:{:02} left", left / 60, left % 60));
+                                        }
+                                        if record.energy_wh > 0.0 {
+                                            let job_receipt = record.receipt(chrono::Utc::now());
+                                            let carbon = job_receipt
+                                                .carbon_g()
+                                                .map(|grams| format!(" · 🌱 {}", receipt::format_carbon(grams)))
+                                                .unwrap_or_default();
+                                            ui.label(format!("⚡ {:.2} kWh{}", job_receipt.energy_kwh, carbon));
+                                        }
+                                    }
+                                    
                                     ui.horizontal(|ui| {
-                                        if ui.button("⏸️ Pause").clicked() {
-                                            job.status = "Paused".to_string();
+                                        if job.status == "Paused" {
+                                            if ui.button("▶️ Resume").clicked() {
+                                                pause_toggles.push((job.id.clone(), false));
+                                            }
+                                        } else if ui.button("⏸️ Pause").clicked() {
+                                            pause_toggles.push((job.id.clone(), true));
                                         }
                                         if ui.button("⏹️ Stop").clicked() {
                                             jobs_to_remove.push(i);
@@ -807,6 +2405,12 @@ impl EryzaaClientApp {
                                         if ui.button("📊 Logs").clicked() {
                                             self.selected_tab = Tab::Logs;
                                         }
+                                        if ui.button("🖥️ Open shell in job").clicked() {
+                                            shell_job = Some(job.id.clone());
+                                        }
+                                        if ui.button("📤 Share Access").clicked() {
+                                            share_job = Some(job.id.clone());
+                                        }
                                     });
                                 });
                                 ui.add_space(5.0);
@@ -819,7 +2423,23 @@ impl EryzaaClientApp {
                             
                             // Remove stopped jobs
                             for i in jobs_to_remove.into_iter().rev() {
-                                self.active_jobs.remove(i);
+                                let job = self.active_jobs.remove(i);
+                                // Cancellations of queued or unreachable jobs wait for connectivity
+                                if job.status == "Queued" || (self.is_offline() && self.known_jobs.contains_key(&job.id)) {
+                                    self.queue_action(QueuedAction::Cancel { job_id: job.id });
+                                }
+                            }
+                            
+                            if let Some(job_id) = shell_job {
+                                self.open_job_shell(&job_id);
+                            }
+                            
+                            if let Some(job_id) = share_job {
+                                self.share_job_access(&job_id);
+                            }
+                            
+                            for (job_id, pause) in pause_toggles {
+                                self.set_job_paused(&job_id, pause);
                             }
                         });
                     }
@@ -863,6 +2483,242 @@ impl EryzaaClientApp {
         });
     }
     
+    fn paste_to_remote(&self, job_id: &str, name: String, content: Vec<u8>) {
+        *self.paste_status.lock().unwrap() = format!("Sending {}...", name);
+        let status = self.paste_status.clone();
+        let job_id = job_id.to_string();
+        thread::spawn(move || {
+            let result = JobManager::load_from(&eryzaa_jobs::default_registry_path())
+                .and_then(|manager| manager.push_to_incoming(&job_id, &name, &content));
+            *status.lock().unwrap() = match result {
+                Ok(path) => format!("✅ Sent to {}", path),
+                Err(e) => format!("❌ {}", e),
+            };
+        });
+    }
+    
+    fn paste_file_to_remote(&mut self, job_id: &str) {
+        let Some(path) = rfd::FileDialog::new().pick_file() else {
+            return;
+        };
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let too_big = std::fs::metadata(&path)
+            .map(|m| m.len() > paste::MAX_PASTE_BYTES as u64)
+            .unwrap_or(false);
+        if too_big {
+            *self.paste_status.lock().unwrap() = format!("❌ {} is over {}; copy it with scp instead", name, transfers::format_bytes(paste::MAX_PASTE_BYTES as u64));
+            return;
+        }
+        match std::fs::read(&path) {
+            Ok(content) => self.paste_to_remote(job_id, name, content),
+            Err(e) => *self.paste_status.lock().unwrap() = format!("❌ Failed to read {}: {}", path.display(), e),
+        }
+    }
+    
+    fn refresh_incoming(&self, job_id: &str) {
+        let files = self.incoming_files.clone();
+        let status = self.paste_status.clone();
+        let job_id = job_id.to_string();
+        thread::spawn(move || {
+            match JobManager::load_from(&eryzaa_jobs::default_registry_path())
+                .and_then(|manager| manager.list_incoming(&job_id))
+            {
+                Ok(names) => *files.lock().unwrap() = names,
+                Err(e) => *status.lock().unwrap() = format!("❌ {}", e),
+            }
+        });
+    }
+    
+    fn pull_from_remote(&self, job_id: &str, name: &str) {
+        let Some(path) = rfd::FileDialog::new().set_file_name(name).save_file() else {
+            return;
+        };
+        *self.paste_status.lock().unwrap() = format!("Fetching {}...", name);
+        let status = self.paste_status.clone();
+        let (job_id, name) = (job_id.to_string(), name.to_string());
+        thread::spawn(move || {
+            let result = JobManager::load_from(&eryzaa_jobs::default_registry_path())
+                .and_then(|manager| manager.pull_from_incoming(&job_id, &name))
+                .and_then(|content| {
+                    std::fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+                });
+            *status.lock().unwrap() = match result {
+                Ok(()) => format!("✅ Saved {} to {}", name, path.display()),
+                Err(e) => format!("❌ {}", e),
+            };
+        });
+    }
+    
+    fn show_paste_channel(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("📥 Paste to Remote");
+            ui.label(format!("Send a snippet or small file into a job session's ~/{}", paste::INCOMING_DIR));
+            
+            let mut running: Vec<&String> = self
+                .known_jobs
+                .values()
+                .filter(|job| job.status == JobStatus::Running)
+                .map(|job| &job.job_id)
+                .collect();
+            running.sort();
+            if running.is_empty() {
+                ui.label("No running jobs to paste into");
+                return;
+            }
+            
+            let previous = self.paste_job.clone();
+            egui::ComboBox::from_id_source("paste_job")
+                .selected_text(self.paste_job.clone().unwrap_or_else(|| "Select a job".to_string()))
+                .show_ui(ui, |ui| {
+                    for job_id in &running {
+                        ui.selectable_value(&mut self.paste_job, Some(job_id.to_string()), job_id.as_str());
+                    }
+                });
+            let Some(job_id) = self.paste_job.clone() else {
+                return;
+            };
+            if previous != self.paste_job {
+                self.incoming_files.lock().unwrap().clear();
+                self.refresh_incoming(&job_id);
+            }
+            
+            ui.add(egui::TextEdit::multiline(&mut self.paste_text).hint_text("Paste text here").desired_rows(4));
+            let mut send_text = false;
+            let mut send_file = false;
+            ui.horizontal(|ui| {
+                send_text = ui.add_enabled(!self.paste_text.is_empty(), egui::Button::new("📤 Send Text")).clicked();
+                send_file = ui.button("📎 Send File...").clicked();
+                if ui.button("🔄 Refresh").clicked() {
+                    self.refresh_incoming(&job_id);
+                }
+            });
+            if send_text {
+                let name = paste::clipboard_name(chrono::Local::now());
+                self.paste_to_remote(&job_id, name, self.paste_text.clone().into_bytes());
+                self.paste_text.clear();
+            }
+            if send_file {
+                self.paste_file_to_remote(&job_id);
+            }
+            
+            let files = self.incoming_files.lock().unwrap().clone();
+            let mut to_pull = None;
+            for name in &files {
+                ui.horizontal(|ui| {
+                    ui.monospace(name);
+                    if ui.small_button("⬇ Pull").clicked() {
+                        to_pull = Some(name.clone());
+                    }
+                });
+            }
+            if let Some(name) = to_pull {
+                self.pull_from_remote(&job_id, &name);
+            }
+            
+            ui.label(self.paste_status.lock().unwrap().as_str());
+        });
+    }
+    
+    fn workspace_store(&self) -> WorkspaceStore {
+        WorkspaceStore::for_profile(self.profiles.active())
+    }
+    
+    fn refresh_snapshots(&self) {
+        *self.workspace_snapshots.lock().unwrap() = self.workspace_store().list();
+    }
+    
+    fn save_workspace(&self, job_id: &str) {
+        *self.workspace_status.lock().unwrap() = format!("Saving the workspace of {}...", job_id);
+        let (status, snapshots) = (self.workspace_status.clone(), self.workspace_snapshots.clone());
+        let (store, job_id, label) = (self.workspace_store(), job_id.to_string(), self.workspace_label.clone());
+        thread::spawn(move || {
+            let result = JobManager::load_from(&eryzaa_jobs::default_registry_path())
+                .and_then(|manager| manager.save_workspace(&job_id, &store, &label));
+            *status.lock().unwrap() = match result {
+                Ok(snapshot) => format!("✅ Saved {} ({})", snapshot.id, transfers::format_bytes(snapshot.bytes)),
+                Err(e) => format!("❌ {}", e),
+            };
+            *snapshots.lock().unwrap() = store.list();
+        });
+    }
+    
+    fn restore_workspace(&self, job_id: &str, snapshot_id: &str) {
+        *self.workspace_status.lock().unwrap() = format!("Restoring {} into {}...", snapshot_id, job_id);
+        let status = self.workspace_status.clone();
+        let (store, job_id, snapshot_id) = (self.workspace_store(), job_id.to_string(), snapshot_id.to_string());
+        thread::spawn(move || {
+            let result = JobManager::load_from(&eryzaa_jobs::default_registry_path())
+                .and_then(|manager| manager.restore_workspace(&job_id, &store, &snapshot_id));
+            *status.lock().unwrap() = match result {
+                Ok(()) => format!("✅ Restored {} into {}", snapshot_id, job_id),
+                Err(e) => format!("❌ {}", e),
+            };
+        });
+    }
+    
+    fn show_workspace_snapshots(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("💾 Workspace Snapshots");
+            ui.label(format!("Keep a job's home directory between rentals; snapshots are stored in {}", self.workspace_store().dir().display()));
+            
+            let mut running: Vec<&String> = self
+                .known_jobs
+                .values()
+                .filter(|job| job.status == JobStatus::Running)
+                .map(|job| &job.job_id)
+                .collect();
+            running.sort();
+            
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("workspace_job")
+                    .selected_text(self.workspace_job.clone().unwrap_or_else(|| "Select a job".to_string()))
+                    .show_ui(ui, |ui| {
+                        for job_id in &running {
+                            ui.selectable_value(&mut self.workspace_job, Some(job_id.to_string()), job_id.as_str());
+                        }
+                    });
+                ui.add(egui::TextEdit::singleline(&mut self.workspace_label).desired_width(120.0));
+                if ui.add_enabled(self.workspace_job.is_some(), egui::Button::new("💾 Save")).clicked() {
+                    if let Some(job_id) = self.workspace_job.clone() {
+                        self.save_workspace(&job_id);
+                    }
+                }
+                if ui.button("🔄 Refresh").clicked() {
+                    self.refresh_snapshots();
+                }
+            });
+            
+            let snapshots = self.workspace_snapshots.lock().unwrap().clone();
+            let mut to_restore = None;
+            let mut to_remove = None;
+            for snapshot in &snapshots {
+                ui.horizontal(|ui| {
+                    ui.monospace(&snapshot.id);
+                    ui.label(transfers::format_bytes(snapshot.bytes));
+                    ui.label(format!("from {}", snapshot.node_id.as_deref().unwrap_or(&snapshot.job_id)));
+                    if ui.add_enabled(self.workspace_job.is_some(), egui::Button::new("⬆ Restore").small()).clicked() {
+                        to_restore = Some(snapshot.id.clone());
+                    }
+                    if ui.small_button("🗑").on_hover_text("Delete snapshot").clicked() {
+                        to_remove = Some(snapshot.id.clone());
+                    }
+                });
+            }
+            if let (Some(snapshot_id), Some(job_id)) = (to_restore, self.workspace_job.clone()) {
+                self.restore_workspace(&job_id, &snapshot_id);
+            }
+            if let Some(snapshot_id) = to_remove {
+                *self.workspace_status.lock().unwrap() = match self.workspace_store().remove(&snapshot_id) {
+                    Ok(()) => format!("🗑 Deleted {}", snapshot_id),
+                    Err(e) => format!("❌ {}", e),
+                };
+                self.refresh_snapshots();
+            }
+            
+            ui.label(self.workspace_status.lock().unwrap().as_str());
+        });
+    }
+    
     fn show_ssh(&mut self, ui: &mut egui::Ui) {
         ui.heading("� Direct SSH Access to PCs");
         ui.separator();
@@ -879,11 +2735,54 @@ impl EryzaaClientApp {
                 if ui.button("🌐 Join ZeroTier Network").clicked() {
                     // Join ZeroTier network
                 }
+                if ui.button("🏷️ Sync DNS Names").clicked() {
+                    self.sync_host_names();
+                }
+                if ui.button("🔑 Write SSH Config").clicked() {
+                    let jobs: Vec<JobRecord> = self.known_jobs.values().cloned().collect();
+                    let writer = SshConfigWriter::for_current_user();
+                    match writer.sync(&jobs) {
+                        Ok(()) => self.log_content.push_str(&format!("Wrote {}\n", writer.config_path().display())),
+                        Err(e) => self.log_content.push_str(&format!("Failed to write SSH config: {}\n", e)),
+                    }
+                }
             });
         });
         
         ui.add_space(10.0);
         
+        // Shared access links
+        if !self.handoff_offers.is_empty() {
+            ui.group(|ui| {
+                ui.heading("📤 Shared Access Links");
+                
+                let now = chrono::Utc::now();
+                let mut to_revoke = None;
+                for offer in &self.handoff_offers {
+                    ui.horizontal(|ui| {
+                        ui.monospace(&offer.id);
+                        ui.label(format!("Job {}", offer.job_id));
+                        let status = offer.status(now);
+                        ui.label(format!("{:?}", status));
+                        if status == eryzaa_jobs::HandoffStatus::Active {
+                            ui.label(format!("expires {}", offer.expires_at.with_timezone(&chrono::Local).format("%H:%M")));
+                            if ui.button("🚫 Revoke").clicked() {
+                                to_revoke = Some(offer.id.clone());
+                            }
+                        } else if let Some(redeemed_at) = offer.redeemed_at {
+                            ui.label(format!("at {}", redeemed_at.with_timezone(&chrono::Local).format("%H:%M")));
+                        }
+                    });
+                }
+                
+                if let Some(id) = to_revoke {
+                    self.revoke_handoff(&id);
+                }
+            });
+            
+            ui.add_space(10.0);
+        }
+        
         let status = self.server_status.lock().unwrap().clone();
         
         // Available servers section
@@ -947,7 +2846,8 @@ impl EryzaaClientApp {
                 ui.text_edit_singleline(&mut self.zerotier_ip);
                 if ui.button("🔗 Direct Connect").clicked() {
                     if !self.zerotier_ip.is_empty() {
-                        self.open_ssh_terminal(&self.zerotier_ip);
+                        let ip = self.zerotier_ip.clone();
+                        self.open_ssh_terminal(&ip);
                     }
                 }
             });
@@ -955,18 +2855,21 @@ impl EryzaaClientApp {
             ui.label("Quick commands:");
             ui.group(|ui| {
                 if let ServerStatus::Running(ip) = &status {
-                    let ssh_cmd = format!("ssh {}@{}", self.settings.ssh_username, ip);
+                    let platform = Platform::current();
+                    let host_key_args = SshConfigWriter::for_current_user().host_key_args(ip);
+                    let ssh_cmd = platform.ssh_snippet(&self.settings.ssh_username, ip, &host_key_args);
                     ui.horizontal(|ui| {
                         ui.monospace(&ssh_cmd);
-                        if ui.button("📋").clicked() {
+                        if icon_button(ui, "📋", "Copy SSH command").clicked() {
                             ui.output_mut(|o| o.copied_text = ssh_cmd);
                         }
                     });
                     
-                    let scp_cmd = format!("scp file.txt {}@{}:/home/{}/", self.settings.ssh_username, ip, self.settings.ssh_username);
+                    let home = format!("/home/{}/", self.settings.ssh_username);
+                    let scp_cmd = platform.scp_snippet("file.txt", &self.settings.ssh_username, ip, &home, &host_key_args);
                     ui.horizontal(|ui| {
                         ui.monospace(&scp_cmd);
-                        if ui.button("📋").clicked() {
+                        if icon_button(ui, "📋", "Copy SCP command").clicked() {
                             ui.output_mut(|o| o.copied_text = scp_cmd);
                         }
                     });
@@ -978,6 +2881,14 @@ impl EryzaaClientApp {
         
         ui.add_space(10.0);
         
+        self.show_paste_channel(ui);
+        
+        ui.add_space(10.0);
+        
+        self.show_workspace_snapshots(ui);
+        
+        ui.add_space(10.0);
+        
         // Security and pricing info
         ui.horizontal(|ui| {
             ui.group(|ui| {
@@ -998,6 +2909,176 @@ impl EryzaaClientApp {
         });
     }
     
+    fn show_history(&mut self, ui: &mut egui::Ui) {
+        ui.heading("🕘 Job History");
+        ui.label("Every job you submit is kept here with its spec; clone one to tweak it and run it again");
+        ui.separator();
+        
+        let mut submit = None;
+        if let Some(draft) = &mut self.history_draft {
+            ui.group(|ui| {
+                ui.label("✏️ Edit and re-submit");
+                egui::Grid::new("history_draft").num_columns(2).show(ui, |ui| {
+                    ui.label("Name:");
+                    ui.text_edit_singleline(&mut draft.spec.name);
+                    ui.end_row();
+                    
+                    ui.label("Image:");
+                    let mut image = draft.spec.image.clone().unwrap_or_default();
+                    if ui.text_edit_singleline(&mut image).changed() {
+                        draft.spec.image = Some(image).filter(|image| !image.trim().is_empty());
+                    }
+                    ui.end_row();
+                    
+                    ui.label("Command:");
+                    ui.text_edit_singleline(&mut draft.command);
+                    ui.end_row();
+                    
+                    ui.label("Environment:");
+                    ui.text_edit_multiline(&mut draft.env);
+                    ui.end_row();
+                    
+                    ui.label("GPUs:");
+                    ui.text_edit_singleline(&mut draft.gpus);
+                    ui.end_row();
+                    
+                    ui.label("Scratch disk:");
+                    ui.horizontal(|ui| {
+                        let mut wanted = draft.spec.scratch.is_some();
+                        ui.checkbox(&mut wanted, "");
+                        if !wanted {
+                            draft.spec.scratch = None;
+                            return;
+                        }
+                        let scratch = draft
+                            .spec
+                            .scratch
+                            .get_or_insert(ScratchRequest { size_gb: 50, class: StorageClass::Ssd });
+                        ui.add(egui::DragValue::new(&mut scratch.size_gb).clamp_range(1..=10_000).suffix(" GB"));
+                        egui::ComboBox::from_id_source("history_scratch_class")
+                            .selected_text(format!("{:?}", scratch.class))
+                            .show_ui(ui, |ui| {
+                                for class in [StorageClass::Hdd, StorageClass::Ssd, StorageClass::Nvme] {
+                                    ui.selectable_value(&mut scratch.class, class, format!("{:?}", class));
+                                }
+                            });
+                        ui.weak("mounted at /scratch");
+                    });
+                    ui.end_row();
+                    
+                    ui.label("Node:");
+                    let selected = draft
+                        .node_id
+                        .as_ref()
+                        .map(|id| {
+                            self.gpu_nodes
+                                .iter()
+                                .find(|node| &node.id == id)
+                                .map(|node| node.name.clone())
+                                .unwrap_or_else(|| id.clone())
+                        })
+                        .unwrap_or_else(|| "Any node".to_string());
+                    egui::ComboBox::from_id_source("history_node")
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut draft.node_id, None, "Any node");
+                            for node in &self.gpu_nodes {
+                                ui.selectable_value(&mut draft.node_id, Some(node.id.clone()), &node.name);
+                            }
+                        });
+                    ui.end_row();
+                });
+                
+                ui.horizontal(|ui| {
+                    if ui.button("🚀 Submit").clicked() {
+                        submit = Some(true);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        submit = Some(false);
+                    }
+                });
+            });
+            ui.add_space(10.0);
+        }
+        match submit {
+            Some(true) => {
+                if let Some(draft) = self.history_draft.take() {
+                    self.submit_spec(draft.to_spec(), draft.node_id, None);
+                    self.selected_tab = Tab::EdgeComputing;
+                }
+            }
+            Some(false) => self.history_draft = None,
+            None => {}
+        }
+        
+        if self.job_history.entries.is_empty() {
+            ui.label("No jobs submitted yet.");
+            return;
+        }
+        
+        let mut clone = None;
+        let mut rerun = None;
+        let mut forget = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for entry in self.job_history.entries.iter().rev() {
+                ui.group(|ui| {
+                    ui.horizontal(|ui| {
+                        ui.strong(&entry.spec.name);
+                        ui.monospace(&entry.job_id);
+                        match &entry.outcome {
+                            None => ui.weak("Submitted"),
+                            Some(JobStatus::Completed) => ui.colored_label(egui::Color32::GREEN, "✅ Completed"),
+                            Some(JobStatus::Failed(reason)) => ui.colored_label(egui::Color32::RED, format!("❌ Failed: {}", reason)),
+                            Some(status) => ui.label(format!("{:?}", status)),
+                        };
+                    });
+                    ui.label(format!(
+                        "{} · {} · {}",
+                        entry.spec.image_tag(),
+                        entry.node_id.as_deref().unwrap_or("any node"),
+                        entry.submitted_at.format("%Y-%m-%d %H:%M")
+                    ));
+                    for hop in &entry.failovers {
+                        ui.weak(format!(
+                            "🛟 {} → {} at {}{}",
+                            hop.from_node,
+                            hop.to_node,
+                            hop.at.format("%Y-%m-%d %H:%M"),
+                            hop.snapshot.as_ref().map(|id| format!(", restored {}", id)).unwrap_or_default()
+                        ));
+                    }
+                    if !entry.spec.command.is_empty() {
+                        ui.monospace(entry.spec.command.join(" "));
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.button("📋 Clone & Edit").clicked() {
+                            clone = Some(entry.job_id.clone());
+                        }
+                        if ui.button("🔁 Re-run").clicked() {
+                            rerun = Some(entry.job_id.clone());
+                        }
+                        if ui.small_button("🗑 Forget").clicked() {
+                            forget = Some(entry.job_id.clone());
+                        }
+                    });
+                });
+            }
+        });
+        
+        if let Some(job_id) = clone {
+            self.history_draft = self.job_history.get(&job_id).map(SpecDraft::from_entry);
+        }
+        if let Some(entry) = rerun.and_then(|job_id| self.job_history.get(&job_id).cloned()) {
+            self.submit_spec(entry.spec, entry.node_id, None);
+        }
+        if let Some(job_id) = forget {
+            self.job_history.remove(&job_id);
+            if let Err(e) = self.job_history.save_to(&eryzaa_jobs::history::default_history_path()) {
+                self.log_content.push_str(&format!("Failed to save job history: {}\n", e));
+            }
+        }
+    }
+    
     fn show_logs(&mut self, ui: &mut egui::Ui) {
         ui.heading("📋 Server Logs");
         ui.separator();
@@ -1024,11 +3105,18 @@ impl EryzaaClientApp {
         ui.heading("⚙️ Eryzaa Settings");
         ui.separator();
         
+        if ui.button("👋 Run Setup Again").on_hover_text("Steps already done are kept").clicked() {
+            self.onboarding.lock().unwrap().completed_at = None;
+            self.show_onboarding = true;
+        }
+        
+        ui.add_space(10.0);
+        
         ui.group(|ui| {
             ui.label("🌐 Network Settings");
             ui.horizontal(|ui| {
-                ui.label("ZeroTier Network ID:");
-                ui.text_edit_singleline(&mut self.settings.zerotier_network_id);
+                let label = ui.label("ZeroTier Network ID:");
+                ui.text_edit_singleline(&mut self.settings.zerotier_network_id).labelled_by(label.id);
             });
         });
         
@@ -1037,12 +3125,12 @@ impl EryzaaClientApp {
         ui.group(|ui| {
             ui.label("🔐 SSH Settings");
             ui.horizontal(|ui| {
-                ui.label("Username:");
-                ui.text_edit_singleline(&mut self.settings.ssh_username);
+                let label = ui.label("Username:");
+                ui.text_edit_singleline(&mut self.settings.ssh_username).labelled_by(label.id);
             });
             ui.horizontal(|ui| {
-                ui.label("Password:");
-                ui.text_edit_singleline(&mut self.settings.ssh_password);
+                let label = ui.label("Password:");
+                ui.text_edit_singleline(&mut self.settings.ssh_password).labelled_by(label.id);
             });
             ui.checkbox(&mut self.settings.auto_connect_ssh, "Auto-connect SSH after deployment");
         });
@@ -1054,8 +3142,8 @@ impl EryzaaClientApp {
             ui.checkbox(&mut self.settings.enable_gpu, "Enable GPU acceleration");
             ui.checkbox(&mut self.settings.auto_save_models, "Auto-save trained models");
             ui.horizontal(|ui| {
-                ui.label("Default training epochs:");
-                ui.add(egui::Slider::new(&mut self.settings.default_epochs, 1..=1000));
+                let label = ui.label("Default training epochs:");
+                ui.add(egui::Slider::new(&mut self.settings.default_epochs, 1..=1000)).labelled_by(label.id);
             });
         });
         
@@ -1066,22 +3154,124 @@ impl EryzaaClientApp {
             ui.checkbox(&mut self.settings.auto_scale, "Enable auto-scaling");
             ui.checkbox(&mut self.settings.cost_optimization, "Enable cost optimization");
             ui.horizontal(|ui| {
-                ui.label("Max simultaneous jobs:");
-                ui.add(egui::Slider::new(&mut self.settings.max_jobs, 1..=10));
+                let label = ui.label("Max simultaneous jobs:");
+                ui.add(egui::Slider::new(&mut self.settings.max_jobs, 1..=10)).labelled_by(label.id);
+            });
+        });
+        
+        ui.add_space(10.0);
+        
+        ui.group(|ui| {
+            ui.label("🛟 Failover");
+            let mut changed = ui
+                .checkbox(&mut self.failover.enabled, "Move running jobs to another node when theirs dies")
+                .changed();
+            ui.add_enabled_ui(self.failover.enabled, |ui| {
+                ui.horizontal(|ui| {
+                    let label = ui.label("Moves per job:");
+                    changed |= ui.add(egui::Slider::new(&mut self.failover.max_attempts, 1..=5)).labelled_by(label.id).changed();
+                });
+                ui.horizontal(|ui| {
+                    let mut capped = self.failover.max_price_per_hour.is_some();
+                    changed |= ui.checkbox(&mut capped, "Max price per hour (AVAX):").changed();
+                    let mut price = self.failover.max_price_per_hour.unwrap_or(1.0);
+                    changed |= ui.add_enabled(capped, egui::DragValue::new(&mut price).speed(0.1).clamp_range(0.0..=1000.0)).changed();
+                    self.failover.max_price_per_hour = capped.then_some(price);
+                });
+                ui.horizontal(|ui| {
+                    let mut capped = self.failover.max_spend.is_some();
+                    changed |= ui.checkbox(&mut capped, "Budget per job (AVAX):").changed();
+                    let mut budget = self.failover.max_spend.unwrap_or(10.0);
+                    changed |= ui.add_enabled(capped, egui::DragValue::new(&mut budget).speed(0.5).clamp_range(0.0..=100000.0)).changed();
+                    self.failover.max_spend = capped.then_some(budget);
+                });
+            });
+            if changed {
+                self.failover_status = match self.failover.save_to(&eryzaa_jobs::failover::default_policy_path()) {
+                    Ok(()) => String::new(),
+                    Err(e) => format!("❌ {}", e),
+                };
+            }
+            ui.label("Jobs restart from their latest workspace snapshot; moves are listed in History.");
+            if !self.failover_status.is_empty() {
+                ui.label(&self.failover_status);
+            }
+        });
+        
+        ui.add_space(10.0);
+        
+        ui.group(|ui| {
+            ui.label("📱 Phone alerts");
+            let mut changed = ui
+                .checkbox(&mut self.push.enabled, "Alert my phone about running jobs, even with this app closed")
+                .changed();
+            ui.add_enabled_ui(self.push.enabled, |ui| {
+                ui.horizontal(|ui| {
+                    let label = ui.label("Relay:");
+                    changed |= ui.text_edit_singleline(&mut self.push.relay).labelled_by(label.id).lost_focus();
+                });
+                ui.horizontal(|ui| {
+                    let url = self.push.subscribe_url();
+                    ui.label("Subscribe in the ntfy app to:");
+                    ui.monospace(&url);
+                    if icon_button(ui, "📋", "Copy subscription URL").clicked() {
+                        ui.output_mut(|o| o.copied_text = url);
+                    }
+                    if ui.button("🔄 New topic").on_hover_text("Anyone who knows the topic sees your alerts").clicked() {
+                        self.push.topic = push::new_topic();
+                        changed = true;
+                    }
+                });
+                for kind in AlertKind::ALL {
+                    let mut wanted = self.push.alerts.contains(&kind);
+                    if ui.checkbox(&mut wanted, kind.label()).changed() {
+                        self.push.alerts.retain(|k| *k != kind);
+                        if wanted {
+                            self.push.alerts.push(kind);
+                        }
+                        changed = true;
+                    }
+                }
+                ui.horizontal(|ui| {
+                    let mut capped = self.push.budget.is_some();
+                    changed |= ui.checkbox(&mut capped, "Budget per job (AVAX):").changed();
+                    let mut budget = self.push.budget.unwrap_or(10.0);
+                    changed |= ui.add_enabled(capped, egui::DragValue::new(&mut budget).speed(0.5).clamp_range(0.0..=100000.0)).changed();
+                    self.push.budget = capped.then_some(budget);
+                });
             });
+            if changed {
+                // Subscriptions pick up the change on the next sync
+                self.last_push_sync = None;
+                *self.push_status.lock().unwrap() = match self.push.save_to(&push_settings_path()) {
+                    Ok(()) => String::new(),
+                    Err(e) => format!("❌ {}", e),
+                };
+            }
+            let status = self.push_status.lock().unwrap().clone();
+            if !status.is_empty() {
+                ui.label(status);
+            }
         });
         
         ui.add_space(10.0);
         
         ui.group(|ui| {
             ui.label("💰 Avalanche Blockchain Settings");
+            let profile = self.profiles.active();
+            ui.label(format!(
+                "Profile: {}{} | Balance: {:.2} AVAX",
+                profile.name,
+                profile.organization.as_ref().map(|org| format!(" ({})", org)).unwrap_or_default(),
+                profile.balance
+            ));
             ui.horizontal(|ui| {
-                ui.label("Wallet Address:");
-                ui.text_edit_singleline(&mut self.settings.wallet_address);
+                let label = ui.label("Wallet Address:");
+                ui.text_edit_singleline(&mut self.settings.wallet_address).labelled_by(label.id);
             });
             ui.horizontal(|ui| {
-                ui.label("RPC Endpoint:");
-                ui.text_edit_singleline(&mut self.settings.avax_rpc_url);
+                let label = ui.label("RPC Endpoint:");
+                ui.text_edit_singleline(&mut self.settings.avax_rpc_url).labelled_by(label.id);
             });
             ui.checkbox(&mut self.settings.auto_approve_payments, "Auto-approve small payments (< 1 AVAX)");
         });
@@ -1095,6 +3285,23 @@ impl EryzaaClientApp {
             ui.checkbox(&mut self.settings.minimize_to_tray, "Minimize to system tray");
         });
         
+        ui.add_space(10.0);
+        
+        ui.group(|ui| {
+            ui.label("♿ Accessibility");
+            if ui.checkbox(&mut self.accessibility.large_text, "Larger text").changed() {
+                self.accessibility.apply(ui.ctx());
+                self.accessibility_status = match self.accessibility.save_to(&accessibility_path()) {
+                    Ok(()) => String::new(),
+                    Err(e) => format!("❌ {}", e),
+                };
+            }
+            ui.label("Keyboard: Tab and Shift+Tab move focus, Space or Enter activates, Ctrl+1-8 or Ctrl+PageUp/PageDown switch tabs, Esc closes dialogs.");
+            if !self.accessibility_status.is_empty() {
+                ui.label(&self.accessibility_status);
+            }
+        });
+        
         ui.add_space(20.0);
         
         ui.horizontal(|ui| {
@@ -1131,14 +3338,163 @@ impl EryzaaClientApp {
         });
     }
     
-    fn save_settings(&self) {
+    fn save_settings(&mut self) {
         // Implementation for saving settings to file
         // This would typically serialize settings to JSON/TOML
+        
+        // The wallet belongs to the active profile
+        let wallet = self.settings.wallet_address.trim().to_string();
+        self.profiles.active_mut().wallet_address = if wallet.is_empty() { None } else { Some(wallet) };
+        if let Err(e) = self.profiles.save() {
+            self.log_content.push_str(&format!("Failed to save profile: {}\n", e));
+        }
+    }
+}
+
+/// Make a wallet whose key is kept in `dir`, encrypted with `password`; returns its address
+fn create_wallet(dir: &std::path::Path, password: &str) -> Result<String, String> {
+    use ethers::signers::{LocalWallet, Signer};
+    
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let (wallet, _) = LocalWallet::new_keystore(dir, &mut ethers::core::rand::thread_rng(), password, None)
+        .map_err(|e| format!("Failed to create wallet: {}", e))?;
+    Ok(ethers::utils::to_checksum(&wallet.address(), None))
+}
+
+/// Display preferences for low vision and keyboard-only use, shared with the rental app
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Accessibility {
+    pub large_text: bool,
+}
+
+/// Text and control size multiplier in larger-text mode
+const LARGE_TEXT_SCALE: f32 = 1.4;
+
+impl Accessibility {
+    fn load_from(path: &std::path::Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+    
+    fn save_to(&self, path: &std::path::Path) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize accessibility settings: {}", e))?;
+        std::fs::write(path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
     }
+    
+    /// Scale text from egui's defaults and outline the focused widget
+    fn apply(&self, ctx: &egui::Context) {
+        let defaults = egui::Style::default();
+        let scale = if self.large_text { LARGE_TEXT_SCALE } else { 1.0 };
+        let mut style = (*ctx.style()).clone();
+        for (text_style, font) in style.text_styles.iter_mut() {
+            font.size = defaults.text_styles[text_style].size * scale;
+        }
+        style.spacing.interact_size.y = defaults.spacing.interact_size.y * scale;
+        style.visuals.widgets.active.bg_stroke.width = 2.0;
+        ctx.set_style(style);
+    }
+}
+
+/// Accessibility settings shared with the rental app
+/// Phone alert settings of the active profile
+fn push_settings_path() -> std::path::PathBuf {
+    eryzaa_jobs::profiles::active_profile().data_dir().join("push.json")
+}
+
+fn accessibility_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("accessibility.json")
+}
+
+/// Keys pinned for the nodes this machine has heard from
+fn node_keys_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("node_keys.json")
+}
+
+/// Ctrl+1.. picks a tab directly, Ctrl+PageUp/PageDown steps through them
+fn tab_shortcut<T: Clone + PartialEq>(ctx: &egui::Context, tabs: &[T], current: &T) -> Option<T> {
+    const KEYS: [egui::Key; 9] = [
+        egui::Key::Num1, egui::Key::Num2, egui::Key::Num3,
+        egui::Key::Num4, egui::Key::Num5, egui::Key::Num6,
+        egui::Key::Num7, egui::Key::Num8, egui::Key::Num9,
+    ];
+    let position = tabs.iter().position(|tab| tab == current).unwrap_or(0);
+    ctx.input_mut(|input| {
+        if let Some(tab) = tabs.iter().zip(KEYS).find(|(_, key)| input.consume_key(egui::Modifiers::COMMAND, *key)) {
+            return Some(tab.0.clone());
+        }
+        if input.consume_key(egui::Modifiers::COMMAND, egui::Key::PageDown) {
+            return Some(tabs[(position + 1) % tabs.len()].clone());
+        }
+        if input.consume_key(egui::Modifiers::COMMAND, egui::Key::PageUp) {
+            return Some(tabs[(position + tabs.len() - 1) % tabs.len()].clone());
+        }
+        None
+    })
+}
+
+/// Icon-only button with `label` as its tooltip and screen-reader name
+fn icon_button(ui: &mut egui::Ui, icon: &str, label: &str) -> egui::Response {
+    let response = ui.small_button(icon).on_hover_text(label);
+    response.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, label));
+    response
+}
+
+/// Owner's description, photo link and notes for a node
+fn show_node_details(ui: &mut egui::Ui, node_id: &str, details: &NodeDetails) {
+    ui.indent(node_id, |ui| {
+        if !details.description.is_empty() {
+            ui.label(&details.description);
+        }
+        if let Some(url) = &details.photo_url {
+            ui.hyperlink_to("📷 Hardware photo", url);
+        }
+        if !details.metadata.is_empty() {
+            egui::Grid::new(("node_metadata", node_id)).num_columns(2).show(ui, |ui| {
+                for (key, value) in &details.metadata {
+                    ui.strong(key);
+                    ui.label(value);
+                    ui.end_row();
+                }
+            });
+        }
+    });
+}
+
+/// One comparison row, highlighting the best value when the nodes differ
+fn compare_row(ui: &mut egui::Ui, label: &str, values: &[Option<f32>], higher_is_better: bool, format: impl Fn(f32) -> String) {
+    let known: Vec<f32> = values.iter().flatten().copied().collect();
+    let best = if higher_is_better {
+        known.iter().copied().reduce(f32::max)
+    } else {
+        known.iter().copied().reduce(f32::min)
+    };
+    let differs = known.iter().any(|v| Some(*v) != best) || known.len() < values.len();
+    
+    ui.label(label);
+    for value in values {
+        match value {
+            Some(v) if differs && Some(*v) == best => ui.colored_label(egui::Color32::GREEN, format(*v)),
+            Some(v) => ui.label(format(*v)),
+            None => ui.weak("—"),
+        };
+    }
+    ui.end_row();
+}
+
+/// GPU memory in GB from labels like "96GB"
+fn memory_gb(memory: &str) -> u32 {
+    memory.trim().trim_end_matches("GB").trim().parse().unwrap_or(0)
 }
 
 fn main() -> Result<(), eframe::Error> {
     env_logger::init();
+    if let Err(e) = eryzaa_jobs::schema::prepare() {
+        eprintln!("Cannot open the saved state: {}", e);
+        std::process::exit(1);
+    }
     
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
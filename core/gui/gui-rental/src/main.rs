@@ -4,6 +4,34 @@ use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, SystemTime};
 use sysinfo::System;
+use chrono::Timelike;
+use eryzaa_node::thermal::{self, GpuReading};
+use eryzaa_node::admission::Evaluation;
+use eryzaa_node::forecast::{self, PriceSchedule, UtilizationHistory};
+use eryzaa_node::lockdown::{self, Incident, LockdownState};
+use eryzaa_node::login_approval;
+use eryzaa_node::onboarding::{self, Onboarding};
+use eryzaa_node::recording::{self, Playback, Recording};
+use eryzaa_node::{AccountedContainer, AdmissionDecision, AdmissionRequest, AdmissionRule, AdmissionRules, Condition, ConcurrencyLimit, EgressPolicy, EgressPreset, EnergyModel, FirewallPolicy, JobUsage, LoginApproval, LoginQueue, LoginRequest, QuietHours, ServiceSet, StartDecision, ThermalPolicy, ThermalScheduler, TrialLedger, UsageSampler};
+use eryzaa_discovery::clock::{self, ClockStatus};
+use eryzaa_discovery::coordinators::CoordinatorSet;
+use eryzaa_discovery::diagnostics::{self, Check, Severity};
+use eryzaa_discovery::market;
+use eryzaa_discovery::observers;
+use eryzaa_discovery::overlay::{self, NetworkMode};
+use eryzaa_discovery::registration;
+use eryzaa_discovery::{AdvertisementPrivacy, NodeDetails, TrialOffer};
+use eryzaa_jobs::abuse::{self, AbuseCategory, BlocklistFeed};
+use eryzaa_jobs::cache::{self, CachePolicy, ResultCache};
+use eryzaa_jobs::receipt;
+use eryzaa_jobs::pinning::{self, CpuTopology, PinningSettings};
+use eryzaa_jobs::reservation::{self, OwnerReservation};
+use eryzaa_jobs::scratch::{self, ScratchPools, StorageClass, StoragePool};
+use eryzaa_jobs::stack;
+use eryzaa_payments::{AccessPolicy, DepositLedger};
+use eryzaa_jobs::transfers::{self, EgressAlert};
+use eryzaa_jobs::warm::{self, WarmPool};
+use eryzaa_jobs::{AdmissionState, DestinationClass, EgressAlertPolicy, JobManager, JobRecord, JobStatus, TransferLedger};
 
 pub struct EryzaaRentalApp {
     // System state
@@ -22,12 +50,171 @@ pub struct EryzaaRentalApp {
     // Setup wizard
     setup_config: SetupConfig,
     
+    // Firewall
+    firewall_status: String,
+    
+    // ZeroTier diagnostics
+    network_checks: Arc<Mutex<Vec<Check>>>,
+    diagnosing: Arc<Mutex<bool>>,
+    
+    // Tenant jobs running on this node
+    tenant_jobs: Vec<JobRecord>,
+    
+    // Live per-tenant usage, sampled off the UI thread
+    job_usage: Arc<Mutex<Vec<JobUsage>>>,
+    usage_sampler: Arc<Mutex<UsageSampler>>, // Locked while a sample is in flight
+    last_usage_sample: SystemTime,
+    
+    // Thermals
+    gpu_readings: Vec<GpuReading>,
+    thermal_scheduler: ThermalScheduler,
+    thermal_status: String,
+    last_thermal_sample: SystemTime,
+    
+    // Transfer accounting
+    transfer_ledger: TransferLedger,
+    accounted_jobs: Vec<String>,
+    egress_alerts: Vec<EgressAlert>,
+    transfer_status: String,
+    last_transfer_sample: SystemTime,
+    
+    // Energy estimation, metered by the rental server
+    energy_model: EnergyModel,
+    energy_status: String,
+    
+    // Owner-written advertisement details, published by the discovery service
+    node_details: NodeDetails,
+    details_status: String,
+    new_metadata: (String, String),
+    
+    // Job slots, enforced by the rental server and advertised to clients
+    concurrency_limit: ConcurrencyLimit,
+    concurrency_status: String,
+    
+    // Result cache for deterministic pipeline stages, used by the rental server
+    cache_policy: CachePolicy,
+    cache_status: String,
+    
+    // Storage pools for job scratch disks, provisioned by the rental server
+    scratch_pools: ScratchPools,
+    scratch_status: String,
+    
+    // Cores kept for the owner when jobs ask for dedicated cores
+    reserved_cores: String,
+    pinning_status: String,
+    
+    // Cores, memory and GPUs the owner keeps out of every rental
+    owner_reservation: OwnerReservation,
+    owner_gpus: String,
+    reservation_status: String,
+    
+    // Claiming onboarded nodes for the owner's account with the code they print
+    claim_code: String,
+    claim_owner: String, // Wallet address of the active profile
+    claim_status: Arc<Mutex<String>>,
+    
+    // Read-only observer tokens sharing this node's status, e.g. in a widget on the owner's website
+    observer_label: String,
+    observer_revoke: String, // Token to revoke
+    observer_status: Arc<Mutex<String>>,
+    
+    // Idle container kept ready for interactive jobs, started by the rental server
+    warm_pool: WarmPool,
+    warm_volumes: String, // One "source:target" per line
+    warm_status: String,
+    
+    // Outbound traffic of tenant jobs, enforced by the rental server
+    egress_policy: EgressPolicy,
+    egress_custom: String, // One domain or CIDR per line
+    egress_status: String,
+    
+    // Reporting abusive tenants to the coordinator and its shared blocklist
+    abuse_job: Option<String>, // Job whose report dialog is open
+    abuse_category: AbuseCategory,
+    abuse_description: String,
+    abuse_status: Arc<Mutex<String>>,
+    blocklist_feed: BlocklistFeed,
+    blocklist_status: String,
+    
+    // Stack control from the dashboard quick actions
+    confirm_stack_action: Option<StackAction>,
+    drain_first: bool,
+    stack_status: Arc<Mutex<String>>,
+    confirm_panic: bool,
+    panic_reason: String,
+    panic_status: Arc<Mutex<String>>,
+    
+    // Log viewer
+    log_source: LogSource,
+    log_filter: String,
+    log_content: String,
+    
+    // Admission rules, evaluated by the rental server
+    admission_rules: AdmissionRules,
+    admission_status: String,
+    dry_run: AdmissionRequest,
+    dry_run_result: Option<Evaluation>,
+    
+    // Reputation or deposit clients need, advertised and enforced by the rental server
+    access_policy: AccessPolicy,
+    access_status: String,
+    deposits: DepositLedger,
+    forfeit_reason: String,
+    
+    // Free trial, advertised by the discovery service and enforced by the rental server
+    trial_offer: Option<TrialOffer>,
+    trial_status: String,
+    trials: TrialLedger,
+    
+    // What the discovery service and the coordinator registration leave out
+    advertisement_privacy: AdvertisementPrivacy,
+    query_only: bool,
+    query_key: String,
+    privacy_status: String,
+    
+    // SSH login approval, gated by the rental server
+    login_approval: LoginApproval,
+    login_approval_status: String,
+    login_requests: Vec<LoginRequest>,
+    last_login_poll: SystemTime,
+    
+    // Recorded job sessions and the one being played back
+    recordings: Vec<Recording>,
+    playback: Option<(Recording, Playback)>,
+    playback_at: f64,
+    playback_tick: Option<SystemTime>, // Set while playing
+    recording_search: String,
+    recording_status: String,
+    
+    // Clock sync; tenants are billed from this machine's clock
+    clock_status: Arc<Mutex<ClockStatus>>,
+    last_clock_check: SystemTime,
+    
+    // Going rate for this machine's GPU, from the coordinator when ERYZAA_COORDINATOR_URL is set
+    market_price: Arc<Mutex<Option<f32>>>,
+    last_market_check: SystemTime,
+    
+    // Busy job slots by the hour, forecasting idle hours to price them by
+    utilization_history: UtilizationHistory,
+    last_utilization_sample: SystemTime,
+    price_schedule: PriceSchedule,
+    price_schedule_status: String,
+    
+    // Accessibility, shared with the client app
+    accessibility: Accessibility,
+    accessibility_status: String,
+    
     // Auto-refresh
     last_update: SystemTime,
 }
 
 impl Default for EryzaaRentalApp {
     fn default() -> Self {
+        let warm_pool = WarmPool::load_from(&warm::default_pool_path());
+        let owner_reservation = OwnerReservation::load_from(&reservation::default_reservation_path());
+        let egress_policy = EgressPolicy::load_from(&egress_policy_path());
+        let onboarding = Onboarding::load_from(&onboarding_path());
+        let advertisement_privacy = AdvertisementPrivacy::load_from(&advertisement_privacy_path());
         Self {
             system: Arc::new(Mutex::new(System::new_all())),
             setup_status: Arc::new(Mutex::new(SetupStatus::default())),
@@ -35,8 +222,113 @@ impl Default for EryzaaRentalApp {
             selected_tab: Tab::default(),
             show_setup_wizard: false,
             setup_step: 0,
-            settings: RentalSettings::default(),
+            settings: RentalSettings {
+                pricing_per_hour: onboarding.price_per_hour.unwrap_or(RentalSettings::default().pricing_per_hour),
+                ..RentalSettings::default()
+            },
             setup_config: SetupConfig::default(),
+            firewall_status: "Not verified".to_string(),
+            network_checks: Arc::new(Mutex::new(vec![])),
+            diagnosing: Arc::new(Mutex::new(false)),
+            tenant_jobs: vec![],
+            job_usage: Arc::new(Mutex::new(vec![])),
+            usage_sampler: Arc::new(Mutex::new(UsageSampler::new())),
+            last_usage_sample: SystemTime::UNIX_EPOCH,
+            gpu_readings: vec![],
+            thermal_scheduler: ThermalScheduler::default(),
+            thermal_status: String::new(),
+            last_thermal_sample: SystemTime::UNIX_EPOCH,
+            transfer_ledger: TransferLedger::load_from(&transfers::default_ledger_path()),
+            accounted_jobs: vec![],
+            egress_alerts: vec![],
+            transfer_status: String::new(),
+            last_transfer_sample: SystemTime::UNIX_EPOCH,
+            energy_model: EnergyModel::load_from(&energy_model_path()),
+            energy_status: String::new(),
+            node_details: NodeDetails::load_from(&node_details_path()),
+            details_status: String::new(),
+            new_metadata: (String::new(), String::new()),
+            concurrency_limit: ConcurrencyLimit::load_from(&concurrency_limit_path()),
+            concurrency_status: String::new(),
+            cache_policy: CachePolicy::load_from(&cache::default_policy_path()),
+            cache_status: String::new(),
+            scratch_pools: ScratchPools::load_from(&scratch::default_pools_path()),
+            scratch_status: String::new(),
+            reserved_cores: pinning::format_cpulist(&PinningSettings::load_from(&pinning::default_pinning_path()).reserved_cores),
+            pinning_status: String::new(),
+            owner_gpus: owner_reservation.gpus.iter().map(u32::to_string).collect::<Vec<_>>().join(","),
+            owner_reservation,
+            reservation_status: String::new(),
+            claim_code: String::new(),
+            claim_owner: eryzaa_jobs::ProfileStore::load()
+                .ok()
+                .and_then(|profiles| profiles.active().wallet_address.clone())
+                .unwrap_or_default(),
+            claim_status: Arc::new(Mutex::new(String::new())),
+            observer_label: String::new(),
+            observer_revoke: String::new(),
+            observer_status: Arc::new(Mutex::new(String::new())),
+            warm_volumes: warm_pool.volumes.join("\n"),
+            warm_pool,
+            warm_status: String::new(),
+            egress_custom: egress_policy.custom.join("\n"),
+            egress_policy,
+            egress_status: String::new(),
+            abuse_job: None,
+            abuse_category: AbuseCategory::Cryptojacking,
+            abuse_description: String::new(),
+            abuse_status: Arc::new(Mutex::new(String::new())),
+            blocklist_feed: BlocklistFeed::load_from(&abuse::default_feed_path()),
+            blocklist_status: String::new(),
+            confirm_stack_action: None,
+            drain_first: true,
+            stack_status: Arc::new(Mutex::new(String::new())),
+            confirm_panic: false,
+            panic_reason: String::new(),
+            panic_status: Arc::new(Mutex::new(String::new())),
+            log_source: LogSource::Container("rental-server".to_string()),
+            log_filter: String::new(),
+            log_content: String::new(),
+            admission_rules: AdmissionRules::load_from(&admission_rules_path()),
+            admission_status: String::new(),
+            dry_run: AdmissionRequest {
+                client_id: "client-1".to_string(),
+                gpus: 1,
+                image: "ubuntu:22.04".to_string(),
+                ..Default::default()
+            },
+            dry_run_result: None,
+            access_policy: AccessPolicy::load_from(&access_policy_path()),
+            access_status: String::new(),
+            deposits: DepositLedger::load_from(&deposits_path()),
+            forfeit_reason: String::new(),
+            trial_offer: TrialOffer::load_from(&trial_offer_path()),
+            trial_status: String::new(),
+            trials: TrialLedger::load_from(&trials_path()),
+            query_only: !advertisement_privacy.broadcasts(),
+            query_key: advertisement_privacy.query_key.clone().unwrap_or_default(),
+            advertisement_privacy,
+            privacy_status: String::new(),
+            login_approval: LoginApproval::load_from(&login_approval_path()),
+            login_approval_status: String::new(),
+            login_requests: vec![],
+            last_login_poll: SystemTime::UNIX_EPOCH,
+            recordings: Recording::list(std::path::Path::new(recording::RECORDINGS_DIR)),
+            playback: None,
+            playback_at: 0.0,
+            playback_tick: None,
+            recording_search: String::new(),
+            recording_status: String::new(),
+            clock_status: Arc::new(Mutex::new(ClockStatus::default())),
+            last_clock_check: SystemTime::UNIX_EPOCH,
+            market_price: Arc::new(Mutex::new(None)),
+            last_market_check: SystemTime::UNIX_EPOCH,
+            utilization_history: UtilizationHistory::load_from(&utilization_history_path()),
+            last_utilization_sample: SystemTime::UNIX_EPOCH,
+            price_schedule: PriceSchedule::load_from(&price_schedule_path()),
+            price_schedule_status: String::new(),
+            accessibility: Accessibility::load_from(&accessibility_path()),
+            accessibility_status: String::new(),
             last_update: SystemTime::now(),
         }
     }
@@ -83,9 +375,62 @@ pub enum Tab {
     Setup,
     System,
     Network,
+    Logs,
     Settings,
 }
 
+impl Tab {
+    /// Tabs in display order, which is also their Ctrl+1.. shortcut order
+    const ALL: [Tab; 6] = [Tab::Dashboard, Tab::Setup, Tab::System, Tab::Network, Tab::Logs, Tab::Settings];
+    
+    fn label(&self) -> &'static str {
+        match self {
+            Tab::Dashboard => "📊 Dashboard",
+            Tab::Setup => "⚙️ Setup",
+            Tab::System => "🖥️ System",
+            Tab::Network => "🌐 Network",
+            Tab::Logs => "📋 Logs",
+            Tab::Settings => "🔧 Settings",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StackAction {
+    Restart,
+    Stop,
+}
+
+/// Where the log viewer reads from
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogSource {
+    Container(String),
+    Unit(String), // systemd unit, read with journalctl
+}
+
+impl LogSource {
+    fn label(&self) -> String {
+        match self {
+            LogSource::Container(name) => format!("🐳 {}", name),
+            LogSource::Unit(unit) => format!("⚙️ {}", unit),
+        }
+    }
+    
+    /// The last 500 lines
+    fn read(&self) -> Result<String, String> {
+        let output = match self {
+            LogSource::Container(name) => Command::new("docker").args(["logs", "--tail", "500", name]).output(),
+            LogSource::Unit(unit) => Command::new("journalctl").args(["-u", unit, "-n", "500", "--no-pager"]).output(),
+        }
+        .map_err(|e| format!("Failed to read logs: {}", e))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        // docker logs replays the container's stderr on stderr
+        Ok(format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr)))
+    }
+}
+
 impl Default for Tab {
     fn default() -> Self {
         Tab::Dashboard
@@ -100,6 +445,10 @@ pub struct RentalSettings {
     max_memory_usage: f32,
     allowed_clients: Vec<String>,
     pricing_per_hour: f32,
+    egress_alerts: bool,
+    gpu_cooldown_c: f32,
+    quiet_hours_enabled: bool,
+    quiet_hours: QuietHours,
 }
 
 impl Default for RentalSettings {
@@ -111,6 +460,10 @@ impl Default for RentalSettings {
             max_memory_usage: 80.0,
             allowed_clients: vec![],
             pricing_per_hour: 5.0,
+            egress_alerts: true,
+            gpu_cooldown_c: 83.0,
+            quiet_hours_enabled: false,
+            quiet_hours: QuietHours { start_hour: 22, end_hour: 7, power_cap_watts: 150 },
         }
     }
 }
@@ -137,16 +490,47 @@ impl Default for SetupConfig {
 }
 
 impl EryzaaRentalApp {
-    pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
+    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
         let system = Arc::new(Mutex::new(System::new_all()));
         
-        Self {
+        let mut app = Self {
             system,
             last_update: SystemTime::now(),
             ..Default::default()
+        };
+        app.accessibility.apply(&cc.egui_ctx);
+        app.verify_firewall();
+        app
+    }
+    
+    fn firewall_policy(&self) -> FirewallPolicy {
+        // The rental server's network mode; LAN direct admits the LAN instead of the overlay
+        let mode = NetworkMode::load_from(&eryzaa_jobs::default_registry_path().with_file_name("network.json"));
+        let lan_subnet = match mode {
+            NetworkMode::Overlay => None,
+            NetworkMode::LanDirect => overlay::lan_subnet(&overlay::local_addresses_in(mode, "").0),
+        };
+        FirewallPolicy {
+            allowlist: self.settings.allowed_clients.clone(),
+            ..FirewallPolicy::for_mode(mode, lan_subnet.as_deref())
         }
     }
     
+    fn apply_firewall(&mut self) {
+        self.firewall_status = match self.firewall_policy().apply_and_verify() {
+            Ok(state) => format!("✅ Enforced by {}", state.backend),
+            Err(e) => format!("❌ {}", e),
+        };
+    }
+    
+    fn verify_firewall(&mut self) {
+        self.firewall_status = match self.firewall_policy().verify() {
+            Ok(state) if state.is_enforced() => format!("✅ Enforced by {}", state.backend),
+            Ok(state) => format!("⚠️ Missing rules in {}: {}", state.backend, state.missing_rules.join(", ")),
+            Err(e) => format!("❌ {}", e),
+        };
+    }
+    
     fn one_click_setup(&mut self) {
         let status = Arc::clone(&self.setup_status);
         let config = self.setup_config.clone();
@@ -260,12 +644,9 @@ impl EryzaaRentalApp {
             }
             
             // Start Docker service
-            let _ = Command::new("systemctl")
-                .args(&["start", "docker"])
-                .output();
-            let _ = Command::new("systemctl")
-                .args(&["enable", "docker"])
-                .output();
+            if let Some(docker) = ServiceSet::load_from(&services_path()).service("docker") {
+                docker.enable()?;
+            }
         }
         
         #[cfg(target_os = "windows")]
@@ -349,6 +730,11 @@ impl EryzaaRentalApp {
             sys.refresh_all();
             self.last_update = SystemTime::now();
             
+            // Reload the job registry for tenant service mappings
+            if let Ok(manager) = JobManager::load_from(&eryzaa_jobs::default_registry_path()) {
+                self.tenant_jobs = manager.list_jobs();
+            }
+            
             // Update server info
             let mut server_info = self.server_info.lock().unwrap();
             
@@ -366,76 +752,1987 @@ impl EryzaaRentalApp {
                 }
             }
             
-            // Check SSH status - cross-platform
-            #[cfg(unix)]
-            {
-                server_info.ssh_status = Command::new("pgrep")
-                    .arg("sshd")
-                    .output()
-                    .map(|o| o.status.success())
-                    .unwrap_or(false);
-            }
+            // Check SSH status
+            server_info.ssh_status = ServiceSet::load_from(&services_path())
+                .service("sshd")
+                .map(|service| service.is_healthy())
+                .unwrap_or(false);
+        }
+    }
+}
+
+impl EryzaaRentalApp {
+    fn thermal_policy(&self) -> ThermalPolicy {
+        ThermalPolicy {
+            cooldown_temp_c: self.settings.gpu_cooldown_c,
+            quiet_hours: self.settings.quiet_hours_enabled.then(|| self.settings.quiet_hours.clone()),
+            ..Default::default()
+        }
+    }
+    
+    /// Read GPU temperatures and keep the quiet hours power cap in force
+    fn sample_thermals(&mut self) {
+        if self.last_thermal_sample.elapsed().unwrap_or(Duration::new(0, 0)) < Duration::from_secs(10) {
+            return;
+        }
+        self.last_thermal_sample = SystemTime::now();
+        
+        self.gpu_readings = thermal::read_gpus().unwrap_or_default();
+        self.thermal_scheduler.policy = self.thermal_policy();
+        
+        let hour = chrono::Local::now().hour();
+        if let Err(e) = self.thermal_scheduler.enforce_power_cap(hour, &self.gpu_readings) {
+            self.thermal_status = format!("❌ {}", e);
+            return;
+        }
+        
+        self.thermal_status = match self.thermal_scheduler.decide(1, &self.gpu_readings, std::time::Instant::now()) {
+            StartDecision::Start => "✅ Accepting new GPU jobs".to_string(),
+            StartDecision::Defer(reason) => format!("⏳ Deferring new GPU jobs: {}", reason),
+        };
+        if let Some(cap) = self.thermal_scheduler.power_cap(hour) {
+            self.thermal_status.push_str(&format!(" | 🌙 Quiet hours, capped at {} W", cap));
+        }
+    }
+    
+    fn show_thermals(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("🌡️ GPU Thermals");
+            if self.gpu_readings.is_empty() {
+                ui.label("No NVIDIA GPUs reported");
+                return;
+            }
+            
+            for reading in &self.gpu_readings {
+                ui.horizontal(|ui| {
+                    ui.label(format!("GPU {}", reading.index));
+                    let color = if reading.temperature_c >= self.settings.gpu_cooldown_c {
+                        egui::Color32::RED
+                    } else {
+                        egui::Color32::GREEN
+                    };
+                    ui.colored_label(color, format!("{:.0}°C", reading.temperature_c));
+                    ui.label(format!("{:.0} / {:.0} W", reading.power_draw_w, reading.power_limit_w));
+                    if reading.ecc_errors > 0 {
+                        ui.colored_label(egui::Color32::RED, format!("⚠️ {} ECC errors", reading.ecc_errors));
+                    }
+                });
+            }
+            ui.label(&self.thermal_status);
+        });
+    }
+    
+    /// Sample every running tenant container in the background every couple of seconds
+    fn sample_job_usage(&mut self) {
+        if self.last_usage_sample.elapsed().unwrap_or(Duration::new(0, 0)) < Duration::from_secs(2) {
+            return;
+        }
+        // Skip this round while the previous sample is still running
+        if self.usage_sampler.try_lock().is_err() {
+            return;
+        }
+        self.last_usage_sample = SystemTime::now();
+        
+        let containers: Vec<(String, String)> = self
+            .tenant_jobs
+            .iter()
+            .filter(|job| job.status == JobStatus::Running)
+            .map(|job| (job.job_id.clone(), job.container_name.clone()))
+            .collect();
+        let sampler = self.usage_sampler.clone();
+        let job_usage = self.job_usage.clone();
+        thread::spawn(move || {
+            let usage = sampler.lock().unwrap().sample(&containers);
+            *job_usage.lock().unwrap() = usage;
+        });
+    }
+    
+    fn show_job_usage(&mut self, ui: &mut egui::Ui) {
+        let mut report = None;
+        ui.group(|ui| {
+            ui.heading("👥 Tenant Usage");
+            let status = self.abuse_status.lock().unwrap().clone();
+            if !status.is_empty() {
+                ui.label(status);
+            }
+            for job in &self.tenant_jobs {
+                if let (JobStatus::Pending, Some(progress)) = (&job.status, &job.staging) {
+                    let bar = egui::ProgressBar::new(progress.fraction().unwrap_or(0.0)).text(progress.to_string());
+                    ui.horizontal(|ui| {
+                        ui.monospace(&job.job_id);
+                        ui.add(bar);
+                    });
+                }
+            }
+            let mut usage = self.job_usage.lock().unwrap().clone();
+            if usage.is_empty() {
+                ui.label("No tenant jobs running");
+                return;
+            }
+            // Heaviest tenants first
+            usage.sort_by(|a, b| {
+                (b.cpu_percent + b.gpu_util_percent).total_cmp(&(a.cpu_percent + a.gpu_util_percent))
+            });
+            
+            let rate = |bytes: u64| format!("{}/s", transfers::format_bytes(bytes));
+            egui::Grid::new("job_usage_grid").striped(true).show(ui, |ui| {
+                for header in ["Job", "Client", "CPU", "RSS", "GPU", "VRAM", "Disk r/w", "Net ↓/↑", "Session"] {
+                    ui.strong(header);
+                }
+                ui.end_row();
+                
+                let now = chrono::Utc::now();
+                for job in &usage {
+                    let record = self.tenant_jobs.iter().find(|record| record.job_id == job.job_id);
+                    ui.monospace(&job.job_id);
+                    ui.label(record.map(|record| record.client_id.clone()).unwrap_or_default());
+                    ui.label(format!("{:.0}%", job.cpu_percent));
+                    ui.label(transfers::format_bytes(job.rss_bytes));
+                    ui.label(format!("{:.0}%", job.gpu_util_percent));
+                    ui.label(format!("{} MiB", job.vram_mb));
+                    ui.label(format!("{} / {}", rate(job.io_read_bps), rate(job.io_write_bps)));
+                    ui.label(format!("{} / {}", rate(job.net_rx_bps), rate(job.net_tx_bps)));
+                    match record {
+                        Some(record) => ui
+                            .label(record.ticker(now).to_string())
+                            .on_hover_text("Billable time, metered energy and what the tenant owes so far at the agreed rate"),
+                        None => ui.label("-"),
+                    };
+                    if ui.small_button("🚩 Report").on_hover_text("Report this tenant to the coordinator").clicked() {
+                        report = Some(job.job_id.clone());
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+        if report.is_some() {
+            self.abuse_job = report;
+            self.abuse_description.clear();
+        }
+    }
+    
+    fn show_abuse_report_window(&mut self, ctx: &egui::Context, job_id: String) {
+        let mut open = true;
+        let mut submit = false;
+        egui::Window::new("🚩 Report Tenant")
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let client = self.tenant_jobs.iter().find(|job| job.job_id == job_id).map(|job| job.client_id.clone());
+                ui.label(format!("Job {} of {}", job_id, client.as_deref().unwrap_or("unknown client")));
+                egui::ComboBox::from_label("Category")
+                    .selected_text(self.abuse_category.label())
+                    .show_ui(ui, |ui| {
+                        for category in AbuseCategory::ALL {
+                            ui.selectable_value(&mut self.abuse_category, category, category.label());
+                        }
+                    });
+                ui.add(egui::TextEdit::multiline(&mut self.abuse_description).hint_text("What happened").desired_rows(3));
+                ui.weak("The job's receipt, transfer ledger and image are attached as evidence.");
+                if std::env::var(market::COORDINATOR_URL_ENV).is_err() {
+                    ui.colored_label(egui::Color32::YELLOW, format!("⚠️ Set {} to reach the coordinator", market::COORDINATOR_URL_ENV));
+                }
+                submit = ui.button("📨 Submit report").clicked();
+            });
+        
+        if submit {
+            let report = JobManager::load_from(&eryzaa_jobs::default_registry_path()).and_then(|manager| {
+                manager.abuse_report(&job_id, self.abuse_category, &self.abuse_description, &self.transfer_ledger)
+            });
+            let abuse_status = self.abuse_status.clone();
+            thread::spawn(move || {
+                let result = report.and_then(|report| {
+                    let coordinators = CoordinatorSet::from_env().ok_or(format!("{} is not set", market::COORDINATOR_URL_ENV))?;
+                    // Every mirror keeps its own reports
+                    let outcomes = coordinators.publish(|url| eryzaa_discovery::abuse::submit_report(url, &report));
+                    let answers: Vec<String> = outcomes.iter().filter_map(|(_, answer)| answer.as_ref().ok().cloned()).collect();
+                    match answers.first() {
+                        Some(answer) => Ok(answer.clone()),
+                        None => Err(outcomes.into_iter().filter_map(|(_, answer)| answer.err()).collect::<Vec<_>>().join("; ")),
+                    }
+                });
+                *abuse_status.lock().unwrap() = match result {
+                    Ok(answer) => format!("✅ {}", answer),
+                    Err(e) => format!("❌ {}", e),
+                };
+            });
+            *self.abuse_status.lock().unwrap() = "⏳ Submitting report...".to_string();
+            open = false;
+        }
+        if !open {
+            self.abuse_job = None;
+        }
+    }
+    
+    /// Fold the per-job nftables counters into the transfer ledger once a minute
+    fn sample_transfers(&mut self) {
+        if self.last_transfer_sample.elapsed().unwrap_or(Duration::new(0, 0)) < Duration::from_secs(60) {
+            return;
+        }
+        self.last_transfer_sample = SystemTime::now();
+        
+        let running: Vec<&JobRecord> = self
+            .tenant_jobs
+            .iter()
+            .filter(|job| job.status == JobStatus::Running)
+            .collect();
+        let job_ids: Vec<String> = running.iter().map(|job| job.job_id.clone()).collect();
+        
+        // Re-install the counters when the set of running jobs changes
+        if job_ids != self.accounted_jobs {
+            let containers: Vec<AccountedContainer> = running
+                .iter()
+                .filter_map(|job| {
+                    eryzaa_node::accounting::container_ip(&job.container_name)
+                        .map(|ip| AccountedContainer { job_id: job.job_id.clone(), ip })
+                })
+                .collect();
+            match eryzaa_node::accounting::apply_accounting(&containers, &self.firewall_policy().overlay_subnets) {
+                Ok(()) => self.accounted_jobs = job_ids,
+                Err(e) => {
+                    self.transfer_status = format!("❌ {}", e);
+                    return;
+                }
+            }
+        }
+        
+        let counters = match eryzaa_node::accounting::read_counters() {
+            Ok(counters) => counters,
+            Err(e) => {
+                self.transfer_status = format!("❌ {}", e);
+                return;
+            }
+        };
+        
+        let today = chrono::Local::now().date_naive();
+        for c in &counters {
+            self.transfer_ledger.record_totals(&c.job_id, DestinationClass::Overlay, c.overlay_rx, c.overlay_tx, today);
+            self.transfer_ledger.record_totals(&c.job_id, DestinationClass::Internet, c.internet_rx, c.internet_tx, today);
+        }
+        // Inputs the node downloaded itself never pass through a job's counters
+        for job in self.tenant_jobs.iter().filter(|job| job.staged_bytes > 0) {
+            self.transfer_ledger.record_totals(&job.job_id, DestinationClass::Staging, job.staged_bytes, 0, today);
+        }
+        
+        let saved = self
+            .transfer_ledger
+            .save_to(&transfers::default_ledger_path())
+            .and_then(|_| self.transfer_ledger.publish(std::path::Path::new(transfers::PUBLISH_DIR)));
+        if let Err(e) = saved {
+            self.transfer_status = format!("❌ {}", e);
+        }
+        
+        if self.settings.egress_alerts {
+            self.egress_alerts = self.transfer_ledger.egress_alerts(&EgressAlertPolicy::default(), today);
+            for alert in &self.egress_alerts {
+                log::warn!("{}", alert);
+            }
+        }
+    }
+    
+    fn export_transfers(&mut self) {
+        let path = dirs::home_dir()
+            .unwrap_or_default()
+            .join(format!("eryzaa-transfers-{}.csv", chrono::Local::now().format("%Y%m%d")));
+        self.transfer_status = match std::fs::write(&path, self.transfer_ledger.to_csv()) {
+            Ok(()) => format!("✅ Exported to {}", path.display()),
+            Err(e) => format!("❌ Failed to export: {}", e),
+        };
+    }
+    
+    fn show_transfers(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("📶 Transfers");
+            
+            for alert in &self.egress_alerts {
+                ui.colored_label(egui::Color32::YELLOW, format!("⚠️ {}", alert));
+            }
+            
+            let days = self.transfer_ledger.daily_totals();
+            if days.is_empty() {
+                ui.label("No traffic recorded yet");
+            } else {
+                egui::Grid::new("transfers_grid").striped(true).show(ui, |ui| {
+                    ui.strong("Day");
+                    ui.strong("Overlay ↓/↑");
+                    ui.strong("Internet ↓/↑");
+                    ui.strong("Staged ↓");
+                    ui.end_row();
+                    for day in days.iter().rev().take(7) {
+                        ui.label(day.day.map(|d| d.to_string()).unwrap_or_default());
+                        ui.label(format!("{} / {}", transfers::format_bytes(day.overlay_rx), transfers::format_bytes(day.overlay_tx)));
+                        ui.label(format!("{} / {}", transfers::format_bytes(day.internet_rx), transfers::format_bytes(day.internet_tx)));
+                        ui.label(transfers::format_bytes(day.staging_rx));
+                        ui.end_row();
+                    }
+                });
+            }
+            
+            ui.horizontal(|ui| {
+                if ui.button("📤 Export CSV").clicked() {
+                    self.export_transfers();
+                }
+                ui.label(&self.transfer_status);
+            });
+        });
+    }
+    
+    fn save_admission_rules(&mut self) {
+        self.admission_status = match self.admission_rules.save_to(&admission_rules_path()) {
+            Ok(()) => "✅ Saved, applies to new jobs".to_string(),
+            Err(e) => format!("❌ {}", e),
+        };
+    }
+    
+    fn show_advertisement_privacy(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("🕶 Advertisement Privacy");
+            ui.label("Keep details out of what the node broadcasts and registers with the coordinator.");
+            let privacy = &mut self.advertisement_privacy;
+            ui.checkbox(&mut privacy.hide_gpu_model, "Hide GPU models (memory is still shown)");
+            ui.checkbox(&mut privacy.hide_location, "Hide location (LAN address, host name, carbon intensity)");
+            ui.checkbox(&mut self.query_only, "Only answer clients that know the query key");
+            if self.query_only {
+                ui.horizontal(|ui| {
+                    ui.label("Query key:");
+                    ui.add(egui::TextEdit::singleline(&mut self.query_key).password(true));
+                });
+                ui.weak("The node stops broadcasting; give the key to the clients you rent to.");
+            }
+            ui.horizontal(|ui| {
+                if ui.button("💾 Save").clicked() {
+                    let privacy = &mut self.advertisement_privacy;
+                    privacy.query_key = Some(self.query_key.trim().to_string()).filter(|_| self.query_only);
+                    self.privacy_status = match privacy.save_to(&advertisement_privacy_path()) {
+                        Ok(()) => "✅ Saved, applies to the next advertisement and registration".to_string(),
+                        Err(e) => format!("❌ {}", e),
+                    };
+                }
+                ui.label(&self.privacy_status);
+            });
+        });
+    }
+    
+    fn show_trial_offer(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("🎁 Free Trial");
+            ui.label("Each client may run one short CPU-only session to check latency and the environment before paying.");
+            let mut offered = self.trial_offer.is_some();
+            ui.checkbox(&mut offered, "Offer a free trial");
+            if offered {
+                let offer = self.trial_offer.get_or_insert_with(TrialOffer::default);
+                ui.horizontal(|ui| {
+                    ui.label("Length:");
+                    ui.add(egui::DragValue::new(&mut offer.minutes).clamp_range(1..=TrialOffer::MAX_MINUTES).suffix(" min"));
+                    ui.label("CPU share:");
+                    ui.add(egui::DragValue::new(&mut offer.cpu_percent).clamp_range(1..=100).suffix("%"));
+                });
+                ui.weak(format!("Advertised as \"{}\"", offer.label()));
+            } else {
+                self.trial_offer = None;
+            }
+            ui.horizontal(|ui| {
+                if ui.button("💾 Save").clicked() {
+                    let saved = match &self.trial_offer {
+                        Some(offer) => offer.save_to(&trial_offer_path()),
+                        None => TrialOffer::withdraw(&trial_offer_path()),
+                    };
+                    self.trial_status = match saved {
+                        Ok(()) => "✅ Saved, applies to new jobs".to_string(),
+                        Err(e) => format!("❌ {}", e),
+                    };
+                }
+                ui.label(&self.trial_status);
+            });
+            
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label(format!("Trials given: {}", self.trials.trials.len()));
+                if ui.small_button("🔄").clicked() {
+                    self.trials = TrialLedger::load_from(&trials_path());
+                }
+            });
+            for trial in self.trials.trials.iter().rev().take(5) {
+                let when = chrono::DateTime::from_timestamp(trial.granted_at as i64, 0)
+                    .map(|at| at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default();
+                ui.label(format!("{} · job {} · {}", trial.client_id, trial.job_id, when));
+            }
+        });
+    }
+    
+    fn show_access_policy(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("🛡 Client Requirements");
+            ui.label("Advertised to clients and checked before the admission rules.");
+            ui.horizontal(|ui| {
+                let mut has_min = self.access_policy.min_reputation.is_some();
+                ui.checkbox(&mut has_min, "Minimum reputation");
+                if has_min {
+                    let min = self.access_policy.min_reputation.get_or_insert(3.0);
+                    ui.add(egui::DragValue::new(min).clamp_range(0.0..=5.0).speed(0.1));
+                } else {
+                    self.access_policy.min_reputation = None;
+                }
+            });
+            ui.horizontal(|ui| {
+                let mut has_deposit = self.access_policy.deposit.is_some();
+                let label = if self.access_policy.min_reputation.is_some() { "Otherwise a deposit of" } else { "Deposit of" };
+                ui.checkbox(&mut has_deposit, label);
+                if has_deposit {
+                    let deposit = self.access_policy.deposit.get_or_insert(5.0);
+                    ui.add(egui::DragValue::new(deposit).clamp_range(0.1..=1000.0).speed(0.1).suffix(" AVAX"));
+                    ui.label("per job, refunded when it ends");
+                } else {
+                    self.access_policy.deposit = None;
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("💾 Save").clicked() {
+                    self.access_status = match self.access_policy.save_to(&access_policy_path()) {
+                        Ok(()) => "✅ Saved, applies to new jobs".to_string(),
+                        Err(e) => format!("❌ {}", e),
+                    };
+                }
+                ui.label(&self.access_status);
+            });
+            
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("💰 Held deposits");
+                if ui.small_button("🔄").clicked() {
+                    self.deposits = DepositLedger::load_from(&deposits_path());
+                }
+            });
+            let held: Vec<_> = self.deposits.held().into_iter().cloned().collect();
+            if held.is_empty() {
+                ui.label("None");
+                return;
+            }
+            ui.horizontal(|ui| {
+                ui.label("Reason to forfeit:");
+                ui.text_edit_singleline(&mut self.forfeit_reason);
+            });
+            let now = chrono::Utc::now().timestamp() as u64;
+            for deposit in held {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}  {:.2} AVAX  job {}", deposit.client_id, deposit.amount, deposit.job_id.as_deref().unwrap_or("-")));
+                    let settled = if ui.small_button("Refund").clicked() {
+                        Some(self.deposits.refund(&deposit.deposit_id, now))
+                    } else if ui.add_enabled(!self.forfeit_reason.trim().is_empty(), egui::Button::new("Forfeit").small()).clicked() {
+                        Some(self.deposits.forfeit(&deposit.deposit_id, self.forfeit_reason.trim(), now))
+                    } else {
+                        None
+                    };
+                    if let Some(result) = settled {
+                        self.access_status = match result.and_then(|_| self.deposits.save_to(&deposits_path())) {
+                            Ok(()) => format!("✅ Settled deposit {}", deposit.deposit_id),
+                            Err(e) => format!("❌ {}", e),
+                        };
+                    }
+                });
+            }
+        });
+    }
+    
+    /// Owner's answer for a job an admission rule left to them
+    fn decide_pending_job(&mut self, job_id: &str, approve: bool) {
+        let registry = eryzaa_jobs::default_registry_path();
+        let result = JobManager::load_from(&registry).and_then(|manager| {
+            if approve {
+                manager.set_admission(job_id, AdmissionState::Admitted)?;
+            } else {
+                manager.update_status(job_id, JobStatus::Failed("Rejected by owner".to_string()))?;
+            }
+            manager.save_to(&registry)?;
+            self.tenant_jobs = manager.list_jobs();
+            Ok(())
+        });
+        if let Err(e) = result {
+            self.admission_status = format!("❌ {}", e);
+        }
+    }
+    
+    fn show_pending_approvals(&mut self, ui: &mut egui::Ui) {
+        let awaiting: Vec<(String, String, String)> = self
+            .tenant_jobs
+            .iter()
+            .filter(|job| job.status == JobStatus::Pending)
+            .filter_map(|job| match &job.admission {
+                AdmissionState::AwaitingOwner(rule) => Some((job.job_id.clone(), job.image.clone(), rule.clone())),
+                _ => None,
+            })
+            .collect();
+        if awaiting.is_empty() {
+            return;
+        }
+        
+        let mut decisions = Vec::new();
+        ui.group(|ui| {
+            ui.heading("🙋 Jobs Awaiting Approval");
+            for (job_id, image, rule) in &awaiting {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Job {} ({})", job_id, image));
+                    ui.weak(format!("rule: {}", rule));
+                    if ui.button("✅ Approve").clicked() {
+                        decisions.push((job_id.clone(), true));
+                    }
+                    if ui.button("❌ Reject").clicked() {
+                        decisions.push((job_id.clone(), false));
+                    }
+                });
+            }
+        });
+        ui.add_space(10.0);
+        
+        for (job_id, approve) in decisions {
+            self.decide_pending_job(&job_id, approve);
+        }
+    }
+    
+    fn show_admission_rules(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("Admission Rules");
+            ui.label("The first enabled rule whose conditions all match decides.");
+            
+            let templates = [
+                Condition::ClientIs(String::new()),
+                Condition::ReputationBelow(3.0),
+                Condition::GpusAbove(1),
+                Condition::MemoryAboveGb(64),
+                Condition::PriceBelow(self.settings.pricing_per_hour),
+                Condition::DepositBelow(self.access_policy.deposit.unwrap_or(5.0)),
+                Condition::HoursBetween { start_hour: 22, end_hour: 7 },
+                Condition::ImageMatches("*".to_string()),
+            ];
+            edit_rules(ui, "rule_decision", &mut self.admission_rules.rules, &templates);
+            
+            ui.horizontal(|ui| {
+                if ui.button("➕ Add Rule").clicked() {
+                    self.admission_rules.rules.push(AdmissionRule {
+                        name: format!("Rule {}", self.admission_rules.rules.len() + 1),
+                        enabled: true,
+                        conditions: vec![],
+                        decision: AdmissionDecision::AskOwner,
+                    });
+                }
+                ui.label("Otherwise:");
+                decision_combo(ui, "default_decision", &mut self.admission_rules.default_decision);
+            });
+            
+            ui.separator();
+            ui.label("🧪 Dry run");
+            ui.horizontal(|ui| {
+                ui.label("Client:");
+                ui.text_edit_singleline(&mut self.dry_run.client_id);
+                ui.label("Image:");
+                ui.text_edit_singleline(&mut self.dry_run.image);
+            });
+            ui.horizontal(|ui| {
+                ui.label("GPUs:");
+                ui.add(egui::DragValue::new(&mut self.dry_run.gpus).clamp_range(0..=64));
+                ui.label("Memory:");
+                ui.add(egui::DragValue::new(&mut self.dry_run.memory_gb).suffix(" GB"));
+                ui.label("Hour:");
+                ui.add(egui::DragValue::new(&mut self.dry_run.hour).clamp_range(0..=23).suffix(":00"));
+            });
+            ui.horizontal(|ui| {
+                let mut has_reputation = self.dry_run.client_reputation.is_some();
+                ui.checkbox(&mut has_reputation, "Reputation");
+                if has_reputation {
+                    let reputation = self.dry_run.client_reputation.get_or_insert(4.0);
+                    ui.add(egui::DragValue::new(reputation).clamp_range(0.0..=5.0).speed(0.1));
+                } else {
+                    self.dry_run.client_reputation = None;
+                }
+                
+                let mut has_offer = self.dry_run.price_per_hour.is_some();
+                ui.checkbox(&mut has_offer, "Offer");
+                if has_offer {
+                    let price = self.dry_run.price_per_hour.get_or_insert(self.settings.pricing_per_hour);
+                    ui.add(egui::DragValue::new(price).speed(0.1).suffix(" /hour"));
+                } else {
+                    self.dry_run.price_per_hour = None;
+                }
+                
+                let mut has_deposit = self.dry_run.deposit.is_some();
+                ui.checkbox(&mut has_deposit, "Deposit");
+                if has_deposit {
+                    let deposit = self.dry_run.deposit.get_or_insert(self.access_policy.deposit.unwrap_or(5.0));
+                    ui.add(egui::DragValue::new(deposit).speed(0.1).suffix(" AVAX"));
+                } else {
+                    self.dry_run.deposit = None;
+                }
+            });
+            if ui.button("▶ Test").clicked() {
+                self.dry_run_result = Some(self.admission_rules.evaluate(&self.dry_run));
+            }
+            if let Some(result) = &self.dry_run_result {
+                let color = match result.decision {
+                    AdmissionDecision::Accept => egui::Color32::GREEN,
+                    AdmissionDecision::Reject => egui::Color32::RED,
+                    AdmissionDecision::AskOwner => egui::Color32::YELLOW,
+                };
+                ui.colored_label(color, format!("{:?} ({})", result.decision, result.rule.as_deref().unwrap_or("default")));
+                for line in &result.trace {
+                    ui.monospace(line);
+                }
+            }
+            
+            ui.horizontal(|ui| {
+                if ui.button("💾 Save").clicked() {
+                    self.save_admission_rules();
+                }
+                ui.label(&self.admission_status);
+            });
+        });
+    }
+    
+    fn save_login_approval(&mut self) {
+        self.login_approval_status = match self.login_approval.save_to(&login_approval_path()) {
+            Ok(()) => "✅ Saved, the rental server applies it within a few seconds".to_string(),
+            Err(e) => format!("❌ {}", e),
+        };
+    }
+    
+    /// Re-check NTP sync every ten minutes, off the UI thread
+    fn sample_clock(&mut self) {
+        if self.last_clock_check.elapsed().unwrap_or(Duration::new(0, 0)) < Duration::from_secs(600) {
+            return;
+        }
+        self.last_clock_check = SystemTime::now();
+        let clock_status = self.clock_status.clone();
+        thread::spawn(move || {
+            *clock_status.lock().unwrap() = clock::local_clock_status();
+        });
+    }
+    
+    /// Median asking price for this machine's GPU model, refreshed every five minutes
+    fn sample_market(&mut self) {
+        if self.last_market_check.elapsed().unwrap_or(Duration::new(0, 0)) < Duration::from_secs(300) {
+            return;
+        }
+        self.last_market_check = SystemTime::now();
+        let Some(coordinators) = CoordinatorSet::from_env() else {
+            return;
+        };
+        let market_price = self.market_price.clone();
+        thread::spawn(move || {
+            let Ok(stats) = coordinators.call(market::fetch_stats) else {
+                return;
+            };
+            let snapshot = eryzaa_node::HardwareSnapshot::scan();
+            let model = snapshot.gpus.first().map(|gpu| gpu.name.as_str());
+            *market_price.lock().unwrap() = stats.price_hint(model);
+        });
+    }
+    
+    /// Share of the job slots in use, recorded once a minute for the idle forecast
+    fn sample_utilization(&mut self) {
+        if self.last_utilization_sample.elapsed().unwrap_or(Duration::new(0, 0)) < Duration::from_secs(60) {
+            return;
+        }
+        self.last_utilization_sample = SystemTime::now();
+        let running = self.tenant_jobs.iter().filter(|job| job.status == JobStatus::Running).count();
+        let offset = chrono::Local::now().offset().local_minus_utc() as i64;
+        self.utilization_history.record(
+            chrono::Utc::now().timestamp() as u64,
+            offset,
+            running as u32,
+            self.concurrency_limit.max_concurrent_jobs,
+        );
+        if let Err(e) = self.utilization_history.save_to(&utilization_history_path()) {
+            eprintln!("{}", e);
+        }
+    }
+    
+    /// The asking price this hour, the scheduled one if a window holds it
+    fn current_price(&self) -> f32 {
+        let now = chrono::Utc::now().timestamp() as u64;
+        let offset = chrono::Local::now().offset().local_minus_utc() as i64;
+        self.price_schedule.price_at(forecast::hour_of_week(now, offset), self.settings.pricing_per_hour)
+    }
+    
+    /// Login requests waiting for the owner, with the job each one belongs to
+    fn poll_login_requests(&mut self) {
+        if self.last_login_poll.elapsed().unwrap_or(Duration::new(0, 0)) < Duration::from_secs(1) {
+            return;
+        }
+        self.last_login_poll = SystemTime::now();
+        if !self.login_approval.enabled {
+            self.login_requests.clear();
+            return;
+        }
+        self.login_requests = LoginQueue::new(login_approval::QUEUE_DIR).pending();
+        for request in &mut self.login_requests {
+            if let Some(job) = self.tenant_jobs.iter().find(|job| job.ssh_user.as_deref() == Some(request.username.as_str())) {
+                request.job_id = Some(job.job_id.clone());
+                request.client_id = Some(job.client_id.clone());
+            }
+        }
+    }
+    
+    fn show_login_requests_window(&mut self, ctx: &egui::Context) {
+        let mut decisions = Vec::new();
+        egui::Window::new("🔐 SSH Login Requests")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+                for request in &self.login_requests {
+                    ui.group(|ui| {
+                        ui.strong(format!("{} from {}", request.username, request.source));
+                        match (&request.job_id, &request.client_id) {
+                            (Some(job_id), Some(client_id)) => ui.label(format!("Job {} of {}", job_id, client_id)),
+                            _ => ui.colored_label(egui::Color32::YELLOW, "⚠️ Not a user of any known job"),
+                        };
+                        let waited = now.saturating_sub(request.requested_at);
+                        ui.weak(format!(
+                            "Waiting {} s, turned away after {} s",
+                            waited, self.login_approval.timeout_secs
+                        ));
+                        ui.horizontal(|ui| {
+                            let approve = ui.button("✅ Approve");
+                            // Land keyboard users in the dialog rather than behind it
+                            if ui.memory(|m| m.focus().is_none()) {
+                                approve.request_focus();
+                            }
+                            if approve.clicked() {
+                                decisions.push((request.id.clone(), true));
+                            }
+                            if ui.button("❌ Deny").clicked() {
+                                decisions.push((request.id.clone(), false));
+                            }
+                        });
+                    });
+                }
+            });
+        
+        let queue = LoginQueue::new(login_approval::QUEUE_DIR);
+        for (id, approve) in decisions {
+            if let Err(e) = queue.decide(&id, approve) {
+                self.login_approval_status = format!("❌ {}", e);
+            }
+            self.login_requests.retain(|request| request.id != id);
+        }
+    }
+    
+    fn show_scratch_pools(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("Scratch Storage");
+            ui.label("Jobs can ask for a scratch disk of a given class; each class is served from its own directory, ideally on its own disk.");
+            let mut removed = None;
+            egui::Grid::new("scratch_pools_grid").num_columns(6).show(ui, |ui| {
+                ui.strong("Class");
+                ui.strong("Directory");
+                ui.strong("Capacity");
+                ui.strong("Device");
+                ui.strong("IOPS per job");
+                ui.end_row();
+                for (i, pool) in self.scratch_pools.pools.iter_mut().enumerate() {
+                    egui::ComboBox::from_id_source(("scratch_class", i))
+                        .selected_text(format!("{:?}", pool.class))
+                        .show_ui(ui, |ui| {
+                            for class in [StorageClass::Hdd, StorageClass::Ssd, StorageClass::Nvme] {
+                                ui.selectable_value(&mut pool.class, class, format!("{:?}", class));
+                            }
+                        });
+                    let mut root = pool.root.to_string_lossy().into_owned();
+                    if ui.add(egui::TextEdit::singleline(&mut root).desired_width(180.0)).changed() {
+                        pool.root = root.into();
+                    }
+                    ui.add(egui::DragValue::new(&mut pool.capacity_gb).clamp_range(1..=100_000).suffix(" GB"))
+                        .on_hover_text(format!("{} GB in use", pool.used_gb()));
+                    let mut device = pool.device.clone().unwrap_or_default();
+                    if ui.add(egui::TextEdit::singleline(&mut device).hint_text("/dev/nvme0n1").desired_width(120.0)).changed() {
+                        pool.device = Some(device).filter(|device| !device.trim().is_empty());
+                    }
+                    let mut capped = pool.iops_per_job.is_some();
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut capped, "");
+                        if capped {
+                            ui.add(egui::DragValue::new(pool.iops_per_job.get_or_insert(1000)).clamp_range(100..=1_000_000));
+                        } else {
+                            pool.iops_per_job = None;
+                        }
+                    });
+                    if icon_button(ui, "🗑", "Remove pool").clicked() {
+                        removed = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+            if let Some(i) = removed {
+                self.scratch_pools.pools.remove(i);
+            }
+            
+            ui.horizontal(|ui| {
+                if ui.button("➕ Add Pool").clicked() {
+                    self.scratch_pools.pools.push(StoragePool {
+                        class: StorageClass::Ssd,
+                        root: "/var/lib/eryzaa/scratch/ssd".into(),
+                        capacity_gb: 100,
+                        device: None,
+                        iops_per_job: None,
+                    });
+                }
+                if ui.button("💾 Save").clicked() {
+                    self.scratch_status = match self.scratch_pools.save_to(&scratch::default_pools_path()) {
+                        Ok(()) => "✅ Saved, used for the next jobs that ask for scratch".to_string(),
+                        Err(e) => format!("❌ {}", e),
+                    };
+                }
+                ui.label(&self.scratch_status);
+            });
+        });
+    }
+    
+    fn show_core_pinning(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("Dedicated Cores");
+            ui.label("Jobs can ask for cores of their own; they are taken from one NUMA node where possible and never from the cores kept here.");
+            let topology = CpuTopology::detect();
+            for node in &topology.nodes {
+                ui.label(format!("🧮 NUMA node {}: cores {}", node.id, pinning::format_cpulist(&node.cpus)));
+            }
+            ui.horizontal(|ui| {
+                ui.label("Keep for myself:");
+                ui.add(egui::TextEdit::singleline(&mut self.reserved_cores).hint_text("0-1").desired_width(120.0));
+                if ui.button("💾 Save").clicked() {
+                    let settings = PinningSettings { reserved_cores: pinning::parse_cpulist(&self.reserved_cores) };
+                    self.pinning_status = match settings.save_to(&pinning::default_pinning_path()) {
+                        Ok(()) => format!("✅ Saved, {} of {} cores can be dedicated to jobs", topology.cpu_count().saturating_sub(settings.reserved_cores.len()), topology.cpu_count()),
+                        Err(e) => format!("❌ {}", e),
+                    };
+                    self.reserved_cores = pinning::format_cpulist(&settings.reserved_cores);
+                }
+                ui.label(&self.pinning_status);
+            });
+        });
+    }
+    
+    fn show_owner_reservation(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("Reserved for Me");
+            ui.label("Kept out of what this node advertises and out of reach of every job and job user, so the machine stays usable while rented.");
+            ui.horizontal(|ui| {
+                ui.label("Cores:");
+                ui.add(egui::DragValue::new(&mut self.owner_reservation.cores).clamp_range(0..=256));
+                ui.label("Memory:");
+                ui.add(egui::DragValue::new(&mut self.owner_reservation.memory_gb).clamp_range(0..=4096).suffix(" GB"));
+                ui.label("GPUs:");
+                ui.add(egui::TextEdit::singleline(&mut self.owner_gpus).hint_text("0,1").desired_width(80.0));
+            });
+            ui.small("Cores kept from dedicated cores count towards these. The integrated GPU is never given to jobs.");
+            ui.horizontal(|ui| {
+                if ui.button("💾 Save").clicked() {
+                    self.owner_reservation.gpus = self.owner_gpus.split(',').filter_map(|index| index.trim().parse().ok()).collect();
+                    self.owner_gpus = self.owner_reservation.gpus.iter().map(u32::to_string).collect::<Vec<_>>().join(",");
+                    self.reservation_status = match self.owner_reservation.save_to(&reservation::default_reservation_path()) {
+                        Ok(()) if self.owner_reservation.is_empty() => "✅ Saved, nothing is reserved".to_string(),
+                        Ok(()) => "✅ Saved, applied to jobs started from now on".to_string(),
+                        Err(e) => format!("❌ {}", e),
+                    };
+                }
+                ui.label(&self.reservation_status);
+            });
+        });
+    }
+    
+    fn show_node_claim(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("🔗 Claim a Node");
+            ui.label("Link a node to your account with the claim code `rental onboard` printed on it.");
+            ui.horizontal(|ui| {
+                ui.label("Claim code:");
+                ui.add(egui::TextEdit::singleline(&mut self.claim_code).hint_text("K7QM-3XPD").desired_width(120.0));
+                ui.label("Account:");
+                ui.add(egui::TextEdit::singleline(&mut self.claim_owner).hint_text("0x...").desired_width(260.0));
+            });
+            // Only the coordinator that issued the claim code can redeem it
+            let coordinator_url = Onboarding::load_from(&onboarding_path()).coordinator_url.or_else(first_coordinator);
+            if coordinator_url.is_none() {
+                ui.colored_label(egui::Color32::YELLOW, format!("⚠️ Set {} to reach the coordinator", market::COORDINATOR_URL_ENV));
+            }
+            ui.horizontal(|ui| {
+                let ready = coordinator_url.is_some() && !self.claim_code.trim().is_empty() && !self.claim_owner.trim().is_empty();
+                if ui.add_enabled(ready, egui::Button::new("🔗 Claim")).clicked() {
+                    let url = coordinator_url.unwrap_or_default();
+                    let code = onboarding::claim_code_from(&self.claim_code).to_string();
+                    let owner = self.claim_owner.trim().to_string();
+                    let claim_status = self.claim_status.clone();
+                    thread::spawn(move || {
+                        *claim_status.lock().unwrap() = match registration::claim_node(&url, &code, &owner) {
+                            Ok(receipt) => {
+                                // Keep the owner secret when this is the node being claimed
+                                let mut onboarding = Onboarding::load_from(&onboarding_path());
+                                if onboarding.node_id == receipt.registration.node_id {
+                                    onboarding.owner = Some(owner.clone());
+                                    onboarding.owner_secret = Some(receipt.owner_secret.clone());
+                                    let _ = onboarding.save_to(&onboarding_path());
+                                }
+                                let node = &receipt.registration;
+                                format!(
+                                    "✅ {} ({}) is now yours; owner secret {} (keep it to share the node's status)",
+                                    node.hostname,
+                                    node.overlay_ip.as_deref().unwrap_or(&node.node_id),
+                                    receipt.owner_secret
+                                )
+                            }
+                            Err(e) => format!("❌ {}", e),
+                        };
+                    });
+                    *self.claim_status.lock().unwrap() = "⏳ Claiming...".to_string();
+                    self.claim_code.clear();
+                }
+                ui.label(self.claim_status.lock().unwrap().as_str());
+            });
+        });
+    }
+    
+    fn show_observer_tokens(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("📡 Share Node Status");
+            ui.label("Observer tokens show this node's status, utilization and price, nothing else. Embed the link on your website or send it to prospective clients.");
+            let onboarding = Onboarding::load_from(&onboarding_path());
+            let coordinator_url = onboarding.coordinator_url.clone().or_else(first_coordinator);
+            let owner = onboarding.owner.clone().unwrap_or_else(|| self.claim_owner.trim().to_string());
+            let owner_secret = onboarding.owner_secret.clone().unwrap_or_default();
+            if coordinator_url.is_none() {
+                ui.colored_label(egui::Color32::YELLOW, format!("⚠️ Set {} to reach the coordinator", market::COORDINATOR_URL_ENV));
+            } else if onboarding.node_id.is_empty() || owner.is_empty() || owner_secret.is_empty() {
+                ui.colored_label(egui::Color32::YELLOW, "⚠️ Onboard and claim this node from this machine first");
+            }
+            let ready = coordinator_url.is_some() && !onboarding.node_id.is_empty() && !owner.is_empty() && !owner_secret.is_empty();
+            
+            ui.horizontal(|ui| {
+                ui.label("Label:");
+                ui.add(egui::TextEdit::singleline(&mut self.observer_label).hint_text("My rig").desired_width(160.0));
+                if ui.add_enabled(ready, egui::Button::new("🔑 Mint token")).clicked() {
+                    let url = coordinator_url.clone().unwrap_or_default();
+                    let (node_id, owner, label) = (onboarding.node_id.clone(), owner.clone(), self.observer_label.trim().to_string());
+                    let owner_secret = owner_secret.clone();
+                    let observer_status = self.observer_status.clone();
+                    thread::spawn(move || {
+                        *observer_status.lock().unwrap() = match observers::mint_token(&url, &node_id, &owner, &owner_secret, &label) {
+                            Ok(token) => {
+                                let page = observers::page_url(&url, &token.token);
+                                format!("✅ Token {}\n{}\n<iframe src=\"{}\" width=\"320\" height=\"80\"></iframe>", token.token, page, page)
+                            }
+                            Err(e) => format!("❌ {}", e),
+                        };
+                    });
+                    *self.observer_status.lock().unwrap() = "⏳ Minting...".to_string();
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Token:");
+                ui.add(egui::TextEdit::singleline(&mut self.observer_revoke).desired_width(260.0));
+                if ui.add_enabled(ready && !self.observer_revoke.trim().is_empty(), egui::Button::new("🚫 Revoke")).clicked() {
+                    let url = coordinator_url.clone().unwrap_or_default();
+                    let token = self.observer_revoke.trim().to_string();
+                    let observer_status = self.observer_status.clone();
+                    thread::spawn(move || {
+                        *observer_status.lock().unwrap() = match observers::revoke_token(&url, &token, &owner, &owner_secret) {
+                            Ok(answer) => format!("✅ {}", answer),
+                            Err(e) => format!("❌ {}", e),
+                        };
+                    });
+                    *self.observer_status.lock().unwrap() = "⏳ Revoking...".to_string();
+                    self.observer_revoke.clear();
+                }
+            });
+            // Selectable, so the link and snippet can be copied
+            let mut status = self.observer_status.lock().unwrap().clone();
+            if !status.is_empty() {
+                ui.add(egui::TextEdit::multiline(&mut status).desired_rows(3).desired_width(f32::INFINITY));
+            }
+        });
+    }
+    
+    fn show_warm_pool(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("⚡ Warm Pool");
+            ui.checkbox(&mut self.warm_pool.enabled, "Keep an idle container ready so interactive jobs start in seconds");
+            ui.add_enabled_ui(self.warm_pool.enabled, |ui| {
+                egui::Grid::new("warm_pool_grid").num_columns(2).show(ui, |ui| {
+                    ui.label("Image:");
+                    ui.add(egui::TextEdit::singleline(&mut self.warm_pool.image).hint_text("pytorch/pytorch:2.1.0-cuda12.1-cudnn8-runtime").desired_width(320.0));
+                    ui.end_row();
+                    ui.label("Datasets:");
+                    ui.add(egui::TextEdit::multiline(&mut self.warm_volumes).hint_text("/data/imagenet:/datasets/imagenet").desired_rows(3).desired_width(320.0))
+                        .on_hover_text("One source:target per line, mounted read-only unless a mode is given");
+                    ui.end_row();
+                    ui.label("GPUs:");
+                    let mut gpus = self.warm_pool.gpus.clone().unwrap_or_default();
+                    if ui.add(egui::TextEdit::singleline(&mut gpus).hint_text("all").desired_width(60.0)).changed() {
+                        self.warm_pool.gpus = Some(gpus.trim().to_string()).filter(|gpus| !gpus.is_empty());
+                    }
+                    ui.end_row();
+                });
+                ui.label("Jobs for this image that run its own command take over the idle container; others start as usual.");
+            });
+            
+            ui.horizontal(|ui| {
+                if ui.button("💾 Save").clicked() {
+                    self.warm_pool.volumes = self
+                        .warm_volumes
+                        .lines()
+                        .map(|line| line.trim().to_string())
+                        .filter(|line| !line.is_empty())
+                        .collect();
+                    self.warm_status = match self.warm_pool.save_to(&warm::default_pool_path()) {
+                        Ok(()) if self.warm_pool.enabled => "✅ Saved, the rental server starts the container on its next pass".to_string(),
+                        Ok(()) => "✅ Saved, the rental server removes the idle container".to_string(),
+                        Err(e) => format!("❌ {}", e),
+                    };
+                }
+                ui.label(&self.warm_status);
+            });
+        });
+    }
+    
+    fn show_egress_policy(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("🌐 Outbound Traffic");
+            ui.label("What tenant jobs may connect to:");
+            for preset in EgressPreset::ALL {
+                ui.radio_value(&mut self.egress_policy.preset, preset, preset.label());
+            }
+            if self.egress_policy.preset == EgressPreset::Registries {
+                ui.collapsing("Allowed registries and git hosts", |ui| {
+                    ui.weak(eryzaa_node::egress::REGISTRY_DOMAINS.join(", "));
+                });
+            }
+            ui.add_enabled_ui(self.egress_policy.is_restricted(), |ui| {
+                ui.label("Also allow:");
+                ui.add(egui::TextEdit::multiline(&mut self.egress_custom).hint_text("wandb.ai\n203.0.113.0/24").desired_rows(3).desired_width(320.0))
+                    .on_hover_text("One domain (subdomains included) or IPv4 address/CIDR per line");
+                ui.weak("Domains are resolved through dnsmasq on the Docker bridge, which must be installed.");
+            });
+            
+            ui.horizontal(|ui| {
+                if ui.button("💾 Save").clicked() {
+                    let custom: Vec<String> = self
+                        .egress_custom
+                        .lines()
+                        .map(|line| line.trim().to_string())
+                        .filter(|line| !line.is_empty())
+                        .collect();
+                    self.egress_status = match custom.iter().try_for_each(|entry| eryzaa_node::egress::validate_entry(entry)) {
+                        Ok(()) => {
+                            self.egress_policy.custom = custom;
+                            match self.egress_policy.save_to(&egress_policy_path()) {
+                                Ok(()) => "✅ Saved, the rental server applies it to running jobs within a minute".to_string(),
+                                Err(e) => format!("❌ {}", e),
+                            }
+                        }
+                        Err(e) => format!("❌ {}", e),
+                    };
+                }
+                ui.label(&self.egress_status);
+            });
+        });
+    }
+    
+    fn show_blocklist_feed(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("🚫 Shared Blocklist");
+            ui.checkbox(&mut self.blocklist_feed.subscribed, "Turn away clients other renters reported to the coordinator");
+            if self.blocklist_feed.subscribed {
+                ui.label(format!("{} clients blocked in the last copy fetched", self.blocklist_feed.blocklist.entries.len()));
+                for entry in &self.blocklist_feed.blocklist.entries {
+                    ui.weak(format!("{}: {}", entry.client_id, entry.reason));
+                }
+            }
+            ui.horizontal(|ui| {
+                if ui.button("💾 Save").clicked() {
+                    self.blocklist_status = match self.blocklist_feed.save_to(&abuse::default_feed_path()) {
+                        Ok(()) if self.blocklist_feed.subscribed => "✅ Saved, the rental server refreshes the blocklist every ten minutes".to_string(),
+                        Ok(()) => "✅ Saved".to_string(),
+                        Err(e) => format!("❌ {}", e),
+                    };
+                }
+                ui.label(&self.blocklist_status);
+            });
+        });
+    }
+    
+    fn show_login_approval(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("🔐 SSH Login Approval");
+            ui.checkbox(&mut self.login_approval.enabled, "Ask me before anyone logs in to a job");
+            ui.checkbox(&mut self.login_approval.record_sessions, "Record interactive job sessions")
+                .on_hover_text("Shells are recorded with script(1); file copies are only noted");
+            ui.add_enabled_ui(self.login_approval.enabled, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Turn logins away after");
+                    ui.add(egui::DragValue::new(&mut self.login_approval.timeout_secs).clamp_range(15..=900).suffix(" s"));
+                });
+                ui.label("Rules answer a login before asking; Accept approves it, Reject denies it.");
+                let templates = [
+                    Condition::ClientIs(String::new()),
+                    Condition::HoursBetween { start_hour: 9, end_hour: 17 },
+                ];
+                edit_rules(ui, "login_rule_decision", &mut self.login_approval.rules.rules, &templates);
+                ui.horizontal(|ui| {
+                    if ui.button("➕ Add Rule").clicked() {
+                        self.login_approval.rules.rules.push(AdmissionRule {
+                            name: "Business hours".to_string(),
+                            enabled: true,
+                            conditions: vec![Condition::HoursBetween { start_hour: 9, end_hour: 17 }],
+                            decision: AdmissionDecision::Accept,
+                        });
+                    }
+                    ui.label("Otherwise:");
+                    decision_combo(ui, "login_default_decision", &mut self.login_approval.rules.default_decision);
+                });
+            });
+            
+            ui.horizontal(|ui| {
+                if ui.button("💾 Save").clicked() {
+                    self.save_login_approval();
+                }
+                ui.label(&self.login_approval_status);
+            });
+        });
+    }
+    
+    fn show_recordings(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("🎞 Session Recordings");
+            ui.horizontal(|ui| {
+                if ui.button("🔄 Refresh").clicked() {
+                    self.recordings = Recording::list(std::path::Path::new(recording::RECORDINGS_DIR));
+                }
+                ui.label(format!("{} recording(s) in {}", self.recordings.len(), recording::RECORDINGS_DIR));
+            });
+            
+            let mut open = None;
+            egui::ScrollArea::vertical().id_source("recordings").max_height(120.0).show(ui, |ui| {
+                for rec in &self.recordings {
+                    let started = chrono::DateTime::from_timestamp(rec.meta.started_at as i64, 0)
+                        .map(|at| at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                        .unwrap_or_default();
+                    let selected = self.playback.as_ref().is_some_and(|(open, _)| open.meta.id == rec.meta.id);
+                    if ui.selectable_label(selected, format!("{}  {} from {}", started, rec.meta.username, rec.meta.source)).clicked() {
+                        open = Some(rec.clone());
+                    }
+                }
+            });
+            if let Some(rec) = open {
+                match rec.load() {
+                    Ok(playback) => {
+                        self.playback = Some((rec, playback));
+                        self.playback_at = 0.0;
+                        self.playback_tick = None;
+                        self.recording_status.clear();
+                    }
+                    Err(e) => self.recording_status = format!("❌ {}", e),
+                }
+            }
+            
+            if let Some((rec, playback)) = &self.playback {
+                let duration = playback.duration();
+                if let Some(tick) = self.playback_tick {
+                    self.playback_at += tick.elapsed().unwrap_or_default().as_secs_f64();
+                    self.playback_tick = Some(SystemTime::now());
+                    if self.playback_at >= duration {
+                        self.playback_at = duration;
+                        self.playback_tick = None;
+                    }
+                    ui.ctx().request_repaint_after(Duration::from_millis(50));
+                }
+                
+                ui.separator();
+                ui.horizontal(|ui| {
+                    let playing = self.playback_tick.is_some();
+                    if ui.button(if playing { "⏸ Pause" } else { "▶ Play" }).clicked() {
+                        if playing {
+                            self.playback_tick = None;
+                        } else {
+                            if self.playback_at >= duration {
+                                self.playback_at = 0.0;
+                            }
+                            self.playback_tick = Some(SystemTime::now());
+                        }
+                    }
+                    ui.add(egui::Slider::new(&mut self.playback_at, 0.0..=duration.max(0.1)).suffix(" s").fixed_decimals(1));
+                    if ui.button("💾 Export asciinema").clicked() {
+                        self.recording_status = match rec.export_asciicast() {
+                            Ok(path) => format!("✅ Exported to {}", path.display()),
+                            Err(e) => format!("❌ {}", e),
+                        };
+                    }
+                });
+                
+                let mut screen = playback.screen_at(self.playback_at, 24);
+                ui.add(
+                    egui::TextEdit::multiline(&mut screen)
+                        .font(egui::TextStyle::Monospace)
+                        .desired_width(f32::INFINITY)
+                        .desired_rows(24)
+                        .interactive(false),
+                );
+                
+                ui.horizontal(|ui| {
+                    ui.label("🔍 Search:");
+                    ui.text_edit_singleline(&mut self.recording_search);
+                });
+                let hits = playback.search(&self.recording_search);
+                if !self.recording_search.is_empty() {
+                    ui.label(format!("{} match(es)", hits.len()));
+                }
+                egui::ScrollArea::vertical().id_source("recording_hits").max_height(100.0).show(ui, |ui| {
+                    for (at, line) in hits.iter().take(100) {
+                        if ui.link(format!("{:>7.1} s  {}", at, line.trim())).clicked() {
+                            self.playback_at = *at;
+                            self.playback_tick = None;
+                        }
+                    }
+                });
+            }
+            ui.label(&self.recording_status);
+        });
+    }
+    
+    fn save_energy_model(&mut self) {
+        self.energy_status = match self.energy_model.save_to(&energy_model_path()) {
+            Ok(()) => "✅ Saved, applied on the next metering pass".to_string(),
+            Err(e) => format!("❌ {}", e),
+        };
+    }
+    
+    fn save_node_details(&mut self) {
+        self.details_status = match self.node_details.save_to(&node_details_path()) {
+            Ok(()) => "✅ Saved, republished with the next advertisement".to_string(),
+            Err(e) => format!("❌ {}", e),
+        };
+    }
+    
+    fn show_node_details_editor(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("Advertisement");
+            ui.label("Description shown to clients:");
+            ui.add(egui::TextEdit::multiline(&mut self.node_details.description).desired_rows(2).hint_text("e.g. Quiet water-cooled box on fiber"));
+            
+            let mut photo_url = self.node_details.photo_url.clone().unwrap_or_default();
+            ui.horizontal(|ui| {
+                ui.label("Hardware photo URL:");
+                ui.add(egui::TextEdit::singleline(&mut photo_url).hint_text("https://..."));
+            });
+            self.node_details.photo_url = if photo_url.trim().is_empty() { None } else { Some(photo_url.trim().to_string()) };
+            
+            let mut removed = None;
+            egui::Grid::new("node_metadata_grid").num_columns(3).show(ui, |ui| {
+                for (i, (key, value)) in self.node_details.metadata.iter_mut().enumerate() {
+                    ui.add(egui::TextEdit::singleline(key).desired_width(120.0));
+                    ui.add(egui::TextEdit::singleline(value).desired_width(200.0));
+                    if icon_button(ui, "🗑", "Remove this note").clicked() {
+                        removed = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+            if let Some(i) = removed {
+                self.node_details.metadata.remove(i);
+            }
+            
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut self.new_metadata.0).desired_width(120.0).hint_text("NVLink"));
+                ui.add(egui::TextEdit::singleline(&mut self.new_metadata.1).desired_width(200.0).hint_text("yes"));
+                let full = self.node_details.metadata.len() >= eryzaa_discovery::details::MAX_METADATA_ENTRIES;
+                if ui.add_enabled(!full && !self.new_metadata.0.trim().is_empty(), egui::Button::new("➕ Add")).clicked() {
+                    let (key, value) = std::mem::take(&mut self.new_metadata);
+                    self.node_details.metadata.push((key.trim().to_string(), value.trim().to_string()));
+                }
+            });
+            
+            ui.horizontal(|ui| {
+                if ui.button("💾 Save").clicked() {
+                    self.save_node_details();
+                }
+                ui.label(&self.details_status);
+            });
+        });
+    }
+    
+    fn show_energy(&self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("⚡ Energy");
+            let now = chrono::Utc::now();
+            let receipts: Vec<_> = self
+                .tenant_jobs
+                .iter()
+                .filter(|job| job.energy_wh > 0.0)
+                .map(|job| job.receipt(now))
+                .collect();
+            if receipts.is_empty() {
+                ui.label("No energy recorded for tenant jobs yet");
+                return;
+            }
+            
+            egui::Grid::new("energy_grid").striped(true).show(ui, |ui| {
+                ui.strong("Job");
+                ui.strong("Energy");
+                ui.strong("Carbon");
+                ui.end_row();
+                for r in &receipts {
+                    ui.label(&r.job_id);
+                    ui.label(format!("{:.2} kWh", r.energy_kwh));
+                    ui.label(r.carbon_g().map(receipt::format_carbon).unwrap_or_else(|| "-".to_string()));
+                    ui.end_row();
+                }
+            });
+            
+            let total_kwh: f64 = receipts.iter().map(|r| r.energy_kwh).sum();
+            let total_carbon: f64 = receipts.iter().filter_map(|r| r.carbon_g()).sum();
+            ui.label(format!("Total: {:.2} kWh · 🌱 {}", total_kwh, receipt::format_carbon(total_carbon)));
+        });
+    }
+}
+
+/// Energy model shared with the rental server
+fn energy_model_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("energy.json")
+}
+
+/// Hourly job slot utilization, recorded by this app
+fn utilization_history_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("utilization.json")
+}
+
+/// Asking prices by hour of the week, over the base price
+fn price_schedule_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("price-schedule.json")
+}
+
+/// Job concurrency limit shared with the rental server
+fn concurrency_limit_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("concurrency.json")
+}
+
+/// Advertisement details read by the discovery service
+fn node_details_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("advertisement.json")
+}
+
+/// Supervised services shared with the rental server
+fn services_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("services.json")
+}
+
+/// Outbound traffic policy enforced by the rental server
+fn onboarding_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("onboarding.json")
+}
+
+/// The first coordinator in the environment, for nodes that have not registered with one
+fn first_coordinator() -> Option<String> {
+    CoordinatorSet::from_env().and_then(|coordinators| coordinators.urls().first().cloned())
+}
+
+fn egress_policy_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("egress.json")
+}
+
+/// Login approval settings read by the rental server
+fn login_approval_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("login-approval.json")
+}
+
+/// Accessibility settings shared with the client app
+fn accessibility_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("accessibility.json")
+}
+
+/// Admission rules shared with the rental server
+fn admission_rules_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("admission.json")
+}
+
+/// Reputation or deposit clients need, shared with the rental server
+fn access_policy_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("access.json")
+}
+
+/// Client deposits the rental server binds to jobs and refunds
+fn deposits_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("deposits.json")
+}
+
+/// Free trial the node offers, shared with the rental server and the discovery service
+fn trial_offer_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("trial.json")
+}
+
+/// Fields the node keeps out of its advertisement, shared with the rental server and the discovery service
+fn advertisement_privacy_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("privacy.json")
+}
+
+/// Clients the rental server gave their free trial
+fn trials_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("trials.json")
+}
+
+/// Lockdown state; the rental server starts no job while it is active
+fn lockdown_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("lockdown.json")
+}
+
+/// Incident snapshots taken by the panic button
+fn incidents_dir() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("incidents")
+}
+
+/// Cut every tenant off as `rental panic` does, stopping their jobs in the registry
+fn engage_lockdown(reason: &str, overlay_subnets: &[String]) -> Result<Incident, String> {
+    let registry = eryzaa_jobs::default_registry_path();
+    let manager = JobManager::load_from(&registry)?;
+    let jobs: Vec<JobRecord> = manager
+        .list_jobs()
+        .into_iter()
+        .filter(|job| matches!(job.status, JobStatus::Running | JobStatus::Paused) && job.client_id != stack::STACK_CLIENT)
+        .collect();
+    let tenants = lockdown::Tenants {
+        containers: jobs.iter().filter(|job| job.node_address.is_none()).map(|job| job.container_name.clone()).collect(),
+        users: jobs.iter().filter_map(|job| job.ssh_user.clone()).collect(),
+    }
+    .with_system_users();
+    
+    let hardware_log = registry.with_file_name("hardware-changes.jsonl");
+    let audit_logs = [("hardware-changes", hardware_log.as_path()), ("job-registry", registry.as_path())];
+    let incident = lockdown::engage(reason, &tenants, overlay_subnets, &audit_logs, &incidents_dir())?;
+    
+    for job in &jobs {
+        let _ = manager.update_status(&job.job_id, JobStatus::Failed(format!("Stopped by the node's panic button ({})", incident.incident_id)));
+    }
+    manager.save_to(&registry)?;
+    LockdownState {
+        active: true,
+        incident_id: Some(incident.incident_id.clone()),
+        engaged_at: Some(incident.taken_at),
+        locked_users: tenants.users,
+    }
+    .save_to(&lockdown_path())?;
+    Ok(incident)
+}
+
+/// Display preferences for low vision and keyboard-only use, shared with the client app
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Accessibility {
+    pub large_text: bool,
+}
+
+/// Text and control size multiplier in larger-text mode
+const LARGE_TEXT_SCALE: f32 = 1.4;
+
+impl Accessibility {
+    fn load_from(path: &std::path::Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+    
+    fn save_to(&self, path: &std::path::Path) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize accessibility settings: {}", e))?;
+        std::fs::write(path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+    
+    /// Scale text from egui's defaults and outline the focused widget
+    fn apply(&self, ctx: &egui::Context) {
+        let defaults = egui::Style::default();
+        let scale = if self.large_text { LARGE_TEXT_SCALE } else { 1.0 };
+        let mut style = (*ctx.style()).clone();
+        for (text_style, font) in style.text_styles.iter_mut() {
+            font.size = defaults.text_styles[text_style].size * scale;
+        }
+        style.spacing.interact_size.y = defaults.spacing.interact_size.y * scale;
+        style.visuals.widgets.active.bg_stroke.width = 2.0;
+        ctx.set_style(style);
+    }
+}
+
+/// Ctrl+1.. picks a tab directly, Ctrl+PageUp/PageDown steps through them
+fn tab_shortcut<T: Clone + PartialEq>(ctx: &egui::Context, tabs: &[T], current: &T) -> Option<T> {
+    const KEYS: [egui::Key; 9] = [
+        egui::Key::Num1, egui::Key::Num2, egui::Key::Num3,
+        egui::Key::Num4, egui::Key::Num5, egui::Key::Num6,
+        egui::Key::Num7, egui::Key::Num8, egui::Key::Num9,
+    ];
+    let position = tabs.iter().position(|tab| tab == current).unwrap_or(0);
+    ctx.input_mut(|input| {
+        if let Some(tab) = tabs.iter().zip(KEYS).find(|(_, key)| input.consume_key(egui::Modifiers::COMMAND, *key)) {
+            return Some(tab.0.clone());
+        }
+        if input.consume_key(egui::Modifiers::COMMAND, egui::Key::PageDown) {
+            return Some(tabs[(position + 1) % tabs.len()].clone());
+        }
+        if input.consume_key(egui::Modifiers::COMMAND, egui::Key::PageUp) {
+            return Some(tabs[(position + tabs.len() - 1) % tabs.len()].clone());
+        }
+        None
+    })
+}
+
+/// Icon-only button with `label` as its tooltip and screen-reader name
+fn icon_button(ui: &mut egui::Ui, icon: &str, label: &str) -> egui::Response {
+    let response = ui.small_button(icon).on_hover_text(label);
+    response.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, label));
+    response
+}
+
+/// Editor for an ordered rule list; `templates` are the conditions that can be added
+fn edit_rules(ui: &mut egui::Ui, id_source: &str, rules: &mut Vec<AdmissionRule>, templates: &[Condition]) {
+    let mut remove_rule = None;
+    let mut move_up = None;
+    for (i, rule) in rules.iter_mut().enumerate() {
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                let enabled = rule.enabled;
+                ui.checkbox(&mut rule.enabled, "")
+                    .widget_info(|| egui::WidgetInfo::selected(egui::WidgetType::Checkbox, enabled, "Rule enabled"));
+                ui.text_edit_singleline(&mut rule.name);
+                ui.label("→");
+                decision_combo(ui, (id_source, i), &mut rule.decision);
+                if i > 0 && icon_button(ui, "⬆", "Move rule up").clicked() {
+                    move_up = Some(i);
+                }
+                if icon_button(ui, "🗑", "Remove rule").clicked() {
+                    remove_rule = Some(i);
+                }
+            });
+            
+            let mut remove_condition = None;
+            for (j, condition) in rule.conditions.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    edit_condition(ui, condition);
+                    if icon_button(ui, "✖", "Remove condition").clicked() {
+                        remove_condition = Some(j);
+                    }
+                });
+            }
+            if let Some(j) = remove_condition {
+                rule.conditions.remove(j);
+            }
+            
+            ui.menu_button("➕ Condition", |ui| {
+                for template in templates {
+                    if ui.button(template.to_string()).clicked() {
+                        rule.conditions.push(template.clone());
+                        ui.close_menu();
+                    }
+                }
+            });
+        });
+    }
+    if let Some(i) = remove_rule {
+        rules.remove(i);
+    }
+    if let Some(i) = move_up {
+        rules.swap(i - 1, i);
+    }
+}
+
+/// Editor for one condition's value
+fn edit_condition(ui: &mut egui::Ui, condition: &mut Condition) {
+    match condition {
+        Condition::ClientIs(client) => {
+            ui.label("Client is");
+            ui.text_edit_singleline(client);
+        }
+        Condition::ReputationBelow(min) => {
+            ui.label("Reputation below");
+            ui.add(egui::DragValue::new(min).clamp_range(0.0..=5.0).speed(0.1));
+        }
+        Condition::GpusAbove(gpus) => {
+            ui.label("More GPUs than");
+            ui.add(egui::DragValue::new(gpus).clamp_range(0..=64));
+        }
+        Condition::MemoryAboveGb(gb) => {
+            ui.label("More memory than");
+            ui.add(egui::DragValue::new(gb).clamp_range(0..=4096).suffix(" GB"));
+        }
+        Condition::PriceBelow(price) => {
+            ui.label("Offer below");
+            ui.add(egui::DragValue::new(price).clamp_range(0.0..=1000.0).speed(0.1).suffix(" /hour"));
+        }
+        Condition::DepositBelow(amount) => {
+            ui.label("Deposit below");
+            ui.add(egui::DragValue::new(amount).clamp_range(0.0..=1000.0).speed(0.1).suffix(" AVAX"));
+        }
+        Condition::HoursBetween { start_hour, end_hour } => {
+            ui.label("Between");
+            ui.add(egui::DragValue::new(start_hour).clamp_range(0..=23).suffix(":00"));
+            ui.label("and");
+            ui.add(egui::DragValue::new(end_hour).clamp_range(0..=23).suffix(":00"));
+        }
+        Condition::ImageMatches(pattern) => {
+            ui.label("Image matches");
+            ui.text_edit_singleline(pattern);
+        }
+    }
+}
+
+fn decision_combo(ui: &mut egui::Ui, id: impl std::hash::Hash, decision: &mut AdmissionDecision) {
+    egui::ComboBox::from_id_source(id)
+        .selected_text(format!("{:?}", decision))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(decision, AdmissionDecision::Accept, "Accept");
+            ui.selectable_value(decision, AdmissionDecision::Reject, "Reject");
+            ui.selectable_value(decision, AdmissionDecision::AskOwner, "AskOwner");
+        });
+}
+
+impl eframe::App for EryzaaRentalApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // Auto-update system info
+        self.update_system_info();
+        self.sample_transfers();
+        self.sample_thermals();
+        self.sample_job_usage();
+        self.sample_clock();
+        self.sample_market();
+        self.sample_utilization();
+        self.poll_login_requests();
+        ctx.request_repaint_after(Duration::from_secs(if self.login_requests.is_empty() { 2 } else { 1 }));
+        
+        // Show setup wizard if not set up
+        let status = self.setup_status.lock().unwrap().clone();
+        if matches!(status, SetupStatus::NotStarted) && !self.show_setup_wizard {
+            self.show_setup_wizard = true;
+        }
+        
+        if self.show_setup_wizard {
+            self.show_setup_wizard_window(ctx);
+        }
+        
+        if let Some(action) = self.confirm_stack_action {
+            self.show_stack_confirm_window(ctx, action);
+        }
+        
+        if self.confirm_panic {
+            self.show_panic_confirm_window(ctx);
+        }
+        
+        if !self.login_requests.is_empty() {
+            self.show_login_requests_window(ctx);
+        }
+        
+        if let Some(job_id) = self.abuse_job.clone() {
+            self.show_abuse_report_window(ctx, job_id);
+        }
+        
+        if let Some(tab) = tab_shortcut(ctx, &Tab::ALL, &self.selected_tab) {
+            self.selected_tab = tab;
+        }
+        
+        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
+            egui::menu::bar(ui, |ui| {
+                ui.heading("🏠 Eryzaa Rental Server");
+                ui.separator();
+                
+                for (i, tab) in Tab::ALL.iter().enumerate() {
+                    ui.selectable_value(&mut self.selected_tab, tab.clone(), tab.label())
+                        .on_hover_text(format!("Ctrl+{}", i + 1));
+                }
+                
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.button("🔄 Refresh").clicked() {
+                        self.update_system_info();
+                    }
+                    if LockdownState::load_from(&lockdown_path()).active {
+                        ui.colored_label(egui::Color32::RED, "🔒 Locked down");
+                    } else if ui
+                        .add(egui::Button::new(egui::RichText::new("🚨 Panic").color(egui::Color32::WHITE)).fill(egui::Color32::DARK_RED))
+                        .on_hover_text("Cut off every tenant at once and keep an incident snapshot")
+                        .clicked()
+                    {
+                        self.confirm_panic = true;
+                    }
+                    if let Some(warning) = self.clock_status.lock().unwrap().warning() {
+                        ui.colored_label(egui::Color32::YELLOW, "🕒 Clock drift")
+                            .on_hover_text(format!("{}. Tenant billing and job expiry use this clock.", warning));
+                    }
+                });
+            });
+        });
+        
+        egui::CentralPanel::default().show(ctx, |ui| {
+            match self.selected_tab {
+                Tab::Dashboard => self.show_dashboard(ui),
+                Tab::Setup => self.show_setup(ui),
+                Tab::System => self.show_system(ui),
+                Tab::Network => self.show_network(ui),
+                Tab::Logs => self.show_logs(ui),
+                Tab::Settings => self.show_settings(ui),
+            }
+        });
+    }
+}
+
+impl EryzaaRentalApp {
+    fn show_stack_confirm_window(&mut self, ctx: &egui::Context, action: StackAction) {
+        let running = self
+            .tenant_jobs
+            .iter()
+            .filter(|job| job.status == JobStatus::Running && job.client_id != stack::STACK_CLIENT)
+            .count();
+        let verb = match action {
+            StackAction::Restart => "Restart",
+            StackAction::Stop => "Stop",
+        };
+        let mut decision = None;
+        
+        egui::Window::new(format!("{} the rental server?", verb))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                match action {
+                    StackAction::Restart => ui.label("The rental stack containers are stopped and started again."),
+                    StackAction::Stop => ui.label("The rental stack containers are stopped; clients can no longer reach this node."),
+                };
+                ui.label(format!("{} tenant jobs are running.", running));
+                ui.checkbox(&mut self.drain_first, "Drain first: start no new jobs and wait for running ones to finish");
+                ui.horizontal(|ui| {
+                    if ui.button(format!("✅ {}", verb)).clicked() {
+                        decision = Some(true);
+                    }
+                    let cancel = ui.button("Cancel");
+                    // Keyboard focus starts on the harmless choice
+                    if ui.memory(|m| m.focus().is_none()) {
+                        cancel.request_focus();
+                    }
+                    if cancel.clicked() || ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape)) {
+                        decision = Some(false);
+                    }
+                });
+            });
+        
+        match decision {
+            Some(true) => {
+                self.confirm_stack_action = None;
+                self.run_stack_action(action, self.drain_first);
+            }
+            Some(false) => self.confirm_stack_action = None,
+            None => {}
+        }
+    }
+    
+    /// Drain if asked, then stop or restart the stack in the background
+    fn run_stack_action(&mut self, action: StackAction, drain: bool) {
+        if drain {
+            if let Err(e) = stack::set_draining(true) {
+                *self.stack_status.lock().unwrap() = format!("❌ {}", e);
+                return;
+            }
+        }
+        
+        let status = self.stack_status.clone();
+        thread::spawn(move || {
+            let registry = eryzaa_jobs::default_registry_path();
+            if drain {
+                loop {
+                    // Lifting the drain cancels the action
+                    if !stack::is_draining() {
+                        *status.lock().unwrap() = "Drain cancelled".to_string();
+                        return;
+                    }
+                    let running = JobManager::load_from(&registry)
+                        .map(|manager| manager.running_tenant_jobs())
+                        .unwrap_or(0);
+                    if running == 0 {
+                        break;
+                    }
+                    *status.lock().unwrap() = format!("⏳ Waiting for {} tenant jobs to finish", running);
+                    thread::sleep(Duration::from_secs(5));
+                }
+            }
+            
+            *status.lock().unwrap() = "⏳ Working...".to_string();
+            let result = JobManager::load_from(&registry).and_then(|manager| {
+                let mode = match action {
+                    StackAction::Restart => manager.restart_stack()?,
+                    StackAction::Stop => manager.stop_stack()?,
+                };
+                manager.save_to(&registry)?;
+                Ok(mode)
+            });
+            let _ = stack::set_draining(false);
             
-            #[cfg(windows)]
-            {
-                server_info.ssh_status = Command::new("sc")
-                    .args(&["query", "sshd"])
-                    .output()
-                    .map(|o| o.status.success())
-                    .unwrap_or(false);
+            *status.lock().unwrap() = match (result, action) {
+                (Ok(mode), StackAction::Restart) => format!("✅ Restarted the {} stack", mode),
+                (Ok(mode), StackAction::Stop) => format!("✅ Stopped the {} stack", mode),
+                (Err(e), _) => format!("❌ {}", e),
+            };
+        });
+    }
+    
+    fn show_panic_confirm_window(&mut self, ctx: &egui::Context) {
+        let mut decision = None;
+        egui::Window::new("🚨 Cut off all tenants?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label("Overlay and container traffic is dropped, tenant sessions are killed, job users are locked and job containers are killed.");
+                ui.label("A snapshot of processes, connections and recent logins is kept for review. Killed jobs do not resume.");
+                ui.horizontal(|ui| {
+                    ui.label("Reason:");
+                    ui.text_edit_singleline(&mut self.panic_reason);
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("🚨 Lock down now").clicked() {
+                        decision = Some(true);
+                    }
+                    let cancel = ui.button("Cancel");
+                    if ui.memory(|m| m.focus().is_none()) {
+                        cancel.request_focus();
+                    }
+                    if cancel.clicked() || ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape)) {
+                        decision = Some(false);
+                    }
+                });
+            });
+        
+        match decision {
+            Some(true) => {
+                self.confirm_panic = false;
+                let reason = match self.panic_reason.trim() {
+                    "" => "Panic button (rental GUI)".to_string(),
+                    reason => reason.to_string(),
+                };
+                let overlay = self.firewall_policy().overlay_subnets;
+                let status = self.panic_status.clone();
+                *status.lock().unwrap() = "⏳ Locking down...".to_string();
+                thread::spawn(move || {
+                    *status.lock().unwrap() = match engage_lockdown(&reason, &overlay) {
+                        Ok(incident) => {
+                            let failed = incident.steps.iter().filter(|step| !step.ok).count();
+                            format!("🔒 Locked down, incident {} ({} of {} steps failed)", incident.incident_id, failed, incident.steps.len())
+                        }
+                        Err(e) => format!("❌ Lockdown incomplete: {}", e),
+                    };
+                });
             }
+            Some(false) => self.confirm_panic = false,
+            None => {}
         }
     }
-}
-
-impl eframe::App for EryzaaRentalApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Auto-update system info
-        self.update_system_info();
-        ctx.request_repaint_after(Duration::from_secs(2));
+    
+    fn show_lockdown(&mut self, ui: &mut egui::Ui) {
+        let state = LockdownState::load_from(&lockdown_path());
+        if state.active {
+            ui.horizontal(|ui| {
+                ui.colored_label(
+                    egui::Color32::RED,
+                    format!("🔒 Locked down ({}); no tenant can reach this node", state.incident_id.as_deref().unwrap_or("no incident")),
+                );
+                if ui.button("Lift lockdown").clicked() {
+                    let failed: Vec<String> = lockdown::lift(&state)
+                        .into_iter()
+                        .filter(|step| !step.ok)
+                        .map(|step| format!("{}: {}", step.action, step.detail))
+                        .collect();
+                    let saved = LockdownState::default().save_to(&lockdown_path());
+                    *self.panic_status.lock().unwrap() = match (failed.is_empty(), saved) {
+                        (_, Err(e)) => format!("❌ {}", e),
+                        (true, Ok(())) => "✅ Lockdown lifted; killed containers stay stopped".to_string(),
+                        (false, Ok(())) => format!("⚠️ Lifted with errors: {}", failed.join("; ")),
+                    };
+                }
+            });
+        }
+        let status = self.panic_status.lock().unwrap().clone();
+        if !status.is_empty() {
+            ui.label(status);
+        }
         
-        // Show setup wizard if not set up
-        let status = self.setup_status.lock().unwrap().clone();
-        if matches!(status, SetupStatus::NotStarted) && !self.show_setup_wizard {
-            self.show_setup_wizard = true;
+        let incidents = Incident::list(&incidents_dir());
+        if !incidents.is_empty() {
+            ui.collapsing(format!("🗂 Incidents ({})", incidents.len()), |ui| {
+                for incident in incidents.iter().take(10) {
+                    let taken = chrono::DateTime::from_timestamp(incident.taken_at as i64, 0)
+                        .map(|at| at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                        .unwrap_or_default();
+                    ui.collapsing(format!("{}  {}", taken, incident.reason), |ui| {
+                        for step in &incident.steps {
+                            ui.label(format!("{} {}{}", if step.ok { "✅" } else { "❌" }, step.action, if step.ok { String::new() } else { format!(": {}", step.detail) }));
+                        }
+                        ui.label(format!("Snapshot: {}", incidents_dir().join(format!("{}.json", incident.incident_id)).display()));
+                    });
+                }
+            });
         }
+    }
+    
+    fn open_logs(&mut self, source: LogSource) {
+        self.log_source = source;
+        self.refresh_logs();
+        self.selected_tab = Tab::Logs;
+    }
+    
+    fn refresh_logs(&mut self) {
+        self.log_content = self.log_source.read().unwrap_or_else(|e| format!("❌ {}", e));
+    }
+    
+    fn show_logs(&mut self, ui: &mut egui::Ui) {
+        ui.heading("📋 Logs");
+        ui.separator();
         
-        if self.show_setup_wizard {
-            self.show_setup_wizard_window(ctx);
+        // Stack containers first, then tenant jobs, then host services
+        let mut sources = Vec::new();
+        for stack_containers in [true, false] {
+            sources.extend(
+                self.tenant_jobs
+                    .iter()
+                    .filter(|job| job.status == JobStatus::Running)
+                    .filter(|job| (job.client_id == stack::STACK_CLIENT) == stack_containers)
+                    .map(|job| LogSource::Container(job.container_name.clone())),
+            );
+        }
+        sources.push(LogSource::Unit("docker".to_string()));
+        sources.push(LogSource::Unit("zerotier-one".to_string()));
+        if !sources.contains(&self.log_source) {
+            sources.insert(0, self.log_source.clone());
         }
         
-        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
-            egui::menu::bar(ui, |ui| {
-                ui.heading("🏠 Eryzaa Rental Server");
-                ui.separator();
-                
-                ui.selectable_value(&mut self.selected_tab, Tab::Dashboard, "📊 Dashboard");
-                ui.selectable_value(&mut self.selected_tab, Tab::Setup, "⚙️ Setup");
-                ui.selectable_value(&mut self.selected_tab, Tab::System, "🖥️ System");
-                ui.selectable_value(&mut self.selected_tab, Tab::Network, "🌐 Network");
-                ui.selectable_value(&mut self.selected_tab, Tab::Settings, "🔧 Settings");
-                
-                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                    if ui.button("🔄 Refresh").clicked() {
-                        self.update_system_info();
+        let mut selected = None;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("log_source")
+                .selected_text(self.log_source.label())
+                .show_ui(ui, |ui| {
+                    for source in &sources {
+                        if ui.selectable_label(source == &self.log_source, source.label()).clicked() {
+                            selected = Some(source.clone());
+                        }
                     }
                 });
-            });
+            if ui.button("🔄 Refresh").clicked() {
+                self.refresh_logs();
+            }
+            ui.label("Filter:");
+            ui.text_edit_singleline(&mut self.log_filter);
         });
+        if let Some(source) = selected {
+            self.open_logs(source);
+        }
         
-        egui::CentralPanel::default().show(ctx, |ui| {
-            match self.selected_tab {
-                Tab::Dashboard => self.show_dashboard(ui),
-                Tab::Setup => self.show_setup(ui),
-                Tab::System => self.show_system(ui),
-                Tab::Network => self.show_network(ui),
-                Tab::Settings => self.show_settings(ui),
+        ui.add_space(10.0);
+        
+        let filter = self.log_filter.to_lowercase();
+        egui::ScrollArea::vertical().stick_to_bottom(true).show(ui, |ui| {
+            for line in self.log_content.lines().filter(|line| line.to_lowercase().contains(&filter)) {
+                ui.monospace(line);
             }
         });
     }
-}
-
-impl EryzaaRentalApp {
+    
     fn show_setup_wizard_window(&mut self, ctx: &egui::Context) {
         egui::Window::new("🚀 Eryzaa Setup Wizard")
             .collapsible(false)
@@ -597,20 +2894,74 @@ impl EryzaaRentalApp {
         
         ui.add_space(10.0);
         
+        // Tenant Services
+        ui.group(|ui| {
+            ui.heading("🔌 Tenant Services");
+            let with_ports: Vec<&JobRecord> = self.tenant_jobs.iter().filter(|j| !j.port_mappings.is_empty()).collect();
+            if with_ports.is_empty() {
+                ui.label("No ports leased to jobs");
+            } else {
+                for job in with_ports {
+                    let ports: Vec<String> = job.port_mappings.iter().map(|m| m.to_string()).collect();
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Job {}", job.job_id));
+                        ui.monospace(ports.join(", "));
+                    });
+                }
+            }
+        });
+        
+        ui.add_space(10.0);
+        
+        drop(sys);
+        self.show_pending_approvals(ui);
+        self.show_job_usage(ui);
+        
+        ui.add_space(10.0);
+        
+        self.show_thermals(ui);
+        
+        ui.add_space(10.0);
+        
+        self.show_energy(ui);
+        
+        ui.add_space(10.0);
+        
+        self.show_transfers(ui);
+        
+        ui.add_space(10.0);
+        
         // Quick Actions
         ui.group(|ui| {
             ui.heading("Quick Actions");
             ui.horizontal(|ui| {
                 if ui.button("🔄 Restart Server").clicked() {
-                    // Restart server
+                    self.confirm_stack_action = Some(StackAction::Restart);
                 }
                 if ui.button("🛑 Stop Server").clicked() {
-                    // Stop server
+                    self.confirm_stack_action = Some(StackAction::Stop);
                 }
                 if ui.button("📋 View Logs").clicked() {
-                    // View logs
+                    let container = JobManager::load_from(&eryzaa_jobs::default_registry_path())
+                        .ok()
+                        .and_then(|manager| manager.active_stack())
+                        .and_then(|(_, config)| config.specs.first().map(|spec| spec.name.clone()))
+                        .unwrap_or_else(|| "rental-server".to_string());
+                    self.open_logs(LogSource::Container(container));
                 }
             });
+            if stack::is_draining() {
+                ui.horizontal(|ui| {
+                    ui.colored_label(egui::Color32::YELLOW, "⏳ Draining: no new jobs start");
+                    if ui.small_button("Cancel drain").clicked() {
+                        if let Err(e) = stack::set_draining(false) {
+                            *self.stack_status.lock().unwrap() = format!("❌ {}", e);
+                        }
+                    }
+                });
+            }
+            ui.label(self.stack_status.lock().unwrap().as_str());
+            self.show_lockdown(ui);
         });
     }
     
@@ -709,6 +3060,51 @@ impl EryzaaRentalApp {
         });
     }
     
+    fn run_network_diagnostics(&mut self, network_id: &str) {
+        *self.diagnosing.lock().unwrap() = true;
+        let checks = self.network_checks.clone();
+        let diagnosing = self.diagnosing.clone();
+        let network_id = network_id.to_string();
+        thread::spawn(move || {
+            *checks.lock().unwrap() = diagnostics::diagnose(Some(&network_id));
+            *diagnosing.lock().unwrap() = false;
+        });
+    }
+    
+    fn show_network_diagnostics(&mut self, ui: &mut egui::Ui, network_id: &str) {
+        ui.group(|ui| {
+            ui.heading("🩺 Network Diagnostics");
+            ui.label("Checks why clients might not reach this node");
+            
+            let running = *self.diagnosing.lock().unwrap();
+            ui.horizontal(|ui| {
+                if ui.add_enabled(!running, egui::Button::new("🔍 Run Diagnostics")).clicked() {
+                    self.run_network_diagnostics(network_id);
+                }
+                if running {
+                    ui.spinner();
+                }
+            });
+            
+            for check in self.network_checks.lock().unwrap().iter() {
+                ui.horizontal(|ui| {
+                    let (icon, color) = match check.severity {
+                        Severity::Ok => ("✅", egui::Color32::GREEN),
+                        Severity::Warning => ("⚠️", egui::Color32::YELLOW),
+                        Severity::Problem => ("❌", egui::Color32::RED),
+                    };
+                    ui.colored_label(color, format!("{} {}", icon, check.name));
+                    ui.label(&check.detail);
+                });
+                if let Some(hint) = &check.hint {
+                    ui.indent((&check.name, &check.detail), |ui| {
+                        ui.weak(format!("→ {}", hint));
+                    });
+                }
+            }
+        });
+    }
+    
     fn show_network(&mut self, ui: &mut egui::Ui) {
         ui.heading("🌐 Network Status");
         ui.separator();
@@ -725,6 +3121,10 @@ impl EryzaaRentalApp {
         
         ui.add_space(10.0);
         
+        self.show_network_diagnostics(ui, &server_info.zerotier_network);
+        
+        ui.add_space(10.0);
+        
         // Network Interfaces - simplified for now
         ui.group(|ui| {
             ui.heading("Network Interfaces");
@@ -734,6 +3134,30 @@ impl EryzaaRentalApp {
         
         ui.add_space(10.0);
         
+        // Firewall Policy
+        ui.group(|ui| {
+            ui.heading("🛡️ Firewall Policy");
+            ui.label("Node ports are only reachable from these sources:");
+            for line in self.firewall_policy().describe() {
+                ui.monospace(line);
+            }
+            ui.label(format!("Status: {}", self.firewall_status));
+            ui.horizontal(|ui| {
+                if ui.button("🛡️ Apply Policy").clicked() {
+                    self.apply_firewall();
+                }
+                if ui.button("🔍 Verify Rules").clicked() {
+                    self.verify_firewall();
+                }
+            });
+        });
+        
+        ui.add_space(10.0);
+        
+        self.show_egress_policy(ui);
+        
+        ui.add_space(10.0);
+        
         // Connection Info
         ui.group(|ui| {
             ui.heading("Connection Information");
@@ -741,7 +3165,7 @@ impl EryzaaRentalApp {
             ui.horizontal(|ui| {
                 let ssh_cmd = format!("ssh rental@{}", server_info.zerotier_ip);
                 ui.monospace(&ssh_cmd);
-                if ui.button("📋").clicked() {
+                if icon_button(ui, "📋", "Copy SSH command").clicked() {
                     ui.output_mut(|o| o.copied_text = ssh_cmd);
                 }
             });
@@ -761,26 +3185,257 @@ impl EryzaaRentalApp {
             ui.add_space(10.0);
             
             ui.horizontal(|ui| {
-                ui.label("Max CPU Usage:");
-                ui.add(egui::Slider::new(&mut self.settings.max_cpu_usage, 10.0..=100.0).suffix("%"));
+                let label = ui.label("Max CPU Usage:");
+                ui.add(egui::Slider::new(&mut self.settings.max_cpu_usage, 10.0..=100.0).suffix("%")).labelled_by(label.id);
             });
             
             ui.horizontal(|ui| {
-                ui.label("Max Memory Usage:");
-                ui.add(egui::Slider::new(&mut self.settings.max_memory_usage, 10.0..=100.0).suffix("%"));
+                let label = ui.label("Max Memory Usage:");
+                ui.add(egui::Slider::new(&mut self.settings.max_memory_usage, 10.0..=100.0).suffix("%")).labelled_by(label.id);
             });
         });
         
         ui.add_space(10.0);
         
+        ui.group(|ui| {
+            ui.heading("♿ Accessibility");
+            if ui.checkbox(&mut self.accessibility.large_text, "Larger text").changed() {
+                self.accessibility.apply(ui.ctx());
+                self.accessibility_status = match self.accessibility.save_to(&accessibility_path()) {
+                    Ok(()) => String::new(),
+                    Err(e) => format!("❌ {}", e),
+                };
+            }
+            ui.label("Keyboard: Tab and Shift+Tab move focus, Space or Enter activates, Ctrl+1-6 or Ctrl+PageUp/PageDown switch tabs, Esc cancels a confirmation.");
+            if !self.accessibility_status.is_empty() {
+                ui.label(&self.accessibility_status);
+            }
+        });
+        
+        ui.add_space(10.0);
+        
         ui.group(|ui| {
             ui.heading("Pricing");
             ui.horizontal(|ui| {
-                ui.label("Price per hour: $");
-                ui.add(egui::DragValue::new(&mut self.settings.pricing_per_hour).speed(0.1));
+                let label = ui.label("Price per hour: $");
+                ui.add(egui::DragValue::new(&mut self.settings.pricing_per_hour).speed(0.1)).labelled_by(label.id);
+                if let Some(median) = *self.market_price.lock().unwrap() {
+                    ui.label(format!("Network median for this GPU: {:.2}", median))
+                        .on_hover_text("Median asking price of comparable nodes, from the coordinator");
+                }
+            });
+            if !self.price_schedule.windows.is_empty() {
+                ui.label(format!("Asking ${:.2} this hour", self.current_price()));
+                for scheduled in &self.price_schedule.windows {
+                    ui.label(format!("  {}: ${:.2} ({})", scheduled.window.label(), scheduled.price_per_hour, scheduled.reason));
+                }
+            }
+            
+            ui.separator();
+            let forecast = self.utilization_history.forecast();
+            let known = forecast.iter().filter(|hour| hour.utilization.is_some()).count();
+            if known == 0 {
+                ui.label(format!(
+                    "Price suggestions appear after {} weeks of recorded utilization",
+                    forecast::MIN_WEEKS
+                ));
+            } else {
+                let idle = forecast::idle_windows(&forecast);
+                ui.label(format!("Forecast from {} of {} hours of the week:", known, forecast::HOURS_PER_WEEK));
+                if idle.is_empty() {
+                    ui.label("  No hours are idle week after week");
+                }
+                for window in &idle {
+                    ui.label(format!("  💤 Usually idle {}", window.label()));
+                }
+                
+                let suggestions = forecast::suggest_prices(&forecast, self.settings.pricing_per_hour);
+                for suggestion in &suggestions {
+                    ui.label(format!("  💡 {}: ${:.2} ({})", suggestion.window.label(), suggestion.price_per_hour, suggestion.reason));
+                }
+                ui.horizontal(|ui| {
+                    let apply = ui.add_enabled(!suggestions.is_empty(), egui::Button::new("✅ Apply suggestions"));
+                    if apply.on_hover_text("Schedule these prices over the base price above").clicked() {
+                        let schedule = PriceSchedule { windows: suggestions.clone() };
+                        self.price_schedule_status = match schedule.save_to(&price_schedule_path()) {
+                            Ok(()) => format!("✅ Scheduled {} price windows", schedule.windows.len()),
+                            Err(e) => format!("❌ {}", e),
+                        };
+                        self.price_schedule = schedule;
+                    }
+                    if !self.price_schedule.windows.is_empty() && ui.button("Clear schedule").clicked() {
+                        let schedule = PriceSchedule::default();
+                        self.price_schedule_status = match schedule.save_to(&price_schedule_path()) {
+                            Ok(()) => "✅ Asking the base price all week".to_string(),
+                            Err(e) => format!("❌ {}", e),
+                        };
+                        self.price_schedule = schedule;
+                    }
+                });
+            }
+            if !self.price_schedule_status.is_empty() {
+                ui.label(&self.price_schedule_status);
+            }
+        });
+        
+        ui.add_space(10.0);
+        
+        ui.group(|ui| {
+            ui.heading("Thermals & Power");
+            ui.horizontal(|ui| {
+                let label = ui.label("Defer new GPU jobs above:");
+                ui.add(egui::Slider::new(&mut self.settings.gpu_cooldown_c, 60.0..=95.0).suffix("°C")).labelled_by(label.id);
+            });
+            ui.checkbox(&mut self.settings.quiet_hours_enabled, "Quiet hours (cap GPU power to keep fans down)");
+            ui.add_enabled_ui(self.settings.quiet_hours_enabled, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("From");
+                    ui.add(egui::DragValue::new(&mut self.settings.quiet_hours.start_hour).clamp_range(0..=23).suffix(":00"));
+                    ui.label("to");
+                    ui.add(egui::DragValue::new(&mut self.settings.quiet_hours.end_hour).clamp_range(0..=23).suffix(":00"));
+                    ui.label("cap");
+                    ui.add(egui::DragValue::new(&mut self.settings.quiet_hours.power_cap_watts).clamp_range(50..=600).suffix(" W"));
+                });
+            });
+        });
+        
+        ui.add_space(10.0);
+        
+        self.show_admission_rules(ui);
+        
+        ui.add_space(10.0);
+        
+        self.show_access_policy(ui);
+        
+        ui.add_space(10.0);
+        
+        self.show_trial_offer(ui);
+        
+        ui.add_space(10.0);
+        
+        self.show_advertisement_privacy(ui);
+        
+        ui.add_space(10.0);
+        
+        self.show_login_approval(ui);
+        
+        ui.add_space(10.0);
+        
+        self.show_recordings(ui);
+        
+        ui.add_space(10.0);
+        
+        self.show_node_details_editor(ui);
+        
+        ui.add_space(10.0);
+        
+        ui.group(|ui| {
+            ui.heading("Energy & Carbon");
+            ui.horizontal(|ui| {
+                ui.label("CPU package TDP:");
+                ui.add(egui::DragValue::new(&mut self.energy_model.cpu_tdp_watts).clamp_range(5.0..=500.0).suffix(" W"));
+            });
+            let mut report_carbon = self.energy_model.carbon_intensity_g_per_kwh.is_some();
+            ui.checkbox(&mut report_carbon, "Report carbon to clients");
+            if report_carbon {
+                let intensity = self.energy_model.carbon_intensity_g_per_kwh.get_or_insert(400.0);
+                ui.horizontal(|ui| {
+                    ui.label("Grid carbon intensity:");
+                    ui.add(egui::DragValue::new(intensity).clamp_range(0.0..=1500.0).suffix(" gCO2/kWh"));
+                });
+            } else {
+                self.energy_model.carbon_intensity_g_per_kwh = None;
+            }
+            ui.horizontal(|ui| {
+                if ui.button("💾 Save").clicked() {
+                    self.save_energy_model();
+                }
+                ui.label(&self.energy_status);
+            });
+        });
+        
+        ui.add_space(10.0);
+        
+        ui.group(|ui| {
+            ui.heading("Concurrency");
+            let running = self.tenant_jobs.iter().filter(|job| job.status == JobStatus::Running).count();
+            ui.horizontal(|ui| {
+                ui.label("Max concurrent jobs:");
+                ui.add(egui::DragValue::new(&mut self.concurrency_limit.max_concurrent_jobs).clamp_range(1..=64));
+                ui.label(format!("({} running, {} free)", running, self.concurrency_limit.free_slots(running)));
+            });
+            ui.horizontal(|ui| {
+                if ui.button("💾 Save").clicked() {
+                    self.concurrency_status = match self.concurrency_limit.save_to(&concurrency_limit_path()) {
+                        Ok(()) => "✅ Saved, advertised with the next update".to_string(),
+                        Err(e) => format!("❌ {}", e),
+                    };
+                }
+                ui.label(&self.concurrency_status);
+            });
+        });
+        
+        ui.add_space(10.0);
+        
+        ui.group(|ui| {
+            ui.heading("Result Cache");
+            ui.checkbox(&mut self.cache_policy.enabled, "Answer identical deterministic stages from cached results");
+            ui.horizontal(|ui| {
+                ui.label("Cache fee per answer: $");
+                ui.add(egui::DragValue::new(&mut self.cache_policy.cache_fee).speed(0.01).clamp_range(0.0..=100.0));
+            });
+            ui.horizontal(|ui| {
+                if ui.button("💾 Save").clicked() {
+                    self.cache_status = match self.cache_policy.save_to(&cache::default_policy_path()) {
+                        Ok(()) => "✅ Saved".to_string(),
+                        Err(e) => format!("❌ {}", e),
+                    };
+                }
+                if ui.button("🗑 Clear cache").clicked() {
+                    self.cache_status = match ResultCache::open_default().clear() {
+                        Ok(()) => "✅ Cache cleared".to_string(),
+                        Err(e) => format!("❌ {}", e),
+                    };
+                }
+                ui.label(&self.cache_status);
             });
         });
         
+        ui.add_space(10.0);
+        
+        self.show_scratch_pools(ui);
+        
+        ui.add_space(10.0);
+        
+        self.show_core_pinning(ui);
+        
+        ui.add_space(10.0);
+        
+        self.show_owner_reservation(ui);
+        
+        ui.add_space(10.0);
+        
+        self.show_node_claim(ui);
+        
+        ui.add_space(10.0);
+        
+        self.show_observer_tokens(ui);
+        
+        ui.add_space(10.0);
+        
+        self.show_warm_pool(ui);
+        
+        ui.add_space(10.0);
+        
+        self.show_blocklist_feed(ui);
+        
+        ui.add_space(10.0);
+        
+        ui.group(|ui| {
+            ui.heading("Transfers");
+            ui.checkbox(&mut self.settings.egress_alerts, "Alert on unusual internet egress from tenant jobs");
+        });
+        
         ui.add_space(20.0);
         
         ui.horizontal(|ui| {
@@ -796,6 +3451,10 @@ impl EryzaaRentalApp {
 
 fn main() -> Result<(), eframe::Error> {
     env_logger::init();
+    if let Err(e) = eryzaa_jobs::schema::prepare() {
+        eprintln!("Cannot open the saved state: {}", e);
+        std::process::exit(1);
+    }
     
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
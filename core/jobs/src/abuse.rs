@@ -0,0 +1,153 @@
+//! Reporting abusive tenants
+//! A renter's report carries what this node metered for the job: its receipt, its transfer
+//! ledger and how it was started. Subscribing to the coordinator's blocklist turns away
+//! clients other renters have reported before their jobs are admitted.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::transfers::{format_bytes, TransferLedger};
+use crate::{DestinationClass, JobManager};
+
+pub use eryzaa_discovery::abuse::{AbuseCategory, AbuseReport, Blocklist, BlocklistEntry, Evidence};
+
+impl JobManager {
+    /// Report on a job's client, with this node's metering of the job as evidence
+    pub fn abuse_report(
+        &self,
+        job_id: &str,
+        category: AbuseCategory,
+        description: &str,
+        ledger: &TransferLedger,
+    ) -> Result<AbuseReport, String> {
+        let record = self
+            .get_job(job_id)
+            .ok_or_else(|| format!("No job found with id '{}'", job_id))?;
+        let now = chrono::Utc::now();
+
+        let receipt = record.receipt(now);
+        let mut evidence = vec![Evidence {
+            kind: "receipt".to_string(),
+            summary: format!(
+                "Ran {}h {:02}m on {} GPUs, {:.3} kWh",
+                receipt.billable_seconds / 3600,
+                (receipt.billable_seconds % 3600) / 60,
+                record.gpus,
+                receipt.energy_kwh
+            ),
+            data: serde_json::to_value(&receipt).unwrap_or_default(),
+        }];
+
+        let transfers = ledger.for_job(job_id);
+        let sent = |class| transfers.iter().filter(|t| t.class == class).map(|t| t.tx_bytes).sum::<u64>();
+        evidence.push(Evidence {
+            kind: "transfers".to_string(),
+            summary: format!(
+                "Sent {} to the internet and {} over the overlay",
+                format_bytes(sent(DestinationClass::Internet)),
+                format_bytes(sent(DestinationClass::Overlay))
+            ),
+            data: serde_json::to_value(&transfers).unwrap_or_default(),
+        });
+
+        evidence.push(Evidence {
+            kind: "job".to_string(),
+            summary: format!("Image {}, status {:?}", record.image, record.status),
+            data: serde_json::json!({
+                "image": record.image,
+                "created_at": record.created_at,
+                "status": format!("{:?}", record.status),
+                "port_mappings": record.port_mappings,
+            }),
+        });
+
+        Ok(AbuseReport {
+            report_id: uuid::Uuid::new_v4().to_string(),
+            reporter_node_id: eryzaa_discovery::abuse::reporter_id(),
+            client_id: record.client_id,
+            job_id: job_id.to_string(),
+            category,
+            description: description.to_string(),
+            reported_at: now.timestamp() as u64,
+            evidence,
+        })
+    }
+}
+
+/// The renter's subscription to the coordinator's blocklist, with the last copy fetched
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct BlocklistFeed {
+    pub subscribed: bool,
+    #[serde(default)]
+    pub blocklist: Blocklist,
+}
+
+impl BlocklistFeed {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize blocklist feed: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write blocklist feed {}: {}", path.display(), e))
+    }
+
+    /// Replace the cached blocklist with the coordinator's current one
+    pub fn refresh(&mut self, base_url: &str) -> Result<(), String> {
+        self.blocklist = eryzaa_discovery::abuse::fetch_blocklist(base_url)?;
+        Ok(())
+    }
+
+    /// Why `client_id` is turned away, when subscribed and listed
+    pub fn blocked(&self, client_id: &str) -> Option<&BlocklistEntry> {
+        self.blocklist.entry(client_id).filter(|_| self.subscribed)
+    }
+}
+
+/// Blocklist subscription of the node, set from the rental GUI
+pub fn default_feed_path() -> PathBuf {
+    crate::default_registry_path().with_file_name("blocklist.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_abuse_report_evidence() {
+        let manager = JobManager::new();
+        manager.register_job(crate::JobRecord::new("job-1", "client-7", "xmrig/xmrig:latest"));
+        let mut ledger = TransferLedger::default();
+        let today = chrono::Utc::now().date_naive();
+        ledger.record_totals("job-1", DestinationClass::Internet, 0, 3 * 1024 * 1024, today);
+
+        let report = manager.abuse_report("job-1", AbuseCategory::Cryptojacking, "Mining on a free tier", &ledger).unwrap();
+        assert_eq!(report.client_id, "client-7");
+        let kinds: Vec<&str> = report.evidence.iter().map(|e| e.kind.as_str()).collect();
+        assert_eq!(kinds, vec!["receipt", "transfers", "job"]);
+        assert_eq!(report.evidence[1].summary, "Sent 3.0 MB to the internet and 0 B over the overlay");
+        assert!(manager.abuse_report("job-2", AbuseCategory::Other, "", &ledger).is_err());
+
+        let mut feed = BlocklistFeed::default();
+        feed.blocklist.entries.push(BlocklistEntry {
+            client_id: "client-7".to_string(),
+            categories: vec![AbuseCategory::Cryptojacking],
+            reporters: 2,
+            flagged_at: 0,
+            reason: "Reported by 2 nodes".to_string(),
+        });
+        assert!(feed.blocked("client-7").is_none());
+        feed.subscribed = true;
+        assert!(feed.blocked("client-7").is_some());
+    }
+}
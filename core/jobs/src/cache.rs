@@ -0,0 +1,266 @@
+//! Content-addressed result cache for deterministic jobs
+//! A job's reproducibility manifest (image digest, command, environment and input hashes)
+//! keys its artifacts; an identical job later gets those artifacts back without running.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use log::info;
+
+use crate::spec::JobSpec;
+
+/// Everything that determines a deterministic job's output
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ReproManifest {
+    pub image_digest: String,
+    pub command: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    pub inputs: BTreeMap<String, String>, // Input name → SHA-256 of its contents
+}
+
+impl ReproManifest {
+    /// Manifest for a spec, resolving its image to a digest and hashing each input path
+    pub fn for_spec(spec: &JobSpec, inputs: &BTreeMap<String, PathBuf>) -> Result<Self, String> {
+        let mut hashed = BTreeMap::new();
+        for (name, path) in inputs {
+            hashed.insert(name.clone(), hash_path(path)?);
+        }
+        Ok(Self {
+            image_digest: image_digest(&spec.image_tag())?,
+            command: spec.command.clone(),
+            env: spec.env.clone(),
+            inputs: hashed,
+        })
+    }
+
+    /// Cache key: SHA-256 of the manifest's canonical JSON
+    pub fn hash(&self) -> String {
+        // Maps are ordered, so the JSON is the same for equal manifests
+        let canonical = serde_json::to_vec(self).unwrap_or_default();
+        format!("{:x}", Sha256::digest(canonical))
+    }
+}
+
+/// Per-node cache settings
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CachePolicy {
+    pub enabled: bool,
+    pub cache_fee: f32, // Flat charge for a job answered from the cache
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cache_fee: 0.05,
+        }
+    }
+}
+
+impl CachePolicy {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize cache policy: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write cache policy {}: {}", path.display(), e))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CacheEntry {
+    pub hash: String,
+    pub manifest: ReproManifest,
+    pub job_id: String, // Job that produced the artifacts
+    pub size_bytes: u64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Cache of job artifacts under `<root>/<hash>/`
+pub struct ResultCache {
+    pub root: PathBuf,
+    pub policy: CachePolicy,
+}
+
+impl ResultCache {
+    pub fn new(root: PathBuf, policy: CachePolicy) -> Self {
+        Self { root, policy }
+    }
+
+    /// The node's cache with its saved policy
+    pub fn open_default() -> Self {
+        Self::new(default_cache_root(), CachePolicy::load_from(&default_policy_path()))
+    }
+
+    pub fn lookup(&self, hash: &str) -> Option<CacheEntry> {
+        let content = std::fs::read_to_string(self.root.join(hash).join("entry.json")).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Keep a copy of a finished job's artifacts under its manifest hash
+    pub fn store(&self, manifest: &ReproManifest, job_id: &str, artifacts: &Path) -> Result<CacheEntry, String> {
+        let hash = manifest.hash();
+        let dir = self.root.join(&hash);
+        let staging = self.root.join(format!("{}.partial", hash));
+        let _ = std::fs::remove_dir_all(&staging);
+
+        let size_bytes = copy_dir(artifacts, &staging.join("artifacts"))?;
+        let entry = CacheEntry {
+            hash: hash.clone(),
+            manifest: manifest.clone(),
+            job_id: job_id.to_string(),
+            size_bytes,
+            created_at: chrono::Utc::now(),
+        };
+        let content = serde_json::to_string_pretty(&entry)
+            .map_err(|e| format!("Failed to serialize cache entry: {}", e))?;
+        std::fs::write(staging.join("entry.json"), content)
+            .map_err(|e| format!("Failed to write cache entry: {}", e))?;
+
+        // Readers only ever see complete entries
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::rename(&staging, &dir).map_err(|e| format!("Failed to publish cache entry {}: {}", hash, e))?;
+        info!("Cached artifacts of job '{}' as {}", job_id, &hash[..12]);
+        Ok(entry)
+    }
+
+    /// Copy a cached entry's artifacts into `dest`
+    pub fn restore(&self, entry: &CacheEntry, dest: &Path) -> Result<(), String> {
+        copy_dir(&self.root.join(&entry.hash).join("artifacts"), dest).map(|_| ())
+    }
+
+    pub fn entries(&self) -> Vec<CacheEntry> {
+        let mut entries: Vec<CacheEntry> = std::fs::read_dir(&self.root)
+            .map(|dir| {
+                dir.flatten()
+                    .filter_map(|e| self.lookup(&e.file_name().to_string_lossy()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        entries.sort_by_key(|entry| entry.created_at);
+        entries
+    }
+
+    pub fn clear(&self) -> Result<(), String> {
+        if self.root.exists() {
+            std::fs::remove_dir_all(&self.root)
+                .map_err(|e| format!("Failed to clear {}: {}", self.root.display(), e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Content digest of a local image, falling back to its image id
+fn image_digest(image: &str) -> Result<String, String> {
+    let output = Command::new("docker")
+        .args(["image", "inspect", "-f", "{{if .RepoDigests}}{{index .RepoDigests 0}}{{else}}{{.Id}}{{end}}", image])
+        .output()
+        .map_err(|e| format!("Failed to execute docker: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("Image '{}' is not available locally", image));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// SHA-256 over a file, or over every file of a directory with its relative path
+pub fn hash_path(path: &Path) -> Result<String, String> {
+    let mut hasher = Sha256::new();
+    let mut files = Vec::new();
+    collect_files(path, path, &mut files)?;
+    files.sort();
+
+    for relative in files {
+        let content = std::fs::read(path.join(&relative))
+            .map_err(|e| format!("Failed to read {}: {}", path.join(&relative).display(), e))?;
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update((content.len() as u64).to_le_bytes());
+        hasher.update(&content);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn collect_files(root: &Path, path: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    if path.is_file() {
+        files.push(path.strip_prefix(root).unwrap_or(path).to_path_buf());
+        return Ok(());
+    }
+    let dir = std::fs::read_dir(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    for entry in dir.flatten() {
+        collect_files(root, &entry.path(), files)?;
+    }
+    Ok(())
+}
+
+/// Copy a directory tree, returning the bytes copied
+fn copy_dir(from: &Path, to: &Path) -> Result<u64, String> {
+    std::fs::create_dir_all(to).map_err(|e| format!("Failed to create {}: {}", to.display(), e))?;
+    let mut copied = 0;
+    let dir = std::fs::read_dir(from).map_err(|e| format!("Failed to read {}: {}", from.display(), e))?;
+    for entry in dir.flatten() {
+        let target = to.join(entry.file_name());
+        if entry.path().is_dir() {
+            copied += copy_dir(&entry.path(), &target)?;
+        } else {
+            copied += std::fs::copy(entry.path(), &target)
+                .map_err(|e| format!("Failed to copy {}: {}", entry.path().display(), e))?;
+        }
+    }
+    Ok(copied)
+}
+
+/// Cache policy of the active profile, next to the job registry
+pub fn default_policy_path() -> PathBuf {
+    crate::default_registry_path().with_file_name("cache.json")
+}
+
+pub fn default_cache_root() -> PathBuf {
+    crate::default_registry_path().with_file_name("result-cache")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_restore() {
+        let base = std::env::temp_dir().join(format!("eryzaa-cache-{}", std::process::id()));
+        let artifacts = base.join("out");
+        std::fs::create_dir_all(artifacts.join("metrics")).unwrap();
+        std::fs::write(artifacts.join("model.bin"), b"weights").unwrap();
+        std::fs::write(artifacts.join("metrics/acc.txt"), b"0.93").unwrap();
+
+        let manifest = ReproManifest {
+            image_digest: "python@sha256:abc".to_string(),
+            command: vec!["python".to_string(), "train.py".to_string()],
+            env: BTreeMap::from([("SEED".to_string(), "1".to_string())]),
+            inputs: BTreeMap::from([("data".to_string(), hash_path(&artifacts).unwrap())]),
+        };
+        let mut other = manifest.clone();
+        other.env.insert("SEED".to_string(), "2".to_string());
+        assert_ne!(manifest.hash(), other.hash());
+
+        let cache = ResultCache::new(base.join("cache"), CachePolicy::default());
+        assert!(cache.lookup(&manifest.hash()).is_none());
+        let entry = cache.store(&manifest, "job-1", &artifacts).unwrap();
+        assert_eq!(entry.size_bytes, 11);
+        assert_eq!(cache.lookup(&manifest.hash()), Some(entry.clone()));
+
+        cache.restore(&entry, &base.join("restored")).unwrap();
+        assert_eq!(hash_path(&base.join("restored")).unwrap(), hash_path(&artifacts).unwrap());
+        let _ = std::fs::remove_dir_all(base);
+    }
+}
@@ -0,0 +1,21 @@
+//! Failure injection on jobs, built with the `chaos` feature
+//! Containers can be killed behind the registry's back, as a crash would, on top of the
+//! shared faults (dropped discovery packets, slow control commands, GPU ECC errors).
+
+use log::warn;
+
+pub use eryzaa_discovery::chaos::{active_faults, clear, inject, Fault};
+
+use crate::JobManager;
+
+impl JobManager {
+    /// Kill a job's container without touching its record
+    pub fn chaos_kill(&self, job_id: &str) -> Result<(), String> {
+        let record = self
+            .get_job(job_id)
+            .ok_or_else(|| format!("No job found with id '{}'", job_id))?;
+
+        warn!("Chaos: killing container of job '{}'", job_id);
+        crate::pause::run_docker(&record, &["kill", &record.container_name])
+    }
+}
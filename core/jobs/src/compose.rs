@@ -0,0 +1,424 @@
+//! Migration from the docker-compose setups to the native executor
+//! Compose files (and the manage.sh modes that pick them) are converted into an
+//! `ExecutorConfig`; anything the executor cannot express is reported instead of dropped silently.
+
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::spec::{BuildSpec, ExecutorConfig, JobSpec, NetworkSpec};
+
+/// manage.sh modes and the compose file each one deploys
+pub const MANAGE_MODES: [(&str, &str); 3] = [
+    ("deploy", "docker-compose.yml"),
+    ("dev", "docker-compose.dev.yml"),
+    ("fast", "docker-compose.fast.yml"),
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct Conversion {
+    pub config: ExecutorConfig,
+    pub warnings: Vec<String>, // Compose settings left out of the config
+}
+
+/// Compose file a manage.sh mode uses, looked up in `dir`
+pub fn compose_file_for_mode(dir: &Path, mode: &str) -> Option<PathBuf> {
+    MANAGE_MODES
+        .iter()
+        .find(|(m, _)| *m == mode)
+        .map(|(_, file)| dir.join(file))
+}
+
+/// Convert the compose file behind a manage.sh mode
+pub fn convert_manage_mode(dir: &Path, mode: &str) -> Result<Conversion, String> {
+    let path = compose_file_for_mode(dir, mode)
+        .ok_or_else(|| format!("Unknown manage.sh mode '{}'", mode))?;
+    let mut conversion = convert_compose_file(&path)?;
+    conversion.config.mode = mode.to_string();
+    Ok(conversion)
+}
+
+pub fn convert_compose_file(path: &Path) -> Result<Conversion, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let base = path.parent().unwrap_or(Path::new("."));
+    let base = base.canonicalize().unwrap_or_else(|_| base.to_path_buf());
+    convert_compose(&content, &base, &|name| std::env::var(name).ok())
+}
+
+/// Convert compose YAML; relative paths resolve against `base`, `${VAR}` through `env`
+pub fn convert_compose(content: &str, base: &Path, env: &dyn Fn(&str) -> Option<String>) -> Result<Conversion, String> {
+    let doc: Value = serde_yaml::from_str(content).map_err(|e| format!("Invalid compose file: {}", e))?;
+    let mut conversion = Conversion::default();
+    let warnings = &mut conversion.warnings;
+
+    let services = doc
+        .get("services")
+        .and_then(Value::as_mapping)
+        .ok_or("Compose file has no services")?;
+
+    let mut specs = Vec::new();
+    let mut depends: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (key, service) in services {
+        let key = key.as_str().unwrap_or_default().to_string();
+        let text = |field: &str| service.get(field).and_then(Value::as_str).map(|s| interpolate(s, env));
+
+        let mut spec = JobSpec {
+            name: text("container_name").unwrap_or_else(|| key.clone()),
+            image: text("image"),
+            hostname: text("hostname"),
+            restart: text("restart"),
+            privileged: service.get("privileged").and_then(Value::as_bool).unwrap_or(false),
+            tty: service.get("tty").and_then(Value::as_bool).unwrap_or(false)
+                || service.get("stdin_open").and_then(Value::as_bool).unwrap_or(false),
+            ports: strings(service.get("ports"), env),
+            devices: strings(service.get("devices"), env),
+            cap_add: strings(service.get("cap_add"), env),
+            security_opt: strings(service.get("security_opt"), env),
+            ..Default::default()
+        };
+
+        spec.build = match service.get("build") {
+            Some(Value::String(context)) => Some(BuildSpec { context: resolve(&interpolate(context, env), base), dockerfile: None }),
+            Some(build) => Some(BuildSpec {
+                context: resolve(&build.get("context").and_then(Value::as_str).map(|c| interpolate(c, env)).unwrap_or_else(|| ".".to_string()), base),
+                dockerfile: build.get("dockerfile").and_then(Value::as_str).map(|d| interpolate(d, env)),
+            }),
+            None => None,
+        };
+
+        // Like compose, a string command is split into words rather than handed to a shell
+        spec.command = match service.get("command") {
+            Some(Value::String(command)) => split_words(&interpolate(command, env)).map_err(|e| format!("{}: command: {}", key, e))?,
+            command => strings(command, env),
+        };
+
+        spec.env = match service.get("environment") {
+            Some(Value::Mapping(map)) => map
+                .iter()
+                .map(|(k, v)| (scalar(k), interpolate(&scalar(v), env)))
+                .collect(),
+            list => strings(list, env)
+                .into_iter()
+                .map(|entry| match entry.split_once('=') {
+                    Some((k, v)) => (k.to_string(), v.to_string()),
+                    None => (entry.clone(), env(&entry).unwrap_or_default()),
+                })
+                .collect(),
+        };
+
+        spec.volumes = strings(service.get("volumes"), env)
+            .into_iter()
+            .map(|volume| match volume.split_once(':') {
+                Some((source, rest)) if source.starts_with('.') || source.starts_with('~') => {
+                    format!("{}:{}", resolve(source, base), rest)
+                }
+                _ => volume,
+            })
+            .collect();
+
+        let networks = match service.get("networks") {
+            Some(Value::Mapping(map)) => map.keys().map(scalar).collect(),
+            list => strings(list, env),
+        };
+        spec.network = networks.first().cloned();
+        if networks.len() > 1 {
+            warnings.push(format!("{}: only the first network ({}) is joined", key, networks[0]));
+        }
+
+        if let Some(devices) = service
+            .get("deploy")
+            .and_then(|d| d.get("resources"))
+            .and_then(|r| r.get("reservations"))
+            .and_then(|r| r.get("devices"))
+            .and_then(Value::as_sequence)
+        {
+            for device in devices {
+                if device.get("driver").and_then(Value::as_str) == Some("nvidia") {
+                    spec.gpus = Some(match device.get("count") {
+                        Some(Value::Number(count)) => count.to_string(),
+                        _ => "all".to_string(),
+                    });
+                }
+            }
+        }
+
+        let deps: Vec<String> = match service.get("depends_on") {
+            Some(Value::Mapping(map)) => map.keys().map(scalar).collect(),
+            list => strings(list, env),
+        };
+        depends.insert(spec.name.clone(), deps.iter().map(|dep| container_name(services, dep)).collect());
+
+        for field in ["healthcheck", "extra_hosts", "labels", "logging", "secrets", "configs", "profiles"] {
+            if service.get(field).is_some() {
+                warnings.push(format!("{}: '{}' is not supported by the native executor", key, field));
+            }
+        }
+        if spec.image.is_none() && spec.build.is_none() {
+            warnings.push(format!("{}: no image or build context", key));
+        }
+        specs.push(spec);
+    }
+
+    conversion.config.specs = start_order(specs, &depends);
+    conversion.config.volumes = doc
+        .get("volumes")
+        .and_then(Value::as_mapping)
+        .map(|m| m.keys().map(scalar).collect())
+        .unwrap_or_default();
+    conversion.config.networks = doc
+        .get("networks")
+        .and_then(Value::as_mapping)
+        .map(|networks| {
+            networks
+                .iter()
+                .map(|(name, network)| NetworkSpec {
+                    name: scalar(name),
+                    subnet: network
+                        .get("ipam")
+                        .and_then(|i| i.get("config"))
+                        .and_then(Value::as_sequence)
+                        .and_then(|c| c.first())
+                        .and_then(|c| c.get("subnet"))
+                        .and_then(Value::as_str)
+                        .map(|s| s.to_string()),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(conversion)
+}
+
+/// Order specs so that every spec starts after the ones it depends on
+fn start_order(specs: Vec<JobSpec>, depends: &BTreeMap<String, Vec<String>>) -> Vec<JobSpec> {
+    let mut ordered: Vec<JobSpec> = Vec::new();
+    let mut remaining = specs;
+    while !remaining.is_empty() {
+        let ready = remaining
+            .iter()
+            .position(|spec| {
+                depends
+                    .get(&spec.name)
+                    .map(|deps| deps.iter().all(|d| ordered.iter().any(|o| &o.name == d) || !remaining.iter().any(|r| &r.name == d)))
+                    .unwrap_or(true)
+            })
+            .unwrap_or(0); // A dependency cycle; keep file order
+        ordered.push(remaining.remove(ready));
+    }
+    ordered
+}
+
+fn container_name(services: &serde_yaml::Mapping, service: &str) -> String {
+    services
+        .get(service)
+        .and_then(|s| s.get("container_name"))
+        .and_then(Value::as_str)
+        .unwrap_or(service)
+        .to_string()
+}
+
+fn strings(value: Option<&Value>, env: &dyn Fn(&str) -> Option<String>) -> Vec<String> {
+    value
+        .and_then(Value::as_sequence)
+        .map(|items| items.iter().map(|item| interpolate(&scalar(item), env)).collect())
+        .unwrap_or_default()
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Expand `${VAR}`, `${VAR:-default}` and `$VAR`
+fn interpolate(text: &str, env: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(index) = rest.find('$') {
+        out.push_str(&rest[..index]);
+        rest = &rest[index + 1..];
+
+        if let Some(braced) = rest.strip_prefix('{') {
+            let Some(end) = braced.find('}') else {
+                out.push_str("${");
+                rest = braced;
+                continue;
+            };
+            let expr = &braced[..end];
+            let (name, default) = match expr.split_once(":-") {
+                Some((name, default)) => (name, default),
+                None => (expr, ""),
+            };
+            out.push_str(&env(name).filter(|v| !v.is_empty()).unwrap_or_else(|| default.to_string()));
+            rest = &braced[end + 1..];
+        } else if let Some(escaped) = rest.strip_prefix('$') {
+            out.push('$');
+            rest = escaped;
+        } else {
+            let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            if end == 0 {
+                out.push('$');
+            } else {
+                out.push_str(&env(&rest[..end]).unwrap_or_default());
+            }
+            rest = &rest[end..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Split a command line into words the way a POSIX shell would, without expanding anything
+fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        // Inside double quotes a backslash only escapes these
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some('\n') => {}
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("unterminated double quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err("trailing backslash".to_string()),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+fn resolve(path: &str, base: &Path) -> String {
+    let path = match path.strip_prefix("~/") {
+        Some(rest) => std::env::var("HOME").map(|home| Path::new(&home).join(rest)).unwrap_or_else(|_| PathBuf::from(path)),
+        None => base.join(path),
+    };
+    // Drop "./" components so the paths read like the ones users wrote
+    path.components()
+        .filter(|c| !matches!(c, std::path::Component::CurDir))
+        .collect::<PathBuf>()
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPOSE: &str = r#"
+services:
+  rental-server:
+    build: .
+    container_name: rental-server
+    environment:
+      - ZEROTIER_NETWORK_ID=363c67c55ad2489d
+    volumes:
+      - ./workspace:/workspace/data
+      - ${HOME}/shared:/workspace/shared
+      - zerotier-data:/var/lib/zerotier-one
+    ports:
+      - "127.0.0.1:2222:22"
+    privileged: true
+    deploy:
+      resources:
+        reservations:
+          devices:
+            - driver: nvidia
+              count: all
+              capabilities: [gpu]
+    networks:
+      - rental-network
+    depends_on:
+      - portainer
+  portainer:
+    image: portainer/portainer-ce:latest
+    container_name: rental-portainer
+    healthcheck:
+      test: ["CMD", "true"]
+volumes:
+  zerotier-data:
+    driver: local
+networks:
+  rental-network:
+    ipam:
+      config:
+        - subnet: 172.20.0.0/16
+"#;
+
+    #[test]
+    fn test_convert_compose() {
+        let env = |name: &str| (name == "HOME").then(|| "/home/alice".to_string());
+        let conversion = convert_compose(COMPOSE, Path::new("/srv/eryzaa"), &env).unwrap();
+        let config = conversion.config;
+
+        assert_eq!(config.specs.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), vec!["rental-portainer", "rental-server"]);
+        let server = &config.specs[1];
+        assert_eq!(server.build, Some(BuildSpec { context: "/srv/eryzaa".to_string(), dockerfile: None }));
+        assert_eq!(server.volumes, vec![
+            "/srv/eryzaa/workspace:/workspace/data",
+            "/home/alice/shared:/workspace/shared",
+            "zerotier-data:/var/lib/zerotier-one",
+        ]);
+        assert_eq!(server.gpus.as_deref(), Some("all"));
+        assert_eq!(server.network.as_deref(), Some("rental-network"));
+        assert_eq!(server.env["ZEROTIER_NETWORK_ID"], "363c67c55ad2489d");
+
+        assert_eq!(config.volumes, vec!["zerotier-data"]);
+        assert_eq!(config.networks[0].subnet.as_deref(), Some("172.20.0.0/16"));
+        assert_eq!(conversion.warnings, vec!["portainer: 'healthcheck' is not supported by the native executor"]);
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let env = |name: &str| (name == "USER").then(|| "bob".to_string());
+        assert_eq!(interpolate("${USER}-$USER-${MISSING:-x}-$$", &env), "bob-bob-x-$");
+        assert_eq!(compose_file_for_mode(Path::new("/srv"), "dev"), Some(PathBuf::from("/srv/docker-compose.dev.yml")));
+    }
+
+    #[test]
+    fn test_string_command_is_split_into_words() {
+        assert_eq!(split_words(r#"python -c 'print("hi")' --name "a b" c\ d ''"#).unwrap(), vec![
+            "python", "-c", "print(\"hi\")", "--name", "a b", "c d", "",
+        ]);
+        assert!(split_words("echo 'open").is_err());
+
+        let compose = "services:\n  app:\n    image: alpine\n    command: sleep \"${DELAY}\"\n";
+        let env = |name: &str| (name == "DELAY").then(|| "10 s".to_string());
+        let conversion = convert_compose(compose, Path::new("/srv"), &env).unwrap();
+        assert_eq!(conversion.config.specs[0].command, vec!["sleep", "10 s"]);
+    }
+}
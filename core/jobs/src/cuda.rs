@@ -0,0 +1,141 @@
+//! CUDA compatibility of images
+//! An image built for a newer CUDA than the node's driver supports crashes at its first CUDA
+//! call. The CUDA version an image needs is read from its config before the job is scheduled,
+//! so the mismatch is reported up front instead.
+
+use serde_json::Value;
+use std::process::Command;
+
+pub use eryzaa_protocol::GpuStack;
+
+/// Label NVIDIA's base images carry, e.g. "12.2.0"
+const CUDA_LABEL: &str = "com.nvidia.cuda.version";
+
+/// CUDA version an image needs, from the local image or, failing that, its tag
+pub fn local_requirement(image: &str) -> Option<String> {
+    inspect(&["image", "inspect", "--format", "{{json .Config}}", image])
+        .and_then(|config| config_requirement(&config))
+        .or_else(|| tag_requirement(image))
+}
+
+/// Like `local_requirement`, but reads the registry manifest of images that are not pulled yet
+pub fn image_requirement(image: &str) -> Option<String> {
+    inspect(&["image", "inspect", "--format", "{{json .Config}}", image])
+        .or_else(|| inspect(&["buildx", "imagetools", "inspect", "--format", "{{json .Image}}", image]))
+        .and_then(|config| config_requirement(&config))
+        .or_else(|| tag_requirement(image))
+}
+
+/// Fail with a clear reason when `stack` cannot run CUDA `required`; unknown versions pass
+pub fn check(image: &str, required: Option<&str>, stack: Option<&GpuStack>) -> Result<(), String> {
+    let (Some(required), Some(stack)) = (required, stack) else {
+        return Ok(());
+    };
+    match stack.cuda_incompatibility(required) {
+        Some(reason) => Err(format!(
+            "Image {} {}; pick a node with a newer driver or an image built for CUDA {} or older",
+            image,
+            reason,
+            stack.cuda_version.as_deref().unwrap_or("?")
+        )),
+        None => Ok(()),
+    }
+}
+
+fn inspect(args: &[&str]) -> Option<Value> {
+    let output = Command::new("docker").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    serde_json::from_slice(&output.stdout).ok()
+}
+
+/// From NVIDIA_REQUIRE_CUDA, CUDA_VERSION or the CUDA label, wherever they appear in the config
+///
+/// Manifests of multi-platform images nest one config per platform, so the whole document is
+/// searched rather than a fixed path.
+pub fn config_requirement(config: &Value) -> Option<String> {
+    let mut env = vec![];
+    let mut labels = vec![];
+    collect(config, &mut env, &mut labels);
+
+    let from_env = |name: &str| {
+        env.iter().find_map(|entry| entry.strip_prefix(name)?.strip_prefix('=').map(|v| v.to_string()))
+    };
+    // "cuda>=12.2 brand=tesla,driver>=470,driver<471"
+    let required = from_env("NVIDIA_REQUIRE_CUDA").and_then(|constraints| {
+        constraints
+            .split_whitespace()
+            .find_map(|c| c.strip_prefix("cuda>=").map(|v| v.to_string()))
+    });
+    required
+        .or_else(|| from_env("CUDA_VERSION"))
+        .or_else(|| labels.iter().find_map(|(key, value)| (key == CUDA_LABEL).then(|| value.clone())))
+}
+
+fn collect(value: &Value, env: &mut Vec<String>, labels: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                match (key.as_str(), value) {
+                    ("Env", Value::Array(entries)) => {
+                        env.extend(entries.iter().filter_map(|e| e.as_str().map(|s| s.to_string())))
+                    }
+                    ("Labels", Value::Object(entries)) => labels.extend(
+                        entries.iter().filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string()))),
+                    ),
+                    _ => collect(value, env, labels),
+                }
+            }
+        }
+        Value::Array(values) => values.iter().for_each(|value| collect(value, env, labels)),
+        _ => {}
+    }
+}
+
+/// From tags such as `nvidia/cuda:12.4.1-runtime-ubuntu22.04` or `pytorch/pytorch:2.1.0-cuda12.1-cudnn8-runtime`
+pub fn tag_requirement(image: &str) -> Option<String> {
+    let (repository, tag) = image.rsplit_once(':')?;
+    let version = if repository.ends_with("nvidia/cuda") {
+        tag.split('-').next()?
+    } else {
+        tag.split('-').find_map(|part| part.strip_prefix("cuda"))?
+    };
+    eryzaa_protocol::node::cuda_major_minor(version).map(|_| version.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cuda_requirement() {
+        let config = serde_json::json!({
+            "linux/amd64": {
+                "config": {
+                    "Env": ["PATH=/usr/bin", "CUDA_VERSION=12.4.1", "NVIDIA_REQUIRE_CUDA=cuda>=12.4 brand=tesla,driver>=470,driver<471"],
+                    "Labels": { "com.nvidia.cuda.version": "12.4.1" }
+                }
+            }
+        });
+        assert_eq!(config_requirement(&config).as_deref(), Some("12.4"));
+        assert_eq!(config_requirement(&serde_json::json!({ "Labels": { CUDA_LABEL: "11.8.0" } })).as_deref(), Some("11.8.0"));
+        assert_eq!(config_requirement(&serde_json::json!({ "Env": ["PATH=/usr/bin"] })), None);
+
+        assert_eq!(tag_requirement("nvidia/cuda:12.4.1-runtime-ubuntu22.04").as_deref(), Some("12.4.1"));
+        assert_eq!(tag_requirement("pytorch/pytorch:2.1.0-cuda12.1-cudnn8-runtime").as_deref(), Some("12.1"));
+        assert_eq!(tag_requirement("ubuntu:22.04"), None);
+
+        let stack = GpuStack {
+            driver_version: "535.104.05".to_string(),
+            cuda_version: Some("12.2".to_string()),
+            cudnn_version: None,
+        };
+        assert!(check("pytorch/pytorch:2.1.0-cuda12.1-cudnn8-runtime", Some("12.1"), Some(&stack)).is_ok());
+        assert_eq!(
+            check("nvidia/cuda:12.4.1-base", Some("12.4.1"), Some(&stack)).unwrap_err(),
+            "Image nvidia/cuda:12.4.1-base needs CUDA 12.4.1, but NVIDIA driver 535.104.05 only supports up to CUDA 12.2; pick a node with a newer driver or an image built for CUDA 12.2 or older"
+        );
+        assert!(check("ubuntu:22.04", None, Some(&stack)).is_ok());
+    }
+}
@@ -0,0 +1,196 @@
+//! All-in-one demo
+//! `eryzaa demo up` runs a coordinator and two rental nodes as local processes that need no
+//! Docker, overlay, root or wallet: the nodes only advertise made-up hardware and prices to the
+//! discovery port on this machine. Jobs and a funded profile are seeded into the "demo" profile,
+//! so the GUIs started with ERYZAA_PROFILE=demo have something to show without touching real data.
+
+use std::path::Path;
+
+use eryzaa_discovery::hardware::{AvxLevel, GpuDevice, GpuLink};
+use eryzaa_discovery::registration::NodeRegistration;
+use eryzaa_discovery::{GpuStack, HardwareProfile, NodeAdvertisement, NodeCapabilities, NodeDetails, NodeStatus};
+
+use crate::profiles::{Profile, ProfileStore};
+use crate::{JobManager, JobRecord, JobStatus};
+
+pub const PROFILE: &str = "demo";
+pub const NETWORK_ID: &str = "demo";
+
+/// Balance the demo profile starts with, in AVAX
+const BALANCE: f64 = 100.0;
+
+/// A made-up rental node and what it charges
+struct MockNode {
+    node_id: &'static str,
+    gpu: &'static str,
+    gpu_count: u32,
+    gpu_memory_gb: u32,
+    compute_capability: &'static str,
+    cpu_cores: u32,
+    memory_gb: u32,
+    price_per_hour: f32,
+    description: &'static str,
+}
+
+const NODES: [MockNode; 2] = [
+    MockNode {
+        node_id: "demo-node-1",
+        gpu: "NVIDIA GeForce RTX 4090",
+        gpu_count: 1,
+        gpu_memory_gb: 24,
+        compute_capability: "8.9",
+        cpu_cores: 16,
+        memory_gb: 64,
+        price_per_hour: 0.8,
+        description: "Demo gaming rig, idle at night",
+    },
+    MockNode {
+        node_id: "demo-node-2",
+        gpu: "NVIDIA A100-SXM4-80GB",
+        gpu_count: 2,
+        gpu_memory_gb: 160,
+        compute_capability: "8.0",
+        cpu_cores: 64,
+        memory_gb: 512,
+        price_per_hour: 2.4,
+        description: "Demo training server",
+    },
+];
+
+/// How many mock nodes the demo runs
+pub fn node_count() -> usize {
+    NODES.len()
+}
+
+/// The advertisement of mock node `index`, stamped `now`
+pub fn mock_node(index: usize, now: u64) -> Option<NodeAdvertisement> {
+    let node = NODES.get(index)?;
+    let gpu = GpuDevice {
+        model: node.gpu.to_string(),
+        memory_mb: node.gpu_memory_gb as u64 * 1024 / node.gpu_count as u64,
+        compute_capability: Some(node.compute_capability.to_string()),
+    };
+    let capabilities = NodeCapabilities {
+        cpu_cores: node.cpu_cores,
+        memory_gb: node.memory_gb,
+        gpu_count: node.gpu_count,
+        gpu_memory_gb: node.gpu_memory_gb,
+        disk_space_gb: 1000,
+        network_speed_mbps: 1000,
+        supports_docker: true,
+        supports_gpu: true,
+        max_concurrent_jobs: 2,
+        gpu_stack: Some(GpuStack { driver_version: "550.54.14".to_string(), cuda_version: Some("12.4".to_string()), cudnn_version: None }),
+        hardware: HardwareProfile {
+            gpus: vec![gpu; node.gpu_count as usize],
+            gpu_link: if node.gpu_count > 1 { GpuLink::NvLink } else { GpuLink::Unknown },
+            avx: Some(AvxLevel::Avx2),
+            nvme: true,
+            ..Default::default()
+        },
+    };
+    let mut advertisement = eryzaa_discovery::create_rental_advertisement(
+        node.node_id.to_string(),
+        "127.0.0.1".to_string(),
+        None,
+        capabilities,
+        NETWORK_ID.to_string(),
+    );
+    advertisement.timestamp = now;
+    advertisement.gpu_model = Some(node.gpu.to_string());
+    advertisement.price_per_hour = Some(node.price_per_hour);
+    advertisement.free_slots = Some(1);
+    advertisement.details = NodeDetails { description: node.description.to_string(), ..Default::default() };
+    // The first node takes a break now and then, so status changes show up in the GUIs
+    if index == 0 && (now / 120).is_multiple_of(3) {
+        advertisement.status = NodeStatus::Busy;
+        advertisement.free_slots = Some(0);
+    }
+    Some(advertisement)
+}
+
+/// What mock node `index` tells the coordinator when it registers
+pub fn mock_registration(index: usize) -> Option<NodeRegistration> {
+    let node = NODES.get(index)?;
+    Some(NodeRegistration {
+        node_id: node.node_id.to_string(),
+        hostname: node.node_id.to_string(),
+        overlay_ip: None,
+        price_per_hour: Some(node.price_per_hour),
+        cpu_score: Some(node.cpu_cores as f64 * 900.0),
+        gpus: vec![node.gpu.to_string(); node.gpu_count as usize],
+        // Mock nodes announce on this machine only, never at the rendezvous
+        public_key: None,
+    })
+}
+
+/// Jobs the demo client has run on the mock nodes, as of `now`
+pub fn seed_jobs(now: chrono::DateTime<chrono::Utc>) -> Vec<JobRecord> {
+    let job = |job_id: &str, node: &MockNode, image: &str, status: JobStatus, hours_ago: i64| {
+        let mut record = JobRecord::new(job_id, "demo-client", image);
+        record.gpus = 1;
+        record.node_id = Some(node.node_id.to_string());
+        record.node_address = Some("127.0.0.1".to_string());
+        record.offered_price = Some(node.price_per_hour);
+        record.status = status;
+        record.created_at = now - chrono::Duration::hours(hours_ago);
+        // Monotonic markers from this boot would bill from now instead
+        record.started_marker = None;
+        record
+    };
+    vec![
+        job("demo-train", &NODES[1], "pytorch/pytorch:latest", JobStatus::Running, 2),
+        job("demo-render", &NODES[0], "blender/blender:latest", JobStatus::Completed, 26),
+        job("demo-notebook", &NODES[0], "jupyter/scipy-notebook", JobStatus::Failed("Out of GPU memory".to_string()), 50),
+        job("demo-queued", &NODES[1], "ubuntu:22.04", JobStatus::Pending, 0),
+    ]
+}
+
+/// Create the demo profile in the profile list at `store_path` unless it exists, funded and
+/// unused by any real wallet; the active profile stays as it was
+pub fn ensure_profile(store_path: &Path) -> Result<Profile, String> {
+    let mut store = ProfileStore::load_from(store_path)?;
+    if store.get(PROFILE).is_none() {
+        let mut profile = Profile::new(PROFILE);
+        profile.organization = Some("Eryzaa demo".to_string());
+        profile.balance = BALANCE;
+        store.create(profile)?;
+        store.save_to(store_path)?;
+    }
+    Ok(store.get(PROFILE).cloned().expect("demo profile exists"))
+}
+
+/// Write the seeded jobs to the demo profile's registry, replacing earlier demo jobs
+pub fn seed_registry(profile: &Profile, now: chrono::DateTime<chrono::Utc>) -> Result<(), String> {
+    let path = profile.data_dir().join("jobs.json");
+    let manager = JobManager::load_from(&path)?;
+    for record in seed_jobs(now) {
+        manager.register_job(record);
+    }
+    manager.save_to(&path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demo_seed() {
+        let now = 1_700_000_000 + 120; // Not a break of the first node
+        let nodes: Vec<NodeAdvertisement> = (0..node_count()).filter_map(|index| mock_node(index, now)).collect();
+        assert_eq!(nodes.len(), 2);
+        assert!(nodes.iter().all(|node| node.status == NodeStatus::Available && node.price_per_hour.is_some()));
+        assert_eq!(nodes[1].capabilities.hardware.summary(), "2× NVIDIA A100-SXM4-80GB (sm 8.0), NVLink, AVX2, NVMe");
+        assert_eq!(mock_node(0, 1_700_000_000).unwrap().status, NodeStatus::Busy);
+
+        let store_path = std::env::temp_dir().join(format!("eryzaa-demo-profiles-{}.json", std::process::id()));
+        let profile = ensure_profile(&store_path).unwrap();
+        assert_eq!(profile.balance, BALANCE);
+        assert_eq!(ProfileStore::load_from(&store_path).unwrap().active().name, crate::profiles::DEFAULT_PROFILE);
+        std::fs::remove_file(&store_path).ok();
+
+        let jobs = seed_jobs(chrono::Utc::now());
+        let running = jobs.iter().find(|job| job.status == JobStatus::Running).unwrap();
+        assert!(running.billable_seconds(chrono::Utc::now()) >= 2 * 3600);
+    }
+}
@@ -0,0 +1,177 @@
+//! Interactive exec/attach channel into a running job container
+//! Locally this is plain `docker exec`; from a client it is bridged over the job's SSH session
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use log::info;
+use eryzaa_ssh_manager::grants::{self, GrantQueue, GrantRequest};
+
+use crate::{JobRecord, SshConfigWriter};
+
+#[derive(Debug, Clone)]
+pub struct ExecRequest {
+    pub job_id: String,
+    pub command: Vec<String>,
+    pub interactive: bool, // Allocate a TTY and keep stdin open
+}
+
+impl ExecRequest {
+    pub fn new(job_id: &str, command: Vec<String>) -> Self {
+        Self {
+            job_id: job_id.to_string(),
+            command,
+            interactive: true,
+        }
+    }
+
+    /// Interactive bash shell inside the job container
+    pub fn shell(job_id: &str) -> Self {
+        Self::new(job_id, vec!["bash".to_string()])
+    }
+}
+
+/// Build the `docker exec` argument list for a request
+pub fn docker_exec_args(container_name: &str, request: &ExecRequest) -> Vec<String> {
+    let mut args = vec!["exec".to_string()];
+    if request.interactive {
+        args.push("-it".to_string());
+    }
+    args.push(container_name.to_string());
+
+    if request.command.is_empty() {
+        args.push("bash".to_string());
+    } else {
+        args.extend(request.command.iter().cloned());
+    }
+
+    args
+}
+
+/// Build a docker command targeting the node that runs a job
+///
+/// Jobs without a node address are assumed to run on this machine; otherwise the
+/// command is bridged over SSH as the job user.
+pub fn docker_command(record: &JobRecord, docker_args: &[String], tty: bool) -> Result<Command, String> {
+    node_command(record, "docker", docker_args, tty)
+}
+
+/// File a grant request in the node's queue over the job's own SSH session
+pub fn file_grant_request(record: &JobRecord, request: &GrantRequest) -> Result<(), String> {
+    let path = GrantQueue::path_for(Path::new(grants::GRANT_QUEUE_DIR), request);
+    let body = serde_json::to_string(request).map_err(|e| format!("Failed to serialize grant request: {}", e))?;
+    let mut child = node_command(record, "tee", &[path.display().to_string()], false)?
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to reach the node of job '{}': {}", record.job_id, e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(body.as_bytes());
+    }
+    match child.wait() {
+        Ok(status) if status.success() => Ok(()),
+        _ => Err(format!("Failed to file the request on the node of job '{}'", record.job_id)),
+    }
+}
+
+/// Build a command that runs `program` on the node that runs a job
+///
+/// ssh hands its arguments to the remote shell as one line, so each is quoted for it.
+pub fn node_command(record: &JobRecord, program: &str, args: &[String], tty: bool) -> Result<Command, String> {
+    #[cfg(feature = "chaos")]
+    if let Some(delay) = eryzaa_discovery::chaos::control_delay() {
+        std::thread::sleep(delay);
+    }
+
+    match record.connect_host() {
+        None => {
+            let mut cmd = Command::new(program);
+            cmd.args(args);
+            Ok(cmd)
+        }
+        Some(address) => {
+            let user = record
+                .ssh_user
+                .as_ref()
+                .ok_or_else(|| format!("Job '{}' has no SSH user to reach its node", record.job_id))?;
+
+            let mut cmd = Command::new("ssh");
+            if tty {
+                cmd.arg("-t");
+            }
+            cmd.args(SshConfigWriter::for_current_user().host_key_args(&address));
+            cmd.arg(format!("{}@{}", user, address));
+            cmd.arg(shell_quote(program));
+            cmd.args(args.iter().map(|arg| shell_quote(arg)));
+            Ok(cmd)
+        }
+    }
+}
+
+/// Build a command that runs a shell script on the node that runs a job
+pub fn node_script(record: &JobRecord, script: &str) -> Result<Command, String> {
+    node_command(record, "sh", &["-c".to_string(), script.to_string()], false)
+}
+
+/// Build the command that opens the exec session for a job
+pub fn exec_command(record: &JobRecord, request: &ExecRequest) -> Result<Command, String> {
+    let docker_args = docker_exec_args(&record.container_name, request);
+    docker_command(record, &docker_args, request.interactive)
+}
+
+/// Shell command line equivalent of `exec_command`, for launching in a terminal emulator
+pub fn exec_command_line(record: &JobRecord, request: &ExecRequest) -> Result<String, String> {
+    let cmd = exec_command(record, request)?;
+    let mut parts = vec![cmd.get_program().to_string_lossy().to_string()];
+    parts.extend(cmd.get_args().map(|arg| shell_quote(&arg.to_string_lossy())));
+    Ok(parts.join(" "))
+}
+
+/// Run an exec session with the caller's terminal attached and return its exit code
+pub fn run_exec(record: &JobRecord, request: &ExecRequest) -> Result<i32, String> {
+    let mut cmd = exec_command(record, request)?;
+    info!("Opening exec session in job '{}': {:?}", record.job_id, request.command);
+
+    let status = cmd
+        .status()
+        .map_err(|e| format!("Failed to start exec session: {}", e))?;
+
+    Ok(status.code().unwrap_or(-1))
+}
+
+pub(crate) fn shell_quote(arg: &str) -> String {
+    if !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./@:=".contains(c)) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_docker_exec_args() {
+        let request = ExecRequest::new("abc", vec!["python3".to_string(), "train.py".to_string()]);
+        let args = docker_exec_args("eryzaa-job-abc", &request);
+        assert_eq!(args, vec!["exec", "-it", "eryzaa-job-abc", "python3", "train.py"]);
+    }
+
+    #[test]
+    fn test_remote_exec_is_bridged_over_ssh() {
+        let mut record = JobRecord::new("abc", "client-1", "ubuntu:22.04");
+        record.node_address = Some("10.242.1.5".to_string());
+        record.ssh_user = Some("job_1234abcd".to_string());
+
+        let line = exec_command_line(&record, &ExecRequest::shell("abc")).unwrap();
+        assert!(line.starts_with("ssh -t -o UserKnownHostsFile="));
+        assert!(line.ends_with(" job_1234abcd@10.242.1.5 docker exec -it eryzaa-job-abc bash"));
+
+        // The remote shell sees each argument as one word, not a command separator
+        let request = ExecRequest::new("abc", vec!["echo".to_string(), "a b; rm -rf ~".to_string()]);
+        let cmd = exec_command(&record, &request).unwrap();
+        let args: Vec<_> = cmd.get_args().map(|arg| arg.to_string_lossy().to_string()).collect();
+        assert_eq!(args.last().unwrap(), "'a b; rm -rf ~'");
+    }
+}
@@ -0,0 +1,302 @@
+//! Failover of jobs off dead nodes
+//! With the client's opt-in, a running job whose node has stopped advertising and stopped
+//! answering is resubmitted to the next best node, within the attempt and spending limits the
+//! client set. Docker checkpoints stay on the dead node, so the job's latest workspace snapshot
+//! is what carries over; each hop is recorded in the job's history entry.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use log::{info, warn};
+use eryzaa_discovery::NodeAdvertisement;
+use eryzaa_protocol::node::NodeStatus;
+
+use crate::history::JobHistory;
+use crate::offline::{is_reachable, NodeCache};
+use crate::requirements::{self, JobNeeds};
+use crate::workspace::{Snapshot, WorkspaceStore};
+use crate::{profiles, HistoryEntry, JobManager, JobRecord, JobStatus, JobSubmission};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct FailoverPolicy {
+    pub enabled: bool,                   // Off unless the client opts in
+    pub max_attempts: u32,               // Hops allowed per job
+    pub max_price_per_hour: Option<f32>, // Replacement nodes asking more are skipped
+    pub max_spend: Option<f32>,          // AVAX a job may cost across all its nodes before it is left failed
+    pub silence_secs: u64,               // How long a node may go unheard before it is probed
+}
+
+impl Default for FailoverPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_attempts: 2,
+            max_price_per_hour: None,
+            max_spend: None,
+            silence_secs: 300,
+        }
+    }
+}
+
+impl FailoverPolicy {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize failover policy: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write failover policy {}: {}", path.display(), e))
+    }
+}
+
+/// One move of a job from a dead node to its replacement
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FailoverHop {
+    pub from_node: String,
+    pub to_node: String,
+    pub at: chrono::DateTime<chrono::Utc>,
+    pub reason: String,
+    pub snapshot: Option<String>, // Workspace snapshot restored on the new node
+    pub spent: f32,               // Estimated AVAX the job cost on `from_node`
+}
+
+/// Where a job goes next and what it takes along
+#[derive(Debug, Clone)]
+pub struct FailoverPlan {
+    pub hop: FailoverHop,
+    pub submission: JobSubmission,
+}
+
+/// Whether the node running `job` is dead: unheard for longer than the policy allows and not
+/// answering on its SSH port. A stale cache means the client itself is offline, so nothing is.
+pub fn node_is_dead(
+    job: &JobRecord,
+    cache: &NodeCache,
+    policy: &FailoverPolicy,
+    now: chrono::DateTime<chrono::Utc>,
+    reachable: &dyn Fn(&str) -> bool,
+) -> bool {
+    let Some(node_id) = &job.node_id else {
+        return false;
+    };
+    let silence = chrono::Duration::seconds(policy.silence_secs as i64);
+    if cache.is_stale(now, silence) {
+        return false;
+    }
+
+    let heard = cache
+        .nodes
+        .iter()
+        .find(|node| &node.node_id == node_id)
+        .map(|node| node.timestamp as i64 + silence.num_seconds() >= now.timestamp())
+        .unwrap_or(false);
+    if heard {
+        return false;
+    }
+    !job.node_address.as_deref().map(reachable).unwrap_or(false)
+}
+
+/// Estimated AVAX `job` has cost on its current node so far
+fn spent_on_node(job: &JobRecord, now: chrono::DateTime<chrono::Utc>) -> f32 {
+    job.offered_price.unwrap_or(0.0) * job.billable_seconds(now) as f32 / 3600.0
+}
+
+/// Pick the next node for a job whose node died, or say why it stays where it is
+pub fn plan(
+    job: &JobRecord,
+    entry: &HistoryEntry,
+    nodes: &[NodeAdvertisement],
+    snapshots: &[Snapshot],
+    policy: &FailoverPolicy,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<FailoverPlan, String> {
+    if !policy.enabled {
+        return Err("failover is off".to_string());
+    }
+    // A free trial belongs to the node that gave it
+    if job.trial.is_some() {
+        return Err("free trials are not moved".to_string());
+    }
+    let from_node = job
+        .node_id
+        .clone()
+        .ok_or_else(|| format!("job {} has no node", job.job_id))?;
+    if entry.failovers.len() as u32 >= policy.max_attempts {
+        return Err(format!("already moved {} times", entry.failovers.len()));
+    }
+    let spent = spent_on_node(job, now);
+    let total: f32 = entry.failovers.iter().map(|hop| hop.spent).sum::<f32>() + spent;
+    if let Some(budget) = policy.max_spend {
+        if total >= budget {
+            return Err(format!("spent {:.2} of its {:.2} AVAX budget", total, budget));
+        }
+    }
+
+    // Never go back to a node the job has already been on
+    let tried: Vec<&str> = entry
+        .node_id
+        .iter()
+        .chain(std::iter::once(&from_node))
+        .map(|id| id.as_str())
+        .chain(entry.failovers.iter().flat_map(|hop| [hop.from_node.as_str(), hop.to_node.as_str()]))
+        .collect();
+    let needs = JobNeeds::declared(&entry.spec);
+    let next = requirements::suggest(&needs, nodes)
+        .into_iter()
+        .filter(|node| !tried.contains(&node.node_id.as_str()))
+        .filter(|node| node.status == NodeStatus::Available && node.free_slots != Some(0))
+        .find(|node| match (policy.max_price_per_hour, node.price_per_hour) {
+            (Some(cap), Some(price)) => price <= cap,
+            _ => true,
+        })
+        .ok_or_else(|| "no other node fits".to_string())?;
+
+    let snapshot = snapshots
+        .iter()
+        .filter(|snapshot| snapshot.job_id == job.job_id)
+        .max_by_key(|snapshot| snapshot.created_at);
+    let hop = FailoverHop {
+        from_node,
+        to_node: next.node_id.clone(),
+        at: now,
+        reason: "node stopped responding".to_string(),
+        snapshot: snapshot.map(|snapshot| snapshot.id.clone()),
+        spent,
+    };
+    let submission = JobSubmission {
+        job_id: job.job_id.clone(),
+        client_id: job.client_id.clone(),
+        image: entry.spec.image_tag(),
+        command: entry.spec.command.clone(),
+        node_id: Some(next.node_id.clone()),
+        node_address: Some(next.zerotier_ip.clone().unwrap_or_else(|| next.ip_address.clone())),
+        ssh_user: None,
+        trial: None,
+        // The deposit stays bound to the old node's ledger; the reputation goes wherever the job does
+        deposit: None,
+        reputation: job.reputation.clone(),
+    };
+    Ok(FailoverPlan { hop, submission })
+}
+
+impl JobManager {
+    /// Move every running job off a dead node, as far as the policy allows; returns one line
+    /// per job moved or left behind
+    pub fn fail_over_dead_nodes(
+        &self,
+        history: &mut JobHistory,
+        cache: &NodeCache,
+        store: &WorkspaceStore,
+        policy: &FailoverPolicy,
+    ) -> Vec<String> {
+        if !policy.enabled {
+            return vec![];
+        }
+
+        let now = chrono::Utc::now();
+        let reachable = |address: &str| is_reachable(address, 22, Duration::from_secs(3));
+        let snapshots = store.list();
+        let mut notes = vec![];
+        for job in self.list_jobs() {
+            if job.status != JobStatus::Running || !node_is_dead(&job, cache, policy, now, &reachable) {
+                continue;
+            }
+            let Some(entry) = history.get(&job.job_id).cloned() else {
+                continue;
+            };
+
+            let plan = match plan(&job, &entry, &cache.nodes, &snapshots, policy, now) {
+                Ok(plan) => plan,
+                Err(reason) => {
+                    warn!("Job {} stays on dead node: {}", job.job_id, reason);
+                    let _ = self.update_status(&job.job_id, JobStatus::Failed(format!("Node died; {}", reason)));
+                    notes.push(format!("Job {} failed with its node: {}", job.job_id, reason));
+                    continue;
+                }
+            };
+            if let Err(e) = self.submit_job(&plan.submission) {
+                notes.push(format!("Failed to move job {} to {}: {}", job.job_id, plan.hop.to_node, e));
+                continue;
+            }
+            if let Some(snapshot) = &plan.hop.snapshot {
+                if let Err(e) = self.restore_workspace(&job.job_id, store, snapshot) {
+                    notes.push(format!("Job {} moved without its workspace: {}", job.job_id, e));
+                }
+            }
+
+            info!("Job {} moved from {} to {}", job.job_id, plan.hop.from_node, plan.hop.to_node);
+            notes.push(format!("Job {} moved from dead node {} to {}", job.job_id, plan.hop.from_node, plan.hop.to_node));
+            history.record_failover(&job.job_id, plan.hop);
+        }
+        notes
+    }
+}
+
+/// Failover policy of the active profile
+pub fn default_policy_path() -> PathBuf {
+    profiles::active_profile().data_dir().join("failover.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec::JobSpec;
+    use eryzaa_discovery::create_client_advertisement;
+
+    fn node(id: &str, price: f32, timestamp: u64) -> NodeAdvertisement {
+        let mut node = create_client_advertisement(id.to_string(), "10.0.0.1".to_string(), None, String::new());
+        node.capabilities.disk_space_gb = 500;
+        node.capabilities.supports_docker = true;
+        node.status = NodeStatus::Available;
+        node.price_per_hour = Some(price);
+        node.timestamp = timestamp;
+        node
+    }
+
+    #[test]
+    fn test_failover_chain() {
+        let now = chrono::Utc::now();
+        let fresh = now.timestamp() as u64;
+        let mut cache = NodeCache::default();
+        cache.update(vec![node("b", 3.0, fresh), node("c", 1.0, fresh), node("d", 9.0, fresh)]);
+
+        let mut job = JobRecord::new("job-1", "alice", "pytorch/pytorch");
+        job.node_id = Some("a".to_string());
+        job.node_address = Some("10.0.0.9".to_string());
+        job.status = JobStatus::Running;
+        let policy = FailoverPolicy { enabled: true, max_price_per_hour: Some(5.0), ..Default::default() };
+        assert!(node_is_dead(&job, &cache, &policy, now, &|_| false));
+        assert!(!node_is_dead(&job, &cache, &policy, now, &|_| true));
+
+        let mut history = JobHistory::default();
+        let spec = JobSpec { name: "train".to_string(), image: Some("pytorch/pytorch".to_string()), ..Default::default() };
+        history.record("job-1", spec, Some("a".to_string()));
+        let entry = history.get("job-1").unwrap().clone();
+        assert!(plan(&job, &entry, &cache.nodes, &[], &FailoverPolicy::default(), now).is_err());
+
+        // Cheapest fitting node first, then the next one not yet tried, never over the price cap
+        let first = plan(&job, &entry, &cache.nodes, &[], &policy, now).unwrap();
+        assert_eq!(first.hop.to_node, "c");
+        assert_eq!(first.submission.node_id.as_deref(), Some("c"));
+        history.record_failover("job-1", first.hop);
+        job.node_id = Some("c".to_string());
+
+        let entry = history.get("job-1").unwrap().clone();
+        assert_eq!(entry.node_id.as_deref(), Some("c"));
+        assert_eq!(plan(&job, &entry, &cache.nodes, &[], &policy, now).unwrap().hop.to_node, "b");
+
+        let limited = FailoverPolicy { max_attempts: 1, ..policy };
+        assert!(plan(&job, &entry, &cache.nodes, &[], &limited, now).is_err());
+    }
+}
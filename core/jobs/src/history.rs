@@ -0,0 +1,123 @@
+//! Submitted job history
+//! Every spec a client submits is kept with its target node and last known outcome, so a past
+//! job can be cloned, edited and submitted again without rebuilding it by hand.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::failover::FailoverHop;
+use crate::spec::JobSpec;
+use crate::{profiles, JobManager, JobStatus};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HistoryEntry {
+    pub job_id: String,
+    pub spec: JobSpec,
+    pub node_id: Option<String>,
+    pub submitted_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub outcome: Option<JobStatus>, // None until the job shows up in the registry
+    #[serde(default)]
+    pub failovers: Vec<FailoverHop>, // Moves off dead nodes, oldest first; node_id is the latest
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobHistory {
+    pub entries: Vec<HistoryEntry>, // Oldest first
+}
+
+impl JobHistory {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize job history: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write job history {}: {}", path.display(), e))
+    }
+
+    pub fn record(&mut self, job_id: &str, spec: JobSpec, node_id: Option<String>) {
+        self.entries.push(HistoryEntry {
+            job_id: job_id.to_string(),
+            spec,
+            node_id,
+            submitted_at: chrono::Utc::now(),
+            outcome: None,
+            failovers: vec![],
+        });
+    }
+
+    /// Note that a job moved to another node after its node died
+    pub fn record_failover(&mut self, job_id: &str, hop: FailoverHop) {
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.job_id == job_id) {
+            entry.node_id = Some(hop.to_node.clone());
+            entry.outcome = None;
+            entry.failovers.push(hop);
+        }
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<&HistoryEntry> {
+        self.entries.iter().find(|entry| entry.job_id == job_id)
+    }
+
+    /// Copy each job's current status from the registry; returns whether anything changed
+    pub fn refresh_outcomes(&mut self, manager: &JobManager) -> bool {
+        let mut changed = false;
+        for entry in &mut self.entries {
+            let status = manager.get_job(&entry.job_id).map(|record| record.status);
+            if status.is_some() && status != entry.outcome {
+                entry.outcome = status;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    pub fn remove(&mut self, job_id: &str) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|entry| entry.job_id != job_id);
+        self.entries.len() != before
+    }
+}
+
+/// Job history of the active profile
+pub fn default_history_path() -> PathBuf {
+    profiles::active_profile().data_dir().join("history.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JobRecord;
+
+    #[test]
+    fn test_refresh_outcomes() {
+        let mut history = JobHistory::default();
+        let spec = JobSpec {
+            name: "train".to_string(),
+            image: Some("pytorch/pytorch".to_string()),
+            ..Default::default()
+        };
+        history.record("job-1", spec, Some("node-a".to_string()));
+
+        let manager = JobManager::new();
+        assert!(!history.refresh_outcomes(&manager));
+
+        let mut record = JobRecord::new("job-1", "alice", "pytorch/pytorch");
+        record.status = JobStatus::Completed;
+        manager.register_job(record);
+        assert!(history.refresh_outcomes(&manager));
+        assert_eq!(history.get("job-1").unwrap().outcome, Some(JobStatus::Completed));
+        assert!(!history.refresh_outcomes(&manager));
+    }
+}
@@ -0,0 +1,440 @@
+//! Job records and container bookkeeping for Eryzaa rental nodes
+//! Shared by the rental node (which runs the containers) and clients (which track their jobs)
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use log::{info, warn};
+use eryzaa_discovery::clock::SessionMarker;
+use eryzaa_discovery::names::{self, HostEntry};
+use eryzaa_discovery::TrialOffer;
+use eryzaa_protocol::{DepositClaim, ReputationAttestation};
+
+pub mod abuse;
+pub mod cache;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+pub mod compose;
+pub mod cuda;
+pub mod demo;
+pub mod exec;
+pub mod failover;
+pub mod handoff;
+pub mod history;
+pub mod offline;
+pub mod onboarding;
+pub mod outputs;
+pub mod paste;
+pub mod pause;
+pub mod pinning;
+pub mod pipeline;
+pub mod ports;
+pub mod profiles;
+pub mod receipt;
+pub mod requirements;
+pub mod reservation;
+pub mod rollout;
+pub mod sandbox;
+pub mod schema;
+pub mod scratch;
+pub mod spec;
+pub mod ssh_config;
+pub mod stack;
+pub mod staging;
+pub mod sweep;
+pub mod terminal;
+pub mod ticker;
+pub mod transfers;
+pub mod trial;
+pub mod warm;
+pub mod workspace;
+
+pub use abuse::BlocklistFeed;
+pub use exec::ExecRequest;
+pub use failover::{FailoverHop, FailoverPolicy};
+pub use handoff::{CredentialBundle, HandoffLink, HandoffStatus, HandoffStore};
+pub use history::{HistoryEntry, JobHistory};
+pub use offline::{JobSubmission, NodeCache, OperationQueue, QueuedAction};
+pub use onboarding::ClientOnboarding;
+pub use outputs::OutputPush;
+pub use pause::{PauseOptions, PausePolicy};
+pub use pinning::{CorePinning, PinningSettings};
+pub use pipeline::{Pipeline, PipelineRun, PipelineStore, StageState};
+pub use ports::{PortAllocator, PortMapping};
+pub use profiles::{NodeBookmark, Profile, ProfileStore};
+pub use receipt::JobReceipt;
+pub use reservation::OwnerReservation;
+pub use sandbox::PathViolation;
+pub use scratch::{ScratchDisk, ScratchPools, StoragePool};
+pub use spec::{ExecutorConfig, JobSpec};
+pub use ssh_config::SshConfigWriter;
+pub use staging::{SourceCredentials, StagingProgress};
+pub use sweep::{SweepRun, SweepStore, SweepTarget, TrialState};
+pub use ticker::CostTicker;
+pub use transfers::{DestinationClass, EgressAlertPolicy, TransferLedger};
+pub use warm::WarmPool;
+pub use workspace::{Snapshot, WorkspaceStore};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Paused,
+    Completed,
+    Stopped,
+    Failed(String),
+}
+
+/// Where a pending job stands with the node's admission rules
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub enum AdmissionState {
+    #[default]
+    Unreviewed,
+    Admitted,
+    AwaitingOwner(String), // Rule that left the decision to the owner
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub job_id: String,
+    pub client_id: String,
+    pub image: String,
+    pub container_name: String,
+    #[serde(default)]
+    pub gpus: u32, // GPUs the job needs
+    pub node_address: Option<String>, // Overlay IP of the node running the job
+    #[serde(default)]
+    pub node_id: Option<String>,
+    pub ssh_user: Option<String>,     // Job user issued by the node's SshManager
+    #[serde(default)]
+    pub host_key: Option<String>, // Node SSH host key received when the job was granted
+    #[serde(default)]
+    pub access_token: Option<String>, // Token authorizing API calls for the job
+    pub status: JobStatus,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub paused_at: Option<chrono::DateTime<chrono::Utc>>, // For display; durations come from `paused_marker` where it can
+    #[serde(default)]
+    pub paused_seconds: u64, // Total time spent paused, excluded from billing
+    #[serde(default)]
+    pub started_marker: Option<SessionMarker>, // Monotonic clock reading when the job was created
+    #[serde(default)]
+    pub paused_marker: Option<SessionMarker>, // Monotonic clock reading when the job was paused
+    #[serde(default)]
+    pub checkpoint: Option<String>, // Checkpoint name when paused with resources released
+    #[serde(default)]
+    pub port_mappings: Vec<PortMapping>, // Host ports leased to the job's services
+    #[serde(default)]
+    pub energy_wh: f64, // Estimated energy used so far
+    #[serde(default)]
+    pub carbon_intensity_g_per_kwh: Option<f32>, // Grid carbon intensity of the node running the job
+    #[serde(default)]
+    pub offered_price: Option<f32>, // Price per hour the client offered
+    #[serde(default)]
+    pub admission: AdmissionState,
+    #[serde(default)]
+    pub cached_result: Option<String>, // Cache entry the job was answered from, instead of running
+    #[serde(default)]
+    pub cache_fee: Option<f32>, // Charged for a cached answer in place of run time
+    #[serde(default)]
+    pub scratch: Option<ScratchDisk>, // Scratch disk provisioned for the job, metered separately
+    #[serde(default)]
+    pub staging: Option<StagingProgress>, // Input being downloaded while the job is pending
+    #[serde(default)]
+    pub staged_bytes: u64, // Input data the node downloaded for the job, metered separately
+    #[serde(default)]
+    pub output_push: Option<OutputPush>, // Outputs to upload to the client's bucket once the job succeeds
+    #[serde(default)]
+    pub pinning: Option<CorePinning>, // Cores dedicated to the job
+    #[serde(default)]
+    pub trial: Option<TrialOffer>, // Terms of the free trial the job runs as, None for paid jobs
+    #[serde(default)]
+    pub deposit: Option<DepositClaim>, // Deposit the client says it paid, checked by the node on admission
+    #[serde(default)]
+    pub reputation: Option<ReputationAttestation>, // Client's signed reputation, sent along with the job
+}
+
+impl JobRecord {
+    pub fn new(job_id: &str, client_id: &str, image: &str) -> Self {
+        Self {
+            job_id: job_id.to_string(),
+            client_id: client_id.to_string(),
+            image: image.to_string(),
+            container_name: container_name_for(job_id),
+            gpus: 0,
+            node_address: None,
+            node_id: None,
+            ssh_user: None,
+            host_key: None,
+            access_token: None,
+            status: JobStatus::Pending,
+            created_at: chrono::Utc::now(),
+            paused_at: None,
+            paused_seconds: 0,
+            started_marker: SessionMarker::now(),
+            paused_marker: None,
+            checkpoint: None,
+            port_mappings: vec![],
+            energy_wh: 0.0,
+            carbon_intensity_g_per_kwh: None,
+            offered_price: None,
+            admission: AdmissionState::Unreviewed,
+            cached_result: None,
+            cache_fee: None,
+            scratch: None,
+            staging: None,
+            staged_bytes: 0,
+            output_push: None,
+            pinning: None,
+            trial: None,
+            deposit: None,
+            reputation: None,
+        }
+    }
+
+    /// Overlay host name entries for this job and the node running it
+    pub fn host_entries(&self) -> Vec<HostEntry> {
+        let Some(ip) = &self.node_address else {
+            return vec![];
+        };
+
+        let mut entries = vec![HostEntry { name: names::job_hostname(&self.job_id), ip: ip.clone() }];
+        if let Some(node_id) = &self.node_id {
+            entries.push(HostEntry { name: names::node_hostname(node_id), ip: ip.clone() });
+        }
+        entries
+    }
+
+    /// Host to use when connecting to the job: its DNS name when it resolves, else the IP
+    pub fn connect_host(&self) -> Option<String> {
+        self.node_address
+            .as_ref()
+            .map(|ip| names::preferred_host(&names::job_hostname(&self.job_id), ip))
+    }
+
+    /// Seconds of billable run time up to `now`, excluding time spent paused
+    pub fn billable_seconds(&self, now: chrono::DateTime<chrono::Utc>) -> u64 {
+        self.billable_seconds_at(now, SessionMarker::now().as_ref())
+    }
+
+    /// Billable seconds measured from monotonic `marker` where the job's own markers come from
+    /// the same boot, so clock steps do not change the bill; wall clock `now` otherwise
+    pub fn billable_seconds_at(&self, now: chrono::DateTime<chrono::Utc>, marker: Option<&SessionMarker>) -> u64 {
+        if self.cached_result.is_some() {
+            return 0;
+        }
+        let elapsed = since(self.created_at, self.started_marker.as_ref(), now, marker);
+        elapsed.saturating_sub(self.paused_seconds + self.current_pause_seconds(now, marker))
+    }
+
+    /// Seconds the job has been paused so far, if it is paused
+    pub(crate) fn current_pause_seconds(&self, now: chrono::DateTime<chrono::Utc>, marker: Option<&SessionMarker>) -> u64 {
+        self.paused_at
+            .map(|paused_at| since(paused_at, self.paused_marker.as_ref(), now, marker))
+            .unwrap_or(0)
+    }
+}
+
+/// Seconds from `start` to `now`, preferring the monotonic markers when both are comparable
+fn since(
+    start: chrono::DateTime<chrono::Utc>,
+    start_marker: Option<&SessionMarker>,
+    now: chrono::DateTime<chrono::Utc>,
+    marker: Option<&SessionMarker>,
+) -> u64 {
+    match (start_marker, marker) {
+        (Some(start_marker), Some(marker)) => start_marker.seconds_until(marker),
+        _ => None,
+    }
+    .unwrap_or_else(|| (now - start).num_seconds().max(0) as u64)
+}
+
+/// Tracks the jobs known to this machine
+pub struct JobManager {
+    jobs: Arc<Mutex<HashMap<String, JobRecord>>>,
+}
+
+impl JobManager {
+    pub fn new() -> Self {
+        Self {
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Load a job registry previously written with `save_to`
+    pub fn load_from(path: &Path) -> Result<Self, String> {
+        let manager = Self::new();
+        if !path.exists() {
+            return Ok(manager);
+        }
+
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read job registry {}: {}", path.display(), e))?;
+        let records: Vec<JobRecord> = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse job registry {}: {}", path.display(), e))?;
+
+        {
+            let mut jobs = manager.jobs.lock().unwrap();
+            for record in records {
+                jobs.insert(record.job_id.clone(), record);
+            }
+        }
+
+        Ok(manager)
+    }
+
+    /// Write the job registry to disk
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let records = self.list_jobs();
+        let content = serde_json::to_string_pretty(&records)
+            .map_err(|e| format!("Failed to serialize job registry: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write job registry {}: {}", path.display(), e))
+    }
+
+    /// Register a new job or replace an existing record
+    pub fn register_job(&self, record: JobRecord) {
+        info!("Registered job '{}' (container: {})", record.job_id, record.container_name);
+        self.jobs.lock().unwrap().insert(record.job_id.clone(), record);
+    }
+
+    /// Get a job by id
+    pub fn get_job(&self, job_id: &str) -> Option<JobRecord> {
+        self.jobs.lock().unwrap().get(job_id).cloned()
+    }
+
+    /// Get all known jobs
+    pub fn list_jobs(&self) -> Vec<JobRecord> {
+        self.jobs.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Number of jobs currently running
+    pub fn running_count(&self) -> usize {
+        self.jobs
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|job| job.status == JobStatus::Running)
+            .count()
+    }
+
+    /// Update the status of a job
+    pub fn update_status(&self, job_id: &str, status: JobStatus) -> Result<(), String> {
+        let mut jobs = self.jobs.lock().unwrap();
+        match jobs.get_mut(job_id) {
+            Some(record) => {
+                // A job that is over gives its service ports back to the node
+                if matches!(status, JobStatus::Completed | JobStatus::Stopped | JobStatus::Failed(_)) && !record.port_mappings.is_empty() {
+                    info!("Released ports {:?} of job '{}'", record.port_mappings, job_id);
+                    record.port_mappings.clear();
+                }
+                record.status = status;
+                Ok(())
+            }
+            None => Err(format!("No job found with id '{}'", job_id)),
+        }
+    }
+
+    /// Record the admission outcome of a pending job
+    pub fn set_admission(&self, job_id: &str, admission: AdmissionState) -> Result<(), String> {
+        let mut jobs = self.jobs.lock().unwrap();
+        match jobs.get_mut(job_id) {
+            Some(record) => {
+                record.admission = admission;
+                Ok(())
+            }
+            None => Err(format!("No job found with id '{}'", job_id)),
+        }
+    }
+
+    /// Start the container of a pending job
+    pub fn start_job(&self, job_id: &str) -> Result<(), String> {
+        let record = self
+            .get_job(job_id)
+            .ok_or_else(|| format!("No job found with id '{}'", job_id))?;
+
+        if record.status != JobStatus::Pending {
+            return Err(format!("Job '{}' is not pending ({:?})", job_id, record.status));
+        }
+
+        pause::run_docker(&record, &["start", &record.container_name])?;
+        self.update_status(job_id, JobStatus::Running)
+    }
+
+    /// Forget a job
+    pub fn remove_job(&self, job_id: &str) -> Option<JobRecord> {
+        let removed = self.jobs.lock().unwrap().remove(job_id);
+        if removed.is_none() {
+            warn!("No job found with id '{}'", job_id);
+        }
+        removed
+    }
+
+    /// Open an exec/attach session inside a running job container
+    ///
+    /// Runs `docker exec` directly when the job lives on this machine, otherwise bridges it
+    /// over SSH to the node. Blocks until the session ends and returns its exit code.
+    pub fn exec(&self, request: &ExecRequest) -> Result<i32, String> {
+        let record = self
+            .get_job(&request.job_id)
+            .ok_or_else(|| format!("No job found with id '{}'", request.job_id))?;
+
+        if record.status != JobStatus::Running {
+            return Err(format!("Job '{}' is not running ({:?})", record.job_id, record.status));
+        }
+
+        exec::run_exec(&record, request)
+    }
+}
+
+impl Default for JobManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Name of the docker container backing a job
+pub fn container_name_for(job_id: &str) -> String {
+    format!("eryzaa-job-{}", job_id)
+}
+
+/// Job registry of the active profile (~/.eryzaa/jobs.json for the default profile)
+pub fn default_registry_path() -> PathBuf {
+    profiles::active_profile().data_dir().join("jobs.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_registry_roundtrip() {
+        let manager = JobManager::new();
+        manager.register_job(JobRecord::new("abc123", "client-1", "ubuntu:22.04"));
+        manager.update_status("abc123", JobStatus::Running).unwrap();
+
+        let path = std::env::temp_dir().join(format!("eryzaa-jobs-{}.json", uuid::Uuid::new_v4()));
+        manager.save_to(&path).unwrap();
+        let loaded = JobManager::load_from(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let record = loaded.get_job("abc123").unwrap();
+        assert_eq!(record.container_name, "eryzaa-job-abc123");
+        assert_eq!(record.status, JobStatus::Running);
+    }
+
+    #[test]
+    fn test_exec_requires_running_job() {
+        let manager = JobManager::new();
+        manager.register_job(JobRecord::new("pending", "client-1", "ubuntu:22.04"));
+
+        let result = manager.exec(&ExecRequest::shell("pending"));
+        assert!(result.is_err());
+    }
+}
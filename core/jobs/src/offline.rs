@@ -0,0 +1,353 @@
+//! Offline awareness for clients
+//! The last node list is cached with its fetch time, and job submissions/cancellations made
+//! while a node is unreachable are queued on disk and replayed in order once it is back.
+
+use serde::{Deserialize, Serialize};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use log::{info, warn};
+use eryzaa_discovery::NodeAdvertisement;
+pub use eryzaa_protocol::job::JobSubmission;
+
+use crate::{profiles, JobManager, JobRecord, JobStatus, SshConfigWriter};
+
+/// Last node list seen from discovery
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NodeCache {
+    pub nodes: Vec<NodeAdvertisement>,
+    pub fetched_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl NodeCache {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        write_json(path, self)
+    }
+
+    /// Replace the cached nodes with a fresh list
+    pub fn update(&mut self, nodes: Vec<NodeAdvertisement>) {
+        self.nodes = nodes;
+        self.fetched_at = Some(chrono::Utc::now());
+    }
+
+    /// Whether the cache is missing or older than `max_age`
+    pub fn is_stale(&self, now: chrono::DateTime<chrono::Utc>, max_age: chrono::Duration) -> bool {
+        self.fetched_at.map(|at| now - at > max_age).unwrap_or(true)
+    }
+
+    /// Short description of the cache age, e.g. "updated 5m ago"
+    pub fn age_label(&self, now: chrono::DateTime<chrono::Utc>) -> String {
+        match self.fetched_at {
+            None => "never updated".to_string(),
+            Some(at) => {
+                let secs = (now - at).num_seconds().max(0);
+                if secs < 60 {
+                    "updated just now".to_string()
+                } else if secs < 3600 {
+                    format!("updated {}m ago", secs / 60)
+                } else if secs < 86400 {
+                    format!("updated {}h ago", secs / 3600)
+                } else {
+                    format!("updated {}d ago", secs / 86400)
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum QueuedAction {
+    Submit(Box<JobSubmission>),
+    Cancel { job_id: String },
+}
+
+impl QueuedAction {
+    pub fn job_id(&self) -> &str {
+        match self {
+            QueuedAction::Submit(submission) => &submission.job_id,
+            QueuedAction::Cancel { job_id } => job_id,
+        }
+    }
+}
+
+impl std::fmt::Display for QueuedAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueuedAction::Submit(submission) => write!(f, "submit {} ({})", submission.job_id, submission.image),
+            QueuedAction::Cancel { job_id } => write!(f, "cancel {}", job_id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedOperation {
+    pub id: String,
+    pub action: QueuedAction,
+    pub queued_at: chrono::DateTime<chrono::Utc>,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReplayReport {
+    pub completed: Vec<String>, // Ids of operations that went through
+    pub remaining: usize,
+    pub error: Option<String>, // Why replay stopped early
+}
+
+/// Operations waiting for connectivity, replayed in the order they were made
+pub struct OperationQueue {
+    operations: Arc<Mutex<Vec<QueuedOperation>>>,
+}
+
+impl OperationQueue {
+    pub fn new() -> Self {
+        Self {
+            operations: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, String> {
+        let queue = Self::new();
+        if !path.exists() {
+            return Ok(queue);
+        }
+
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read operation queue {}: {}", path.display(), e))?;
+        let operations: Vec<QueuedOperation> = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse operation queue {}: {}", path.display(), e))?;
+        *queue.operations.lock().unwrap() = operations;
+
+        Ok(queue)
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        write_json(path, &self.pending())
+    }
+
+    /// Queue an action; cancelling a job whose submission is still queued drops both
+    pub fn enqueue(&self, action: QueuedAction) -> Option<String> {
+        let mut operations = self.operations.lock().unwrap();
+
+        if let QueuedAction::Cancel { job_id } = &action {
+            let before = operations.len();
+            operations.retain(|op| !matches!(&op.action, QueuedAction::Submit(s) if &s.job_id == job_id));
+            if operations.len() != before {
+                info!("Dropped queued submission of job '{}' that was cancelled offline", job_id);
+                return None;
+            }
+        }
+
+        let id = uuid::Uuid::new_v4().to_string();
+        info!("Queued {}", action);
+        operations.push(QueuedOperation {
+            id: id.clone(),
+            action,
+            queued_at: chrono::Utc::now(),
+            attempts: 0,
+            last_error: None,
+        });
+        Some(id)
+    }
+
+    pub fn pending(&self) -> Vec<QueuedOperation> {
+        self.operations.lock().unwrap().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.operations.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Run queued operations in order until one fails
+    ///
+    /// A failed operation stays at the head of the queue so later ones never overtake it.
+    pub fn replay<F>(&self, mut execute: F) -> ReplayReport
+    where
+        F: FnMut(&QueuedAction) -> Result<(), String>,
+    {
+        let mut operations = self.operations.lock().unwrap();
+        let mut report = ReplayReport::default();
+
+        while let Some(op) = operations.first_mut() {
+            op.attempts += 1;
+            match execute(&op.action) {
+                Ok(()) => {
+                    report.completed.push(op.id.clone());
+                    operations.remove(0);
+                }
+                Err(e) => {
+                    warn!("Replay of queued operation for job '{}' failed: {}", op.action.job_id(), e);
+                    op.last_error = Some(e.clone());
+                    report.error = Some(e);
+                    break;
+                }
+            }
+        }
+
+        report.remaining = operations.len();
+        report
+    }
+}
+
+impl Default for OperationQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JobManager {
+    /// Start a job container on its node and record it
+    pub fn submit_job(&self, submission: &JobSubmission) -> Result<(), String> {
+        let mut record = JobRecord::new(&submission.job_id, &submission.client_id, &submission.image);
+        record.node_id = submission.node_id.clone();
+        record.node_address = submission.node_address.clone();
+        record.ssh_user = submission.ssh_user.clone();
+        record.trial = submission.trial;
+        record.deposit = submission.deposit.clone();
+        record.reputation = submission.reputation.clone();
+
+        let mut args = vec!["run", "-d", "--name", &record.container_name, &submission.image];
+        args.extend(submission.command.iter().map(|s| s.as_str()));
+        crate::pause::run_docker(&record, &args)?;
+
+        // The first connection to the node pinned its key; later ones must present the same
+        if let Some(host) = record.connect_host() {
+            record.host_key = SshConfigWriter::for_current_user().pinned_key(&host);
+        }
+        record.status = JobStatus::Running;
+        self.register_job(record);
+        Ok(())
+    }
+
+    /// Stop and remove a job's container, keeping the record for history
+    pub fn cancel_job(&self, job_id: &str) -> Result<(), String> {
+        let record = self
+            .get_job(job_id)
+            .ok_or_else(|| format!("No job found with id '{}'", job_id))?;
+
+        crate::pause::run_docker(&record, &["rm", "-f", &record.container_name])?;
+        self.update_status(job_id, JobStatus::Stopped)
+    }
+
+    /// Carry out a queued action, failing fast if its node is still unreachable
+    pub fn execute_queued(&self, action: &QueuedAction) -> Result<(), String> {
+        let address = match action {
+            QueuedAction::Submit(submission) => submission.node_address.clone(),
+            QueuedAction::Cancel { job_id } => self.get_job(job_id).and_then(|job| job.node_address),
+        };
+        if let Some(address) = &address {
+            if !is_reachable(address, 22, Duration::from_secs(3)) {
+                return Err(format!("Node {} is unreachable", address));
+            }
+        }
+
+        match action {
+            QueuedAction::Submit(submission) => self.submit_job(submission),
+            QueuedAction::Cancel { job_id } => self.cancel_job(job_id),
+        }
+    }
+}
+
+/// Whether a TCP connection to `host:port` can be opened within `timeout`
+pub fn is_reachable(host: &str, port: u16, timeout: Duration) -> bool {
+    match (host, port).to_socket_addrs() {
+        Ok(addrs) => addrs
+            .into_iter()
+            .any(|addr| TcpStream::connect_timeout(&addr, timeout).is_ok()),
+        Err(_) => false,
+    }
+}
+
+/// Operation queue of the active profile
+pub fn default_queue_path() -> PathBuf {
+    profiles::active_profile().data_dir().join("queue.json")
+}
+
+/// Node cache of the active profile
+pub fn default_node_cache_path() -> PathBuf {
+    profiles::active_profile().data_dir().join("nodes.json")
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+
+    let content = serde_json::to_string_pretty(value)
+        .map_err(|e| format!("Failed to serialize {}: {}", path.display(), e))?;
+    std::fs::write(path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn submission(job_id: &str) -> QueuedAction {
+        QueuedAction::Submit(Box::new(JobSubmission {
+            job_id: job_id.to_string(),
+            client_id: "client-1".to_string(),
+            image: "ubuntu:22.04".to_string(),
+            command: vec![],
+            node_id: None,
+            node_address: Some("10.242.1.5".to_string()),
+            ssh_user: None,
+            trial: None,
+            deposit: None,
+            reputation: None,
+        }))
+    }
+
+    #[test]
+    fn test_replay_stops_at_first_failure() {
+        let queue = OperationQueue::new();
+        queue.enqueue(submission("a"));
+        queue.enqueue(submission("b"));
+        queue.enqueue(QueuedAction::Cancel { job_id: "old".to_string() });
+
+        let report = queue.replay(|action| match action.job_id() {
+            "b" => Err("unreachable".to_string()),
+            _ => Ok(()),
+        });
+        assert_eq!(report.completed.len(), 1);
+        assert_eq!(report.remaining, 2);
+
+        let head = &queue.pending()[0];
+        assert_eq!(head.action.job_id(), "b");
+        assert_eq!(head.attempts, 1);
+
+        assert_eq!(queue.replay(|_| Ok(())).remaining, 0);
+    }
+
+    #[test]
+    fn test_cancel_drops_queued_submission() {
+        let queue = OperationQueue::new();
+        queue.enqueue(submission("a"));
+        assert!(queue.enqueue(QueuedAction::Cancel { job_id: "a".to_string() }).is_none());
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_node_cache_staleness() {
+        let now = chrono::Utc::now();
+        let mut cache = NodeCache::default();
+        assert!(cache.is_stale(now, chrono::Duration::minutes(5)));
+
+        cache.update(vec![]);
+        assert!(!cache.is_stale(now, chrono::Duration::minutes(5)));
+        assert_eq!(cache.age_label(now + chrono::Duration::minutes(90)), "updated 1h ago");
+    }
+}
@@ -0,0 +1,204 @@
+//! First-run onboarding of the client
+//! Gives the profile a client id and an SSH key, records the wallet it pays from, joins the
+//! overlay and checks that the network carries traffic by bouncing a line off an echo node.
+//! Each step is saved as it completes, so the app reopens where the owner left off.
+
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use crate::profiles;
+
+/// `host:port` of the echo node the self-test uses, instead of the first coordinator's host
+pub const ECHO_NODE_ENV: &str = "ERYZAA_ECHO_NODE";
+
+/// The TCP echo service (RFC 862), which echo nodes run
+pub const ECHO_PORT: u16 = 7;
+
+/// Outcome of the connectivity self-test
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SelfTest {
+    pub echo_node: String,
+    pub round_trip_ms: Option<u32>, // None when the test failed
+    pub error: Option<String>,
+    pub tested_at: u64, // Unix seconds
+}
+
+/// Progress of onboarding, saved after each step
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ClientOnboarding {
+    #[serde(default)]
+    pub client_id: String,
+    #[serde(default)]
+    pub ssh_key: Option<PathBuf>, // Private key; the public one sits next to it
+    #[serde(default)]
+    pub wallet_address: Option<String>,
+    #[serde(default)]
+    pub wallet_skipped: bool, // The owner chose to pay later
+    #[serde(default)]
+    pub overlay_ip: Option<String>,
+    #[serde(default)]
+    pub self_test: Option<SelfTest>,
+    #[serde(default)]
+    pub completed_at: Option<u64>,
+}
+
+impl ClientOnboarding {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize onboarding state: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write onboarding state {}: {}", path.display(), e))
+    }
+
+    /// Give the client an id unless it has one; true when a new one was made
+    pub fn ensure_identity(&mut self) -> bool {
+        if !self.client_id.is_empty() {
+            return false;
+        }
+        self.client_id = uuid::Uuid::new_v4().to_string();
+        true
+    }
+
+    /// Whether the wallet step is behind the owner, with an address or by skipping it
+    pub fn wallet_done(&self) -> bool {
+        self.wallet_address.is_some() || self.wallet_skipped
+    }
+
+    /// Whether the self-test went through
+    pub fn self_test_passed(&self) -> bool {
+        self.self_test.as_ref().is_some_and(|test| test.round_trip_ms.is_some())
+    }
+
+    /// Whether every step has been done; the overlay may be left out where the echo node is reachable without it
+    pub fn is_complete(&self) -> bool {
+        !self.client_id.is_empty() && self.ssh_key.is_some() && self.wallet_done() && self.self_test_passed()
+    }
+}
+
+/// Onboarding state of the active profile
+pub fn default_onboarding_path() -> PathBuf {
+    profiles::active_profile().data_dir().join("onboarding.json")
+}
+
+/// Make an ed25519 key at `path` unless one is there already
+pub fn generate_ssh_key(path: &Path, comment: &str) -> Result<(), String> {
+    if path.exists() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+
+    let output = Command::new("ssh-keygen")
+        .args(["-q", "-t", "ed25519", "-N", "", "-C", comment, "-f"])
+        .arg(path)
+        .output()
+        .map_err(|e| format!("Failed to execute ssh-keygen: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("Failed to generate SSH key: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
+
+/// Whether `address` looks like an Ethereum-style address, as C-Chain wallets have
+pub fn is_wallet_address(address: &str) -> bool {
+    address
+        .strip_prefix("0x")
+        .is_some_and(|hex| hex.len() == 40 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// The echo node from `ERYZAA_ECHO_NODE`, else the host of the first of `coordinator_urls`
+pub fn echo_node(coordinator_urls: &[String]) -> Option<String> {
+    if let Ok(node) = std::env::var(ECHO_NODE_ENV) {
+        return Some(node.trim().to_string()).filter(|node| !node.is_empty());
+    }
+    let url = coordinator_urls.first()?;
+    let authority = url.split("://").nth(1).unwrap_or(url).split('/').next()?;
+    let host = match authority.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => authority,
+    };
+    Some(format!("{}:{}", host, ECHO_PORT))
+}
+
+/// Bounce a line off the echo service at `target`; the round trip in milliseconds
+pub fn echo_test(target: &str, timeout: Duration) -> Result<u32, String> {
+    let addr = target
+        .to_socket_addrs()
+        .map_err(|e| format!("Failed to resolve {}: {}", target, e))?
+        .next()
+        .ok_or_else(|| format!("Failed to resolve {}", target))?;
+    let started = Instant::now();
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)
+        .map_err(|e| format!("Failed to connect to {}: {}", target, e))?;
+    stream.set_read_timeout(Some(timeout)).map_err(|e| format!("Failed to set timeout: {}", e))?;
+
+    let probe = format!("eryzaa-{}", uuid::Uuid::new_v4());
+    writeln!(stream, "{}", probe).map_err(|e| format!("Failed to write to {}: {}", target, e))?;
+    let mut echoed = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut echoed)
+        .map_err(|e| format!("No echo from {}: {}", target, e))?;
+    if echoed.trim_end() != probe {
+        return Err(format!("{} answered with something other than the echo", target));
+    }
+    Ok(started.elapsed().as_millis() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_onboarding_steps() {
+        let path = std::env::temp_dir().join(format!("eryzaa-client-onboarding-{}.json", std::process::id()));
+        let mut onboarding = ClientOnboarding::load_from(&path);
+        assert!(onboarding.ensure_identity());
+        assert!(!onboarding.ensure_identity());
+        onboarding.ssh_key = Some(PathBuf::from("/home/me/.ssh/eryzaa_ed25519"));
+        assert!(!onboarding.wallet_done());
+        onboarding.wallet_skipped = true;
+        assert!(!onboarding.is_complete());
+
+        assert!(is_wallet_address("0x52908400098527886E0F7030069857D2E4169EE7"));
+        assert!(!is_wallet_address("52908400098527886E0F7030069857D2E4169EE7"));
+        assert!(!is_wallet_address("0x5290"));
+        assert_eq!(echo_node(&["https://coord.example:8090/api".to_string()]), Some("coord.example:7".to_string()));
+        assert_eq!(echo_node(&[]), None);
+
+        // A local echo service stands in for the public one
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            (&stream).write_all(line.as_bytes()).unwrap();
+        });
+        let round_trip_ms = echo_test(&target, Duration::from_secs(2)).unwrap();
+        onboarding.self_test = Some(SelfTest { echo_node: target, round_trip_ms: Some(round_trip_ms), error: None, tested_at: 0 });
+        assert!(onboarding.is_complete());
+
+        onboarding.save_to(&path).unwrap();
+        assert_eq!(ClientOnboarding::load_from(&path), onboarding);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,399 @@
+//! Job output push
+//! Outputs listed in a spec are uploaded by the node straight to the client's own S3 or GCS
+//! bucket once the job succeeds, so large results never pass through the client's machine.
+//! Files larger than a part go up as multipart uploads whose finished parts are remembered on
+//! disk, so a push cut short by a network failure or a restart resumes where it stopped.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use log::{info, warn};
+
+pub use eryzaa_protocol::job::JobOutput;
+
+use crate::pipeline::container_exit_code;
+use crate::sandbox;
+use crate::staging::{self, SourceCredentials};
+use crate::{JobManager, JobStatus};
+
+/// Where unfinished uploads are remembered, one file per job
+pub const UPLOAD_STATE_DIR: &str = "/var/lib/eryzaa/uploads";
+
+/// Pushes are given up after this many failed attempts
+pub const MAX_PUSH_ATTEMPTS: u32 = 5;
+
+/// Size of each multipart part; smaller files go up in a single request
+const PART_SIZE: u64 = 64 * 1024 * 1024;
+
+/// A job's outputs and where they ended up
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct OutputPush {
+    pub outputs: Vec<JobOutput>,
+    #[serde(default)]
+    pub urls: Vec<String>, // Objects written, once the push is done
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default)]
+    pub error: Option<String>, // Why the last attempt failed
+    #[serde(default)]
+    pub done: bool,
+}
+
+impl OutputPush {
+    pub fn new(outputs: Vec<JobOutput>) -> Self {
+        Self { outputs, ..Default::default() }
+    }
+
+    /// Whether another attempt should be made
+    pub fn is_pending(&self) -> bool {
+        !self.done && self.attempts < MAX_PUSH_ATTEMPTS
+    }
+}
+
+/// A multipart upload that was started but not completed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct PartialUpload {
+    upload_id: String,
+    size: u64,                    // Of the file when the upload began; a changed file starts over
+    etags: BTreeMap<u32, String>, // Finished parts by number
+}
+
+/// What a job's push has written so far
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+struct UploadState {
+    uploaded: BTreeSet<String>, // Object URLs already complete
+    partial: BTreeMap<String, PartialUpload>,
+}
+
+impl UploadState {
+    fn path(job_id: &str) -> PathBuf {
+        Path::new(UPLOAD_STATE_DIR).join(format!("{}.json", job_id))
+    }
+
+    fn load(job_id: &str) -> Self {
+        std::fs::read_to_string(Self::path(job_id))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, job_id: &str) {
+        let path = Self::path(job_id);
+        let result = std::fs::create_dir_all(UPLOAD_STATE_DIR)
+            .and_then(|()| std::fs::write(&path, serde_json::to_string(self).unwrap_or_default()));
+        if let Err(e) = result {
+            warn!("Failed to write upload state {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// `segment` percent-encoded for use in a URL
+fn encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Files under `source` and the object each one goes to
+///
+/// A directory keeps its layout below the `target` prefix. A single file goes to `target`
+/// itself, or below it when `target` ends in '/'. Symlinks are skipped, so a job cannot push
+/// files from outside its workspace.
+fn objects(source: &Path, target: &str) -> Result<Vec<(PathBuf, String)>, String> {
+    let metadata = std::fs::symlink_metadata(source)
+        .map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+    if metadata.is_file() {
+        let name = source.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        let object = match target.ends_with('/') {
+            true => format!("{}{}", target, encode(&name)),
+            false => target.to_string(),
+        };
+        return Ok(vec![(source.to_path_buf(), object)]);
+    }
+    if !metadata.is_dir() {
+        return Err(format!("{} is neither a file nor a directory", source.display()));
+    }
+
+    let mut files = vec![];
+    collect_files(source, &mut files)?;
+    files.sort();
+    let prefix = target.trim_end_matches('/');
+    Ok(files
+        .into_iter()
+        .map(|path| {
+            let key = path
+                .strip_prefix(source)
+                .unwrap_or(&path)
+                .components()
+                .map(|part| encode(&part.as_os_str().to_string_lossy()))
+                .collect::<Vec<_>>()
+                .join("/");
+            (path, format!("{}/{}", prefix, key))
+        })
+        .collect())
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    for entry in entries.flatten() {
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() => collect_files(&entry.path(), files)?,
+            Ok(kind) if kind.is_file() => files.push(entry.path()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Text of the first `<tag>` element of an S3 XML response
+fn xml_value<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+    Some(&xml[start..end])
+}
+
+/// ETag of the last response in `curl -D -` output
+fn etag(headers: &str) -> Option<&str> {
+    headers
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .rfind(|(name, _)| name.trim().eq_ignore_ascii_case("etag"))
+        .map(|(_, value)| value.trim())
+}
+
+/// Body of the request that joins the uploaded parts into one object
+fn complete_body(etags: &BTreeMap<u32, String>) -> String {
+    let parts: String = etags
+        .iter()
+        .map(|(number, etag)| format!("<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>", number, etag))
+        .collect();
+    format!("<CompleteMultipartUpload>{}</CompleteMultipartUpload>", parts)
+}
+
+/// Run curl against `url` and return what it printed
+fn request(args: &[&str], url: &str, config: &str) -> Result<String, String> {
+    let output = staging::curl(args, url, config, Stdio::piped())?
+        .wait_with_output()
+        .map_err(|e| format!("Failed to wait for curl: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Upload `path` to `url`, resuming the multipart upload recorded in `state` if there is one
+fn upload(
+    path: &Path,
+    url: &str,
+    config: &str,
+    chunk: &Path,
+    state: &mut UploadState,
+    save: &dyn Fn(&UploadState),
+) -> Result<(), String> {
+    let size = std::fs::metadata(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+        .len();
+    if size <= PART_SIZE {
+        request(&["-T", &path.to_string_lossy()], url, config)?;
+        return Ok(());
+    }
+
+    let mut partial = match state.partial.get(url).filter(|partial| partial.size == size) {
+        Some(partial) => partial.clone(),
+        None => {
+            let response = request(&["--data-binary", ""], &format!("{}?uploads", url), config)?;
+            let upload_id = xml_value(&response, "UploadId")
+                .ok_or_else(|| format!("No upload id in response: {}", response.trim()))?;
+            PartialUpload { upload_id: upload_id.to_string(), size, etags: BTreeMap::new() }
+        }
+    };
+    let query = format!("uploadId={}", encode(&partial.upload_id));
+
+    let mut file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let chunk_arg = chunk.to_string_lossy();
+    for number in 1..=size.div_ceil(PART_SIZE) as u32 {
+        if partial.etags.contains_key(&number) {
+            continue;
+        }
+        file.seek(SeekFrom::Start(u64::from(number - 1) * PART_SIZE))
+            .and_then(|_| File::create(chunk))
+            .and_then(|mut out| std::io::copy(&mut (&mut file).take(PART_SIZE), &mut out))
+            .map_err(|e| format!("Failed to read part {} of {}: {}", number, path.display(), e))?;
+
+        let part_url = format!("{}?partNumber={}&{}", url, number, query);
+        let headers = match request(&["-D", "-", "-o", "/dev/null", "-T", &chunk_arg], &part_url, config) {
+            Ok(headers) => headers,
+            Err(e) => {
+                if e.contains("404") {
+                    // The bucket dropped the upload; start over on the next attempt
+                    state.partial.remove(url);
+                    save(state);
+                }
+                return Err(e);
+            }
+        };
+        let etag = etag(&headers).ok_or_else(|| format!("No ETag for part {}", number))?;
+        partial.etags.insert(number, etag.to_string());
+        state.partial.insert(url.to_string(), partial.clone());
+        save(state);
+    }
+    let _ = std::fs::remove_file(chunk);
+
+    let body = complete_body(&partial.etags);
+    let args = ["-H", "Content-Type: application/xml", "--data-binary", &body];
+    let response = request(&args, &format!("{}?{}", url, query), config)?;
+    // A failed completion can still arrive with status 200
+    if response.contains("<Error>") {
+        return Err(format!("Failed to complete the upload: {}", response.trim()));
+    }
+    state.partial.remove(url);
+    Ok(())
+}
+
+/// Upload every output of a job from its workspace, skipping objects a previous attempt finished
+fn push_all(job_id: &str, outputs: &[JobOutput], access_token: Option<&str>) -> Result<Vec<String>, String> {
+    let root = staging::workspace_dir(job_id)?;
+    let chunk = Path::new(UPLOAD_STATE_DIR).join(format!("{}.part", job_id));
+    let save = |state: &UploadState| state.save(job_id);
+    let mut state = UploadState::load(job_id);
+    let mut urls = vec![];
+
+    for output in outputs {
+        if !(output.target.starts_with("s3://") || output.target.starts_with("gs://")) {
+            return Err(format!("Output target {} must be an s3:// or gs:// bucket", output.target));
+        }
+        let credentials: Option<SourceCredentials> = match &output.credentials {
+            Some(sealed) => {
+                let token = access_token
+                    .ok_or_else(|| format!("Job '{}' has no access token to open its credentials", job_id))?;
+                Some(staging::open_credentials(sealed, &output.target, token, chrono::Utc::now())?)
+            }
+            None => None,
+        };
+        // Resolved so a symlinked path can't point outside the workspace
+        let source = sandbox::resolve(&root, &output.source)?;
+        if std::fs::symlink_metadata(&source).is_err() {
+            return Err(format!("Output '{}' is missing from the workspace", output.source));
+        }
+
+        for (path, object) in objects(&source, &output.target)? {
+            let (url, config) = staging::request_for(&object, credentials.as_ref())?;
+            if !state.uploaded.contains(&url) {
+                upload(&path, &url, &config, &chunk, &mut state, &save)
+                    .map_err(|e| format!("Failed to upload {}: {}", path.display(), e))?;
+                state.uploaded.insert(url.clone());
+                save(&state);
+            }
+            urls.push(url);
+        }
+    }
+
+    let _ = std::fs::remove_file(UploadState::path(job_id));
+    Ok(urls)
+}
+
+impl JobManager {
+    /// Jobs whose outputs are waiting to be pushed
+    ///
+    /// Running jobs with outputs are checked first: a container that exited cleanly marks its job
+    /// completed, and any other exit marks it failed, which leaves its outputs where they are.
+    pub fn pending_pushes(&self) -> Vec<String> {
+        for job in self.list_jobs() {
+            if job.status != JobStatus::Running || job.output_push.is_none() {
+                continue;
+            }
+            let status = match container_exit_code(&job.container_name) {
+                Ok(Some(0)) => JobStatus::Completed,
+                Ok(Some(code)) => JobStatus::Failed(format!("Exited with code {}", code)),
+                _ => continue,
+            };
+            let _ = self.update_status(&job.job_id, status);
+        }
+
+        self.list_jobs()
+            .into_iter()
+            .filter(|job| job.status == JobStatus::Completed)
+            .filter(|job| job.output_push.as_ref().is_some_and(OutputPush::is_pending))
+            .map(|job| job.job_id)
+            .collect()
+    }
+
+    /// Upload a completed job's outputs to the client's buckets, resuming an earlier attempt
+    ///
+    /// The object URLs, or the reason the attempt failed, are kept in the job's `output_push`.
+    /// The workspace is deleted once everything is uploaded.
+    pub fn push_outputs(&self, job_id: &str) -> Result<Vec<String>, String> {
+        let record = self
+            .get_job(job_id)
+            .ok_or_else(|| format!("No job found with id '{}'", job_id))?;
+        let push = record
+            .output_push
+            .ok_or_else(|| format!("Job '{}' has no outputs to push", job_id))?;
+
+        let result = push_all(job_id, &push.outputs, record.access_token.as_deref());
+        self.with_record(job_id, |record| {
+            if let Some(push) = &mut record.output_push {
+                push.attempts += 1;
+                match &result {
+                    Ok(urls) => {
+                        push.urls = urls.clone();
+                        push.error = None;
+                        push.done = true;
+                    }
+                    Err(e) => push.error = Some(e.clone()),
+                }
+            }
+        });
+
+        match &result {
+            Ok(urls) => {
+                info!("Pushed {} output objects of job '{}'", urls.len(), job_id);
+                if let Err(e) = staging::remove_workspace(job_id) {
+                    warn!("{}", e);
+                }
+            }
+            Err(e) => warn!("Failed to push the outputs of job '{}': {}", job_id, e),
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_objects_and_multipart_responses() {
+        let dir = std::env::temp_dir().join(format!("eryzaa-outputs-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("ckpt")).unwrap();
+        std::fs::write(dir.join("model.pt"), b"weights").unwrap();
+        std::fs::write(dir.join("ckpt/step 10.pt"), b"step").unwrap();
+
+        let listed = objects(&dir, "s3://bucket/run-1/").unwrap();
+        let keys: Vec<&str> = listed.iter().map(|(_, object)| object.as_str()).collect();
+        assert_eq!(keys, ["s3://bucket/run-1/ckpt/step%2010.pt", "s3://bucket/run-1/model.pt"]);
+        let single = objects(&dir.join("model.pt"), "gs://bucket/final.pt").unwrap();
+        assert_eq!(single[0].1, "gs://bucket/final.pt");
+        std::fs::remove_dir_all(&dir).ok();
+
+        let created = "<InitiateMultipartUploadResult><UploadId>abc/123+</UploadId></InitiateMultipartUploadResult>";
+        assert_eq!(xml_value(created, "UploadId"), Some("abc/123+"));
+        assert_eq!(encode("abc/123+"), "abc%2F123%2B");
+        let headers = "HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nETag: \"d41d8\"\r\n";
+        assert_eq!(etag(headers), Some("\"d41d8\""));
+
+        let etags = BTreeMap::from([(1, "\"a\"".to_string()), (2, "\"b\"".to_string())]);
+        assert_eq!(
+            complete_body(&etags),
+            "<CompleteMultipartUpload><Part><PartNumber>1</PartNumber><ETag>\"a\"</ETag></Part>\
+             <Part><PartNumber>2</PartNumber><ETag>\"b\"</ETag></Part></CompleteMultipartUpload>"
+        );
+    }
+}
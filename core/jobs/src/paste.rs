@@ -0,0 +1,145 @@
+//! Paste channel into a rented session
+//! Clipboard text or a small file goes into the tenant's ~/incoming over the job's control
+//! channel, and files there can be pulled back, without an scp round trip for every snippet.
+
+use std::io::Write;
+use std::process::Stdio;
+use log::info;
+
+use crate::exec::node_script;
+use crate::JobManager;
+
+/// Largest paste or pull; anything bigger belongs in a proper transfer
+pub const MAX_PASTE_BYTES: usize = 1 << 20;
+
+/// Directory in the session user's home that pastes land in
+pub const INCOMING_DIR: &str = "incoming";
+
+/// Pasted names are a single plain path component, so they need no quoting on the node
+pub fn validate_name(name: &str) -> Result<(), String> {
+    let plain = name.chars().all(|c| c.is_ascii_alphanumeric() || "._-".contains(c));
+    if name.is_empty() || name.len() > 128 || name.starts_with('.') || !plain {
+        return Err(format!(
+            "Invalid file name '{}': use letters, digits, '.', '_' and '-', not starting with '.'",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Name for a clipboard paste, e.g. clipboard-20260101-120000.txt
+pub fn clipboard_name(at: chrono::DateTime<chrono::Local>) -> String {
+    format!("clipboard-{}.txt", at.format("%Y%m%d-%H%M%S"))
+}
+
+impl JobManager {
+    /// Write `content` to ~/incoming/`name` in the job's session; returns the remote path
+    pub fn push_to_incoming(&self, job_id: &str, name: &str, content: &[u8]) -> Result<String, String> {
+        validate_name(name)?;
+        if content.len() > MAX_PASTE_BYTES {
+            return Err(format!(
+                "{} is {} bytes; pastes are limited to {} bytes",
+                name,
+                content.len(),
+                MAX_PASTE_BYTES
+            ));
+        }
+        let record = self
+            .get_job(job_id)
+            .ok_or_else(|| format!("No job found with id '{}'", job_id))?;
+
+        // Written under a temporary name so a dropped connection never leaves half a file
+        let path = format!("~/{}/{}", INCOMING_DIR, name);
+        let script = format!(
+            "umask 077 && mkdir -p ~/{dir} && cat > {path}.part && mv {path}.part {path}",
+            dir = INCOMING_DIR,
+            path = path
+        );
+        let mut child = node_script(&record, &script)?
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to open paste channel to job '{}': {}", job_id, e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(content)
+                .map_err(|e| format!("Failed to send {} to job '{}': {}", name, job_id, e))?;
+        }
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("Failed to paste into job '{}': {}", job_id, e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "Failed to paste into job '{}': {}",
+                job_id,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        info!("Pasted {} ({} bytes) into job '{}'", name, content.len(), job_id);
+        Ok(path)
+    }
+
+    /// Files waiting in the job session's ~/incoming
+    pub fn list_incoming(&self, job_id: &str) -> Result<Vec<String>, String> {
+        let record = self
+            .get_job(job_id)
+            .ok_or_else(|| format!("No job found with id '{}'", job_id))?;
+        let output = node_script(&record, &format!("ls -1 ~/{} 2>/dev/null || true", INCOMING_DIR))?
+            .output()
+            .map_err(|e| format!("Failed to list incoming files of job '{}': {}", job_id, e))?;
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|name| validate_name(name).is_ok() && !name.ends_with(".part"))
+            .map(|name| name.to_string())
+            .collect())
+    }
+
+    /// Read ~/incoming/`name` back from the job's session
+    pub fn pull_from_incoming(&self, job_id: &str, name: &str) -> Result<Vec<u8>, String> {
+        validate_name(name)?;
+        let record = self
+            .get_job(job_id)
+            .ok_or_else(|| format!("No job found with id '{}'", job_id))?;
+
+        // One byte past the limit tells an oversized file from one exactly at it
+        let script = format!("head -c {} ~/{}/{}", MAX_PASTE_BYTES + 1, INCOMING_DIR, name);
+        let output = node_script(&record, &script)?
+            .output()
+            .map_err(|e| format!("Failed to read {} from job '{}': {}", name, job_id, e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "Failed to read {} from job '{}': {}",
+                name,
+                job_id,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        if output.stdout.len() > MAX_PASTE_BYTES {
+            return Err(format!("{} is larger than {} bytes; copy it with scp instead", name, MAX_PASTE_BYTES));
+        }
+        Ok(output.stdout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("config.yaml").is_ok());
+        assert!(validate_name("train_v2-final.py").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name(".bashrc").is_err());
+        assert!(validate_name("../etc/passwd").is_err());
+        assert!(validate_name("a b.txt").is_err());
+        assert!(validate_name("$(reboot)").is_err());
+
+        let at = chrono::TimeZone::with_ymd_and_hms(&chrono::Local, 2026, 1, 2, 3, 4, 5).unwrap();
+        assert_eq!(clipboard_name(at), "clipboard-20260102-030405.txt");
+        assert!(validate_name(&clipboard_name(at)).is_ok());
+    }
+}
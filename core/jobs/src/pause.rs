@@ -0,0 +1,219 @@
+//! Pause and resume for running jobs
+//! A plain pause freezes the container cgroup; a checkpointed pause also stops the
+//! container so its GPU memory is released until the job is resumed.
+
+use log::{info, warn};
+use eryzaa_discovery::clock::SessionMarker;
+
+use crate::exec::docker_command;
+use crate::{JobManager, JobRecord, JobStatus};
+
+#[derive(Debug, Clone, Default)]
+pub struct PauseOptions {
+    pub release_gpu: bool, // Checkpoint the container instead of freezing it
+}
+
+#[derive(Debug, Clone)]
+pub struct PausePolicy {
+    pub max_pause: chrono::Duration, // Paused jobs are stopped after this long
+}
+
+impl Default for PausePolicy {
+    fn default() -> Self {
+        Self {
+            max_pause: chrono::Duration::hours(4),
+        }
+    }
+}
+
+impl JobManager {
+    /// Pause a running job
+    pub fn pause_job(&self, job_id: &str, options: &PauseOptions) -> Result<(), String> {
+        let record = self.running_job(job_id)?;
+
+        let checkpoint = if options.release_gpu {
+            let name = format!("pause-{}", chrono::Utc::now().timestamp());
+            run_docker(&record, &["checkpoint", "create", &record.container_name, &name])?;
+            Some(name)
+        } else {
+            run_docker(&record, &["pause", &record.container_name])?;
+            None
+        };
+
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(record) = jobs.get_mut(job_id) {
+            record.status = JobStatus::Paused;
+            record.paused_at = Some(chrono::Utc::now());
+            record.paused_marker = SessionMarker::now();
+            record.checkpoint = checkpoint;
+        }
+
+        info!("Paused job '{}'", job_id);
+        Ok(())
+    }
+
+    /// Resume a paused job, restoring from its checkpoint if it has one
+    pub fn resume_job(&self, job_id: &str) -> Result<(), String> {
+        let record = self
+            .get_job(job_id)
+            .ok_or_else(|| format!("No job found with id '{}'", job_id))?;
+
+        if record.status != JobStatus::Paused {
+            return Err(format!("Job '{}' is not paused ({:?})", job_id, record.status));
+        }
+
+        match &record.checkpoint {
+            Some(name) => run_docker(
+                &record,
+                &["start", "--checkpoint", name, &record.container_name],
+            )?,
+            None => run_docker(&record, &["unpause", &record.container_name])?,
+        }
+
+        let now = chrono::Utc::now();
+        let marker = SessionMarker::now();
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(record) = jobs.get_mut(job_id) {
+            record.paused_seconds += record.current_pause_seconds(now, marker.as_ref());
+            record.paused_at = None;
+            record.paused_marker = None;
+            record.checkpoint = None;
+            record.status = JobStatus::Running;
+        }
+
+        info!("Resumed job '{}'", job_id);
+        Ok(())
+    }
+
+    /// Stop jobs that have been paused longer than the policy allows
+    ///
+    /// Returns the ids of the jobs that were stopped.
+    pub fn enforce_pause_limit(&self, policy: &PausePolicy) -> Vec<String> {
+        let now = chrono::Utc::now();
+        let marker = SessionMarker::now();
+        let expired: Vec<JobRecord> = self
+            .list_jobs()
+            .into_iter()
+            .filter(|job| job.pause_exceeded(policy, now, marker.as_ref()))
+            .collect();
+
+        let mut stopped = Vec::new();
+        for record in expired {
+            warn!("Job '{}' exceeded the pause limit, stopping it", record.job_id);
+
+            // A frozen container must be thawed before docker will stop it
+            if record.checkpoint.is_none() {
+                let _ = run_docker(&record, &["unpause", &record.container_name]);
+            }
+            if let Err(e) = run_docker(&record, &["stop", &record.container_name]) {
+                warn!("Failed to stop job '{}': {}", record.job_id, e);
+                continue;
+            }
+
+            if let Some(job) = self.jobs.lock().unwrap().get_mut(&record.job_id) {
+                job.paused_seconds += job.current_pause_seconds(now, marker.as_ref());
+                job.paused_at = None;
+                job.paused_marker = None;
+            }
+            let _ = self.update_status(&record.job_id, JobStatus::Stopped);
+            stopped.push(record.job_id);
+        }
+
+        stopped
+    }
+
+    fn running_job(&self, job_id: &str) -> Result<JobRecord, String> {
+        let record = self
+            .get_job(job_id)
+            .ok_or_else(|| format!("No job found with id '{}'", job_id))?;
+
+        if record.status != JobStatus::Running {
+            return Err(format!("Job '{}' is not running ({:?})", job_id, record.status));
+        }
+
+        Ok(record)
+    }
+}
+
+impl JobRecord {
+    /// Whether the job has been paused longer than `policy` allows, timed like the bill so a
+    /// stepped wall clock neither stops it early nor keeps it paused
+    fn pause_exceeded(&self, policy: &PausePolicy, now: chrono::DateTime<chrono::Utc>, marker: Option<&SessionMarker>) -> bool {
+        self.status == JobStatus::Paused
+            && self.paused_at.is_some()
+            && self.current_pause_seconds(now, marker) > policy.max_pause.num_seconds().max(0) as u64
+    }
+}
+
+/// Run a non-interactive docker command against a job's node
+pub(crate) fn run_docker(record: &JobRecord, args: &[&str]) -> Result<(), String> {
+    let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+    let output = docker_command(record, &args, false)?
+        .output()
+        .map_err(|e| format!("Failed to execute docker {}: {}", args[0], e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "docker {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paused_time_is_not_billed() {
+        let now = chrono::Utc::now();
+        let mut record = JobRecord::new("abc", "client-1", "ubuntu:22.04");
+        record.created_at = now - chrono::Duration::seconds(600);
+        record.paused_seconds = 100;
+        record.paused_at = Some(now - chrono::Duration::seconds(200));
+        record.started_marker = None;
+
+        assert_eq!(record.billable_seconds(now), 300);
+
+        // Within one boot the monotonic markers win over a wall clock that was stepped
+        let marker = |uptime_secs| SessionMarker { boot_id: "boot-1".to_string(), uptime_secs };
+        record.started_marker = Some(marker(1000));
+        record.paused_marker = Some(marker(1500));
+        assert_eq!(record.billable_seconds_at(now + chrono::Duration::hours(1), Some(&marker(1600))), 400);
+        assert_eq!(record.billable_seconds_at(now, Some(&SessionMarker { boot_id: "boot-2".to_string(), uptime_secs: 5 })), 300);
+    }
+
+    #[test]
+    fn test_pause_limit_uses_monotonic_time() {
+        let now = chrono::Utc::now();
+        let policy = PausePolicy::default();
+        let marker = |boot_id: &str, uptime_secs| SessionMarker { boot_id: boot_id.to_string(), uptime_secs };
+        let mut record = JobRecord::new("abc", "client-1", "ubuntu:22.04");
+        record.status = JobStatus::Paused;
+        record.paused_at = Some(now - chrono::Duration::hours(10));
+        record.paused_marker = Some(marker("boot-1", 1000));
+
+        // The wall clock jumped ten hours, the machine only saw a minute go by
+        assert!(!record.pause_exceeded(&policy, now, Some(&marker("boot-1", 1060))));
+        assert!(record.pause_exceeded(&policy, now, Some(&marker("boot-1", 1000 + 5 * 3600))));
+        // After a reboot only the wall clock is left
+        assert!(record.pause_exceeded(&policy, now, Some(&marker("boot-2", 5))));
+
+        record.status = JobStatus::Running;
+        assert!(!record.pause_exceeded(&policy, now, Some(&marker("boot-1", 1000 + 5 * 3600))));
+    }
+
+    #[test]
+    fn test_resume_requires_paused_job() {
+        let manager = JobManager::new();
+        let mut record = JobRecord::new("abc", "client-1", "ubuntu:22.04");
+        record.status = JobStatus::Running;
+        manager.register_job(record);
+
+        assert!(manager.resume_job("abc").is_err());
+        assert!(manager.enforce_pause_limit(&PausePolicy::default()).is_empty());
+    }
+}
@@ -0,0 +1,230 @@
+//! Dedicated cores for jobs
+//! A job can ask for cores of its own. They come from a single NUMA node when one has enough
+//! free, so the job's threads stay next to their memory, and never from the cores the owner keeps
+//! for themselves in the rental settings. Docker's cpuset flags hold the container to them.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::reservation::{default_reservation_path, OwnerReservation};
+use crate::{JobManager, JobStatus};
+
+/// Where the kernel lists NUMA nodes and their cores
+const NUMA_SYSFS: &str = "/sys/devices/system/node";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumaNode {
+    pub id: u32,
+    pub cpus: Vec<u32>,
+}
+
+/// The machine's cores, grouped by NUMA node
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CpuTopology {
+    pub nodes: Vec<NumaNode>,
+}
+
+impl CpuTopology {
+    /// Read from sysfs; machines without NUMA information count as one node of every core
+    pub fn detect() -> Self {
+        let mut nodes: Vec<NumaNode> = std::fs::read_dir(NUMA_SYSFS)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|entry| {
+                        let id = entry.file_name().to_str()?.strip_prefix("node")?.parse().ok()?;
+                        let cpus = parse_cpulist(&std::fs::read_to_string(entry.path().join("cpulist")).ok()?);
+                        (!cpus.is_empty()).then_some(NumaNode { id, cpus })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        if nodes.is_empty() {
+            let count = std::thread::available_parallelism().map(|n| n.get() as u32).unwrap_or(1);
+            nodes.push(NumaNode { id: 0, cpus: (0..count).collect() });
+        }
+        nodes.sort_by_key(|node| node.id);
+        Self { nodes }
+    }
+
+    pub fn cpu_count(&self) -> usize {
+        self.nodes.iter().map(|node| node.cpus.len()).sum()
+    }
+}
+
+/// Cores in the kernel's list format, e.g. "0-3,8-11"
+pub fn parse_cpulist(list: &str) -> Vec<u32> {
+    let mut cpus = BTreeSet::new();
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        let bounds = match range.split_once('-') {
+            Some((first, last)) => first.trim().parse::<u32>().ok().zip(last.trim().parse::<u32>().ok()),
+            None => range.trim().parse::<u32>().ok().map(|cpu| (cpu, cpu)),
+        };
+        if let Some((first, last)) = bounds {
+            cpus.extend(first..=last);
+        }
+    }
+    cpus.into_iter().collect()
+}
+
+/// Cores as the kernel and docker write them, runs collapsed into ranges
+pub fn format_cpulist(cpus: &[u32]) -> String {
+    let sorted: BTreeSet<u32> = cpus.iter().copied().collect();
+    let mut ranges: Vec<(u32, u32)> = vec![];
+    for cpu in sorted {
+        match ranges.last_mut() {
+            Some((_, last)) if *last + 1 == cpu => *last = cpu,
+            _ => ranges.push((cpu, cpu)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|(first, last)| if first == last { first.to_string() } else { format!("{}-{}", first, last) })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Pinning settings of the node owner
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PinningSettings {
+    #[serde(default)]
+    pub reserved_cores: Vec<u32>, // Kept for the owner, never dedicated to a job
+}
+
+impl PinningSettings {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize pinning settings: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write pinning settings {}: {}", path.display(), e))
+    }
+}
+
+/// Cores a job was given and the NUMA nodes they are on
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CorePinning {
+    pub cpus: Vec<u32>,
+    pub numa_nodes: Vec<u32>, // Memory is allocated from these only
+}
+
+impl CorePinning {
+    /// Options for `docker run`
+    pub fn run_args(&self) -> Vec<String> {
+        vec![
+            "--cpuset-cpus".to_string(),
+            format_cpulist(&self.cpus),
+            "--cpuset-mems".to_string(),
+            format_cpulist(&self.numa_nodes),
+        ]
+    }
+}
+
+/// Pick `count` cores that are neither reserved nor `taken`
+///
+/// The fullest NUMA node that still fits the job is used, keeping larger nodes whole for larger
+/// jobs; a job no single node fits is spread over the nodes with the most free cores.
+pub fn place(topology: &CpuTopology, reserved: &[u32], taken: &[u32], count: u32) -> Result<CorePinning, String> {
+    if count == 0 {
+        return Err("A job must ask for at least one dedicated core".to_string());
+    }
+    let count = count as usize;
+    let mut free: Vec<(u32, Vec<u32>)> = topology
+        .nodes
+        .iter()
+        .map(|node| {
+            let cpus = node.cpus.iter().copied().filter(|cpu| !reserved.contains(cpu) && !taken.contains(cpu)).collect();
+            (node.id, cpus)
+        })
+        .collect();
+
+    let fitting = free.iter().filter(|(_, cpus)| cpus.len() >= count).min_by_key(|(id, cpus)| (cpus.len(), *id));
+    if let Some((id, cpus)) = fitting {
+        return Ok(CorePinning { cpus: cpus[..count].to_vec(), numa_nodes: vec![*id] });
+    }
+
+    let available: usize = free.iter().map(|(_, cpus)| cpus.len()).sum();
+    if available < count {
+        return Err(format!("Only {} of the {} cores asked for are free on this node", available, count));
+    }
+    free.sort_by_key(|(id, cpus)| (std::cmp::Reverse(cpus.len()), *id));
+    let mut pinning = CorePinning { cpus: vec![], numa_nodes: vec![] };
+    for (id, cpus) in free {
+        let wanted = count - pinning.cpus.len();
+        if wanted == 0 {
+            break;
+        }
+        if !cpus.is_empty() {
+            pinning.cpus.extend(cpus.into_iter().take(wanted));
+            pinning.numa_nodes.push(id);
+        }
+    }
+    pinning.cpus.sort_unstable();
+    pinning.numa_nodes.sort_unstable();
+    Ok(pinning)
+}
+
+/// Pinning settings saved from the rental GUI
+pub fn default_pinning_path() -> PathBuf {
+    crate::default_registry_path().with_file_name("cpu-pinning.json")
+}
+
+impl JobManager {
+    /// Dedicate `count` cores to `job_id`, avoiding the owner's and those of other running jobs
+    pub fn pin_cores(&self, job_id: &str, count: u32) -> Result<CorePinning, String> {
+        let taken: Vec<u32> = self
+            .jobs
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|record| record.job_id != job_id && matches!(record.status, JobStatus::Running | JobStatus::Pending | JobStatus::Paused))
+            .filter_map(|record| record.pinning.as_ref())
+            .flat_map(|pinning| pinning.cpus.iter().copied())
+            .collect();
+        let settings = PinningSettings::load_from(&default_pinning_path());
+        let topology = CpuTopology::detect();
+        let reserved = OwnerReservation::load_from(&default_reservation_path()).reserved_cpus(&topology, &settings.reserved_cores);
+        place(&topology, &reserved, &taken, count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_place_on_numa_nodes() {
+        assert_eq!(parse_cpulist("0-3,8-9,12\n"), vec![0, 1, 2, 3, 8, 9, 12]);
+        assert_eq!(format_cpulist(&[12, 0, 1, 2, 3, 8, 9]), "0-3,8-9,12");
+
+        let topology = CpuTopology {
+            nodes: vec![
+                NumaNode { id: 0, cpus: parse_cpulist("0-7") },
+                NumaNode { id: 1, cpus: parse_cpulist("8-15") },
+            ],
+        };
+        // The owner keeps cores 0-1, so node 0 is the fuller one and takes a small job
+        let pinning = place(&topology, &[0, 1], &[], 4).unwrap();
+        assert_eq!(pinning, CorePinning { cpus: vec![2, 3, 4, 5], numa_nodes: vec![0] });
+        assert_eq!(pinning.run_args(), vec!["--cpuset-cpus", "2-5", "--cpuset-mems", "0"]);
+        assert_eq!(place(&topology, &[0, 1], &[], 8).unwrap().numa_nodes, vec![1]);
+
+        // Nine cores fit no node, so they are spread
+        let spread = place(&topology, &[0, 1], &[2, 3], 9).unwrap();
+        assert_eq!(spread.cpus, vec![4, 8, 9, 10, 11, 12, 13, 14, 15]);
+        assert_eq!(spread.numa_nodes, vec![0, 1]);
+        assert!(place(&topology, &[0, 1], &[2, 3], 13).is_err());
+    }
+}
@@ -0,0 +1,463 @@
+//! Job pipelines
+//! A pipeline is a set of `JobSpec` stages with dependencies, run as a DAG: a stage starts once
+//! every stage it depends on has succeeded, and reads their artifacts under `/artifacts/in/<stage>`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use log::{info, warn};
+
+use crate::cache::{ReproManifest, ResultCache};
+use crate::spec::JobSpec;
+use crate::{JobManager, JobRecord, JobStatus};
+
+/// Where a stage writes the artifacts later stages receive
+pub const ARTIFACTS_OUT: &str = "/artifacts/out";
+/// Where a stage finds the artifacts of the stages it depends on, one directory per stage
+pub const ARTIFACTS_IN: &str = "/artifacts/in";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Stage {
+    pub name: String,
+    pub spec: JobSpec,
+    #[serde(default)]
+    pub after: Vec<String>, // Stages that must succeed first
+    #[serde(default)]
+    pub cache: bool, // Deterministic: identical runs may be answered from the result cache
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Pipeline {
+    pub name: String,
+    pub stages: Vec<Stage>,
+}
+
+impl Pipeline {
+    /// Parse a pipeline from YAML (or JSON, which is valid YAML)
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let pipeline: Pipeline = serde_yaml::from_str(content).map_err(|e| format!("Invalid pipeline: {}", e))?;
+        pipeline.validate()?;
+        Ok(pipeline)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read pipeline {}: {}", path.display(), e))?;
+        Self::parse(&content)
+    }
+
+    /// Check stage names are unique, dependencies exist and there are no cycles
+    pub fn validate(&self) -> Result<(), String> {
+        if self.stages.is_empty() {
+            return Err(format!("Pipeline '{}' has no stages", self.name));
+        }
+
+        let mut names = HashSet::new();
+        for stage in &self.stages {
+            if !names.insert(stage.name.as_str()) {
+                return Err(format!("Stage '{}' is defined twice", stage.name));
+            }
+        }
+        for stage in &self.stages {
+            if let Some(missing) = stage.after.iter().find(|dep| !names.contains(dep.as_str())) {
+                return Err(format!("Stage '{}' runs after unknown stage '{}'", stage.name, missing));
+            }
+        }
+
+        self.order().map(|_| ())
+    }
+
+    /// Stage names in an order that respects every dependency
+    pub fn order(&self) -> Result<Vec<String>, String> {
+        let mut remaining: BTreeMap<&str, usize> = self
+            .stages
+            .iter()
+            .map(|stage| (stage.name.as_str(), stage.after.len()))
+            .collect();
+        let mut ready: VecDeque<&str> = self
+            .stages
+            .iter()
+            .filter(|stage| stage.after.is_empty())
+            .map(|stage| stage.name.as_str())
+            .collect();
+
+        let mut order = Vec::new();
+        while let Some(name) = ready.pop_front() {
+            order.push(name.to_string());
+            for stage in self.stages.iter().filter(|s| s.after.iter().any(|dep| dep == name)) {
+                let count = remaining.get_mut(stage.name.as_str()).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    ready.push_back(&stage.name);
+                }
+            }
+        }
+
+        if order.len() != self.stages.len() {
+            return Err(format!("Pipeline '{}' has a dependency cycle", self.name));
+        }
+        Ok(order)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum StageState {
+    Waiting,
+    Running,
+    Succeeded,
+    Failed(String),
+    Skipped, // A stage it depends on did not succeed
+}
+
+impl StageState {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, StageState::Succeeded | StageState::Failed(_) | StageState::Skipped)
+    }
+}
+
+/// One submission of a pipeline and the progress of its stages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineRun {
+    pub run_id: String,
+    pub client_id: String,
+    pub pipeline: Pipeline,
+    pub states: BTreeMap<String, StageState>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub manifests: BTreeMap<String, ReproManifest>, // Cacheable stages that missed the cache
+}
+
+impl PipelineRun {
+    pub fn new(pipeline: Pipeline, client_id: &str) -> Self {
+        let states = pipeline
+            .stages
+            .iter()
+            .map(|stage| (stage.name.clone(), StageState::Waiting))
+            .collect();
+        Self {
+            run_id: uuid::Uuid::new_v4().to_string()[..8].to_string(),
+            client_id: client_id.to_string(),
+            pipeline,
+            states,
+            created_at: chrono::Utc::now(),
+            manifests: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.states.values().all(StageState::is_finished)
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.states.values().all(|state| *state == StageState::Succeeded)
+    }
+
+    /// Job id (and container name) of a stage
+    pub fn stage_job_id(&self, stage: &str) -> String {
+        format!("pipeline-{}-{}", self.run_id, stage)
+    }
+
+    /// Host directory holding a stage's artifacts
+    pub fn artifacts_dir(&self, stage: &str) -> PathBuf {
+        default_artifacts_root().join(&self.run_id).join(stage)
+    }
+
+    /// Skip waiting stages behind a failure and return the stages that can start now
+    pub fn ready_stages(&mut self) -> Vec<Stage> {
+        // Skips cascade, so repeat until nothing changes
+        loop {
+            let blocked: Vec<String> = self
+                .pipeline
+                .stages
+                .iter()
+                .filter(|stage| self.states[&stage.name] == StageState::Waiting)
+                .filter(|stage| {
+                    stage.after.iter().any(|dep| {
+                        matches!(self.states[dep], StageState::Failed(_) | StageState::Skipped)
+                    })
+                })
+                .map(|stage| stage.name.clone())
+                .collect();
+            if blocked.is_empty() {
+                break;
+            }
+            for name in blocked {
+                self.states.insert(name, StageState::Skipped);
+            }
+        }
+
+        self.pipeline
+            .stages
+            .iter()
+            .filter(|stage| self.states[&stage.name] == StageState::Waiting)
+            .filter(|stage| stage.after.iter().all(|dep| self.states[dep] == StageState::Succeeded))
+            .cloned()
+            .collect()
+    }
+
+    /// Inputs that determine a stage's output: upstream artifacts and host paths it mounts
+    ///
+    /// None when the stage mounts a named volume, whose contents cannot be hashed.
+    pub fn stage_inputs(&self, stage: &Stage) -> Option<BTreeMap<String, PathBuf>> {
+        let mut inputs: BTreeMap<String, PathBuf> = stage
+            .after
+            .iter()
+            .map(|dep| (format!("stage:{}", dep), self.artifacts_dir(dep)))
+            .collect();
+        for volume in &stage.spec.volumes {
+            let mut parts = volume.split(':');
+            let (source, target) = (parts.next()?, parts.next()?);
+            if !source.starts_with('/') {
+                return None;
+            }
+            inputs.insert(target.to_string(), PathBuf::from(source));
+        }
+        Some(inputs)
+    }
+
+    /// The spec a stage runs with: its job id as name and its artifact directories mounted
+    pub fn stage_spec(&self, stage: &Stage) -> JobSpec {
+        let mut spec = stage.spec.clone();
+        spec.name = self.stage_job_id(&stage.name);
+        spec.restart = None;
+        spec.volumes.push(format!("{}:{}", self.artifacts_dir(&stage.name).display(), ARTIFACTS_OUT));
+        for dep in &stage.after {
+            spec.volumes.push(format!("{}:{}/{}:ro", self.artifacts_dir(dep).display(), ARTIFACTS_IN, dep));
+        }
+        spec
+    }
+}
+
+/// Pipeline runs known to this node
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PipelineStore {
+    pub runs: Vec<PipelineRun>,
+}
+
+impl PipelineStore {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize pipelines: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write pipelines {}: {}", path.display(), e))
+    }
+
+    pub fn get_mut(&mut self, run_id: &str) -> Option<&mut PipelineRun> {
+        self.runs.iter_mut().find(|run| run.run_id == run_id)
+    }
+}
+
+impl JobManager {
+    /// Move a pipeline run forward: record finished stages and start the ones now ready
+    ///
+    /// Cacheable stages are answered from `cache` when an identical run already produced their
+    /// artifacts. `can_start` lets the scheduler hold a ready stage back (e.g. while GPUs cool
+    /// down); returns whether anything changed.
+    pub fn advance_pipeline(
+        &self,
+        run: &mut PipelineRun,
+        cache: Option<&ResultCache>,
+        can_start: &mut dyn FnMut(&JobSpec) -> bool,
+    ) -> Result<bool, String> {
+        let before = run.states.clone();
+
+        let running: Vec<String> = run
+            .states
+            .iter()
+            .filter(|(_, state)| **state == StageState::Running)
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in running {
+            let job_id = run.stage_job_id(&name);
+            let state = match container_exit_code(&job_id)? {
+                None => continue,
+                Some(0) => {
+                    let _ = self.update_status(&job_id, JobStatus::Completed);
+                    if let (Some(cache), Some(manifest)) = (cache, run.manifests.remove(&name)) {
+                        if let Err(e) = cache.store(&manifest, &job_id, &run.artifacts_dir(&name)) {
+                            warn!("Failed to cache stage '{}': {}", name, e);
+                        }
+                    }
+                    StageState::Succeeded
+                }
+                Some(code) => {
+                    let reason = format!("Exited with code {}", code);
+                    let _ = self.update_status(&job_id, JobStatus::Failed(reason.clone()));
+                    StageState::Failed(reason)
+                }
+            };
+            info!("Pipeline {} stage '{}': {:?}", run.run_id, name, state);
+            let _ = self.release_scratch(&job_id);
+            run.states.insert(name, state);
+        }
+
+        for stage in run.ready_stages() {
+            let out = run.artifacts_dir(&stage.name);
+            std::fs::create_dir_all(&out).map_err(|e| format!("Failed to create {}: {}", out.display(), e))?;
+
+            if let Some(cache) = cache.filter(|cache| stage.cache && cache.policy.enabled) {
+                if self.answer_from_cache(run, &stage, cache)? {
+                    continue;
+                }
+            }
+
+            let spec = run.stage_spec(&stage);
+            if !can_start(&spec) {
+                continue;
+            }
+
+            let state = match self.run_spec(&spec, &run.client_id) {
+                Ok(()) => StageState::Running,
+                Err(e) => {
+                    warn!("Pipeline {} stage '{}' failed to start: {}", run.run_id, stage.name, e);
+                    StageState::Failed(e)
+                }
+            };
+            run.states.insert(stage.name, state);
+        }
+
+        Ok(run.states != before)
+    }
+
+    /// Restore a stage's artifacts from the cache on a hit; on a miss remember its manifest
+    fn answer_from_cache(&self, run: &mut PipelineRun, stage: &Stage, cache: &ResultCache) -> Result<bool, String> {
+        let Some(inputs) = run.stage_inputs(stage) else {
+            return Ok(false);
+        };
+        let manifest = match ReproManifest::for_spec(&stage.spec, &inputs) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                warn!("Stage '{}' cannot be cached: {}", stage.name, e);
+                return Ok(false);
+            }
+        };
+
+        let Some(entry) = cache.lookup(&manifest.hash()) else {
+            run.manifests.insert(stage.name.clone(), manifest);
+            return Ok(false);
+        };
+        cache.restore(&entry, &run.artifacts_dir(&stage.name))?;
+
+        let job_id = run.stage_job_id(&stage.name);
+        let mut record = JobRecord::new(&job_id, &run.client_id, &stage.spec.image_tag());
+        record.status = JobStatus::Completed;
+        record.cached_result = Some(entry.hash.clone());
+        record.cache_fee = Some(cache.policy.cache_fee);
+        self.register_job(record);
+
+        info!("Pipeline {} stage '{}' answered from cache (job '{}')", run.run_id, stage.name, entry.job_id);
+        run.states.insert(stage.name.clone(), StageState::Succeeded);
+        Ok(true)
+    }
+
+    /// Stop a run's running stages and mark everything left as failed
+    pub fn cancel_pipeline(&self, run: &mut PipelineRun) {
+        for (name, state) in run.states.iter_mut() {
+            if state.is_finished() {
+                continue;
+            }
+            let job_id = format!("pipeline-{}-{}", run.run_id, name);
+            if *state == StageState::Running {
+                let _ = Command::new("docker").args(["rm", "-f", &job_id]).output();
+                let _ = self.update_status(&job_id, JobStatus::Stopped);
+                let _ = self.release_scratch(&job_id);
+            }
+            *state = StageState::Failed("Cancelled".to_string());
+        }
+    }
+}
+
+/// Exit code of a finished container, None while it is still running
+pub(crate) fn container_exit_code(name: &str) -> Result<Option<i32>, String> {
+    let output = Command::new("docker")
+        .args(["inspect", "-f", "{{.State.Status}} {{.State.ExitCode}}", name])
+        .output()
+        .map_err(|e| format!("Failed to execute docker: {}", e))?;
+    if !output.status.success() {
+        // The container is gone; treat it like a crash
+        return Ok(Some(-1));
+    }
+
+    let state = String::from_utf8_lossy(&output.stdout);
+    let mut fields = state.split_whitespace();
+    match (fields.next(), fields.next().and_then(|code| code.parse().ok())) {
+        (Some("exited" | "dead"), Some(code)) => Ok(Some(code)),
+        _ => Ok(None),
+    }
+}
+
+/// Pipeline runs of the active profile, next to the job registry
+pub fn default_store_path() -> PathBuf {
+    crate::default_registry_path().with_file_name("pipelines.json")
+}
+
+fn default_artifacts_root() -> PathBuf {
+    crate::default_registry_path().with_file_name("artifacts")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIPELINE: &str = r#"
+name: train-resnet
+stages:
+  - name: prep
+    spec: { name: prep, image: "python:3.11", command: ["python", "prep.py"] }
+  - name: train-a
+    after: [prep]
+    spec: { name: train-a, image: "pytorch/pytorch", gpus: "1" }
+  - name: train-b
+    after: [prep]
+    spec: { name: train-b, image: "pytorch/pytorch", gpus: "1" }
+  - name: evaluate
+    after: [train-a, train-b]
+    spec: { name: evaluate, image: "python:3.11" }
+"#;
+
+    #[test]
+    fn test_fan_out_fan_in() {
+        let pipeline = Pipeline::parse(PIPELINE).unwrap();
+        assert_eq!(pipeline.order().unwrap(), vec!["prep", "train-a", "train-b", "evaluate"]);
+
+        let mut run = PipelineRun::new(pipeline, "client-1");
+        let ready: Vec<String> = run.ready_stages().into_iter().map(|s| s.name).collect();
+        assert_eq!(ready, vec!["prep"]);
+
+        run.states.insert("prep".to_string(), StageState::Succeeded);
+        assert_eq!(run.ready_stages().len(), 2);
+
+        let evaluate = run.pipeline.stages[3].clone();
+        let spec = run.stage_spec(&evaluate);
+        assert_eq!(spec.name, format!("pipeline-{}-evaluate", run.run_id));
+        assert!(spec.volumes.iter().any(|v| v.ends_with(":/artifacts/in/train-b:ro")));
+
+        run.states.insert("train-a".to_string(), StageState::Succeeded);
+        run.states.insert("train-b".to_string(), StageState::Failed("Exited with code 1".to_string()));
+        assert!(run.ready_stages().is_empty());
+        assert_eq!(run.states["evaluate"], StageState::Skipped);
+        assert!(run.is_finished() && !run.succeeded());
+    }
+
+    #[test]
+    fn test_validation() {
+        let cyclic = PIPELINE.replace("after: [prep]\n    spec: { name: train-a", "after: [evaluate]\n    spec: { name: train-a");
+        assert!(Pipeline::parse(&cyclic).unwrap_err().contains("cycle"));
+
+        let unknown = PIPELINE.replace("after: [train-a, train-b]", "after: [train-c]");
+        assert!(Pipeline::parse(&unknown).unwrap_err().contains("unknown stage 'train-c'"));
+    }
+}
@@ -0,0 +1,252 @@
+//! Port allocation for tenant services
+//! Host ports are leased from a configured range, published on the job container and
+//! recorded in the job record so clients know where to reach the service.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::TcpListener;
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex};
+use log::{info, warn};
+
+use crate::{JobManager, JobRecord};
+
+/// Default range leased to tenant services
+pub const DEFAULT_PORT_RANGE: RangeInclusive<u16> = 20000..=20999;
+
+/// Environment variable overriding the leased range, e.g. "30000-30099"
+pub const PORT_RANGE_ENV: &str = "ERYZAA_PORT_RANGE";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PortMapping {
+    pub host_port: u16,
+    pub container_port: u16,
+}
+
+impl std::fmt::Display for PortMapping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} → {}", self.host_port, self.container_port)
+    }
+}
+
+/// Leases host ports to job containers
+pub struct PortAllocator {
+    range: RangeInclusive<u16>,
+    leases: Arc<Mutex<HashMap<u16, String>>>, // host port -> job id
+}
+
+impl PortAllocator {
+    pub fn new(range: RangeInclusive<u16>) -> Self {
+        Self {
+            range,
+            leases: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Parse a range such as "20000-20999"
+    pub fn from_range_str(range: &str) -> Result<Self, String> {
+        let (start, end) = range
+            .split_once('-')
+            .ok_or_else(|| format!("Invalid port range '{}', expected START-END", range))?;
+        let start: u16 = start.trim().parse().map_err(|_| format!("Invalid port '{}'", start))?;
+        let end: u16 = end.trim().parse().map_err(|_| format!("Invalid port '{}'", end))?;
+
+        if start == 0 || start > end {
+            return Err(format!("Invalid port range '{}'", range));
+        }
+
+        Ok(Self::new(start..=end))
+    }
+
+    /// The node's allocator, holding the leases recorded on `jobs`
+    pub fn for_node(jobs: &[JobRecord]) -> Result<Self, String> {
+        let allocator = match std::env::var(PORT_RANGE_ENV) {
+            Ok(range) => Self::from_range_str(&range)?,
+            Err(_) => Self::default(),
+        };
+        allocator.restore(jobs);
+        Ok(allocator)
+    }
+
+    /// Re-establish leases for jobs that already have mappings (e.g. after a restart)
+    pub fn restore(&self, jobs: &[JobRecord]) {
+        let mut leases = self.leases.lock().unwrap();
+        for job in jobs {
+            for mapping in &job.port_mappings {
+                leases.insert(mapping.host_port, job.job_id.clone());
+            }
+        }
+    }
+
+    /// Lease one host port per container port for a job
+    pub fn allocate(&self, job_id: &str, container_ports: &[u16]) -> Result<Vec<PortMapping>, String> {
+        let mut leases = self.leases.lock().unwrap();
+        let mut mappings = Vec::new();
+
+        for &container_port in container_ports {
+            let host_port = self
+                .range
+                .clone()
+                .find(|port| !leases.contains_key(port) && port_is_free(*port));
+
+            match host_port {
+                Some(host_port) => {
+                    leases.insert(host_port, job_id.to_string());
+                    mappings.push(PortMapping { host_port, container_port });
+                }
+                None => {
+                    // Roll back what we leased so far
+                    for mapping in &mappings {
+                        leases.remove(&mapping.host_port);
+                    }
+                    return Err(format!(
+                        "No free ports left in {}-{}",
+                        self.range.start(),
+                        self.range.end()
+                    ));
+                }
+            }
+        }
+
+        Ok(mappings)
+    }
+
+    /// Release every port leased to a job
+    pub fn release_job(&self, job_id: &str) -> Vec<u16> {
+        let mut leases = self.leases.lock().unwrap();
+        let released: Vec<u16> = leases
+            .iter()
+            .filter(|(_, owner)| owner.as_str() == job_id)
+            .map(|(port, _)| *port)
+            .collect();
+
+        for port in &released {
+            leases.remove(port);
+        }
+
+        released
+    }
+
+    /// Current leases as (host port, job id)
+    pub fn leases(&self) -> Vec<(u16, String)> {
+        let mut leases: Vec<(u16, String)> = self
+            .leases
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(port, job)| (*port, job.clone()))
+            .collect();
+        leases.sort();
+        leases
+    }
+}
+
+impl Default for PortAllocator {
+    fn default() -> Self {
+        Self::new(DEFAULT_PORT_RANGE)
+    }
+}
+
+/// `docker run` arguments that publish the given mappings
+pub fn docker_publish_args(mappings: &[PortMapping]) -> Vec<String> {
+    mappings
+        .iter()
+        .flat_map(|m| vec!["-p".to_string(), format!("{}:{}", m.host_port, m.container_port)])
+        .collect()
+}
+
+impl JobManager {
+    /// Lease ports for a job's services and record the mappings on the job
+    pub fn allocate_ports(
+        &self,
+        job_id: &str,
+        container_ports: &[u16],
+        allocator: &PortAllocator,
+    ) -> Result<Vec<PortMapping>, String> {
+        if self.get_job(job_id).is_none() {
+            return Err(format!("No job found with id '{}'", job_id));
+        }
+
+        let mappings = allocator.allocate(job_id, container_ports)?;
+
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(record) = jobs.get_mut(job_id) {
+            record.port_mappings.extend(mappings.iter().cloned());
+        }
+
+        info!("Leased ports for job '{}': {:?}", job_id, mappings);
+        Ok(mappings)
+    }
+
+    /// Remove a job's container, release its ports and forget the job
+    pub fn teardown_job(&self, job_id: &str, allocator: &PortAllocator) -> Result<JobRecord, String> {
+        let record = self
+            .get_job(job_id)
+            .ok_or_else(|| format!("No job found with id '{}'", job_id))?;
+
+        if let Err(e) = crate::pause::run_docker(&record, &["rm", "-f", &record.container_name]) {
+            warn!("Failed to remove container for job '{}': {}", job_id, e);
+        }
+
+        let released = allocator.release_job(job_id);
+        info!("Released ports {:?} for job '{}'", released, job_id);
+
+        self.remove_job(job_id)
+            .ok_or_else(|| format!("No job found with id '{}'", job_id))
+    }
+}
+
+fn port_is_free(port: u16) -> bool {
+    TcpListener::bind(("0.0.0.0", port)).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocate_and_release() {
+        let allocator = PortAllocator::new(40100..=40109);
+        let first = allocator.allocate("job-a", &[8888, 6006]).unwrap();
+        let second = allocator.allocate("job-b", &[8888]).unwrap();
+
+        assert_eq!(first.len(), 2);
+        assert!(first.iter().all(|m| m.host_port != second[0].host_port));
+
+        let released = allocator.release_job("job-a");
+        assert_eq!(released.len(), 2);
+        assert_eq!(allocator.leases().len(), 1);
+    }
+
+    #[test]
+    fn test_exhausted_range_rolls_back() {
+        let allocator = PortAllocator::new(40110..=40110);
+        assert!(allocator.allocate("job-a", &[80, 443]).is_err());
+        assert!(allocator.leases().is_empty());
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert!(PortAllocator::from_range_str("20000-20999").is_ok());
+        assert!(PortAllocator::from_range_str("20999-20000").is_err());
+        assert!(PortAllocator::from_range_str("nope").is_err());
+    }
+
+    #[test]
+    fn test_stopping_a_job_releases_its_ports() {
+        let manager = JobManager::new();
+        let mut record = JobRecord::new("web", "client-1", "nginx");
+        record.port_mappings = vec![PortMapping { host_port: 40120, container_port: 80 }];
+        manager.register_job(record);
+
+        let allocator = PortAllocator::new(40120..=40121);
+        allocator.restore(&manager.list_jobs());
+        assert_eq!(allocator.leases(), vec![(40120, "web".to_string())]);
+
+        manager.update_status("web", crate::JobStatus::Stopped).unwrap();
+        assert!(manager.get_job("web").unwrap().port_mappings.is_empty());
+        let allocator = PortAllocator::new(40120..=40121);
+        allocator.restore(&manager.list_jobs());
+        assert!(allocator.leases().is_empty());
+    }
+}
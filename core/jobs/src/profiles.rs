@@ -0,0 +1,265 @@
+//! Client profiles
+//! Each profile has its own SSH key, balance, job history and node bookmarks, kept under
+//! ~/.eryzaa/profiles/<name>, so separate billing contexts never mix.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use log::info;
+
+/// Profile used when none has been created; its data lives directly in ~/.eryzaa
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Overrides the active profile for a single process
+pub const PROFILE_ENV: &str = "ERYZAA_PROFILE";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NodeBookmark {
+    pub node_id: String,
+    pub label: String,
+    pub address: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    #[serde(default)]
+    pub organization: Option<String>,
+    #[serde(default)]
+    pub wallet_address: Option<String>,
+    #[serde(default)]
+    pub balance: f64, // Last known wallet balance in AVAX
+    #[serde(default)]
+    pub bookmarks: Vec<NodeBookmark>,
+    #[serde(default)]
+    pub workspace_dir: Option<PathBuf>, // Where workspace snapshots go, e.g. a synced folder
+}
+
+impl Profile {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            organization: None,
+            wallet_address: None,
+            balance: 0.0,
+            bookmarks: vec![],
+            workspace_dir: None,
+        }
+    }
+
+    /// Directory holding this profile's job registry and handoffs
+    pub fn data_dir(&self) -> PathBuf {
+        if self.name == DEFAULT_PROFILE {
+            eryzaa_home()
+        } else {
+            eryzaa_home().join("profiles").join(&self.name)
+        }
+    }
+
+    /// SSH key used for jobs started under this profile
+    pub fn identity_file(&self, ssh_dir: &Path) -> PathBuf {
+        if self.name == DEFAULT_PROFILE {
+            ssh_dir.join("eryzaa_ed25519")
+        } else {
+            ssh_dir.join(format!("eryzaa_{}_profile_ed25519", self.name))
+        }
+    }
+
+    /// Add a node bookmark, replacing any existing one for the same node
+    pub fn bookmark(&mut self, bookmark: NodeBookmark) {
+        self.bookmarks.retain(|b| b.node_id != bookmark.node_id);
+        self.bookmarks.push(bookmark);
+    }
+
+    pub fn remove_bookmark(&mut self, node_id: &str) -> bool {
+        let before = self.bookmarks.len();
+        self.bookmarks.retain(|b| b.node_id != node_id);
+        self.bookmarks.len() != before
+    }
+}
+
+/// The set of profiles on this machine and which one is active
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileStore {
+    active: String,
+    profiles: Vec<Profile>,
+}
+
+impl Default for ProfileStore {
+    fn default() -> Self {
+        Self {
+            active: DEFAULT_PROFILE.to_string(),
+            profiles: vec![Profile::new(DEFAULT_PROFILE)],
+        }
+    }
+}
+
+impl ProfileStore {
+    /// Load the machine's profiles (~/.eryzaa/profiles.json)
+    pub fn load() -> Result<Self, String> {
+        Self::load_from(&default_store_path())
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read profiles {}: {}", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse profiles {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        self.save_to(&default_store_path())
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize profiles: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write profiles {}: {}", path.display(), e))
+    }
+
+    pub fn list(&self) -> &[Profile] {
+        &self.profiles
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Profile> {
+        self.profiles.iter_mut().find(|p| p.name == name)
+    }
+
+    /// The active profile, honouring ERYZAA_PROFILE when it names a known profile
+    pub fn active(&self) -> &Profile {
+        let name = std::env::var(PROFILE_ENV).unwrap_or_else(|_| self.active.clone());
+        self.get(&name)
+            .or_else(|| self.get(&self.active))
+            .unwrap_or(&self.profiles[0])
+    }
+
+    pub fn active_mut(&mut self) -> &mut Profile {
+        let name = self.active().name.clone();
+        self.get_mut(&name).expect("active profile exists")
+    }
+
+    /// Add a new profile
+    pub fn create(&mut self, profile: Profile) -> Result<(), String> {
+        validate_name(&profile.name)?;
+        if self.get(&profile.name).is_some() {
+            return Err(format!("Profile '{}' already exists", profile.name));
+        }
+
+        info!("Created profile '{}'", profile.name);
+        self.profiles.push(profile);
+        Ok(())
+    }
+
+    /// Make `name` the active profile
+    pub fn switch(&mut self, name: &str) -> Result<(), String> {
+        if self.get(name).is_none() {
+            return Err(format!("No profile named '{}'", name));
+        }
+
+        self.active = name.to_string();
+        info!("Switched to profile '{}'", name);
+        Ok(())
+    }
+
+    /// Remove a profile; its data directory is left on disk
+    pub fn remove(&mut self, name: &str) -> Result<Profile, String> {
+        if name == DEFAULT_PROFILE {
+            return Err("The default profile cannot be removed".to_string());
+        }
+        if name == self.active {
+            return Err(format!("Profile '{}' is active, switch to another one first", name));
+        }
+
+        let index = self
+            .profiles
+            .iter()
+            .position(|p| p.name == name)
+            .ok_or_else(|| format!("No profile named '{}'", name))?;
+        Ok(self.profiles.remove(index))
+    }
+}
+
+/// Root of Eryzaa's client state (~/.eryzaa)
+pub fn eryzaa_home() -> PathBuf {
+    let home = std::env::var("HOME")
+        .or_else(|_| std::env::var("USERPROFILE"))
+        .unwrap_or_else(|_| ".".to_string());
+    PathBuf::from(home).join(".eryzaa")
+}
+
+/// Default location of the profile list (~/.eryzaa/profiles.json)
+pub fn default_store_path() -> PathBuf {
+    eryzaa_home().join("profiles.json")
+}
+
+/// The active profile on this machine, falling back to the default one
+pub fn active_profile() -> Profile {
+    ProfileStore::load()
+        .map(|store| store.active().clone())
+        .unwrap_or_else(|_| Profile::new(DEFAULT_PROFILE))
+}
+
+fn validate_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.len() <= 32
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid profile name '{}': use letters, digits, '-' or '_'", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_switch_remove() {
+        let mut store = ProfileStore::default();
+        let mut work = Profile::new("work");
+        work.organization = Some("Acme".to_string());
+        store.create(work).unwrap();
+        assert!(store.create(Profile::new("work")).is_err());
+        assert!(store.create(Profile::new("../etc")).is_err());
+
+        store.switch("work").unwrap();
+        assert!(store.remove("work").is_err());
+        assert!(store.remove(DEFAULT_PROFILE).is_err());
+
+        store.switch(DEFAULT_PROFILE).unwrap();
+        assert_eq!(store.remove("work").unwrap().organization.as_deref(), Some("Acme"));
+    }
+
+    #[test]
+    fn test_profiles_keep_separate_data() {
+        let default = Profile::new(DEFAULT_PROFILE);
+        let work = Profile::new("work");
+        assert_eq!(default.data_dir(), eryzaa_home());
+        assert_eq!(work.data_dir(), eryzaa_home().join("profiles").join("work"));
+        assert_ne!(default.identity_file(Path::new("/s")), work.identity_file(Path::new("/s")));
+    }
+
+    #[test]
+    fn test_bookmark_replaces_same_node() {
+        let mut profile = Profile::new("work");
+        profile.bookmark(NodeBookmark { node_id: "n1".to_string(), label: "old".to_string(), address: None });
+        profile.bookmark(NodeBookmark { node_id: "n1".to_string(), label: "gpu box".to_string(), address: None });
+        assert_eq!(profile.bookmarks.len(), 1);
+        assert_eq!(profile.bookmarks[0].label, "gpu box");
+        assert!(profile.remove_bookmark("n1"));
+    }
+}
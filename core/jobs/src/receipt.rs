@@ -0,0 +1,189 @@
+//! Job receipts
+//! A receipt summarises what a job consumed: billable run time, estimated energy and, when
+//! the node's renter set a grid carbon intensity, the grams of CO2 behind that energy.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use log::warn;
+
+use crate::scratch::StorageClass;
+use crate::{JobManager, JobRecord};
+
+/// Where a rental node publishes each job's receipt for its tenant to read
+pub const PUBLISH_DIR: &str = "/var/lib/eryzaa/receipts";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JobReceipt {
+    pub job_id: String,
+    pub client_id: String,
+    pub node_id: Option<String>,
+    pub image: String,
+    pub billable_seconds: u64,
+    pub energy_kwh: f64,
+    pub carbon_intensity_g_per_kwh: Option<f32>,
+    #[serde(default)]
+    pub cache_fee: Option<f32>, // Set when the job was answered from the result cache
+    #[serde(default)]
+    pub scratch: Option<ScratchMeter>,
+    pub issued_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Scratch disk usage, billed apart from run time since the disk is held while paused too
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScratchMeter {
+    pub class: StorageClass,
+    pub size_gb: u32,
+    pub gb_hours: f64,
+}
+
+impl JobReceipt {
+    /// Grams of CO2 for the job's energy, if the node reports a carbon intensity
+    pub fn carbon_g(&self) -> Option<f64> {
+        self.carbon_intensity_g_per_kwh
+            .map(|intensity| self.energy_kwh * intensity as f64)
+    }
+}
+
+impl std::fmt::Display for JobReceipt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Receipt for job {}", self.job_id)?;
+        writeln!(f, "  Image:    {}", self.image)?;
+        writeln!(f, "  Node:     {}", self.node_id.as_deref().unwrap_or("-"))?;
+        writeln!(
+            f,
+            "  Runtime:  {}h {:02}m",
+            self.billable_seconds / 3600,
+            (self.billable_seconds % 3600) / 60
+        )?;
+        if let Some(fee) = self.cache_fee {
+            writeln!(f, "  Cached:   answered from the result cache, fee {:.2}", fee)?;
+        }
+        if let Some(scratch) = &self.scratch {
+            writeln!(
+                f,
+                "  Scratch:  {} GB {:?}, {:.1} GB-hours",
+                scratch.size_gb, scratch.class, scratch.gb_hours
+            )?;
+        }
+        writeln!(f, "  Energy:   {:.3} kWh", self.energy_kwh)?;
+        match self.carbon_g() {
+            Some(grams) => write!(f, "  Carbon:   {}", format_carbon(grams)),
+            None => write!(f, "  Carbon:   not reported by node"),
+        }
+    }
+}
+
+impl JobRecord {
+    /// Receipt for the job as of `now`
+    pub fn receipt(&self, now: chrono::DateTime<chrono::Utc>) -> JobReceipt {
+        JobReceipt {
+            job_id: self.job_id.clone(),
+            client_id: self.client_id.clone(),
+            node_id: self.node_id.clone(),
+            image: self.image.clone(),
+            billable_seconds: self.billable_seconds(now),
+            energy_kwh: self.energy_wh / 1000.0,
+            carbon_intensity_g_per_kwh: self.carbon_intensity_g_per_kwh,
+            cache_fee: self.cache_fee,
+            scratch: self.scratch.as_ref().map(|disk| ScratchMeter {
+                class: disk.class,
+                size_gb: disk.size_gb,
+                gb_hours: disk.gb_hours(now),
+            }),
+            issued_at: now,
+        }
+    }
+}
+
+impl JobManager {
+    /// Add estimated energy to a job and stamp the node's current carbon intensity on it
+    pub fn add_energy(&self, job_id: &str, wh: f64, carbon_intensity_g_per_kwh: Option<f32>) -> Result<(), String> {
+        let mut jobs = self.jobs.lock().unwrap();
+        match jobs.get_mut(job_id) {
+            Some(record) => {
+                record.energy_wh += wh;
+                record.carbon_intensity_g_per_kwh = carbon_intensity_g_per_kwh;
+                Ok(())
+            }
+            None => Err(format!("No job found with id '{}'", job_id)),
+        }
+    }
+
+    /// Write every job's receipt to `<dir>/<job_id>.json` for its tenant
+    pub fn publish_receipts(&self, dir: &Path) -> Result<(), String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+        let now = chrono::Utc::now();
+        for record in self.list_jobs() {
+            let path = dir.join(format!("{}.json", record.job_id));
+            let content = serde_json::to_string(&record.receipt(now))
+                .map_err(|e| format!("Failed to serialize receipt: {}", e))?;
+            std::fs::write(&path, content)
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+
+    /// Fetch the receipt a job's node has published and copy its energy figures locally
+    pub fn fetch_receipt(&self, job_id: &str) -> Result<Option<JobReceipt>, String> {
+        let record = self
+            .get_job(job_id)
+            .ok_or_else(|| format!("No job found with id '{}'", job_id))?;
+
+        let path = format!("{}/{}.json", PUBLISH_DIR, job_id);
+        let output = crate::exec::node_command(&record, "cat", &[path], false)?
+            .output()
+            .map_err(|e| format!("Failed to read receipt for job '{}': {}", job_id, e))?;
+        if !output.status.success() {
+            warn!("No receipt published for job '{}'", job_id);
+            return Ok(None);
+        }
+
+        let receipt: JobReceipt = serde_json::from_slice(&output.stdout)
+            .map_err(|e| format!("Failed to parse receipt for job '{}': {}", job_id, e))?;
+        if let Some(local) = self.jobs.lock().unwrap().get_mut(job_id) {
+            local.energy_wh = receipt.energy_kwh * 1000.0;
+            local.carbon_intensity_g_per_kwh = receipt.carbon_intensity_g_per_kwh;
+        }
+        Ok(Some(receipt))
+    }
+
+    /// Average energy of the jobs on this machine, in kWh
+    pub fn average_job_kwh(&self) -> Option<f32> {
+        let jobs: Vec<JobRecord> = self.list_jobs().into_iter().filter(|job| job.energy_wh > 0.0).collect();
+        if jobs.is_empty() {
+            return None;
+        }
+        let total: f64 = jobs.iter().map(|job| job.energy_wh).sum();
+        Some((total / jobs.len() as f64 / 1000.0) as f32)
+    }
+}
+
+/// Human-readable CO2 mass, e.g. "850 g" or "1.2 kg"
+pub fn format_carbon(grams: f64) -> String {
+    if grams >= 1000.0 {
+        format!("{:.1} kg CO2", grams / 1000.0)
+    } else {
+        format!("{:.0} g CO2", grams)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_receipt_carbon() {
+        let manager = JobManager::new();
+        manager.register_job(JobRecord::new("abc", "client-1", "ubuntu:22.04"));
+        manager.add_energy("abc", 1500.0, Some(400.0)).unwrap();
+        manager.add_energy("abc", 500.0, Some(400.0)).unwrap();
+
+        let receipt = manager.get_job("abc").unwrap().receipt(chrono::Utc::now());
+        assert_eq!(receipt.energy_kwh, 2.0);
+        assert_eq!(receipt.carbon_g(), Some(800.0));
+        assert_eq!(format_carbon(800.0), "800 g CO2");
+        assert_eq!(manager.average_job_kwh(), Some(2.0));
+        assert!(manager.add_energy("missing", 1.0, None).is_err());
+    }
+}
@@ -0,0 +1,264 @@
+//! Requirements analysis before submission
+//! What a spec needs (image and dataset size, GPUs, GPU memory, CUDA, specific hardware) is
+//! measured on the client and compared with a node's advertised capabilities, so a job that would
+//! run out of memory or disk, or lacks an NVIDIA runtime, is flagged before it is sent.
+
+use std::path::Path;
+use std::process::Command;
+
+use eryzaa_protocol::{HardwareConstraints, NodeAdvertisement, NodeCapabilities};
+
+use crate::cuda;
+use crate::spec::JobSpec;
+
+/// Share of a node's disk a job may take before it is called tight
+const DISK_HEADROOM: f32 = 0.8;
+
+/// What a spec needs from the node that runs it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JobNeeds {
+    pub image: String,
+    pub image_gb: Option<f32>, // None when the image could not be inspected
+    pub dataset_gb: f32,       // Declared dataset plus what the volumes hold
+    pub scratch_gb: u32,
+    pub gpus: u32,
+    pub vram_gb: Option<u32>, // Per GPU
+    pub dedicated_cores: u32,
+    pub cuda: Option<String>, // CUDA version the image is built for
+    pub hardware: Option<HardwareConstraints>,
+}
+
+impl JobNeeds {
+    /// Measure a spec: the image through docker, volume sources on disk
+    pub fn measure(spec: &JobSpec) -> Self {
+        let image = spec.image_tag();
+        let volumes_gb: f32 = spec
+            .volumes
+            .iter()
+            .filter_map(|volume| volume.split(':').next().filter(|source| source.starts_with('/')))
+            .map(|source| dir_size(Path::new(source)) as f32 / 1e9)
+            .sum();
+        let mut needs = Self::declared(spec);
+        needs.image_gb = image_size_gb(&image);
+        needs.cuda = cuda::image_requirement(&image);
+        needs.dataset_gb += volumes_gb;
+        needs
+    }
+
+    /// Only what the spec states, without touching docker or the disk
+    pub fn declared(spec: &JobSpec) -> Self {
+        Self {
+            image: spec.image_tag(),
+            image_gb: None,
+            dataset_gb: spec.dataset_gb.unwrap_or(0.0),
+            scratch_gb: spec.scratch.as_ref().map(|s| s.size_gb).unwrap_or(0),
+            gpus: match spec.gpus.as_deref() {
+                Some("all") => 1,
+                Some(count) => count.parse().unwrap_or(0),
+                None => 0,
+            },
+            vram_gb: spec.vram_gb,
+            dedicated_cores: spec.dedicated_cores.unwrap_or(0),
+            cuda: None,
+            hardware: spec.hardware.clone(),
+        }
+    }
+
+    /// Disk the job takes on the node: image, data and scratch
+    pub fn disk_gb(&self) -> f32 {
+        self.image_gb.unwrap_or(0.0) + self.dataset_gb + self.scratch_gb as f32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning, // Likely to hurt, e.g. a tight disk
+    Blocker, // The job cannot run there
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mark = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Blocker => "blocker",
+        };
+        write!(f, "{}: {}", mark, self.message)
+    }
+}
+
+/// Everything that speaks against running the job on a node with `capabilities`
+pub fn analyze(needs: &JobNeeds, capabilities: &NodeCapabilities) -> Vec<Finding> {
+    let mut findings = vec![];
+    let mut flag = |severity, message: String| findings.push(Finding { severity, message });
+
+    if !capabilities.supports_docker {
+        flag(Severity::Blocker, "Node does not run Docker".to_string());
+    }
+
+    if needs.gpus > 0 {
+        if !capabilities.supports_gpu || capabilities.gpu_count == 0 {
+            flag(Severity::Blocker, format!("Job needs {} GPU(s); node has none", needs.gpus));
+        } else if capabilities.gpu_count < needs.gpus {
+            flag(Severity::Blocker, format!("Job needs {} GPUs; node has {}", needs.gpus, capabilities.gpu_count));
+        }
+        if capabilities.supports_gpu && capabilities.gpu_stack.is_none() {
+            flag(Severity::Blocker, "Node advertises no NVIDIA driver, so containers get no GPU runtime".to_string());
+        }
+        if let Some(vram) = needs.vram_gb {
+            if capabilities.supports_gpu && capabilities.gpu_memory_gb < vram {
+                flag(
+                    Severity::Warning,
+                    format!("Job needs {} GB of GPU memory; node's GPUs have {} GB, likely out of memory", vram, capabilities.gpu_memory_gb),
+                );
+            }
+        }
+        if let Err(reason) = cuda::check(&needs.image, needs.cuda.as_deref(), capabilities.gpu_stack.as_ref()) {
+            flag(Severity::Blocker, reason);
+        }
+    }
+
+    if let Some(constraints) = &needs.hardware {
+        for reason in constraints.unmet(&capabilities.hardware) {
+            flag(Severity::Blocker, format!("Job {}", reason));
+        }
+    }
+
+    if needs.dedicated_cores > capabilities.cpu_cores {
+        flag(Severity::Blocker, format!("Job needs {} dedicated cores; node has {}", needs.dedicated_cores, capabilities.cpu_cores));
+    }
+
+    let disk = needs.disk_gb();
+    let available = capabilities.disk_space_gb as f32;
+    if disk > available {
+        flag(Severity::Blocker, format!("Job needs {:.1} GB of disk; node has {} GB", disk, capabilities.disk_space_gb));
+    } else if disk > available * DISK_HEADROOM {
+        flag(Severity::Warning, format!("Job needs {:.1} GB of the node's {} GB disk, which may be partly used", disk, capabilities.disk_space_gb));
+    }
+    if needs.dataset_gb > capabilities.memory_gb as f32 {
+        flag(
+            Severity::Warning,
+            format!("Dataset of {:.1} GB exceeds the node's {} GB of RAM; loading it whole will run out of memory", needs.dataset_gb, capabilities.memory_gb),
+        );
+    }
+
+    findings
+}
+
+/// Nodes nothing blocks the job on, fewest warnings first, then cheapest
+pub fn suggest<'a>(needs: &JobNeeds, nodes: &'a [NodeAdvertisement]) -> Vec<&'a NodeAdvertisement> {
+    let mut fitting: Vec<(usize, &NodeAdvertisement)> = nodes
+        .iter()
+        .filter_map(|node| {
+            let findings = analyze(needs, &node.capabilities);
+            (!findings.iter().any(|f| f.severity == Severity::Blocker)).then_some((findings.len(), node))
+        })
+        .collect();
+    fitting.sort_by(|(a_warnings, a), (b_warnings, b)| {
+        a_warnings
+            .cmp(b_warnings)
+            .then(a.price_per_hour.unwrap_or(0.0).total_cmp(&b.price_per_hour.unwrap_or(0.0)))
+    });
+    fitting.into_iter().map(|(_, node)| node).collect()
+}
+
+/// Uncompressed size of a local image, else the compressed size of its registry manifest
+fn image_size_gb(image: &str) -> Option<f32> {
+    let local = Command::new("docker")
+        .args(["image", "inspect", "--format", "{{.Size}}", image])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8_lossy(&output.stdout).trim().parse::<u64>().ok());
+    if let Some(bytes) = local {
+        return Some(bytes as f32 / 1e9);
+    }
+
+    let output = Command::new("docker").args(["manifest", "inspect", "-v", image]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let manifest: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+    // Multi-platform images list one manifest per platform; the first is close enough
+    let manifest = manifest.as_array().and_then(|all| all.first()).unwrap_or(&manifest);
+    let layers = manifest.pointer("/SchemaV2Manifest/layers")?.as_array()?;
+    let bytes: u64 = layers.iter().filter_map(|layer| layer["size"].as_u64()).sum();
+    Some(bytes as f32 / 1e9)
+}
+
+/// Bytes under `path`, following no symlinks
+fn dir_size(path: &Path) -> u64 {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    std::fs::read_dir(path)
+        .map(|entries| entries.flatten().map(|entry| dir_size(&entry.path())).sum())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eryzaa_protocol::{GpuDevice, GpuStack, HardwareProfile};
+
+    #[test]
+    fn test_analyze_against_capabilities() {
+        let spec = JobSpec {
+            name: "train".to_string(),
+            image: Some("pytorch/pytorch:2.1.0-cuda12.1-cudnn8-runtime".to_string()),
+            gpus: Some("2".to_string()),
+            vram_gb: Some(40),
+            dataset_gb: Some(300.0),
+            ..Default::default()
+        };
+        let mut needs = JobNeeds::declared(&spec);
+        needs.image_gb = Some(8.0);
+        needs.cuda = cuda::tag_requirement(&needs.image);
+
+        let roomy = NodeCapabilities {
+            cpu_cores: 64,
+            memory_gb: 512,
+            gpu_count: 4,
+            gpu_memory_gb: 80,
+            disk_space_gb: 2000,
+            network_speed_mbps: 10000,
+            supports_docker: true,
+            supports_gpu: true,
+            max_concurrent_jobs: 4,
+            gpu_stack: Some(GpuStack { driver_version: "535.104".to_string(), cuda_version: Some("12.2".to_string()), cudnn_version: None }),
+            hardware: HardwareProfile::default(),
+        };
+        assert!(analyze(&needs, &roomy).is_empty());
+
+        let small = NodeCapabilities {
+            memory_gb: 64,
+            gpu_count: 1,
+            gpu_memory_gb: 24,
+            disk_space_gb: 320,
+            gpu_stack: Some(GpuStack { driver_version: "470.82".to_string(), cuda_version: Some("11.4".to_string()), cudnn_version: None }),
+            ..roomy.clone()
+        };
+        let findings = analyze(&needs, &small);
+        let severities: Vec<Severity> = findings.iter().map(|f| f.severity).collect();
+        // Too few GPUs, too little VRAM, CUDA too old, tight disk, dataset above RAM
+        assert_eq!(severities, vec![Severity::Blocker, Severity::Warning, Severity::Blocker, Severity::Warning, Severity::Warning]);
+
+        let no_runtime = NodeCapabilities { gpu_stack: None, ..roomy.clone() };
+        assert!(analyze(&needs, &no_runtime)[0].message.contains("NVIDIA"));
+        assert_eq!(needs.disk_gb(), 308.0);
+
+        needs.hardware = Some(HardwareConstraints { gpu_models: vec!["A100".to_string()], ..Default::default() });
+        assert_eq!(analyze(&needs, &roomy)[0].message, "Job needs a GPU matching A100");
+        let a100 = GpuDevice { model: "NVIDIA A100-SXM4-80GB".to_string(), memory_mb: 81920, compute_capability: Some("8.0".to_string()) };
+        let hardware = HardwareProfile { gpus: vec![a100; 4], ..Default::default() };
+        assert!(analyze(&needs, &NodeCapabilities { hardware, ..roomy }).is_empty());
+    }
+}
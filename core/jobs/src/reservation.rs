@@ -0,0 +1,210 @@
+//! Resources the owner keeps for themselves
+//! A rental that is also someone's desktop holds back some cores, memory and GPUs. They are left
+//! out of what the node advertises, job containers run in a tenant slice whose cgroup limits
+//! leave them free, and job users' sessions get the same limits on their own slices.
+
+use eryzaa_protocol::{HardwareProfile, NodeCapabilities};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::pinning::{format_cpulist, CpuTopology};
+
+/// Parent cgroup of every job container while a reservation is set
+pub const TENANT_SLICE: &str = "eryzaa-tenants.slice";
+
+/// What the owner keeps, from the rental settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct OwnerReservation {
+    #[serde(default)]
+    pub cores: u32, // At least this many cores, counting those kept from dedicated cores
+    #[serde(default)]
+    pub memory_gb: u32,
+    #[serde(default)]
+    pub gpus: Vec<u32>, // nvidia-smi indices; integrated GPUs are never passed to jobs anyway
+}
+
+impl OwnerReservation {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize owner reservation: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write owner reservation {}: {}", path.display(), e))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cores == 0 && self.memory_gb == 0 && self.gpus.is_empty()
+    }
+
+    /// Cores tenants may not use: `kept` for dedicated cores, topped up from the highest-numbered
+    pub fn reserved_cpus(&self, topology: &CpuTopology, kept: &[u32]) -> Vec<u32> {
+        let all: Vec<u32> = topology.nodes.iter().flat_map(|node| node.cpus.iter().copied()).collect();
+        let mut reserved: BTreeSet<u32> = kept.iter().copied().filter(|cpu| all.contains(cpu)).collect();
+        for cpu in all.iter().rev() {
+            if reserved.len() >= self.cores as usize {
+                break;
+            }
+            reserved.insert(*cpu);
+        }
+        reserved.into_iter().collect()
+    }
+
+    /// `capabilities` as advertised, without what the owner keeps
+    pub fn advertised(&self, capabilities: &NodeCapabilities, reserved_cpus: usize) -> NodeCapabilities {
+        let gpus_kept = self.gpus.iter().filter(|index| **index < capabilities.gpu_count).count() as u32;
+        let gpu_count = capabilities.gpu_count.saturating_sub(gpus_kept);
+        NodeCapabilities {
+            cpu_cores: capabilities.cpu_cores.saturating_sub(reserved_cpus as u32),
+            memory_gb: capabilities.memory_gb.saturating_sub(self.memory_gb),
+            gpu_count,
+            gpu_memory_gb: if gpu_count == 0 { 0 } else { capabilities.gpu_memory_gb },
+            supports_gpu: capabilities.supports_gpu && gpu_count > 0,
+            gpu_stack: capabilities.gpu_stack.clone().filter(|_| gpu_count > 0),
+            hardware: HardwareProfile {
+                gpus: capabilities
+                    .hardware
+                    .gpus
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| !self.gpus.contains(&(*index as u32)))
+                    .map(|(_, gpu)| gpu.clone())
+                    .collect(),
+                ..capabilities.hardware.clone()
+            },
+            ..capabilities.clone()
+        }
+    }
+
+    /// systemd properties of the tenant slice on a machine with `memory_mb` of memory
+    pub fn slice_properties(&self, topology: &CpuTopology, reserved_cpus: &[u32], memory_mb: Option<u64>) -> Vec<String> {
+        let mut properties = vec![];
+        let tenant_cpus: Vec<u32> = topology
+            .nodes
+            .iter()
+            .flat_map(|node| node.cpus.iter().copied())
+            .filter(|cpu| !reserved_cpus.contains(cpu))
+            .collect();
+        if !reserved_cpus.is_empty() && !tenant_cpus.is_empty() {
+            properties.push(format!("AllowedCPUs={}", format_cpulist(&tenant_cpus)));
+        }
+        if let Some(total) = memory_mb.filter(|_| self.memory_gb > 0) {
+            let kept = u64::from(self.memory_gb) * 1024;
+            properties.push(format!("MemoryMax={}M", total.saturating_sub(kept).max(1024)));
+        }
+        properties
+    }
+
+    /// The `--gpus` value for a job asking for `requested`, leaving out the owner's GPUs
+    ///
+    /// `available` are the GPUs the machine has; Docker's own value is kept when none are kept.
+    pub fn gpu_request(&self, requested: &str, available: &[u32]) -> Result<String, String> {
+        if self.gpus.is_empty() {
+            return Ok(requested.to_string());
+        }
+        let free: Vec<String> = available.iter().filter(|index| !self.gpus.contains(index)).map(u32::to_string).collect();
+        let wanted = match requested {
+            "all" => free.len(),
+            count => count.parse().map_err(|_| format!("Unsupported GPU request '{}'", requested))?,
+        };
+        if wanted == 0 || wanted > free.len() {
+            return Err(format!("Only {} GPUs are free for jobs; the owner keeps the rest", free.len()));
+        }
+        // Docker splits the value on commas unless the device list is quoted
+        Ok(format!("\"device={}\"", free[..wanted].join(",")))
+    }
+
+    /// Apply the limits to the tenant slice and return the options that put a container in it
+    pub fn confine(&self, topology: &CpuTopology, reserved_cpus: &[u32]) -> Result<Vec<String>, String> {
+        if self.is_empty() {
+            return Ok(vec![]);
+        }
+        let properties = self.slice_properties(topology, reserved_cpus, machine_memory_mb());
+        if !properties.is_empty() {
+            let output = Command::new("systemctl")
+                .args(["set-property", "--runtime", TENANT_SLICE])
+                .args(&properties)
+                .output()
+                .map_err(|e| format!("Failed to execute systemctl: {}", e))?;
+            if !output.status.success() {
+                return Err(format!("Failed to limit {}: {}", TENANT_SLICE, String::from_utf8_lossy(&output.stderr).trim()));
+            }
+        }
+        Ok(vec!["--cgroup-parent".to_string(), TENANT_SLICE.to_string()])
+    }
+}
+
+/// Memory of this machine in MB, from /proc/meminfo
+pub fn machine_memory_mb() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let total = meminfo.lines().find_map(|line| line.strip_prefix("MemTotal:"))?;
+    let kb: u64 = total.trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kb / 1024)
+}
+
+/// Indices of the machine's NVIDIA GPUs
+pub fn gpu_indices() -> Vec<u32> {
+    Command::new("nvidia-smi")
+        .args(["--query-gpu=index", "--format=csv,noheader"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).lines().filter_map(|line| line.trim().parse().ok()).collect())
+        .unwrap_or_default()
+}
+
+/// Reservation saved from the rental GUI
+pub fn default_reservation_path() -> PathBuf {
+    crate::default_registry_path().with_file_name("owner-reservation.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinning::{parse_cpulist, NumaNode};
+
+    #[test]
+    fn test_carve_out_for_owner() {
+        let topology = CpuTopology { nodes: vec![NumaNode { id: 0, cpus: parse_cpulist("0-7") }] };
+        let reservation = OwnerReservation { cores: 2, memory_gb: 8, gpus: vec![1] };
+        // Core 0 is kept from dedicated cores already, so only one more is taken
+        let reserved = reservation.reserved_cpus(&topology, &[0]);
+        assert_eq!(reserved, vec![0, 7]);
+        assert_eq!(
+            reservation.slice_properties(&topology, &reserved, Some(32768)),
+            vec!["AllowedCPUs=1-6", "MemoryMax=24576M"]
+        );
+
+        let capabilities = NodeCapabilities {
+            cpu_cores: 8,
+            memory_gb: 32,
+            gpu_count: 2,
+            gpu_memory_gb: 24,
+            disk_space_gb: 1000,
+            network_speed_mbps: 1000,
+            supports_docker: true,
+            supports_gpu: true,
+            max_concurrent_jobs: 4,
+            gpu_stack: None,
+            hardware: HardwareProfile::default(),
+        };
+        let advertised = reservation.advertised(&capabilities, reserved.len());
+        assert_eq!((advertised.cpu_cores, advertised.memory_gb, advertised.gpu_count), (6, 24, 1));
+
+        assert_eq!(reservation.gpu_request("all", &[0, 1]), Ok("\"device=0\"".to_string()));
+        assert!(reservation.gpu_request("2", &[0, 1]).is_err());
+        assert_eq!(OwnerReservation::default().gpu_request("all", &[0, 1]), Ok("all".to_string()));
+    }
+}
@@ -0,0 +1,312 @@
+//! Rolling fleet updates
+//! Nodes are updated a few at a time. Each one is drained so no new jobs start, waits for its
+//! tenant jobs to finish, runs the update, and has to report healthy before it takes jobs again.
+//! A node that stays unhealthy is rolled back, and the rollout stops before the next node.
+
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+/// Rental server binary on the nodes, which answers `drain` and `health`
+pub const REMOTE_BINARY: &str = "rental";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FleetNode {
+    pub address: String,
+    pub ssh_user: Option<String>,
+}
+
+impl FleetNode {
+    /// Parse `[user@]address`
+    pub fn parse(target: &str) -> Result<Self, String> {
+        let (ssh_user, address) = match target.split_once('@') {
+            Some((user, address)) => (Some(user.to_string()), address),
+            None => (None, target),
+        };
+        if address.is_empty() {
+            return Err(format!("Node '{}' has no address", target));
+        }
+        Ok(Self { address: address.to_string(), ssh_user })
+    }
+
+    pub fn destination(&self) -> String {
+        match &self.ssh_user {
+            Some(user) => format!("{}@{}", user, self.address),
+            None => self.address.clone(),
+        }
+    }
+}
+
+/// What `rental health` reports
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct NodeHealth {
+    pub draining: bool,
+    pub running_jobs: usize, // Tenant jobs only
+    pub unhealthy_services: Vec<String>,
+}
+
+impl NodeHealth {
+    pub fn is_healthy(&self) -> bool {
+        self.unhealthy_services.is_empty()
+    }
+}
+
+/// What the rollout asks of a node
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeCommand {
+    Drain(bool),
+    Health,
+    Run(String), // Shell command, e.g. the update
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RolloutPlan {
+    pub update_command: String,
+    pub rollback_command: Option<String>, // Run on a node that fails its health check
+    pub max_unavailable: usize,           // Nodes out of service at once
+    pub drain_timeout_secs: u64,          // Longest wait for tenant jobs to finish
+    pub health_timeout_secs: u64,         // Longest wait for an updated node to report healthy
+}
+
+impl Default for RolloutPlan {
+    fn default() -> Self {
+        Self {
+            update_command: String::new(),
+            rollback_command: None,
+            max_unavailable: 1,
+            drain_timeout_secs: 3600,
+            health_timeout_secs: 300,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Phase {
+    Waiting,
+    Draining,                  // No new jobs; waiting for running ones to finish
+    Verifying,                 // Updated; waiting for the node to report healthy
+    VerifyingRollback(String), // Rolled back after the update failed for this reason
+    Done,
+    RolledBack(String),
+    Failed(String), // Left drained for the operator, unless it never stopped taking jobs
+}
+
+impl Phase {
+    /// Out of service while in this phase
+    pub fn is_unavailable(&self) -> bool {
+        matches!(self, Phase::Draining | Phase::Verifying | Phase::VerifyingRollback(_))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeRollout {
+    pub node: FleetNode,
+    pub phase: Phase,
+    pub since: u64, // Unix seconds the phase began
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rollout {
+    pub plan: RolloutPlan,
+    pub nodes: Vec<NodeRollout>,
+    pub halted: Option<String>, // Why no further node is started
+}
+
+impl Rollout {
+    pub fn new(plan: RolloutPlan, nodes: Vec<FleetNode>) -> Self {
+        Self {
+            plan,
+            nodes: nodes.into_iter().map(|node| NodeRollout { node, phase: Phase::Waiting, since: 0 }).collect(),
+            halted: None,
+        }
+    }
+
+    pub fn unavailable(&self) -> usize {
+        self.nodes.iter().filter(|n| n.phase.is_unavailable()).count()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.unavailable() == 0 && (self.halted.is_some() || self.nodes.iter().all(|n| n.phase == Phase::Done))
+    }
+
+    /// Move every node along one step, talking to them through `control`
+    pub fn advance(&mut self, now: u64, control: &mut dyn FnMut(&FleetNode, &NodeCommand) -> Result<String, String>) {
+        for i in 0..self.nodes.len() {
+            let node = self.nodes[i].node.clone();
+            let elapsed = now.saturating_sub(self.nodes[i].since);
+            let next = match &self.nodes[i].phase {
+                Phase::Waiting => {
+                    if self.halted.is_some() || self.unavailable() >= self.plan.max_unavailable.max(1) {
+                        continue;
+                    }
+                    match control(&node, &NodeCommand::Drain(true)) {
+                        Ok(_) => Phase::Draining,
+                        Err(e) => self.halt(&node, Phase::Failed(format!("Could not drain: {}", e))),
+                    }
+                }
+                Phase::Draining => match health(&node, control) {
+                    Ok(health) if health.running_jobs == 0 => match control(&node, &NodeCommand::Run(self.plan.update_command.clone())) {
+                        Ok(_) => Phase::Verifying,
+                        Err(e) => self.roll_back(&node, format!("Update failed: {}", e), control),
+                    },
+                    result if elapsed > self.plan.drain_timeout_secs => {
+                        // Nothing changed on the node, so it goes back into service
+                        let _ = control(&node, &NodeCommand::Drain(false));
+                        let reason = match result {
+                            Ok(health) => format!("{} jobs still running after {} s", health.running_jobs, elapsed),
+                            Err(e) => e,
+                        };
+                        self.halt(&node, Phase::Failed(reason))
+                    }
+                    _ => continue,
+                },
+                Phase::Verifying => match health(&node, control) {
+                    Ok(health) if health.is_healthy() => match control(&node, &NodeCommand::Drain(false)) {
+                        Ok(_) => Phase::Done,
+                        Err(e) => self.halt(&node, Phase::Failed(format!("Updated but could not lift the drain: {}", e))),
+                    },
+                    result if elapsed > self.plan.health_timeout_secs => {
+                        let reason = match result {
+                            Ok(health) => format!("Unhealthy after the update: {}", health.unhealthy_services.join(", ")),
+                            Err(e) => format!("No health report after the update: {}", e),
+                        };
+                        self.roll_back(&node, reason, control)
+                    }
+                    _ => continue,
+                },
+                Phase::VerifyingRollback(reason) => {
+                    let reason = reason.clone();
+                    match health(&node, control) {
+                        Ok(health) if health.is_healthy() => match control(&node, &NodeCommand::Drain(false)) {
+                            Ok(_) => Phase::RolledBack(reason),
+                            Err(e) => Phase::Failed(format!("{}; rolled back but could not lift the drain: {}", reason, e)),
+                        },
+                        _ if elapsed > self.plan.health_timeout_secs => Phase::Failed(format!("{}; still unhealthy after the rollback", reason)),
+                        _ => continue,
+                    }
+                }
+                Phase::Done | Phase::RolledBack(_) | Phase::Failed(_) => continue,
+            };
+            self.nodes[i].phase = next;
+            self.nodes[i].since = now;
+        }
+    }
+
+    fn halt(&mut self, node: &FleetNode, phase: Phase) -> Phase {
+        if let Phase::Failed(reason) | Phase::RolledBack(reason) | Phase::VerifyingRollback(reason) = &phase {
+            self.halted.get_or_insert_with(|| format!("{}: {}", node.address, reason));
+        }
+        phase
+    }
+
+    fn roll_back(
+        &mut self,
+        node: &FleetNode,
+        reason: String,
+        control: &mut dyn FnMut(&FleetNode, &NodeCommand) -> Result<String, String>,
+    ) -> Phase {
+        let phase = match &self.plan.rollback_command {
+            Some(command) => match control(node, &NodeCommand::Run(command.clone())) {
+                Ok(_) => Phase::VerifyingRollback(reason),
+                Err(e) => Phase::Failed(format!("{}; rollback failed: {}", reason, e)),
+            },
+            None => Phase::Failed(format!("{}; no rollback command", reason)),
+        };
+        self.halt(node, phase)
+    }
+}
+
+/// Ask `node` for its health report
+pub fn health(node: &FleetNode, control: &mut dyn FnMut(&FleetNode, &NodeCommand) -> Result<String, String>) -> Result<NodeHealth, String> {
+    let report = control(node, &NodeCommand::Health)?;
+    serde_json::from_str(report.trim()).map_err(|e| format!("Unreadable health report: {}", e))
+}
+
+/// Carry out `command` on `node` over SSH through its rental binary
+pub fn ssh_control(node: &FleetNode, command: &NodeCommand) -> Result<String, String> {
+    let remote = match command {
+        NodeCommand::Drain(on) => format!("{} drain {}", REMOTE_BINARY, if *on { "on" } else { "off" }),
+        NodeCommand::Health => format!("{} health", REMOTE_BINARY),
+        NodeCommand::Run(script) => script.clone(),
+    };
+    let output = Command::new("ssh")
+        .args(["-o", "BatchMode=yes", "-o", "ConnectTimeout=10"])
+        .arg(node.destination())
+        .arg(&remote)
+        .output()
+        .map_err(|e| format!("Failed to run ssh: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("'{}' failed: {}", remote, stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_rolling_update_with_rollback() {
+        let nodes: Vec<FleetNode> = ["a", "root@b", "c"].iter().map(|n| FleetNode::parse(n).unwrap()).collect();
+        let plan = RolloutPlan {
+            update_command: "eryzaa-update 2.0".to_string(),
+            rollback_command: Some("eryzaa-update 1.9".to_string()),
+            health_timeout_secs: 60,
+            ..Default::default()
+        };
+        let mut rollout = Rollout::new(plan, nodes);
+
+        // a has one job left; b breaks on 2.0
+        let jobs_on_a = Cell::new(1);
+        let mut version: HashMap<String, &str> = HashMap::new();
+        let mut log = vec![];
+        let mut control = |node: &FleetNode, command: &NodeCommand| -> Result<String, String> {
+            log.push(format!("{} {:?}", node.address, command));
+            match command {
+                NodeCommand::Health => {
+                    let broken = node.address == "b" && version.get("b") == Some(&"2.0");
+                    let health = NodeHealth {
+                        draining: true,
+                        running_jobs: if node.address == "a" { jobs_on_a.get() } else { 0 },
+                        unhealthy_services: if broken { vec!["sshd".to_string()] } else { vec![] },
+                    };
+                    Ok(serde_json::to_string(&health).unwrap())
+                }
+                NodeCommand::Run(script) => {
+                    version.insert(node.address.clone(), if script.ends_with("2.0") { "2.0" } else { "1.9" });
+                    Ok(String::new())
+                }
+                NodeCommand::Drain(_) => Ok(String::new()),
+            }
+        };
+
+        rollout.advance(0, &mut control);
+        assert_eq!(rollout.nodes[0].phase, Phase::Draining);
+        assert_eq!(rollout.nodes[1].phase, Phase::Waiting); // max_unavailable 1
+        rollout.advance(10, &mut control);
+        assert_eq!(rollout.nodes[0].phase, Phase::Draining);
+
+        jobs_on_a.set(0);
+        rollout.advance(20, &mut control);
+        assert_eq!(rollout.nodes[0].phase, Phase::Verifying);
+        rollout.advance(30, &mut control);
+        assert_eq!(rollout.nodes[0].phase, Phase::Done);
+        assert_eq!(rollout.nodes[1].phase, Phase::Draining);
+
+        rollout.advance(40, &mut control);
+        rollout.advance(50, &mut control);
+        assert_eq!(rollout.nodes[1].phase, Phase::Verifying);
+        rollout.advance(200, &mut control);
+        assert!(matches!(rollout.nodes[1].phase, Phase::VerifyingRollback(_)));
+        rollout.advance(210, &mut control);
+        assert!(matches!(&rollout.nodes[1].phase, Phase::RolledBack(reason) if reason.contains("sshd")));
+
+        // The rollout stopped before c
+        assert_eq!(rollout.nodes[2].phase, Phase::Waiting);
+        assert!(rollout.is_finished());
+        assert!(rollout.halted.as_deref().unwrap().starts_with("b: "));
+        assert!(!log.iter().any(|line| line.starts_with("c ")));
+    }
+}
@@ -0,0 +1,343 @@
+//! Workspace path policy
+//! Input targets and output sources in a job spec are strings from the client, and the job's name
+//! becomes its workspace directory. Each one must stay inside the workspace: relative, made of
+//! plain names, and not leading out through a symlink the job or an earlier input left there.
+//! Paths are resolved against the workspace on disk before anything is read. Writes go through
+//! `WorkspaceFile`, which walks the path one directory at a time instead, so a symlink swapped in
+//! after a check is never followed.
+
+use std::fmt;
+use std::fs::File;
+use std::path::{Component, Path, PathBuf};
+
+use crate::JobSpec;
+
+/// Why a path from a job spec was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathViolation {
+    Empty,
+    Absolute(String),
+    Traversal(String),     // Has `..`, `.` or a prefix instead of plain names
+    SymlinkEscape(String), // Resolves outside the workspace
+    InvalidJobName(String),
+    Unresolvable { path: String, reason: String },
+}
+
+impl fmt::Display for PathViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathViolation::Empty => write!(f, "Workspace paths must not be empty"),
+            PathViolation::Absolute(path) => write!(f, "'{}' must be a relative path inside the workspace", path),
+            PathViolation::Traversal(path) => write!(f, "'{}' must not leave the workspace", path),
+            PathViolation::SymlinkEscape(path) => write!(f, "'{}' leads out of the workspace through a symlink", path),
+            PathViolation::InvalidJobName(name) => write!(f, "Job name '{}' can't name a workspace", name),
+            PathViolation::Unresolvable { path, reason } => write!(f, "Failed to resolve '{}': {}", path, reason),
+        }
+    }
+}
+
+impl std::error::Error for PathViolation {}
+
+impl From<PathViolation> for String {
+    fn from(violation: PathViolation) -> Self {
+        violation.to_string()
+    }
+}
+
+/// `relative` as a path, if it is made of plain names only
+pub fn check_relative(relative: &str) -> Result<&Path, PathViolation> {
+    let path = Path::new(relative);
+    if relative.is_empty() {
+        return Err(PathViolation::Empty);
+    }
+    if path.has_root() {
+        return Err(PathViolation::Absolute(relative.to_string()));
+    }
+    match path.components().all(|part| matches!(part, Component::Normal(_))) {
+        true => Ok(path),
+        false => Err(PathViolation::Traversal(relative.to_string())),
+    }
+}
+
+/// Check that `name` is one plain path component, so its workspace is a child of the root
+pub fn check_job_name(name: &str) -> Result<(), PathViolation> {
+    let mut parts = Path::new(name).components();
+    match (parts.next(), parts.next()) {
+        (Some(Component::Normal(_)), None) if !name.contains(['/', '\\']) => Ok(()),
+        _ => Err(PathViolation::InvalidJobName(name.to_string())),
+    }
+}
+
+/// Check every path `spec` would have the node write or read, before anything is staged
+pub fn check_spec(spec: &JobSpec) -> Result<(), PathViolation> {
+    if spec.inputs.is_empty() && spec.outputs.is_empty() {
+        return Ok(());
+    }
+    check_job_name(&spec.name)?;
+    for input in &spec.inputs {
+        check_relative(&input.target)?;
+    }
+    for output in &spec.outputs {
+        check_relative(&output.source)?;
+    }
+    Ok(())
+}
+
+/// Host path of `relative` inside the existing directory `workspace`, with symlinks resolved
+///
+/// The part of the path that already exists is resolved and must stay inside the workspace; the
+/// rest is appended as given. The path itself need not exist.
+pub fn resolve(workspace: &Path, relative: &str) -> Result<PathBuf, PathViolation> {
+    let unresolvable = |path: &Path, e: std::io::Error| PathViolation::Unresolvable { path: path.display().to_string(), reason: e.to_string() };
+    let relative_path = check_relative(relative)?;
+    let root = workspace.canonicalize().map_err(|e| unresolvable(workspace, e))?;
+    let path = root.join(relative_path);
+    let existing = path
+        .ancestors()
+        .find(|ancestor| std::fs::symlink_metadata(ancestor).is_ok())
+        .unwrap_or(&root);
+    let resolved = match existing.canonicalize() {
+        Ok(resolved) => resolved,
+        // A dangling symlink points at something that may be created later, anywhere
+        Err(_) if existing != root => return Err(PathViolation::SymlinkEscape(relative.to_string())),
+        Err(e) => return Err(unresolvable(existing, e)),
+    };
+    if !resolved.starts_with(&root) {
+        return Err(PathViolation::SymlinkEscape(relative.to_string()));
+    }
+    Ok(resolved.join(path.strip_prefix(existing).unwrap_or(Path::new(""))))
+}
+
+/// A file to write inside a workspace, reached through a handle on the directory holding it
+///
+/// On Unix each directory on the way is opened with O_NOFOLLOW, relative to the one before and
+/// created when missing, and files are created relative to the last one, also with O_NOFOLLOW.
+/// What the path names when the file is written is then what was checked, whatever the job
+/// changes in its workspace meanwhile.
+pub struct WorkspaceFile {
+    dir: sys::Dir,
+    dir_path: PathBuf, // For messages; never opened again
+    name: String,
+}
+
+impl WorkspaceFile {
+    /// The file at `relative` inside the existing directory `workspace`, creating the directories
+    /// leading to it; symlinks on the way are refused, even ones that stay inside
+    pub fn open(workspace: &Path, relative: &str) -> Result<Self, PathViolation> {
+        let relative_path = check_relative(relative)?;
+        let names: Vec<String> = relative_path.iter().map(|name| name.to_string_lossy().into_owned()).collect();
+        let (name, parents) = names.split_last().ok_or(PathViolation::Empty)?;
+        let unresolvable = |path: &Path, e: std::io::Error| PathViolation::Unresolvable { path: path.display().to_string(), reason: e.to_string() };
+
+        let mut dir_path = workspace.canonicalize().map_err(|e| unresolvable(workspace, e))?;
+        let mut dir = sys::open_root(&dir_path).map_err(|e| unresolvable(&dir_path, e))?;
+        for parent in parents {
+            let path = dir_path.join(parent);
+            // Looked at again only to word the error
+            dir = sys::open_dir(&dir, parent).map_err(|e| match std::fs::symlink_metadata(&path) {
+                Ok(metadata) if metadata.file_type().is_symlink() => PathViolation::SymlinkEscape(relative.to_string()),
+                _ => unresolvable(&path, e),
+            })?;
+            dir_path.push(parent);
+        }
+        Ok(Self { dir, dir_path, name: name.clone() })
+    }
+
+    /// Host path of the file, for messages
+    pub fn path(&self) -> PathBuf {
+        self.dir_path.join(&self.name)
+    }
+
+    /// Create or truncate the file's sibling with `suffix` appended to its name, e.g. ".part"
+    pub fn create(&self, suffix: &str) -> Result<File, String> {
+        let name = format!("{}{}", self.name, suffix);
+        sys::create_file(&self.dir, &name).map_err(|e| format!("Failed to create {}: {}", self.dir_path.join(&name).display(), e))
+    }
+
+    /// Move the sibling written through `create(suffix)` over the file
+    pub fn commit(&self, suffix: &str) -> Result<(), String> {
+        sys::rename(&self.dir, &format!("{}{}", self.name, suffix), &self.name)
+            .map_err(|e| format!("Failed to move {} into place: {}", self.path().display(), e))
+    }
+
+    /// Delete the sibling written through `create(suffix)`, if there is one
+    pub fn discard(&self, suffix: &str) {
+        let _ = sys::remove(&self.dir, &format!("{}{}", self.name, suffix));
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::ffi::CString;
+    use std::fs::File;
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd};
+    use std::path::Path;
+
+    pub type Dir = File;
+
+    pub fn open_root(path: &Path) -> io::Result<Dir> {
+        File::open(path)
+    }
+
+    fn c_name(name: &str) -> io::Result<CString> {
+        CString::new(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    fn open_at(dir: &File, name: &str, flags: libc::c_int) -> io::Result<File> {
+        let name = c_name(name)?;
+        let fd = unsafe { libc::openat(dir.as_raw_fd(), name.as_ptr(), flags | libc::O_NOFOLLOW | libc::O_CLOEXEC, 0o644 as libc::c_uint) };
+        match fd {
+            -1 => Err(io::Error::last_os_error()),
+            fd => Ok(unsafe { File::from_raw_fd(fd) }),
+        }
+    }
+
+    /// The directory `name` inside `dir`, created when missing
+    pub fn open_dir(dir: &File, name: &str) -> io::Result<File> {
+        match open_at(dir, name, libc::O_RDONLY | libc::O_DIRECTORY) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let c_name = c_name(name)?;
+                if unsafe { libc::mkdirat(dir.as_raw_fd(), c_name.as_ptr(), 0o755) } == -1 {
+                    let e = io::Error::last_os_error();
+                    if e.kind() != io::ErrorKind::AlreadyExists {
+                        return Err(e);
+                    }
+                }
+                open_at(dir, name, libc::O_RDONLY | libc::O_DIRECTORY)
+            }
+            opened => opened,
+        }
+    }
+
+    pub fn create_file(dir: &File, name: &str) -> io::Result<File> {
+        open_at(dir, name, libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC)
+    }
+
+    /// renameat replaces a symlink at `to` rather than following it
+    pub fn rename(dir: &File, from: &str, to: &str) -> io::Result<()> {
+        let (from, to) = (c_name(from)?, c_name(to)?);
+        match unsafe { libc::renameat(dir.as_raw_fd(), from.as_ptr(), dir.as_raw_fd(), to.as_ptr()) } {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn remove(dir: &File, name: &str) -> io::Result<()> {
+        let name = c_name(name)?;
+        match unsafe { libc::unlinkat(dir.as_raw_fd(), name.as_ptr(), 0) } {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Without openat each directory is checked, then used by path
+#[cfg(not(unix))]
+mod sys {
+    use std::fs::File;
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    pub type Dir = PathBuf;
+
+    pub fn open_root(path: &Path) -> io::Result<Dir> {
+        Ok(path.to_path_buf())
+    }
+
+    pub fn open_dir(dir: &Dir, name: &str) -> io::Result<Dir> {
+        let path = dir.join(name);
+        if std::fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "is a symlink"));
+        }
+        std::fs::create_dir_all(&path)?;
+        Ok(path)
+    }
+
+    pub fn create_file(dir: &Dir, name: &str) -> io::Result<File> {
+        File::create(dir.join(name))
+    }
+
+    pub fn rename(dir: &Dir, from: &str, to: &str) -> io::Result<()> {
+        std::fs::rename(dir.join(from), dir.join(to))
+    }
+
+    pub fn remove(dir: &Dir, name: &str) -> io::Result<()> {
+        std::fs::remove_file(dir.join(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_paths() {
+        for target in ["", "/etc/passwd", "../job-2/x", "data/../../x", "./data"] {
+            assert!(check_relative(target).is_err(), "{}", target);
+        }
+        assert_eq!(check_relative("/etc/passwd"), Err(PathViolation::Absolute("/etc/passwd".to_string())));
+        assert!(check_job_name("job-1").is_ok());
+        for name in ["", "..", "a/b", "/tmp", "."] {
+            assert!(check_job_name(name).is_err(), "{}", name);
+        }
+
+        let base = std::env::temp_dir().join(format!("eryzaa-sandbox-{}", std::process::id()));
+        let workspace = base.join("job-1");
+        std::fs::create_dir_all(workspace.join("data")).unwrap();
+        std::fs::create_dir_all(base.join("secrets")).unwrap();
+        std::fs::write(base.join("secrets/key"), b"hunter2").unwrap();
+        let root = workspace.canonicalize().unwrap();
+        assert_eq!(resolve(&workspace, "data/train.tar").unwrap(), root.join("data/train.tar"));
+        assert_eq!(resolve(&workspace, "new/dir/file").unwrap(), root.join("new/dir/file"));
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(base.join("secrets"), workspace.join("out")).unwrap();
+            std::os::unix::fs::symlink(workspace.join("data"), workspace.join("inside")).unwrap();
+            std::os::unix::fs::symlink(base.join("gone"), workspace.join("dangling")).unwrap();
+            assert_eq!(resolve(&workspace, "out/key"), Err(PathViolation::SymlinkEscape("out/key".to_string())));
+            assert_eq!(resolve(&workspace, "out/new"), Err(PathViolation::SymlinkEscape("out/new".to_string())));
+            assert_eq!(resolve(&workspace, "dangling"), Err(PathViolation::SymlinkEscape("dangling".to_string())));
+            assert_eq!(resolve(&workspace, "inside/x").unwrap(), root.join("data/x"));
+        }
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_writes_ignore_symlinks_swapped_in() {
+        use std::io::Write;
+
+        let base = std::env::temp_dir().join(format!("eryzaa-sandbox-writes-{}", std::process::id()));
+        let workspace = base.join("job-1");
+        std::fs::create_dir_all(&workspace).unwrap();
+        std::fs::create_dir_all(base.join("secrets")).unwrap();
+        std::fs::write(base.join("secrets/key"), b"hunter2").unwrap();
+        let write = |target: &WorkspaceFile, content: &[u8]| {
+            target.create(".part").unwrap().write_all(content).unwrap();
+            target.commit(".part").unwrap();
+        };
+
+        // Directories on the way are created, and symlinks among them refused
+        write(&WorkspaceFile::open(&workspace, "new/dir/file").unwrap(), b"data");
+        assert_eq!(std::fs::read(workspace.join("new/dir/file")).unwrap(), b"data");
+        std::os::unix::fs::symlink(base.join("secrets"), workspace.join("out")).unwrap();
+        assert_eq!(WorkspaceFile::open(&workspace, "out/key").err(), Some(PathViolation::SymlinkEscape("out/key".to_string())));
+
+        // A directory swapped for a symlink after the check is not followed
+        let target = WorkspaceFile::open(&workspace, "new/dir/file").unwrap();
+        std::fs::rename(workspace.join("new"), workspace.join("moved")).unwrap();
+        std::os::unix::fs::symlink(base.join("secrets"), workspace.join("new")).unwrap();
+        write(&target, b"late");
+        assert_eq!(std::fs::read(workspace.join("moved/dir/file")).unwrap(), b"late");
+        assert!(!base.join("secrets/file").exists());
+
+        // Nor is a symlink planted where the file itself goes: it is replaced
+        std::os::unix::fs::symlink(base.join("secrets/key"), workspace.join("model.bin")).unwrap();
+        write(&WorkspaceFile::open(&workspace, "model.bin").unwrap(), b"weights");
+        assert_eq!(std::fs::read(base.join("secrets/key")).unwrap(), b"hunter2");
+        assert!(!std::fs::symlink_metadata(workspace.join("model.bin")).unwrap().file_type().is_symlink());
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,238 @@
+//! Versioned state on disk
+//! Everything a profile keeps (job registry, SSH users, settings files) sits in its data
+//! directory, stamped with one schema version in `schema.json`. When a saved format changes in a
+//! way serde defaults can't absorb, a `Migration` to the next version rewrites the affected
+//! files; the apps run pending migrations on startup, backing up each file they change, and
+//! refuse to touch a directory stamped by a newer build. Directories from before the stamp
+//! existed count as version 1.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use log::info;
+
+/// Stamp file in each data directory
+pub const SCHEMA_FILE: &str = "schema.json";
+
+/// Version of directories that were never stamped
+const UNSTAMPED_VERSION: u32 = 1;
+
+/// Version this build reads and writes
+pub const CURRENT_VERSION: u32 = 1;
+
+/// Migrations in order, each to the version after the previous one's
+///
+/// Add one, and bump `CURRENT_VERSION` to its `version`, with every incompatible format change.
+pub const MIGRATIONS: &[Migration] = &[];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct Stamp {
+    version: u32,
+    migrated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// One step forward, rewriting the JSON files of a data directory
+pub struct Migration {
+    pub version: u32, // What the directory is afterwards
+    pub description: &'static str,
+    pub apply: fn(&mut StateFiles) -> Result<(), String>,
+}
+
+/// JSON files of a data directory, read when a migration first asks for them
+pub struct StateFiles {
+    dir: PathBuf,
+    files: BTreeMap<String, (Value, Value)>, // As read, as migrated so far
+}
+
+impl StateFiles {
+    fn new(dir: &Path) -> Self {
+        Self { dir: dir.to_path_buf(), files: BTreeMap::new() }
+    }
+
+    /// Contents of `name` to change in place, None when the directory has no such file
+    pub fn get_mut(&mut self, name: &str) -> Result<Option<&mut Value>, String> {
+        if !self.files.contains_key(name) {
+            let path = self.dir.join(name);
+            if !path.exists() {
+                return Ok(None);
+            }
+            let content = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let value: Value = serde_json::from_str(&content).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+            self.files.insert(name.to_string(), (value.clone(), value));
+        }
+        Ok(self.files.get_mut(name).map(|(_, migrated)| migrated))
+    }
+
+    /// Files the migrations changed
+    fn changed(&self) -> Vec<&str> {
+        self.files
+            .iter()
+            .filter(|(_, (read, migrated))| read != migrated)
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+}
+
+/// What migrating a data directory does, or did
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationReport {
+    pub dir: PathBuf,
+    pub from: u32,
+    pub to: u32,
+    pub steps: Vec<String>, // Descriptions of the migrations run
+    pub changed_files: Vec<String>,
+}
+
+impl MigrationReport {
+    pub fn is_current(&self) -> bool {
+        self.from == self.to
+    }
+}
+
+/// Schema version of the data directory `dir`
+pub fn version_of(dir: &Path) -> Result<u32, String> {
+    let path = dir.join(SCHEMA_FILE);
+    if !path.exists() {
+        return Ok(UNSTAMPED_VERSION);
+    }
+    let content = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let stamp: Stamp = serde_json::from_str(&content).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    Ok(stamp.version)
+}
+
+/// Bring `dir` from its version to `target` with `migrations`; a dry run only reports
+///
+/// Each changed file is first copied to `<file>.v<old version>.bak`, and the stamp is written
+/// last, so a migration cut short runs again from the start next time; migrations must leave
+/// files they already rewrote as they are, and the first backup is kept.
+pub fn migrate_with(dir: &Path, migrations: &[Migration], target: u32, dry_run: bool) -> Result<MigrationReport, String> {
+    let from = version_of(dir)?;
+    if from > target {
+        return Err(format!(
+            "State in {} is schema version {}, newer than version {} this build understands; upgrade Eryzaa or restore a backup",
+            dir.display(),
+            from,
+            target
+        ));
+    }
+
+    let mut files = StateFiles::new(dir);
+    let mut steps = vec![];
+    let mut version = from;
+    for migration in migrations.iter().filter(|migration| migration.version > from && migration.version <= target) {
+        if migration.version != version + 1 {
+            return Err(format!("No migration from schema version {} to {}", version, version + 1));
+        }
+        (migration.apply)(&mut files).map_err(|e| format!("Migration to schema version {} failed: {}", migration.version, e))?;
+        steps.push(migration.description.to_string());
+        version = migration.version;
+    }
+    if version != target {
+        return Err(format!("No migration from schema version {} to {}", version, target));
+    }
+
+    let report = MigrationReport {
+        dir: dir.to_path_buf(),
+        from,
+        to: target,
+        steps,
+        changed_files: files.changed().into_iter().map(str::to_string).collect(),
+    };
+    // Fresh directories are stamped too, once something lives in them
+    if dry_run || (report.is_current() && dir.join(SCHEMA_FILE).exists()) || !dir.exists() {
+        return Ok(report);
+    }
+
+    for name in &report.changed_files {
+        let path = dir.join(name);
+        let backup = dir.join(format!("{}.v{}.bak", name, from));
+        if !backup.exists() {
+            std::fs::copy(&path, &backup).map_err(|e| format!("Failed to back up {}: {}", path.display(), e))?;
+        }
+        let content = serde_json::to_string_pretty(&files.files[name].1).map_err(|e| format!("Failed to serialize {}: {}", name, e))?;
+        write_replacing(&path, &content)?;
+    }
+    let stamp = Stamp { version: target, migrated_at: chrono::Utc::now() };
+    let content = serde_json::to_string_pretty(&stamp).map_err(|e| format!("Failed to serialize schema stamp: {}", e))?;
+    write_replacing(&dir.join(SCHEMA_FILE), &content)?;
+    if !report.is_current() {
+        info!("Migrated {} from schema version {} to {}", dir.display(), from, target);
+    }
+    Ok(report)
+}
+
+/// Bring `dir` up to this build's schema version
+pub fn migrate(dir: &Path, dry_run: bool) -> Result<MigrationReport, String> {
+    migrate_with(dir, MIGRATIONS, CURRENT_VERSION, dry_run)
+}
+
+/// Data directories the active profile reads: its own, and the home with the profile list
+pub fn data_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![crate::profiles::eryzaa_home(), crate::profiles::active_profile().data_dir()];
+    dirs.dedup();
+    dirs
+}
+
+/// Migrate every data directory the active profile reads, as apps do on startup
+pub fn prepare() -> Result<Vec<MigrationReport>, String> {
+    data_dirs().iter().map(|dir| migrate(dir, false)).collect()
+}
+
+/// Write `content` to a temporary file beside `path` and move it over `path`
+fn write_replacing(path: &Path, content: &str) -> Result<(), String> {
+    let temporary = PathBuf::from(format!("{}.tmp", path.display()));
+    std::fs::write(&temporary, content).map_err(|e| format!("Failed to write {}: {}", temporary.display(), e))?;
+    std::fs::rename(&temporary, path).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename_client(files: &mut StateFiles) -> Result<(), String> {
+        if let Some(Value::Array(records)) = files.get_mut("jobs.json")? {
+            for record in records.iter_mut().filter_map(Value::as_object_mut) {
+                if let Some(client) = record.remove("client") {
+                    record.insert("client_id".to_string(), client);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_settings(files: &mut StateFiles) -> Result<(), String> {
+        files.get_mut("settings.json").map(|_| ())
+    }
+
+    #[test]
+    fn test_schema_migrations() {
+        let dir = std::env::temp_dir().join(format!("eryzaa-schema-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("jobs.json"), r#"[{"job_id": "j1", "client": "alice"}]"#).unwrap();
+        let migrations = [
+            Migration { version: 2, description: "Rename client to client_id", apply: rename_client },
+            Migration { version: 3, description: "Read settings without changing them", apply: read_settings },
+        ];
+
+        let dry = migrate_with(&dir, &migrations, 3, true).unwrap();
+        assert_eq!((dry.from, dry.to, dry.steps.len()), (1, 3, 2));
+        assert_eq!(dry.changed_files, vec!["jobs.json"]);
+        assert_eq!(version_of(&dir), Ok(1));
+        assert!(std::fs::read_to_string(dir.join("jobs.json")).unwrap().contains("\"client\""));
+
+        let done = migrate_with(&dir, &migrations, 3, false).unwrap();
+        assert_eq!(done, dry);
+        assert_eq!(version_of(&dir), Ok(3));
+        let jobs: Value = serde_json::from_str(&std::fs::read_to_string(dir.join("jobs.json")).unwrap()).unwrap();
+        assert_eq!(jobs[0]["client_id"], "alice");
+        assert!(dir.join("jobs.json.v1.bak").exists());
+        assert!(migrate_with(&dir, &migrations, 3, false).unwrap().is_current());
+
+        // An older build leaves the directory alone
+        let refused = migrate_with(&dir, &migrations[..1], 2, false).unwrap_err();
+        assert!(refused.contains("newer"), "{}", refused);
+        assert!(migrate_with(&dir, &[], 4, true).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
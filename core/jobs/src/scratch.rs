@@ -0,0 +1,246 @@
+//! Per-job scratch disks
+//! The node owner configures storage pools, one directory per media class on its own disk. A job
+//! asking for scratch space gets a fixed-size loopback filesystem in a pool of its class, mounted
+//! at /scratch, so it can neither outgrow its request nor starve other jobs of the pool.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use log::{info, warn};
+
+pub use eryzaa_protocol::job::{ScratchRequest, StorageClass};
+
+use crate::JobManager;
+
+/// Where scratch disks appear inside the container
+pub const SCRATCH_MOUNT: &str = "/scratch";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StoragePool {
+    pub class: StorageClass,
+    pub root: PathBuf,    // Directory on the pool's disk; disk images and mounts go here
+    pub capacity_gb: u32, // Most the pool hands out across all jobs
+    #[serde(default)]
+    pub device: Option<String>, // Block device behind `root`, needed for the IOPS cap
+    #[serde(default)]
+    pub iops_per_job: Option<u32>, // Read and write IOPS each job's disk is held to
+}
+
+impl StoragePool {
+    /// Gigabytes currently handed out, from the disk images in the pool
+    pub fn used_gb(&self) -> u32 {
+        let Ok(entries) = std::fs::read_dir(&self.root) else {
+            return 0;
+        };
+        let bytes: u64 = entries
+            .flatten()
+            .filter(|entry| entry.path().extension().map(|e| e == "img").unwrap_or(false))
+            .filter_map(|entry| entry.metadata().ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        bytes.div_ceil(1 << 30) as u32
+    }
+}
+
+/// The node's storage pools
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ScratchPools {
+    pub pools: Vec<StoragePool>,
+}
+
+impl ScratchPools {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize storage pools: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write storage pools {}: {}", path.display(), e))
+    }
+
+    /// First pool of the requested class with room left, given each pool's used gigabytes
+    pub fn pick(&self, request: &ScratchRequest, used_gb: impl Fn(&StoragePool) -> u32) -> Result<&StoragePool, String> {
+        let mut pools = self.pools.iter().filter(|pool| pool.class == request.class).peekable();
+        if pools.peek().is_none() {
+            return Err(format!("This node has no {:?} storage pool", request.class));
+        }
+        pools
+            .find(|pool| pool.capacity_gb.saturating_sub(used_gb(pool)) >= request.size_gb)
+            .ok_or_else(|| format!("No {:?} storage pool has {} GB free", request.class, request.size_gb))
+    }
+
+    /// Create and mount a scratch disk for `job_id`, replacing one left from an earlier run
+    pub fn provision(&self, job_id: &str, request: &ScratchRequest) -> Result<ScratchDisk, String> {
+        for pool in &self.pools {
+            let stale = ScratchDisk::in_pool(pool, job_id, 0);
+            if stale.image.exists() {
+                stale.release()?;
+            }
+        }
+
+        let pool = self.pick(request, StoragePool::used_gb)?;
+        let disk = ScratchDisk::in_pool(pool, job_id, request.size_gb);
+        disk.create()?;
+        info!("Provisioned {} GB {:?} scratch for job '{}'", disk.size_gb, disk.class, job_id);
+        Ok(disk)
+    }
+}
+
+/// A job's scratch filesystem and when it was held
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScratchDisk {
+    pub class: StorageClass,
+    pub size_gb: u32,
+    pub image: PathBuf, // Backing file of the loopback filesystem
+    pub mount: PathBuf, // Host mount point, bound to /scratch in the container
+    #[serde(default)]
+    pub iops_limit: Option<(String, u32)>, // Device and IOPS from the pool
+    pub provisioned_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub released_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl ScratchDisk {
+    fn in_pool(pool: &StoragePool, job_id: &str, size_gb: u32) -> Self {
+        Self {
+            class: pool.class,
+            size_gb,
+            image: pool.root.join(format!("{}.img", job_id)),
+            mount: pool.root.join(job_id),
+            iops_limit: pool.device.clone().zip(pool.iops_per_job),
+            provisioned_at: chrono::Utc::now(),
+            released_at: None,
+        }
+    }
+
+    fn create(&self) -> Result<(), String> {
+        let image = self.image.to_string_lossy();
+        let mount = self.mount.to_string_lossy();
+        std::fs::create_dir_all(&self.mount)
+            .map_err(|e| format!("Failed to create {}: {}", mount, e))?;
+        let created = run("fallocate", &["-l", &format!("{}G", self.size_gb), &image])
+            .and_then(|_| run("mkfs.ext4", &["-q", "-F", "-m", "0", &image]))
+            .and_then(|_| run("mount", &["-o", "loop", &image, &mount]));
+        if created.is_err() {
+            let _ = std::fs::remove_file(&self.image);
+            let _ = std::fs::remove_dir(&self.mount);
+        }
+        created
+    }
+
+    /// Unmount and delete the disk; its contents are gone afterwards
+    pub fn release(&self) -> Result<(), String> {
+        if self.mount.exists() {
+            // Not mounted is fine, e.g. after a reboot
+            let _ = run("umount", &[&self.mount.to_string_lossy()]);
+            std::fs::remove_dir(&self.mount)
+                .map_err(|e| format!("Failed to remove {}: {}", self.mount.display(), e))?;
+        }
+        if self.image.exists() {
+            std::fs::remove_file(&self.image)
+                .map_err(|e| format!("Failed to remove {}: {}", self.image.display(), e))?;
+        }
+        Ok(())
+    }
+
+    /// `docker run` arguments that mount the disk and apply the pool's IOPS cap
+    pub fn run_args(&self) -> Vec<String> {
+        let mut args = vec!["-v".to_string(), format!("{}:{}", self.mount.display(), SCRATCH_MOUNT)];
+        if let Some((device, iops)) = &self.iops_limit {
+            for flag in ["--device-read-iops", "--device-write-iops"] {
+                args.push(flag.to_string());
+                args.push(format!("{}:{}", device, iops));
+            }
+        }
+        args
+    }
+
+    /// Gigabyte-hours held up to `now`, or up to release
+    pub fn gb_hours(&self, now: chrono::DateTime<chrono::Utc>) -> f64 {
+        let held = self.released_at.unwrap_or(now) - self.provisioned_at;
+        self.size_gb as f64 * held.num_seconds().max(0) as f64 / 3600.0
+    }
+}
+
+impl JobManager {
+    /// Release a job's scratch disk and stop its meter
+    pub fn release_scratch(&self, job_id: &str) -> Result<(), String> {
+        let disk = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let Some(disk) = jobs.get_mut(job_id).and_then(|record| record.scratch.as_mut()) else {
+                return Ok(());
+            };
+            if disk.released_at.is_some() {
+                return Ok(());
+            }
+            disk.released_at = Some(chrono::Utc::now());
+            disk.clone()
+        };
+        disk.release().inspect_err(|e| warn!("Failed to release scratch of job '{}': {}", job_id, e))
+    }
+}
+
+/// Storage pools configured by the node owner
+pub fn default_pools_path() -> PathBuf {
+    crate::default_registry_path().with_file_name("storage-pools.json")
+}
+
+fn run(program: &str, args: &[&str]) -> Result<(), String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to execute {}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_pool() {
+        let pool = |class, root: &str, capacity_gb| StoragePool {
+            class,
+            root: PathBuf::from(root),
+            capacity_gb,
+            device: Some("/dev/nvme0n1".to_string()),
+            iops_per_job: Some(5000),
+        };
+        let pools = ScratchPools {
+            pools: vec![
+                pool(StorageClass::Nvme, "/mnt/nvme-a", 100),
+                pool(StorageClass::Nvme, "/mnt/nvme-b", 500),
+                pool(StorageClass::Hdd, "/mnt/hdd", 4000),
+            ],
+        };
+        let used = |pool: &StoragePool| if pool.root.ends_with("nvme-a") { 80 } else { 0 };
+
+        let request = ScratchRequest { size_gb: 50, class: StorageClass::Nvme };
+        assert_eq!(pools.pick(&request, used).unwrap().root, PathBuf::from("/mnt/nvme-b"));
+        let too_big = ScratchRequest { size_gb: 600, class: StorageClass::Nvme };
+        assert_eq!(pools.pick(&too_big, used).unwrap_err(), "No Nvme storage pool has 600 GB free");
+        assert!(pools.pick(&ScratchRequest { size_gb: 1, class: StorageClass::Ssd }, used).is_err());
+
+        let mut disk = ScratchDisk::in_pool(&pools.pools[1], "job_1", 50);
+        assert_eq!(disk.run_args(), vec![
+            "-v", "/mnt/nvme-b/job_1:/scratch",
+            "--device-read-iops", "/dev/nvme0n1:5000",
+            "--device-write-iops", "/dev/nvme0n1:5000",
+        ]);
+        disk.released_at = Some(disk.provisioned_at + chrono::Duration::minutes(90));
+        assert_eq!(disk.gb_hours(chrono::Utc::now()), 75.0);
+    }
+}
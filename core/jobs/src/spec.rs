@@ -0,0 +1,214 @@
+//! Job specs for the native executor
+//! `JobSpec` itself is a wire type from eryzaa-protocol; an `ExecutorConfig` groups the specs,
+//! volumes and networks of one setup and `JobManager` runs them with plain `docker` commands.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use log::info;
+
+pub use eryzaa_protocol::job::{BuildSpec, JobSpec};
+
+use crate::pinning::{default_pinning_path, CpuTopology, PinningSettings};
+use crate::reservation::{self, default_reservation_path, OwnerReservation};
+use crate::ports::{self, PortAllocator};
+use crate::sandbox;
+use crate::scratch::{default_pools_path, ScratchPools};
+use crate::staging;
+use crate::{JobManager, JobRecord, JobStatus, OutputPush};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NetworkSpec {
+    pub name: String,
+    pub subnet: Option<String>,
+}
+
+/// Everything one deployment mode needs, in start order
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ExecutorConfig {
+    pub mode: String,
+    pub specs: Vec<JobSpec>,
+    #[serde(default)]
+    pub networks: Vec<NetworkSpec>,
+    #[serde(default)]
+    pub volumes: Vec<String>, // Named volumes to create
+}
+
+impl ExecutorConfig {
+    pub fn load_from(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read executor config {}: {}", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse executor config {}: {}", path.display(), e))
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize executor config: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write executor config {}: {}", path.display(), e))
+    }
+}
+
+impl JobManager {
+    /// Create the networks and volumes of a config and start its specs in order
+    pub fn run_config(&self, config: &ExecutorConfig, client_id: &str) -> Result<(), String> {
+        for network in &config.networks {
+            let mut args = vec!["network", "create"];
+            if let Some(subnet) = &network.subnet {
+                args.extend(["--subnet", subnet]);
+            }
+            args.push(&network.name);
+            // Already existing networks are fine
+            let _ = docker(&args);
+        }
+        for volume in &config.volumes {
+            docker(&["volume", "create", volume])?;
+        }
+
+        for spec in &config.specs {
+            self.run_spec(spec, client_id)?;
+        }
+        Ok(())
+    }
+
+    /// Build (if needed) and start one spec, replacing a container of the same name
+    pub fn run_spec(&self, spec: &JobSpec, client_id: &str) -> Result<(), String> {
+        // Refused before anything is built, pulled or staged
+        sandbox::check_spec(spec)?;
+        if let Some(args) = spec.build_args() {
+            info!("Building {}", spec.image_tag());
+            docker(&args)?;
+        }
+
+        let _ = docker(&["rm", "-f", &spec.name]);
+        let pinning = match spec.dedicated_cores {
+            Some(count) => Some(self.pin_cores(&spec.name, count)?),
+            None => None,
+        };
+        // Containers stay off what the owner keeps for themselves
+        let reservation = OwnerReservation::load_from(&default_reservation_path());
+        let topology = CpuTopology::detect();
+        let reserved = reservation.reserved_cpus(&topology, &PinningSettings::load_from(&default_pinning_path()).reserved_cores);
+        let confinement = reservation.confine(&topology, &reserved)?;
+        let gpus = match &spec.gpus {
+            Some(gpus) if !reservation.gpus.is_empty() => Some(reservation.gpu_request(gpus, &reservation::gpu_indices())?),
+            gpus => gpus.clone(),
+        };
+        let workspace = match spec.inputs.is_empty() && spec.outputs.is_empty() {
+            true => None,
+            false => {
+                // Registered as pending so the staging progress shows while the inputs download
+                if self.get_job(&spec.name).is_none() {
+                    let mut pending = JobRecord::new(&spec.name, client_id, &spec.image_tag());
+                    pending.container_name = spec.name.clone();
+                    self.register_job(pending);
+                }
+                let staged = self.stage_inputs(&spec.name, &spec.inputs);
+                if let Err(e) = &staged {
+                    let _ = self.update_status(&spec.name, JobStatus::Failed(e.clone()));
+                }
+                Some(staged?)
+            }
+        };
+        // Leases live in the job records; the container being replaced gives its ports up
+        let mappings = match spec.services.is_empty() {
+            true => vec![],
+            false => {
+                let others: Vec<JobRecord> = self.list_jobs().into_iter().filter(|job| job.job_id != spec.name).collect();
+                PortAllocator::for_node(&others)?.allocate(&spec.name, &spec.services)?
+            }
+        };
+        let scratch = match &spec.scratch {
+            Some(request) => Some(ScratchPools::load_from(&default_pools_path()).provision(&spec.name, request)?),
+            None => None,
+        };
+        let mut args = JobSpec { gpus, ..spec.clone() }.run_args();
+        args.splice(2..2, confinement);
+        args.splice(2..2, ports::docker_publish_args(&mappings));
+        if let Some(disk) = &scratch {
+            // Options go between `run -d` and the image
+            args.splice(2..2, disk.run_args());
+        }
+        if let Some(workspace) = &workspace {
+            args.splice(2..2, staging::run_args(workspace));
+        }
+        if let Some(pinning) = &pinning {
+            args.splice(2..2, pinning.run_args());
+        }
+        if let Err(e) = docker(&args) {
+            if let Some(disk) = &scratch {
+                let _ = disk.release();
+            }
+            return Err(e);
+        }
+
+        let mut record = JobRecord::new(&spec.name, client_id, &spec.image_tag());
+        record.container_name = spec.name.clone();
+        record.gpus = match spec.gpus.as_deref() {
+            Some("all") => 1,
+            Some(count) => count.parse().unwrap_or(0),
+            None => 0,
+        };
+        record.status = JobStatus::Running;
+        record.scratch = scratch;
+        record.pinning = pinning;
+        record.port_mappings = mappings;
+        if !spec.outputs.is_empty() {
+            record.output_push = Some(OutputPush::new(spec.outputs.clone()));
+        }
+        if workspace.is_some() {
+            if let Some(staged) = self.get_job(&spec.name) {
+                record.access_token = staged.access_token;
+                record.staged_bytes = staged.staged_bytes;
+            }
+        }
+        self.register_job(record);
+        Ok(())
+    }
+
+    /// Stop and remove every container of a config
+    pub fn stop_config(&self, config: &ExecutorConfig) -> Result<(), String> {
+        for spec in config.specs.iter().rev() {
+            docker(&["rm", "-f", &spec.name])?;
+            let _ = self.update_status(&spec.name, JobStatus::Stopped);
+            let _ = self.release_scratch(&spec.name);
+            if !spec.inputs.is_empty() || !spec.outputs.is_empty() {
+                let _ = staging::remove_workspace(&spec.name);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read a job spec from a YAML (or JSON) file
+pub fn load_spec(path: &Path) -> Result<JobSpec, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read job spec {}: {}", path.display(), e))?;
+    serde_yaml::from_str(&content).map_err(|e| format!("Failed to parse job spec {}: {}", path.display(), e))
+}
+
+/// Where converted executor configs are kept, one file per mode
+pub fn default_config_path(mode: &str) -> PathBuf {
+    crate::default_registry_path().with_file_name(format!("executor-{}.json", mode))
+}
+
+fn docker<S: AsRef<std::ffi::OsStr>>(args: &[S]) -> Result<(), String> {
+    let output = Command::new("docker")
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to execute docker: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "docker {} failed: {}",
+            args.first().map(|a| a.as_ref().to_string_lossy()).unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
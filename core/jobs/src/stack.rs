@@ -0,0 +1,79 @@
+//! Rental stack control
+//! The stack is the executor config of the manage.sh mode the node runs. Stopping or restarting
+//! it can drain the node first: no new tenant jobs start while running ones finish.
+
+use std::path::PathBuf;
+use log::info;
+
+use crate::compose::MANAGE_MODES;
+use crate::spec::{self, ExecutorConfig};
+use crate::{JobManager, JobStatus};
+
+/// Client id the executor registers stack containers under
+pub const STACK_CLIENT: &str = "local";
+
+/// Marker file the rental server checks before starting jobs
+pub fn drain_marker_path() -> PathBuf {
+    crate::default_registry_path().with_file_name("draining")
+}
+
+pub fn is_draining() -> bool {
+    drain_marker_path().exists()
+}
+
+pub fn set_draining(draining: bool) -> Result<(), String> {
+    let path = drain_marker_path();
+    if draining {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        std::fs::write(&path, chrono::Utc::now().to_rfc3339())
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        info!("Draining: no new jobs will start");
+    } else if path.exists() {
+        std::fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        info!("Drain lifted");
+    }
+    Ok(())
+}
+
+impl JobManager {
+    /// Tenant jobs still running, leaving out the stack's own containers
+    pub fn running_tenant_jobs(&self) -> usize {
+        self.list_jobs()
+            .iter()
+            .filter(|job| job.status == JobStatus::Running && job.client_id != STACK_CLIENT)
+            .count()
+    }
+
+    /// The mode whose stack containers are running, with its config
+    pub fn active_stack(&self) -> Option<(String, ExecutorConfig)> {
+        MANAGE_MODES.iter().find_map(|(mode, _)| {
+            let config = ExecutorConfig::load_from(&spec::default_config_path(mode)).ok()?;
+            let running = config.specs.iter().any(|spec| {
+                self.get_job(&spec.name)
+                    .map(|job| job.status == JobStatus::Running)
+                    .unwrap_or(false)
+            });
+            running.then(|| (mode.to_string(), config))
+        })
+    }
+
+    /// Stop the running stack
+    pub fn stop_stack(&self) -> Result<String, String> {
+        let (mode, config) = self.active_stack().ok_or("The rental stack is not running")?;
+        self.stop_config(&config)?;
+        info!("Stopped the {} stack", mode);
+        Ok(mode)
+    }
+
+    /// Stop and start the running stack again in the same mode
+    pub fn restart_stack(&self) -> Result<String, String> {
+        let (mode, config) = self.active_stack().ok_or("The rental stack is not running")?;
+        self.stop_config(&config)?;
+        self.run_config(&config, STACK_CLIENT)?;
+        info!("Restarted the {} stack", mode);
+        Ok(mode)
+    }
+}
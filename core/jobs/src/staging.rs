@@ -0,0 +1,368 @@
+//! Job input staging
+//! Inputs listed in a spec are downloaded by the node into the job's workspace before the
+//! container starts, from S3, GCS or plain HTTPS. Credentials for private buckets arrive sealed
+//! with a key derived from the job's access token and expire within hours; curl reads them on
+//! stdin, so they never show up in the process list. Outputs are pushed the same way, see `outputs`.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine as _;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+use log::info;
+
+pub use eryzaa_protocol::job::{JobInput, SealedCredentials};
+
+use crate::sandbox::{self, PathViolation, WorkspaceFile};
+use crate::transfers::format_bytes;
+use crate::{JobManager, JobRecord};
+
+/// Where each job's workspace lives on the node
+pub const WORKSPACE_ROOT: &str = "/var/lib/eryzaa/workspaces";
+
+/// Where the workspace appears inside the container
+pub const WORKSPACE_MOUNT: &str = "/workspace";
+
+/// Longest sealed credentials may live
+pub const MAX_CREDENTIAL_TTL_HOURS: i64 = 12;
+
+const NONCE_LEN: usize = 12;
+
+/// How often a running download is checked for progress
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Credentials for one bucket or URL, as the client seals them
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SourceCredentials {
+    S3 {
+        access_key_id: String,
+        secret_access_key: String,
+        #[serde(default)]
+        session_token: Option<String>, // Set for temporary STS credentials
+        region: String,
+    },
+    Gcs {
+        access_token: String, // OAuth token, e.g. from `gcloud auth print-access-token`
+    },
+    Bearer {
+        token: String, // Sent as the Authorization header of an https source
+    },
+}
+
+/// Input a pending job is downloading
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StagingProgress {
+    pub input: usize, // Counted from 1
+    pub inputs: usize,
+    pub source: String,
+    pub bytes: u64,
+    pub total: Option<u64>, // None when the source doesn't send a length
+}
+
+impl StagingProgress {
+    /// Share of the current input downloaded, if its size is known
+    pub fn fraction(&self) -> Option<f32> {
+        self.total.filter(|total| *total > 0).map(|total| (self.bytes as f32 / total as f32).min(1.0))
+    }
+}
+
+impl fmt::Display for StagingProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Staging input {}/{} from {}: {}", self.input, self.inputs, self.source, format_bytes(self.bytes))?;
+        match self.total {
+            Some(total) => write!(f, " of {}", format_bytes(total)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn sealing_key(access_token: &str) -> Key {
+    let mut hasher = Sha256::new();
+    hasher.update(b"eryzaa-staging:");
+    hasher.update(access_token.as_bytes());
+    *Key::from_slice(&hasher.finalize())
+}
+
+/// The location and expiry are authenticated with the credentials, so neither can be swapped
+fn sealing_aad(location: &str, expires_at: u64) -> String {
+    format!("{}|{}", location, expires_at)
+}
+
+/// Seal `credentials` for an input source or output target, readable by a node that knows the
+/// job's access token
+pub fn seal_credentials(
+    credentials: &SourceCredentials,
+    location: &str,
+    access_token: &str,
+    ttl: chrono::Duration,
+) -> Result<SealedCredentials, String> {
+    if ttl <= chrono::Duration::zero() || ttl > chrono::Duration::hours(MAX_CREDENTIAL_TTL_HOURS) {
+        return Err(format!("Input credentials must expire within {} hours", MAX_CREDENTIAL_TTL_HOURS));
+    }
+    let expires_at = (chrono::Utc::now() + ttl).timestamp() as u64;
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let plaintext = serde_json::to_vec(credentials)
+        .map_err(|e| format!("Failed to serialize credentials: {}", e))?;
+    let aad = sealing_aad(location, expires_at);
+    let ciphertext = ChaCha20Poly1305::new(&sealing_key(access_token))
+        .encrypt(&nonce, Payload { msg: &plaintext, aad: aad.as_bytes() })
+        .map_err(|_| "Failed to seal credentials".to_string())?;
+
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    Ok(SealedCredentials { sealed: URL_SAFE_NO_PAD.encode(sealed), expires_at })
+}
+
+/// Open credentials sealed for `location`, unless they have expired by `now`
+pub fn open_credentials(
+    sealed: &SealedCredentials,
+    location: &str,
+    access_token: &str,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<SourceCredentials, String> {
+    if now.timestamp() as u64 >= sealed.expires_at {
+        return Err(format!("Credentials for {} have expired", location));
+    }
+    let bytes = URL_SAFE_NO_PAD
+        .decode(&sealed.sealed)
+        .ok()
+        .filter(|bytes| bytes.len() > NONCE_LEN)
+        .ok_or_else(|| format!("Credentials for {} are corrupted", location))?;
+
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    let aad = sealing_aad(location, sealed.expires_at);
+    let plaintext = ChaCha20Poly1305::new(&sealing_key(access_token))
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: aad.as_bytes() })
+        .map_err(|_| format!("Credentials for {} were not sealed for this job", location))?;
+    serde_json::from_slice(&plaintext).map_err(|e| format!("Failed to parse credentials: {}", e))
+}
+
+/// URL of `source`, and the curl config that authenticates requests to it
+pub(crate) fn request_for(source: &str, credentials: Option<&SourceCredentials>) -> Result<(String, String), String> {
+    let header = |value: String| format!("header = {}\n", quoted(&value));
+    let mismatch = || Err(format!("Credentials don't match the kind of source {}", source));
+
+    if source.starts_with("https://") {
+        return match credentials {
+            None => Ok((source.to_string(), String::new())),
+            Some(SourceCredentials::Bearer { token }) => Ok((source.to_string(), header(format!("Authorization: Bearer {}", token)))),
+            Some(_) => mismatch(),
+        };
+    }
+
+    let object = |rest: &str| match rest.split_once('/') {
+        Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() => Ok((bucket.to_string(), key.to_string())),
+        _ => Err(format!("{} names no object", source)),
+    };
+    if let Some(rest) = source.strip_prefix("s3://") {
+        let (bucket, key) = object(rest)?;
+        return match credentials {
+            None => Ok((format!("https://{}.s3.amazonaws.com/{}", bucket, key), String::new())),
+            Some(SourceCredentials::S3 { access_key_id, secret_access_key, session_token, region }) => {
+                let mut config = format!(
+                    "aws-sigv4 = {}\nuser = {}\n",
+                    quoted(&format!("aws:amz:{}:s3", region)),
+                    quoted(&format!("{}:{}", access_key_id, secret_access_key))
+                );
+                if let Some(token) = session_token {
+                    config.push_str(&header(format!("x-amz-security-token: {}", token)));
+                }
+                Ok((format!("https://{}.s3.{}.amazonaws.com/{}", bucket, region, key), config))
+            }
+            Some(_) => mismatch(),
+        };
+    }
+    if let Some(rest) = source.strip_prefix("gs://") {
+        let (bucket, key) = object(rest)?;
+        let url = format!("https://storage.googleapis.com/{}/{}", bucket, key);
+        return match credentials {
+            None => Ok((url, String::new())),
+            Some(SourceCredentials::Gcs { access_token }) => Ok((url, header(format!("Authorization: Bearer {}", access_token)))),
+            Some(_) => mismatch(),
+        };
+    }
+    Err(format!("Unsupported location {}; use s3://, gs:// or https://", source))
+}
+
+/// A curl config file value
+fn quoted(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Length of the last response in `curl -I` output, after any redirects
+fn content_length(headers: &str) -> Option<u64> {
+    headers
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .rfind(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse().ok())
+}
+
+/// curl restricted to https, reading `config` on stdin
+pub(crate) fn curl(args: &[&str], url: &str, config: &str, stdout: Stdio) -> Result<Child, String> {
+    let mut child = Command::new("curl")
+        .args(["-fsSL", "--proto", "=https", "--proto-redir", "=https", "--config", "-"])
+        .args(args)
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(stdout)
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute curl: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(config.as_bytes()).map_err(|e| format!("Failed to write to curl: {}", e))?;
+    }
+    Ok(child)
+}
+
+/// Download `url` to `target`, reporting the bytes received so far and the expected total
+fn download(url: &str, config: &str, target: &WorkspaceFile, mut progress: impl FnMut(u64, Option<u64>)) -> Result<u64, String> {
+    let total = curl(&["-I"], url, config, Stdio::piped())
+        .and_then(|child| child.wait_with_output().map_err(|e| e.to_string()))
+        .ok()
+        .and_then(|output| content_length(&String::from_utf8_lossy(&output.stdout)));
+
+    // curl writes to the file opened here, never to a path the job could redirect
+    let part = target.create(".part")?;
+    let received = || part.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+    let output = part.try_clone().map_err(|e| format!("Failed to open {}: {}", target.path().display(), e))?;
+    let mut child = curl(&[], url, config, Stdio::from(output))?;
+    while child.try_wait().map_err(|e| format!("Failed to wait for curl: {}", e))?.is_none() {
+        progress(received(), total);
+        std::thread::sleep(PROGRESS_INTERVAL);
+    }
+    progress(received(), total);
+
+    let output = child.wait_with_output().map_err(|e| format!("Failed to wait for curl: {}", e))?;
+    if !output.status.success() {
+        target.discard(".part");
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let bytes = received();
+    target.commit(".part")?;
+    Ok(bytes)
+}
+
+impl JobManager {
+    /// Download a job's inputs into its workspace, keeping the job's staging progress current
+    ///
+    /// Returns the workspace directory, to be mounted at /workspace. Every byte received is
+    /// added to the job's staged bytes, whether or not the download completes.
+    pub fn stage_inputs(&self, job_id: &str, inputs: &[JobInput]) -> Result<PathBuf, String> {
+        let access_token = self.get_job(job_id).and_then(|record| record.access_token);
+        let workspace = workspace_dir(job_id)?;
+        std::fs::create_dir_all(&workspace).map_err(|e| format!("Failed to create {}: {}", workspace.display(), e))?;
+
+        for (index, input) in inputs.iter().enumerate() {
+            let credentials = match &input.credentials {
+                Some(sealed) => {
+                    let token = access_token
+                        .as_deref()
+                        .ok_or_else(|| format!("Job '{}' has no access token to open the credentials for {}", job_id, input.source))?;
+                    Some(open_credentials(sealed, &input.source, token, chrono::Utc::now())?)
+                }
+                None => None,
+            };
+            let (url, config) = request_for(&input.source, credentials.as_ref())?;
+            let target = WorkspaceFile::open(&workspace, &input.target)?;
+
+            let mut progress = StagingProgress {
+                input: index + 1,
+                inputs: inputs.len(),
+                source: input.source.clone(),
+                bytes: 0,
+                total: None,
+            };
+            let result = download(&url, &config, &target, |bytes, total| {
+                progress.bytes = bytes;
+                progress.total = total;
+                self.with_record(job_id, |record| record.staging = Some(progress.clone()));
+            });
+            let received = *result.as_ref().unwrap_or(&progress.bytes);
+            self.with_record(job_id, |record| {
+                record.staged_bytes += received;
+                record.staging = None;
+            });
+            result.map_err(|e| format!("Failed to stage {}: {}", input.source, e))?;
+            info!("Staged {} for job '{}' ({})", input.source, job_id, format_bytes(received));
+        }
+        Ok(workspace)
+    }
+
+    pub(crate) fn with_record(&self, job_id: &str, update: impl FnOnce(&mut JobRecord)) {
+        if let Some(record) = self.jobs.lock().unwrap().get_mut(job_id) {
+            update(record);
+        }
+    }
+}
+
+/// Host directory mounted as a job's workspace
+pub fn workspace_dir(job_id: &str) -> Result<PathBuf, PathViolation> {
+    sandbox::check_job_name(job_id)?;
+    Ok(Path::new(WORKSPACE_ROOT).join(job_id))
+}
+
+/// `docker run` arguments that mount a staged workspace
+pub fn run_args(workspace: &Path) -> Vec<String> {
+    vec!["-v".to_string(), format!("{}:{}", workspace.display(), WORKSPACE_MOUNT)]
+}
+
+/// Delete a job's workspace with everything staged or written into it
+pub fn remove_workspace(job_id: &str) -> Result<(), String> {
+    let workspace = workspace_dir(job_id)?;
+    match workspace.exists() {
+        true => std::fs::remove_dir_all(&workspace).map_err(|e| format!("Failed to remove {}: {}", workspace.display(), e)),
+        false => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sealed_source_requests() {
+        let credentials = SourceCredentials::S3 {
+            access_key_id: "AKIAEXAMPLE".to_string(),
+            secret_access_key: "wJalr\"secret".to_string(),
+            session_token: Some("FwoGZX".to_string()),
+            region: "eu-west-1".to_string(),
+        };
+        let source = "s3://datasets/imagenet/train.tar";
+        let sealed = seal_credentials(&credentials, source, "token-1", chrono::Duration::minutes(30)).unwrap();
+        let now = chrono::Utc::now();
+        assert_eq!(open_credentials(&sealed, source, "token-1", now), Ok(credentials.clone()));
+        assert!(open_credentials(&sealed, source, "token-2", now).is_err());
+        assert!(open_credentials(&sealed, "s3://datasets/other.tar", "token-1", now).is_err());
+        assert!(open_credentials(&sealed, source, "token-1", now + chrono::Duration::hours(1)).is_err());
+        assert!(seal_credentials(&credentials, source, "token-1", chrono::Duration::days(2)).is_err());
+
+        let (url, config) = request_for(source, Some(&credentials)).unwrap();
+        assert_eq!(url, "https://datasets.s3.eu-west-1.amazonaws.com/imagenet/train.tar");
+        assert_eq!(
+            config,
+            "aws-sigv4 = \"aws:amz:eu-west-1:s3\"\nuser = \"AKIAEXAMPLE:wJalr\\\"secret\"\nheader = \"x-amz-security-token: FwoGZX\"\n"
+        );
+        let gcs = SourceCredentials::Gcs { access_token: "ya29".to_string() };
+        assert_eq!(
+            request_for("gs://bucket/a/b.parquet", Some(&gcs)).unwrap(),
+            ("https://storage.googleapis.com/bucket/a/b.parquet".to_string(), "header = \"Authorization: Bearer ya29\"\n".to_string())
+        );
+        assert!(request_for("https://example.com/data.csv", Some(&gcs)).is_err());
+        assert!(request_for("ftp://example.com/data.csv", None).is_err());
+        assert!(request_for("s3://bucket-only", None).is_err());
+
+        assert_eq!(workspace_dir("job-1"), Ok(PathBuf::from("/var/lib/eryzaa/workspaces/job-1")));
+        assert!(workspace_dir("../etc").is_err());
+        let headers = "HTTP/1.1 301 Moved\r\nContent-Length: 0\r\n\r\nHTTP/1.1 200 OK\r\ncontent-length: 4096\r\n";
+        assert_eq!(content_length(headers), Some(4096));
+    }
+}
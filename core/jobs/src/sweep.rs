@@ -0,0 +1,582 @@
+//! Hyperparameter sweeps
+//! A spec with a `SweepSpec` is expanded into trials, each a child job with its own parameter
+//! values. Trials are spread over the sweep's target nodes within its cost budget, report
+//! metrics on stdout, and the best trial by the sweep's metric wins.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use log::{info, warn};
+
+pub use eryzaa_protocol::job::{ParameterSpace, SweepGoal, SweepSpec};
+
+use crate::exec::docker_command;
+use crate::spec::JobSpec;
+use crate::{JobManager, JobRecord, JobStatus, SshConfigWriter};
+
+/// Trials report a metric by printing "eryzaa-metric val_loss=0.231"; the last value counts
+pub const METRIC_PREFIX: &str = "eryzaa-metric ";
+
+/// A node trials run on
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SweepTarget {
+    pub address: Option<String>, // Overlay IP; None is this machine
+    pub ssh_user: Option<String>,
+    pub price_per_hour: f32,
+    pub slots: u32, // Trials run there at once
+}
+
+impl SweepTarget {
+    /// This machine, one trial at a time, free
+    pub fn local() -> Self {
+        Self { address: None, ssh_user: None, price_per_hour: 0.0, slots: 1 }
+    }
+
+    /// Parse "[user@]address[:price[:slots]]", where address "local" is this machine
+    pub fn parse(target: &str) -> Result<Self, String> {
+        let mut parts = target.split(':');
+        let host = parts.next().unwrap_or_default();
+        let price = parts.next().map(|p| p.parse::<f32>()).transpose();
+        let slots = parts.next().map(|s| s.parse::<u32>()).transpose();
+        let (Ok(price), Ok(slots)) = (price, slots) else {
+            return Err(format!("Invalid target '{}', expected [user@]address[:price[:slots]]", target));
+        };
+
+        let (ssh_user, address) = match host.split_once('@') {
+            Some((user, address)) => (Some(user.to_string()), address),
+            None => (None, host),
+        };
+        if address.is_empty() {
+            return Err(format!("Target '{}' has no address", target));
+        }
+        Ok(Self {
+            address: (address != "local").then(|| address.to_string()),
+            ssh_user,
+            price_per_hour: price.unwrap_or(0.0),
+            slots: slots.unwrap_or(1).max(1),
+        })
+    }
+
+    pub fn label(&self) -> String {
+        self.address.clone().unwrap_or_else(|| "local".to_string())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum TrialState {
+    Waiting,
+    Running,
+    Succeeded,
+    Failed(String),
+    Skipped(String), // Never started, e.g. when the budget ran out
+}
+
+impl TrialState {
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, TrialState::Waiting | TrialState::Running)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Trial {
+    pub index: u32,
+    pub params: BTreeMap<String, String>,
+    pub state: TrialState,
+    #[serde(default)]
+    pub target: Option<usize>, // Index into the run's targets once started
+    #[serde(default)]
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub metrics: BTreeMap<String, f64>,
+}
+
+impl Trial {
+    /// Hours the trial has run up to `now`
+    pub fn hours(&self, now: chrono::DateTime<chrono::Utc>) -> f64 {
+        match self.started_at {
+            Some(started) => (self.finished_at.unwrap_or(now) - started).num_seconds().max(0) as f64 / 3600.0,
+            None => 0.0,
+        }
+    }
+}
+
+/// One submission of a sweep and its trials
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepRun {
+    pub run_id: String,
+    pub client_id: String,
+    pub spec: JobSpec,
+    pub targets: Vec<SweepTarget>,
+    pub trials: Vec<Trial>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl SweepRun {
+    /// Expand a spec's sweep into trials; no targets means this machine
+    pub fn new(spec: JobSpec, client_id: &str, targets: Vec<SweepTarget>) -> Result<Self, String> {
+        let sweep = spec
+            .sweep
+            .as_ref()
+            .ok_or_else(|| format!("Spec '{}' declares no sweep", spec.name))?;
+        if sweep.metric.is_empty() {
+            return Err("Sweep needs a metric to compare trials by".to_string());
+        }
+        let trials = expand(sweep)?
+            .into_iter()
+            .enumerate()
+            .map(|(index, params)| Trial {
+                index: index as u32,
+                params,
+                state: TrialState::Waiting,
+                target: None,
+                started_at: None,
+                finished_at: None,
+                metrics: BTreeMap::new(),
+            })
+            .collect();
+        Ok(Self {
+            run_id: uuid::Uuid::new_v4().to_string()[..8].to_string(),
+            client_id: client_id.to_string(),
+            targets: if targets.is_empty() { vec![SweepTarget::local()] } else { targets },
+            spec,
+            trials,
+            created_at: chrono::Utc::now(),
+        })
+    }
+
+    pub fn sweep(&self) -> SweepSpec {
+        self.spec.sweep.clone().unwrap_or_default()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.trials.iter().all(|trial| trial.state.is_finished())
+    }
+
+    /// Job id (and container name) of a trial
+    pub fn trial_job_id(&self, trial: &Trial) -> String {
+        format!("sweep-{}-{}", self.run_id, trial.index)
+    }
+
+    /// The spec a trial runs: parameters in its environment and substituted into its command
+    pub fn trial_spec(&self, trial: &Trial) -> JobSpec {
+        let mut spec = self.spec.clone();
+        spec.name = self.trial_job_id(trial);
+        spec.sweep = None;
+        spec.restart = None;
+        for (name, value) in &trial.params {
+            spec.env.insert(name.clone(), value.clone());
+            let placeholder = format!("${{{}}}", name);
+            for arg in spec.command.iter_mut() {
+                *arg = arg.replace(&placeholder, value);
+            }
+        }
+        spec
+    }
+
+    /// What the trials have cost up to `now`
+    pub fn spent(&self, now: chrono::DateTime<chrono::Utc>) -> f32 {
+        self.trials
+            .iter()
+            .filter_map(|trial| Some(trial.hours(now) as f32 * self.targets.get(trial.target?)?.price_per_hour))
+            .sum()
+    }
+
+    /// Succeeded trial with the best value of the sweep's metric
+    pub fn best(&self) -> Option<&Trial> {
+        let sweep = self.sweep();
+        let value = |trial: &Trial| trial.metrics.get(&sweep.metric).copied();
+        let scored = self
+            .trials
+            .iter()
+            .filter(|trial| trial.state == TrialState::Succeeded)
+            .filter_map(|trial| Some((trial, value(trial)?)));
+        match sweep.goal {
+            SweepGoal::Minimize => scored.min_by(|a, b| a.1.total_cmp(&b.1)),
+            SweepGoal::Maximize => scored.max_by(|a, b| a.1.total_cmp(&b.1)),
+        }
+        .map(|(trial, _)| trial)
+    }
+
+    /// Hours a trial is expected to take: the time limit, else the average of finished trials
+    fn expected_hours(&self, now: chrono::DateTime<chrono::Utc>) -> Option<f64> {
+        if let Some(minutes) = self.sweep().max_trial_minutes {
+            return Some(minutes as f64 / 60.0);
+        }
+        let finished: Vec<f64> = self
+            .trials
+            .iter()
+            .filter(|trial| trial.state == TrialState::Succeeded)
+            .map(|trial| trial.hours(now))
+            .collect();
+        (!finished.is_empty()).then(|| finished.iter().sum::<f64>() / finished.len() as f64)
+    }
+
+    /// Whether one more trial on `target` still fits the budget, counting what running trials
+    /// are expected to add
+    ///
+    /// Without a time limit or a finished trial to go by, the first trial runs alone.
+    fn fits_budget(&self, target: usize, now: chrono::DateTime<chrono::Utc>) -> bool {
+        let Some(budget) = self.sweep().budget else {
+            return true;
+        };
+        let running: Vec<&Trial> = self.trials.iter().filter(|trial| trial.state == TrialState::Running).collect();
+        let Some(expected) = self.expected_hours(now) else {
+            return running.is_empty() && self.spent(now) < budget;
+        };
+        let price = |index: Option<usize>| index.and_then(|i| self.targets.get(i)).map(|t| t.price_per_hour).unwrap_or(0.0);
+        let remaining: f32 = running
+            .iter()
+            .map(|trial| (expected - trial.hours(now)).max(0.0) as f32 * price(trial.target))
+            .sum();
+        self.spent(now) + remaining + expected as f32 * price(Some(target)) <= budget
+    }
+}
+
+/// Parameter values of every trial: grid points in order, random spaces sampled per trial
+pub fn expand(sweep: &SweepSpec) -> Result<Vec<BTreeMap<String, String>>, String> {
+    let mut grid: Vec<BTreeMap<String, String>> = vec![BTreeMap::new()];
+    for (name, space) in &sweep.parameters {
+        match space {
+            ParameterSpace::Grid { values } | ParameterSpace::Choice { values } if values.is_empty() => {
+                return Err(format!("Parameter '{}' has no values", name));
+            }
+            ParameterSpace::Uniform { min, max } | ParameterSpace::LogUniform { min, max } if min > max => {
+                return Err(format!("Parameter '{}' has min above max", name));
+            }
+            ParameterSpace::LogUniform { min, .. } if *min <= 0.0 => {
+                return Err(format!("Parameter '{}' needs a positive min for a log scale", name));
+            }
+            ParameterSpace::Grid { values } => {
+                grid = grid
+                    .into_iter()
+                    .flat_map(|point| {
+                        values.iter().map(move |value| {
+                            let mut point = point.clone();
+                            point.insert(name.clone(), value_string(value));
+                            point
+                        })
+                    })
+                    .collect();
+            }
+            _ => {}
+        }
+    }
+
+    let count = sweep.trials.unwrap_or(grid.len() as u32) as usize;
+    let mut rng = SplitMix64(sweep.seed.unwrap_or_else(|| chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64));
+    Ok((0..count)
+        .map(|i| {
+            let mut params = grid[i % grid.len()].clone();
+            for (name, space) in &sweep.parameters {
+                let value = match space {
+                    ParameterSpace::Grid { .. } => continue,
+                    ParameterSpace::Choice { values } => value_string(&values[(rng.next() * values.len() as f64) as usize % values.len()]),
+                    ParameterSpace::Uniform { min, max } => format!("{}", min + rng.next() * (max - min)),
+                    ParameterSpace::LogUniform { min, max } => format!("{}", (min.ln() + rng.next() * (max.ln() - min.ln())).exp()),
+                };
+                params.insert(name.clone(), value);
+            }
+            params
+        })
+        .collect())
+}
+
+fn value_string(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Small deterministic generator, so a seeded sweep expands the same way everywhere
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    /// Uniform in [0, 1)
+    fn next(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        (z ^ (z >> 31)) as f64 / (u64::MAX as f64 + 1.0)
+    }
+}
+
+/// Last value of each metric printed in a trial's log
+pub fn parse_metrics(log: &str) -> BTreeMap<String, f64> {
+    log.lines()
+        .filter_map(|line| line.trim().strip_prefix(METRIC_PREFIX)?.split_once('='))
+        .filter_map(|(name, value)| Some((name.trim().to_string(), value.trim().parse().ok()?)))
+        .collect()
+}
+
+/// Sweep runs known to this machine
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SweepStore {
+    pub runs: Vec<SweepRun>,
+}
+
+impl SweepStore {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize sweeps: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write sweeps {}: {}", path.display(), e))
+    }
+
+    pub fn get_mut(&mut self, run_id: &str) -> Option<&mut SweepRun> {
+        self.runs.iter_mut().find(|run| run.run_id == run_id)
+    }
+}
+
+impl JobManager {
+    /// Move a sweep forward: collect metrics, finish or time out running trials, and start
+    /// waiting ones on targets with a free slot while the budget allows
+    ///
+    /// `can_start` lets the scheduler hold a local trial back; returns whether anything changed.
+    pub fn advance_sweep(&self, run: &mut SweepRun, can_start: &mut dyn FnMut(&JobSpec) -> bool) -> Result<bool, String> {
+        let before = run.trials.clone();
+        let now = chrono::Utc::now();
+        let sweep = run.sweep();
+
+        for i in 0..run.trials.len() {
+            if run.trials[i].state != TrialState::Running {
+                continue;
+            }
+            let job_id = run.trial_job_id(&run.trials[i]);
+            let Some(record) = self.get_job(&job_id) else {
+                run.trials[i].state = TrialState::Failed("Job record is missing".to_string());
+                continue;
+            };
+            if let Ok(log) = docker_output(&record, &["logs", &record.container_name]) {
+                run.trials[i].metrics.extend(parse_metrics(&log));
+            }
+
+            let timed_out = sweep
+                .max_trial_minutes
+                .is_some_and(|minutes| run.trials[i].hours(now) * 60.0 > minutes as f64);
+            let state = match exit_code(&record)? {
+                Some(0) => TrialState::Succeeded,
+                Some(code) => TrialState::Failed(format!("Exited with code {}", code)),
+                None if timed_out => {
+                    let _ = docker_output(&record, &["rm", "-f", &record.container_name]);
+                    TrialState::Failed(format!("Stopped after {} minutes", sweep.max_trial_minutes.unwrap_or_default()))
+                }
+                None => continue,
+            };
+            self.finish_trial(run, i, state, now);
+        }
+
+        // Out of money: stop what runs and start nothing more
+        if sweep.budget.is_some_and(|budget| run.spent(now) >= budget) {
+            for i in 0..run.trials.len() {
+                match run.trials[i].state {
+                    TrialState::Running => {
+                        if let Some(record) = self.get_job(&run.trial_job_id(&run.trials[i])) {
+                            let _ = docker_output(&record, &["rm", "-f", &record.container_name]);
+                        }
+                        self.finish_trial(run, i, TrialState::Failed("Budget exhausted".to_string()), now);
+                    }
+                    TrialState::Waiting => run.trials[i].state = TrialState::Skipped("Budget exhausted".to_string()),
+                    _ => {}
+                }
+            }
+        }
+
+        for i in 0..run.trials.len() {
+            if run.trials[i].state != TrialState::Waiting {
+                continue;
+            }
+            let busy = |target: usize| {
+                run.trials
+                    .iter()
+                    .filter(|trial| trial.state == TrialState::Running && trial.target == Some(target))
+                    .count() as u32
+            };
+            let Some(target) = (0..run.targets.len()).find(|&t| busy(t) < run.targets[t].slots) else {
+                break;
+            };
+            if !run.fits_budget(target, now) {
+                break;
+            }
+
+            let spec = run.trial_spec(&run.trials[i]);
+            let started = match &run.targets[target].address {
+                None if !can_start(&spec) => break,
+                None => self.run_spec(&spec, &run.client_id),
+                Some(address) => {
+                    let mut record = JobRecord::new(&spec.name, &run.client_id, &spec.image_tag());
+                    record.container_name = spec.name.clone();
+                    record.node_address = Some(address.clone());
+                    record.ssh_user = run.targets[target].ssh_user.clone();
+                    let _ = docker_output(&record, &["rm", "-f", &spec.name]);
+                    let result = docker_output(&record, &spec.run_args()).map(|_| ());
+                    if result.is_ok() {
+                        record.host_key = record.connect_host().and_then(|host| SshConfigWriter::for_current_user().pinned_key(&host));
+                        record.status = JobStatus::Running;
+                        self.register_job(record);
+                    }
+                    result
+                }
+            };
+
+            let trial = &mut run.trials[i];
+            match started {
+                Ok(()) => {
+                    info!("Sweep {} trial {} started on {} with {:?}", run.run_id, trial.index, run.targets[target].label(), trial.params);
+                    trial.state = TrialState::Running;
+                    trial.target = Some(target);
+                    trial.started_at = Some(now);
+                }
+                Err(e) => {
+                    warn!("Sweep {} trial {} failed to start: {}", run.run_id, trial.index, e);
+                    trial.state = TrialState::Failed(e);
+                }
+            }
+        }
+
+        Ok(run.trials != before)
+    }
+
+    fn finish_trial(&self, run: &mut SweepRun, index: usize, state: TrialState, now: chrono::DateTime<chrono::Utc>) {
+        let job_id = run.trial_job_id(&run.trials[index]);
+        let status = match &state {
+            TrialState::Succeeded => JobStatus::Completed,
+            TrialState::Failed(reason) | TrialState::Skipped(reason) => JobStatus::Failed(reason.clone()),
+            _ => JobStatus::Stopped,
+        };
+        let _ = self.update_status(&job_id, status);
+        let _ = self.release_scratch(&job_id);
+
+        let trial = &mut run.trials[index];
+        info!("Sweep {} trial {}: {:?}", run.run_id, trial.index, state);
+        trial.state = state;
+        trial.finished_at = Some(now);
+    }
+
+    /// Stop a sweep's running trials and skip the ones not started
+    pub fn cancel_sweep(&self, run: &mut SweepRun) {
+        let now = chrono::Utc::now();
+        for i in 0..run.trials.len() {
+            match run.trials[i].state {
+                TrialState::Running => {
+                    if let Some(record) = self.get_job(&run.trial_job_id(&run.trials[i])) {
+                        let _ = docker_output(&record, &["rm", "-f", &record.container_name]);
+                    }
+                    self.finish_trial(run, i, TrialState::Failed("Cancelled".to_string()), now);
+                }
+                TrialState::Waiting => run.trials[i].state = TrialState::Skipped("Cancelled".to_string()),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Run docker on the trial's node, returning what it printed
+fn docker_output<S: AsRef<str>>(record: &JobRecord, args: &[S]) -> Result<String, String> {
+    let args: Vec<String> = args.iter().map(|a| a.as_ref().to_string()).collect();
+    let output = docker_command(record, &args, false)?
+        .output()
+        .map_err(|e| format!("Failed to execute docker {}: {}", args[0], e))?;
+    if !output.status.success() {
+        return Err(format!("docker {} failed: {}", args[0], String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    // Trials log to either stream
+    Ok(format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr)))
+}
+
+/// Exit code of a finished trial container, None while it is still running
+fn exit_code(record: &JobRecord) -> Result<Option<i32>, String> {
+    let state = match docker_output(record, &["inspect", "-f", "{{.State.Status}} {{.State.ExitCode}}", &record.container_name]) {
+        Ok(state) => state,
+        // The container is gone; treat it like a crash
+        Err(_) => return Ok(Some(-1)),
+    };
+    let mut fields = state.split_whitespace();
+    match (fields.next(), fields.next().and_then(|code| code.parse().ok())) {
+        (Some("exited" | "dead"), Some(code)) => Ok(Some(code)),
+        _ => Ok(None),
+    }
+}
+
+/// Sweep runs of the active profile, next to the job registry
+pub fn default_store_path() -> PathBuf {
+    crate::default_registry_path().with_file_name("sweeps.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &str = r#"
+name: train
+image: pytorch/pytorch
+command: ["python", "train.py", "--lr", "${lr}"]
+sweep:
+  metric: val_loss
+  trials: 6
+  seed: 7
+  budget: 8.0
+  max_trial_minutes: 60
+  parameters:
+    batch: { type: grid, values: [32, 64] }
+    lr: { type: log_uniform, min: 0.0001, max: 0.1 }
+"#;
+
+    #[test]
+    fn test_sweep_expansion_and_best_trial() {
+        let spec: JobSpec = serde_yaml::from_str(SPEC).unwrap();
+        let targets = vec![SweepTarget::parse("job7@10.242.1.5:2.5:2").unwrap(), SweepTarget::parse("local").unwrap()];
+        assert_eq!(targets[0].ssh_user.as_deref(), Some("job7"));
+        assert_eq!((targets[0].price_per_hour, targets[0].slots), (2.5, 2));
+        assert_eq!(targets[1], SweepTarget::local());
+        let mut run = SweepRun::new(spec.clone(), "client-1", targets).unwrap();
+
+        assert_eq!(run.trials.len(), 6);
+        let batches: Vec<&str> = run.trials.iter().map(|t| t.params["batch"].as_str()).collect();
+        assert_eq!(batches, vec!["32", "64", "32", "64", "32", "64"]);
+        let lr: f64 = run.trials[0].params["lr"].parse().unwrap();
+        assert!((0.0001..=0.1).contains(&lr));
+        assert_eq!(SweepRun::new(spec, "client-1", vec![]).unwrap().trials[0].params, run.trials[0].params);
+
+        let trial_spec = run.trial_spec(&run.trials[0]);
+        assert_eq!(trial_spec.command[3], run.trials[0].params["lr"]);
+        assert_eq!(trial_spec.env["batch"], "32");
+        assert!(trial_spec.sweep.is_none());
+
+        // Two one-hour trials at 2.5/h are half done; a third brings the projection to 7.5, a fourth to 10
+        let now = chrono::Utc::now();
+        for (i, loss) in [(0, 0.4), (1, 0.3)] {
+            let trial = &mut run.trials[i];
+            trial.state = TrialState::Running;
+            trial.target = Some(0);
+            trial.started_at = Some(now - chrono::Duration::minutes(30));
+            trial.metrics.insert("val_loss".to_string(), loss);
+        }
+        assert!(run.fits_budget(0, now));
+        run.trials[2].state = TrialState::Running;
+        run.trials[2].target = Some(0);
+        run.trials[2].started_at = Some(now);
+        assert!(!run.fits_budget(0, now));
+
+        assert!(run.best().is_none());
+        run.trials[0].state = TrialState::Succeeded;
+        run.trials[1].state = TrialState::Succeeded;
+        assert_eq!(run.best().map(|t| t.index), Some(1));
+        assert_eq!(parse_metrics("epoch 1\neryzaa-metric val_loss=0.5\neryzaa-metric val_loss=0.25\n")["val_loss"], 0.25);
+    }
+}
@@ -0,0 +1,220 @@
+//! Terminals and copyable commands for each client platform
+//! Opening a shell on a node starts whichever terminal the client's desktop has, running a command
+//! quoted for the shell inside it: bash on Linux and macOS, PowerShell on Windows. The ssh and scp
+//! commands offered for copying are quoted the same way, so they paste into that shell as they are.
+
+use std::path::Path;
+use std::process::Command;
+
+use crate::exec::shell_quote;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Platform {
+    Linux,
+    MacOs,
+    Windows,
+}
+
+impl Platform {
+    pub fn current() -> Self {
+        if cfg!(windows) {
+            Self::Windows
+        } else if cfg!(target_os = "macos") {
+            Self::MacOs
+        } else {
+            Self::Linux
+        }
+    }
+
+    /// `arg` quoted for this platform's shell, untouched when it needs no quoting
+    pub fn quote(&self, arg: &str) -> String {
+        match self {
+            Self::Windows if !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./@:=\\".contains(c)) => arg.to_string(),
+            Self::Windows => format!("'{}'", arg.replace('\'', "''")),
+            Self::Linux | Self::MacOs => shell_quote(arg),
+        }
+    }
+
+    /// `command` as one line for this platform's shell
+    pub fn command_line(&self, command: &Command) -> String {
+        let program = self.quote(&command.get_program().to_string_lossy());
+        // PowerShell only runs a quoted program through the call operator
+        let mut parts = vec![match self {
+            Self::Windows if program.starts_with('\'') => format!("& {}", program),
+            _ => program,
+        }];
+        parts.extend(command.get_args().map(|arg| self.quote(&arg.to_string_lossy())));
+        parts.join(" ")
+    }
+
+    /// ssh into `user` at `host`, with `options` such as `SshConfigWriter::host_key_args`
+    pub fn ssh_snippet(&self, user: &str, host: &str, options: &[String]) -> String {
+        let mut command = Command::new("ssh");
+        command.args(options).arg(format!("{}@{}", user, host));
+        self.command_line(&command)
+    }
+
+    /// Copy `local_file` into `remote_dir` of `user` at `host`
+    pub fn scp_snippet(&self, local_file: &str, user: &str, host: &str, remote_dir: &str, options: &[String]) -> String {
+        // scp would read the colons of an IPv6 address as the end of the host
+        let host = match host.contains(':') {
+            true => format!("[{}]", host),
+            false => host.to_string(),
+        };
+        let mut command = Command::new("scp");
+        command.args(options).arg(local_file).arg(format!("{}@{}:{}", user, host, remote_dir));
+        self.command_line(&command)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Terminal {
+    WindowsTerminal,
+    PowerShell,
+    MacTerminal,
+    ITerm,
+    GnomeTerminal,
+    Konsole,
+    Xfce,
+    Kitty,
+    Alacritty,
+    XTerminalEmulator,
+    XTerm,
+}
+
+/// Linux terminals in the order they are tried; the desktops' own come first
+const LINUX_TERMINALS: [Terminal; 7] = [
+    Terminal::GnomeTerminal,
+    Terminal::Konsole,
+    Terminal::Xfce,
+    Terminal::Kitty,
+    Terminal::Alacritty,
+    Terminal::XTerminalEmulator,
+    Terminal::XTerm,
+];
+
+impl Terminal {
+    /// The terminal to open on `platform`, given which programs `installed` finds
+    pub fn detect(platform: Platform, installed: &dyn Fn(&str) -> bool) -> Option<Self> {
+        match platform {
+            Platform::Windows if installed("wt") => Some(Self::WindowsTerminal),
+            Platform::Windows => Some(Self::PowerShell),
+            Platform::MacOs if installed("/Applications/iTerm.app") => Some(Self::ITerm),
+            Platform::MacOs => Some(Self::MacTerminal),
+            Platform::Linux => LINUX_TERMINALS.into_iter().find(|terminal| installed(terminal.program())),
+        }
+    }
+
+    pub fn program(&self) -> &'static str {
+        match self {
+            Self::WindowsTerminal => "wt",
+            Self::PowerShell => "powershell",
+            Self::MacTerminal | Self::ITerm => "osascript",
+            Self::GnomeTerminal => "gnome-terminal",
+            Self::Konsole => "konsole",
+            Self::Xfce => "xfce4-terminal",
+            Self::Kitty => "kitty",
+            Self::Alacritty => "alacritty",
+            Self::XTerminalEmulator => "x-terminal-emulator",
+            Self::XTerm => "xterm",
+        }
+    }
+
+    /// Arguments opening this terminal titled `title`, running `line` and leaving a shell open after it
+    pub fn args(&self, title: &str, line: &str) -> Vec<String> {
+        let posix = format!("echo {}; {}; exec bash", Platform::Linux.quote(title), line);
+        let bash = |before: &[&str]| {
+            let mut args: Vec<String> = before.iter().map(|arg| arg.to_string()).collect();
+            args.extend(["bash".to_string(), "-c".to_string(), posix.clone()]);
+            args
+        };
+        let powershell = format!("$Host.UI.RawUI.WindowTitle = {}; {}", Platform::Windows.quote(title), line);
+
+        match self {
+            // wt splits its own commands at semicolons unless they are escaped
+            Self::WindowsTerminal => ["new-tab", "--title", title, "powershell", "-NoExit", "-Command", &powershell.replace(';', "\\;")]
+                .map(String::from)
+                .to_vec(),
+            Self::PowerShell => ["-NoExit", "-Command", &powershell].map(String::from).to_vec(),
+            Self::MacTerminal => vec![
+                "-e".to_string(),
+                format!("tell application \"Terminal\" to do script \"{}\"", applescript_escape(&posix)),
+                "-e".to_string(),
+                "tell application \"Terminal\" to activate".to_string(),
+            ],
+            Self::ITerm => vec![
+                "-e".to_string(),
+                format!(
+                    "tell application \"iTerm\" to create window with default profile command \"{}\"",
+                    applescript_escape(&format!("bash -c {}", Platform::MacOs.quote(&posix)))
+                ),
+            ],
+            Self::GnomeTerminal => bash(&["--title", title, "--"]),
+            Self::Xfce => bash(&["--title", title, "-x"]),
+            Self::Kitty => bash(&["--title", title]),
+            Self::Alacritty => bash(&["--title", title, "-e"]),
+            Self::XTerm => bash(&["-T", title, "-e"]),
+            Self::Konsole | Self::XTerminalEmulator => bash(&["-e"]),
+        }
+    }
+}
+
+fn applescript_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Whether `program` is on PATH, or exists when given as a path
+pub fn installed(program: &str) -> bool {
+    if Path::new(program).is_absolute() {
+        return Path::new(program).exists();
+    }
+    let suffix = if cfg!(windows) { ".exe" } else { "" };
+    std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).any(|dir| dir.join(format!("{}{}", program, suffix)).is_file()))
+        .unwrap_or(false)
+}
+
+/// Open a terminal titled `title` running `line`, a command for this platform's shell
+pub fn launch(title: &str, line: &str) -> Result<(), String> {
+    let terminal = Terminal::detect(Platform::current(), &installed)
+        .ok_or_else(|| "No terminal found; install gnome-terminal, konsole or xterm".to_string())?;
+    Command::new(terminal.program())
+        .args(terminal.args(title, line))
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to start {}: {}", terminal.program(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snippets_per_platform() {
+        let options = vec!["-o".to_string(), "UserKnownHostsFile=C:\\Users\\Jo Doe\\.ssh\\eryzaa_known_hosts".to_string()];
+        assert_eq!(
+            Platform::Windows.ssh_snippet("job_1", "10.0.0.5", &options),
+            "ssh -o 'UserKnownHostsFile=C:\\Users\\Jo Doe\\.ssh\\eryzaa_known_hosts' job_1@10.0.0.5"
+        );
+        assert_eq!(Platform::Windows.quote("it's"), "'it''s'");
+        assert_eq!(Platform::Linux.quote("it's"), "'it'\\''s'");
+        assert_eq!(
+            Platform::Linux.scp_snippet("my file.txt", "job_1", "fd00::5", "/home/job_1/", &[]),
+            "scp 'my file.txt' 'job_1@[fd00::5]:/home/job_1/'"
+        );
+    }
+
+    #[test]
+    fn test_terminal_detection() {
+        let only = |name: &'static str| move |program: &str| program == name;
+        assert_eq!(Terminal::detect(Platform::Linux, &only("xterm")), Some(Terminal::XTerm));
+        assert_eq!(Terminal::detect(Platform::Linux, &|_| false), None);
+        assert_eq!(Terminal::detect(Platform::Windows, &|_| false), Some(Terminal::PowerShell));
+        assert_eq!(Terminal::detect(Platform::MacOs, &only("/Applications/iTerm.app")), Some(Terminal::ITerm));
+
+        let args = Terminal::GnomeTerminal.args("Eryzaa", "ssh job_1@10.0.0.5");
+        assert_eq!(args, vec!["--title", "Eryzaa", "--", "bash", "-c", "echo Eryzaa; ssh job_1@10.0.0.5; exec bash"]);
+        let args = Terminal::WindowsTerminal.args("Eryzaa", "ssh job_1@10.0.0.5");
+        assert_eq!(args.last().unwrap(), "$Host.UI.RawUI.WindowTitle = Eryzaa\\; ssh job_1@10.0.0.5");
+    }
+}
@@ -0,0 +1,75 @@
+//! Session cost ticker
+//! What a running job has run up so far, for the live tickers in the client and rental GUIs:
+//! billable time, the energy the node has metered and the cost at the agreed rate. It is built
+//! from the same record and figures as the receipt, so neither side is surprised at settlement.
+
+use crate::JobRecord;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostTicker {
+    pub billable_seconds: u64,
+    pub energy_kwh: f64,
+    pub rate: Option<f32>, // Agreed AVAX per hour, None when the job runs unpriced
+    pub trial: bool,
+    pub accrued: f32, // AVAX so far, the cache fee for a cached answer
+}
+
+impl JobRecord {
+    /// The job's ticker as of `now`
+    pub fn ticker(&self, now: chrono::DateTime<chrono::Utc>) -> CostTicker {
+        let billable_seconds = self.billable_seconds(now);
+        let rate = self.offered_price.filter(|_| self.trial.is_none());
+        let accrued = match self.cache_fee {
+            Some(fee) => fee,
+            None => rate.unwrap_or(0.0) * billable_seconds as f32 / 3600.0,
+        };
+        CostTicker {
+            billable_seconds,
+            energy_kwh: self.energy_wh / 1000.0,
+            rate,
+            trial: self.trial.is_some(),
+            accrued,
+        }
+    }
+}
+
+impl std::fmt::Display for CostTicker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "⏱ {} · ⚡ {:.3} kWh · ", format_elapsed(self.billable_seconds), self.energy_kwh)?;
+        match (self.trial, self.rate) {
+            (true, _) => write!(f, "free trial"),
+            (false, Some(rate)) => write!(f, "{:.4} AVAX at {:.2}/h", self.accrued, rate),
+            (false, None) if self.accrued > 0.0 => write!(f, "{:.4} AVAX", self.accrued),
+            (false, None) => write!(f, "no price agreed"),
+        }
+    }
+}
+
+/// Elapsed time as a ticker shows it, e.g. "1:02:05"
+pub fn format_elapsed(seconds: u64) -> String {
+    format!("{}:{:02}:{:02}", seconds / 3600, (seconds % 3600) / 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TrialOffer;
+
+    #[test]
+    fn test_cost_ticker() {
+        let mut job = JobRecord::new("abc", "client-1", "ubuntu:22.04");
+        job.started_marker = None;
+        job.offered_price = Some(1.2);
+        job.energy_wh = 250.0;
+        let now = job.created_at + chrono::Duration::seconds(1800);
+        let ticker = job.ticker(now);
+        assert_eq!(ticker.accrued, 0.6);
+        assert_eq!(ticker.to_string(), "⏱ 0:30:00 · ⚡ 0.250 kWh · 0.6000 AVAX at 1.20/h");
+        assert_eq!(format_elapsed(3725), "1:02:05");
+
+        job.trial = Some(TrialOffer::default());
+        let trial = job.ticker(now);
+        assert!(trial.rate.is_none() && trial.accrued == 0.0);
+        assert!(trial.to_string().ends_with("free trial"));
+    }
+}
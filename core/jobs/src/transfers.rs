@@ -0,0 +1,327 @@
+//! Transfer ledger for rentals
+//! Cumulative per-job byte counters are folded into daily totals per destination class
+//! (overlay or internet), which both GUIs show, export and check for unusual egress. Inputs the
+//! node downloads for a job before it starts are metered on their own, as staging traffic.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use log::warn;
+
+use crate::JobManager;
+
+/// Where a rental node publishes each job's ledger for its tenant to read
+pub const PUBLISH_DIR: &str = "/var/lib/eryzaa/transfers";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum DestinationClass {
+    Overlay,
+    Internet,
+    Staging, // Job inputs fetched by the node itself, before the container starts
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TransferEntry {
+    pub job_id: String,
+    pub day: chrono::NaiveDate,
+    pub class: DestinationClass,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+/// Transfer totals of one day across all jobs
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DailyTransfers {
+    pub day: Option<chrono::NaiveDate>,
+    pub overlay_rx: u64,
+    pub overlay_tx: u64,
+    pub internet_rx: u64,
+    pub internet_tx: u64,
+    pub staging_rx: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct EgressAlertPolicy {
+    pub multiplier: f64,    // Alert when today's egress exceeds the baseline by this factor
+    pub min_bytes: u64,     // Never alert below this volume
+    pub baseline_days: i64, // Days averaged into the baseline
+}
+
+impl Default for EgressAlertPolicy {
+    fn default() -> Self {
+        Self {
+            multiplier: 3.0,
+            min_bytes: 1 << 30,
+            baseline_days: 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EgressAlert {
+    pub job_id: String,
+    pub today_bytes: u64,
+    pub baseline_bytes: u64, // Average daily internet egress over the baseline window
+}
+
+impl std::fmt::Display for EgressAlert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Job {} sent {} to the internet today (usual: {}/day)",
+            self.job_id,
+            format_bytes(self.today_bytes),
+            format_bytes(self.baseline_bytes)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TransferLedger {
+    entries: Vec<TransferEntry>,
+    #[serde(default)]
+    last_totals: HashMap<String, (u64, u64)>, // "job:class" -> last cumulative (rx, tx)
+}
+
+impl TransferLedger {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize transfer ledger: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write transfer ledger {}: {}", path.display(), e))
+    }
+
+    /// Add the traffic behind a cumulative counter reading to `day`
+    ///
+    /// A reading lower than the previous one means the counter was reset.
+    pub fn record_totals(&mut self, job_id: &str, class: DestinationClass, rx_total: u64, tx_total: u64, day: chrono::NaiveDate) {
+        let key = format!("{}:{:?}", job_id, class);
+        let (last_rx, last_tx) = self.last_totals.get(&key).copied().unwrap_or((0, 0));
+        let rx = if rx_total >= last_rx { rx_total - last_rx } else { rx_total };
+        let tx = if tx_total >= last_tx { tx_total - last_tx } else { tx_total };
+        self.last_totals.insert(key, (rx_total, tx_total));
+
+        if rx == 0 && tx == 0 {
+            return;
+        }
+
+        match self
+            .entries
+            .iter_mut()
+            .find(|e| e.job_id == job_id && e.day == day && e.class == class)
+        {
+            Some(entry) => {
+                entry.rx_bytes += rx;
+                entry.tx_bytes += tx;
+            }
+            None => self.entries.push(TransferEntry {
+                job_id: job_id.to_string(),
+                day,
+                class,
+                rx_bytes: rx,
+                tx_bytes: tx,
+            }),
+        }
+    }
+
+    pub fn entries(&self) -> &[TransferEntry] {
+        &self.entries
+    }
+
+    pub fn for_job(&self, job_id: &str) -> Vec<TransferEntry> {
+        self.entries.iter().filter(|e| e.job_id == job_id).cloned().collect()
+    }
+
+    /// Replace everything known about a job with entries fetched from its node
+    pub fn merge_job(&mut self, job_id: &str, entries: Vec<TransferEntry>) {
+        self.entries.retain(|e| e.job_id != job_id);
+        self.entries.extend(entries.into_iter().filter(|e| e.job_id == job_id));
+    }
+
+    /// Totals per day, oldest first
+    pub fn daily_totals(&self) -> Vec<DailyTransfers> {
+        let mut days: BTreeMap<chrono::NaiveDate, DailyTransfers> = BTreeMap::new();
+        for entry in &self.entries {
+            let day = days.entry(entry.day).or_insert_with(|| DailyTransfers {
+                day: Some(entry.day),
+                ..Default::default()
+            });
+            match entry.class {
+                DestinationClass::Overlay => {
+                    day.overlay_rx += entry.rx_bytes;
+                    day.overlay_tx += entry.tx_bytes;
+                }
+                DestinationClass::Internet => {
+                    day.internet_rx += entry.rx_bytes;
+                    day.internet_tx += entry.tx_bytes;
+                }
+                DestinationClass::Staging => day.staging_rx += entry.rx_bytes,
+            }
+        }
+        days.into_values().collect()
+    }
+
+    /// CSV export with one row per job, day and destination class
+    pub fn to_csv(&self) -> String {
+        let mut entries = self.entries.clone();
+        entries.sort_by(|a, b| (a.day, &a.job_id).cmp(&(b.day, &b.job_id)));
+
+        let mut csv = "day,job_id,destination,rx_bytes,tx_bytes\n".to_string();
+        for e in entries {
+            let class = match e.class {
+                DestinationClass::Overlay => "overlay",
+                DestinationClass::Internet => "internet",
+                DestinationClass::Staging => "staging",
+            };
+            csv.push_str(&format!("{},{},{},{},{}\n", e.day, e.job_id, class, e.rx_bytes, e.tx_bytes));
+        }
+        csv
+    }
+
+    /// Jobs whose internet egress today is far above their recent average
+    pub fn egress_alerts(&self, policy: &EgressAlertPolicy, today: chrono::NaiveDate) -> Vec<EgressAlert> {
+        let window_start = today - chrono::Duration::days(policy.baseline_days);
+        let mut per_job: BTreeMap<&str, (u64, u64)> = BTreeMap::new(); // today, baseline window
+
+        for e in self.entries.iter().filter(|e| e.class == DestinationClass::Internet) {
+            let totals = per_job.entry(&e.job_id).or_default();
+            if e.day == today {
+                totals.0 += e.tx_bytes;
+            } else if e.day >= window_start && e.day < today {
+                totals.1 += e.tx_bytes;
+            }
+        }
+
+        per_job
+            .into_iter()
+            .filter_map(|(job_id, (today_bytes, window_bytes))| {
+                let baseline = window_bytes / policy.baseline_days.max(1) as u64;
+                let unusual = today_bytes >= policy.min_bytes
+                    && today_bytes as f64 > baseline as f64 * policy.multiplier;
+                unusual.then(|| EgressAlert {
+                    job_id: job_id.to_string(),
+                    today_bytes,
+                    baseline_bytes: baseline,
+                })
+            })
+            .collect()
+    }
+
+    /// Write each job's entries to `<dir>/<job_id>.json` for its tenant
+    pub fn publish(&self, dir: &Path) -> Result<(), String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+        let mut job_ids: Vec<&str> = self.entries.iter().map(|e| e.job_id.as_str()).collect();
+        job_ids.sort();
+        job_ids.dedup();
+
+        for job_id in job_ids {
+            let path = dir.join(format!("{}.json", job_id));
+            let content = serde_json::to_string(&self.for_job(job_id))
+                .map_err(|e| format!("Failed to serialize transfers: {}", e))?;
+            std::fs::write(&path, content)
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl JobManager {
+    /// Fetch the transfer entries a job's node has published for it
+    pub fn fetch_transfers(&self, job_id: &str) -> Result<Vec<TransferEntry>, String> {
+        let record = self
+            .get_job(job_id)
+            .ok_or_else(|| format!("No job found with id '{}'", job_id))?;
+
+        let path = format!("{}/{}.json", PUBLISH_DIR, job_id);
+        let output = crate::exec::node_command(&record, "cat", &[path], false)?
+            .output()
+            .map_err(|e| format!("Failed to read transfers for job '{}': {}", job_id, e))?;
+        if !output.status.success() {
+            warn!("No transfer data published for job '{}'", job_id);
+            return Ok(vec![]);
+        }
+
+        serde_json::from_slice(&output.stdout)
+            .map_err(|e| format!("Failed to parse transfers for job '{}': {}", job_id, e))
+    }
+}
+
+/// Transfer ledger of the active profile (or of the node, on a rental machine)
+pub fn default_ledger_path() -> PathBuf {
+    crate::default_registry_path().with_file_name("transfers.json")
+}
+
+/// Human-readable byte count, e.g. "1.5 GB"
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> chrono::NaiveDate {
+        chrono::NaiveDate::from_ymd_opt(2024, 5, d).unwrap()
+    }
+
+    #[test]
+    fn test_record_totals_handles_counter_reset() {
+        let mut ledger = TransferLedger::default();
+        ledger.record_totals("abc", DestinationClass::Internet, 100, 1000, day(1));
+        ledger.record_totals("abc", DestinationClass::Internet, 150, 1500, day(1));
+        ledger.record_totals("abc", DestinationClass::Internet, 20, 200, day(2)); // reset
+
+        let totals = ledger.daily_totals();
+        assert_eq!(totals.len(), 2);
+        assert_eq!((totals[0].internet_rx, totals[0].internet_tx), (150, 1500));
+        assert_eq!((totals[1].internet_rx, totals[1].internet_tx), (20, 200));
+    }
+
+    #[test]
+    fn test_egress_alerts() {
+        let mut ledger = TransferLedger::default();
+        for d in 1..=7 {
+            ledger.record_totals("quiet", DestinationClass::Internet, 0, 10 << 30, day(d));
+            ledger.last_totals.clear();
+        }
+        ledger.record_totals("quiet", DestinationClass::Internet, 0, 11 << 30, day(8));
+        ledger.record_totals("noisy", DestinationClass::Internet, 0, 5 << 30, day(8));
+
+        let alerts = ledger.egress_alerts(&EgressAlertPolicy::default(), day(8));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].job_id, "noisy");
+    }
+
+    #[test]
+    fn test_csv_and_format() {
+        let mut ledger = TransferLedger::default();
+        ledger.record_totals("abc", DestinationClass::Overlay, 1, 2, day(1));
+        assert_eq!(ledger.to_csv(), "day,job_id,destination,rx_bytes,tx_bytes\n2024-05-01,abc,overlay,1,2\n");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+    }
+}
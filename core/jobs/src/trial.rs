@@ -0,0 +1,76 @@
+//! Free trial jobs
+//! A client may take a node's advertised free trial once. The node admits it on its own terms,
+//! whatever the client asked for: no price, no GPU, a share of the CPU set with `docker update`
+//! once the container runs, and a time limit after which the container is removed. Time the
+//! trial spends paused does not count, as for billing.
+
+use crate::{JobManager, JobRecord, JobStatus, TrialOffer};
+
+impl JobRecord {
+    /// Seconds of a running trial left at `now`, None for paid jobs
+    pub fn trial_seconds_left(&self, now: chrono::DateTime<chrono::Utc>) -> Option<u64> {
+        let trial = self.trial?;
+        Some((trial.minutes as u64 * 60).saturating_sub(self.billable_seconds(now)))
+    }
+}
+
+impl JobManager {
+    /// Turn a pending job into a free trial on `offer`, dropping any price it was offered at
+    pub fn begin_trial(&self, job_id: &str, offer: TrialOffer) -> Result<(), String> {
+        let mut jobs = self.jobs.lock().unwrap();
+        match jobs.get_mut(job_id) {
+            Some(record) => {
+                record.trial = Some(offer);
+                record.offered_price = None;
+                Ok(())
+            }
+            None => Err(format!("No job found with id '{}'", job_id)),
+        }
+    }
+
+    /// Hold a started trial to its share of the node's `cpu_cores`
+    pub fn confine_trial(&self, job_id: &str, cpu_cores: u32) -> Result<(), String> {
+        let record = self
+            .get_job(job_id)
+            .ok_or_else(|| format!("No job found with id '{}'", job_id))?;
+        let trial = record.trial.ok_or_else(|| format!("Job '{}' is not a free trial", job_id))?;
+
+        let cpus = format!("{:.2}", trial.cpus(cpu_cores));
+        crate::pause::run_docker(&record, &["update", "--cpus", &cpus, &record.container_name])
+    }
+
+    /// Remove the containers of trials that ran out by `now`; returns the jobs ended
+    pub fn end_expired_trials(&self, now: chrono::DateTime<chrono::Utc>) -> Vec<String> {
+        let expired: Vec<String> = self
+            .list_jobs()
+            .into_iter()
+            .filter(|job| job.status == JobStatus::Running && job.trial_seconds_left(now) == Some(0))
+            .map(|job| job.job_id)
+            .collect();
+        expired.into_iter().filter(|job_id| self.cancel_job(job_id).is_ok()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trial_time_left() {
+        let now = chrono::Utc::now();
+        let mut record = JobRecord::new("abc", "client-1", "ubuntu:22.04");
+        record.created_at = now - chrono::Duration::seconds(600);
+        record.started_marker = None;
+        assert_eq!(record.trial_seconds_left(now), None);
+
+        let manager = JobManager::new();
+        record.offered_price = Some(2.5);
+        manager.register_job(record);
+        manager.begin_trial("abc", TrialOffer { minutes: 15, cpu_percent: 25 }).unwrap();
+        let trial = manager.get_job("abc").unwrap();
+        assert_eq!(trial.offered_price, None);
+        assert_eq!(trial.trial_seconds_left(now), Some(300));
+        assert_eq!(trial.trial_seconds_left(now + chrono::Duration::seconds(900)), Some(0));
+        assert!(manager.begin_trial("missing", TrialOffer::default()).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+//! Warm pool
+//! The node owner can keep one idle container of a popular image running, datasets already
+//! mounted, and hand it to the next interactive job for that image. The job then starts in
+//! seconds instead of waiting for the image to pull and the container to initialize.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use log::info;
+
+use crate::{JobManager, JobStatus};
+
+/// Name of the idle container while it waits for a job
+pub const WARM_CONTAINER: &str = "eryzaa-warm";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct WarmPool {
+    pub enabled: bool,
+    pub image: String,
+    #[serde(default)]
+    pub volumes: Vec<String>, // Datasets as "source:target", mounted read-only unless a mode is given
+    #[serde(default)]
+    pub gpus: Option<String>, // "all" or a GPU count, as for `docker run --gpus`
+}
+
+impl WarmPool {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize warm pool: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write warm pool {}: {}", path.display(), e))
+    }
+
+    /// `docker run` arguments of the idle container, which only sleeps until a job takes it
+    pub fn run_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = ["run", "-d", "--name", WARM_CONTAINER].iter().map(|s| s.to_string()).collect();
+        for volume in &self.volumes {
+            args.push("-v".to_string());
+            match volume.matches(':').count() {
+                1 => args.push(format!("{}:ro", volume)),
+                _ => args.push(volume.clone()),
+            }
+        }
+        if let Some(gpus) = &self.gpus {
+            args.push("--gpus".to_string());
+            args.push(gpus.clone());
+        }
+        args.extend([self.image.clone(), "sleep".to_string(), "infinity".to_string()]);
+        args
+    }
+
+    /// Images a job can start warm from right now, for the node's advertisement
+    pub fn warm_images(&self) -> Vec<String> {
+        match self.enabled && is_running(WARM_CONTAINER) {
+            true => vec![self.image.clone()],
+            false => vec![],
+        }
+    }
+
+    /// Start the idle container when the pool is on and it is missing, remove it when off
+    pub fn replenish(&self) -> Result<(), String> {
+        let running = is_running(WARM_CONTAINER);
+        if !self.enabled || self.image.is_empty() {
+            if running {
+                docker(&["rm", "-f", WARM_CONTAINER])?;
+                info!("Warm pool turned off");
+            }
+            return Ok(());
+        }
+        if running {
+            return Ok(());
+        }
+
+        // A stopped leftover would block the name
+        let _ = docker(&["rm", "-f", WARM_CONTAINER]);
+        docker(&self.run_args())?;
+        info!("Warm container of {} ready", self.image);
+        Ok(())
+    }
+}
+
+impl JobManager {
+    /// Start a pending job by handing it the warm container; false when the job cannot use it
+    ///
+    /// Only jobs on this machine that run the image's own command and publish no ports can
+    /// take over, which covers interactive sessions; anything else starts cold.
+    pub fn start_warm(&self, job_id: &str, pool: &WarmPool) -> Result<bool, String> {
+        let record = self
+            .get_job(job_id)
+            .ok_or_else(|| format!("No job found with id '{}'", job_id))?;
+        let eligible = pool.enabled
+            && record.status == JobStatus::Pending
+            && record.image == pool.image
+            && record.node_address.is_none()
+            && record.port_mappings.is_empty()
+            && container_cmd(&record.container_name) == image_cmd(&record.image)
+            && is_running(WARM_CONTAINER);
+        if !eligible {
+            return Ok(false);
+        }
+
+        docker(&["rm", "-f", &record.container_name])?;
+        docker(&["rename", WARM_CONTAINER, &record.container_name])?;
+        self.update_status(job_id, JobStatus::Running)?;
+        info!("Job '{}' took over the warm container", job_id);
+        Ok(true)
+    }
+}
+
+/// Warm pool settings from the rental GUI
+pub fn default_pool_path() -> PathBuf {
+    crate::default_registry_path().with_file_name("warm-pool.json")
+}
+
+fn is_running(container: &str) -> bool {
+    docker(&["inspect", "--format", "{{.State.Running}}", container])
+        .map(|output| output.trim() == "true")
+        .unwrap_or(false)
+}
+
+fn container_cmd(container: &str) -> Option<String> {
+    docker(&["inspect", "--format", "{{json .Config.Cmd}}", container]).ok()
+}
+
+fn image_cmd(image: &str) -> Option<String> {
+    docker(&["image", "inspect", "--format", "{{json .Config.Cmd}}", image]).ok()
+}
+
+fn docker<S: AsRef<std::ffi::OsStr>>(args: &[S]) -> Result<String, String> {
+    let output = Command::new("docker")
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to execute docker: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("docker failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warm_run_args() {
+        let pool = WarmPool {
+            enabled: true,
+            image: "pytorch/pytorch:2.1.0-cuda12.1-cudnn8-runtime".to_string(),
+            volumes: vec!["/data/imagenet:/datasets/imagenet".to_string(), "/data/cache:/cache:rw".to_string()],
+            gpus: Some("all".to_string()),
+        };
+        assert_eq!(pool.run_args(), vec![
+            "run", "-d", "--name", "eryzaa-warm",
+            "-v", "/data/imagenet:/datasets/imagenet:ro",
+            "-v", "/data/cache:/cache:rw",
+            "--gpus", "all",
+            "pytorch/pytorch:2.1.0-cuda12.1-cudnn8-runtime", "sleep", "infinity",
+        ]);
+        assert!(WarmPool::default().warm_images().is_empty());
+    }
+}
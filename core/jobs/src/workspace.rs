@@ -0,0 +1,259 @@
+//! Workspace snapshots
+//! Job accounts are ephemeral, so a tenant can save the session's home directory as a tar+zstd
+//! snapshot when a rental ends and unpack it into the next one, on the same node or another.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use log::{info, warn};
+
+use crate::exec::node_script;
+use crate::profiles::Profile;
+use crate::JobManager;
+
+/// Packs the session home, leaving out caches that are cheaper to rebuild than to move
+const SAVE_SCRIPT: &str = "tar -I 'zstd -q -T0' -C ~ --exclude=./.cache --exclude='./incoming/*.part' -cf - .";
+
+/// Unpacks a snapshot over the session home
+const RESTORE_SCRIPT: &str = "tar -I 'zstd -q -d' -C ~ -xf -";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Snapshot {
+    pub id: String,
+    pub label: String,
+    pub job_id: String, // Job the workspace was saved from
+    pub node_id: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub bytes: u64,
+    pub sha256: String, // Of the .tar.zst, checked before restoring
+}
+
+impl Snapshot {
+    /// "<label>-<YYYYmmdd-HHMMSS>", also the file name stem
+    pub fn id_for(label: &str, at: chrono::DateTime<chrono::Utc>) -> String {
+        format!("{}-{}", label, at.format("%Y%m%d-%H%M%S"))
+    }
+}
+
+/// A directory of snapshots, one `<id>.tar.zst` and `<id>.json` pair each
+pub struct WorkspaceStore {
+    dir: PathBuf,
+}
+
+impl WorkspaceStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The profile's chosen snapshot directory, or `workspaces` in its data dir
+    pub fn for_profile(profile: &Profile) -> Self {
+        Self::new(profile.workspace_dir.clone().unwrap_or_else(|| profile.data_dir().join("workspaces")))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn archive_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.tar.zst", id))
+    }
+
+    fn info_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    /// Saved snapshots, newest first
+    pub fn list(&self) -> Vec<Snapshot> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return vec![];
+        };
+        let mut snapshots: Vec<Snapshot> = entries
+            .flatten()
+            .filter(|entry| entry.path().extension().map(|e| e == "json").unwrap_or(false))
+            .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+            .filter_map(|content| serde_json::from_str::<Snapshot>(&content).ok())
+            .filter(|snapshot| self.archive_path(&snapshot.id).exists())
+            .collect();
+        snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.created_at));
+        snapshots
+    }
+
+    pub fn get(&self, id: &str) -> Option<Snapshot> {
+        self.list().into_iter().find(|snapshot| snapshot.id == id)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<(), String> {
+        for path in [self.archive_path(id), self.info_path(id)] {
+            std::fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+
+    fn record(&self, snapshot: &Snapshot) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(snapshot)
+            .map_err(|e| format!("Failed to serialize snapshot: {}", e))?;
+        let path = self.info_path(&snapshot.id);
+        std::fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+impl JobManager {
+    /// Pack the job session's home into a new snapshot in `store`
+    pub fn save_workspace(&self, job_id: &str, store: &WorkspaceStore, label: &str) -> Result<Snapshot, String> {
+        crate::paste::validate_name(label)?;
+        let record = self
+            .get_job(job_id)
+            .ok_or_else(|| format!("No job found with id '{}'", job_id))?;
+        std::fs::create_dir_all(&store.dir)
+            .map_err(|e| format!("Failed to create {}: {}", store.dir.display(), e))?;
+
+        let created_at = chrono::Utc::now();
+        let id = Snapshot::id_for(label, created_at);
+        let archive = store.archive_path(&id);
+        // Written under a temporary name so an interrupted save never looks complete
+        let partial = archive.with_extension("zst.part");
+        let mut child = node_script(&record, SAVE_SCRIPT)?
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to save workspace of job '{}': {}", job_id, e))?;
+
+        let copied = copy_hashed(child.stdout.take(), &partial);
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("Failed to save workspace of job '{}': {}", job_id, e))?;
+        // GNU tar exits 1 when files changed while being read; the snapshot is still usable
+        let (bytes, sha256) = match (copied, output.status.code()) {
+            (Ok(copied), Some(0 | 1)) => copied,
+            (copied, _) => {
+                let _ = std::fs::remove_file(&partial);
+                let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+                return Err(format!(
+                    "Failed to save workspace of job '{}': {}",
+                    job_id,
+                    copied.err().unwrap_or(stderr)
+                ));
+            }
+        };
+        if output.status.code() == Some(1) {
+            warn!("Files changed while saving the workspace of job '{}'", job_id);
+        }
+        std::fs::rename(&partial, &archive)
+            .map_err(|e| format!("Failed to write {}: {}", archive.display(), e))?;
+
+        let snapshot = Snapshot {
+            id,
+            label: label.to_string(),
+            job_id: job_id.to_string(),
+            node_id: record.node_id.clone(),
+            created_at,
+            bytes,
+            sha256,
+        };
+        store.record(&snapshot)?;
+        info!("Saved workspace of job '{}' as {} ({} bytes)", job_id, snapshot.id, bytes);
+        Ok(snapshot)
+    }
+
+    /// Unpack a snapshot into the job session's home, overwriting files of the same name
+    pub fn restore_workspace(&self, job_id: &str, store: &WorkspaceStore, snapshot_id: &str) -> Result<(), String> {
+        let snapshot = store
+            .get(snapshot_id)
+            .ok_or_else(|| format!("No snapshot '{}' in {}", snapshot_id, store.dir.display()))?;
+        let record = self
+            .get_job(job_id)
+            .ok_or_else(|| format!("No job found with id '{}'", job_id))?;
+
+        let archive = store.archive_path(&snapshot.id);
+        let (_, sha256) = copy_hashed(std::fs::File::open(&archive).ok(), Path::new(""))?;
+        if sha256 != snapshot.sha256 {
+            return Err(format!("Snapshot {} is corrupt: checksum does not match", snapshot.id));
+        }
+
+        let mut file = std::fs::File::open(&archive)
+            .map_err(|e| format!("Failed to open {}: {}", archive.display(), e))?;
+        let mut child = node_script(&record, RESTORE_SCRIPT)?
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to restore workspace into job '{}': {}", job_id, e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            std::io::copy(&mut file, &mut stdin)
+                .map_err(|e| format!("Failed to send snapshot to job '{}': {}", job_id, e))?;
+        }
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("Failed to restore workspace into job '{}': {}", job_id, e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "Failed to restore workspace into job '{}': {}",
+                job_id,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        info!("Restored snapshot {} into job '{}'", snapshot.id, job_id);
+        Ok(())
+    }
+}
+
+/// Copy `source` to `path` (or only hash it when `path` is empty); returns bytes and SHA-256
+fn copy_hashed(source: Option<impl Read>, path: &Path) -> Result<(u64, String), String> {
+    let mut source = source.ok_or("Nothing to read the snapshot from")?;
+    let mut file = match path.as_os_str().is_empty() {
+        true => None,
+        false => Some(std::fs::File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?),
+    };
+    let mut hasher = Sha256::new();
+    let mut bytes = 0u64;
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let n = source.read(&mut buffer).map_err(|e| format!("Failed to read snapshot: {}", e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        if let Some(file) = file.as_mut() {
+            file.write_all(&buffer[..n])
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        }
+        bytes += n as u64;
+    }
+    Ok((bytes, format!("{:x}", hasher.finalize())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_store() {
+        let dir = std::env::temp_dir().join(format!("eryzaa-workspaces-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = WorkspaceStore::new(&dir);
+
+        let archive = b"not really zstd";
+        let (bytes, sha256) = copy_hashed(Some(&archive[..]), &store.archive_path("thesis-20260101-120000")).unwrap();
+        let at = chrono::TimeZone::with_ymd_and_hms(&chrono::Utc, 2026, 1, 1, 12, 0, 0).unwrap();
+        let snapshot = Snapshot {
+            id: Snapshot::id_for("thesis", at),
+            label: "thesis".to_string(),
+            job_id: "job_1".to_string(),
+            node_id: None,
+            created_at: at,
+            bytes,
+            sha256,
+        };
+        store.record(&snapshot).unwrap();
+
+        assert_eq!(snapshot.id, "thesis-20260101-120000");
+        assert_eq!(store.list(), vec![snapshot.clone()]);
+        assert_eq!(store.get("thesis-20260101-120000").unwrap().bytes, archive.len() as u64);
+        store.remove(&snapshot.id).unwrap();
+        assert!(store.list().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
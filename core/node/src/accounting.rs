@@ -0,0 +1,194 @@
+//! Per-job transfer accounting
+//! Every job container gets nftables counters for its traffic to the overlay and for all of
+//! its traffic; whatever did not go to the overlay went to the internet.
+
+use std::process::Command;
+use log::info;
+
+const ACCT_TABLE: &str = "eryzaa_acct";
+
+/// Cumulative byte counts for one job container
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JobCounters {
+    pub job_id: String,
+    pub overlay_rx: u64,
+    pub overlay_tx: u64,
+    pub internet_rx: u64,
+    pub internet_tx: u64,
+}
+
+/// Container whose traffic is counted
+#[derive(Debug, Clone)]
+pub struct AccountedContainer {
+    pub job_id: String,
+    pub ip: String,
+}
+
+/// Render counter rules for the given containers
+///
+/// Counters restart from zero whenever the table is re-applied.
+pub fn accounting_ruleset(containers: &[AccountedContainer], overlay_subnets: &[String]) -> String {
+    let overlay = overlay_subnets.join(", ");
+    let mut ruleset = format!(
+        "table inet {table}\ndelete table inet {table}\ntable inet {table} {{\n    chain forward {{\n        type filter hook forward priority -10; policy accept;\n",
+        table = ACCT_TABLE
+    );
+
+    for container in containers {
+        let (job, ip) = (&container.job_id, &container.ip);
+        ruleset.push_str(&format!("        ip saddr {} ip daddr {{ {} }} counter comment \"{}:overlay:tx\"\n", ip, overlay, job));
+        ruleset.push_str(&format!("        ip saddr {} counter comment \"{}:all:tx\"\n", ip, job));
+        ruleset.push_str(&format!("        ip daddr {} ip saddr {{ {} }} counter comment \"{}:overlay:rx\"\n", ip, overlay, job));
+        ruleset.push_str(&format!("        ip daddr {} counter comment \"{}:all:rx\"\n", ip, job));
+    }
+
+    ruleset.push_str("    }\n}\n");
+    ruleset
+}
+
+/// Install counters for the given containers
+pub fn apply_accounting(containers: &[AccountedContainer], overlay_subnets: &[String]) -> Result<(), String> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new("nft")
+        .args(["-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute nft: {}", e))?;
+
+    if let Some(stdin) = child.stdin.as_mut() {
+        stdin
+            .write_all(accounting_ruleset(containers, overlay_subnets).as_bytes())
+            .map_err(|e| format!("Failed to write accounting ruleset: {}", e))?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to wait for nft: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "nft rejected accounting ruleset: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    info!("Counting traffic for {} job containers", containers.len());
+    Ok(())
+}
+
+/// Read the current counters of every accounted container
+pub fn read_counters() -> Result<Vec<JobCounters>, String> {
+    let output = Command::new("nft")
+        .args(["list", "table", "inet", ACCT_TABLE])
+        .output()
+        .map_err(|e| format!("Failed to execute nft: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to list accounting counters: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(parse_counters(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Bridge address of a job container
+pub fn container_ip(container_name: &str) -> Option<String> {
+    let output = Command::new("docker")
+        .args(["inspect", "-f", "{{range .NetworkSettings.Networks}}{{.IPAddress}} {{end}}", container_name])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .next()
+        .map(|ip| ip.to_string())
+}
+
+/// Parse `nft list table` output into per-job counters
+pub fn parse_counters(listing: &str) -> Vec<JobCounters> {
+    let mut counters: Vec<JobCounters> = Vec::new();
+    let mut totals: Vec<(String, u64, u64)> = Vec::new(); // job, all rx, all tx
+
+    for line in listing.lines() {
+        let (Some(bytes), Some(comment)) = (field_after(line, "bytes"), quoted_comment(line)) else {
+            continue;
+        };
+        let Ok(bytes) = bytes.parse::<u64>() else {
+            continue;
+        };
+        let mut parts = comment.rsplitn(3, ':');
+        let (Some(direction), Some(scope), Some(job_id)) = (parts.next(), parts.next(), parts.next()) else {
+            continue;
+        };
+
+        if !counters.iter().any(|c| c.job_id == job_id) {
+            counters.push(JobCounters { job_id: job_id.to_string(), ..Default::default() });
+            totals.push((job_id.to_string(), 0, 0));
+        }
+        let index = counters.iter().position(|c| c.job_id == job_id).unwrap();
+
+        match (scope, direction) {
+            ("overlay", "rx") => counters[index].overlay_rx = bytes,
+            ("overlay", "tx") => counters[index].overlay_tx = bytes,
+            ("all", "rx") => totals[index].1 = bytes,
+            ("all", "tx") => totals[index].2 = bytes,
+            _ => {}
+        }
+    }
+
+    for (counter, (_, all_rx, all_tx)) in counters.iter_mut().zip(totals) {
+        counter.internet_rx = all_rx.saturating_sub(counter.overlay_rx);
+        counter.internet_tx = all_tx.saturating_sub(counter.overlay_tx);
+    }
+
+    counters
+}
+
+fn field_after<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let mut words = line.split_whitespace();
+    words.find(|w| *w == name)?;
+    words.next()
+}
+
+fn quoted_comment(line: &str) -> Option<&str> {
+    let start = line.find("comment \"")? + "comment \"".len();
+    let end = line[start..].find('"')?;
+    Some(&line[start..start + end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_counters_splits_overlay_and_internet() {
+        let listing = r#"table inet eryzaa_acct {
+    chain forward {
+        ip saddr 172.17.0.2 ip daddr { 10.242.0.0/16 } counter packets 3 bytes 300 comment "abc:overlay:tx"
+        ip saddr 172.17.0.2 counter packets 10 bytes 1000 comment "abc:all:tx"
+        ip daddr 172.17.0.2 ip saddr { 10.242.0.0/16 } counter packets 1 bytes 50 comment "abc:overlay:rx"
+        ip daddr 172.17.0.2 counter packets 9 bytes 5050 comment "abc:all:rx"
+    }
+}"#;
+
+        let counters = parse_counters(listing);
+        assert_eq!(counters, vec![JobCounters {
+            job_id: "abc".to_string(),
+            overlay_rx: 50,
+            overlay_tx: 300,
+            internet_rx: 5000,
+            internet_tx: 700,
+        }]);
+    }
+
+    #[test]
+    fn test_ruleset_counts_each_container() {
+        let containers = vec![AccountedContainer { job_id: "abc".to_string(), ip: "172.17.0.2".to_string() }];
+        let ruleset = accounting_ruleset(&containers, &["10.242.0.0/16".to_string()]);
+        assert!(ruleset.contains("ip saddr 172.17.0.2 ip daddr { 10.242.0.0/16 } counter comment \"abc:overlay:tx\""));
+        assert!(ruleset.contains("ip daddr 172.17.0.2 counter comment \"abc:all:rx\""));
+    }
+}
@@ -0,0 +1,255 @@
+//! Job admission rules
+//! Owners list rules that match on who is asking and what for; the first enabled rule whose
+//! conditions all match decides whether a job is accepted, rejected or left to the owner.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::thermal::hour_in_window;
+
+/// What a job asks of this node
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AdmissionRequest {
+    pub client_id: String,
+    pub client_reputation: Option<f32>, // 0-5, None when the client has no history
+    pub gpus: u32,
+    pub memory_gb: u32,
+    pub price_per_hour: Option<f32>, // Price the client offered
+    pub deposit: Option<f32>,        // Refundable deposit the client has posted for the job
+    pub image: String,
+    pub hour: u32, // Local hour of the request
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum Condition {
+    ClientIs(String),
+    ReputationBelow(f32), // Clients without history count as below
+    GpusAbove(u32),
+    MemoryAboveGb(u32),
+    PriceBelow(f32),   // Requests without an offer count as below
+    DepositBelow(f32), // Requests without a deposit count as below
+    HoursBetween { start_hour: u32, end_hour: u32 },
+    ImageMatches(String), // Glob pattern, `*` matches anything
+}
+
+impl Condition {
+    pub fn matches(&self, request: &AdmissionRequest) -> bool {
+        match self {
+            Condition::ClientIs(client) => &request.client_id == client,
+            Condition::ReputationBelow(min) => request.client_reputation.map(|r| r < *min).unwrap_or(true),
+            Condition::GpusAbove(gpus) => request.gpus > *gpus,
+            Condition::MemoryAboveGb(gb) => request.memory_gb > *gb,
+            Condition::PriceBelow(price) => request.price_per_hour.map(|p| p < *price).unwrap_or(true),
+            Condition::DepositBelow(amount) => request.deposit.map(|d| d < *amount).unwrap_or(true),
+            Condition::HoursBetween { start_hour, end_hour } => hour_in_window(*start_hour, *end_hour, request.hour),
+            Condition::ImageMatches(pattern) => glob_match(pattern, &request.image),
+        }
+    }
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Condition::ClientIs(client) => write!(f, "client is {}", client),
+            Condition::ReputationBelow(min) => write!(f, "reputation below {:.1}", min),
+            Condition::GpusAbove(gpus) => write!(f, "more than {} GPUs", gpus),
+            Condition::MemoryAboveGb(gb) => write!(f, "more than {} GB memory", gb),
+            Condition::PriceBelow(price) => write!(f, "offer below {:.2}/hour", price),
+            Condition::DepositBelow(amount) => write!(f, "deposit below {:.2}", amount),
+            Condition::HoursBetween { start_hour, end_hour } => write!(f, "between {:02}:00 and {:02}:00", start_hour, end_hour),
+            Condition::ImageMatches(pattern) => write!(f, "image matches {}", pattern),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum AdmissionDecision {
+    Accept,
+    Reject,
+    AskOwner,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AdmissionRule {
+    pub name: String,
+    pub enabled: bool,
+    pub conditions: Vec<Condition>, // All must match; an empty list matches every request
+    pub decision: AdmissionDecision,
+}
+
+/// Outcome of evaluating a request, with a line per rule for dry runs
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    pub decision: AdmissionDecision,
+    pub rule: Option<String>, // Rule that decided, None for the default
+    pub trace: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdmissionRules {
+    pub rules: Vec<AdmissionRule>,
+    pub default_decision: AdmissionDecision,
+}
+
+impl Default for AdmissionRules {
+    fn default() -> Self {
+        Self {
+            rules: vec![],
+            default_decision: AdmissionDecision::Accept,
+        }
+    }
+}
+
+impl AdmissionRules {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize admission rules: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write admission rules {}: {}", path.display(), e))
+    }
+
+    /// Decide on a request; the first enabled rule whose conditions all match wins
+    pub fn evaluate(&self, request: &AdmissionRequest) -> Evaluation {
+        let mut trace = Vec::new();
+
+        for rule in &self.rules {
+            if !rule.enabled {
+                trace.push(format!("{}: disabled", rule.name));
+                continue;
+            }
+            match rule.conditions.iter().find(|c| !c.matches(request)) {
+                Some(failed) => trace.push(format!("{}: no match ({})", rule.name, failed)),
+                None => {
+                    trace.push(format!("{}: matched → {:?}", rule.name, rule.decision));
+                    return Evaluation {
+                        decision: rule.decision,
+                        rule: Some(rule.name.clone()),
+                        trace,
+                    };
+                }
+            }
+        }
+
+        trace.push(format!("default → {:?}", self.default_decision));
+        Evaluation {
+            decision: self.default_decision,
+            rule: None,
+            trace,
+        }
+    }
+}
+
+/// Match `text` against a pattern where `*` stands for any run of characters
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !text.starts_with(first) || text.len() < first.len() + last.len() || !text.ends_with(last) {
+        return false;
+    }
+
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> AdmissionRequest {
+        AdmissionRequest {
+            client_id: "client-1".to_string(),
+            client_reputation: Some(4.5),
+            gpus: 2,
+            memory_gb: 32,
+            price_per_hour: Some(3.0),
+            deposit: None,
+            image: "pytorch/pytorch:2.1-cuda12".to_string(),
+            hour: 23,
+        }
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let rules = AdmissionRules {
+            rules: vec![
+                AdmissionRule {
+                    name: "night jobs".to_string(),
+                    enabled: false,
+                    conditions: vec![Condition::HoursBetween { start_hour: 22, end_hour: 6 }],
+                    decision: AdmissionDecision::Reject,
+                },
+                AdmissionRule {
+                    name: "cheap big jobs".to_string(),
+                    enabled: true,
+                    conditions: vec![Condition::GpusAbove(1), Condition::PriceBelow(5.0)],
+                    decision: AdmissionDecision::AskOwner,
+                },
+                AdmissionRule {
+                    name: "trusted pytorch".to_string(),
+                    enabled: true,
+                    conditions: vec![Condition::ImageMatches("pytorch/*".to_string())],
+                    decision: AdmissionDecision::Accept,
+                },
+            ],
+            default_decision: AdmissionDecision::Reject,
+        };
+
+        let evaluation = rules.evaluate(&request());
+        assert_eq!(evaluation.decision, AdmissionDecision::AskOwner);
+        assert_eq!(evaluation.rule.as_deref(), Some("cheap big jobs"));
+        assert_eq!(evaluation.trace[0], "night jobs: disabled");
+
+        let generous = AdmissionRequest { price_per_hour: Some(8.0), ..request() };
+        assert_eq!(rules.evaluate(&generous).decision, AdmissionDecision::Accept);
+
+        let other = AdmissionRequest { image: "ubuntu:22.04".to_string(), ..generous };
+        assert_eq!(rules.evaluate(&other), Evaluation {
+            decision: AdmissionDecision::Reject,
+            rule: None,
+            trace: vec![
+                "night jobs: disabled".to_string(),
+                "cheap big jobs: no match (offer below 5.00/hour)".to_string(),
+                "trusted pytorch: no match (image matches pytorch/*)".to_string(),
+                "default → Reject".to_string(),
+            ],
+        });
+    }
+
+    #[test]
+    fn test_unknown_reputation_and_globs() {
+        let newcomer = AdmissionRequest { client_reputation: None, ..request() };
+        assert!(Condition::ReputationBelow(3.0).matches(&newcomer));
+        assert!(!Condition::ReputationBelow(3.0).matches(&request()));
+        // Newcomers may get in with a deposit instead
+        let deposited = AdmissionRequest { deposit: Some(5.0), ..newcomer.clone() };
+        assert!(Condition::DepositBelow(5.0).matches(&newcomer));
+        assert!(!Condition::DepositBelow(5.0).matches(&deposited));
+
+        assert!(glob_match("*cuda*", "nvidia/cuda:12.2"));
+        assert!(glob_match("ubuntu:*", "ubuntu:22.04"));
+        assert!(!glob_match("ubuntu:*", "debian:12"));
+        assert!(!glob_match("a*a", "a"));
+    }
+}
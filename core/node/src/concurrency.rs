@@ -0,0 +1,62 @@
+//! Per-node job concurrency
+//! The owner caps how many jobs run at once; the rental server holds pending jobs back at the
+//! cap and the discovery service advertises the slots still free.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ConcurrencyLimit {
+    pub max_concurrent_jobs: u32,
+}
+
+impl Default for ConcurrencyLimit {
+    fn default() -> Self {
+        Self { max_concurrent_jobs: 4 }
+    }
+}
+
+impl ConcurrencyLimit {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize concurrency limit: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write concurrency limit {}: {}", path.display(), e))
+    }
+
+    /// Slots left with `running` jobs
+    pub fn free_slots(&self, running: usize) -> u32 {
+        self.max_concurrent_jobs.saturating_sub(running as u32)
+    }
+
+    /// Whether another job may start with `running` jobs
+    pub fn admits(&self, running: usize) -> bool {
+        self.free_slots(running) > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_free_slots() {
+        let limit = ConcurrencyLimit { max_concurrent_jobs: 2 };
+        assert_eq!(limit.free_slots(0), 2);
+        assert!(limit.admits(1));
+        assert!(!limit.admits(2));
+        assert_eq!(limit.free_slots(5), 0);
+    }
+}
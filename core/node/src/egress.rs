@@ -0,0 +1,343 @@
+//! Outbound traffic policy of tenant jobs
+//! The renter picks how far job containers may reach: only the overlay, the overlay plus
+//! package registries and git hosts, or the whole internet, plus extra domains and CIDRs.
+//! Domains are allowed by the addresses dnsmasq resolves for them, which it adds to an
+//! nftables set the per-job rules match on.
+
+use serde::{Deserialize, Serialize};
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::process::Command;
+use log::info;
+
+use crate::AccountedContainer;
+
+const EGRESS_TABLE: &str = "eryzaa_egress";
+const ALLOWED_SET: &str = "allowed_v4"; // CIDRs from the custom list
+const RESOLVED_SET: &str = "resolved_v4"; // Filled by dnsmasq as allowed domains resolve
+const RESOLVED_TIMEOUT: &str = "1h";
+
+/// dnsmasq drop-in that answers job DNS on the Docker bridge
+pub const DNSMASQ_CONFIG_PATH: &str = "/etc/dnsmasq.d/eryzaa-egress.conf";
+
+/// Reached by the "registries + git" preset; subdomains are included
+pub const REGISTRY_DOMAINS: &[&str] = &[
+    "pypi.org",
+    "pythonhosted.org",
+    "download.pytorch.org",
+    "anaconda.org",
+    "anaconda.com",
+    "registry.npmjs.org",
+    "crates.io",
+    "proxy.golang.org",
+    "sum.golang.org",
+    "huggingface.co",
+    "docker.io",
+    "ghcr.io",
+    "archive.ubuntu.com",
+    "security.ubuntu.com",
+    "deb.debian.org",
+    "github.com",
+    "githubusercontent.com",
+    "gitlab.com",
+    "bitbucket.org",
+];
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum EgressPreset {
+    OverlayOnly,
+    Registries,
+    #[default]
+    FullInternet,
+}
+
+impl EgressPreset {
+    pub const ALL: [EgressPreset; 3] = [Self::OverlayOnly, Self::Registries, Self::FullInternet];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::OverlayOnly => "Overlay only",
+            Self::Registries => "Package registries + git",
+            Self::FullInternet => "Full internet",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EgressPolicy {
+    pub preset: EgressPreset,
+    #[serde(default)]
+    pub custom: Vec<String>, // Extra domains or IPv4 CIDRs, allowed on top of a restricted preset
+}
+
+impl EgressPolicy {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize egress policy: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write egress policy {}: {}", path.display(), e))
+    }
+
+    pub fn is_restricted(&self) -> bool {
+        self.preset != EgressPreset::FullInternet
+    }
+
+    /// Domains jobs may reach, resolved through dnsmasq
+    pub fn domains(&self) -> Vec<String> {
+        if !self.is_restricted() {
+            return vec![];
+        }
+        let preset = match self.preset {
+            EgressPreset::Registries => REGISTRY_DOMAINS,
+            _ => &[],
+        };
+        let mut domains: Vec<String> = preset.iter().map(|d| d.to_string()).collect();
+        for entry in self.custom.iter().filter(|entry| !is_cidr(entry)) {
+            let domain = entry.trim().trim_start_matches("*.").to_lowercase();
+            if !domain.is_empty() && !domains.contains(&domain) {
+                domains.push(domain);
+            }
+        }
+        domains
+    }
+
+    /// Addresses and subnets jobs may reach outside the overlay
+    pub fn cidrs(&self) -> Vec<String> {
+        match self.is_restricted() {
+            true => self.custom.iter().filter(|entry| is_cidr(entry)).map(|entry| entry.trim().to_string()).collect(),
+            false => vec![],
+        }
+    }
+
+    /// Render the per-job rules; `resolver` is the dnsmasq address job DNS is redirected to
+    ///
+    /// Replies on connections made to the job, such as published ports, are always let out.
+    pub fn nftables_ruleset(&self, containers: &[AccountedContainer], overlay_subnets: &[String], resolver: &str) -> String {
+        let mut ruleset = format!("table inet {table}\ndelete table inet {table}\n", table = EGRESS_TABLE);
+        if !self.is_restricted() || containers.is_empty() {
+            return ruleset;
+        }
+
+        let domains = !self.domains().is_empty();
+        let cidrs = self.cidrs();
+        ruleset.push_str(&format!("table inet {} {{\n", EGRESS_TABLE));
+        ruleset.push_str(&format!("    set {} {{\n        type ipv4_addr; flags interval;\n", ALLOWED_SET));
+        if !cidrs.is_empty() {
+            ruleset.push_str(&format!("        elements = {{ {} }}\n", cidrs.join(", ")));
+        }
+        ruleset.push_str("    }\n");
+        ruleset.push_str(&format!(
+            "    set {} {{\n        type ipv4_addr; flags timeout; timeout {};\n    }}\n",
+            RESOLVED_SET, RESOLVED_TIMEOUT
+        ));
+
+        if domains {
+            ruleset.push_str("    chain dns {\n        type nat hook prerouting priority dstnat; policy accept;\n");
+            for container in containers {
+                ruleset.push_str(&format!(
+                    "        ip saddr {} meta l4proto {{ tcp, udp }} th dport 53 dnat ip to {} comment \"{}:dns\"\n",
+                    container.ip, resolver, container.job_id
+                ));
+            }
+            ruleset.push_str("    }\n");
+        }
+
+        // Ahead of the transfer counters, so refused traffic is not billed as egress
+        ruleset.push_str("    chain forward {\n        type filter hook forward priority -20; policy accept;\n");
+        let overlay = overlay_subnets.join(", ");
+        for container in containers {
+            let ip = &container.ip;
+            ruleset.push_str(&format!("        ip saddr {} ct state established,related accept\n", ip));
+            if !overlay.is_empty() {
+                ruleset.push_str(&format!("        ip saddr {} ip daddr {{ {} }} accept\n", ip, overlay));
+            }
+            ruleset.push_str(&format!("        ip saddr {} ip daddr @{} accept\n", ip, ALLOWED_SET));
+            ruleset.push_str(&format!("        ip saddr {} ip daddr @{} accept\n", ip, RESOLVED_SET));
+            ruleset.push_str(&format!("        ip saddr {} drop comment \"{}:egress\"\n", ip, container.job_id));
+        }
+        ruleset.push_str("    }\n}\n");
+        ruleset
+    }
+
+    /// dnsmasq drop-in that records the addresses of allowed domains; None when no domain is allowed
+    pub fn dnsmasq_config(&self, resolver: &str) -> Option<String> {
+        let domains = self.domains();
+        if domains.is_empty() {
+            return None;
+        }
+        let mut config = format!(
+            "# Written by the Eryzaa rental server; changes are overwritten\nlisten-address={}\nbind-interfaces\n",
+            resolver
+        );
+        for domain in domains {
+            config.push_str(&format!("nftset=/{}/4#inet#{}#{}\n", domain, EGRESS_TABLE, RESOLVED_SET));
+        }
+        Some(config)
+    }
+
+    /// One line per thing jobs may reach
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = vec![format!("Preset: {}", self.preset.label())];
+        if self.is_restricted() {
+            lines.push("Overlay: allowed".to_string());
+            let domains = self.domains();
+            if !domains.is_empty() {
+                lines.push(format!("Domains: {}", domains.join(", ")));
+            }
+            let cidrs = self.cidrs();
+            if !cidrs.is_empty() {
+                lines.push(format!("Addresses: {}", cidrs.join(", ")));
+            }
+        }
+        lines
+    }
+
+    /// Program the rules for the running job containers, and dnsmasq when domains are allowed
+    pub fn apply(&self, containers: &[AccountedContainer], overlay_subnets: &[String]) -> Result<(), String> {
+        let resolver = bridge_gateway();
+        // The sets must exist before dnsmasq writes to them
+        nft(&self.nftables_ruleset(containers, overlay_subnets, &resolver))?;
+
+        let current = std::fs::read_to_string(DNSMASQ_CONFIG_PATH).ok();
+        let wanted = self.dnsmasq_config(&resolver).filter(|_| !containers.is_empty());
+        if current != wanted {
+            match &wanted {
+                Some(config) => std::fs::write(DNSMASQ_CONFIG_PATH, config)
+                    .map_err(|e| format!("Failed to write {}: {}", DNSMASQ_CONFIG_PATH, e))?,
+                None => std::fs::remove_file(DNSMASQ_CONFIG_PATH)
+                    .map_err(|e| format!("Failed to remove {}: {}", DNSMASQ_CONFIG_PATH, e))?,
+            }
+            let status = Command::new("systemctl")
+                .args(["restart", "dnsmasq"])
+                .status()
+                .map_err(|e| format!("Failed to execute systemctl: {}", e))?;
+            if !status.success() {
+                return Err("Failed to restart dnsmasq; is it installed?".to_string());
+            }
+        }
+
+        info!("Egress policy '{}' applied to {} job containers", self.preset.label(), containers.len());
+        Ok(())
+    }
+}
+
+/// Check a custom entry: a domain such as "example.com" or "*.example.com", or an IPv4 address or CIDR
+pub fn validate_entry(entry: &str) -> Result<(), String> {
+    let entry = entry.trim();
+    if is_cidr(entry) {
+        return Ok(());
+    }
+    if entry.contains('/') || entry.contains(':') {
+        return Err(format!("'{}' is not an IPv4 address or CIDR", entry));
+    }
+    let domain = entry.trim_start_matches("*.");
+    let valid = domain.contains('.')
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    match valid {
+        true => Ok(()),
+        false => Err(format!("'{}' is not a domain, address or CIDR", entry)),
+    }
+}
+
+fn is_cidr(entry: &str) -> bool {
+    let (address, prefix) = entry.trim().split_once('/').unwrap_or((entry.trim(), "32"));
+    address.parse::<Ipv4Addr>().is_ok() && prefix.parse::<u8>().map(|p| p <= 32).unwrap_or(false)
+}
+
+/// Address of the Docker bridge on this host, where dnsmasq answers job DNS
+pub fn bridge_gateway() -> String {
+    Command::new("docker")
+        .args(["network", "inspect", "bridge", "--format", "{{range .IPAM.Config}}{{.Gateway}} {{end}}"])
+        .output()
+        .ok()
+        .and_then(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .split_whitespace()
+                .find(|gateway| gateway.parse::<Ipv4Addr>().is_ok())
+                .map(|gateway| gateway.to_string())
+        })
+        .unwrap_or_else(|| "172.17.0.1".to_string())
+}
+
+fn nft(ruleset: &str) -> Result<(), String> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new("nft")
+        .args(["-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute nft: {}", e))?;
+
+    if let Some(stdin) = child.stdin.as_mut() {
+        stdin
+            .write_all(ruleset.as_bytes())
+            .map_err(|e| format!("Failed to write egress ruleset: {}", e))?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to wait for nft: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "nft rejected egress ruleset: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_egress_presets() {
+        let containers = vec![AccountedContainer { job_id: "abc".to_string(), ip: "172.17.0.2".to_string() }];
+        let overlay = vec!["10.242.0.0/16".to_string()];
+
+        let open = EgressPolicy::default();
+        assert_eq!(open.nftables_ruleset(&containers, &overlay, "172.17.0.1"), "table inet eryzaa_egress\ndelete table inet eryzaa_egress\n");
+        assert!(open.dnsmasq_config("172.17.0.1").is_none());
+
+        let overlay_only = EgressPolicy { preset: EgressPreset::OverlayOnly, custom: vec!["203.0.113.0/24".to_string()] };
+        let ruleset = overlay_only.nftables_ruleset(&containers, &overlay, "172.17.0.1");
+        assert!(ruleset.contains("elements = { 203.0.113.0/24 }"));
+        assert!(ruleset.contains("ip saddr 172.17.0.2 ip daddr { 10.242.0.0/16 } accept"));
+        assert!(ruleset.contains("ip saddr 172.17.0.2 drop comment \"abc:egress\""));
+        assert!(!ruleset.contains("dnat"));
+
+        let registries = EgressPolicy { preset: EgressPreset::Registries, custom: vec!["*.Example.com".to_string()] };
+        let ruleset = registries.nftables_ruleset(&containers, &overlay, "172.17.0.1");
+        assert!(ruleset.contains("ip saddr 172.17.0.2 meta l4proto { tcp, udp } th dport 53 dnat ip to 172.17.0.1 comment \"abc:dns\""));
+        assert!(ruleset.contains("ip saddr 172.17.0.2 ip daddr @resolved_v4 accept"));
+        let config = registries.dnsmasq_config("172.17.0.1").unwrap();
+        assert!(config.contains("listen-address=172.17.0.1\n"));
+        assert!(config.contains("nftset=/pypi.org/4#inet#eryzaa_egress#resolved_v4\n"));
+        assert!(config.ends_with("nftset=/example.com/4#inet#eryzaa_egress#resolved_v4\n"));
+
+        assert!(validate_entry("*.example.com").is_ok());
+        assert!(validate_entry("10.0.0.0/8").is_ok());
+        assert!(validate_entry("10.0.0.0/33").is_err());
+        assert!(validate_entry("2001:db8::/32").is_err());
+        assert!(validate_entry("localhost").is_err());
+    }
+}
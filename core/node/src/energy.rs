@@ -0,0 +1,168 @@
+//! Per-job energy estimation
+//! GPU draw is measured and split between GPU jobs by the GPUs they hold; CPU draw is
+//! modelled from the container's CPU share of the package TDP.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+use std::time::Instant;
+
+use crate::thermal::GpuReading;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnergyModel {
+    pub cpu_tdp_watts: f32,
+    pub cpu_cores: u32,
+    pub carbon_intensity_g_per_kwh: Option<f32>, // Grid carbon intensity set by the renter
+}
+
+impl Default for EnergyModel {
+    fn default() -> Self {
+        Self {
+            cpu_tdp_watts: 65.0,
+            cpu_cores: std::thread::available_parallelism().map(|n| n.get() as u32).unwrap_or(1),
+            carbon_intensity_g_per_kwh: None,
+        }
+    }
+}
+
+/// A running job as seen by the energy meter
+#[derive(Debug, Clone)]
+pub struct MeteredJob {
+    pub job_id: String,
+    pub container_name: String,
+    pub gpus: u32,
+}
+
+impl EnergyModel {
+    /// Load the model the renter saved, falling back to defaults
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize energy model: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write energy model {}: {}", path.display(), e))
+    }
+
+    /// Estimated draw of a container using `cpu_percent` (100 = one full core)
+    pub fn cpu_watts(&self, cpu_percent: f32) -> f32 {
+        let share = (cpu_percent / 100.0) / self.cpu_cores.max(1) as f32;
+        self.cpu_tdp_watts * share.clamp(0.0, 1.0)
+    }
+
+    /// Estimated draw of every job right now, in watts
+    pub fn job_watts(&self, jobs: &[MeteredJob], readings: &[GpuReading], cpu_percent: &HashMap<String, f32>) -> HashMap<String, f32> {
+        let gpu_draw: f32 = readings.iter().map(|r| r.power_draw_w).sum();
+        let gpu_slots: u32 = jobs.iter().map(|j| j.gpus).sum();
+
+        jobs.iter()
+            .map(|job| {
+                let gpu = if gpu_slots > 0 {
+                    gpu_draw * job.gpus as f32 / gpu_slots as f32
+                } else {
+                    0.0
+                };
+                let cpu = self.cpu_watts(cpu_percent.get(&job.container_name).copied().unwrap_or(0.0));
+                (job.job_id.clone(), gpu + cpu)
+            })
+            .collect()
+    }
+}
+
+/// Integrates job power draw between samples
+pub struct EnergyMeter {
+    pub model: EnergyModel,
+    last_sample: Option<Instant>,
+}
+
+impl EnergyMeter {
+    pub fn new(model: EnergyModel) -> Self {
+        Self { model, last_sample: None }
+    }
+
+    /// Energy each job used since the previous sample, in watt-hours
+    ///
+    /// The first sample only sets the starting point and returns nothing.
+    pub fn sample(&mut self, jobs: &[MeteredJob], readings: &[GpuReading], cpu_percent: &HashMap<String, f32>, now: Instant) -> HashMap<String, f64> {
+        let Some(last) = self.last_sample.replace(now) else {
+            return HashMap::new();
+        };
+        let hours = now.saturating_duration_since(last).as_secs_f64() / 3600.0;
+
+        self.model
+            .job_watts(jobs, readings, cpu_percent)
+            .into_iter()
+            .map(|(job_id, watts)| (job_id, watts as f64 * hours))
+            .collect()
+    }
+}
+
+/// CPU usage of every running container, keyed by container name
+pub fn container_cpu_percent() -> Result<HashMap<String, f32>, String> {
+    let output = Command::new("docker")
+        .args(["stats", "--no-stream", "--format", "{{.Name}} {{.CPUPerc}}"])
+        .output()
+        .map_err(|e| format!("Failed to execute docker stats: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "docker stats failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (name, percent) = line.split_once(' ')?;
+            let percent = percent.trim().trim_end_matches('%').parse().ok()?;
+            Some((name.to_string(), percent))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_watts_splits_gpu_draw() {
+        let model = EnergyModel { cpu_tdp_watts: 100.0, cpu_cores: 4, carbon_intensity_g_per_kwh: None };
+        let jobs = vec![
+            MeteredJob { job_id: "a".to_string(), container_name: "eryzaa-job-a".to_string(), gpus: 1 },
+            MeteredJob { job_id: "b".to_string(), container_name: "eryzaa-job-b".to_string(), gpus: 3 },
+        ];
+        let readings: Vec<GpuReading> = (0..4)
+            .map(|index| GpuReading {
+                index,
+                temperature_c: 60.0,
+                power_draw_w: 100.0,
+                power_limit_w: 300.0,
+                default_power_limit_w: 300.0,
+                ecc_errors: 0,
+            })
+            .collect();
+        let cpu = HashMap::from([("eryzaa-job-a".to_string(), 200.0)]);
+
+        let watts = model.job_watts(&jobs, &readings, &cpu);
+        assert_eq!(watts["a"], 100.0 + 50.0);
+        assert_eq!(watts["b"], 300.0);
+
+        let mut meter = EnergyMeter::new(model);
+        let start = Instant::now();
+        assert!(meter.sample(&jobs, &readings, &cpu, start).is_empty());
+        let used = meter.sample(&jobs, &readings, &cpu, start + std::time::Duration::from_secs(1800));
+        assert_eq!(used["b"], 150.0);
+    }
+}
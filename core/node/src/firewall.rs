@@ -0,0 +1,292 @@
+//! Overlay-aware firewall management
+//! Node ports (SSH, control, API, discovery) are only reachable from the overlay
+//! subnets and explicitly allowlisted addresses. In LAN direct mode the LAN subnet takes the
+//! overlay's place.
+
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use log::{info, warn};
+
+pub use eryzaa_discovery::overlay::DEFAULT_OVERLAY_SUBNETS;
+use eryzaa_discovery::overlay::{NetworkMode, PRIVATE_SUBNETS};
+
+const NFT_TABLE: &str = "eryzaa";
+const WINDOWS_RULE_PREFIX: &str = "Eryzaa";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl Protocol {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Protocol::Tcp => "tcp",
+            Protocol::Udp => "udp",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FirewallRule {
+    pub name: String,
+    pub port: u16,
+    pub protocol: Protocol,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FirewallPolicy {
+    pub overlay_subnets: Vec<String>,
+    pub allowlist: Vec<String>, // Extra addresses/subnets allowed outside the overlay
+    pub rules: Vec<FirewallRule>,
+}
+
+impl Default for FirewallPolicy {
+    fn default() -> Self {
+        Self {
+            overlay_subnets: DEFAULT_OVERLAY_SUBNETS.iter().map(|s| s.to_string()).collect(),
+            allowlist: vec![],
+            rules: vec![
+                FirewallRule { name: "SSH".to_string(), port: 22, protocol: Protocol::Tcp },
+                FirewallRule { name: "API".to_string(), port: 8080, protocol: Protocol::Tcp },
+                FirewallRule { name: "Discovery".to_string(), port: 9999, protocol: Protocol::Udp },
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FirewallState {
+    pub backend: String,
+    pub missing_rules: Vec<String>, // Names of rules not found in the live firewall
+}
+
+impl FirewallState {
+    pub fn is_enforced(&self) -> bool {
+        self.missing_rules.is_empty()
+    }
+}
+
+impl FirewallPolicy {
+    /// Policy for a node in `mode`; a LAN direct node whose subnet is unknown accepts the
+    /// private ranges
+    pub fn for_mode(mode: NetworkMode, lan_subnet: Option<&str>) -> Self {
+        let overlay_subnets = match (mode, lan_subnet) {
+            (NetworkMode::Overlay, _) => return Self::default(),
+            (NetworkMode::LanDirect, Some(subnet)) => vec![subnet.to_string()],
+            (NetworkMode::LanDirect, None) => PRIVATE_SUBNETS.iter().map(|s| s.to_string()).collect(),
+        };
+        Self { overlay_subnets, ..Self::default() }
+    }
+
+    /// All sources allowed to reach node ports
+    pub fn allowed_sources(&self) -> Vec<String> {
+        self.overlay_subnets
+            .iter()
+            .chain(self.allowlist.iter())
+            .cloned()
+            .collect()
+    }
+
+    /// Human-readable summary of the policy, one line per rule
+    pub fn describe(&self) -> Vec<String> {
+        let sources = self.allowed_sources().join(", ");
+        self.rules
+            .iter()
+            .map(|rule| format!("{} {}/{} ← {}", rule.name, rule.port, rule.protocol.as_str(), sources))
+            .collect()
+    }
+
+    /// Render the policy as an nftables ruleset
+    ///
+    /// The table is deleted and recreated so re-applying is idempotent.
+    pub fn nftables_ruleset(&self) -> String {
+        let sources = self.allowed_sources().join(", ");
+        let mut ruleset = format!(
+            "table inet {table}\ndelete table inet {table}\ntable inet {table} {{\n    chain input {{\n        type filter hook input priority 0; policy accept;\n",
+            table = NFT_TABLE
+        );
+
+        for rule in &self.rules {
+            let proto = rule.protocol.as_str();
+            if !sources.is_empty() {
+                ruleset.push_str(&format!(
+                    "        {} dport {} ip saddr {{ {} }} accept comment \"{}\"\n",
+                    proto, rule.port, sources, rule.name
+                ));
+            }
+            ruleset.push_str(&format!("        {} dport {} drop\n", proto, rule.port));
+        }
+
+        ruleset.push_str("    }\n}\n");
+        ruleset
+    }
+
+    /// PowerShell commands that install the policy into Windows Firewall
+    pub fn windows_commands(&self) -> Vec<String> {
+        let mut commands = vec![format!(
+            "Remove-NetFirewallRule -DisplayName '{}-*' -ErrorAction SilentlyContinue",
+            WINDOWS_RULE_PREFIX
+        )];
+
+        let sources = self.allowed_sources().join(",");
+        for rule in &self.rules {
+            commands.push(format!(
+                "New-NetFirewallRule -DisplayName '{}-{}' -Direction Inbound -Protocol {} -LocalPort {} -RemoteAddress {} -Action Allow -Profile Any",
+                WINDOWS_RULE_PREFIX,
+                rule.name,
+                rule.protocol.as_str().to_uppercase(),
+                rule.port,
+                sources
+            ));
+        }
+
+        commands
+    }
+
+    /// Program the host firewall with this policy
+    pub fn apply(&self) -> Result<(), String> {
+        #[cfg(target_os = "windows")]
+        {
+            for command in self.windows_commands() {
+                let output = Command::new("powershell")
+                    .args(["-Command", &command])
+                    .output()
+                    .map_err(|e| format!("Failed to run PowerShell: {}", e))?;
+                if !output.status.success() {
+                    return Err(format!(
+                        "Windows Firewall rejected rule: {}",
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ));
+                }
+            }
+        }
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            use std::io::Write;
+            use std::process::Stdio;
+
+            let mut child = Command::new("nft")
+                .args(["-f", "-"])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped())
+                .spawn()
+                .map_err(|e| format!("Failed to execute nft: {}", e))?;
+
+            if let Some(stdin) = child.stdin.as_mut() {
+                stdin
+                    .write_all(self.nftables_ruleset().as_bytes())
+                    .map_err(|e| format!("Failed to write nftables ruleset: {}", e))?;
+            }
+
+            let output = child
+                .wait_with_output()
+                .map_err(|e| format!("Failed to wait for nft: {}", e))?;
+            if !output.status.success() {
+                return Err(format!(
+                    "nft rejected ruleset: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+        }
+
+        info!("Applied firewall policy ({} rules)", self.rules.len());
+        Ok(())
+    }
+
+    /// Check that the live firewall still contains every rule of this policy
+    pub fn verify(&self) -> Result<FirewallState, String> {
+        #[cfg(target_os = "windows")]
+        let (backend, listing) = {
+            let output = Command::new("powershell")
+                .args([
+                    "-Command",
+                    &format!(
+                        "Get-NetFirewallRule -DisplayName '{}-*' | Select-Object -ExpandProperty DisplayName",
+                        WINDOWS_RULE_PREFIX
+                    ),
+                ])
+                .output()
+                .map_err(|e| format!("Failed to run PowerShell: {}", e))?;
+            ("Windows Firewall".to_string(), String::from_utf8_lossy(&output.stdout).to_string())
+        };
+
+        #[cfg(not(target_os = "windows"))]
+        let (backend, listing) = {
+            let output = Command::new("nft")
+                .args(["list", "table", "inet", NFT_TABLE])
+                .output()
+                .map_err(|e| format!("Failed to execute nft: {}", e))?;
+            ("nftables".to_string(), String::from_utf8_lossy(&output.stdout).to_string())
+        };
+
+        let missing_rules: Vec<String> = self
+            .rules
+            .iter()
+            .filter(|rule| !rule_present(&listing, rule))
+            .map(|rule| rule.name.clone())
+            .collect();
+
+        if !missing_rules.is_empty() {
+            warn!("Firewall is missing rules: {}", missing_rules.join(", "));
+        }
+
+        Ok(FirewallState { backend, missing_rules })
+    }
+
+    /// Apply the policy and confirm it took effect
+    pub fn apply_and_verify(&self) -> Result<FirewallState, String> {
+        self.apply()?;
+        let state = self.verify()?;
+        if !state.is_enforced() {
+            return Err(format!("Firewall rules missing after apply: {}", state.missing_rules.join(", ")));
+        }
+        Ok(state)
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn rule_present(listing: &str, rule: &FirewallRule) -> bool {
+    let name = format!("{}-{}", WINDOWS_RULE_PREFIX, rule.name);
+    listing.lines().any(|line| line.trim() == name)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn rule_present(listing: &str, rule: &FirewallRule) -> bool {
+    let needle = format!("{} dport {} drop", rule.protocol.as_str(), rule.port);
+    listing.lines().any(|line| line.trim() == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nftables_ruleset_restricts_to_overlay() {
+        let mut policy = FirewallPolicy::default();
+        policy.allowlist.push("203.0.113.7".to_string());
+
+        let ruleset = policy.nftables_ruleset();
+        assert!(ruleset.contains("tcp dport 22 ip saddr { 10.242.0.0/16, 10.243.0.0/16, 192.168.191.0/24, 203.0.113.7 } accept"));
+        assert!(ruleset.contains("tcp dport 22 drop"));
+        assert!(ruleset.contains("udp dport 9999 drop"));
+
+        let lan = FirewallPolicy::for_mode(NetworkMode::LanDirect, Some("192.168.1.0/24"));
+        assert!(lan.nftables_ruleset().contains("tcp dport 22 ip saddr { 192.168.1.0/24 } accept"));
+        assert_eq!(FirewallPolicy::for_mode(NetworkMode::LanDirect, None).allowed_sources().len(), 3);
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_verify_detects_missing_rules() {
+        let policy = FirewallPolicy::default();
+        let listing = "table inet eryzaa {\n  tcp dport 22 drop\n  udp dport 9999 drop\n}";
+
+        let missing: Vec<&FirewallRule> = policy.rules.iter().filter(|r| !rule_present(listing, r)).collect();
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].name, "API");
+    }
+}
@@ -0,0 +1,298 @@
+//! Utilization history, idle forecasts and price suggestions
+//! The rental GUI records what share of the node's job slots is busy, averaged per hour and kept
+//! for four weeks. Averaging those hours by hour of the week forecasts when the node sits idle;
+//! hours that are idle week after week are suggested at a discount and hours that are always
+//! full at a premium. Applied suggestions become a price schedule for the week.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub const HOURS_PER_WEEK: u32 = 168;
+
+/// Hourly samples kept, four weeks of them
+const KEPT_HOURS: u64 = 4 * HOURS_PER_WEEK as u64;
+
+/// Samples an hour of the week needs, one a week, before it is forecast
+pub const MIN_WEEKS: u32 = 2;
+
+/// Hours forecast below this share of busy slots are idle
+pub const IDLE_BELOW: f32 = 0.2;
+
+/// Hours forecast at or above this share are full
+pub const FULL_FROM: f32 = 0.9;
+
+const IDLE_DISCOUNT: f32 = 0.25;
+const FULL_PREMIUM: f32 = 0.15;
+
+const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Hour of the week, 0 being Monday 00:00, of `unix_secs` at `utc_offset_secs` from UTC
+pub fn hour_of_week(unix_secs: u64, utc_offset_secs: i64) -> u32 {
+    let local_hours = (unix_secs as i64 + utc_offset_secs).div_euclid(3600);
+    // 1970-01-01 was a Thursday, three days into its week
+    (local_hours + 72).rem_euclid(HOURS_PER_WEEK as i64) as u32
+}
+
+/// Busy share of the job slots over one hour
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HourSample {
+    pub hour: u64,         // Hours since the epoch
+    pub hour_of_week: u32, // Local, when it was recorded
+    pub utilization: f32,  // 0-1, the mean of the readings
+    pub readings: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct UtilizationHistory {
+    pub samples: Vec<HourSample>,
+}
+
+impl UtilizationHistory {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize utilization history: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write utilization history {}: {}", path.display(), e))
+    }
+
+    /// Add a reading of `busy` of `slots` job slots taken at `unix_secs`, forgetting hours
+    /// older than four weeks
+    pub fn record(&mut self, unix_secs: u64, utc_offset_secs: i64, busy: u32, slots: u32) {
+        let utilization = match slots {
+            0 => 0.0,
+            _ => (busy as f32 / slots as f32).min(1.0),
+        };
+        let hour = unix_secs / 3600;
+        match self.samples.last_mut() {
+            Some(sample) if sample.hour == hour => {
+                sample.utilization = (sample.utilization * sample.readings as f32 + utilization) / (sample.readings + 1) as f32;
+                sample.readings += 1;
+            }
+            _ => self.samples.push(HourSample {
+                hour,
+                hour_of_week: hour_of_week(unix_secs, utc_offset_secs),
+                utilization,
+                readings: 1,
+            }),
+        }
+        self.samples.retain(|sample| sample.hour + KEPT_HOURS > hour);
+    }
+
+    /// Expected utilization of each hour of the week, Monday 00:00 first
+    pub fn forecast(&self) -> Vec<HourForecast> {
+        (0..HOURS_PER_WEEK)
+            .map(|hour_of_week| {
+                let seen: Vec<f32> = self
+                    .samples
+                    .iter()
+                    .filter(|sample| sample.hour_of_week == hour_of_week)
+                    .map(|sample| sample.utilization)
+                    .collect();
+                let weeks = seen.len() as u32;
+                HourForecast {
+                    hour_of_week,
+                    utilization: (weeks >= MIN_WEEKS).then(|| seen.iter().sum::<f32>() / weeks as f32),
+                    weeks,
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HourForecast {
+    pub hour_of_week: u32,
+    pub utilization: Option<f32>, // None until `MIN_WEEKS` weeks were seen
+    pub weeks: u32,
+}
+
+/// Consecutive hours of the week, which may run from Sunday into Monday
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct WeekWindow {
+    pub start: u32, // Hour of the week
+    pub hours: u32,
+}
+
+impl WeekWindow {
+    pub fn contains(&self, hour_of_week: u32) -> bool {
+        (hour_of_week + HOURS_PER_WEEK - self.start % HOURS_PER_WEEK) % HOURS_PER_WEEK < self.hours
+    }
+
+    /// e.g. "Mon 02:00-07:00" or "Sat 22:00-Mon 06:00"
+    pub fn label(&self) -> String {
+        if self.hours >= HOURS_PER_WEEK {
+            return "All week".to_string();
+        }
+        let end = (self.start + self.hours) % HOURS_PER_WEEK;
+        let (start_day, end_day) = (DAYS[(self.start / 24) as usize % 7], DAYS[(end / 24) as usize]);
+        let same_day = self.start / 24 == end / 24 || (end.is_multiple_of(24) && self.hours <= 24);
+        match same_day {
+            true => format!("{} {:02}:00-{:02}:00", start_day, self.start % 24, match end % 24 { 0 => 24, hour => hour }),
+            false => format!("{} {:02}:00-{} {:02}:00", start_day, self.start % 24, end_day, end % 24),
+        }
+    }
+
+    /// Mean forecast utilization over the window
+    fn utilization(&self, forecast: &[HourForecast]) -> f32 {
+        let seen: Vec<f32> = forecast
+            .iter()
+            .filter(|hour| self.contains(hour.hour_of_week))
+            .filter_map(|hour| hour.utilization)
+            .collect();
+        seen.iter().sum::<f32>() / seen.len().max(1) as f32
+    }
+}
+
+/// Runs of consecutive forecast hours whose utilization `matches`
+fn windows(forecast: &[HourForecast], matches: impl Fn(f32) -> bool) -> Vec<WeekWindow> {
+    let hits: Vec<bool> = forecast.iter().map(|hour| hour.utilization.is_some_and(&matches)).collect();
+    // Scanning from a miss keeps a run across the end of the week whole
+    let Some(first_miss) = hits.iter().position(|hit| !hit) else {
+        return match hits.is_empty() {
+            true => vec![],
+            false => vec![WeekWindow { start: 0, hours: HOURS_PER_WEEK }],
+        };
+    };
+
+    let mut found = vec![];
+    let mut run: Option<WeekWindow> = None;
+    for offset in 1..=hits.len() {
+        let hour = (first_miss + offset) % hits.len();
+        match (hits[hour], &mut run) {
+            (true, Some(window)) => window.hours += 1,
+            (true, None) => run = Some(WeekWindow { start: hour as u32, hours: 1 }),
+            (false, _) => found.extend(run.take()),
+        }
+    }
+    found.sort_by_key(|window| window.start);
+    found
+}
+
+/// When the node is expected to sit idle
+pub fn idle_windows(forecast: &[HourForecast]) -> Vec<WeekWindow> {
+    windows(forecast, |utilization| utilization < IDLE_BELOW)
+}
+
+/// An asking price for part of the week
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PriceWindow {
+    pub window: WeekWindow,
+    pub price_per_hour: f32,
+    pub reason: String,
+}
+
+/// A discount off `base_price` for the chronically idle hours and a premium for the full ones
+pub fn suggest_prices(forecast: &[HourForecast], base_price: f32) -> Vec<PriceWindow> {
+    let cents = |price: f32| (price * 100.0).round() / 100.0;
+    let idle = idle_windows(forecast).into_iter().map(|window| PriceWindow {
+        window,
+        price_per_hour: cents(base_price * (1.0 - IDLE_DISCOUNT)),
+        reason: format!("Usually idle, {:.0}% busy", window.utilization(forecast) * 100.0),
+    });
+    let full = windows(forecast, |utilization| utilization >= FULL_FROM).into_iter().map(|window| PriceWindow {
+        window,
+        price_per_hour: cents(base_price * (1.0 + FULL_PREMIUM)),
+        reason: format!("Usually full, {:.0}% busy", window.utilization(forecast) * 100.0),
+    });
+    let mut suggestions: Vec<PriceWindow> = idle.chain(full).collect();
+    suggestions.sort_by_key(|suggestion| suggestion.window.start);
+    suggestions
+}
+
+/// Asking prices by hour of the week, over the owner's base price
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PriceSchedule {
+    pub windows: Vec<PriceWindow>,
+}
+
+impl PriceSchedule {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize price schedule: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write price schedule {}: {}", path.display(), e))
+    }
+
+    /// The asking price at `hour_of_week`: the first window holding it, otherwise `base_price`
+    pub fn price_at(&self, hour_of_week: u32, base_price: f32) -> f32 {
+        self.windows
+            .iter()
+            .find(|scheduled| scheduled.window.contains(hour_of_week))
+            .map(|scheduled| scheduled.price_per_hour)
+            .unwrap_or(base_price)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forecast_and_suggestions() {
+        // Thursday 1970-01-01 00:00 UTC is 02:00 two hours east
+        assert_eq!(hour_of_week(0, 0), 72);
+        assert_eq!(hour_of_week(0, 7200), 74);
+
+        // Two weeks from Monday 1970-01-05: idle from Sunday 22:00 to Monday 06:00, full on Friday evenings
+        let monday = 4 * 24 * 3600;
+        let mut history = UtilizationHistory::default();
+        for hour in 0..2 * HOURS_PER_WEEK as u64 {
+            let at = monday + hour * 3600;
+            let of_week = hour_of_week(at, 0);
+            let busy = match of_week {
+                0..=5 | 166..=167 => 0,
+                114..=117 => 4,
+                _ => 2,
+            };
+            history.record(at, 0, busy, 4);
+            history.record(at + 1800, 0, busy, 4);
+        }
+        assert_eq!(history.samples.len(), 2 * HOURS_PER_WEEK as usize);
+        assert_eq!(history.samples[0].readings, 2);
+
+        let forecast = history.forecast();
+        assert_eq!(forecast[3].weeks, 2);
+        assert_eq!(idle_windows(&forecast), vec![WeekWindow { start: 166, hours: 8 }]);
+        assert_eq!(WeekWindow { start: 166, hours: 8 }.label(), "Sun 22:00-Mon 06:00");
+        assert_eq!(WeekWindow { start: 114, hours: 4 }.label(), "Fri 18:00-22:00");
+
+        let suggestions = suggest_prices(&forecast, 4.0);
+        assert_eq!(suggestions.len(), 2);
+        assert_eq!((suggestions[0].window.start, suggestions[0].price_per_hour), (114, 4.6));
+        assert_eq!(suggestions[1].price_per_hour, 3.0);
+
+        let schedule = PriceSchedule { windows: suggestions };
+        assert_eq!(schedule.price_at(167, 4.0), 3.0);
+        assert_eq!(schedule.price_at(2, 4.0), 3.0);
+        assert_eq!(schedule.price_at(50, 4.0), 4.0);
+
+        // One week is not enough to go by
+        let mut recent = UtilizationHistory::default();
+        recent.record(monday, 0, 0, 4);
+        assert!(idle_windows(&recent.forecast()).is_empty());
+    }
+}
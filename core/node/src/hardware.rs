@@ -0,0 +1,567 @@
+//! Hardware change detection
+//! The watcher rescans GPUs, memory, disks and the NVIDIA driver periodically and whenever udev
+//! reports a PCI, block or DRM event, so the node's advertised capabilities follow the machine.
+//! Each scan also names the parts jobs can ask for by model: GPU compute capability and NVLink,
+//! the CPU and its AVX level, RAM speed, NVMe drives and TPU or FPGA accelerators.
+
+use eryzaa_discovery::hardware::{Accelerator, AvxLevel, GpuDevice, GpuLink, HardwareProfile};
+use eryzaa_discovery::{GpuStack, NodeCapabilities};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long to let a hotplug settle before rescanning
+const SETTLE_DELAY: Duration = Duration::from_secs(2);
+
+/// Where cuDNN packages install their version header
+const CUDNN_HEADERS: &[&str] = &[
+    "/usr/include/cudnn_version.h",
+    "/usr/include/x86_64-linux-gnu/cudnn_version_v9.h",
+    "/usr/include/x86_64-linux-gnu/cudnn_version_v8.h",
+    "/usr/local/cuda/include/cudnn_version.h",
+];
+
+/// Device nodes of Edge TPUs (apex) and of TPUs under the kernel's accel subsystem
+const TPU_DEVICE_PREFIXES: &[&str] = &["apex_", "accel"];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GpuInfo {
+    pub index: u32,
+    pub name: String,
+    pub memory_mb: u32,
+    #[serde(default)]
+    pub compute_capability: Option<String>, // e.g. "8.6"; drivers before 510 do not report it
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DiskInfo {
+    pub mount: String,
+    pub size_gb: u32,
+}
+
+/// What the machine has right now
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct HardwareSnapshot {
+    pub cpu_cores: u32,
+    pub memory_gb: u32,
+    pub gpus: Vec<GpuInfo>,
+    pub driver_version: Option<String>,
+    #[serde(default)]
+    pub cuda_version: Option<String>, // Newest CUDA the driver supports
+    #[serde(default)]
+    pub cudnn_version: Option<String>,
+    pub disks: Vec<DiskInfo>,
+    #[serde(default)]
+    pub nvlink: bool, // Some NVLink between the GPUs is active
+    #[serde(default)]
+    pub cpu_model: Option<String>,
+    #[serde(default)]
+    pub avx: Option<AvxLevel>,
+    #[serde(default)]
+    pub memory_speed_mts: Option<u32>,
+    #[serde(default)]
+    pub nvme: bool,
+    #[serde(default)]
+    pub accelerators: Vec<Accelerator>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum HardwareChange {
+    GpuAdded { index: u32, name: String },
+    GpuRemoved { index: u32, name: String },
+    DriverChanged { from: Option<String>, to: Option<String> },
+    MemoryChanged { from_gb: u32, to_gb: u32 },
+    CpuCoresChanged { from: u32, to: u32 },
+    DiskAdded { mount: String, size_gb: u32 },
+    DiskRemoved { mount: String },
+}
+
+impl std::fmt::Display for HardwareChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HardwareChange::GpuAdded { index, name } => write!(f, "GPU {} added ({})", index, name),
+            HardwareChange::GpuRemoved { index, name } => write!(f, "GPU {} removed ({})", index, name),
+            HardwareChange::DriverChanged { from, to } => write!(
+                f,
+                "NVIDIA driver {} → {}",
+                from.as_deref().unwrap_or("none"),
+                to.as_deref().unwrap_or("none")
+            ),
+            HardwareChange::MemoryChanged { from_gb, to_gb } => write!(f, "memory {} GB → {} GB", from_gb, to_gb),
+            HardwareChange::CpuCoresChanged { from, to } => write!(f, "CPU cores {} → {}", from, to),
+            HardwareChange::DiskAdded { mount, size_gb } => write!(f, "disk {} added ({} GB)", mount, size_gb),
+            HardwareChange::DiskRemoved { mount } => write!(f, "disk {} removed", mount),
+        }
+    }
+}
+
+impl HardwareSnapshot {
+    /// Scan the local machine; parts that cannot be queried are left empty
+    pub fn scan() -> Self {
+        // Drivers that do not know compute_cap refuse the whole query, so retry without it
+        let (gpus, driver_version) = ["index,name,memory.total,driver_version,compute_cap", "index,name,memory.total,driver_version"]
+            .iter()
+            .find_map(|fields| {
+                Command::new("nvidia-smi")
+                    .args([format!("--query-gpu={}", fields).as_str(), "--format=csv,noheader,nounits"])
+                    .output()
+                    .ok()
+                    .filter(|output| output.status.success())
+            })
+            .map(|output| parse_gpu_inventory(&String::from_utf8_lossy(&output.stdout)))
+            .unwrap_or_default();
+        let nvlink = gpus.len() > 1
+            && Command::new("nvidia-smi")
+                .args(["nvlink", "--status"])
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .is_some_and(|output| parse_nvlink_status(&String::from_utf8_lossy(&output.stdout)));
+
+        // Only the banner of plain `nvidia-smi` reports the CUDA version
+        let cuda_version = Command::new("nvidia-smi")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| parse_cuda_banner(&String::from_utf8_lossy(&output.stdout)));
+        let cudnn_version = CUDNN_HEADERS
+            .iter()
+            .find_map(|path| std::fs::read_to_string(path).ok())
+            .and_then(|header| parse_cudnn_header(&header));
+
+        let memory_gb = std::fs::read_to_string("/proc/meminfo")
+            .map(|content| parse_meminfo_gb(&content))
+            .unwrap_or(0);
+
+        let disks = Command::new("df")
+            .args(["-B1G", "--output=target,size", "-x", "tmpfs", "-x", "devtmpfs", "-x", "overlay", "-x", "squashfs"])
+            .output()
+            .ok()
+            .map(|output| parse_df(&String::from_utf8_lossy(&output.stdout)))
+            .unwrap_or_default();
+
+        let (cpu_model, avx) = std::fs::read_to_string("/proc/cpuinfo")
+            .map(|content| parse_cpuinfo(&content))
+            .unwrap_or_default();
+        // dmidecode needs root; without it the speed is simply not advertised
+        let memory_speed_mts = Command::new("dmidecode")
+            .args(["-t", "memory"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| parse_memory_speed(&String::from_utf8_lossy(&output.stdout)));
+        let has_entries = |dir: &str, prefixes: &[&str]| {
+            std::fs::read_dir(dir)
+                .map(|entries| {
+                    entries
+                        .flatten()
+                        .any(|entry| prefixes.iter().any(|prefix| entry.file_name().to_string_lossy().starts_with(prefix)))
+                })
+                .unwrap_or(false)
+        };
+        let mut accelerators = vec![];
+        if has_entries("/dev", TPU_DEVICE_PREFIXES) {
+            accelerators.push(Accelerator::Tpu);
+        }
+        if has_entries("/sys/class/fpga_manager", &[""]) {
+            accelerators.push(Accelerator::Fpga);
+        }
+
+        Self {
+            cpu_cores: thread::available_parallelism().map(|n| n.get() as u32).unwrap_or(1),
+            memory_gb,
+            gpus,
+            driver_version,
+            cuda_version,
+            cudnn_version,
+            disks,
+            nvlink,
+            cpu_model,
+            avx,
+            memory_speed_mts,
+            nvme: has_entries("/sys/class/nvme", &["nvme"]),
+            accelerators,
+        }
+    }
+
+    /// Changes going from `self` to `current`
+    pub fn diff(&self, current: &HardwareSnapshot) -> Vec<HardwareChange> {
+        let mut changes = Vec::new();
+
+        for gpu in &current.gpus {
+            if !self.gpus.iter().any(|g| g.index == gpu.index && g.name == gpu.name) {
+                changes.push(HardwareChange::GpuAdded { index: gpu.index, name: gpu.name.clone() });
+            }
+        }
+        for gpu in &self.gpus {
+            if !current.gpus.iter().any(|g| g.index == gpu.index && g.name == gpu.name) {
+                changes.push(HardwareChange::GpuRemoved { index: gpu.index, name: gpu.name.clone() });
+            }
+        }
+        if self.driver_version != current.driver_version {
+            changes.push(HardwareChange::DriverChanged {
+                from: self.driver_version.clone(),
+                to: current.driver_version.clone(),
+            });
+        }
+        if self.memory_gb != current.memory_gb {
+            changes.push(HardwareChange::MemoryChanged { from_gb: self.memory_gb, to_gb: current.memory_gb });
+        }
+        if self.cpu_cores != current.cpu_cores {
+            changes.push(HardwareChange::CpuCoresChanged { from: self.cpu_cores, to: current.cpu_cores });
+        }
+        for disk in &current.disks {
+            if !self.disks.iter().any(|d| d.mount == disk.mount) {
+                changes.push(HardwareChange::DiskAdded { mount: disk.mount.clone(), size_gb: disk.size_gb });
+            }
+        }
+        for disk in &self.disks {
+            if !current.disks.iter().any(|d| d.mount == disk.mount) {
+                changes.push(HardwareChange::DiskRemoved { mount: disk.mount.clone() });
+            }
+        }
+
+        changes
+    }
+
+    /// Driver, CUDA and cuDNN versions, when there is an NVIDIA driver
+    pub fn gpu_stack(&self) -> Option<GpuStack> {
+        Some(GpuStack {
+            driver_version: self.driver_version.clone()?,
+            cuda_version: self.cuda_version.clone(),
+            cudnn_version: self.cudnn_version.clone(),
+        })
+    }
+
+    /// The parts jobs can ask for by name
+    pub fn hardware_profile(&self) -> HardwareProfile {
+        HardwareProfile {
+            gpus: self
+                .gpus
+                .iter()
+                .map(|gpu| GpuDevice {
+                    model: gpu.name.clone(),
+                    memory_mb: gpu.memory_mb as u64,
+                    compute_capability: gpu.compute_capability.clone(),
+                })
+                .collect(),
+            gpu_link: match (self.gpus.len(), self.nvlink) {
+                (0 | 1, _) => GpuLink::Unknown,
+                (_, true) => GpuLink::NvLink,
+                (_, false) => GpuLink::Pcie,
+            },
+            cpu_model: self.cpu_model.clone(),
+            avx: self.avx,
+            memory_speed_mts: self.memory_speed_mts,
+            nvme: self.nvme,
+            accelerators: self.accelerators.clone(),
+        }
+    }
+
+    /// `base` with its hardware fields replaced by this snapshot
+    pub fn capabilities(&self, base: &NodeCapabilities) -> NodeCapabilities {
+        NodeCapabilities {
+            cpu_cores: self.cpu_cores,
+            memory_gb: self.memory_gb,
+            gpu_count: self.gpus.len() as u32,
+            gpu_memory_gb: self.gpus.iter().map(|g| g.memory_mb).max().unwrap_or(0) / 1024,
+            disk_space_gb: self.disks.iter().map(|d| d.size_gb).sum(),
+            supports_gpu: !self.gpus.is_empty(),
+            gpu_stack: self.gpu_stack(),
+            hardware: self.hardware_profile(),
+            ..base.clone()
+        }
+    }
+}
+
+/// A hardware change as logged for running-job impact analysis
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CapabilityChangeEvent {
+    pub timestamp: u64,
+    pub changes: Vec<HardwareChange>,
+    pub running_jobs: Vec<String>,
+    pub at_risk_jobs: Vec<String>, // Jobs that may have lost hardware they were using
+}
+
+impl CapabilityChangeEvent {
+    /// Build the event from the running jobs and the GPUs each of them holds
+    pub fn new(changes: Vec<HardwareChange>, current: &HardwareSnapshot, running: &[(String, u32)]) -> Self {
+        let driver_changed = changes.iter().any(|c| matches!(c, HardwareChange::DriverChanged { .. }));
+        let gpu_removed = changes.iter().any(|c| matches!(c, HardwareChange::GpuRemoved { .. }));
+        let gpus_left = current.gpus.len() as u32;
+
+        let at_risk_jobs = running
+            .iter()
+            .filter(|(_, gpus)| *gpus > 0 && (driver_changed || (gpu_removed && *gpus > gpus_left)))
+            .map(|(job_id, _)| job_id.clone())
+            .collect();
+
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            changes,
+            running_jobs: running.iter().map(|(job_id, _)| job_id.clone()).collect(),
+            at_risk_jobs,
+        }
+    }
+
+    /// Append the event as one JSON line
+    pub fn append_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let line = serde_json::to_string(self)
+            .map_err(|e| format!("Failed to serialize capability change: {}", e))?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        writeln!(file, "{}", line).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+/// Rescans the hardware on udev events and every `interval`
+pub struct HardwareWatcher {
+    pub interval: Duration,
+    last: HardwareSnapshot,
+}
+
+impl HardwareWatcher {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: HardwareSnapshot::scan(),
+        }
+    }
+
+    pub fn snapshot(&self) -> &HardwareSnapshot {
+        &self.last
+    }
+
+    /// Watch on a background thread, calling `on_change` with the new snapshot and what changed
+    pub fn spawn<F>(mut self, mut on_change: F) -> thread::JoinHandle<()>
+    where
+        F: FnMut(&HardwareSnapshot, Vec<HardwareChange>) + Send + 'static,
+    {
+        let (wake, woken) = mpsc::channel();
+        watch_udev(wake);
+
+        thread::spawn(move || loop {
+            match woken.recv_timeout(self.interval) {
+                Ok(()) => {
+                    // Let the device finish appearing, then drop the burst of events it caused
+                    thread::sleep(SETTLE_DELAY);
+                    while woken.try_recv().is_ok() {}
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                // udev monitoring is unavailable; rely on the periodic rescan
+                Err(mpsc::RecvTimeoutError::Disconnected) => thread::sleep(self.interval),
+            }
+
+            let current = HardwareSnapshot::scan();
+            let changes = self.last.diff(&current);
+            if !changes.is_empty() {
+                for change in &changes {
+                    info!("Hardware change: {}", change);
+                }
+                on_change(&current, changes);
+                self.last = current;
+            }
+        })
+    }
+}
+
+/// Forward PCI, block and DRM udev events to `wake`
+fn watch_udev(wake: mpsc::Sender<()>) {
+    let child = Command::new("udevadm")
+        .args(["monitor", "--udev", "--subsystem-match=pci", "--subsystem-match=block", "--subsystem-match=drm"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("udev monitoring unavailable, rescanning periodically: {}", e);
+            return;
+        }
+    };
+
+    let Some(stdout) = child.stdout.take() else {
+        return;
+    };
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            // Event lines look like "UDEV  [1234.5678] add /devices/... (pci)"
+            if line.starts_with("UDEV") && (line.contains(" add ") || line.contains(" remove ") || line.contains(" change ")) && wake.send(()).is_err() {
+                break;
+            }
+        }
+        let _ = child.kill();
+    });
+}
+
+/// Parse `nvidia-smi --query-gpu=index,name,memory.total,driver_version[,compute_cap]` rows
+pub fn parse_gpu_inventory(output: &str) -> (Vec<GpuInfo>, Option<String>) {
+    let mut driver = None;
+    let gpus = output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            if fields.len() < 4 {
+                return None;
+            }
+            driver = Some(fields[3].to_string());
+            Some(GpuInfo {
+                index: fields[0].parse().ok()?,
+                name: fields[1].to_string(),
+                memory_mb: fields[2].parse().unwrap_or(0),
+                compute_capability: fields.get(4).filter(|cc| cc.contains('.')).map(|cc| cc.to_string()),
+            })
+        })
+        .collect();
+    (gpus, driver)
+}
+
+/// "| NVIDIA-SMI 535.104.05   Driver Version: 535.104.05   CUDA Version: 12.2 |" → "12.2"
+pub fn parse_cuda_banner(output: &str) -> Option<String> {
+    let rest = output.split("CUDA Version:").nth(1)?;
+    rest.split_whitespace().next().map(|version| version.to_string())
+}
+
+/// Version from the CUDNN_MAJOR, CUDNN_MINOR and CUDNN_PATCHLEVEL defines
+fn parse_cudnn_header(header: &str) -> Option<String> {
+    let define = |name: &str| {
+        header.lines().find_map(|line| {
+            let mut fields = line.split_whitespace();
+            (fields.next() == Some("#define") && fields.next() == Some(name)).then(|| fields.next())?
+        })
+    };
+    Some(format!("{}.{}.{}", define("CUDNN_MAJOR")?, define("CUDNN_MINOR")?, define("CUDNN_PATCHLEVEL")?))
+}
+
+/// Whether `nvidia-smi nvlink --status` lists an active link, e.g. "Link 0: 25.781 GB/s"
+pub fn parse_nvlink_status(output: &str) -> bool {
+    output.lines().any(|line| line.trim_start().starts_with("Link") && line.contains("GB/s"))
+}
+
+/// CPU model and AVX level from /proc/cpuinfo; ARM kernels list neither line
+fn parse_cpuinfo(content: &str) -> (Option<String>, Option<AvxLevel>) {
+    let field = |name: &str| {
+        content.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            (key.trim() == name).then(|| value.trim().to_string())
+        })
+    };
+    (field("model name"), field("flags").map(|flags| AvxLevel::from_cpu_flags(&flags)))
+}
+
+/// Slowest configured speed among the DIMMs `dmidecode -t memory` lists, in MT/s
+fn parse_memory_speed(output: &str) -> Option<u32> {
+    output
+        .lines()
+        .filter_map(|line| {
+            // dmidecode before 3.2 calls it a clock speed in MHz
+            let value = line.trim().strip_prefix("Configured Memory Speed:").or_else(|| line.trim().strip_prefix("Configured Clock Speed:"))?;
+            value.split_whitespace().next()?.parse().ok()
+        })
+        .min()
+}
+
+fn parse_meminfo_gb(content: &str) -> u32 {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("MemTotal:"))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|kb| kb.parse::<u64>().ok())
+        .map(|kb| (kb / 1_048_576) as u32)
+        .unwrap_or(0)
+}
+
+/// Parse `df -B1G --output=target,size` rows, skipping the header
+fn parse_df(output: &str) -> Vec<DiskInfo> {
+    output
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mount = fields.next()?.to_string();
+            let size_gb = fields.next()?.trim_end_matches('G').parse().ok()?;
+            Some(DiskInfo { mount, size_gb })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_and_impact() {
+        let (gpus, driver) = parse_gpu_inventory("0, NVIDIA GeForce RTX 3090, 24576, 535.104.05, 8.6\n1, NVIDIA GeForce RTX 3090, 24576, 535.104.05, 8.6\n");
+        let (cpu_model, avx) = parse_cpuinfo("processor\t: 0\nmodel name\t: AMD Ryzen 9 5950X 16-Core Processor\nflags\t\t: fpu sse2 avx avx2\n");
+        let before = HardwareSnapshot {
+            cpu_cores: 16,
+            memory_gb: parse_meminfo_gb("MemTotal:       65843212 kB\nMemFree:  1000 kB\n"),
+            gpus,
+            driver_version: driver,
+            cuda_version: parse_cuda_banner("| NVIDIA-SMI 535.104.05   Driver Version: 535.104.05   CUDA Version: 12.2     |\n"),
+            cudnn_version: parse_cudnn_header("#define CUDNN_MAJOR 8\n#define CUDNN_MINOR 9\n#define CUDNN_PATCHLEVEL 2\n"),
+            disks: parse_df("Mounted on  1G-blocks\n/  937G\n/data  1863G\n"),
+            nvlink: parse_nvlink_status("GPU 0: NVIDIA GeForce RTX 3090\n\t Link 0: 14.062 GB/s\n\t Link 1: <inactive>\n"),
+            cpu_model,
+            avx,
+            memory_speed_mts: parse_memory_speed("\tSpeed: 3600 MT/s\n\tConfigured Memory Speed: 3200 MT/s\n\tConfigured Memory Speed: Unknown\n"),
+            nvme: true,
+            accelerators: vec![],
+        };
+        assert_eq!(before.memory_gb, 62);
+        assert_eq!(before.disks[1], DiskInfo { mount: "/data".to_string(), size_gb: 1863 });
+        assert_eq!(before.hardware_profile().summary(), "2× NVIDIA GeForce RTX 3090 (sm 8.6), NVLink, AVX2, RAM 3200 MT/s, NVMe");
+
+        let mut after = before.clone();
+        after.gpus.pop();
+        after.disks.push(DiskInfo { mount: "/scratch".to_string(), size_gb: 500 });
+
+        let changes = before.diff(&after);
+        assert_eq!(changes, vec![
+            HardwareChange::GpuRemoved { index: 1, name: "NVIDIA GeForce RTX 3090".to_string() },
+            HardwareChange::DiskAdded { mount: "/scratch".to_string(), size_gb: 500 },
+        ]);
+
+        let running = [("two-gpus".to_string(), 2), ("one-gpu".to_string(), 1), ("cpu".to_string(), 0)];
+        let event = CapabilityChangeEvent::new(changes, &after, &running);
+        assert_eq!(event.at_risk_jobs, vec!["two-gpus".to_string()]);
+        assert_eq!(event.running_jobs.len(), 3);
+
+        let base = NodeCapabilities {
+            cpu_cores: 0,
+            memory_gb: 0,
+            gpu_count: 0,
+            gpu_memory_gb: 0,
+            disk_space_gb: 0,
+            network_speed_mbps: 1000,
+            supports_docker: true,
+            supports_gpu: false,
+            max_concurrent_jobs: 4,
+            gpu_stack: None,
+            hardware: HardwareProfile::default(),
+        };
+        let capabilities = after.capabilities(&base);
+        assert_eq!((capabilities.gpu_count, capabilities.gpu_memory_gb, capabilities.disk_space_gb), (1, 24, 3300));
+        assert!(capabilities.supports_gpu && capabilities.supports_docker);
+        // One GPU left has nothing to link to
+        assert_eq!(capabilities.hardware.gpu_link, GpuLink::Unknown);
+        let stack = capabilities.gpu_stack.unwrap();
+        assert_eq!((stack.cuda_version.as_deref(), stack.cudnn_version.as_deref()), (Some("12.2"), Some("8.9.2")));
+    }
+}
@@ -0,0 +1,39 @@
+//! Host-level management for Eryzaa rental nodes
+//! Used by the rental server and the rental GUI to configure the machine they run on
+
+pub mod accounting;
+pub mod admission;
+pub mod concurrency;
+pub mod egress;
+pub mod energy;
+pub mod firewall;
+pub mod forecast;
+pub mod hardware;
+pub mod lockdown;
+pub mod login_approval;
+pub mod onboarding;
+pub mod readiness;
+pub mod recording;
+pub mod selftest;
+pub mod supervisor;
+pub mod thermal;
+pub mod trials;
+pub mod usage;
+
+pub use accounting::{AccountedContainer, JobCounters};
+pub use admission::{AdmissionDecision, AdmissionRequest, AdmissionRule, AdmissionRules, Condition};
+pub use concurrency::ConcurrencyLimit;
+pub use egress::{EgressPolicy, EgressPreset};
+pub use energy::{EnergyMeter, EnergyModel, MeteredJob};
+pub use firewall::{FirewallPolicy, FirewallRule, Protocol};
+pub use forecast::{PriceSchedule, UtilizationHistory};
+pub use hardware::{CapabilityChangeEvent, HardwareChange, HardwareSnapshot, HardwareWatcher};
+pub use lockdown::{Incident, LockdownState, Tenants};
+pub use login_approval::{LoginApproval, LoginQueue, LoginRequest};
+pub use onboarding::{Benchmark, Onboarding};
+pub use recording::{Playback, Recording};
+pub use selftest::SelfTest;
+pub use supervisor::{HealthCheck, RestartPolicy, Service, ServiceReport, ServiceSet, ServiceStatus, Supervisor};
+pub use thermal::{QuietHours, StartDecision, ThermalPolicy, ThermalScheduler};
+pub use trials::{TrialLedger, TrialRecord};
+pub use usage::{JobUsage, UsageSampler};
@@ -0,0 +1,420 @@
+//! Panic button
+//! One action cuts every tenant off the node: overlay and container traffic is dropped, job
+//! users' sessions are killed and their accounts locked, and job containers are killed but not
+//! removed, so their filesystems stay for review. An incident snapshot (processes, connections,
+//! recent logins and audit logs) is taken right after the network is cut, as dispute evidence.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use log::{info, warn};
+
+use eryzaa_ssh_manager::accounts::job_user_prefix;
+
+const LOCKDOWN_TABLE: &str = "eryzaa_lockdown";
+const AUDIT_TAIL_LINES: usize = 100;
+const TOKEN_FILE: &str = "trigger-token";
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Port of the rental server's panic endpoint, bound to localhost only
+pub const TRIGGER_PORT: u16 = 8091;
+pub const TRIGGER_PATH: &str = "/api/panic";
+
+/// Who has access to the node right now
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tenants {
+    pub containers: Vec<String>,
+    pub users: Vec<String>,
+}
+
+impl Tenants {
+    /// Add job users that exist on the machine but are missing from the job registry
+    pub fn with_system_users(mut self) -> Self {
+        let passwd = std::fs::read_to_string("/etc/passwd").unwrap_or_default();
+        self.users.extend(
+            passwd
+                .lines()
+                .filter_map(|line| line.split(':').next())
+                .filter(|user| user.starts_with(job_user_prefix()))
+                .map(|user| user.to_string()),
+        );
+        self.users.sort();
+        self.users.dedup();
+        self
+    }
+}
+
+/// One thing the panic button did, and whether it worked
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Step {
+    pub action: String,
+    pub ok: bool,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Incident {
+    pub incident_id: String,
+    pub reason: String,
+    pub taken_at: u64, // Unix seconds
+    pub sections: BTreeMap<String, String>, // "processes", "connections", ... as captured
+    pub steps: Vec<Step>,
+}
+
+impl Incident {
+    /// Every incident kept in `dir`, newest first
+    pub fn list(dir: &Path) -> Vec<Incident> {
+        let mut incidents: Vec<Incident> = std::fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+                    .filter_map(|entry| serde_json::from_str(&std::fs::read_to_string(entry.path()).ok()?).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        incidents.sort_by_key(|incident| std::cmp::Reverse(incident.taken_at));
+        incidents
+    }
+
+    fn save_in(&self, dir: &Path) -> Result<PathBuf, String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let path = dir.join(format!("{}.json", self.incident_id));
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize incident: {}", e))?;
+        std::fs::write(&path, content).map_err(|e| format!("Failed to write incident {}: {}", path.display(), e))?;
+        Ok(path)
+    }
+}
+
+/// Whether the node is locked down, kept so the rental server starts nothing until it is lifted
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct LockdownState {
+    pub active: bool,
+    pub incident_id: Option<String>,
+    pub engaged_at: Option<u64>,
+    #[serde(default)]
+    pub locked_users: Vec<String>, // Unlocked again when the lockdown is lifted
+}
+
+impl LockdownState {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize lockdown state: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write lockdown state {}: {}", path.display(), e))
+    }
+}
+
+/// Drop everything from the overlay and to or from job containers, ahead of any accept rule
+///
+/// Established connections are not exempt, so open tenant sessions stall at once.
+pub fn nftables_ruleset(overlay_subnets: &[String]) -> String {
+    let mut input = String::from("        iifname \"lo\" accept\n");
+    if !overlay_subnets.is_empty() {
+        input.push_str(&format!("        ip saddr {{ {} }} drop comment \"lockdown\"\n", overlay_subnets.join(", ")));
+    }
+    input.push_str("        iifname \"zt*\" drop comment \"lockdown\"\n");
+    let bridges = ["docker0", "br-*"];
+    for bridge in bridges {
+        input.push_str(&format!("        iifname \"{}\" drop comment \"lockdown\"\n", bridge));
+    }
+    let forward: String = bridges
+        .iter()
+        .map(|bridge| format!("        iifname \"{0}\" drop comment \"lockdown\"\n        oifname \"{0}\" drop comment \"lockdown\"\n", bridge))
+        .collect();
+
+    format!(
+        "table inet {table}\ndelete table inet {table}\ntable inet {table} {{\n    chain input {{\n        type filter hook input priority -50; policy accept;\n{input}    }}\n    chain forward {{\n        type filter hook forward priority -50; policy accept;\n{forward}    }}\n}}\n",
+        table = LOCKDOWN_TABLE,
+        input = input,
+        forward = forward
+    )
+}
+
+/// Cut every tenant off and keep an incident snapshot in `incidents_dir`
+///
+/// Every step runs even when an earlier one fails; the incident records what worked.
+/// `audit_logs` are files whose last lines go into the snapshot, by section name.
+pub fn engage(
+    reason: &str,
+    tenants: &Tenants,
+    overlay_subnets: &[String],
+    audit_logs: &[(&str, &Path)],
+    incidents_dir: &Path,
+) -> Result<Incident, String> {
+    let taken_at = now();
+    let mut steps = vec![];
+    let mut step = |action: String, result: Result<String, String>| {
+        match &result {
+            Ok(_) => info!("Lockdown: {}", action),
+            Err(e) => warn!("Lockdown: {} failed: {}", action, e),
+        }
+        steps.push(Step { ok: result.is_ok(), detail: result.unwrap_or_else(|e| e), action });
+    };
+
+    // Network first: it is one command and stops everything in flight
+    step("Block overlay and container traffic".to_string(), nft(&nftables_ruleset(overlay_subnets)).map(|_| String::new()));
+
+    let mut sections = BTreeMap::new();
+    let until = taken_at.to_string();
+    let since = taken_at.saturating_sub(3600).to_string();
+    let captures: [(&str, &str, &[&str]); 7] = [
+        ("processes", "ps", &["auxww"]),
+        ("connections", "ss", &["-tunap"]),
+        ("containers", "docker", &["ps", "-a", "--no-trunc"]),
+        ("sessions", "who", &["-a"]),
+        ("logins", "last", &["-n", "50"]),
+        ("sshd", "journalctl", &["-u", "ssh", "-u", "sshd", "-n", "200", "--no-pager"]),
+        ("docker-events", "docker", &["events", "--since", &since, "--until", &until]),
+    ];
+    for (section, program, args) in captures {
+        sections.insert(section.to_string(), capture(program, args));
+    }
+    for (section, path) in audit_logs {
+        let tail = match std::fs::read_to_string(path) {
+            Ok(content) => {
+                let lines: Vec<&str> = content.lines().collect();
+                lines[lines.len().saturating_sub(AUDIT_TAIL_LINES)..].join("\n")
+            }
+            Err(e) => format!("<unreadable: {}>", e),
+        };
+        sections.insert(section.to_string(), tail);
+    }
+
+    for user in &tenants.users {
+        // pkill exits 1 when the user has no processes
+        let killed = Command::new("pkill")
+            .args(["-KILL", "-u", user])
+            .status()
+            .map_err(|e| format!("Failed to execute pkill: {}", e))
+            .and_then(|status| match status.code() {
+                Some(0) => Ok("Sessions killed".to_string()),
+                Some(1) => Ok("No running processes".to_string()),
+                _ => Err(format!("pkill exited with {}", status)),
+            });
+        step(format!("Kill sessions of {}", user), killed);
+        // Locked password and an expired account: no new login by key or password
+        step(format!("Disable user {}", user), run("usermod", &["-L", "-e", "1", user]));
+    }
+    for container in &tenants.containers {
+        let _ = run("docker", &["update", "--restart=no", container]);
+        step(format!("Kill container {}", container), run("docker", &["kill", container]));
+    }
+
+    let incident = Incident {
+        incident_id: format!("incident-{}", taken_at),
+        reason: reason.to_string(),
+        taken_at,
+        sections,
+        steps,
+    };
+    let path = incident.save_in(incidents_dir)?;
+    info!("Lockdown engaged; incident snapshot at {}", path.display());
+    Ok(incident)
+}
+
+/// Let tenants back: drop the lockdown rules and unlock the users locked by it
+///
+/// Killed containers stay stopped; their jobs are over.
+pub fn lift(state: &LockdownState) -> Vec<Step> {
+    let mut actions = vec![("Remove lockdown rules".to_string(), run("nft", &["delete", "table", "inet", LOCKDOWN_TABLE]))];
+    for user in &state.locked_users {
+        actions.push((format!("Enable user {}", user), run("usermod", &["-U", "-e", "", user])));
+    }
+    info!("Lockdown lifted");
+    actions
+        .into_iter()
+        .map(|(action, result)| Step { action, ok: result.is_ok(), detail: result.unwrap_or_else(|e| e) })
+        .collect()
+}
+
+/// Shared secret callers of the panic endpoint send as a bearer token, created on first use
+pub fn trigger_token(dir: &Path) -> Result<String, String> {
+    let path = dir.join(TOKEN_FILE);
+    if let Ok(token) = std::fs::read_to_string(&path) {
+        return Ok(token.trim().to_string());
+    }
+
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    std::fs::write(&path, &token).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("Failed to restrict {}: {}", path.display(), e))?;
+    }
+    Ok(token)
+}
+
+/// Answer panic requests on `listener`; `trigger` engages the lockdown with the posted reason
+pub fn serve_trigger(listener: TcpListener, token: &str, trigger: impl Fn(&str) -> Result<String, String>) {
+    for stream in listener.incoming().flatten() {
+        let _ = respond(stream, token, &trigger);
+    }
+}
+
+fn respond(mut stream: TcpStream, token: &str, trigger: &impl Fn(&str) -> Result<String, String>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    let mut content_length = 0;
+    let mut authorization = String::new();
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            } else if name.eq_ignore_ascii_case("authorization") {
+                authorization = value.trim().to_string();
+            }
+        }
+    }
+    let mut body = vec![0; content_length.min(4096)];
+    reader.read_exact(&mut body)?;
+
+    let (status, body) = route(&request_line, &authorization, &String::from_utf8_lossy(&body), token, trigger);
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+/// Status and body for a request to the panic endpoint
+pub fn route(
+    request_line: &str,
+    authorization: &str,
+    body: &str,
+    token: &str,
+    trigger: &impl Fn(&str) -> Result<String, String>,
+) -> (&'static str, String) {
+    let mut parts = request_line.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("POST"), Some(TRIGGER_PATH)) => {
+            if authorization.strip_prefix("Bearer ") != Some(token) {
+                return ("401 Unauthorized", "Missing or wrong token\n".to_string());
+            }
+            let reason = match body.trim() {
+                "" => "Panic endpoint",
+                reason => reason,
+            };
+            match trigger(reason) {
+                Ok(message) => ("200 OK", message + "\n"),
+                Err(e) => ("500 Internal Server Error", e + "\n"),
+            }
+        }
+        (Some("POST"), _) => ("404 Not Found", "Not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "Only POST is supported\n".to_string()),
+    }
+}
+
+fn capture(program: &str, args: &[&str]) -> String {
+    match Command::new(program).args(args).output() {
+        Ok(output) => {
+            let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+            if !output.status.success() {
+                text.push_str(&format!("<{} exited with {}: {}>", program, output.status, String::from_utf8_lossy(&output.stderr).trim()));
+            }
+            text
+        }
+        Err(e) => format!("<failed to execute {}: {}>", program, e),
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to execute {}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn nft(ruleset: &str) -> Result<(), String> {
+    use std::process::Stdio;
+
+    let mut child = Command::new("nft")
+        .args(["-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute nft: {}", e))?;
+
+    if let Some(stdin) = child.stdin.as_mut() {
+        stdin
+            .write_all(ruleset.as_bytes())
+            .map_err(|e| format!("Failed to write lockdown ruleset: {}", e))?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to wait for nft: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "nft rejected lockdown ruleset: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockdown_rules_and_trigger() {
+        let ruleset = nftables_ruleset(&["10.242.0.0/16".to_string()]);
+        assert!(ruleset.contains("type filter hook input priority -50; policy accept;"));
+        assert!(ruleset.contains("ip saddr { 10.242.0.0/16 } drop comment \"lockdown\""));
+        assert!(ruleset.contains("oifname \"br-*\" drop"));
+        assert!(!ruleset.contains("established"));
+
+        let trigger = |reason: &str| Ok(format!("Locked down: {}", reason));
+        assert_eq!(route("POST /api/panic HTTP/1.1", "Bearer s3cret", "", "s3cret", &trigger), ("200 OK", "Locked down: Panic endpoint\n".to_string()));
+        assert_eq!(route("POST /api/panic HTTP/1.1", "Bearer s3cret", "miner found\n", "s3cret", &trigger).1, "Locked down: miner found\n");
+        assert_eq!(route("POST /api/panic HTTP/1.1", "Bearer guess", "", "s3cret", &trigger).0, "401 Unauthorized");
+        assert_eq!(route("GET /api/panic HTTP/1.1", "Bearer s3cret", "", "s3cret", &trigger).0, "405 Method Not Allowed");
+
+        let dir = std::env::temp_dir().join(format!("eryzaa-lockdown-{}", std::process::id()));
+        let token = trigger_token(&dir).unwrap();
+        assert_eq!(token.len(), 32);
+        assert_eq!(trigger_token(&dir).unwrap(), token);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,286 @@
+//! SSH login approval
+//! In approval mode sshd runs every job user's login through a gate that files a request and
+//! waits; the owner's rules answer it straight away or leave it to a click in the rental GUI.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::admission::{AdmissionDecision, AdmissionRequest, AdmissionRules};
+
+/// Where gates file login requests and owners answer them; job users must be able to write here
+pub const QUEUE_DIR: &str = "/var/lib/eryzaa/logins";
+
+/// sshd drop-in that routes job users through the gate while approval is on
+pub const SSHD_SNIPPET_PATH: &str = "/etc/ssh/sshd_config.d/eryzaa-login-approval.conf";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginApproval {
+    pub enabled: bool,
+    pub rules: AdmissionRules, // Accept approves, Reject denies, AskOwner waits for a click
+    pub timeout_secs: u64,     // Unanswered logins are turned away after this
+    #[serde(default)]
+    pub record_sessions: bool, // Interactive job logins are recorded, see `recording`
+}
+
+impl Default for LoginApproval {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            rules: AdmissionRules {
+                rules: vec![],
+                default_decision: AdmissionDecision::AskOwner,
+            },
+            timeout_secs: 120,
+            record_sessions: false,
+        }
+    }
+}
+
+impl LoginApproval {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize login approval: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write login approval {}: {}", path.display(), e))
+    }
+
+    /// Answer the rules give for a login at local `hour`, or None when the owner decides
+    pub fn auto_decision(&self, request: &LoginRequest, hour: u32) -> Option<bool> {
+        if !self.enabled {
+            return Some(true);
+        }
+        let admission = AdmissionRequest {
+            client_id: request.client_id.clone().unwrap_or_default(),
+            hour,
+            ..Default::default()
+        };
+        match self.rules.evaluate(&admission).decision {
+            AdmissionDecision::Accept => Some(true),
+            AdmissionDecision::Reject => Some(false),
+            AdmissionDecision::AskOwner => None,
+        }
+    }
+
+    /// Whether job logins have to go through the gate at all
+    pub fn gate_needed(&self) -> bool {
+        self.enabled || self.record_sessions
+    }
+
+    /// sshd drop-in for `gate`, the command that runs the login gate
+    ///
+    /// The gate gets the approval timeout (0 without approval) and `--record` when recording.
+    pub fn sshd_snippet(&self, gate: &str) -> String {
+        let purpose = match (self.enabled, self.record_sessions) {
+            (true, true) => "job logins wait for the owner's approval and are recorded",
+            (true, false) => "job logins wait for the owner's approval",
+            _ => "job logins are recorded",
+        };
+        format!(
+            "# Managed by Eryzaa: {}\nMatch User job_*\n    ForceCommand {} {}{}\n",
+            purpose,
+            gate,
+            if self.enabled { self.timeout_secs } else { 0 },
+            if self.record_sessions { " --record" } else { "" }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LoginRequest {
+    pub id: String,
+    pub username: String,
+    pub source: String,    // Client address from SSH_CONNECTION
+    pub requested_at: u64, // Unix seconds
+    #[serde(default)]
+    pub job_id: Option<String>, // Filled in by the owner's side, which can read the job registry
+    #[serde(default)]
+    pub client_id: Option<String>,
+}
+
+impl LoginRequest {
+    pub fn new(username: &str, source: &str) -> Self {
+        let requested_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        Self {
+            id: format!("{}-{}-{}", username, requested_at, std::process::id()),
+            username: username.to_string(),
+            source: source.to_string(),
+            requested_at,
+            job_id: None,
+            client_id: None,
+        }
+    }
+}
+
+/// Login requests and answers as files in one directory
+///
+/// Anyone who can file a request can also create files, so an answer only counts when it is
+/// owned by root or by whoever owns the directory.
+pub struct LoginQueue {
+    dir: PathBuf,
+}
+
+impl LoginQueue {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Create the directory so job users can add requests but not remove anyone else's
+    pub fn prepare(&self) -> Result<(), String> {
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create {}: {}", self.dir.display(), e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&self.dir, std::fs::Permissions::from_mode(0o1777))
+                .map_err(|e| format!("Failed to set permissions on {}: {}", self.dir.display(), e))?;
+        }
+        Ok(())
+    }
+
+    fn request_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    fn answer_path(&self, id: &str, approve: bool) -> PathBuf {
+        self.dir.join(format!("{}.{}", id, if approve { "approved" } else { "denied" }))
+    }
+
+    pub fn submit(&self, request: &LoginRequest) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(request)
+            .map_err(|e| format!("Failed to serialize login request: {}", e))?;
+        let path = self.request_path(&request.id);
+        std::fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Requests nobody has answered yet, oldest first
+    pub fn pending(&self) -> Vec<LoginRequest> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return vec![];
+        };
+        let mut requests: Vec<LoginRequest> = entries
+            .flatten()
+            .filter(|entry| entry.path().extension().map(|e| e == "json").unwrap_or(false))
+            .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+            .filter_map(|content| serde_json::from_str::<LoginRequest>(&content).ok())
+            .filter(|request| self.answer(&request.id).is_none())
+            .collect();
+        requests.sort_by_key(|request| request.requested_at);
+        requests
+    }
+
+    pub fn decide(&self, id: &str, approve: bool) -> Result<(), String> {
+        let path = self.answer_path(id, approve);
+        // Replace any untrusted answer planted under the same name
+        let _ = std::fs::remove_file(&path);
+        std::fs::write(&path, "").map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// The trusted answer to a request, if there is one
+    pub fn answer(&self, id: &str) -> Option<bool> {
+        [false, true]
+            .into_iter()
+            .find(|approve| self.trusted(&self.answer_path(id, *approve)))
+    }
+
+    #[cfg(unix)]
+    fn trusted(&self, path: &Path) -> bool {
+        use std::os::unix::fs::MetadataExt;
+        let (Ok(answer), Ok(dir)) = (std::fs::metadata(path), std::fs::metadata(&self.dir)) else {
+            return false;
+        };
+        answer.uid() == 0 || answer.uid() == dir.uid()
+    }
+
+    #[cfg(not(unix))]
+    fn trusted(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    /// Block until the request is answered; an unanswered request counts as denied
+    pub fn wait(&self, id: &str, timeout: Duration) -> bool {
+        let started = Instant::now();
+        let approved = loop {
+            if let Some(approve) = self.answer(id) {
+                break approve;
+            }
+            if started.elapsed() >= timeout {
+                break false;
+            }
+            std::thread::sleep(Duration::from_millis(500));
+        };
+        let _ = std::fs::remove_file(self.request_path(id));
+        approved
+    }
+
+    /// Drop answers whose request is gone and requests older than `max_age`
+    pub fn prune(&self, max_age: Duration) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        let now = SystemTime::now();
+        for path in entries.flatten().map(|entry| entry.path()) {
+            let orphaned = path.extension().map(|e| e != "json").unwrap_or(false)
+                && path.file_stem().map(|id| !self.request_path(&id.to_string_lossy()).exists()).unwrap_or(false);
+            let stale = std::fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .map(|modified| now.duration_since(modified).unwrap_or_default() > max_age)
+                .unwrap_or(false);
+            if orphaned || stale {
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::admission::{AdmissionRule, Condition};
+
+    #[test]
+    fn test_login_queue_and_rules() {
+        let dir = std::env::temp_dir().join(format!("eryzaa-logins-{}", std::process::id()));
+        let queue = LoginQueue::new(&dir);
+        queue.prepare().unwrap();
+
+        let mut request = LoginRequest::new("job_1234abcd", "10.242.1.9");
+        queue.submit(&request).unwrap();
+        assert_eq!(queue.pending(), vec![request.clone()]);
+        queue.decide(&request.id, true).unwrap();
+        assert!(queue.pending().is_empty());
+        assert!(queue.wait(&request.id, Duration::from_secs(1)));
+        queue.prune(Duration::from_secs(3600));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut approval = LoginApproval { enabled: true, ..Default::default() };
+        approval.rules.rules.push(AdmissionRule {
+            name: "business hours".to_string(),
+            enabled: true,
+            conditions: vec![Condition::HoursBetween { start_hour: 9, end_hour: 17 }],
+            decision: AdmissionDecision::Accept,
+        });
+        request.client_id = Some("client-1".to_string());
+        assert_eq!(approval.auto_decision(&request, 10), Some(true));
+        assert_eq!(approval.auto_decision(&request, 22), None);
+        approval.enabled = false;
+        assert_eq!(approval.auto_decision(&request, 22), Some(true));
+
+        approval.record_sessions = true;
+        assert!(approval.gate_needed());
+        assert!(approval.sshd_snippet("/usr/bin/rental login-gate").ends_with("ForceCommand /usr/bin/rental login-gate 0 --record\n"));
+    }
+}
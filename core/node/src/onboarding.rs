@@ -0,0 +1,218 @@
+//! First-run onboarding of a rental node
+//! Gives the node a lasting identity, joins the overlay, measures the machine, suggests a price
+//! from the coordinator's market stats and registers the node, which returns a claim code for
+//! the owner to enter in the rental GUI. Each step is saved as it completes, so an interrupted
+//! run picks up where it stopped.
+
+use eryzaa_discovery::market::MarketStats;
+use eryzaa_discovery::registration::{NodeRegistration, RegistrationReceipt};
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::hardware::HardwareSnapshot;
+
+const CLAIM_LINK_PREFIX: &str = "eryzaa://claim/";
+
+/// How long the CPU benchmark runs
+pub const BENCHMARK_DURATION: Duration = Duration::from_secs(3);
+
+/// Result of the onboarding CPU benchmark
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Benchmark {
+    pub cpu_score: f64, // Million operations per second over all cores
+    pub single_core_score: f64,
+    pub cores: u32,
+    pub measured_at: u64, // Unix seconds
+}
+
+/// Progress of onboarding, saved after each step
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Onboarding {
+    #[serde(default)]
+    pub node_id: String,
+    #[serde(default)]
+    pub overlay_ip: Option<String>,
+    #[serde(default)]
+    pub benchmark: Option<Benchmark>,
+    #[serde(default)]
+    pub price_per_hour: Option<f32>, // AVAX, as the owner set it
+    #[serde(default)]
+    pub coordinator_url: Option<String>,
+    #[serde(default)]
+    pub registered_at: Option<u64>,
+    #[serde(default)]
+    pub claim_code: Option<String>, // Until the owner has claimed the node
+    #[serde(default)]
+    pub claim_expires_at: u64,
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub owner_secret: Option<String>, // Issued when this machine claimed the node, for owner-only requests
+}
+
+impl Onboarding {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize onboarding state: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write onboarding state {}: {}", path.display(), e))
+    }
+
+    /// Give the node an id unless it has one; true when a new one was made
+    pub fn ensure_identity(&mut self) -> bool {
+        if !self.node_id.is_empty() {
+            return false;
+        }
+        self.node_id = uuid::Uuid::new_v4().to_string();
+        true
+    }
+
+    /// Whether every step has been done
+    pub fn is_complete(&self) -> bool {
+        !self.node_id.is_empty() && self.benchmark.is_some() && self.price_per_hour.is_some() && self.registered_at.is_some()
+    }
+
+    /// What to tell the coordinator about this node, which signs its advertisements with
+    /// `public_key`
+    pub fn registration(&self, hostname: &str, hardware: &HardwareSnapshot, public_key: &str) -> NodeRegistration {
+        NodeRegistration {
+            node_id: self.node_id.clone(),
+            hostname: hostname.to_string(),
+            overlay_ip: self.overlay_ip.clone(),
+            price_per_hour: self.price_per_hour,
+            cpu_score: self.benchmark.as_ref().map(|benchmark| benchmark.cpu_score),
+            gpus: hardware.gpus.iter().map(|gpu| gpu.name.clone()).collect(),
+            public_key: Some(public_key.to_string()),
+        }
+    }
+
+    /// Note the coordinator's answer to a registration
+    pub fn registered(&mut self, coordinator_url: &str, receipt: RegistrationReceipt, now: u64) {
+        self.coordinator_url = Some(coordinator_url.to_string());
+        self.registered_at = Some(now);
+        self.claim_code = receipt.owner.is_none().then_some(receipt.claim_code);
+        self.claim_expires_at = receipt.expires_at;
+        // A secret only proves ownership to the owner it was issued to
+        if receipt.owner != self.owner {
+            self.owner_secret = None;
+        }
+        self.owner = receipt.owner;
+    }
+}
+
+/// Operations one core gets through in `duration`, in millions per second
+fn core_score(duration: Duration) -> f64 {
+    let started = Instant::now();
+    let (mut state, mut operations) = (0x9e37_79b9_7f4a_7c15u64, 0u64);
+    while started.elapsed() < duration {
+        // xorshift rounds, cheap enough that the clock check barely counts
+        for _ in 0..100_000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+        }
+        operations += 100_000;
+    }
+    std::hint::black_box(state);
+    operations as f64 / started.elapsed().as_secs_f64() / 1e6
+}
+
+/// Measure one core and then all `cores` at once, for `duration` each
+pub fn run_benchmark(cores: u32, duration: Duration) -> Benchmark {
+    let single_core_score = core_score(duration);
+    let threads: Vec<_> = (0..cores.max(1)).map(|_| std::thread::spawn(move || core_score(duration))).collect();
+    let cpu_score = threads.into_iter().filter_map(|thread| thread.join().ok()).sum();
+    Benchmark {
+        cpu_score,
+        single_core_score,
+        cores,
+        measured_at: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs(),
+    }
+}
+
+/// A starting price: what nodes with the same GPU ask, else the network's median
+pub fn suggest_price(stats: &MarketStats, hardware: &HardwareSnapshot) -> Option<f32> {
+    hardware
+        .gpus
+        .iter()
+        .find_map(|gpu| stats.median_price_by_model.get(&gpu.name).copied())
+        .or(stats.median_price_per_hour)
+}
+
+/// Link the rental GUI accepts in place of the bare claim code
+pub fn claim_link(claim_code: &str) -> String {
+    format!("{}{}", CLAIM_LINK_PREFIX, claim_code)
+}
+
+/// The claim code in `text`, which may be a claim link
+pub fn claim_code_from(text: &str) -> &str {
+    let text = text.trim();
+    text.strip_prefix(CLAIM_LINK_PREFIX).unwrap_or(text)
+}
+
+/// QR code of the claim link drawn with Unicode half blocks, for terminals
+pub fn claim_qr_text(claim_code: &str) -> Result<String, String> {
+    let code = QrCode::new(claim_link(claim_code).as_bytes())
+        .map_err(|e| format!("Failed to encode QR code: {}", e))?;
+    Ok(code.render::<Dense1x2>().quiet_zone(true).build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::GpuInfo;
+
+    #[test]
+    fn test_onboarding_steps() {
+        let path = std::env::temp_dir().join(format!("eryzaa-onboarding-{}.json", std::process::id()));
+        let mut onboarding = Onboarding::load_from(&path);
+        assert!(onboarding.ensure_identity());
+        let node_id = onboarding.node_id.clone();
+        assert!(!onboarding.ensure_identity());
+
+        let benchmark = run_benchmark(2, Duration::from_millis(20));
+        assert!(benchmark.cpu_score > 0.0 && benchmark.single_core_score > 0.0);
+        onboarding.benchmark = Some(benchmark);
+
+        let hardware = HardwareSnapshot {
+            cpu_cores: 2,
+            memory_gb: 16,
+            gpus: vec![GpuInfo { index: 0, name: "RTX 3090".to_string(), memory_mb: 24576, compute_capability: None }],
+            ..Default::default()
+        };
+        let mut stats = MarketStats { median_price_per_hour: Some(1.5), ..Default::default() };
+        assert_eq!(suggest_price(&stats, &hardware), Some(1.5));
+        stats.median_price_by_model.insert("RTX 3090".to_string(), 2.25);
+        onboarding.price_per_hour = suggest_price(&stats, &hardware);
+        assert_eq!(onboarding.price_per_hour, Some(2.25));
+        let registration = onboarding.registration("desk", &hardware, &"ab".repeat(32));
+        assert_eq!(registration.gpus, vec!["RTX 3090"]);
+        assert_eq!(registration.public_key, Some("ab".repeat(32)));
+
+        let receipt = RegistrationReceipt { claim_code: "K7QM-3XPD".to_string(), expires_at: 90_000, owner: None };
+        onboarding.registered("http://10.242.0.1:8090", receipt, 3600);
+        assert!(onboarding.is_complete());
+        assert_eq!(claim_code_from(&claim_link("K7QM-3XPD")), "K7QM-3XPD");
+        assert!(claim_qr_text("K7QM-3XPD").unwrap().contains('█'));
+
+        onboarding.save_to(&path).unwrap();
+        let loaded = Onboarding::load_from(&path);
+        assert_eq!((loaded.node_id.as_str(), loaded.claim_code.as_deref()), (node_id.as_str(), Some("K7QM-3XPD")));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
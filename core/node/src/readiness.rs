@@ -0,0 +1,113 @@
+//! Rental readiness checks
+//! A node can advertise itself as available while a job on it would fail. These checks look at
+//! each part of the stack a job needs, so clients can tell a working node from one whose GPU
+//! runtime is broken and filter on it.
+
+use eryzaa_discovery::overlay::NetworkMode;
+use eryzaa_discovery::{Check, Readiness};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Check the stack, running commands through `run` (program, args) -> stdout
+///
+/// `expects_gpu` is false on nodes without GPUs, which leaves the GPU check Unknown, as LAN
+/// direct mode leaves the overlay check.
+pub fn probe(
+    mode: NetworkMode,
+    expects_gpu: bool,
+    payments_configured: bool,
+    mut run: impl FnMut(&str, &[&str]) -> Result<String, String>,
+) -> Readiness {
+    let overlay = match mode.uses_overlay().then(|| run("zerotier-cli", &["info"])) {
+        None => Check::Unknown,
+        Some(Ok(info)) if info.contains("ONLINE") => Check::Ok,
+        Some(Ok(info)) => Check::Failed(format!("ZeroTier is {}", info.split_whitespace().last().unwrap_or("not online"))),
+        Some(Err(e)) => Check::Failed(format!("ZeroTier is not running: {}", e)),
+    };
+    let sshd = match run("pgrep", &["-x", "sshd"]) {
+        Ok(_) => Check::Ok,
+        Err(_) => Check::Failed("sshd is not running".to_string()),
+    };
+    let runtimes = run("docker", &["info", "--format", "{{json .Runtimes}}"]);
+    let docker = match &runtimes {
+        Ok(_) => Check::Ok,
+        Err(e) => Check::Failed(format!("Docker daemon unreachable: {}", e)),
+    };
+    let gpu = match (expects_gpu, &runtimes) {
+        (false, _) => Check::Unknown,
+        (true, runtimes) => match run("nvidia-smi", &["-L"]) {
+            Err(e) => Check::Failed(format!("nvidia-smi failed: {}", e)),
+            Ok(list) if !list.contains("GPU ") => Check::Failed("nvidia-smi lists no GPUs".to_string()),
+            Ok(_) => match runtimes {
+                Ok(runtimes) if runtimes.contains("nvidia") => Check::Ok,
+                Ok(_) => Check::Failed("NVIDIA container runtime is not registered with Docker".to_string()),
+                // Reported under docker already
+                Err(_) => Check::Unknown,
+            },
+        },
+    };
+    let payments = match payments_configured {
+        true => Check::Ok,
+        false => Check::Failed("No payout wallet set".to_string()),
+    };
+
+    Readiness {
+        overlay,
+        sshd,
+        docker,
+        gpu,
+        payments,
+        checked_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+        // Run once at startup, see `selftest`
+        self_test: Check::Unknown,
+    }
+}
+
+/// Run a command for `probe`: its output if it succeeded, otherwise why not
+pub fn run_command(program: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(program).args(args).output().map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match stderr.trim() {
+            "" => format!("exited with {}", output.status),
+            stderr => stderr.lines().next().unwrap_or(stderr).to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe() {
+        let run = |program: &str, _args: &[&str]| -> Result<String, String> {
+            match program {
+                "zerotier-cli" => Ok("200 info 8056c2e21c 1.12.2 ONLINE\n".to_string()),
+                "pgrep" => Ok("812\n".to_string()),
+                "docker" => Ok(r#"{"io.containerd.runc.v2":{"path":"runc"},"runc":{"path":"runc"}}"#.to_string()),
+                "nvidia-smi" => Ok("GPU 0: NVIDIA GeForce RTX 3090 (UUID: GPU-5d1c)\n".to_string()),
+                _ => Err("not found".to_string()),
+            }
+        };
+        let readiness = probe(NetworkMode::Overlay, true, true, run);
+        assert_eq!((&readiness.overlay, &readiness.sshd, &readiness.docker), (&Check::Ok, &Check::Ok, &Check::Ok));
+        assert_eq!(readiness.problems(), vec!["gpu: NVIDIA container runtime is not registered with Docker"]);
+        assert!(readiness.is_functional(false));
+        assert!(!readiness.is_functional(true));
+
+        let offline = |program: &str, _args: &[&str]| -> Result<String, String> {
+            match program {
+                "zerotier-cli" => Ok("200 info 8056c2e21c 1.12.2 OFFLINE\n".to_string()),
+                _ => Err("No such file or directory (os error 2)".to_string()),
+            }
+        };
+        let readiness = probe(NetworkMode::Overlay, false, false, offline);
+        assert_eq!(readiness.overlay, Check::Failed("ZeroTier is OFFLINE".to_string()));
+        assert_eq!(readiness.gpu, Check::Unknown);
+        assert_eq!(readiness.problems().len(), 4);
+        // A LAN direct node has no overlay to be offline
+        assert_eq!(probe(NetworkMode::LanDirect, false, false, offline).overlay, Check::Unknown);
+    }
+}
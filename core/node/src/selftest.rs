@@ -0,0 +1,144 @@
+//! Startup self-test
+//! Readiness checks ask whether each part of the stack is up; the self-test has the stack do
+//! what a rental needs, once, before the node first advertises itself Available: create and
+//! delete a throwaway job user the way jobs get theirs, run a hello-world container, bind the
+//! control port and reach our own overlay address. Until it passes the node advertises
+//! Maintenance with the steps that failed in its readiness, so clients never rent a node that
+//! cannot serve them.
+
+use eryzaa_discovery::overlay::NetworkMode;
+use eryzaa_discovery::Check;
+use eryzaa_ssh_manager::SshManager;
+use std::net::TcpListener;
+
+/// Image the container step runs
+pub const TEST_IMAGE: &str = "hello-world";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfTest {
+    pub steps: Vec<(&'static str, Check)>,
+}
+
+impl SelfTest {
+    pub fn passed(&self) -> bool {
+        self.steps.iter().all(|(_, check)| !check.is_failed())
+    }
+
+    /// Failed steps as "name: reason"
+    pub fn failures(&self) -> Vec<String> {
+        self.steps
+            .iter()
+            .filter_map(|(name, check)| match check {
+                Check::Failed(reason) => Some(format!("{}: {}", name, reason)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The outcome as readiness carries it, every failed step in one report
+    pub fn check(&self) -> Check {
+        match self.failures() {
+            failures if failures.is_empty() => Check::Ok,
+            failures => Check::Failed(failures.join("; ")),
+        }
+    }
+}
+
+/// The user step through `users`, which creates and deletes job users via the privileged
+/// service on Unix and directly on Windows
+pub fn try_users(users: &SshManager) -> Check {
+    let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+        Ok(runtime) => runtime,
+        Err(e) => return Check::Failed(format!("Cannot start the async runtime: {}", e)),
+    };
+    match runtime.block_on(users.try_job_user()) {
+        Ok(_) => Check::Ok,
+        Err(e) => Check::Failed(e.to_string()),
+    }
+}
+
+/// Run the self-test, the user step through `user`, e.g. `try_users`, and other commands
+/// through `command` (program, args) -> stdout as `readiness::probe` does
+///
+/// `overlay_ip` is the address the node advertises on the overlay; LAN direct mode has none to
+/// reach and leaves the overlay step Unknown.
+pub fn run(
+    mode: NetworkMode,
+    control_port: u16,
+    overlay_ip: Option<&str>,
+    user: impl FnOnce() -> Check,
+    mut command: impl FnMut(&str, &[&str]) -> Result<String, String>,
+) -> SelfTest {
+    let user = user();
+    let container = match command("docker", &["run", "--rm", TEST_IMAGE]) {
+        Ok(output) if output.contains("Hello from Docker!") => Check::Ok,
+        Ok(_) => Check::Failed(format!("{} ran but did not greet", TEST_IMAGE)),
+        Err(e) => Check::Failed(format!("{} did not run: {}", TEST_IMAGE, e)),
+    };
+    // Dropped at once; the port only has to be ours to take
+    let port = match TcpListener::bind(("0.0.0.0", control_port)) {
+        Ok(_) => Check::Ok,
+        Err(e) => Check::Failed(format!("Cannot bind port {}: {}", control_port, e)),
+    };
+    let overlay = match (mode.uses_overlay(), overlay_ip) {
+        (false, _) => Check::Unknown,
+        (true, None) => Check::Failed("No overlay address assigned".to_string()),
+        (true, Some(ip)) => match command("ping", &ping_once(ip)) {
+            Ok(_) => Check::Ok,
+            Err(e) => Check::Failed(format!("{} is unreachable from this machine: {}", ip, e)),
+        },
+    };
+
+    SelfTest { steps: vec![("user", user), ("container", container), ("control port", port), ("overlay", overlay)] }
+}
+
+/// Arguments for one ping of `ip` that gives up after two seconds
+fn ping_once(ip: &str) -> [&str; 5] {
+    match cfg!(windows) {
+        true => ["-n", "1", "-w", "2000", ip],
+        false => ["-c", "1", "-W", "2", ip],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_test() {
+        let working = |program: &str, _args: &[&str]| -> Result<String, String> {
+            match program {
+                "docker" => Ok("\nHello from Docker!\nThis message shows that your installation appears to be working correctly.\n".to_string()),
+                "ping" => Ok(String::new()),
+                _ => Err("not found".to_string()),
+            }
+        };
+        let taken = TcpListener::bind(("0.0.0.0", 0)).unwrap();
+        let port = taken.local_addr().unwrap().port();
+
+        let report = run(NetworkMode::Overlay, port, Some("10.242.0.7"), || Check::Ok, working);
+        assert!(!report.passed());
+        assert_eq!(report.failures().len(), 1);
+        assert!(report.failures()[0].starts_with(&format!("control port: Cannot bind port {}", port)));
+        drop(taken);
+        assert_eq!(run(NetworkMode::Overlay, port, Some("10.242.0.7"), || Check::Ok, working).check(), Check::Ok);
+
+        let broken = |program: &str, _args: &[&str]| -> Result<String, String> {
+            match program {
+                "docker" => Err("Cannot connect to the Docker daemon".to_string()),
+                _ => Err("permission denied".to_string()),
+            }
+        };
+        let undeletable = || Check::Failed("Created job_a1b2c3d4 but cannot delete it: permission denied".to_string());
+        let report = run(NetworkMode::Overlay, port, None, undeletable, broken);
+        assert_eq!(
+            report.check(),
+            Check::Failed(format!(
+                "user: Created job_a1b2c3d4 but cannot delete it: permission denied; container: {} did not run: Cannot connect to the Docker daemon; overlay: No overlay address assigned",
+                TEST_IMAGE
+            ))
+        );
+        // A LAN direct node has no overlay address to reach
+        assert_eq!(run(NetworkMode::LanDirect, port, None, || Check::Ok, working).steps[3].1, Check::Unknown);
+    }
+}
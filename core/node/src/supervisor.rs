@@ -0,0 +1,375 @@
+//! Auxiliary service supervisor
+//! The rental stack leans on zerotier-one, sshd, Docker, the caching proxy and TensorBoard. Each
+//! is declared once with a health check, a restart policy and the services it needs running first.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{SocketAddr, TcpStream};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use log::{info, warn};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum HealthCheck {
+    Process { name: String },      // A process with exactly this name is running
+    Port { port: u16 },            // Something accepts TCP connections on localhost
+    Command { argv: Vec<String> }, // The command exits successfully
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RestartPolicy {
+    Never,
+    Always,
+    Limited { max_restarts: u32, window_secs: u64 }, // Give up after this many restarts in the window
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Service {
+    pub name: String,
+    pub units: Vec<String>, // systemd units to try in order; distributions name them differently
+    pub start_command: Vec<String>, // Used where systemd is not available, e.g. inside a container
+    pub health: HealthCheck,
+    pub restart: RestartPolicy,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+impl Service {
+    pub fn is_healthy(&self) -> bool {
+        match &self.health {
+            HealthCheck::Process { name } => process_running(name),
+            HealthCheck::Port { port } => {
+                let addr = SocketAddr::from(([127, 0, 0, 1], *port));
+                TcpStream::connect_timeout(&addr, Duration::from_secs(1)).is_ok()
+            }
+            HealthCheck::Command { argv } => match argv.split_first() {
+                Some((program, args)) => Command::new(program)
+                    .args(args)
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()
+                    .map(|status| status.success())
+                    .unwrap_or(false),
+                None => false,
+            },
+        }
+    }
+
+    /// Start the service, or restart it if it is running but unhealthy
+    pub fn restart(&self) -> Result<(), String> {
+        if self.units.iter().any(|unit| systemctl(&["restart", unit])) {
+            return Ok(());
+        }
+        self.spawn_start_command()
+    }
+
+    /// Enable the service at boot and start it now
+    pub fn enable(&self) -> Result<(), String> {
+        if self.units.iter().any(|unit| systemctl(&["enable", "--now", unit])) {
+            return Ok(());
+        }
+        self.spawn_start_command()
+    }
+
+    fn spawn_start_command(&self) -> Result<(), String> {
+        let (program, args) = self
+            .start_command
+            .split_first()
+            .ok_or_else(|| format!("No way to start {}: no systemd unit answered and no start command is set", self.name))?;
+        Command::new(program)
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map(|_| ())
+            .map_err(|e| format!("Failed to start {}: {}", self.name, e))
+    }
+}
+
+/// The services this node supervises
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServiceSet {
+    pub services: Vec<Service>,
+}
+
+impl Default for ServiceSet {
+    fn default() -> Self {
+        let service = |name: &str, units: &[&str], start: &[&str], health: HealthCheck, restart: RestartPolicy, depends_on: &[&str]| Service {
+            name: name.to_string(),
+            units: units.iter().map(|u| u.to_string()).collect(),
+            start_command: start.iter().map(|s| s.to_string()).collect(),
+            health,
+            restart,
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            enabled: true,
+        };
+        let limited = RestartPolicy::Limited { max_restarts: 3, window_secs: 600 };
+
+        let mut proxy = service(
+            "cache-proxy",
+            &["squid"],
+            &["squid"],
+            HealthCheck::Port { port: 3128 },
+            limited.clone(),
+            &["zerotier-one"],
+        );
+        proxy.enabled = false;
+        let mut tensorboard = service(
+            "tensorboard",
+            &[],
+            &["tensorboard", "--logdir", "/workspace/logs", "--bind_all", "--port", "6006"],
+            HealthCheck::Port { port: 6006 },
+            limited.clone(),
+            &["zerotier-one"],
+        );
+        tensorboard.enabled = false;
+
+        Self {
+            services: vec![
+                service(
+                    "zerotier-one",
+                    &["zerotier-one"],
+                    &["zerotier-one", "-d"],
+                    HealthCheck::Process { name: "zerotier-one".to_string() },
+                    RestartPolicy::Always,
+                    &[],
+                ),
+                // SSH only listens on the overlay, so it needs ZeroTier up first
+                service(
+                    "sshd",
+                    &["ssh", "sshd"],
+                    &["/usr/sbin/sshd"],
+                    HealthCheck::Process { name: "sshd".to_string() },
+                    RestartPolicy::Always,
+                    &["zerotier-one"],
+                ),
+                service(
+                    "docker",
+                    &["docker"],
+                    &["dockerd"],
+                    HealthCheck::Command { argv: vec!["docker".to_string(), "info".to_string()] },
+                    limited,
+                    &[],
+                ),
+                proxy,
+                tensorboard,
+            ],
+        }
+    }
+}
+
+impl ServiceSet {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize services: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write services {}: {}", path.display(), e))
+    }
+
+    pub fn service(&self, name: &str) -> Option<&Service> {
+        self.services.iter().find(|service| service.name == name)
+    }
+
+    /// Enabled services with every dependency before its dependents
+    pub fn start_order(&self) -> Result<Vec<&Service>, String> {
+        let enabled: Vec<&Service> = self.services.iter().filter(|s| s.enabled).collect();
+        for service in &enabled {
+            if let Some(missing) = service.depends_on.iter().find(|d| !enabled.iter().any(|s| &&s.name == d)) {
+                return Err(format!("{} depends on {}, which is not an enabled service", service.name, missing));
+            }
+        }
+
+        let mut ordered: Vec<&Service> = Vec::new();
+        while ordered.len() < enabled.len() {
+            let ready = enabled.iter().find(|service| {
+                !ordered.iter().any(|o| o.name == service.name)
+                    && service.depends_on.iter().all(|d| ordered.iter().any(|o| &o.name == d))
+            });
+            match ready {
+                Some(service) => ordered.push(service),
+                None => {
+                    let stuck: Vec<&str> = enabled
+                        .iter()
+                        .filter(|s| !ordered.iter().any(|o| o.name == s.name))
+                        .map(|s| s.name.as_str())
+                        .collect();
+                    return Err(format!("Service dependencies form a cycle: {}", stuck.join(", ")));
+                }
+            }
+        }
+        Ok(ordered)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServiceStatus {
+    Running,
+    Restarted,
+    Failed(String),  // The restart itself failed
+    Blocked(String), // Down, waiting for a dependency
+    GaveUp,          // Down, and the restart policy allows no more restarts
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceReport {
+    pub name: String,
+    pub status: ServiceStatus,
+}
+
+/// Keeps the enabled services running, in dependency order
+pub struct Supervisor {
+    pub services: ServiceSet,
+    restarts: HashMap<String, Vec<Instant>>, // Recent restarts per service, for Limited policies
+}
+
+impl Supervisor {
+    pub fn new(services: ServiceSet) -> Self {
+        Self { services, restarts: HashMap::new() }
+    }
+
+    /// Health of each enabled service, without restarting anything
+    pub fn status(&self) -> Vec<(String, bool)> {
+        self.services
+            .services
+            .iter()
+            .filter(|s| s.enabled)
+            .map(|s| (s.name.clone(), s.is_healthy()))
+            .collect()
+    }
+
+    /// Check every service and restart the ones that are down and allowed to be
+    pub fn tick(&mut self) -> Result<Vec<ServiceReport>, String> {
+        let order: Vec<Service> = self.services.start_order()?.into_iter().cloned().collect();
+        let mut reports: Vec<ServiceReport> = Vec::new();
+
+        for service in order {
+            let status = if service.is_healthy() {
+                ServiceStatus::Running
+            } else if let Some(down) = service.depends_on.iter().find(|d| {
+                reports.iter().any(|r| &r.name == *d && !matches!(r.status, ServiceStatus::Running | ServiceStatus::Restarted))
+            }) {
+                ServiceStatus::Blocked(format!("waiting for {}", down))
+            } else if !self.may_restart(&service, Instant::now()) {
+                ServiceStatus::GaveUp
+            } else {
+                warn!("{} is down, restarting", service.name);
+                match service.restart() {
+                    Ok(()) => {
+                        info!("Restarted {}", service.name);
+                        ServiceStatus::Restarted
+                    }
+                    Err(e) => ServiceStatus::Failed(e),
+                }
+            };
+            reports.push(ServiceReport { name: service.name.clone(), status });
+        }
+        Ok(reports)
+    }
+
+    /// Record a restart at `now` if the service's policy still allows one
+    fn may_restart(&mut self, service: &Service, now: Instant) -> bool {
+        let history = self.restarts.entry(service.name.clone()).or_default();
+        let allowed = match &service.restart {
+            RestartPolicy::Never => false,
+            RestartPolicy::Always => true,
+            RestartPolicy::Limited { max_restarts, window_secs } => {
+                let window = Duration::from_secs(*window_secs);
+                history.retain(|at| now.duration_since(*at) < window);
+                (history.len() as u32) < *max_restarts
+            }
+        };
+        if allowed {
+            history.push(now);
+        }
+        allowed
+    }
+}
+
+fn process_running(name: &str) -> bool {
+    #[cfg(windows)]
+    {
+        Command::new("tasklist")
+            .args(["/FI", &format!("IMAGENAME eq {}.exe", name), "/NH"])
+            .output()
+            .map(|o| String::from_utf8_lossy(&o.stdout).contains(name))
+            .unwrap_or(false)
+    }
+    #[cfg(not(windows))]
+    {
+        Command::new("pgrep")
+            .args(["-x", name])
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    }
+}
+
+/// Run systemctl, through sudo unless already root
+fn systemctl(args: &[&str]) -> bool {
+    let is_root = Command::new("id")
+        .arg("-u")
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim() == "0")
+        .unwrap_or(false);
+    let mut command = if is_root {
+        Command::new("systemctl")
+    } else {
+        let mut sudo = Command::new("sudo");
+        sudo.arg("systemctl");
+        sudo
+    };
+    command
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start_order_and_restart_limits() {
+        let mut set = ServiceSet::default();
+        let names: Vec<&str> = set.start_order().unwrap().iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["zerotier-one", "sshd", "docker"]);
+
+        set.services[0].depends_on.push("sshd".to_string());
+        assert!(set.start_order().unwrap_err().contains("cycle"));
+        set.services[0].depends_on.clear();
+        set.services[3].enabled = true;
+        set.services[3].depends_on.push("tensorboard".to_string());
+        assert!(set.start_order().unwrap_err().contains("not an enabled service"));
+
+        let mut supervisor = Supervisor::new(ServiceSet::default());
+        let docker = supervisor.services.service("docker").unwrap().clone();
+        let start = Instant::now();
+        assert!((0..3).all(|_| supervisor.may_restart(&docker, start)));
+        assert!(!supervisor.may_restart(&docker, start));
+        assert!(supervisor.may_restart(&docker, start + Duration::from_secs(601)));
+    }
+}
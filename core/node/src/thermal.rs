@@ -0,0 +1,312 @@
+//! Thermal and power-aware job starts
+//! GPU temperature and power readings decide whether a new GPU job may start now, starts
+//! are staggered so several GPUs do not spin up at once, and quiet hours cap GPU power.
+
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use log::{info, warn};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpuReading {
+    pub index: u32,
+    pub temperature_c: f32,
+    pub power_draw_w: f32,
+    pub power_limit_w: f32,
+    pub default_power_limit_w: f32,
+    pub ecc_errors: u64, // Uncorrected ECC errors since the driver loaded
+}
+
+/// Hours during which GPU power is capped, e.g. 22:00-07:00
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuietHours {
+    pub start_hour: u32,
+    pub end_hour: u32,
+    pub power_cap_watts: u32,
+}
+
+impl QuietHours {
+    /// Whether `hour` (0-23) falls inside the quiet window; windows may wrap midnight
+    pub fn contains(&self, hour: u32) -> bool {
+        hour_in_window(self.start_hour, self.end_hour, hour)
+    }
+
+    /// Parse "START-END:WATTS", e.g. "22-7:150"
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid quiet hours '{}', expected START-END:WATTS", spec);
+        let (hours, watts) = spec.split_once(':').ok_or_else(invalid)?;
+        let (start, end) = hours.split_once('-').ok_or_else(invalid)?;
+
+        let quiet = Self {
+            start_hour: start.trim().parse().map_err(|_| invalid())?,
+            end_hour: end.trim().parse().map_err(|_| invalid())?,
+            power_cap_watts: watts.trim().parse().map_err(|_| invalid())?,
+        };
+        if quiet.start_hour > 23 || quiet.end_hour > 23 || quiet.power_cap_watts == 0 {
+            return Err(invalid());
+        }
+        Ok(quiet)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThermalPolicy {
+    pub cooldown_temp_c: f32, // GPUs at or above this are too hot for a new job
+    pub stagger_secs: u64,    // Gap after a GPU job start, per GPU it used
+    pub quiet_hours: Option<QuietHours>,
+}
+
+impl Default for ThermalPolicy {
+    fn default() -> Self {
+        Self {
+            cooldown_temp_c: 83.0,
+            stagger_secs: 30,
+            quiet_hours: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StartDecision {
+    Start,
+    Defer(String),
+}
+
+/// Gatekeeper for starting GPU jobs on this node
+pub struct ThermalScheduler {
+    pub policy: ThermalPolicy,
+    last_start: Mutex<Option<(Instant, u32)>>, // When the last GPU job started and how many GPUs it took
+}
+
+impl ThermalScheduler {
+    pub fn new(policy: ThermalPolicy) -> Self {
+        Self {
+            policy,
+            last_start: Mutex::new(None),
+        }
+    }
+
+    /// Decide whether a job needing `gpus` GPUs may start now
+    pub fn decide(&self, gpus: u32, readings: &[GpuReading], now: Instant) -> StartDecision {
+        if gpus == 0 {
+            return StartDecision::Start;
+        }
+
+        if let Some((started, last_gpus)) = *self.last_start.lock().unwrap() {
+            let gap = Duration::from_secs(self.policy.stagger_secs * last_gpus.max(1) as u64);
+            let elapsed = now.saturating_duration_since(started);
+            if elapsed < gap {
+                return StartDecision::Defer(format!(
+                    "staggering GPU job starts, {}s left",
+                    (gap - elapsed).as_secs()
+                ));
+            }
+        }
+
+        let faulty = readings.iter().filter(|r| r.ecc_errors > 0).count();
+        let cool = readings
+            .iter()
+            .filter(|r| r.temperature_c < self.policy.cooldown_temp_c && r.ecc_errors == 0)
+            .count() as u32;
+        if cool < gpus && faulty > 0 {
+            return StartDecision::Defer(format!(
+                "{} of {} GPUs usable, {} reporting ECC errors",
+                cool, gpus, faulty
+            ));
+        }
+        if cool < gpus {
+            let hottest = readings.iter().map(|r| r.temperature_c).fold(0.0, f32::max);
+            return StartDecision::Defer(format!(
+                "{} of {} GPUs below {:.0}°C (hottest {:.0}°C)",
+                cool, gpus, self.policy.cooldown_temp_c, hottest
+            ));
+        }
+
+        StartDecision::Start
+    }
+
+    /// Record that a GPU job just started
+    pub fn mark_started(&self, gpus: u32, now: Instant) {
+        if gpus > 0 {
+            *self.last_start.lock().unwrap() = Some((now, gpus));
+        }
+    }
+
+    /// Power limit each GPU should have at `hour`; None means the default limit
+    pub fn power_cap(&self, hour: u32) -> Option<u32> {
+        self.policy
+            .quiet_hours
+            .as_ref()
+            .filter(|quiet| quiet.contains(hour))
+            .map(|quiet| quiet.power_cap_watts)
+    }
+
+    /// Limit a GPU should be set to at `hour`, or None when its current one is right
+    ///
+    /// GPUs that report no default limit ("[N/A]", read as 0 W) are left alone rather than
+    /// capped to nothing.
+    pub fn power_target(&self, hour: u32, reading: &GpuReading) -> Option<f32> {
+        if reading.default_power_limit_w <= 0.0 {
+            return None;
+        }
+        let target = match self.power_cap(hour) {
+            Some(cap) => (cap as f32).min(reading.default_power_limit_w),
+            None => reading.default_power_limit_w,
+        };
+        ((reading.power_limit_w - target).abs() >= 1.0).then_some(target)
+    }
+
+    /// Apply or lift the quiet hours power cap on GPUs whose limit is off
+    pub fn enforce_power_cap(&self, hour: u32, readings: &[GpuReading]) -> Result<(), String> {
+        for reading in readings {
+            let Some(target) = self.power_target(hour, reading) else {
+                continue;
+            };
+
+            let output = Command::new("nvidia-smi")
+                .args(["-i", &reading.index.to_string(), "-pl", &format!("{:.0}", target)])
+                .output()
+                .map_err(|e| format!("Failed to execute nvidia-smi: {}", e))?;
+            if !output.status.success() {
+                warn!("Failed to set power limit on GPU {}: {}", reading.index, String::from_utf8_lossy(&output.stderr).trim());
+                continue;
+            }
+            info!("GPU {} power limit set to {:.0} W", reading.index, target);
+        }
+        Ok(())
+    }
+}
+
+impl Default for ThermalScheduler {
+    fn default() -> Self {
+        Self::new(ThermalPolicy::default())
+    }
+}
+
+/// Whether `hour` falls in [start, end), wrapping past midnight when start > end
+pub fn hour_in_window(start_hour: u32, end_hour: u32, hour: u32) -> bool {
+    if start_hour <= end_hour {
+        hour >= start_hour && hour < end_hour
+    } else {
+        hour >= start_hour || hour < end_hour
+    }
+}
+
+/// Current temperature and power of every NVIDIA GPU
+pub fn read_gpus() -> Result<Vec<GpuReading>, String> {
+    let output = Command::new("nvidia-smi")
+        .args([
+            "--query-gpu=index,temperature.gpu,power.draw,power.limit,power.default_limit,ecc.errors.uncorrected.volatile.total",
+            "--format=csv,noheader,nounits",
+        ])
+        .output()
+        .map_err(|e| format!("Failed to execute nvidia-smi: {}", e))?;
+    if !output.status.success() {
+        return Err("nvidia-smi could not query the GPUs".to_string());
+    }
+
+    let readings = parse_gpu_readings(&String::from_utf8_lossy(&output.stdout));
+
+    #[cfg(feature = "chaos")]
+    let readings: Vec<GpuReading> = readings
+        .into_iter()
+        .map(|mut reading| {
+            reading.ecc_errors += eryzaa_discovery::chaos::ecc_errors(reading.index);
+            reading
+        })
+        .collect();
+
+    Ok(readings)
+}
+
+/// Parse `nvidia-smi --format=csv,noheader,nounits` rows
+pub fn parse_gpu_readings(output: &str) -> Vec<GpuReading> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            if fields.len() < 5 {
+                return None;
+            }
+            // Unsupported fields are reported as "[N/A]"
+            let number = |i: usize| fields[i].parse::<f32>().unwrap_or(0.0);
+            Some(GpuReading {
+                index: fields[0].parse().ok()?,
+                temperature_c: number(1),
+                power_draw_w: number(2),
+                power_limit_w: number(3),
+                default_power_limit_w: number(4),
+                ecc_errors: fields.get(5).and_then(|f| f.parse().ok()).unwrap_or(0),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(index: u32, temperature_c: f32) -> GpuReading {
+        GpuReading {
+            index,
+            temperature_c,
+            power_draw_w: 200.0,
+            power_limit_w: 350.0,
+            default_power_limit_w: 350.0,
+            ecc_errors: 0,
+        }
+    }
+
+    #[test]
+    fn test_defers_hot_gpus_and_staggers() {
+        let scheduler = ThermalScheduler::default();
+        let now = Instant::now();
+
+        let readings = vec![reading(0, 86.0), reading(1, 60.0)];
+        assert_eq!(scheduler.decide(1, &readings, now), StartDecision::Start);
+        assert!(matches!(scheduler.decide(2, &readings, now), StartDecision::Defer(_)));
+
+        scheduler.mark_started(2, now);
+        assert!(matches!(scheduler.decide(1, &readings, now + Duration::from_secs(59)), StartDecision::Defer(_)));
+        assert_eq!(scheduler.decide(1, &readings, now + Duration::from_secs(60)), StartDecision::Start);
+        assert_eq!(scheduler.decide(0, &readings, now), StartDecision::Start);
+
+        let failing = vec![reading(0, 50.0), GpuReading { ecc_errors: 2, ..reading(1, 50.0) }];
+        let fresh = ThermalScheduler::default();
+        assert_eq!(fresh.decide(1, &failing, now), StartDecision::Start);
+        assert!(matches!(fresh.decide(2, &failing, now), StartDecision::Defer(reason) if reason.contains("ECC")));
+    }
+
+    #[test]
+    fn test_quiet_hours_wrap_midnight() {
+        let quiet = QuietHours::parse("22-7:150").unwrap();
+        assert!(quiet.contains(23) && quiet.contains(3));
+        assert!(!quiet.contains(7) && !quiet.contains(12));
+        assert!(QuietHours::parse("25-7:150").is_err());
+
+        let scheduler = ThermalScheduler::new(ThermalPolicy { quiet_hours: Some(quiet), ..Default::default() });
+        assert_eq!(scheduler.power_cap(2), Some(150));
+        assert_eq!(scheduler.power_cap(12), None);
+    }
+
+    #[test]
+    fn test_power_target_skips_unreadable_default() {
+        let quiet = QuietHours::parse("22-7:150").unwrap();
+        let scheduler = ThermalScheduler::new(ThermalPolicy { quiet_hours: Some(quiet), ..Default::default() });
+        let readings = parse_gpu_readings("0, 65, 120.50, 350.00, 350.00, 0\n1, 40, 90.00, [N/A], [N/A], 0\n");
+
+        assert_eq!(scheduler.power_target(2, &readings[0]), Some(150.0));
+        assert_eq!(scheduler.power_target(12, &readings[0]), None);
+        assert_eq!(scheduler.power_target(2, &readings[1]), None);
+        assert_eq!(scheduler.power_target(12, &readings[1]), None);
+    }
+
+    #[test]
+    fn test_parse_gpu_readings() {
+        let readings = parse_gpu_readings("0, 65, 120.50, 350.00, 350.00, 0\n1, 40, [N/A], 250.00, 300.00, [N/A]\n");
+        assert_eq!(readings.len(), 2);
+        assert_eq!(readings[0].temperature_c, 65.0);
+        assert_eq!(readings[1].power_draw_w, 0.0);
+    }
+}
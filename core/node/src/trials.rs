@@ -0,0 +1,83 @@
+//! Free trials given
+//! A node advertising a free trial gives it to each client identity once. The ledger remembers
+//! who took one and with which job, so a client cannot take a second by submitting again.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use eryzaa_discovery::TrialOffer;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TrialRecord {
+    pub client_id: String,
+    pub job_id: String,
+    pub granted_at: u64, // Unix seconds
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrialLedger {
+    pub trials: Vec<TrialRecord>,
+}
+
+impl TrialLedger {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize trial ledger: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write trial ledger {}: {}", path.display(), e))
+    }
+
+    /// The trial `client_id` already took, if any
+    pub fn taken_by(&self, client_id: &str) -> Option<&TrialRecord> {
+        self.trials.iter().find(|trial| trial.client_id == client_id)
+    }
+
+    /// Whether `client_id` may take the node's trial with a job needing `gpus`; the terms it
+    /// would run on
+    pub fn check(&self, offer: Option<TrialOffer>, client_id: &str, gpus: u32) -> Result<TrialOffer, String> {
+        let offer = offer.ok_or_else(|| "Node does not offer free trials".to_string())?;
+        if gpus > 0 {
+            return Err("Free trials run without GPUs".to_string());
+        }
+        if let Some(taken) = self.taken_by(client_id) {
+            return Err(format!("Client {} already took its free trial on this node (job {})", client_id, taken.job_id));
+        }
+        Ok(offer)
+    }
+
+    /// Record that `client_id` took its trial with `job_id`
+    pub fn grant(&mut self, client_id: &str, job_id: &str, now: u64) {
+        self.trials.push(TrialRecord { client_id: client_id.to_string(), job_id: job_id.to_string(), granted_at: now });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_trial_per_client() {
+        let offer = TrialOffer { minutes: 10, cpu_percent: 50 };
+        let mut ledger = TrialLedger::default();
+
+        assert!(ledger.check(None, "client-1", 0).unwrap_err().contains("does not offer"));
+        assert!(ledger.check(Some(offer), "client-1", 1).unwrap_err().contains("without GPUs"));
+        assert_eq!(ledger.check(Some(offer), "client-1", 0), Ok(offer));
+        ledger.grant("client-1", "job-1", 100);
+        assert!(ledger.check(Some(offer), "client-1", 0).unwrap_err().contains("job-1"));
+        assert!(ledger.check(Some(offer), "client-2", 0).is_ok());
+        assert_eq!(ledger.taken_by("client-1").map(|trial| trial.granted_at), Some(100));
+    }
+}
@@ -0,0 +1,262 @@
+//! Per-job resource usage
+//! CPU, memory and IO come from each container's cgroup, network from its namespace's
+//! interfaces and GPU use from the NVIDIA compute processes that belong to the cgroup.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Instant;
+
+/// Resource use of one job container; rates are per second since the previous sample
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JobUsage {
+    pub job_id: String,
+    pub cpu_percent: f32, // 100% is one core
+    pub rss_bytes: u64,
+    pub gpu_util_percent: f32, // Summed over the GPUs the job uses
+    pub vram_mb: u64,
+    pub io_read_bps: u64,
+    pub io_write_bps: u64,
+    pub net_rx_bps: u64,
+    pub net_tx_bps: u64,
+}
+
+/// Cumulative counters of a container at one instant
+#[derive(Debug, Clone, Copy)]
+struct Totals {
+    cpu_usec: u64,
+    io_read: u64,
+    io_write: u64,
+    net_rx: u64,
+    net_tx: u64,
+    at: Instant,
+}
+
+/// Samples job containers, keeping the previous counters to turn them into rates
+#[derive(Debug, Default)]
+pub struct UsageSampler {
+    previous: HashMap<String, Totals>,
+}
+
+impl UsageSampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Usage of each `(job_id, container_name)`; containers that are not running are left out
+    pub fn sample(&mut self, containers: &[(String, String)]) -> Vec<JobUsage> {
+        let now = Instant::now();
+        let gpu_processes = read_gpu_processes();
+        let mut usages = Vec::new();
+        let mut current = HashMap::new();
+
+        for (job_id, container_name) in containers {
+            let Some(pid) = container_pid(container_name) else {
+                continue;
+            };
+            let Some(cgroup) = std::fs::read_to_string(format!("/proc/{}/cgroup", pid))
+                .ok()
+                .and_then(|content| parse_cgroup_path(&content))
+            else {
+                continue;
+            };
+            let dir = PathBuf::from("/sys/fs/cgroup").join(cgroup.trim_start_matches('/'));
+            let read = |file: &str| std::fs::read_to_string(dir.join(file)).unwrap_or_default();
+
+            let (io_read, io_write) = parse_io_stat(&read("io.stat"));
+            let (net_rx, net_tx) = std::fs::read_to_string(format!("/proc/{}/net/dev", pid))
+                .map(|content| parse_net_dev(&content))
+                .unwrap_or_default();
+            let totals = Totals {
+                cpu_usec: stat_value(&read("cpu.stat"), "usage_usec").unwrap_or(0),
+                io_read,
+                io_write,
+                net_rx,
+                net_tx,
+                at: now,
+            };
+
+            let mut usage = JobUsage {
+                job_id: job_id.clone(),
+                rss_bytes: stat_value(&read("memory.stat"), "anon").unwrap_or(0),
+                ..Default::default()
+            };
+            if let Some(prev) = self.previous.get(job_id) {
+                let secs = totals.at.duration_since(prev.at).as_secs_f64();
+                if secs > 0.0 {
+                    let rate = |now: u64, before: u64| (now.saturating_sub(before) as f64 / secs) as u64;
+                    usage.cpu_percent = (totals.cpu_usec.saturating_sub(prev.cpu_usec) as f64 / secs / 10_000.0) as f32;
+                    usage.io_read_bps = rate(totals.io_read, prev.io_read);
+                    usage.io_write_bps = rate(totals.io_write, prev.io_write);
+                    usage.net_rx_bps = rate(totals.net_rx, prev.net_rx);
+                    usage.net_tx_bps = rate(totals.net_tx, prev.net_tx);
+                }
+            }
+
+            for process in &gpu_processes {
+                if process.cgroup.starts_with(&cgroup) {
+                    usage.gpu_util_percent += process.util_percent;
+                    usage.vram_mb += process.used_memory_mb;
+                }
+            }
+
+            current.insert(job_id.clone(), totals);
+            usages.push(usage);
+        }
+
+        self.previous = current;
+        usages
+    }
+}
+
+/// An NVIDIA compute process and the cgroup it runs in
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GpuProcess {
+    pub pid: u32,
+    pub used_memory_mb: u64,
+    pub util_percent: f32,
+    pub cgroup: String,
+}
+
+/// Every NVIDIA compute process with its memory and SM utilisation
+fn read_gpu_processes() -> Vec<GpuProcess> {
+    let Ok(apps) = Command::new("nvidia-smi")
+        .args(["--query-compute-apps=pid,used_memory", "--format=csv,noheader,nounits"])
+        .output()
+    else {
+        return vec![];
+    };
+    let util = Command::new("nvidia-smi")
+        .args(["pmon", "-c", "1", "-s", "u"])
+        .output()
+        .map(|output| parse_pmon(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or_default();
+
+    parse_compute_apps(&String::from_utf8_lossy(&apps.stdout))
+        .into_iter()
+        .filter_map(|(pid, used_memory_mb)| {
+            let cgroup = std::fs::read_to_string(format!("/proc/{}/cgroup", pid))
+                .ok()
+                .and_then(|content| parse_cgroup_path(&content))?;
+            Some(GpuProcess {
+                pid,
+                used_memory_mb,
+                util_percent: util.get(&pid).copied().unwrap_or(0.0),
+                cgroup,
+            })
+        })
+        .collect()
+}
+
+/// Host PID of a running container's init process
+fn container_pid(container_name: &str) -> Option<u32> {
+    let output = Command::new("docker")
+        .args(["inspect", "-f", "{{.State.Pid}}", container_name])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .ok()
+        .filter(|pid| *pid != 0)
+}
+
+/// Unified (cgroup v2) path from `/proc/<pid>/cgroup`
+pub fn parse_cgroup_path(content: &str) -> Option<String> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(|path| path.trim().to_string())
+}
+
+/// Value of a `key value` line in cgroup stat files such as `cpu.stat`
+pub fn stat_value(content: &str, key: &str) -> Option<u64> {
+    content.lines().find_map(|line| {
+        let (name, value) = line.split_once(' ')?;
+        (name == key).then(|| value.trim().parse().ok()).flatten()
+    })
+}
+
+/// Bytes read and written over all devices in `io.stat`
+pub fn parse_io_stat(content: &str) -> (u64, u64) {
+    let mut totals = (0, 0);
+    for field in content.split_whitespace() {
+        if let Some(bytes) = field.strip_prefix("rbytes=") {
+            totals.0 += bytes.parse::<u64>().unwrap_or(0);
+        } else if let Some(bytes) = field.strip_prefix("wbytes=") {
+            totals.1 += bytes.parse::<u64>().unwrap_or(0);
+        }
+    }
+    totals
+}
+
+/// Bytes received and sent over all interfaces but loopback in `/proc/<pid>/net/dev`
+pub fn parse_net_dev(content: &str) -> (u64, u64) {
+    let mut totals = (0, 0);
+    for line in content.lines().skip(2) {
+        let Some((interface, counters)) = line.split_once(':') else {
+            continue;
+        };
+        if interface.trim() == "lo" {
+            continue;
+        }
+        let fields: Vec<u64> = counters.split_whitespace().filter_map(|f| f.parse().ok()).collect();
+        if fields.len() >= 9 {
+            totals.0 += fields[0];
+            totals.1 += fields[8];
+        }
+    }
+    totals
+}
+
+/// `(pid, used MiB)` rows of `nvidia-smi --query-compute-apps=pid,used_memory`
+pub fn parse_compute_apps(output: &str) -> Vec<(u32, u64)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (pid, memory) = line.split_once(',')?;
+            Some((pid.trim().parse().ok()?, memory.trim().parse().unwrap_or(0)))
+        })
+        .collect()
+}
+
+/// SM utilisation per PID from `nvidia-smi pmon -s u`, summed over GPUs
+pub fn parse_pmon(output: &str) -> HashMap<u32, f32> {
+    let mut util = HashMap::new();
+    for line in output.lines().filter(|line| !line.starts_with('#')) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        // gpu pid type sm mem ...; idle processes report "-"
+        let (Some(pid), Some(sm)) = (fields.get(1).and_then(|f| f.parse::<u32>().ok()), fields.get(3)) else {
+            continue;
+        };
+        *util.entry(pid).or_insert(0.0) += sm.parse::<f32>().unwrap_or(0.0);
+    }
+    util
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sampler_sources() {
+        assert_eq!(
+            parse_cgroup_path("0::/system.slice/docker-4f2a.scope\n"),
+            Some("/system.slice/docker-4f2a.scope".to_string())
+        );
+        assert_eq!(stat_value("usage_usec 1500\nuser_usec 1000\n", "usage_usec"), Some(1500));
+        assert_eq!(
+            parse_io_stat("8:0 rbytes=100 wbytes=20 rios=1 wios=1\n259:0 rbytes=5 wbytes=0 rios=1 wios=0\n"),
+            (105, 20)
+        );
+
+        let net_dev = "Inter-|   Receive |  Transmit\n face |bytes packets errs drop fifo frame compressed multicast|bytes\n    lo: 999 1 0 0 0 0 0 0 999 1 0 0 0 0 0 0\n  eth0: 4096 10 0 0 0 0 0 0 1024 8 0 0 0 0 0 0\n";
+        assert_eq!(parse_net_dev(net_dev), (4096, 1024));
+
+        assert_eq!(parse_compute_apps("1234, 2048\n5678, 512\n"), vec![(1234, 2048), (5678, 512)]);
+        let pmon = "# gpu   pid  type  sm  mem  enc  dec  command\n# Idx     #   C/G   %    %    %    %   name\n    0  1234     C   87   40    -    -   python\n    1  1234     C   13    5    -    -   python\n    1  5678     C    -    -    -    -   python\n";
+        let util = parse_pmon(pmon);
+        assert_eq!(util[&1234], 100.0);
+        assert_eq!(util[&5678], 0.0);
+    }
+}
@@ -0,0 +1,492 @@
+//! Client deposits
+//! A node whose access policy asks for a deposit holds one per job from clients below its
+//! reputation bar. The client pays it to the node's payout wallet and sends the transfer's
+//! reference along with its job; the node checks the transfer on chain, binds the deposit to the
+//! job on admission, then refunds it when the job ends or keeps it when the owner forfeits it.
+//! The ledger records who is owed what; the transfers go through the wallets. A client's
+//! reputation travels with its jobs too, signed by an issuer the node chooses to trust.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::Duration;
+
+pub use eryzaa_protocol::{AccessPolicy, DepositClaim, ReputationAttestation};
+
+/// Environment variable with the JSON-RPC endpoint of the C-Chain node used to check transfers
+pub const CHAIN_RPC_ENV: &str = "ERYZAA_CHAIN_RPC";
+
+/// A local AvalancheGo node's C-Chain endpoint
+pub const DEFAULT_CHAIN_RPC: &str = "http://127.0.0.1:9650/ext/bc/C/rpc";
+
+/// Oldest reputation attestation a node accepts
+pub const ATTESTATION_MAX_AGE: u64 = 30 * 24 * 3600;
+
+const RPC_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum DepositState {
+    Held,
+    Refunded,
+    Forfeited(String), // Why the owner kept it
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Deposit {
+    pub deposit_id: String,
+    pub client_id: String,
+    pub amount: f32,            // AVAX
+    pub tx_ref: String,         // Transaction the client paid the deposit with
+    pub job_id: Option<String>, // Job the deposit secures, None until one is admitted
+    pub state: DepositState,
+    pub posted_at: u64,          // Unix seconds
+    pub settled_at: Option<u64>, // When it was refunded or forfeited
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DepositLedger {
+    pub deposits: Vec<Deposit>,
+}
+
+impl DepositLedger {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize deposit ledger: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write deposit ledger {}: {}", path.display(), e))
+    }
+
+    /// Record a client's deposit; each transfer can back only one deposit
+    pub fn post(&mut self, client_id: &str, amount: f32, tx_ref: &str, now: u64) -> Result<String, String> {
+        if amount <= 0.0 {
+            return Err("Deposit must be more than 0 AVAX".to_string());
+        }
+        if tx_ref.trim().is_empty() {
+            return Err("Deposit needs the reference of the transfer that paid it".to_string());
+        }
+        if self.deposits.iter().any(|deposit| deposit.tx_ref == tx_ref) {
+            return Err(format!("Transfer {} already backs a deposit", tx_ref));
+        }
+
+        let deposit_id = uuid::Uuid::new_v4().simple().to_string()[..10].to_string();
+        self.deposits.push(Deposit {
+            deposit_id: deposit_id.clone(),
+            client_id: client_id.to_string(),
+            amount,
+            tx_ref: tx_ref.to_string(),
+            job_id: None,
+            state: DepositState::Held,
+            posted_at: now,
+            settled_at: None,
+        });
+        Ok(deposit_id)
+    }
+
+    /// Record a deposit the client claims to have paid to `payee`, once `check` finds the transfer
+    pub fn post_verified(&mut self, client_id: &str, claim: &DepositClaim, payee: &str, check: &impl TransferCheck, now: u64) -> Result<String, String> {
+        // Cheap refusals first, so a replayed claim never reaches the chain
+        if claim.amount <= 0.0 || claim.tx_ref.trim().is_empty() || self.deposits.iter().any(|d| d.tx_ref == claim.tx_ref) {
+            return self.post(client_id, claim.amount, &claim.tx_ref, now);
+        }
+        check.confirm(&claim.tx_ref, payee, claim.amount)?;
+        self.post(client_id, claim.amount, &claim.tx_ref, now)
+    }
+
+    /// The deposit to secure a client's job with under `policy`, taking up the claim sent with the
+    /// job when the client has no deposit held yet
+    ///
+    /// Returns the deposit's id and amount, or None when the client needs no deposit.
+    #[allow(clippy::too_many_arguments)]
+    pub fn secure(
+        &mut self,
+        policy: &AccessPolicy,
+        client_id: &str,
+        reputation: Option<f32>,
+        claim: Option<&DepositClaim>,
+        payee: Option<&str>,
+        check: &impl TransferCheck,
+        now: u64,
+    ) -> Result<Option<(String, f32)>, String> {
+        if let Err(reason) = self.access(policy, client_id, reputation) {
+            let Some(claim) = claim else { return Err(reason) };
+            let payee = payee.ok_or("Node has no payout wallet to check deposits against")?;
+            self.post_verified(client_id, claim, payee, check, now)?;
+        }
+        Ok(self
+            .access(policy, client_id, reputation)?
+            .map(|deposit| (deposit.deposit_id.clone(), deposit.amount)))
+    }
+
+    /// The client's largest held deposit not yet securing a job
+    pub fn free_for(&self, client_id: &str) -> Option<&Deposit> {
+        self.deposits
+            .iter()
+            .filter(|d| d.client_id == client_id && d.state == DepositState::Held && d.job_id.is_none())
+            .max_by(|a, b| a.amount.total_cmp(&b.amount))
+    }
+
+    /// Check a client's job against `policy`; the deposit to secure it with, if the client has one
+    pub fn access(&self, policy: &AccessPolicy, client_id: &str, reputation: Option<f32>) -> Result<Option<&Deposit>, String> {
+        let free = self.free_for(client_id);
+        match policy.deposit_required(reputation)? {
+            Some(required) if free.is_none_or(|deposit| deposit.amount < required) => Err(format!(
+                "Node requires a refundable deposit of {:.2} AVAX from clients below its reputation bar",
+                required
+            )),
+            _ => Ok(free),
+        }
+    }
+
+    pub fn bind(&mut self, deposit_id: &str, job_id: &str) -> Result<(), String> {
+        let deposit = self.held_mut(deposit_id)?;
+        if let Some(bound) = &deposit.job_id {
+            return Err(format!("Deposit {} already secures job {}", deposit_id, bound));
+        }
+        deposit.job_id = Some(job_id.to_string());
+        Ok(())
+    }
+
+    /// Refund the deposit securing `job_id`; returns the amount owed back
+    pub fn refund_job(&mut self, job_id: &str, now: u64) -> Option<f32> {
+        let deposit = self
+            .deposits
+            .iter_mut()
+            .find(|d| d.job_id.as_deref() == Some(job_id) && d.state == DepositState::Held)?;
+        deposit.state = DepositState::Refunded;
+        deposit.settled_at = Some(now);
+        Some(deposit.amount)
+    }
+
+    /// Refund a deposit, e.g. one the client no longer needs or the owner releases
+    pub fn refund(&mut self, deposit_id: &str, now: u64) -> Result<f32, String> {
+        let deposit = self.held_mut(deposit_id)?;
+        deposit.state = DepositState::Refunded;
+        deposit.settled_at = Some(now);
+        Ok(deposit.amount)
+    }
+
+    /// Keep a deposit, e.g. after the job abused the node
+    pub fn forfeit(&mut self, deposit_id: &str, reason: &str, now: u64) -> Result<f32, String> {
+        let deposit = self.held_mut(deposit_id)?;
+        deposit.state = DepositState::Forfeited(reason.to_string());
+        deposit.settled_at = Some(now);
+        Ok(deposit.amount)
+    }
+
+    pub fn held(&self) -> Vec<&Deposit> {
+        self.deposits.iter().filter(|d| d.state == DepositState::Held).collect()
+    }
+
+    fn held_mut(&mut self, deposit_id: &str) -> Result<&mut Deposit, String> {
+        let deposit = self
+            .deposits
+            .iter_mut()
+            .find(|d| d.deposit_id == deposit_id)
+            .ok_or_else(|| format!("No deposit '{}'", deposit_id))?;
+        if deposit.state != DepositState::Held {
+            return Err(format!("Deposit {} was already settled", deposit_id));
+        }
+        Ok(deposit)
+    }
+}
+
+/// Looks up transfers so a node only holds deposits that were paid
+pub trait TransferCheck {
+    /// Ok when `tx_ref` moved at least `amount` AVAX to `payee` and went through
+    fn confirm(&self, tx_ref: &str, payee: &str, amount: f32) -> Result<(), String>;
+}
+
+/// Checks transfers with a C-Chain node's Ethereum JSON-RPC API, over plain HTTP
+#[derive(Debug, Clone)]
+pub struct ChainRpc {
+    pub url: String,
+}
+
+impl ChainRpc {
+    pub fn from_env() -> Self {
+        Self { url: std::env::var(CHAIN_RPC_ENV).unwrap_or_else(|_| DEFAULT_CHAIN_RPC.to_string()) }
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value, String> {
+        let rest = self
+            .url
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("Chain RPC {} must be an http:// URL", self.url))?;
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        let address = match authority.contains(':') {
+            true => authority.to_string(),
+            false => format!("{}:80", authority),
+        };
+        let addr = address
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .ok_or_else(|| format!("Cannot resolve chain RPC {}", self.url))?;
+
+        let error = |e: std::io::Error| format!("Failed to reach chain RPC {}: {}", self.url, e);
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }).to_string();
+        let mut stream = TcpStream::connect_timeout(&addr, RPC_TIMEOUT).map_err(error)?;
+        stream.set_read_timeout(Some(RPC_TIMEOUT)).map_err(error)?;
+        write!(
+            stream,
+            "POST {} HTTP/1.0\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            path,
+            authority,
+            body.len(),
+            body
+        )
+        .map_err(error)?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).map_err(error)?;
+
+        let body = response
+            .split_once("\r\n\r\n")
+            .map(|(_, body)| body)
+            .ok_or_else(|| format!("Malformed response from chain RPC {}", self.url))?;
+        let answer: Value = serde_json::from_str(body).map_err(|e| format!("Malformed answer from chain RPC {}: {}", self.url, e))?;
+        if let Some(error) = answer.get("error") {
+            return Err(format!("Chain RPC {} refused {}: {}", self.url, method, error));
+        }
+        Ok(answer.get("result").cloned().unwrap_or(Value::Null))
+    }
+}
+
+impl TransferCheck for ChainRpc {
+    fn confirm(&self, tx_ref: &str, payee: &str, amount: f32) -> Result<(), String> {
+        let transaction = self.call("eth_getTransactionByHash", json!([tx_ref]))?;
+        let receipt = self.call("eth_getTransactionReceipt", json!([tx_ref]))?;
+        check_transfer(tx_ref, &transaction, &receipt, payee, amount)
+    }
+}
+
+/// Whether a transaction and its receipt, as JSON-RPC returns them, pay `amount` AVAX to `payee`
+pub fn check_transfer(tx_ref: &str, transaction: &Value, receipt: &Value, payee: &str, amount: f32) -> Result<(), String> {
+    if transaction.is_null() {
+        return Err(format!("Transfer {} is not on chain", tx_ref));
+    }
+    if receipt.get("status").and_then(Value::as_str) != Some("0x1") {
+        return Err(format!("Transfer {} has not gone through", tx_ref));
+    }
+    let to = transaction.get("to").and_then(Value::as_str).unwrap_or_default();
+    if !to.eq_ignore_ascii_case(payee) {
+        return Err(format!("Transfer {} went to {} rather than the node's wallet", tx_ref, to));
+    }
+    let wei = transaction
+        .get("value")
+        .and_then(Value::as_str)
+        .and_then(|value| u128::from_str_radix(value.trim_start_matches("0x"), 16).ok())
+        .ok_or_else(|| format!("Transfer {} has no readable value", tx_ref))?;
+    // Float AVAX amounts are compared in whole nano-AVAX
+    if wei / 1_000_000_000 < (amount as f64 * 1e9).round() as u128 {
+        return Err(format!("Transfer {} paid {:.4} AVAX, short of {:.2}", tx_ref, wei as f64 / 1e18, amount));
+    }
+    Ok(())
+}
+
+/// The bytes an attestation's issuer signs
+pub fn attestation_bytes(client_id: &str, score: f32, issued_at: u64) -> Vec<u8> {
+    format!("eryzaa-reputation-v1\n{}\n{:.2}\n{}", client_id, score, issued_at).into_bytes()
+}
+
+/// Vouch for a client's reputation with the issuer's `key`
+pub fn attest(key: &SigningKey, client_id: &str, score: f32, now: u64) -> ReputationAttestation {
+    ReputationAttestation {
+        client_id: client_id.to_string(),
+        score,
+        issued_at: now,
+        issuer: hex::encode(key.verifying_key().to_bytes()),
+        signature: hex::encode(key.sign(&attestation_bytes(client_id, score, now)).to_bytes()),
+    }
+}
+
+/// The reputation `attestation` vouches for, if it names `client_id`, is recent, and is signed by
+/// one of the `trusted` issuers (hex public keys); anything else counts as no history
+pub fn verified_reputation(attestation: Option<&ReputationAttestation>, client_id: &str, trusted: &[String], now: u64) -> Option<f32> {
+    let attestation = attestation?;
+    if attestation.client_id != client_id
+        || now.saturating_sub(attestation.issued_at) > ATTESTATION_MAX_AGE
+        || !trusted.iter().any(|issuer| issuer.eq_ignore_ascii_case(&attestation.issuer))
+    {
+        return None;
+    }
+    let issuer: [u8; 32] = hex::decode(&attestation.issuer).ok()?.try_into().ok()?;
+    let signature: [u8; 64] = hex::decode(&attestation.signature).ok()?.try_into().ok()?;
+    VerifyingKey::from_bytes(&issuer)
+        .ok()?
+        .verify(&attestation_bytes(client_id, attestation.score, attestation.issued_at), &Signature::from_bytes(&signature))
+        .ok()?;
+    Some(attestation.score.clamp(0.0, 5.0))
+}
+
+/// What a client sends along with its next job: a deposit it paid and its reputation
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ClientAccess {
+    pub deposit: Option<DepositClaim>,
+    pub reputation: Option<ReputationAttestation>,
+}
+
+impl ClientAccess {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize client access: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write client access {}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deposit_flow() {
+        let policy = AccessPolicy { min_reputation: Some(4.0), deposit: Some(5.0) };
+        let mut ledger = DepositLedger::default();
+
+        assert!(ledger.access(&policy, "client-1", Some(4.2)).unwrap().is_none());
+        assert!(ledger.access(&policy, "client-1", None).unwrap_err().contains("5.00 AVAX"));
+
+        let small = ledger.post("client-1", 2.0, "0xaaa", 100).unwrap();
+        assert!(ledger.access(&policy, "client-1", None).is_err());
+        let deposit = ledger.post("client-1", 5.0, "0xbbb", 110).unwrap();
+        assert!(ledger.post("client-2", 5.0, "0xbbb", 120).is_err());
+        assert_eq!(ledger.access(&policy, "client-1", Some(1.0)).unwrap().map(|d| d.deposit_id.clone()), Some(deposit.clone()));
+
+        ledger.bind(&deposit, "job-1").unwrap();
+        assert!(ledger.access(&policy, "client-1", None).is_err());
+        assert_eq!(ledger.refund_job("job-1", 200), Some(5.0));
+        assert_eq!(ledger.refund_job("job-1", 210), None);
+
+        assert_eq!(ledger.forfeit(&small, "Crypto mining", 220), Ok(2.0));
+        assert!(ledger.refund(&small, 230).is_err());
+        assert!(ledger.held().is_empty());
+    }
+
+    /// A JSON-RPC server answering for one paid transfer, standing in for the chain
+    fn fake_chain(tx_ref: &'static str, payee: &'static str, wei: &'static str) -> ChainRpc {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/ext/bc/C/rpc", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0u8; 4096];
+                // Read headers and the whole body before answering
+                while let Ok(read) = stream.read(&mut buffer) {
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let length = head
+                            .lines()
+                            .find_map(|line| line.strip_prefix("Content-Length: "))
+                            .and_then(|length| length.trim().parse::<usize>().ok())
+                            .unwrap_or(0);
+                        if read == 0 || body.len() >= length {
+                            break;
+                        }
+                    }
+                }
+                let text = String::from_utf8_lossy(&request).to_string();
+                let known = text.contains(tx_ref);
+                let result = match (known, text.contains("eth_getTransactionReceipt")) {
+                    (false, _) => Value::Null,
+                    (true, true) => json!({ "status": "0x1", "blockNumber": "0x10" }),
+                    (true, false) => json!({ "hash": tx_ref, "to": payee, "value": wei, "blockNumber": "0x10" }),
+                };
+                let body = json!({ "jsonrpc": "2.0", "id": 1, "result": result }).to_string();
+                let _ = write!(stream, "HTTP/1.0 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+            }
+        });
+        ChainRpc { url }
+    }
+
+    #[test]
+    fn test_deposit_and_reputation_travel_with_the_job() {
+        let payee = "0x00000000000000000000000000000000000000aa";
+        // 5 AVAX in wei
+        let chain = fake_chain("0xpaid", payee, "0x4563918244f40000");
+        let issuer = SigningKey::from_bytes(&[7u8; 32]);
+        let trusted = vec![hex::encode(issuer.verifying_key().to_bytes())];
+        let policy = AccessPolicy { min_reputation: Some(4.0), deposit: Some(5.0) };
+
+        // On the client: what it saved with `deposit post` goes out with its next job
+        let access = ClientAccess {
+            deposit: Some(DepositClaim { tx_ref: "0xpaid".to_string(), amount: 5.0 }),
+            reputation: Some(attest(&issuer, "client-1", 3.0, 1_000)),
+        };
+        let wire = json!({
+            "job_id": "job-1", "client_id": "client-1", "image": "ubuntu", "command": [],
+            "node_id": null, "node_address": null, "ssh_user": null,
+            "deposit": access.deposit, "reputation": access.reputation,
+        })
+        .to_string();
+
+        // On the node: only what it can check counts
+        let job: eryzaa_protocol::JobSubmission = serde_json::from_str(&wire).unwrap();
+        let mut ledger = DepositLedger::default();
+        let reputation = verified_reputation(job.reputation.as_ref(), &job.client_id, &trusted, 2_000);
+        assert_eq!(reputation, Some(3.0));
+        assert_eq!(verified_reputation(job.reputation.as_ref(), "client-2", &trusted, 2_000), None);
+        assert_eq!(verified_reputation(job.reputation.as_ref(), &job.client_id, &[], 2_000), None);
+        let mut inflated = job.reputation.clone().unwrap();
+        inflated.score = 5.0;
+        assert_eq!(verified_reputation(Some(&inflated), &job.client_id, &trusted, 2_000), None);
+
+        let (deposit, amount) = ledger
+            .secure(&policy, &job.client_id, reputation, job.deposit.as_ref(), Some(payee), &chain, 2_000)
+            .unwrap()
+            .unwrap();
+        assert_eq!(amount, 5.0);
+        ledger.bind(&deposit, &job.job_id).unwrap();
+
+        // The same transfer cannot back a second job, nor can one that never happened
+        let replay = ledger.secure(&policy, "client-2", None, job.deposit.as_ref(), Some(payee), &chain, 2_100);
+        assert!(replay.unwrap_err().contains("already backs"));
+        let forged = DepositClaim { tx_ref: "0xforged".to_string(), amount: 5.0 };
+        let forged = ledger.secure(&policy, "client-2", None, Some(&forged), Some(payee), &chain, 2_100);
+        assert!(forged.unwrap_err().contains("not on chain"));
+        assert_eq!(ledger.deposits.len(), 1);
+    }
+
+    #[test]
+    fn test_check_transfer() {
+        let payee = "0xAbC";
+        let tx = json!({ "to": "0xabc", "value": "0x4563918244f40000", "blockNumber": "0x1" });
+        let ok = json!({ "status": "0x1" });
+        assert!(check_transfer("0x1", &tx, &ok, payee, 5.0).is_ok());
+        assert!(check_transfer("0x1", &tx, &ok, payee, 5.5).unwrap_err().contains("short of"));
+        assert!(check_transfer("0x1", &tx, &ok, "0xdef", 5.0).unwrap_err().contains("rather than"));
+        assert!(check_transfer("0x1", &tx, &json!({ "status": "0x0" }), payee, 5.0).is_err());
+        assert!(check_transfer("0x1", &Value::Null, &Value::Null, payee, 5.0).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+//! Abuse reports and the shared blocklist
+//! A renter reports a tenant who misused their machine to the coordinator, with the node's own
+//! metering as evidence. Clients reported by enough nodes, or flagged by the coordinator's
+//! operator, are published on a blocklist other renters can subscribe to.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AbuseCategory {
+    Cryptojacking,         // Mining on hardware rented or granted for something else
+    AttackingThirdParties, // Scans, floods or intrusion attempts from the node
+    Other,
+}
+
+impl AbuseCategory {
+    pub const ALL: [AbuseCategory; 3] = [Self::Cryptojacking, Self::AttackingThirdParties, Self::Other];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Cryptojacking => "cryptojacking",
+            Self::AttackingThirdParties => "attacking-third-parties",
+            Self::Other => "other",
+        }
+    }
+
+    pub fn parse(label: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|category| category.label() == label)
+    }
+}
+
+/// One piece of evidence, kept verbatim by the coordinator
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Evidence {
+    pub kind: String,    // "receipt", "transfers", "job", ...
+    pub summary: String, // One line for people reviewing the report
+    pub data: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AbuseReport {
+    pub report_id: String,
+    pub reporter_node_id: String,
+    pub client_id: String,
+    pub job_id: String,
+    pub category: AbuseCategory,
+    pub description: String,
+    pub reported_at: u64, // Unix seconds
+    pub evidence: Vec<Evidence>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BlocklistEntry {
+    pub client_id: String,
+    pub categories: Vec<AbuseCategory>,
+    pub reporters: u32, // Distinct nodes that reported the client
+    pub flagged_at: u64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Blocklist {
+    pub generated_at: u64,
+    pub entries: Vec<BlocklistEntry>,
+}
+
+impl Blocklist {
+    pub fn entry(&self, client_id: &str) -> Option<&BlocklistEntry> {
+        self.entries.iter().find(|entry| entry.client_id == client_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocklist_lookup() {
+        for category in AbuseCategory::ALL {
+            assert_eq!(AbuseCategory::parse(category.label()), Some(category));
+        }
+        assert_eq!(AbuseCategory::parse("spam"), None);
+
+        let blocklist = Blocklist {
+            generated_at: 1_760_000_000,
+            entries: vec![BlocklistEntry {
+                client_id: "client-7".to_string(),
+                categories: vec![AbuseCategory::Cryptojacking],
+                reporters: 2,
+                flagged_at: 1_759_990_000,
+                reason: "Reported by 2 nodes".to_string(),
+            }],
+        };
+        let json = serde_json::to_string(&blocklist).unwrap();
+        let blocklist: Blocklist = serde_json::from_str(&json).unwrap();
+        assert_eq!(blocklist.entry("client-7").map(|entry| entry.reporters), Some(2));
+        assert!(blocklist.entry("client-8").is_none());
+    }
+}
@@ -0,0 +1,122 @@
+//! Owner-written advertisement details
+//! A short description, a hardware photo link and free-form key/value notes that help clients
+//! tell similar nodes apart. Sizes are capped so the advertisement still fits one packet.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub const MAX_DESCRIPTION_BYTES: usize = 500;
+pub const MAX_PHOTO_URL_BYTES: usize = 200;
+pub const MAX_METADATA_ENTRIES: usize = 8;
+pub const MAX_METADATA_KEY_BYTES: usize = 32;
+pub const MAX_METADATA_VALUE_BYTES: usize = 64;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct NodeDetails {
+    pub description: String,
+    pub photo_url: Option<String>,
+    pub metadata: Vec<(String, String)>, // Shown in order, e.g. ("NVLink", "yes")
+}
+
+impl NodeDetails {
+    pub fn is_empty(&self) -> bool {
+        self.description.is_empty() && self.photo_url.is_none() && self.metadata.is_empty()
+    }
+
+    /// Check the details fit the advertisement limits
+    pub fn validate(&self) -> Result<(), String> {
+        if self.description.len() > MAX_DESCRIPTION_BYTES {
+            return Err(format!("Description is longer than {} bytes", MAX_DESCRIPTION_BYTES));
+        }
+        if let Some(url) = &self.photo_url {
+            if !url.starts_with("https://") {
+                return Err("Photo URL must start with https://".to_string());
+            }
+            if url.len() > MAX_PHOTO_URL_BYTES {
+                return Err(format!("Photo URL is longer than {} bytes", MAX_PHOTO_URL_BYTES));
+            }
+        }
+        if self.metadata.len() > MAX_METADATA_ENTRIES {
+            return Err(format!("At most {} metadata entries are allowed", MAX_METADATA_ENTRIES));
+        }
+        for (key, value) in &self.metadata {
+            if key.trim().is_empty() {
+                return Err("Metadata keys cannot be empty".to_string());
+            }
+            if key.len() > MAX_METADATA_KEY_BYTES || value.len() > MAX_METADATA_VALUE_BYTES {
+                return Err(format!(
+                    "Metadata '{}' is too long (keys up to {} bytes, values up to {})",
+                    key, MAX_METADATA_KEY_BYTES, MAX_METADATA_VALUE_BYTES
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        self.validate()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize node details: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write node details {}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_limits() {
+        let mut details = NodeDetails {
+            description: "Quiet water-cooled box on fiber".to_string(),
+            photo_url: Some("https://example.com/rig.jpg".to_string()),
+            metadata: vec![
+                ("NVLink".to_string(), "yes".to_string()),
+                ("Internet".to_string(), "1 Gbps fiber".to_string()),
+            ],
+        };
+        assert!(details.validate().is_ok());
+
+        details.photo_url = Some("http://example.com/rig.jpg".to_string());
+        assert!(details.validate().is_err());
+        details.photo_url = None;
+
+        details.metadata.push(("x".repeat(MAX_METADATA_KEY_BYTES + 1), "1".to_string()));
+        assert!(details.validate().is_err());
+        details.metadata.pop();
+
+        details.description = "a".repeat(MAX_DESCRIPTION_BYTES + 1);
+        assert!(details.validate().is_err());
+    }
+
+    #[test]
+    fn test_largest_details_fit_one_packet() {
+        let details = NodeDetails {
+            description: "d".repeat(MAX_DESCRIPTION_BYTES),
+            photo_url: Some(format!("https://{}", "p".repeat(MAX_PHOTO_URL_BYTES - 8))),
+            metadata: (0..MAX_METADATA_ENTRIES)
+                .map(|_| ("k".repeat(MAX_METADATA_KEY_BYTES), "v".repeat(MAX_METADATA_VALUE_BYTES)))
+                .collect(),
+        };
+        assert!(details.validate().is_ok());
+
+        let mut advertisement: crate::NodeAdvertisement =
+            serde_json::from_str(include_str!("../fixtures/v2/node_advertisement.json")).unwrap();
+        advertisement.details = details;
+        // The listener reads advertisements into a 4 KiB buffer
+        assert!(crate::node::encode_advertisement(&advertisement).unwrap().len() < 4096);
+    }
+}
@@ -0,0 +1,58 @@
+//! Control-plane events
+//! Published on the event bus under `<subsystem>.<event>` topics, in-process or as JSON over
+//! NATS between nodes and the coordinator.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum Event {
+    JobAdmitted { job_id: String },
+    JobRejected { job_id: String, rule: String },
+    JobAwaitingOwner { job_id: String, rule: String },
+    JobStarted { job_id: String },
+    JobDeferred { job_id: String, reason: String },
+    PipelineStageChanged { run_id: String, stage: String, state: String },
+    JobStatusChanged { job_id: String, status: String },
+    EnergyMetered { job_id: String, wh: f64 },
+    SshUserCreated { username: String },
+    SshUserRemoved { username: String },
+    NodeDiscovered { node_id: String },
+    NodeLost { node_id: String },
+    CapabilitiesChanged { changes: Vec<String>, at_risk_jobs: Vec<String> },
+}
+
+impl Event {
+    /// Topic the event is published on, as `<subsystem>.<event>`
+    pub fn topic(&self) -> &'static str {
+        match self {
+            Event::JobAdmitted { .. } => "scheduler.admitted",
+            Event::JobRejected { .. } => "scheduler.rejected",
+            Event::JobAwaitingOwner { .. } => "scheduler.awaiting_owner",
+            Event::JobStarted { .. } => "scheduler.started",
+            Event::JobDeferred { .. } => "scheduler.deferred",
+            Event::PipelineStageChanged { .. } => "scheduler.pipeline_stage",
+            Event::JobStatusChanged { .. } => "jobs.status",
+            Event::EnergyMetered { .. } => "metering.energy",
+            Event::SshUserCreated { .. } => "ssh.user_created",
+            Event::SshUserRemoved { .. } => "ssh.user_removed",
+            Event::NodeDiscovered { .. } => "discovery.node_discovered",
+            Event::NodeLost { .. } => "discovery.node_lost",
+            Event::CapabilitiesChanged { .. } => "node.capabilities_changed",
+        }
+    }
+}
+
+/// An event as delivered to subscribers
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Envelope {
+    pub source: String, // Node or process that published it
+    pub timestamp_ms: u64,
+    pub event: Event,
+}
+
+/// Whether `topic` falls under `prefix`; an empty prefix matches every topic
+pub fn topic_matches(prefix: &str, topic: &str) -> bool {
+    prefix.is_empty()
+        || topic == prefix
+        || (topic.starts_with(prefix) && topic[prefix.len()..].starts_with('.'))
+}
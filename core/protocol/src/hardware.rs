@@ -0,0 +1,217 @@
+//! Specialized hardware a node advertises and jobs can ask for
+//! Counts of cores and GPUs do not tell an A100 from a T4, or a box with NVLink from one without.
+//! Nodes advertise a `HardwareProfile` naming their parts, and a job's `HardwareConstraints` are
+//! checked against it. A value the node did not report never satisfies a constraint on it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::node::cuda_major_minor;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct HardwareProfile {
+    pub gpus: Vec<GpuDevice>,
+    pub gpu_link: GpuLink,
+    pub cpu_model: Option<String>, // e.g. "AMD EPYC 7763 64-Core Processor"
+    pub avx: Option<AvxLevel>,     // None on CPUs without x86 vector extensions, e.g. ARM
+    pub memory_speed_mts: Option<u32>, // Configured RAM speed in MT/s
+    pub nvme: bool,
+    pub accelerators: Vec<Accelerator>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GpuDevice {
+    pub model: String, // e.g. "NVIDIA A100-SXM4-80GB"
+    pub memory_mb: u64,
+    pub compute_capability: Option<String>, // e.g. "8.0"
+}
+
+/// How the node's GPUs talk to each other
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum GpuLink {
+    #[default]
+    Unknown, // Not detected, or a single GPU
+    Pcie,
+    NvLink,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AvxLevel {
+    None,
+    Avx,
+    Avx2,
+    Avx512,
+}
+
+impl AvxLevel {
+    /// The highest level among the flags of a /proc/cpuinfo "flags" line
+    pub fn from_cpu_flags(flags: &str) -> Self {
+        let has = |flag: &str| flags.split_whitespace().any(|set| set == flag);
+        if has("avx512f") {
+            Self::Avx512
+        } else if has("avx2") {
+            Self::Avx2
+        } else if has("avx") {
+            Self::Avx
+        } else {
+            Self::None
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::None => "no AVX",
+            Self::Avx => "AVX",
+            Self::Avx2 => "AVX2",
+            Self::Avx512 => "AVX-512",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Accelerator {
+    Tpu,
+    Fpga,
+}
+
+impl Accelerator {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Tpu => "TPU",
+            Self::Fpga => "FPGA",
+        }
+    }
+}
+
+impl HardwareProfile {
+    /// One line for node cards, e.g. "2× NVIDIA A100 (sm 8.0), NVLink, AVX-512, NVMe"
+    pub fn summary(&self) -> String {
+        let mut parts = vec![];
+        if let Some(gpu) = self.gpus.first() {
+            let capability = gpu.compute_capability.as_deref().map(|cc| format!(" (sm {})", cc)).unwrap_or_default();
+            parts.push(format!("{}× {}{}", self.gpus.len(), gpu.model, capability));
+        }
+        match self.gpu_link {
+            GpuLink::NvLink => parts.push("NVLink".to_string()),
+            GpuLink::Pcie => parts.push("PCIe".to_string()),
+            GpuLink::Unknown => {}
+        }
+        if let Some(avx) = self.avx.filter(|avx| *avx != AvxLevel::None) {
+            parts.push(avx.label().to_string());
+        }
+        if let Some(speed) = self.memory_speed_mts {
+            parts.push(format!("RAM {} MT/s", speed));
+        }
+        if self.nvme {
+            parts.push("NVMe".to_string());
+        }
+        parts.extend(self.accelerators.iter().map(|accelerator| accelerator.label().to_string()));
+        parts.join(", ")
+    }
+}
+
+/// Hardware a job insists on; unset fields accept anything
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct HardwareConstraints {
+    #[serde(default)]
+    pub gpu_models: Vec<String>, // Any GPU whose model contains one of these, ignoring case
+    #[serde(default)]
+    pub min_compute_capability: Option<String>, // Every GPU at least this, e.g. "8.0"
+    #[serde(default)]
+    pub nvlink: bool,
+    #[serde(default)]
+    pub min_avx: Option<AvxLevel>,
+    #[serde(default)]
+    pub min_memory_speed_mts: Option<u32>,
+    #[serde(default)]
+    pub nvme: bool,
+    #[serde(default)]
+    pub accelerators: Vec<Accelerator>,
+}
+
+impl HardwareConstraints {
+    /// Why a node with `profile` does not qualify, one reason per unmet constraint
+    pub fn unmet(&self, profile: &HardwareProfile) -> Vec<String> {
+        let mut unmet = vec![];
+        if !self.gpu_models.is_empty() {
+            let wanted: Vec<String> = self.gpu_models.iter().map(|model| model.to_lowercase()).collect();
+            let matches = profile.gpus.iter().any(|gpu| wanted.iter().any(|model| gpu.model.to_lowercase().contains(model)));
+            if !matches {
+                unmet.push(format!("needs a GPU matching {}", self.gpu_models.join(" or ")));
+            }
+        }
+        if let Some(required) = &self.min_compute_capability {
+            let below = |gpu: &GpuDevice| {
+                let have = gpu.compute_capability.as_deref().and_then(cuda_major_minor);
+                match (have, cuda_major_minor(required)) {
+                    (Some(have), Some(required)) => have < required,
+                    _ => true,
+                }
+            };
+            if profile.gpus.is_empty() || profile.gpus.iter().any(below) {
+                unmet.push(format!("needs GPUs of compute capability {} or newer", required));
+            }
+        }
+        if self.nvlink && profile.gpu_link != GpuLink::NvLink {
+            unmet.push("needs GPUs linked by NVLink".to_string());
+        }
+        if let Some(required) = self.min_avx {
+            if profile.avx.is_none_or(|avx| avx < required) {
+                unmet.push(format!("needs a CPU with {}", required.label()));
+            }
+        }
+        if let Some(required) = self.min_memory_speed_mts {
+            if profile.memory_speed_mts.is_none_or(|speed| speed < required) {
+                unmet.push(format!("needs RAM of at least {} MT/s", required));
+            }
+        }
+        if self.nvme && !profile.nvme {
+            unmet.push("needs NVMe storage".to_string());
+        }
+        for accelerator in &self.accelerators {
+            if !profile.accelerators.contains(accelerator) {
+                unmet.push(format!("needs a {} accelerator", accelerator.label()));
+            }
+        }
+        unmet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hardware_constraints() {
+        let profile = HardwareProfile {
+            gpus: vec![GpuDevice { model: "NVIDIA A100-SXM4-80GB".to_string(), memory_mb: 81920, compute_capability: Some("8.0".to_string()) }; 2],
+            gpu_link: GpuLink::NvLink,
+            cpu_model: Some("AMD EPYC 7763 64-Core Processor".to_string()),
+            avx: Some(AvxLevel::from_cpu_flags("fpu sse2 avx avx2 fma")),
+            memory_speed_mts: Some(3200),
+            nvme: true,
+            accelerators: vec![],
+        };
+        assert_eq!(profile.summary(), "2× NVIDIA A100-SXM4-80GB (sm 8.0), NVLink, AVX2, RAM 3200 MT/s, NVMe");
+
+        let fits = HardwareConstraints {
+            gpu_models: vec!["a100".to_string(), "H100".to_string()],
+            min_compute_capability: Some("8.0".to_string()),
+            nvlink: true,
+            min_avx: Some(AvxLevel::Avx2),
+            nvme: true,
+            ..Default::default()
+        };
+        assert!(fits.unmet(&profile).is_empty());
+
+        let too_much = HardwareConstraints {
+            min_compute_capability: Some("9.0".to_string()),
+            min_avx: Some(AvxLevel::Avx512),
+            min_memory_speed_mts: Some(4800),
+            accelerators: vec![Accelerator::Fpga],
+            ..Default::default()
+        };
+        assert_eq!(too_much.unmet(&profile).len(), 4);
+        // Nothing reported satisfies nothing
+        assert_eq!(fits.unmet(&HardwareProfile::default()).len(), 5);
+    }
+}
@@ -0,0 +1,280 @@
+//! Job wire types
+//! A `JobSpec` describes one container the way the executor starts it with plain `docker`
+//! commands; a `JobSubmission` is what a client sends to have a job started on a node. A spec
+//! with a `SweepSpec` stands for a hyperparameter search, run as one child job per trial.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::hardware::HardwareConstraints;
+use crate::node::TrialOffer;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BuildSpec {
+    pub context: String,
+    pub dockerfile: Option<String>,
+}
+
+/// Scratch disk media, matched to one of the node's storage pools
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageClass {
+    Hdd,
+    Ssd,
+    Nvme,
+}
+
+/// Dedicated scratch space for a job, mounted at `/scratch` and billed on its own meter
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScratchRequest {
+    pub size_gb: u32,
+    pub class: StorageClass,
+}
+
+/// Data the node downloads into the job's workspace before the container starts
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JobInput {
+    pub source: String, // s3://bucket/key, gs://bucket/object or an https:// URL
+    pub target: String, // Relative path under /workspace
+    #[serde(default)]
+    pub credentials: Option<SealedCredentials>, // None for public sources
+}
+
+/// Results the node uploads to the client's own bucket once the job succeeds
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JobOutput {
+    pub source: String, // Relative path under /workspace, a file or a directory
+    pub target: String, // s3://bucket/prefix or gs://bucket/prefix
+    #[serde(default)]
+    pub credentials: Option<SealedCredentials>,
+}
+
+/// Bucket credentials sealed by the client with a key derived from the job's access token
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SealedCredentials {
+    pub sealed: String,  // Nonce and ciphertext, base64
+    pub expires_at: u64, // Unix seconds; the node refuses to open them afterwards
+}
+
+/// Values one sweep parameter takes
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ParameterSpace {
+    Grid { values: Vec<serde_json::Value> },   // Every value, crossed with the other grids
+    Choice { values: Vec<serde_json::Value> }, // One value at random per trial
+    Uniform { min: f64, max: f64 },
+    LogUniform { min: f64, max: f64 },
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SweepGoal {
+    #[default]
+    Minimize,
+    Maximize,
+}
+
+/// Hyperparameter search over a spec; each trial gets its parameters as environment variables
+/// and as `${name}` in the command
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SweepSpec {
+    pub parameters: BTreeMap<String, ParameterSpace>,
+    pub metric: String, // Trials print "eryzaa-metric <name>=<value>" lines
+    #[serde(default)]
+    pub goal: SweepGoal,
+    #[serde(default)]
+    pub trials: Option<u32>, // Defaults to one per grid point
+    #[serde(default)]
+    pub budget: Option<f32>, // Most the whole sweep may cost, in AVAX
+    #[serde(default)]
+    pub max_trial_minutes: Option<u32>, // Longer trials are stopped
+    #[serde(default)]
+    pub seed: Option<u64>, // For reproducible random samples
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct JobSpec {
+    pub name: String,
+    pub image: Option<String>,
+    #[serde(default)]
+    pub build: Option<BuildSpec>, // Built locally when there is no image to pull
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub volumes: Vec<String>, // "source:target[:mode]", sources are absolute paths or named volumes
+    #[serde(default)]
+    pub ports: Vec<String>, // Published as given to `docker run -p`
+    #[serde(default)]
+    pub services: Vec<u16>, // Container ports the node publishes on host ports leased from its range
+    #[serde(default)]
+    pub gpus: Option<String>, // "all" or a GPU count
+    #[serde(default)]
+    pub vram_gb: Option<u32>, // GPU memory the job needs per GPU, checked before submission
+    #[serde(default)]
+    pub dataset_gb: Option<f32>, // Data the job fetches itself, on top of what its volumes hold
+    #[serde(default)]
+    pub dedicated_cores: Option<u32>, // Cores pinned to the job alone, from one NUMA node where possible
+    #[serde(default)]
+    pub devices: Vec<String>,
+    #[serde(default)]
+    pub cap_add: Vec<String>,
+    #[serde(default)]
+    pub security_opt: Vec<String>,
+    #[serde(default)]
+    pub privileged: bool,
+    #[serde(default)]
+    pub tty: bool,
+    #[serde(default)]
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub restart: Option<String>,
+    #[serde(default)]
+    pub network: Option<String>,
+    #[serde(default)]
+    pub scratch: Option<ScratchRequest>, // Provisioned by the node before the container starts
+    #[serde(default)]
+    pub inputs: Vec<JobInput>, // Staged by the node before the container starts
+    #[serde(default)]
+    pub outputs: Vec<JobOutput>, // Pushed by the node after the job succeeds
+    #[serde(default)]
+    pub sweep: Option<SweepSpec>, // Expanded into trials instead of running as one job
+    #[serde(default)]
+    pub hardware: Option<HardwareConstraints>, // Only nodes advertising this hardware take the job
+}
+
+impl JobSpec {
+    /// Image the container runs; locally built specs get an `eryzaa/` tag
+    pub fn image_tag(&self) -> String {
+        self.image
+            .clone()
+            .unwrap_or_else(|| format!("eryzaa/{}:local", self.name))
+    }
+
+    /// Arguments for `docker build`, if the spec is built locally
+    pub fn build_args(&self) -> Option<Vec<String>> {
+        let build = self.build.as_ref()?;
+        let mut args = vec!["build".to_string(), "-t".to_string(), self.image_tag()];
+        if let Some(dockerfile) = &build.dockerfile {
+            args.push("-f".to_string());
+            args.push(Path::new(&build.context).join(dockerfile).to_string_lossy().into_owned());
+        }
+        args.push(build.context.clone());
+        Some(args)
+    }
+
+    /// Arguments for `docker run`
+    pub fn run_args(&self) -> Vec<String> {
+        let mut args = vec!["run".to_string(), "-d".to_string(), "--name".to_string(), self.name.clone()];
+        let mut flag = |name: &str, value: &str| {
+            args.push(name.to_string());
+            args.push(value.to_string());
+        };
+
+        if let Some(hostname) = &self.hostname {
+            flag("--hostname", hostname);
+        }
+        for (key, value) in &self.env {
+            flag("-e", &format!("{}={}", key, value));
+        }
+        for volume in &self.volumes {
+            flag("-v", volume);
+        }
+        for port in &self.ports {
+            flag("-p", port);
+        }
+        if let Some(gpus) = &self.gpus {
+            flag("--gpus", gpus);
+        }
+        for device in &self.devices {
+            flag("--device", device);
+        }
+        for cap in &self.cap_add {
+            flag("--cap-add", cap);
+        }
+        for opt in &self.security_opt {
+            flag("--security-opt", opt);
+        }
+        if let Some(restart) = &self.restart {
+            flag("--restart", restart);
+        }
+        if let Some(network) = &self.network {
+            flag("--network", network);
+        }
+        if self.privileged {
+            args.push("--privileged".to_string());
+        }
+        if self.tty {
+            args.push("-it".to_string());
+        }
+
+        args.push(self.image_tag());
+        args.extend(self.command.iter().cloned());
+        args
+    }
+}
+
+/// A job to start on a node
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JobSubmission {
+    pub job_id: String,
+    pub client_id: String,
+    pub image: String,
+    pub command: Vec<String>,
+    pub node_id: Option<String>,
+    pub node_address: Option<String>,
+    pub ssh_user: Option<String>,
+    #[serde(default)]
+    pub trial: Option<TrialOffer>, // Terms of the node's free trial the client is taking, if any
+    #[serde(default)]
+    pub deposit: Option<DepositClaim>, // Deposit the client paid the node, for nodes that ask for one
+    #[serde(default)]
+    pub reputation: Option<ReputationAttestation>,
+}
+
+/// A client's word that it paid a node's deposit; the node checks the transfer on chain
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DepositClaim {
+    pub tx_ref: String, // Hash of the transfer to the node's payout wallet
+    pub amount: f32,    // AVAX
+}
+
+/// A client's reputation, vouched for by an issuer a node may choose to trust
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReputationAttestation {
+    pub client_id: String,
+    pub score: f32,        // 0-5
+    pub issued_at: u64,    // Unix seconds
+    pub issuer: String,    // Hex ed25519 public key
+    pub signature: String, // Hex ed25519 signature, see eryzaa-payments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_args() {
+        let spec = JobSpec {
+            name: "rental-server".to_string(),
+            build: Some(BuildSpec { context: "/srv/eryzaa".to_string(), dockerfile: Some("Dockerfile.dev".to_string()) }),
+            env: BTreeMap::from([("DEV_MODE".to_string(), "true".to_string())]),
+            ports: vec!["127.0.0.1:2222:22".to_string()],
+            gpus: Some("all".to_string()),
+            privileged: true,
+            ..Default::default()
+        };
+
+        assert_eq!(spec.build_args().unwrap(), vec!["build", "-t", "eryzaa/rental-server:local", "-f", "/srv/eryzaa/Dockerfile.dev", "/srv/eryzaa"]);
+        assert_eq!(spec.run_args(), vec![
+            "run", "-d", "--name", "rental-server",
+            "-e", "DEV_MODE=true",
+            "-p", "127.0.0.1:2222:22",
+            "--gpus", "all",
+            "--privileged",
+            "eryzaa/rental-server:local",
+        ]);
+    }
+}
@@ -0,0 +1,223 @@
+//! Wire types shared by every Eryzaa component
+//! Node advertisements, job specs and submissions, and control-plane events live here so the
+//! client, rental node, coordinator and SDK serialize them identically.
+
+pub mod abuse;
+pub mod details;
+pub mod event;
+pub mod hardware;
+pub mod job;
+pub mod market;
+pub mod node;
+
+pub use abuse::{AbuseCategory, AbuseReport, Blocklist, BlocklistEntry, Evidence};
+pub use details::NodeDetails;
+pub use event::{Envelope, Event};
+pub use hardware::{Accelerator, AvxLevel, GpuDevice, GpuLink, HardwareConstraints, HardwareProfile};
+pub use job::{BuildSpec, DepositClaim, JobInput, JobOutput, JobSpec, JobSubmission, ParameterSpace, ReputationAttestation, ScratchRequest, SealedCredentials, StorageClass, SweepGoal, SweepSpec};
+pub use market::MarketStats;
+pub use node::{AccessPolicy, Check, GpuStack, NodeAdvertisement, NodeCapabilities, NodeStatus, NodeType, Readiness, TrialOffer};
+
+/// Version of the wire format this build speaks; bump it on any incompatible change
+pub const PROTOCOL_VERSION: u32 = 11;
+
+/// Oldest version this build still reads
+pub const MIN_SUPPORTED_VERSION: u32 = 1;
+
+pub fn is_compatible(version: u32) -> bool {
+    (MIN_SUPPORTED_VERSION..=PROTOCOL_VERSION).contains(&version)
+}
+
+/// Every fixture written by an earlier release must still be read the same way. When a change
+/// breaks one, bump `PROTOCOL_VERSION` and add fixtures for the new version instead of editing.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+    use serde_json::Value;
+
+    /// Whether every field of `old` is still present in `new` with the same value
+    fn keeps_fields(old: &Value, new: &Value) -> bool {
+        match (old, new) {
+            (Value::Object(old), Value::Object(new)) => old
+                .iter()
+                .all(|(key, value)| new.get(key).map(|v| keeps_fields(value, v)).unwrap_or(false)),
+            (Value::Array(old), Value::Array(new)) => {
+                old.len() == new.len() && old.iter().zip(new).all(|(o, n)| keeps_fields(o, n))
+            }
+            _ => old == new,
+        }
+    }
+
+    fn assert_reads<T: DeserializeOwned + Serialize>(fixture: &str) -> T {
+        let parsed: T = serde_json::from_str(fixture).expect("fixture no longer parses");
+        let old: Value = serde_json::from_str(fixture).unwrap();
+        assert!(keeps_fields(&old, &serde_json::to_value(&parsed).unwrap()), "fixture fields changed");
+        parsed
+    }
+
+    fn hex_packet(hex: &str) -> Vec<u8> {
+        hex.trim()
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_v1_fixtures() {
+        assert_reads::<JobSpec>(include_str!("../fixtures/v1/job_spec.json"));
+        assert_reads::<JobSubmission>(include_str!("../fixtures/v1/job_submission.json"));
+        let envelopes: Vec<Envelope> = assert_reads(include_str!("../fixtures/v1/envelopes.json"));
+        assert_eq!(envelopes[3].event.topic(), "scheduler.started");
+
+        // v1 discovery packets from older nodes still decode, with no clock fields
+        let advertisement: NodeAdvertisement = assert_reads(include_str!("../fixtures/v1/node_advertisement.json"));
+        let decoded = node::decode_advertisement(&hex_packet(include_str!("../fixtures/v1/node_advertisement.hex"))).unwrap();
+        assert_eq!(decoded.slots(), Some((3, 4)));
+        assert_eq!(decoded.sent_at_ms, advertisement.timestamp * 1000);
+        assert_eq!(decoded.clock_offset_ms, None);
+    }
+
+    #[test]
+    fn test_v2_fixtures() {
+        // v2 discovery packets still decode, with no GPU stack
+        let advertisement: NodeAdvertisement = assert_reads(include_str!("../fixtures/v2/node_advertisement.json"));
+        let decoded = node::decode_advertisement(&hex_packet(include_str!("../fixtures/v2/node_advertisement.hex"))).unwrap();
+        assert_eq!(decoded.sent_at_ms, advertisement.sent_at_ms);
+        assert_eq!(decoded.clock_offset_ms, Some(-40));
+        assert_eq!(decoded.capabilities.gpu_stack, None);
+    }
+
+    #[test]
+    fn test_v3_fixtures() {
+        // v3 discovery packets still decode, with no warm pool
+        let advertisement: NodeAdvertisement = assert_reads(include_str!("../fixtures/v3/node_advertisement.json"));
+        let decoded = node::decode_advertisement(&hex_packet(include_str!("../fixtures/v3/node_advertisement.hex"))).unwrap();
+        assert_eq!(decoded.capabilities.gpu_stack, advertisement.capabilities.gpu_stack);
+        assert!(decoded.warm_images.is_empty());
+        let stack = decoded.capabilities.gpu_stack.unwrap();
+        assert_eq!(stack.cuda_incompatibility("12.1.0"), None);
+        assert_eq!(stack.cuda_incompatibility("12.4").unwrap(), "needs CUDA 12.4, but NVIDIA driver 535.104.05 only supports up to CUDA 12.2");
+    }
+
+    #[test]
+    fn test_v4_fixtures() {
+        // v4 discovery packets still decode, with no market fields
+        let advertisement: NodeAdvertisement = assert_reads(include_str!("../fixtures/v4/node_advertisement.json"));
+        let decoded = node::decode_advertisement(&hex_packet(include_str!("../fixtures/v4/node_advertisement.hex"))).unwrap();
+        assert_eq!(decoded.warm_images, advertisement.warm_images);
+        assert!(decoded.warm_start("pytorch/pytorch:2.1.0-cuda12.1-cudnn8-runtime"));
+        assert_eq!((decoded.gpu_model, decoded.price_per_hour), (None, None));
+    }
+
+    #[test]
+    fn test_v5_fixtures() {
+        // v5 discovery packets still decode, open to every client
+        let advertisement: NodeAdvertisement = assert_reads(include_str!("../fixtures/v5/node_advertisement.json"));
+        let decoded = node::decode_advertisement(&hex_packet(include_str!("../fixtures/v5/node_advertisement.hex"))).unwrap();
+        assert_eq!(decoded.price_per_hour, advertisement.price_per_hour);
+        assert_eq!(decoded.price_per_hour, Some(2.5));
+        assert!(decoded.access.is_open());
+    }
+
+    #[test]
+    fn test_v6_fixtures() {
+        // v6 discovery packets still decode, with readiness unchecked
+        let advertisement: NodeAdvertisement = assert_reads(include_str!("../fixtures/v6/node_advertisement.json"));
+        let packet = hex_packet(include_str!("../fixtures/v6/node_advertisement.hex"));
+        let decoded = node::decode_advertisement(&packet).unwrap();
+        assert_eq!(decoded.access, advertisement.access);
+        assert_eq!(decoded.readiness, Readiness::default());
+        assert!(decoded.readiness.is_functional(true));
+        let access = decoded.access;
+        assert_eq!(access.deposit_required(Some(4.5)), Ok(None));
+        assert_eq!(access.deposit_required(None), Ok(Some(5.0)));
+
+        let reputation_only = AccessPolicy { deposit: None, ..access };
+        assert!(reputation_only.deposit_required(Some(3.2)).unwrap_err().contains("3.2"));
+    }
+
+    #[test]
+    fn test_v7_fixtures() {
+        // v7 discovery packets still decode, with no hardware profile
+        let advertisement: NodeAdvertisement = assert_reads(include_str!("../fixtures/v7/node_advertisement.json"));
+        let packet = hex_packet(include_str!("../fixtures/v7/node_advertisement.hex"));
+        let decoded = node::decode_advertisement(&packet).unwrap();
+        assert_eq!(decoded.capabilities.gpu_stack, advertisement.capabilities.gpu_stack);
+        assert_eq!(decoded.capabilities.hardware, HardwareProfile::default());
+        let readiness = decoded.readiness;
+        assert_eq!(readiness.problems(), vec!["gpu: NVIDIA container runtime is not registered with Docker"]);
+        assert!(readiness.is_functional(false));
+        assert!(!readiness.is_functional(true));
+    }
+
+    #[test]
+    fn test_v8_fixtures() {
+        // v8 discovery packets still decode, with no free trial
+        let advertisement: NodeAdvertisement = assert_reads(include_str!("../fixtures/v8/node_advertisement.json"));
+        let packet = hex_packet(include_str!("../fixtures/v8/node_advertisement.hex"));
+        let decoded = node::decode_advertisement(&packet).unwrap();
+        assert_eq!(decoded.readiness, advertisement.readiness);
+        assert_eq!(decoded.trial, None);
+        let hardware = decoded.capabilities.hardware;
+        assert_eq!(hardware.gpu_link, GpuLink::NvLink);
+        let constraints = HardwareConstraints { gpu_models: vec!["RTX 3090".to_string()], nvlink: true, ..Default::default() };
+        assert!(constraints.unmet(&hardware).is_empty());
+    }
+
+    #[test]
+    fn test_v9_fixtures() {
+        // v9 discovery packets still decode, unsigned
+        let advertisement: NodeAdvertisement = assert_reads(include_str!("../fixtures/v9/node_advertisement.json"));
+        let packet = hex_packet(include_str!("../fixtures/v9/node_advertisement.hex"));
+        let decoded = node::decode_advertisement(&packet).unwrap();
+        assert_eq!(decoded.trial, advertisement.trial);
+        assert_eq!((decoded.public_key, decoded.signature), (None, None));
+        let trial = decoded.trial.unwrap();
+        assert_eq!(trial.label(), "Free 15-minute trial (25% CPU, no GPU)");
+        assert_eq!(trial.cpus(16), 4.0);
+        assert_eq!(trial.cpus(2), 0.5);
+        assert!(TrialOffer { minutes: TrialOffer::MAX_MINUTES + 1, ..trial }.validate().is_err());
+    }
+
+    #[test]
+    fn test_v10_fixtures() {
+        // v10 discovery packets still decode, with no self-test, and their signatures still
+        // cover the bytes as the node sent them
+        let advertisement: NodeAdvertisement = assert_reads(include_str!("../fixtures/v10/node_advertisement.json"));
+        let packet = hex_packet(include_str!("../fixtures/v10/node_advertisement.hex"));
+        let decoded = node::decode_advertisement(&packet).unwrap();
+        assert_eq!(decoded.signature, advertisement.signature);
+        assert_eq!(decoded.readiness.self_test, Check::Unknown);
+        let signed = node::signed_body(&packet).unwrap().unwrap();
+        // The body with the signature's length and 128 hex digits gone and its tag turned to None
+        assert_eq!(signed.len(), packet.len() - 4 - 8 - 128);
+        assert_ne!(signed, decoded.signed_bytes().unwrap());
+    }
+
+    #[test]
+    fn test_v11_fixtures() {
+        // Discovery packets are bincode, so the bytes must match exactly
+        let advertisement: NodeAdvertisement = assert_reads(include_str!("../fixtures/v11/node_advertisement.json"));
+        let packet = hex_packet(include_str!("../fixtures/v11/node_advertisement.hex"));
+        assert_eq!(node::encode_advertisement(&advertisement).unwrap(), packet);
+        let decoded = node::decode_advertisement(&packet).unwrap();
+        assert_eq!(decoded.status, NodeStatus::Maintenance);
+        assert_eq!(decoded.readiness.problems(), vec!["self-test: container: hello-world exited with status 125"]);
+        assert!(!decoded.readiness.is_functional(false));
+        // The signature covers everything else, the key included
+        let signed = decoded.signed_bytes().unwrap();
+        assert_eq!(node::signed_body(&packet).unwrap(), Some(signed.clone()));
+        assert_eq!(signed, NodeAdvertisement { signature: None, ..decoded.clone() }.signed_bytes().unwrap());
+        assert_ne!(signed, NodeAdvertisement { public_key: None, ..decoded.clone() }.signed_bytes().unwrap());
+        let unsigned = NodeAdvertisement { public_key: None, signature: None, ..decoded };
+        assert_eq!(node::signed_body(&node::encode_advertisement(&unsigned).unwrap()), Ok(None));
+
+        let mut future = packet.clone();
+        future[..4].copy_from_slice(&(PROTOCOL_VERSION + 1).to_le_bytes());
+        assert!(node::decode_advertisement(&future).is_err());
+    }
+
+}
@@ -0,0 +1,117 @@
+//! Marketplace statistics
+//! What a coordinator serves about the rental nodes it can see: how many there are, which GPUs
+//! they offer, what they ask and how busy they are. Renters use it to price their hardware and
+//! clients to judge a bid.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::node::{NodeAdvertisement, NodeStatus, NodeType};
+
+/// Model key for GPU nodes that do not report one
+pub const UNKNOWN_MODEL: &str = "unknown";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct MarketStats {
+    pub generated_at: u64, // Unix seconds
+    pub total_nodes: u32,
+    pub available_nodes: u32,
+    pub total_gpus: u32,
+    pub gpus_by_model: BTreeMap<String, u32>,
+    pub median_price_per_hour: Option<f32>, // Over nodes that advertise a price
+    pub median_price_by_model: BTreeMap<String, f32>,
+    pub utilization: Option<f32>, // Share of advertised job slots in use, 0-1
+}
+
+impl MarketStats {
+    /// Aggregate the rental nodes among `nodes`
+    pub fn from_nodes<'a>(nodes: impl IntoIterator<Item = &'a NodeAdvertisement>, generated_at: u64) -> Self {
+        let mut stats = Self { generated_at, ..Default::default() };
+        let mut prices = vec![];
+        let mut prices_by_model: BTreeMap<String, Vec<f32>> = BTreeMap::new();
+        let (mut used_slots, mut total_slots) = (0, 0);
+
+        for node in nodes.into_iter().filter(|node| node.node_type == NodeType::Rental) {
+            stats.total_nodes += 1;
+            if node.status == NodeStatus::Available {
+                stats.available_nodes += 1;
+            }
+
+            let model = node.gpu_model.clone().unwrap_or_else(|| UNKNOWN_MODEL.to_string());
+            if node.capabilities.gpu_count > 0 {
+                stats.total_gpus += node.capabilities.gpu_count;
+                *stats.gpus_by_model.entry(model.clone()).or_default() += node.capabilities.gpu_count;
+            }
+            if let Some(price) = node.price_per_hour {
+                prices.push(price);
+                prices_by_model.entry(model).or_default().push(price);
+            }
+            if let Some((free, max)) = node.slots() {
+                used_slots += max.saturating_sub(free);
+                total_slots += max;
+            }
+        }
+
+        stats.median_price_per_hour = median(&mut prices);
+        stats.median_price_by_model = prices_by_model
+            .into_iter()
+            .filter_map(|(model, mut prices)| Some((model, median(&mut prices)?)))
+            .collect();
+        stats.utilization = (total_slots > 0).then(|| used_slots as f32 / total_slots as f32);
+        stats
+    }
+
+    /// Typical asking price for a GPU model, falling back to the whole network
+    pub fn price_hint(&self, gpu_model: Option<&str>) -> Option<f32> {
+        gpu_model
+            .and_then(|model| self.median_price_by_model.get(model).copied())
+            .or(self.median_price_per_hour)
+    }
+}
+
+fn median(values: &mut [f32]) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f32::total_cmp);
+    let mid = values.len() / 2;
+    Some(match values.len() % 2 {
+        0 => (values[mid - 1] + values[mid]) / 2.0,
+        _ => values[mid],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_market_stats() {
+        let advertisement: NodeAdvertisement = serde_json::from_str(include_str!("../fixtures/v5/node_advertisement.json")).unwrap();
+        let node = |id: &str, model: Option<&str>, price: Option<f32>, free: u32| NodeAdvertisement {
+            node_id: id.to_string(),
+            gpu_model: model.map(|m| m.to_string()),
+            price_per_hour: price,
+            free_slots: Some(free),
+            ..advertisement.clone()
+        };
+        let mut client = node("client", None, Some(99.0), 0);
+        client.node_type = NodeType::Client;
+        let nodes = [
+            node("a", Some("RTX 3090"), Some(2.0), 4),
+            node("b", Some("RTX 3090"), Some(3.0), 2),
+            node("c", Some("A100"), Some(8.0), 0),
+            node("d", None, None, 4),
+            client,
+        ];
+
+        let stats = MarketStats::from_nodes(&nodes, 1_760_000_000);
+        assert_eq!((stats.total_nodes, stats.total_gpus), (4, 8));
+        assert_eq!(stats.gpus_by_model.get("RTX 3090"), Some(&4));
+        assert_eq!(stats.gpus_by_model.get(UNKNOWN_MODEL), Some(&2));
+        assert_eq!(stats.median_price_per_hour, Some(3.0));
+        assert_eq!(stats.price_hint(Some("RTX 3090")), Some(2.5));
+        assert_eq!(stats.price_hint(Some("H100")), Some(3.0));
+        assert_eq!(stats.utilization, Some(6.0 / 16.0));
+    }
+}
@@ -0,0 +1,1015 @@
+//! Node advertisements
+//! What a node multicasts on the discovery port. Packets are bincode, which has no field
+//! names or defaults, so each one is prefixed with the sender's protocol version.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::details::NodeDetails;
+use crate::hardware::HardwareProfile;
+use crate::{is_compatible, PROTOCOL_VERSION};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeAdvertisement {
+    pub node_id: String,
+    pub node_type: NodeType,
+    pub ip_address: String,
+    pub zerotier_ip: Option<String>,
+    pub ssh_port: u16,
+    pub api_port: u16,
+    pub capabilities: NodeCapabilities,
+    pub status: NodeStatus,
+    pub timestamp: u64,
+    pub network_id: String, // ZeroTier network ID
+    #[serde(default)]
+    pub carbon_intensity_g_per_kwh: Option<f32>, // Grid carbon intensity set by the node's renter
+    #[serde(default)]
+    pub avg_job_kwh: Option<f32>, // Average energy used by the node's jobs
+    #[serde(default)]
+    pub details: NodeDetails, // Description and notes written by the node's owner
+    #[serde(default)]
+    pub free_slots: Option<u32>, // Jobs the node can still start, out of max_concurrent_jobs
+    #[serde(default)]
+    pub sent_at_ms: u64, // Sender's wall clock when the packet left, for clock offset estimates
+    #[serde(default)]
+    pub clock_offset_ms: Option<i64>, // Sender's own offset from NTP time, positive when ahead
+    #[serde(default)]
+    pub warm_images: Vec<String>, // Images with an idle container ready, for near-instant starts
+    #[serde(default)]
+    pub gpu_model: Option<String>, // e.g. "NVIDIA GeForce RTX 3090", for market statistics
+    #[serde(default)]
+    pub price_per_hour: Option<f32>, // Renter's asking price in AVAX
+    #[serde(default)]
+    pub access: AccessPolicy, // Reputation or deposit a client needs before its jobs run
+    #[serde(default)]
+    pub readiness: Readiness, // Which parts of the rental stack last worked
+    #[serde(default)]
+    pub trial: Option<TrialOffer>, // Free session each client may take once, None if not offered
+    #[serde(default)]
+    pub public_key: Option<String>, // Hex ed25519 key the node signs its advertisements with
+    #[serde(default)]
+    pub signature: Option<String>, // Hex ed25519 signature over `signed_bytes`
+}
+
+/// What a node asks of clients before their jobs run: enough reputation or, failing that, a
+/// refundable deposit. With neither set the node is open to everyone.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AccessPolicy {
+    pub min_reputation: Option<f32>, // 0-5; clients without history count as below
+    pub deposit: Option<f32>,        // AVAX held for each job, refunded when it ends
+}
+
+impl AccessPolicy {
+    pub fn is_open(&self) -> bool {
+        self.min_reputation.is_none() && self.deposit.is_none()
+    }
+
+    /// Deposit a client with `reputation` has to post, or why it cannot get in at all
+    pub fn deposit_required(&self, reputation: Option<f32>) -> Result<Option<f32>, String> {
+        let reputable = match self.min_reputation {
+            Some(min) => reputation.is_some_and(|r| r >= min),
+            None => self.deposit.is_none(),
+        };
+        match (reputable, self.deposit) {
+            (true, _) => Ok(None),
+            (false, Some(deposit)) => Ok(Some(deposit)),
+            (false, None) => Err(format!(
+                "Node requires a reputation of {:.1}, client has {}",
+                self.min_reputation.unwrap_or(0.0),
+                reputation.map(|r| format!("{:.1}", r)).unwrap_or_else(|| "no history".to_string())
+            )),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize access policy: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write access policy {}: {}", path.display(), e))
+    }
+}
+
+/// A short free session a node gives each client once, to check latency and the environment
+/// before paying. Trials run on a share of the CPU and never get a GPU.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct TrialOffer {
+    pub minutes: u32,
+    pub cpu_percent: u32, // Of the node's cores the trial container may use
+}
+
+impl Default for TrialOffer {
+    fn default() -> Self {
+        Self { minutes: 15, cpu_percent: 25 }
+    }
+}
+
+impl TrialOffer {
+    /// Longest trial a node may offer
+    pub const MAX_MINUTES: u32 = 60;
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=Self::MAX_MINUTES).contains(&self.minutes) {
+            return Err(format!("Free trials last 1 to {} minutes", Self::MAX_MINUTES));
+        }
+        if !(1..=100).contains(&self.cpu_percent) {
+            return Err("Free trials get 1 to 100% of the CPU".to_string());
+        }
+        Ok(())
+    }
+
+    /// CPUs for `docker update --cpus` on a node with `cpu_cores`, at least a tenth of one
+    pub fn cpus(&self, cpu_cores: u32) -> f32 {
+        (cpu_cores as f32 * self.cpu_percent.min(100) as f32 / 100.0).max(0.1)
+    }
+
+    pub fn label(&self) -> String {
+        format!("Free {}-minute trial ({}% CPU, no GPU)", self.minutes, self.cpu_percent)
+    }
+
+    /// The offer saved at `path`, None when the node offers no trials
+    pub fn load_from(path: &Path) -> Option<Self> {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Self>(&content).ok())
+            .filter(|offer| offer.validate().is_ok())
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        self.validate()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize trial offer: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write trial offer {}: {}", path.display(), e))
+    }
+
+    /// Stop offering trials
+    pub fn withdraw(path: &Path) -> Result<(), String> {
+        match std::fs::remove_file(path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("Failed to remove trial offer {}: {}", path.display(), e)),
+        }
+    }
+}
+
+/// Outcome of one readiness check
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub enum Check {
+    #[default]
+    Unknown, // Not checked, e.g. by nodes before protocol v7
+    Ok,
+    Failed(String), // What went wrong, for the client to show
+}
+
+impl Check {
+    pub fn is_failed(&self) -> bool {
+        matches!(self, Check::Failed(_))
+    }
+}
+
+/// What a rental node found working when it last checked its stack
+///
+/// "Available" only says the node takes jobs; this says whether a job would actually run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Readiness {
+    pub overlay: Check, // ZeroTier joined and online
+    pub sshd: Check,
+    pub docker: Check,
+    pub gpu: Check,      // Driver answers and Docker has the NVIDIA runtime; Unknown without GPUs
+    pub payments: Check, // A payout wallet is set
+    pub checked_at: u64, // Unix seconds, 0 if never checked
+    #[serde(default)]
+    pub self_test: Check, // Startup self-test, failed with every step that failed; Unknown before v11
+}
+
+impl Readiness {
+    /// Each check by name
+    pub fn checks(&self) -> [(&'static str, &Check); 6] {
+        [
+            ("overlay", &self.overlay),
+            ("sshd", &self.sshd),
+            ("docker", &self.docker),
+            ("gpu", &self.gpu),
+            ("payments", &self.payments),
+            ("self-test", &self.self_test),
+        ]
+    }
+
+    /// Failed checks as "name: reason"
+    pub fn problems(&self) -> Vec<String> {
+        self.checks()
+            .into_iter()
+            .filter_map(|(name, check)| match check {
+                Check::Failed(reason) => Some(format!("{}: {}", name, reason)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Whether nothing a job needs has failed; the GPU only counts when the job needs one
+    ///
+    /// Unchecked parts are given the benefit of the doubt, so older nodes are not filtered out.
+    pub fn is_functional(&self, needs_gpu: bool) -> bool {
+        self.checks()
+            .into_iter()
+            .filter(|(name, _)| needs_gpu || *name != "gpu")
+            .all(|(_, check)| !check.is_failed())
+    }
+}
+
+/// Advertisement as protocol v1 sent it, before the clock fields
+#[derive(Deserialize)]
+struct AdvertisementV1 {
+    node_id: String,
+    node_type: NodeType,
+    ip_address: String,
+    zerotier_ip: Option<String>,
+    ssh_port: u16,
+    api_port: u16,
+    capabilities: CapabilitiesV2,
+    status: NodeStatus,
+    timestamp: u64,
+    network_id: String,
+    carbon_intensity_g_per_kwh: Option<f32>,
+    avg_job_kwh: Option<f32>,
+    details: NodeDetails,
+    free_slots: Option<u32>,
+}
+
+impl From<AdvertisementV1> for NodeAdvertisement {
+    fn from(v1: AdvertisementV1) -> Self {
+        Self {
+            node_id: v1.node_id,
+            node_type: v1.node_type,
+            ip_address: v1.ip_address,
+            zerotier_ip: v1.zerotier_ip,
+            ssh_port: v1.ssh_port,
+            api_port: v1.api_port,
+            capabilities: v1.capabilities.into(),
+            status: v1.status,
+            timestamp: v1.timestamp,
+            network_id: v1.network_id,
+            carbon_intensity_g_per_kwh: v1.carbon_intensity_g_per_kwh,
+            avg_job_kwh: v1.avg_job_kwh,
+            details: v1.details,
+            free_slots: v1.free_slots,
+            sent_at_ms: v1.timestamp * 1000,
+            clock_offset_ms: None,
+            warm_images: vec![],
+            gpu_model: None,
+            price_per_hour: None,
+            access: AccessPolicy::default(),
+            readiness: Readiness::default(),
+            trial: None,
+            public_key: None,
+            signature: None,
+        }
+    }
+}
+
+/// Advertisement as protocol v2 sent it, before the GPU stack
+#[derive(Deserialize)]
+struct AdvertisementV2 {
+    node_id: String,
+    node_type: NodeType,
+    ip_address: String,
+    zerotier_ip: Option<String>,
+    ssh_port: u16,
+    api_port: u16,
+    capabilities: CapabilitiesV2,
+    status: NodeStatus,
+    timestamp: u64,
+    network_id: String,
+    carbon_intensity_g_per_kwh: Option<f32>,
+    avg_job_kwh: Option<f32>,
+    details: NodeDetails,
+    free_slots: Option<u32>,
+    sent_at_ms: u64,
+    clock_offset_ms: Option<i64>,
+}
+
+impl From<AdvertisementV2> for NodeAdvertisement {
+    fn from(v2: AdvertisementV2) -> Self {
+        Self {
+            node_id: v2.node_id,
+            node_type: v2.node_type,
+            ip_address: v2.ip_address,
+            zerotier_ip: v2.zerotier_ip,
+            ssh_port: v2.ssh_port,
+            api_port: v2.api_port,
+            capabilities: v2.capabilities.into(),
+            status: v2.status,
+            timestamp: v2.timestamp,
+            network_id: v2.network_id,
+            carbon_intensity_g_per_kwh: v2.carbon_intensity_g_per_kwh,
+            avg_job_kwh: v2.avg_job_kwh,
+            details: v2.details,
+            free_slots: v2.free_slots,
+            sent_at_ms: v2.sent_at_ms,
+            clock_offset_ms: v2.clock_offset_ms,
+            warm_images: vec![],
+            gpu_model: None,
+            price_per_hour: None,
+            access: AccessPolicy::default(),
+            readiness: Readiness::default(),
+            trial: None,
+            public_key: None,
+            signature: None,
+        }
+    }
+}
+
+/// Advertisement as protocol v3 sent it, before the warm pool
+#[derive(Deserialize)]
+struct AdvertisementV3 {
+    node_id: String,
+    node_type: NodeType,
+    ip_address: String,
+    zerotier_ip: Option<String>,
+    ssh_port: u16,
+    api_port: u16,
+    capabilities: CapabilitiesV7,
+    status: NodeStatus,
+    timestamp: u64,
+    network_id: String,
+    carbon_intensity_g_per_kwh: Option<f32>,
+    avg_job_kwh: Option<f32>,
+    details: NodeDetails,
+    free_slots: Option<u32>,
+    sent_at_ms: u64,
+    clock_offset_ms: Option<i64>,
+}
+
+impl From<AdvertisementV3> for NodeAdvertisement {
+    fn from(v3: AdvertisementV3) -> Self {
+        Self {
+            node_id: v3.node_id,
+            node_type: v3.node_type,
+            ip_address: v3.ip_address,
+            zerotier_ip: v3.zerotier_ip,
+            ssh_port: v3.ssh_port,
+            api_port: v3.api_port,
+            capabilities: v3.capabilities.into(),
+            status: v3.status,
+            timestamp: v3.timestamp,
+            network_id: v3.network_id,
+            carbon_intensity_g_per_kwh: v3.carbon_intensity_g_per_kwh,
+            avg_job_kwh: v3.avg_job_kwh,
+            details: v3.details,
+            free_slots: v3.free_slots,
+            sent_at_ms: v3.sent_at_ms,
+            clock_offset_ms: v3.clock_offset_ms,
+            warm_images: vec![],
+            gpu_model: None,
+            price_per_hour: None,
+            access: AccessPolicy::default(),
+            readiness: Readiness::default(),
+            trial: None,
+            public_key: None,
+            signature: None,
+        }
+    }
+}
+
+/// Advertisement as protocol v4 sent it, before the market fields
+#[derive(Deserialize)]
+struct AdvertisementV4 {
+    node_id: String,
+    node_type: NodeType,
+    ip_address: String,
+    zerotier_ip: Option<String>,
+    ssh_port: u16,
+    api_port: u16,
+    capabilities: CapabilitiesV7,
+    status: NodeStatus,
+    timestamp: u64,
+    network_id: String,
+    carbon_intensity_g_per_kwh: Option<f32>,
+    avg_job_kwh: Option<f32>,
+    details: NodeDetails,
+    free_slots: Option<u32>,
+    sent_at_ms: u64,
+    clock_offset_ms: Option<i64>,
+    warm_images: Vec<String>,
+}
+
+impl From<AdvertisementV4> for NodeAdvertisement {
+    fn from(v4: AdvertisementV4) -> Self {
+        Self {
+            node_id: v4.node_id,
+            node_type: v4.node_type,
+            ip_address: v4.ip_address,
+            zerotier_ip: v4.zerotier_ip,
+            ssh_port: v4.ssh_port,
+            api_port: v4.api_port,
+            capabilities: v4.capabilities.into(),
+            status: v4.status,
+            timestamp: v4.timestamp,
+            network_id: v4.network_id,
+            carbon_intensity_g_per_kwh: v4.carbon_intensity_g_per_kwh,
+            avg_job_kwh: v4.avg_job_kwh,
+            details: v4.details,
+            free_slots: v4.free_slots,
+            sent_at_ms: v4.sent_at_ms,
+            clock_offset_ms: v4.clock_offset_ms,
+            warm_images: v4.warm_images,
+            gpu_model: None,
+            price_per_hour: None,
+            access: AccessPolicy::default(),
+            readiness: Readiness::default(),
+            trial: None,
+            public_key: None,
+            signature: None,
+        }
+    }
+}
+
+/// Advertisement as protocol v5 sent it, before the access policy
+#[derive(Deserialize)]
+struct AdvertisementV5 {
+    node_id: String,
+    node_type: NodeType,
+    ip_address: String,
+    zerotier_ip: Option<String>,
+    ssh_port: u16,
+    api_port: u16,
+    capabilities: CapabilitiesV7,
+    status: NodeStatus,
+    timestamp: u64,
+    network_id: String,
+    carbon_intensity_g_per_kwh: Option<f32>,
+    avg_job_kwh: Option<f32>,
+    details: NodeDetails,
+    free_slots: Option<u32>,
+    sent_at_ms: u64,
+    clock_offset_ms: Option<i64>,
+    warm_images: Vec<String>,
+    gpu_model: Option<String>,
+    price_per_hour: Option<f32>,
+}
+
+impl From<AdvertisementV5> for NodeAdvertisement {
+    fn from(v5: AdvertisementV5) -> Self {
+        Self {
+            node_id: v5.node_id,
+            node_type: v5.node_type,
+            ip_address: v5.ip_address,
+            zerotier_ip: v5.zerotier_ip,
+            ssh_port: v5.ssh_port,
+            api_port: v5.api_port,
+            capabilities: v5.capabilities.into(),
+            status: v5.status,
+            timestamp: v5.timestamp,
+            network_id: v5.network_id,
+            carbon_intensity_g_per_kwh: v5.carbon_intensity_g_per_kwh,
+            avg_job_kwh: v5.avg_job_kwh,
+            details: v5.details,
+            free_slots: v5.free_slots,
+            sent_at_ms: v5.sent_at_ms,
+            clock_offset_ms: v5.clock_offset_ms,
+            warm_images: v5.warm_images,
+            gpu_model: v5.gpu_model,
+            price_per_hour: v5.price_per_hour,
+            access: AccessPolicy::default(),
+            readiness: Readiness::default(),
+            trial: None,
+            public_key: None,
+            signature: None,
+        }
+    }
+}
+
+/// Advertisement as protocol v6 sent it, before readiness
+#[derive(Deserialize)]
+struct AdvertisementV6 {
+    node_id: String,
+    node_type: NodeType,
+    ip_address: String,
+    zerotier_ip: Option<String>,
+    ssh_port: u16,
+    api_port: u16,
+    capabilities: CapabilitiesV7,
+    status: NodeStatus,
+    timestamp: u64,
+    network_id: String,
+    carbon_intensity_g_per_kwh: Option<f32>,
+    avg_job_kwh: Option<f32>,
+    details: NodeDetails,
+    free_slots: Option<u32>,
+    sent_at_ms: u64,
+    clock_offset_ms: Option<i64>,
+    warm_images: Vec<String>,
+    gpu_model: Option<String>,
+    price_per_hour: Option<f32>,
+    access: AccessPolicy,
+}
+
+impl From<AdvertisementV6> for NodeAdvertisement {
+    fn from(v6: AdvertisementV6) -> Self {
+        Self {
+            node_id: v6.node_id,
+            node_type: v6.node_type,
+            ip_address: v6.ip_address,
+            zerotier_ip: v6.zerotier_ip,
+            ssh_port: v6.ssh_port,
+            api_port: v6.api_port,
+            capabilities: v6.capabilities.into(),
+            status: v6.status,
+            timestamp: v6.timestamp,
+            network_id: v6.network_id,
+            carbon_intensity_g_per_kwh: v6.carbon_intensity_g_per_kwh,
+            avg_job_kwh: v6.avg_job_kwh,
+            details: v6.details,
+            free_slots: v6.free_slots,
+            sent_at_ms: v6.sent_at_ms,
+            clock_offset_ms: v6.clock_offset_ms,
+            warm_images: v6.warm_images,
+            gpu_model: v6.gpu_model,
+            price_per_hour: v6.price_per_hour,
+            access: v6.access,
+            readiness: Readiness::default(),
+            trial: None,
+            public_key: None,
+            signature: None,
+        }
+    }
+}
+
+/// Advertisement as protocol v7 sent it, before the hardware profile
+#[derive(Deserialize)]
+struct AdvertisementV7 {
+    node_id: String,
+    node_type: NodeType,
+    ip_address: String,
+    zerotier_ip: Option<String>,
+    ssh_port: u16,
+    api_port: u16,
+    capabilities: CapabilitiesV7,
+    status: NodeStatus,
+    timestamp: u64,
+    network_id: String,
+    carbon_intensity_g_per_kwh: Option<f32>,
+    avg_job_kwh: Option<f32>,
+    details: NodeDetails,
+    free_slots: Option<u32>,
+    sent_at_ms: u64,
+    clock_offset_ms: Option<i64>,
+    warm_images: Vec<String>,
+    gpu_model: Option<String>,
+    price_per_hour: Option<f32>,
+    access: AccessPolicy,
+    readiness: ReadinessV10,
+}
+
+impl From<AdvertisementV7> for NodeAdvertisement {
+    fn from(v7: AdvertisementV7) -> Self {
+        Self {
+            node_id: v7.node_id,
+            node_type: v7.node_type,
+            ip_address: v7.ip_address,
+            zerotier_ip: v7.zerotier_ip,
+            ssh_port: v7.ssh_port,
+            api_port: v7.api_port,
+            capabilities: v7.capabilities.into(),
+            status: v7.status,
+            timestamp: v7.timestamp,
+            network_id: v7.network_id,
+            carbon_intensity_g_per_kwh: v7.carbon_intensity_g_per_kwh,
+            avg_job_kwh: v7.avg_job_kwh,
+            details: v7.details,
+            free_slots: v7.free_slots,
+            sent_at_ms: v7.sent_at_ms,
+            clock_offset_ms: v7.clock_offset_ms,
+            warm_images: v7.warm_images,
+            gpu_model: v7.gpu_model,
+            price_per_hour: v7.price_per_hour,
+            access: v7.access,
+            readiness: v7.readiness.into(),
+            trial: None,
+            public_key: None,
+            signature: None,
+        }
+    }
+}
+
+/// Advertisement as protocol v8 sent it, before free trials
+#[derive(Deserialize)]
+struct AdvertisementV8 {
+    node_id: String,
+    node_type: NodeType,
+    ip_address: String,
+    zerotier_ip: Option<String>,
+    ssh_port: u16,
+    api_port: u16,
+    capabilities: NodeCapabilities,
+    status: NodeStatus,
+    timestamp: u64,
+    network_id: String,
+    carbon_intensity_g_per_kwh: Option<f32>,
+    avg_job_kwh: Option<f32>,
+    details: NodeDetails,
+    free_slots: Option<u32>,
+    sent_at_ms: u64,
+    clock_offset_ms: Option<i64>,
+    warm_images: Vec<String>,
+    gpu_model: Option<String>,
+    price_per_hour: Option<f32>,
+    access: AccessPolicy,
+    readiness: ReadinessV10,
+}
+
+impl From<AdvertisementV8> for NodeAdvertisement {
+    fn from(v8: AdvertisementV8) -> Self {
+        Self {
+            node_id: v8.node_id,
+            node_type: v8.node_type,
+            ip_address: v8.ip_address,
+            zerotier_ip: v8.zerotier_ip,
+            ssh_port: v8.ssh_port,
+            api_port: v8.api_port,
+            capabilities: v8.capabilities,
+            status: v8.status,
+            timestamp: v8.timestamp,
+            network_id: v8.network_id,
+            carbon_intensity_g_per_kwh: v8.carbon_intensity_g_per_kwh,
+            avg_job_kwh: v8.avg_job_kwh,
+            details: v8.details,
+            free_slots: v8.free_slots,
+            sent_at_ms: v8.sent_at_ms,
+            clock_offset_ms: v8.clock_offset_ms,
+            warm_images: v8.warm_images,
+            gpu_model: v8.gpu_model,
+            price_per_hour: v8.price_per_hour,
+            access: v8.access,
+            readiness: v8.readiness.into(),
+            trial: None,
+            public_key: None,
+            signature: None,
+        }
+    }
+}
+
+/// Advertisement as protocol v9 sent it, before signatures
+#[derive(Deserialize)]
+struct AdvertisementV9 {
+    node_id: String,
+    node_type: NodeType,
+    ip_address: String,
+    zerotier_ip: Option<String>,
+    ssh_port: u16,
+    api_port: u16,
+    capabilities: NodeCapabilities,
+    status: NodeStatus,
+    timestamp: u64,
+    network_id: String,
+    carbon_intensity_g_per_kwh: Option<f32>,
+    avg_job_kwh: Option<f32>,
+    details: NodeDetails,
+    free_slots: Option<u32>,
+    sent_at_ms: u64,
+    clock_offset_ms: Option<i64>,
+    warm_images: Vec<String>,
+    gpu_model: Option<String>,
+    price_per_hour: Option<f32>,
+    access: AccessPolicy,
+    readiness: ReadinessV10,
+    trial: Option<TrialOffer>,
+}
+
+impl From<AdvertisementV9> for NodeAdvertisement {
+    fn from(v9: AdvertisementV9) -> Self {
+        Self {
+            node_id: v9.node_id,
+            node_type: v9.node_type,
+            ip_address: v9.ip_address,
+            zerotier_ip: v9.zerotier_ip,
+            ssh_port: v9.ssh_port,
+            api_port: v9.api_port,
+            capabilities: v9.capabilities,
+            status: v9.status,
+            timestamp: v9.timestamp,
+            network_id: v9.network_id,
+            carbon_intensity_g_per_kwh: v9.carbon_intensity_g_per_kwh,
+            avg_job_kwh: v9.avg_job_kwh,
+            details: v9.details,
+            free_slots: v9.free_slots,
+            sent_at_ms: v9.sent_at_ms,
+            clock_offset_ms: v9.clock_offset_ms,
+            warm_images: v9.warm_images,
+            gpu_model: v9.gpu_model,
+            price_per_hour: v9.price_per_hour,
+            access: v9.access,
+            readiness: v9.readiness.into(),
+            trial: v9.trial,
+            public_key: None,
+            signature: None,
+        }
+    }
+}
+
+/// Advertisement as protocol v10 sent it, before the startup self-test
+#[derive(Deserialize)]
+struct AdvertisementV10 {
+    node_id: String,
+    node_type: NodeType,
+    ip_address: String,
+    zerotier_ip: Option<String>,
+    ssh_port: u16,
+    api_port: u16,
+    capabilities: NodeCapabilities,
+    status: NodeStatus,
+    timestamp: u64,
+    network_id: String,
+    carbon_intensity_g_per_kwh: Option<f32>,
+    avg_job_kwh: Option<f32>,
+    details: NodeDetails,
+    free_slots: Option<u32>,
+    sent_at_ms: u64,
+    clock_offset_ms: Option<i64>,
+    warm_images: Vec<String>,
+    gpu_model: Option<String>,
+    price_per_hour: Option<f32>,
+    access: AccessPolicy,
+    readiness: ReadinessV10,
+    trial: Option<TrialOffer>,
+    public_key: Option<String>,
+    signature: Option<String>,
+}
+
+impl From<AdvertisementV10> for NodeAdvertisement {
+    fn from(v10: AdvertisementV10) -> Self {
+        Self {
+            node_id: v10.node_id,
+            node_type: v10.node_type,
+            ip_address: v10.ip_address,
+            zerotier_ip: v10.zerotier_ip,
+            ssh_port: v10.ssh_port,
+            api_port: v10.api_port,
+            capabilities: v10.capabilities,
+            status: v10.status,
+            timestamp: v10.timestamp,
+            network_id: v10.network_id,
+            carbon_intensity_g_per_kwh: v10.carbon_intensity_g_per_kwh,
+            avg_job_kwh: v10.avg_job_kwh,
+            details: v10.details,
+            free_slots: v10.free_slots,
+            sent_at_ms: v10.sent_at_ms,
+            clock_offset_ms: v10.clock_offset_ms,
+            warm_images: v10.warm_images,
+            gpu_model: v10.gpu_model,
+            price_per_hour: v10.price_per_hour,
+            access: v10.access,
+            readiness: v10.readiness.into(),
+            trial: v10.trial,
+            public_key: v10.public_key,
+            signature: v10.signature,
+        }
+    }
+}
+
+/// Readiness as protocol v7 to v10 sent it, before the startup self-test
+#[derive(Deserialize)]
+struct ReadinessV10 {
+    overlay: Check,
+    sshd: Check,
+    docker: Check,
+    gpu: Check,
+    payments: Check,
+    checked_at: u64,
+}
+
+impl From<ReadinessV10> for Readiness {
+    fn from(v10: ReadinessV10) -> Self {
+        Self {
+            overlay: v10.overlay,
+            sshd: v10.sshd,
+            docker: v10.docker,
+            gpu: v10.gpu,
+            payments: v10.payments,
+            checked_at: v10.checked_at,
+            self_test: Check::Unknown,
+        }
+    }
+}
+
+impl NodeAdvertisement {
+    /// The bytes the node signs: the bincode advertisement with the signature left out, so
+    /// the public key is covered too
+    pub fn signed_bytes(&self) -> Result<Vec<u8>, String> {
+        let unsigned = NodeAdvertisement { signature: None, ..self.clone() };
+        bincode::serialize(&unsigned).map_err(|e| format!("Failed to encode advertisement: {}", e))
+    }
+
+    /// Estimated grams of CO2 for an average job on this node
+    pub fn carbon_per_job_g(&self) -> Option<f32> {
+        Some(self.carbon_intensity_g_per_kwh? * self.avg_job_kwh?)
+    }
+
+    /// Whether a job of `image` can take over an idle container instead of starting cold
+    pub fn warm_start(&self, image: &str) -> bool {
+        self.warm_images.iter().any(|warm| warm == image)
+    }
+
+    /// Free job slots out of the node's maximum, if the node reports them
+    pub fn slots(&self) -> Option<(u32, u32)> {
+        self.free_slots.map(|free| (free, self.capabilities.max_concurrent_jobs))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum NodeType {
+    Rental,
+    Client,
+    Coordinator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeCapabilities {
+    pub cpu_cores: u32,
+    pub memory_gb: u32,
+    pub gpu_count: u32,
+    pub gpu_memory_gb: u32,
+    pub disk_space_gb: u32,
+    pub network_speed_mbps: u32,
+    pub supports_docker: bool,
+    pub supports_gpu: bool,
+    pub max_concurrent_jobs: u32,
+    #[serde(default)]
+    pub gpu_stack: Option<GpuStack>, // NVIDIA driver and CUDA versions, None without a GPU
+    #[serde(default)]
+    pub hardware: HardwareProfile, // GPU and CPU models, interconnects and accelerators
+}
+
+/// Capabilities as protocol v1 and v2 sent them
+#[derive(Deserialize)]
+struct CapabilitiesV2 {
+    cpu_cores: u32,
+    memory_gb: u32,
+    gpu_count: u32,
+    gpu_memory_gb: u32,
+    disk_space_gb: u32,
+    network_speed_mbps: u32,
+    supports_docker: bool,
+    supports_gpu: bool,
+    max_concurrent_jobs: u32,
+}
+
+impl From<CapabilitiesV2> for NodeCapabilities {
+    fn from(v2: CapabilitiesV2) -> Self {
+        Self {
+            cpu_cores: v2.cpu_cores,
+            memory_gb: v2.memory_gb,
+            gpu_count: v2.gpu_count,
+            gpu_memory_gb: v2.gpu_memory_gb,
+            disk_space_gb: v2.disk_space_gb,
+            network_speed_mbps: v2.network_speed_mbps,
+            supports_docker: v2.supports_docker,
+            supports_gpu: v2.supports_gpu,
+            max_concurrent_jobs: v2.max_concurrent_jobs,
+            gpu_stack: None,
+            hardware: HardwareProfile::default(),
+        }
+    }
+}
+
+/// Capabilities as protocol v3 to v7 sent them, before the hardware profile
+#[derive(Deserialize)]
+struct CapabilitiesV7 {
+    cpu_cores: u32,
+    memory_gb: u32,
+    gpu_count: u32,
+    gpu_memory_gb: u32,
+    disk_space_gb: u32,
+    network_speed_mbps: u32,
+    supports_docker: bool,
+    supports_gpu: bool,
+    max_concurrent_jobs: u32,
+    gpu_stack: Option<GpuStack>,
+}
+
+impl From<CapabilitiesV7> for NodeCapabilities {
+    fn from(v7: CapabilitiesV7) -> Self {
+        Self {
+            cpu_cores: v7.cpu_cores,
+            memory_gb: v7.memory_gb,
+            gpu_count: v7.gpu_count,
+            gpu_memory_gb: v7.gpu_memory_gb,
+            disk_space_gb: v7.disk_space_gb,
+            network_speed_mbps: v7.network_speed_mbps,
+            supports_docker: v7.supports_docker,
+            supports_gpu: v7.supports_gpu,
+            max_concurrent_jobs: v7.max_concurrent_jobs,
+            gpu_stack: v7.gpu_stack,
+            hardware: HardwareProfile::default(),
+        }
+    }
+}
+
+/// The NVIDIA software a node's containers run against
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GpuStack {
+    pub driver_version: String,
+    pub cuda_version: Option<String>,  // Newest CUDA the driver supports, e.g. "12.2"
+    pub cudnn_version: Option<String>, // cuDNN installed on the host, if any
+}
+
+impl GpuStack {
+    /// Why an image needing CUDA `required` cannot run here, if it cannot
+    ///
+    /// Like the NVIDIA container runtime, a driver runs images up to its own CUDA version.
+    pub fn cuda_incompatibility(&self, required: &str) -> Option<String> {
+        let supported = self.cuda_version.as_deref()?;
+        if cuda_major_minor(required)? <= cuda_major_minor(supported)? {
+            return None;
+        }
+        Some(format!(
+            "needs CUDA {}, but NVIDIA driver {} only supports up to CUDA {}",
+            required, self.driver_version, supported
+        ))
+    }
+}
+
+/// (major, minor) of a CUDA version such as "12.4.1" or "11.8"
+pub fn cuda_major_minor(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.trim().split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().map(|minor| minor.parse().ok()).unwrap_or(Some(0))?;
+    Some((major, minor))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum NodeStatus {
+    Available,
+    Busy,
+    Maintenance,
+    Offline,
+}
+
+/// What the sender of a signed `packet` signed: the body as it came with the signature left
+/// out, None for unsigned packets
+///
+/// Checking against these bytes rather than re-encoding the advertisement keeps nodes on an
+/// older protocol version verifiable after fields are added; the signature is always last.
+pub fn signed_body(packet: &[u8]) -> Result<Option<Vec<u8>>, String> {
+    let Some(signature) = decode_advertisement(packet)?.signature else {
+        return Ok(None);
+    };
+    let encode = |signature: Option<String>| {
+        bincode::serialize(&signature).map_err(|e| format!("Failed to encode signature: {}", e))
+    };
+    let rest = packet[4..]
+        .strip_suffix(encode(Some(signature))?.as_slice())
+        .ok_or_else(|| "Discovery packet does not end with its signature".to_string())?;
+    Ok(Some([rest, &encode(None)?].concat()))
+}
+
+/// Discovery packet: the protocol version, then the bincode advertisement
+pub fn encode_advertisement(advertisement: &NodeAdvertisement) -> Result<Vec<u8>, String> {
+    let mut packet = PROTOCOL_VERSION.to_le_bytes().to_vec();
+    let body = bincode::serialize(advertisement).map_err(|e| format!("Failed to encode advertisement: {}", e))?;
+    packet.extend(body);
+    Ok(packet)
+}
+
+/// Decode a discovery packet, refusing versions this build cannot read
+pub fn decode_advertisement(packet: &[u8]) -> Result<NodeAdvertisement, String> {
+    if packet.len() < 4 {
+        return Err("Discovery packet is too short".to_string());
+    }
+    let (version, body) = packet.split_at(4);
+    let version = u32::from_le_bytes([version[0], version[1], version[2], version[3]]);
+    if !is_compatible(version) {
+        return Err(format!("Discovery packet uses protocol v{}, this node speaks v{}", version, PROTOCOL_VERSION));
+    }
+    let decoded = match version {
+        1 => bincode::deserialize::<AdvertisementV1>(body).map(NodeAdvertisement::from),
+        2 => bincode::deserialize::<AdvertisementV2>(body).map(NodeAdvertisement::from),
+        3 => bincode::deserialize::<AdvertisementV3>(body).map(NodeAdvertisement::from),
+        4 => bincode::deserialize::<AdvertisementV4>(body).map(NodeAdvertisement::from),
+        5 => bincode::deserialize::<AdvertisementV5>(body).map(NodeAdvertisement::from),
+        6 => bincode::deserialize::<AdvertisementV6>(body).map(NodeAdvertisement::from),
+        7 => bincode::deserialize::<AdvertisementV7>(body).map(NodeAdvertisement::from),
+        8 => bincode::deserialize::<AdvertisementV8>(body).map(NodeAdvertisement::from),
+        9 => bincode::deserialize::<AdvertisementV9>(body).map(NodeAdvertisement::from),
+        10 => bincode::deserialize::<AdvertisementV10>(body).map(NodeAdvertisement::from),
+        _ => bincode::deserialize(body),
+    };
+    decoded.map_err(|e| format!("Failed to decode advertisement: {}", e))
+}
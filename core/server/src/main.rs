@@ -2,8 +2,140 @@ use std::process::{Command, Stdio};
 use std::thread;
 use std::time::Duration;
 use std::env;
+use std::time::Instant;
+use std::sync::Arc;
+use chrono::Timelike;
+use eryzaa_node::{AccountedContainer, AdmissionDecision, AdmissionRequest, AdmissionRules, CapabilityChangeEvent, ConcurrencyLimit, EgressPolicy, EnergyMeter, EnergyModel, FirewallPolicy, HardwareSnapshot, HardwareWatcher, LockdownState, LoginApproval, LoginQueue, LoginRequest, MeteredJob, QuietHours, Recording, ServiceSet, ServiceStatus, StartDecision, Supervisor, ThermalPolicy, ThermalScheduler, TrialLedger};
+use eryzaa_node::{energy, lockdown, login_approval, onboarding, recording, thermal};
+use eryzaa_jobs::cache::ResultCache;
+use eryzaa_jobs::cuda;
+use eryzaa_jobs::rollout;
+use eryzaa_jobs::abuse::{self, BlocklistFeed};
+use eryzaa_jobs::{AdmissionState, JobManager, JobStatus, PausePolicy, PipelineStore, SweepStore, WarmPool};
+use eryzaa_bus::{Bus, Event};
+use eryzaa_discovery::clock;
+use eryzaa_discovery::coordinators::{self, CoordinatorSet};
+use eryzaa_discovery::market::{self, COORDINATOR_URL_ENV};
+use eryzaa_discovery::overlay::{self, NetworkMode};
+use eryzaa_discovery::push::{self, FinishedReport};
+use eryzaa_discovery::registration;
+use eryzaa_discovery::{AdvertisementPrivacy, NodeIdentity, TrialOffer};
+use eryzaa_payments::{AccessPolicy, ChainRpc, DepositLedger};
 
 fn main() {
+    // sshd runs this for job users while login approval is on
+    if env::args().nth(1).as_deref() == Some("login-gate") {
+        run_login_gate();
+    }
+    // The owner's panic button, also on the GUI and a localhost endpoint
+    if env::args().nth(1).as_deref() == Some("panic") {
+        let reason = env::args().skip(2).collect::<Vec<_>>().join(" ");
+        run_panic(if reason.is_empty() { "Panic button (command line)" } else { &reason });
+    }
+    if env::args().nth(1).as_deref() == Some("lift") {
+        run_lift();
+    }
+    if env::args().nth(1).as_deref() == Some("incidents") {
+        for incident in lockdown::Incident::list(&incidents_dir()) {
+            let failed = incident.steps.iter().filter(|step| !step.ok).count();
+            println!("{}  {}  {} steps, {} failed", incident.incident_id, incident.reason, incident.steps.len(), failed);
+        }
+        return;
+    }
+    // Fleet rollouts drive these over SSH, see `eryzaa fleet rollout`
+    if env::args().nth(1).as_deref() == Some("drain") {
+        let draining = match env::args().nth(2).as_deref() {
+            Some("on") => true,
+            Some("off") => false,
+            _ => {
+                println!("{}", if eryzaa_jobs::stack::is_draining() { "draining" } else { "accepting jobs" });
+                return;
+            }
+        };
+        if let Err(e) = eryzaa_jobs::stack::set_draining(draining) {
+            eprintln!("[-] {}", e);
+            std::process::exit(1);
+        }
+        println!("[+] {}", if draining { "Draining: no new jobs start" } else { "Accepting jobs again" });
+        return;
+    }
+    if env::args().nth(1).as_deref() == Some("health") {
+        let running_jobs = JobManager::load_from(&eryzaa_jobs::default_registry_path())
+            .map(|manager| manager.running_tenant_jobs())
+            .unwrap_or(0);
+        let health = rollout::NodeHealth {
+            draining: eryzaa_jobs::stack::is_draining(),
+            running_jobs,
+            unhealthy_services: Supervisor::new(service_set())
+                .status()
+                .into_iter()
+                .filter(|(_, healthy)| !healthy)
+                .map(|(name, _)| name)
+                .collect(),
+        };
+        println!("{}", serde_json::to_string(&health).unwrap_or_default());
+        return;
+    }
+    // Recorded job sessions; `recordings export <id>` writes an asciicast beside the recording
+    if env::args().nth(1).as_deref() == Some("recordings") {
+        let recordings = Recording::list(std::path::Path::new(recording::RECORDINGS_DIR));
+        match (env::args().nth(2).as_deref(), env::args().nth(3)) {
+            (Some("export"), Some(id)) => match recordings.iter().find(|r| r.meta.id == id).map(|r| r.export_asciicast()) {
+                Some(Ok(path)) => println!("[+] Exported to {}", path.display()),
+                Some(Err(e)) => println!("[-] {}", e),
+                None => println!("[-] No recording {}", id),
+            },
+            _ => {
+                for recording in recordings {
+                    let started = chrono::DateTime::from_timestamp(recording.meta.started_at as i64, 0)
+                        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                        .unwrap_or_default();
+                    println!("{}  {}  {} from {}", recording.meta.id, started, recording.meta.username, recording.meta.source);
+                }
+            }
+        }
+        return;
+    }
+    // `network lan` serves clients on the LAN without ZeroTier, `network overlay` goes back
+    if env::args().nth(1).as_deref() == Some("network") {
+        let mode = match env::args().nth(2).as_deref() {
+            Some("lan") => NetworkMode::LanDirect,
+            Some("overlay") => NetworkMode::Overlay,
+            _ => {
+                println!("{}", network_mode().label());
+                return;
+            }
+        };
+        if let Err(e) = mode.save_to(&network_mode_path()) {
+            eprintln!("[-] {}", e);
+            std::process::exit(1);
+        }
+        println!("[+] Network mode: {}; restart the rental server to apply it", mode.label());
+        return;
+    }
+    // Identity, overlay, benchmark, price and registration in one guided flow
+    if env::args().nth(1).as_deref() == Some("onboard") {
+        if let Err(e) = run_onboarding() {
+            eprintln!("[-] {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    
+    // State saved by an older build is brought up to date; a newer build's is left alone
+    if let Err(e) = eryzaa_jobs::schema::prepare() {
+        eprintln!("[-] {}", e);
+        std::process::exit(1);
+    }
+    
+    // First run on a terminal: onboard before serving; unattended starts skip it
+    if !eryzaa_node::Onboarding::load_from(&onboarding_path()).is_complete() && std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+        println!("[*] This node has not been onboarded yet");
+        if let Err(e) = run_onboarding() {
+            println!("[!] Onboarding stopped: {} (run `rental onboard` to finish it)", e);
+        }
+    }
+    
     println!("=== Rental Server Application ===");
     println!("Running inside Docker container with Ubuntu");
     
@@ -11,11 +143,20 @@ fn main() {
     display_system_info();
     
     // Check ZeroTier status
-    check_zerotier_status();
+    match network_mode() {
+        NetworkMode::Overlay => check_zerotier_status(),
+        NetworkMode::LanDirect => check_lan_direct(),
+    }
     
     // Check SSH service
     check_ssh_status();
     
+    // Job expiry and billing rely on this clock
+    check_clock();
+    
+    // Restrict node ports to the overlay
+    configure_firewall();
+    
     // Check GPU access
     check_gpu_access();
     
@@ -25,20 +166,38 @@ fn main() {
     println!("[+] Rental server is ready!");
     println!("[*] Monitoring services...");
     
+    let bus = Arc::new(event_bus());
+    watch_hardware(Arc::clone(&bus));
+    watch_login_requests();
+    watch_blocklist();
+    watch_output_pushes();
+    report_finished_jobs();
+    serve_panic_endpoint();
+    let scheduler = thermal_scheduler();
+    let mut meter = EnergyMeter::new(energy_model());
+    let mut supervisor = Supervisor::new(service_set());
+    let mut egress_applied = None;
+    
     // Keep the application running and monitor services
     loop {
         thread::sleep(Duration::from_secs(30));
         
-        // Periodic health checks
-        if !is_zerotier_running() {
-            println!("[!] ZeroTier service is down, attempting restart...");
-            restart_zerotier();
+        // Quiet hours power cap and thermally gated job starts; nothing starts while locked down
+        if LockdownState::load_from(&lockdown_path()).active {
+            println!("[!] Node is locked down; run `rental lift` to let tenants back");
+        } else {
+            schedule_pending_jobs(&scheduler, &bus);
         }
         
-        if !is_ssh_running() {
-            println!("[!] SSH service is down, attempting restart...");
-            restart_ssh();
-        }
+        // Outbound traffic of running jobs, as the renter restricted it
+        enforce_egress(&mut egress_applied);
+        
+        // Per-job energy estimates and receipts
+        meter.model = energy_model();
+        meter_energy(&mut meter, &bus);
+        
+        // Periodic health checks
+        supervise_services(&mut supervisor);
     }
 }
 
@@ -98,6 +257,18 @@ fn check_zerotier_status() {
     }
 }
 
+fn check_lan_direct() {
+    println!("
+=== Network ===");
+    
+    let (lan_ip, _) = overlay::local_addresses_in(NetworkMode::LanDirect, "");
+    println!("[*] LAN direct mode, no overlay");
+    match overlay::lan_subnet(&lan_ip) {
+        Some(subnet) => println!("[+] Clients on {} connect to {}", subnet, lan_ip),
+        None => println!("[!] Could not tell the subnet of {}; accepting private ranges", lan_ip),
+    }
+}
+
 fn check_ssh_status() {
     println!("
 === SSH Status ===");
@@ -111,6 +282,1154 @@ fn check_ssh_status() {
     }
 }
 
+fn check_clock() {
+    println!("
+=== Clock ===");
+    
+    let status = clock::local_clock_status();
+    match (status.warning(), status.offset_ms) {
+        (Some(warning), _) => println!("[!] {}", warning),
+        (None, Some(offset_ms)) => println!("[+] Clock synchronized ({})", clock::describe_offset(offset_ms)),
+        (None, None) if status.synchronized == Some(true) => println!("[+] Clock synchronized"),
+        (None, None) => println!("[!] Could not determine NTP sync; install chrony or systemd-timesyncd"),
+    }
+}
+
+fn firewall_policy() -> FirewallPolicy {
+    let mode = network_mode();
+    let lan_subnet = match mode {
+        NetworkMode::Overlay => None,
+        NetworkMode::LanDirect => overlay::lan_subnet(&overlay::local_addresses_in(mode, "").0),
+    };
+    let mut policy = FirewallPolicy::for_mode(mode, lan_subnet.as_deref());
+    
+    // Extra sources allowed outside the overlay, comma separated
+    if let Ok(allowlist) = env::var("ERYZAA_FIREWALL_ALLOWLIST") {
+        policy.allowlist = allowlist
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+    }
+    
+    policy
+}
+
+/// Re-program the egress rules when the policy or the set of running jobs changed
+fn enforce_egress(applied: &mut Option<String>) {
+    let policy = EgressPolicy::load_from(&eryzaa_jobs::default_registry_path().with_file_name("egress.json"));
+    let containers: Vec<AccountedContainer> = JobManager::load_from(&eryzaa_jobs::default_registry_path())
+        .map(|manager| manager.list_jobs())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|job| job.status == JobStatus::Running && job.client_id != eryzaa_jobs::stack::STACK_CLIENT)
+        .filter_map(|job| {
+            eryzaa_node::accounting::container_ip(&job.container_name)
+                .map(|ip| AccountedContainer { job_id: job.job_id, ip })
+        })
+        .collect();
+    
+    let state = format!("{:?} {:?}", policy, containers);
+    if applied.as_ref() == Some(&state) {
+        return;
+    }
+    match policy.apply(&containers, &firewall_policy().overlay_subnets) {
+        Ok(()) => {
+            if policy.is_restricted() {
+                println!("[+] Egress '{}' enforced for {} jobs", policy.preset.label(), containers.len());
+            }
+            *applied = Some(state);
+        }
+        Err(e) => println!("[-] Failed to enforce egress policy: {}", e),
+    }
+}
+
+fn configure_firewall() {
+    println!("
+=== Firewall ===");
+    
+    let policy = firewall_policy();
+    match policy.apply_and_verify() {
+        Ok(state) => {
+            println!("[+] {} rules verified", state.backend);
+            for line in policy.describe() {
+                println!("    {}", line);
+            }
+        }
+        Err(e) => {
+            println!("[-] Failed to configure firewall: {}", e);
+        }
+    }
+}
+
+fn event_bus() -> Bus {
+    let source = env::var("HOSTNAME").unwrap_or_else(|_| "rental-node".to_string());
+    
+    // Shared with a coordinator when ERYZAA_NATS_URL is set
+    match Bus::from_env(&source) {
+        Ok(bus) => {
+            println!("[+] Control-plane events on the {} bus", bus.backend());
+            bus
+        }
+        Err(e) => {
+            println!("[-] {}, using the local bus", e);
+            Bus::local(&source)
+        }
+    }
+}
+
+/// Log hardware changes with the running jobs they may affect
+fn watch_hardware(bus: Arc<Bus>) {
+    let interval = env::var("ERYZAA_HARDWARE_RESCAN_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(300);
+    let log_path = eryzaa_jobs::default_registry_path().with_file_name("hardware-changes.jsonl");
+    
+    HardwareWatcher::new(Duration::from_secs(interval)).spawn(move |current, changes| {
+        let running: Vec<(String, u32)> = JobManager::load_from(&eryzaa_jobs::default_registry_path())
+            .map(|manager| manager.list_jobs())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|job| job.status == JobStatus::Running)
+            .map(|job| (job.job_id, job.gpus))
+            .collect();
+        
+        let event = CapabilityChangeEvent::new(changes, current, &running);
+        for change in &event.changes {
+            println!("[*] Hardware change: {}", change);
+        }
+        if !event.at_risk_jobs.is_empty() {
+            println!("[!] Jobs that may have lost hardware: {}", event.at_risk_jobs.join(", "));
+        }
+        if let Err(e) = event.append_to(&log_path) {
+            println!("[-] {}", e);
+        }
+        bus.publish(Event::CapabilitiesChanged {
+            changes: event.changes.iter().map(|c| c.to_string()).collect(),
+            at_risk_jobs: event.at_risk_jobs,
+        });
+    });
+}
+
+fn login_approval_path() -> std::path::PathBuf {
+    // Saved from the rental GUI
+    eryzaa_jobs::default_registry_path().with_file_name("login-approval.json")
+}
+
+/// Hold a job user's SSH login until it is approved, then hand over to their shell
+///
+/// Arguments are the approval timeout, 0 to skip approval, and `--record` to record the session.
+fn run_login_gate() -> ! {
+    let username = env::var("USER").unwrap_or_default();
+    let source = env::var("SSH_CONNECTION")
+        .ok()
+        .and_then(|c| c.split_whitespace().next().map(|ip| ip.to_string()))
+        .unwrap_or_else(|| "unknown".to_string());
+    let timeout: u64 = env::args().nth(2).and_then(|t| t.parse().ok()).unwrap_or(120);
+    let record = env::args().skip(3).any(|arg| arg == "--record");
+    
+    if timeout > 0 {
+        let queue = LoginQueue::new(login_approval::QUEUE_DIR);
+        let request = LoginRequest::new(&username, &source);
+        if let Err(e) = queue.submit(&request) {
+            eprintln!("[-] Login approval is unavailable: {}", e);
+            std::process::exit(1);
+        }
+        
+        eprintln!("[*] Waiting for the node owner to approve this login (up to {} s)...", timeout);
+        if !queue.wait(&request.id, Duration::from_secs(timeout)) {
+            eprintln!("[-] Login was not approved");
+            std::process::exit(1);
+        }
+        eprintln!("[+] Login approved");
+    }
+    
+    let shell = env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string());
+    let original = env::var("SSH_ORIGINAL_COMMAND").ok();
+    let mut command = Command::new(&shell);
+    match &original {
+        Some(original) => command.args(&["-c", original]),
+        None => command.arg("-l"),
+    };
+    if record {
+        let dir = std::path::Path::new(recording::RECORDINGS_DIR);
+        match Recording::start(dir, &username, &source, &shell, original.as_deref()) {
+            Ok((_, recorded)) => {
+                if original.is_none() {
+                    eprintln!("[*] This session is recorded by the node owner");
+                }
+                command = recorded;
+            }
+            Err(e) => {
+                eprintln!("[-] Session recording is unavailable: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let e = command.exec();
+        eprintln!("[-] Failed to start {}: {}", shell, e);
+        std::process::exit(1);
+    }
+    
+    #[cfg(not(unix))]
+    {
+        let code = command.status().map(|s| s.code().unwrap_or(1)).unwrap_or(1);
+        std::process::exit(code);
+    }
+}
+
+fn lockdown_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("lockdown.json")
+}
+
+fn onboarding_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("onboarding.json")
+}
+
+/// Key the node signs its advertisements with, shared with the rental GUI
+fn node_identity_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("node_identity.key")
+}
+
+fn advertisement_privacy_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("privacy.json")
+}
+
+fn network_mode_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("network.json")
+}
+
+fn network_mode() -> NetworkMode {
+    NetworkMode::load_from(&network_mode_path())
+}
+
+/// A line typed by the owner, or `default` when they just press Enter
+fn prompt(question: &str, default: &str) -> String {
+    use std::io::Write;
+    match default.is_empty() {
+        true => print!("{}: ", question),
+        false => print!("{} [{}]: ", question, default),
+    }
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    let _ = std::io::stdin().read_line(&mut answer);
+    match answer.trim() {
+        "" => default.to_string(),
+        answer => answer.to_string(),
+    }
+}
+
+/// Walk the owner through onboarding, skipping steps done on an earlier run
+///
+/// Registration always runs again, so a lost or expired claim code can be replaced.
+fn run_onboarding() -> Result<(), String> {
+    let path = onboarding_path();
+    let mut state = eryzaa_node::Onboarding::load_from(&path);
+    println!("
+=== Onboarding ===");
+    
+    // Step 1: identity
+    match state.ensure_identity() {
+        true => println!("[+] Generated node identity {}", state.node_id),
+        false => println!("[+] Node identity {}", state.node_id),
+    }
+    state.save_to(&path)?;
+    
+    // Step 2: coordinator
+    let default_url = state.coordinator_url.clone().or_else(|| env::var(COORDINATOR_URL_ENV).ok()).unwrap_or_default();
+    let coordinators = CoordinatorSet::new(coordinators::parse_urls(&prompt("Coordinator URLs, comma separated", &default_url)));
+    if coordinators.urls().is_empty() {
+        return Err(format!("No coordinator URL given (set {})", COORDINATOR_URL_ENV));
+    }
+    
+    // Step 3: overlay, unless the node serves its LAN directly
+    let network_id = env::var("ZEROTIER_NETWORK_ID").unwrap_or_else(|_| "363c67c55ad2489d".to_string());
+    if network_mode().uses_overlay() {
+        println!("[*] Joining overlay network {}...", network_id);
+        match overlay::join(&network_id, Duration::from_secs(60)) {
+            Ok(overlay_ip) => {
+                println!("[+] Overlay address {}", overlay_ip);
+                state.overlay_ip = Some(overlay_ip);
+            }
+            Err(e) => println!("[!] {}", e),
+        }
+    } else {
+        println!("[*] LAN direct mode, not joining an overlay");
+    }
+    state.save_to(&path)?;
+    
+    // Step 4: benchmark
+    let hardware = HardwareSnapshot::scan();
+    if state.benchmark.is_none() {
+        println!("[*] Benchmarking {} cores for a few seconds...", hardware.cpu_cores);
+        state.benchmark = Some(onboarding::run_benchmark(hardware.cpu_cores, onboarding::BENCHMARK_DURATION));
+        state.save_to(&path)?;
+    }
+    if let Some(benchmark) = &state.benchmark {
+        println!("[+] CPU score {:.0} ({:.0} on one core)", benchmark.cpu_score, benchmark.single_core_score);
+    }
+    for gpu in &hardware.gpus {
+        println!("[+] GPU {}: {} ({} MB)", gpu.index, gpu.name, gpu.memory_mb);
+    }
+    
+    // Step 5: pricing
+    let suggested = match coordinators.call(market::fetch_stats) {
+        Ok(stats) => onboarding::suggest_price(&stats, &hardware),
+        Err(e) => {
+            println!("[!] No market prices to compare with: {}", e);
+            None
+        }
+    };
+    let default_price = state.price_per_hour.or(suggested).map(|price| format!("{:.2}", price)).unwrap_or_default();
+    loop {
+        match prompt("Price per hour in AVAX", &default_price).parse::<f32>() {
+            Ok(price) if price > 0.0 => {
+                state.price_per_hour = Some(price);
+                break;
+            }
+            _ => println!("[-] Enter a price above zero"),
+        }
+    }
+    state.save_to(&path)?;
+    
+    // Step 6: registration
+    let hostname = std::fs::read_to_string("/etc/hostname").unwrap_or_default();
+    // Every mirror learns of the node, short of what the owner keeps private; the owner claims it
+    // with the code of the first that took it
+    let privacy = AdvertisementPrivacy::load_from(&advertisement_privacy_path());
+    // The key the GUI signs advertisements with, so the coordinators list them under this claim
+    let identity = NodeIdentity::load_or_create(&node_identity_path())?;
+    let node = privacy.apply_to_registration(&state.registration(hostname.trim(), &hardware, &identity.public_key()));
+    let mut first_receipt = None;
+    for (url, result) in coordinators.publish(|url| registration::register_node(url, &node)) {
+        match result {
+            Ok(receipt) => {
+                println!("[+] Registered with {}", url);
+                first_receipt.get_or_insert((url, receipt));
+            }
+            Err(e) => println!("[-] {}", e),
+        }
+    }
+    let (coordinator_url, receipt) = first_receipt.ok_or("No coordinator took the registration")?;
+    state.registered(&coordinator_url, receipt, chrono::Utc::now().timestamp() as u64);
+    state.save_to(&path)?;
+    
+    // Step 7: claim code
+    match (&state.owner, &state.claim_code) {
+        (Some(owner), _) => println!("[+] This node belongs to {}", owner),
+        (None, Some(code)) => {
+            if let Ok(qr) = onboarding::claim_qr_text(code) {
+                println!("{}", qr);
+            }
+            let expires = chrono::DateTime::from_timestamp(state.claim_expires_at as i64, 0)
+                .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default();
+            println!("[+] Claim code: {}", code);
+            println!("[*] Enter it under Settings > Claim a Node in the rental GUI before {}", expires);
+        }
+        (None, None) => {}
+    }
+    Ok(())
+}
+
+fn incidents_dir() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("incidents")
+}
+
+/// Cut every tenant off, snapshot the node and stop its jobs; returns the incident id
+fn engage_lockdown(reason: &str) -> Result<String, String> {
+    let registry = eryzaa_jobs::default_registry_path();
+    let manager = JobManager::load_from(&registry)?;
+    let jobs: Vec<_> = manager
+        .list_jobs()
+        .into_iter()
+        .filter(|job| matches!(job.status, JobStatus::Running | JobStatus::Paused) && job.client_id != eryzaa_jobs::stack::STACK_CLIENT)
+        .collect();
+    let tenants = lockdown::Tenants {
+        containers: jobs.iter().filter(|job| job.node_address.is_none()).map(|job| job.container_name.clone()).collect(),
+        users: jobs.iter().filter_map(|job| job.ssh_user.clone()).collect(),
+    }
+    .with_system_users();
+    
+    let hardware_log = registry.with_file_name("hardware-changes.jsonl");
+    let audit_logs = [("hardware-changes", hardware_log.as_path()), ("job-registry", registry.as_path())];
+    let incident = lockdown::engage(reason, &tenants, &firewall_policy().overlay_subnets, &audit_logs, &incidents_dir())?;
+    
+    for job in &jobs {
+        let _ = manager.update_status(&job.job_id, JobStatus::Failed(format!("Stopped by the node's panic button ({})", incident.incident_id)));
+    }
+    manager.save_to(&registry)?;
+    LockdownState {
+        active: true,
+        incident_id: Some(incident.incident_id.clone()),
+        engaged_at: Some(incident.taken_at),
+        locked_users: tenants.users,
+    }
+    .save_to(&lockdown_path())?;
+    
+    for step in incident.steps.iter().filter(|step| !step.ok) {
+        println!("[-] {}: {}", step.action, step.detail);
+    }
+    Ok(incident.incident_id)
+}
+
+fn run_panic(reason: &str) -> ! {
+    match engage_lockdown(reason) {
+        Ok(incident_id) => {
+            println!("[+] Node locked down; incident snapshot {} in {}", incident_id, incidents_dir().display());
+            std::process::exit(0);
+        }
+        Err(e) => {
+            println!("[-] Lockdown incomplete: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_lift() -> ! {
+    let path = lockdown_path();
+    let state = LockdownState::load_from(&path);
+    if !state.active {
+        println!("[*] Node is not locked down");
+        std::process::exit(0);
+    }
+    for step in lockdown::lift(&state) {
+        println!("[{}] {}{}", if step.ok { "+" } else { "-" }, step.action, if step.ok { String::new() } else { format!(": {}", step.detail) });
+    }
+    if let Err(e) = LockdownState::default().save_to(&path) {
+        println!("[-] {}", e);
+        std::process::exit(1);
+    }
+    println!("[+] Lockdown lifted; killed containers stay stopped");
+    std::process::exit(0);
+}
+
+/// Localhost endpoint for scripts and monitors to hit the panic button
+fn serve_panic_endpoint() {
+    let token = match lockdown::trigger_token(&incidents_dir()) {
+        Ok(token) => token,
+        Err(e) => {
+            println!("[-] Panic endpoint disabled: {}", e);
+            return;
+        }
+    };
+    let listener = match std::net::TcpListener::bind(("127.0.0.1", lockdown::TRIGGER_PORT)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            println!("[-] Panic endpoint disabled: {}", e);
+            return;
+        }
+    };
+    println!("[+] Panic endpoint at http://127.0.0.1:{}{} (token in {})", lockdown::TRIGGER_PORT, lockdown::TRIGGER_PATH, incidents_dir().display());
+    thread::spawn(move || {
+        lockdown::serve_trigger(listener, &token, |reason| {
+            println!("[!] Panic endpoint triggered: {}", reason);
+            engage_lockdown(reason).map(|incident_id| format!("Locked down, incident {}", incident_id))
+        })
+    });
+}
+
+/// Answer login requests the owner's rules decide and keep sshd's gate in step with the setting
+fn watch_login_requests() {
+    thread::spawn(|| {
+        let queue = LoginQueue::new(login_approval::QUEUE_DIR);
+        let mut gate_enabled = None;
+        loop {
+            let approval = LoginApproval::load_from(&login_approval_path());
+            let gate = (approval.enabled, approval.timeout_secs, approval.record_sessions);
+            if gate_enabled != Some(gate) {
+                match configure_login_gate(&approval, &queue) {
+                    Ok(()) => gate_enabled = Some(gate),
+                    Err(e) => println!("[-] Failed to configure login approval: {}", e),
+                }
+            }
+            
+            let pending = queue.pending();
+            if !pending.is_empty() {
+                let jobs = JobManager::load_from(&eryzaa_jobs::default_registry_path())
+                    .map(|manager| manager.list_jobs())
+                    .unwrap_or_default();
+                let hour = chrono::Local::now().hour();
+                for mut request in pending {
+                    let job = jobs.iter().find(|job| job.ssh_user.as_deref() == Some(request.username.as_str()));
+                    request.client_id = job.map(|job| job.client_id.clone());
+                    if let Some(approve) = approval.auto_decision(&request, hour) {
+                        println!("[*] Login of {} from {} {} by rule", request.username, request.source,
+                                 if approve { "approved" } else { "denied" });
+                        if let Err(e) = queue.decide(&request.id, approve) {
+                            println!("[-] {}", e);
+                        }
+                    }
+                }
+            }
+            queue.prune(Duration::from_secs(approval.timeout_secs + 60));
+            
+            thread::sleep(Duration::from_secs(1));
+        }
+    });
+}
+
+/// Keep the subscribed blocklist current; admissions read the cached copy
+fn watch_blocklist() {
+    let Some(coordinators) = CoordinatorSet::from_env() else {
+        return;
+    };
+    thread::spawn(move || loop {
+        let path = abuse::default_feed_path();
+        let mut feed = BlocklistFeed::load_from(&path);
+        if feed.subscribed {
+            let result = coordinators.call(|url| feed.refresh(url)).and_then(|()| feed.save_to(&path));
+            if let Err(e) = result {
+                println!("[-] Failed to refresh the blocklist: {}", e);
+            }
+        }
+        thread::sleep(Duration::from_secs(600));
+    });
+}
+
+/// Tell the coordinators about jobs that ended, so they can alert clients watching them on their phones
+fn report_finished_jobs() {
+    let Some(coordinators) = CoordinatorSet::from_env() else {
+        return;
+    };
+    thread::spawn(move || {
+        let mut reported = std::collections::HashSet::new();
+        loop {
+            thread::sleep(Duration::from_secs(60));
+            let Ok(manager) = JobManager::load_from(&eryzaa_jobs::default_registry_path()) else {
+                continue;
+            };
+            for job in manager.list_jobs() {
+                let outcome = match &job.status {
+                    JobStatus::Completed => "Completed".to_string(),
+                    JobStatus::Stopped => "Stopped".to_string(),
+                    JobStatus::Failed(reason) => format!("Failed: {}", reason),
+                    _ => continue,
+                };
+                let report = FinishedReport { job_id: job.job_id.clone(), outcome };
+                // Clients may watch the job on any mirror, and each hears of it once
+                let outcomes = coordinators.publish(|url| match reported.contains(&(url.to_string(), job.job_id.clone())) {
+                    true => Ok(None),
+                    false => push::report_finished(url, &report).map(Some),
+                });
+                for (url, result) in outcomes {
+                    match result {
+                        Ok(_) => {
+                            reported.insert((url, job.job_id.clone()));
+                        }
+                        Err(e) => println!("[-] Failed to report job {} finished: {}", job.job_id, e),
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Upload the outputs of jobs that finished to their clients' buckets
+fn watch_output_pushes() {
+    thread::spawn(|| loop {
+        thread::sleep(Duration::from_secs(60));
+        let registry = eryzaa_jobs::default_registry_path();
+        let Ok(manager) = JobManager::load_from(&registry) else {
+            continue;
+        };
+        let pending = manager.pending_pushes();
+        for job_id in &pending {
+            match manager.push_outputs(job_id) {
+                Ok(urls) => println!("[+] Pushed {} outputs of job {} to its client's bucket", urls.len(), job_id),
+                Err(e) => println!("[-] Failed to push the outputs of job {}: {}", job_id, e),
+            }
+        }
+        
+        // The main loop saves the registry too; only write back what changed here
+        let finished: Vec<_> = manager.list_jobs().into_iter().filter(|job| job.output_push.is_some()).collect();
+        let Ok(current) = JobManager::load_from(&registry) else {
+            continue;
+        };
+        let mut changed = false;
+        for job in finished {
+            if let Some(mut record) = current.get_job(&job.job_id) {
+                if record.status != job.status || record.output_push != job.output_push {
+                    record.status = job.status;
+                    record.output_push = job.output_push;
+                    current.register_job(record);
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            if let Err(e) = current.save_to(&registry) {
+                println!("[-] {}", e);
+            }
+        }
+    });
+}
+
+fn configure_login_gate(approval: &LoginApproval, queue: &LoginQueue) -> Result<(), String> {
+    let snippet = std::path::Path::new(login_approval::SSHD_SNIPPET_PATH);
+    if approval.gate_needed() {
+        queue.prepare()?;
+        if approval.record_sessions {
+            Recording::prepare(std::path::Path::new(recording::RECORDINGS_DIR))?;
+        }
+        let exe = env::current_exe().map_err(|e| format!("Failed to locate the rental server: {}", e))?;
+        std::fs::write(snippet, approval.sshd_snippet(&format!("{} login-gate", exe.display())))
+            .map_err(|e| format!("Failed to write {}: {}", snippet.display(), e))?;
+        if approval.enabled {
+            println!("[+] SSH logins of job users now wait for approval");
+        }
+        if approval.record_sessions {
+            println!("[+] SSH sessions of job users are now recorded in {}", recording::RECORDINGS_DIR);
+        }
+    } else if snippet.exists() {
+        std::fs::remove_file(snippet).map_err(|e| format!("Failed to remove {}: {}", snippet.display(), e))?;
+        println!("[*] SSH login gate turned off");
+    } else {
+        return Ok(());
+    }
+    
+    // sshd only reads drop-ins when it starts
+    match service_set().service("sshd") {
+        Some(sshd) => sshd.restart(),
+        None => Ok(()),
+    }
+}
+
+fn thermal_scheduler() -> ThermalScheduler {
+    let mut policy = ThermalPolicy::default();
+    
+    if let Some(temp) = env::var("ERYZAA_GPU_COOLDOWN_C").ok().and_then(|t| t.parse().ok()) {
+        policy.cooldown_temp_c = temp;
+    }
+    
+    // Quiet hours as START-END:WATTS, e.g. 22-7:150
+    if let Ok(spec) = env::var("ERYZAA_QUIET_HOURS") {
+        match QuietHours::parse(&spec) {
+            Ok(quiet) => {
+                println!("[+] Quiet hours {:02}:00-{:02}:00, GPU power capped at {} W",
+                         quiet.start_hour, quiet.end_hour, quiet.power_cap_watts);
+                policy.quiet_hours = Some(quiet);
+            }
+            Err(e) => println!("[-] {}", e),
+        }
+    }
+    
+    ThermalScheduler::new(policy)
+}
+
+fn schedule_pending_jobs(scheduler: &ThermalScheduler, bus: &Bus) {
+    let readings = thermal::read_gpus().unwrap_or_default();
+    
+    if let Err(e) = scheduler.enforce_power_cap(chrono::Local::now().hour(), &readings) {
+        println!("[-] Failed to apply GPU power cap: {}", e);
+    }
+    
+    let registry = eryzaa_jobs::default_registry_path();
+    let manager = match JobManager::load_from(&registry) {
+        Ok(manager) => manager,
+        Err(e) => {
+            println!("[-] {}", e);
+            return;
+        }
+    };
+    
+    let mut changed = review_admissions(&manager, bus);
+    settle_deposits(&manager);
+    
+    // Free trials end on time, whatever else the node is doing
+    for job_id in manager.end_expired_trials(chrono::Utc::now()) {
+        println!("[*] Free trial {} is over", job_id);
+        bus.publish(Event::JobStatusChanged { job_id, status: "Stopped".to_string() });
+        changed = true;
+    }
+    
+    // Paused jobs hold their slot and any checkpoint; past the limit they are stopped
+    for job_id in manager.enforce_pause_limit(&pause_policy()) {
+        println!("[*] Job {} was paused too long, stopped it", job_id);
+        bus.publish(Event::JobStatusChanged { job_id, status: "Stopped".to_string() });
+        changed = true;
+    }
+    
+    let mut pending: Vec<_> = manager
+        .list_jobs()
+        .into_iter()
+        .filter(|job| job.status == JobStatus::Pending && job.admission == AdmissionState::Admitted)
+        .collect();
+    pending.sort_by_key(|job| job.created_at);
+    
+    // The owner is stopping or restarting the stack; running jobs finish, nothing new starts
+    if eryzaa_jobs::stack::is_draining() {
+        if !pending.is_empty() {
+            println!("[*] Draining, holding {} pending jobs", pending.len());
+        }
+        pending.clear();
+    }
+    
+    let limit = concurrency_limit();
+    let warm = WarmPool::load_from(&eryzaa_jobs::warm::default_pool_path());
+    for job in pending {
+        if !limit.admits(manager.running_count()) {
+            println!("[*] Deferring job {}: all {} job slots in use", job.job_id, limit.max_concurrent_jobs);
+            bus.publish(Event::JobDeferred { job_id: job.job_id.clone(), reason: "no free job slots".to_string() });
+            break;
+        }
+        match scheduler.decide(job.gpus, &readings, Instant::now()) {
+            StartDecision::Start => {
+                // The warm container may have GPUs, which trials never get
+                let warm_start = match job.trial {
+                    Some(_) => Ok(false),
+                    None => manager.start_warm(&job.job_id, &warm),
+                };
+                let started = match warm_start {
+                    Ok(true) => Ok(" from the warm pool"),
+                    Ok(false) => manager.start_job(&job.job_id).map(|()| ""),
+                    Err(e) => Err(e),
+                };
+                match started {
+                    Ok(how) => {
+                        scheduler.mark_started(job.gpus, Instant::now());
+                        println!("[+] Started job {}{}", job.job_id, how);
+                        if job.trial.is_some() {
+                            let cores = thread::available_parallelism().map(|n| n.get() as u32).unwrap_or(1);
+                            if let Err(e) = manager.confine_trial(&job.job_id, cores) {
+                                // A trial the node cannot hold to its CPU share does not run
+                                println!("[-] Failed to limit the CPU of free trial {}: {}", job.job_id, e);
+                                if let Err(e) = manager.cancel_job(&job.job_id) {
+                                    println!("[-] {}", e);
+                                }
+                            }
+                        }
+                        bus.publish(Event::JobStarted { job_id: job.job_id.clone() });
+                        changed = true;
+                    }
+                    Err(e) => println!("[-] Failed to start job {}: {}", job.job_id, e),
+                }
+            }
+            StartDecision::Defer(reason) => {
+                println!("[*] Deferring job {}: {}", job.job_id, reason);
+                bus.publish(Event::JobDeferred { job_id: job.job_id.clone(), reason });
+                // Keep jobs in order; later ones wait for this one
+                break;
+            }
+        }
+    }
+    
+    // Keep an idle container ready for the next job; a job that took it leaves none behind
+    if !eryzaa_jobs::stack::is_draining() {
+        if let Err(e) = warm.replenish() {
+            println!("[-] Failed to prepare the warm container: {}", e);
+        }
+    }
+    
+    if !eryzaa_jobs::stack::is_draining() && advance_pipelines(&manager, scheduler, &limit, &readings, bus) {
+        changed = true;
+    }
+    
+    if !eryzaa_jobs::stack::is_draining() && advance_sweeps(&manager, scheduler, &limit, &readings) {
+        changed = true;
+    }
+    
+    if changed {
+        if let Err(e) = manager.save_to(&registry) {
+            println!("[-] {}", e);
+        }
+    }
+}
+
+/// Start ready pipeline stages (thermally gated like single jobs) and record finished ones
+fn advance_pipelines(manager: &JobManager, scheduler: &ThermalScheduler, limit: &ConcurrencyLimit, readings: &[thermal::GpuReading], bus: &Bus) -> bool {
+    let path = eryzaa_jobs::pipeline::default_store_path();
+    let mut store = PipelineStore::load_from(&path);
+    let cache = ResultCache::open_default();
+    let mut changed = false;
+    
+    for run in store.runs.iter_mut().filter(|run| !run.is_finished()) {
+        let before = run.states.clone();
+        let mut can_start = |spec: &eryzaa_jobs::JobSpec| admit_spec("stage", spec, manager, scheduler, limit, readings);
+        
+        match manager.advance_pipeline(run, Some(&cache), &mut can_start) {
+            Ok(false) => continue,
+            Ok(true) => changed = true,
+            Err(e) => {
+                println!("[-] Pipeline {}: {}", run.run_id, e);
+                continue;
+            }
+        }
+        
+        for (stage, state) in &run.states {
+            if before.get(stage) != Some(state) {
+                println!("[*] Pipeline {} stage '{}': {:?}", run.run_id, stage, state);
+                bus.publish(Event::PipelineStageChanged {
+                    run_id: run.run_id.clone(),
+                    stage: stage.clone(),
+                    state: format!("{:?}", state),
+                });
+            }
+        }
+        if run.is_finished() {
+            println!("[{}] Pipeline {} ({}) finished", if run.succeeded() { "+" } else { "-" }, run.run_id, run.pipeline.name);
+        }
+    }
+    
+    if changed {
+        if let Err(e) = store.save_to(&path) {
+            println!("[-] {}", e);
+        }
+    }
+    changed
+}
+
+/// Start sweep trials on their targets within budget and collect finished trials' metrics
+fn advance_sweeps(manager: &JobManager, scheduler: &ThermalScheduler, limit: &ConcurrencyLimit, readings: &[thermal::GpuReading]) -> bool {
+    let path = eryzaa_jobs::sweep::default_store_path();
+    let mut store = SweepStore::load_from(&path);
+    let mut changed = false;
+    
+    for run in store.runs.iter_mut().filter(|run| !run.is_finished()) {
+        let before = run.trials.clone();
+        let mut can_start = |spec: &eryzaa_jobs::JobSpec| admit_spec("trial", spec, manager, scheduler, limit, readings);
+        
+        match manager.advance_sweep(run, &mut can_start) {
+            Ok(false) => continue,
+            Ok(true) => changed = true,
+            Err(e) => {
+                println!("[-] Sweep {}: {}", run.run_id, e);
+                continue;
+            }
+        }
+        
+        for (trial, old) in run.trials.iter().zip(&before) {
+            if trial.state != old.state {
+                println!("[*] Sweep {} trial {}: {:?}", run.run_id, trial.index, trial.state);
+            }
+        }
+        if run.is_finished() {
+            let metric = run.sweep().metric;
+            match run.best() {
+                Some(best) => println!("[+] Sweep {} finished; best trial {} with {} = {} ({:?})", run.run_id, best.index, metric, best.metrics[&metric], best.params),
+                None => println!("[-] Sweep {} finished without a successful trial", run.run_id),
+            }
+        }
+    }
+    
+    if changed {
+        if let Err(e) = store.save_to(&path) {
+            println!("[-] {}", e);
+        }
+    }
+    changed
+}
+
+/// Whether a pipeline stage or sweep trial may start now: a free job slot and cool enough GPUs
+fn admit_spec(kind: &str, spec: &eryzaa_jobs::JobSpec, manager: &JobManager, scheduler: &ThermalScheduler, limit: &ConcurrencyLimit, readings: &[thermal::GpuReading]) -> bool {
+    if !limit.admits(manager.running_count()) {
+        println!("[*] Deferring {} {}: all {} job slots in use", kind, spec.name, limit.max_concurrent_jobs);
+        return false;
+    }
+    let gpus = match spec.gpus.as_deref() {
+        Some("all") => 1,
+        Some(count) => count.parse().unwrap_or(0),
+        None => 0,
+    };
+    match scheduler.decide(gpus, readings, Instant::now()) {
+        StartDecision::Start => {
+            scheduler.mark_started(gpus, Instant::now());
+            true
+        }
+        StartDecision::Defer(reason) => {
+            println!("[*] Deferring {} {}: {}", kind, spec.name, reason);
+            false
+        }
+    }
+}
+
+fn concurrency_limit() -> ConcurrencyLimit {
+    // Saved from the rental GUI; the environment takes precedence
+    let mut limit = ConcurrencyLimit::load_from(&eryzaa_jobs::default_registry_path().with_file_name("concurrency.json"));
+    
+    if let Some(max) = env::var("ERYZAA_MAX_CONCURRENT_JOBS").ok().and_then(|m| m.parse().ok()) {
+        limit.max_concurrent_jobs = max;
+    }
+    
+    limit
+}
+
+fn pause_policy() -> PausePolicy {
+    let mut policy = PausePolicy::default();
+    
+    if let Some(hours) = env::var("ERYZAA_MAX_PAUSE_HOURS").ok().and_then(|h| h.parse().ok()) {
+        policy.max_pause = chrono::Duration::hours(hours);
+    }
+    
+    policy
+}
+
+/// Run new pending jobs past the owner's admission rules; returns whether any changed
+fn review_admissions(manager: &JobManager, bus: &Bus) -> bool {
+    let rules = AdmissionRules::load_from(&eryzaa_jobs::default_registry_path().with_file_name("admission.json"));
+    let access = AccessPolicy::load_from(&access_policy_path());
+    let mut deposits = DepositLedger::load_from(&deposits_path());
+    let mut deposits_changed = false;
+    let payee = payout_wallet();
+    let chain = ChainRpc::from_env();
+    let issuers = reputation_issuers();
+    let now = chrono::Utc::now().timestamp() as u64;
+    let blocklist = BlocklistFeed::load_from(&abuse::default_feed_path());
+    let offer = TrialOffer::load_from(&trial_offer_path());
+    let mut trials = TrialLedger::load_from(&trials_path());
+    let mut trials_changed = false;
+    let mut changed = false;
+    // Scanned once, and only when there is a GPU job to check
+    let mut gpu_stack = None;
+    
+    for job in manager.list_jobs() {
+        if job.status != JobStatus::Pending || job.admission != AdmissionState::Unreviewed {
+            continue;
+        }
+        
+        // Clients other renters reported never reach the owner's rules
+        if let Some(entry) = blocklist.blocked(&job.client_id) {
+            let reason = format!("Client {} is on the shared blocklist: {}", job.client_id, entry.reason);
+            println!("[-] Rejected job {}: {}", job.job_id, reason);
+            bus.publish(Event::JobRejected { job_id: job.job_id.clone(), rule: "blocklist".to_string() });
+            if let Err(e) = manager.update_status(&job.job_id, JobStatus::Failed(reason)) {
+                println!("[-] {}", e);
+            }
+            changed = true;
+            continue;
+        }
+        
+        // Refuse images this driver cannot run before they crash at their first CUDA call
+        if job.gpus > 0 {
+            let stack = gpu_stack.get_or_insert_with(|| HardwareSnapshot::scan().gpu_stack());
+            let required = cuda::image_requirement(&job.image);
+            if let Err(reason) = cuda::check(&job.image, required.as_deref(), stack.as_ref()) {
+                println!("[-] Rejected job {}: {}", job.job_id, reason);
+                bus.publish(Event::JobRejected { job_id: job.job_id.clone(), rule: "cuda-compatibility".to_string() });
+                if let Err(e) = manager.update_status(&job.job_id, JobStatus::Failed(reason)) {
+                    println!("[-] {}", e);
+                }
+                changed = true;
+                continue;
+            }
+        }
+        
+        // Free trials are held to the node's own terms, once per client, and need no deposit
+        let trial = match job.trial {
+            Some(_) => match trials.check(offer, &job.client_id, job.gpus) {
+                Ok(terms) => Some(terms),
+                Err(reason) => {
+                    println!("[-] Rejected job {}: {}", job.job_id, reason);
+                    bus.publish(Event::JobRejected { job_id: job.job_id.clone(), rule: "trial".to_string() });
+                    if let Err(e) = manager.update_status(&job.job_id, JobStatus::Failed(reason)) {
+                        println!("[-] {}", e);
+                    }
+                    changed = true;
+                    continue;
+                }
+            },
+            None => None,
+        };
+        
+        // Reputation counts only when an issuer the owner trusts vouches for it
+        let reputation = eryzaa_payments::verified_reputation(job.reputation.as_ref(), &job.client_id, &issuers, now);
+        
+        // Clients below the advertised reputation bar need a deposit before the owner's rules;
+        // one sent with the job counts once its transfer to the payout wallet is on chain
+        let posted = deposits.deposits.len();
+        let secured = match trial {
+            Some(_) => Ok(None),
+            None => deposits.secure(&access, &job.client_id, reputation, job.deposit.as_ref(), payee.as_deref(), &chain, now),
+        };
+        deposits_changed |= deposits.deposits.len() != posted;
+        let deposit = match secured {
+            Ok(deposit) => deposit,
+            Err(reason) => {
+                println!("[-] Rejected job {}: {}", job.job_id, reason);
+                bus.publish(Event::JobRejected { job_id: job.job_id.clone(), rule: "access".to_string() });
+                if let Err(e) = manager.update_status(&job.job_id, JobStatus::Failed(reason)) {
+                    println!("[-] {}", e);
+                }
+                changed = true;
+                continue;
+            }
+        };
+        
+        let request = AdmissionRequest {
+            client_id: job.client_id.clone(),
+            client_reputation: reputation,
+            gpus: job.gpus,
+            memory_gb: 0,
+            price_per_hour: if trial.is_some() { None } else { job.offered_price },
+            deposit: deposit.as_ref().map(|(_, amount)| *amount),
+            image: job.image.clone(),
+            hour: chrono::Local::now().hour(),
+        };
+        let evaluation = rules.evaluate(&request);
+        let rule = evaluation.rule.unwrap_or_else(|| "default".to_string());
+        let job_id = job.job_id.clone();
+        let result = match evaluation.decision {
+            AdmissionDecision::Accept => {
+                bus.publish(Event::JobAdmitted { job_id });
+                manager.set_admission(&job.job_id, AdmissionState::Admitted)
+            }
+            AdmissionDecision::Reject => {
+                println!("[-] Rejected job {} (rule: {})", job.job_id, rule);
+                bus.publish(Event::JobRejected { job_id, rule: rule.clone() });
+                manager.update_status(&job.job_id, JobStatus::Failed(format!("Rejected by admission rule '{}'", rule)))
+            }
+            AdmissionDecision::AskOwner => {
+                println!("[*] Job {} is waiting for owner approval (rule: {})", job.job_id, rule);
+                bus.publish(Event::JobAwaitingOwner { job_id, rule: rule.clone() });
+                manager.set_admission(&job.job_id, AdmissionState::AwaitingOwner(rule))
+            }
+        };
+        if let Err(e) = result {
+            println!("[-] {}", e);
+        }
+        if let (Some((deposit_id, amount)), false) = (&deposit, evaluation.decision == AdmissionDecision::Reject) {
+            match deposits.bind(deposit_id, &job.job_id) {
+                Ok(()) => println!("[*] Job {} is secured by deposit {} ({:.2} AVAX)", job.job_id, deposit_id, amount),
+                Err(e) => println!("[-] {}", e),
+            }
+            deposits_changed = true;
+        }
+        if let (Some(terms), false) = (trial, evaluation.decision == AdmissionDecision::Reject) {
+            trials.grant(&job.client_id, &job.job_id, chrono::Utc::now().timestamp() as u64);
+            trials_changed = true;
+            match manager.begin_trial(&job.job_id, terms) {
+                Ok(()) => println!("[*] Job {} runs as {}'s {}", job.job_id, job.client_id, terms.label().to_lowercase()),
+                Err(e) => println!("[-] {}", e),
+            }
+        }
+        changed = true;
+    }
+    
+    if deposits_changed {
+        if let Err(e) = deposits.save_to(&deposits_path()) {
+            println!("[-] {}", e);
+        }
+    }
+    if trials_changed {
+        if let Err(e) = trials.save_to(&trials_path()) {
+            println!("[-] {}", e);
+        }
+    }
+    changed
+}
+
+/// Refund the deposits of jobs that ended normally; failed jobs keep theirs for the owner to settle
+fn settle_deposits(manager: &JobManager) {
+    let mut deposits = DepositLedger::load_from(&deposits_path());
+    let now = chrono::Utc::now().timestamp() as u64;
+    let mut changed = false;
+    for job in manager.list_jobs() {
+        if !matches!(job.status, JobStatus::Completed | JobStatus::Stopped) {
+            continue;
+        }
+        if let Some(amount) = deposits.refund_job(&job.job_id, now) {
+            println!("[+] Refunded the {:.2} AVAX deposit of job {} to {}", amount, job.job_id, job.client_id);
+            changed = true;
+        }
+    }
+    if changed {
+        if let Err(e) = deposits.save_to(&deposits_path()) {
+            println!("[-] {}", e);
+        }
+    }
+}
+
+fn access_policy_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("access.json")
+}
+
+/// Wallet clients pay deposits to: `ERYZAA_PAYOUT_WALLET`, else the active profile's wallet
+fn payout_wallet() -> Option<String> {
+    std::env::var("ERYZAA_PAYOUT_WALLET")
+        .ok()
+        .or_else(|| eryzaa_jobs::profiles::active_profile().wallet_address.clone())
+        .filter(|wallet| !wallet.trim().is_empty())
+}
+
+/// Public keys of the reputation issuers the owner trusts, comma-separated in `ERYZAA_REPUTATION_ISSUERS`
+fn reputation_issuers() -> Vec<String> {
+    std::env::var("ERYZAA_REPUTATION_ISSUERS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|issuer| issuer.trim().to_string())
+        .filter(|issuer| !issuer.is_empty())
+        .collect()
+}
+
+fn deposits_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("deposits.json")
+}
+
+fn trial_offer_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("trial.json")
+}
+
+fn trials_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("trials.json")
+}
+
+fn energy_model() -> EnergyModel {
+    // Saved from the rental GUI; environment variables take precedence
+    let mut model = EnergyModel::load_from(&eryzaa_jobs::default_registry_path().with_file_name("energy.json"));
+    
+    if let Some(tdp) = env::var("ERYZAA_CPU_TDP_W").ok().and_then(|t| t.parse().ok()) {
+        model.cpu_tdp_watts = tdp;
+    }
+    
+    // Grid carbon intensity in gCO2/kWh, reported on receipts
+    if let Some(intensity) = env::var("ERYZAA_CARBON_INTENSITY").ok().and_then(|c| c.parse().ok()) {
+        model.carbon_intensity_g_per_kwh = Some(intensity);
+    }
+    
+    model
+}
+
+fn meter_energy(meter: &mut EnergyMeter, bus: &Bus) {
+    let registry = eryzaa_jobs::default_registry_path();
+    let manager = match JobManager::load_from(&registry) {
+        Ok(manager) => manager,
+        Err(e) => {
+            println!("[-] {}", e);
+            return;
+        }
+    };
+    
+    let jobs: Vec<MeteredJob> = manager
+        .list_jobs()
+        .into_iter()
+        .filter(|job| job.status == JobStatus::Running)
+        .map(|job| MeteredJob { job_id: job.job_id, container_name: job.container_name, gpus: job.gpus })
+        .collect();
+    let readings = thermal::read_gpus().unwrap_or_default();
+    let cpu = energy::container_cpu_percent().unwrap_or_default();
+    
+    let used = meter.sample(&jobs, &readings, &cpu, Instant::now());
+    if used.is_empty() {
+        return;
+    }
+    for (job_id, wh) in used {
+        let _ = manager.add_energy(&job_id, wh, meter.model.carbon_intensity_g_per_kwh);
+        bus.publish(Event::EnergyMetered { job_id, wh });
+    }
+    
+    if let Err(e) = manager.save_to(&registry) {
+        println!("[-] {}", e);
+    }
+    if let Err(e) = manager.publish_receipts(std::path::Path::new(eryzaa_jobs::receipt::PUBLISH_DIR)) {
+        println!("[-] Failed to publish receipts: {}", e);
+    }
+}
+
 fn check_gpu_access() {
     println!("
 === GPU Status ===");
@@ -173,35 +1492,53 @@ Available Tools:");
     }
 }
 
+fn service_set() -> ServiceSet {
+    // Saved next to the job registry; edit it to enable the caching proxy or TensorBoard
+    ServiceSet::load_from(&eryzaa_jobs::default_registry_path().with_file_name("services.json"))
+}
+
+fn service_running(name: &str) -> bool {
+    service_set().service(name).map(|service| service.is_healthy()).unwrap_or(false)
+}
+
 fn is_zerotier_running() -> bool {
-    Command::new("pgrep")
-        .arg("zerotier-one")
-        .output()
-        .map(|output| output.status.success())
-        .unwrap_or(false)
+    service_running("zerotier-one")
 }
 
 fn is_ssh_running() -> bool {
-    Command::new("pgrep")
-        .arg("sshd")
-        .output()
-        .map(|output| output.status.success())
-        .unwrap_or(false)
+    service_running("sshd")
 }
 
-fn restart_zerotier() {
-    let _ = Command::new("zerotier-one").arg("-d").status();
-    thread::sleep(Duration::from_secs(3));
+fn supervise_services(supervisor: &mut Supervisor) {
+    let reports = match supervisor.tick() {
+        Ok(reports) => reports,
+        Err(e) => {
+            println!("[-] Services not supervised: {}", e);
+            return;
+        }
+    };
     
-    if let Ok(network_id) = env::var("ZEROTIER_NETWORK_ID") {
-        let _ = Command::new("zerotier-cli").args(&["join", &network_id]).status();
+    for report in reports {
+        match report.status {
+            ServiceStatus::Running => {}
+            ServiceStatus::Restarted => {
+                println!("[!] {} was down and has been restarted", report.name);
+                
+                // A fresh zerotier-one has to rejoin the network
+                if report.name == "zerotier-one" {
+                    thread::sleep(Duration::from_secs(3));
+                    if let Ok(network_id) = env::var("ZEROTIER_NETWORK_ID") {
+                        let _ = Command::new("zerotier-cli").args(&["join", &network_id]).status();
+                    }
+                }
+            }
+            ServiceStatus::Failed(e) => println!("[-] {} is down: {}", report.name, e),
+            ServiceStatus::Blocked(reason) => println!("[*] {} is down, {}", report.name, reason),
+            ServiceStatus::GaveUp => println!("[-] {} keeps failing, no more restarts for now", report.name),
+        }
     }
 }
 
-fn restart_ssh() {
-    let _ = Command::new("service").args(&["ssh", "restart"]).status();
-}
-
 // Restart the application
 fn restart_application() {
     let current_exe = env::current_exe().expect("Failed to get current executable path");
@@ -454,14 +1791,11 @@ fn install_zerotier() {
                 println!("ZeroTier installed successfully!");
                 
                 // Start and enable the service
-                let _ = Command::new("sudo")
-                    .args(&["systemctl", "enable", "zerotier-one"])
-                    .status();
-                let _ = Command::new("sudo")
-                    .args(&["systemctl", "start", "zerotier-one"])
-                    .status();
-                    
-                println!("ZeroTier service started and enabled.");
+                match service_set().service("zerotier-one").map(|service| service.enable()) {
+                    Some(Ok(())) => println!("ZeroTier service started and enabled."),
+                    Some(Err(e)) => println!("Failed to start ZeroTier: {}", e),
+                    None => println!("ZeroTier is not a configured service; start it manually."),
+                }
             }
             Ok(_) => {
                 println!("ZeroTier installation may have failed. Please install manually:");
@@ -635,23 +1969,15 @@ fn start_ssh_server() {
             .args(&["-Command", "Set-Service -Name sshd -StartupType 'Automatic' -ErrorAction SilentlyContinue"])
             .status();
         
-        // Configure Windows Firewall - create comprehensive rules
+        // Configure Windows Firewall - only allow SSH from the overlay
         println!("Configuring Windows Firewall for SSH...");
-        let _ = Command::new("powershell")
-            .args(&["-Command", "Remove-NetFirewallRule -DisplayName 'OpenSSH*' -ErrorAction SilentlyContinue"])
-            .status();
-        let _ = Command::new("powershell")
-            .args(&["-Command", "New-NetFirewallRule -DisplayName 'OpenSSH-Server-In-TCP' -Direction Inbound -Protocol TCP -LocalPort 22 -Action Allow -Profile Any -ErrorAction SilentlyContinue"])
-            .status();
-        let _ = Command::new("powershell")
-            .args(&["-Command", "New-NetFirewallRule -DisplayName 'SSH-Remote-Access' -Direction Inbound -Protocol TCP -LocalPort 22 -Action Allow -RemoteAddress Any -ErrorAction SilentlyContinue"])
-            .status();
-        
-        // Disable Windows Defender Firewall temporarily for testing (can be re-enabled manually)
-        println!("Temporarily disabling Windows Firewall for SSH testing...");
-        let _ = Command::new("powershell")
-            .args(&["-Command", "Set-NetFirewallProfile -Profile Domain,Public,Private -Enabled False -ErrorAction SilentlyContinue"])
-            .status();
+        let firewall_ok = match firewall_policy().apply_and_verify() {
+            Ok(_) => true,
+            Err(e) => {
+                println!("⚠ Failed to configure Windows Firewall: {}", e);
+                false
+            }
+        };
         
         thread::sleep(Duration::from_secs(2));
         
@@ -693,8 +2019,9 @@ fn start_ssh_server() {
                 
                 if ssh_running {
                     println!("✓ SSH server configured for remote access");
-                    println!("⚠ Windows Firewall temporarily disabled for testing");
-                    println!("  You can re-enable it with: Set-NetFirewallProfile -Profile Domain,Public,Private -Enabled True");
+                    if firewall_ok {
+                        println!("✓ Windows Firewall restricts SSH to the ZeroTier network");
+                    }
                 } else {
                     println!("⚠ SSH service not running properly. Try restarting as Administrator");
                 }
@@ -768,31 +2095,14 @@ fn start_ssh_server() {
         }
         
         // Enable and start SSH service
-        let _ = Command::new("sudo")
-            .args(&["systemctl", "enable", "ssh"])
-            .status();
-        let _ = Command::new("sudo")
-            .args(&["systemctl", "start", "ssh"])
-            .status();
-        
-        // Also try sshd (different distributions use different service names)
-        let _ = Command::new("sudo")
-            .args(&["systemctl", "enable", "sshd"])
-            .status();
-        let _ = Command::new("sudo")
-            .args(&["systemctl", "start", "sshd"])
-            .status();
-        
-        // Configure firewall if ufw is available
-        let ufw_check = Command::new("which")
-            .arg("ufw")
-            .output();
+        if let Some(Err(e)) = service_set().service("sshd").map(|service| service.enable()) {
+            println!("⚠ Failed to start SSH: {}", e);
+        }
         
-        if ufw_check.is_ok() && ufw_check.unwrap().status.success() {
-            println!("Configuring UFW firewall for SSH...");
-            let _ = Command::new("sudo")
-                .args(&["ufw", "allow", "ssh"])
-                .status();
+        // Only allow SSH from the overlay
+        println!("Configuring nftables firewall for SSH...");
+        if let Err(e) = firewall_policy().apply_and_verify() {
+            println!("⚠ Failed to configure firewall: {}", e);
         }
         
         // Get current user
@@ -828,23 +2138,7 @@ fn start_ssh_server() {
         }
         
         // Check SSH service status
-        let service_status = Command::new("systemctl")
-            .args(&["is-active", "ssh"])
-            .output();
-        
-        let service_active = if let Ok(output) = service_status {
-            String::from_utf8_lossy(&output.stdout).trim() == "active"
-        } else {
-            // Try sshd if ssh didn't work
-            let sshd_status = Command::new("systemctl")
-                .args(&["is-active", "sshd"])
-                .output();
-            if let Ok(output) = sshd_status {
-                String::from_utf8_lossy(&output.stdout).trim() == "active"
-            } else {
-                false
-            }
-        };
+        let service_active = is_ssh_running();
         
         if service_active {
             println!("✓ SSH server is running and configured for remote access");
@@ -0,0 +1,554 @@
+//! Job user accounts on this machine
+//! Creates and deletes the local users jobs sign in as, with the platform's own tools: see
+//! `unix` and `windows`. Names and credentials are checked here, before any of them run.
+
+use serde::{Deserialize, Serialize};
+
+use log::info;
+
+use crate::bandwidth::{BandwidthCaps, UserTraffic};
+use crate::ca::{self, CertificateAuthority};
+use crate::docker::DockerAccess;
+use crate::config::SshManagerConfig;
+use crate::error::SshManagerError;
+use crate::grants::{self, AccessScope, Grant};
+use crate::login;
+use crate::quota::QuotaMethod;
+use crate::sources::SourceRestriction;
+
+#[cfg(unix)]
+use crate::unix as platform;
+#[cfg(windows)]
+use crate::windows as platform;
+
+#[cfg(unix)]
+pub use crate::unix::privileged;
+
+/// Prefix of every job user's name, unless the node's config sets another
+pub const JOB_USER_PREFIX: &str = "job_";
+
+/// Prefix of this node's job users, see `SshManagerConfig::system`
+pub fn job_user_prefix() -> &'static str {
+    &SshManagerConfig::system().username_prefix
+}
+
+/// How a job user signs in
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Credential {
+    Password(String),
+    PublicKey(String), // Password login is disabled for the account
+    Certificate,       // Only keys certified by the host CA, see `ca`; no password either
+}
+
+/// How much of the machine a job user gets when signed in
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IsolationLevel {
+    #[default]
+    FullShell, // bash, and docker as `DockerAccess` says; the docker group amounts to root on the host
+    RestrictedShell, // rbash with a handful of read-only tools, no forwarding
+    Chroot,          // SFTP only, locked into the home directory
+    ContainerOnly,   // Every login lands in the job's container
+}
+
+impl IsolationLevel {
+    pub const ALL: [Self; 4] = [Self::FullShell, Self::RestrictedShell, Self::Chroot, Self::ContainerOnly];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::FullShell => "Full shell",
+            Self::RestrictedShell => "Restricted shell (rbash)",
+            Self::Chroot => "Files only (chroot to home)",
+            Self::ContainerOnly => "Job container only",
+        }
+    }
+
+    /// Login shell of the account
+    pub fn shell(&self) -> &'static str {
+        match self {
+            Self::FullShell => "/bin/bash",
+            Self::RestrictedShell => "/bin/rbash",
+            Self::Chroot => "/usr/sbin/nologin", // internal-sftp runs inside sshd
+            Self::ContainerOnly => "/bin/sh",    // Runs the forced command
+        }
+    }
+
+    /// Whether the user gets a container engine, see `DockerAccess`
+    pub fn docker_access(&self) -> bool {
+        matches!(self, Self::FullShell | Self::ContainerOnly)
+    }
+
+    /// sshd settings for `username`, as a Match block of its own drop-in, with `policy` on top
+    pub fn sshd_match_block(&self, username: &str, job_id: &str, policy: &SshPolicy) -> Result<Option<String>, SshManagerError> {
+        let mut settings = match self {
+            Self::FullShell => vec![],
+            // `ssh host cmd` skips the login profile, so sshd hands out the restricted PATH itself
+            Self::RestrictedShell => vec![format!("SetEnv PATH=/home/{}/bin", username), "DisableForwarding yes".to_string()],
+            Self::Chroot => vec![
+                "ChrootDirectory %h".to_string(),
+                format!("ForceCommand internal-sftp -d /{}", CHROOT_WORK_DIR),
+                "DisableForwarding yes".to_string(),
+            ],
+            Self::ContainerOnly => {
+                if !grants::command_safe(job_id) {
+                    return Err(SshManagerError::InvalidInput(format!("Job id '{}' cannot be used in a forced command", job_id)));
+                }
+                // A terminal only when the client asked for one, so `ssh host cmd` works too
+                vec![
+                    format!(
+                        "ForceCommand exec docker exec -i$([ -t 0 ] && echo t) {} sh -c \"${{SSH_ORIGINAL_COMMAND:-command -v bash >/dev/null && exec bash -l || exec sh -l}}\"",
+                        grants::container_name(job_id)
+                    ),
+                    "DisableForwarding yes".to_string(),
+                ]
+            }
+        };
+        settings.extend(policy.sshd_settings(*self)?);
+        if settings.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(format!("Match User {}\n{}\n", username, settings.iter().map(|line| format!("    {}", line)).collect::<Vec<_>>().join("\n"))))
+    }
+}
+
+/// What a job user may do over SSH beyond its isolation level, enforced by sshd per user
+///
+/// The default leaves sshd's own settings alone; `untrusted` turns every kind of forwarding off.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SshPolicy {
+    #[serde(default)]
+    pub no_tcp_forwarding: bool, // Also refuses -L/-R/-D tunnels and stream-local sockets
+    #[serde(default)]
+    pub no_x11_forwarding: bool,
+    #[serde(default)]
+    pub no_agent_forwarding: bool,
+    #[serde(default)]
+    pub sftp_only: bool, // File transfer only, no shell or commands
+    #[serde(default)]
+    pub max_sessions: Option<u32>, // Shells, commands and SFTP sessions per connection
+    #[serde(default)]
+    pub no_password_auth: bool, // Keys and certificates only, whatever the account's password
+}
+
+impl SshPolicy {
+    /// For jobs of clients the owner does not know
+    pub fn untrusted() -> Self {
+        Self { no_tcp_forwarding: true, no_x11_forwarding: true, no_agent_forwarding: true, sftp_only: false, max_sessions: Some(4), no_password_auth: false }
+    }
+
+    /// sshd settings for a user isolated at `isolation`, leaving out what the isolation already does
+    fn sshd_settings(&self, isolation: IsolationLevel) -> Result<Vec<String>, SshManagerError> {
+        let mut settings = vec![];
+        // SFTP only turns off all forwarding below
+        if isolation == IsolationLevel::FullShell && !self.sftp_only {
+            if self.no_tcp_forwarding {
+                settings.push("AllowTcpForwarding no".to_string());
+                settings.push("AllowStreamLocalForwarding no".to_string());
+                settings.push("PermitTunnel no".to_string());
+            }
+            if self.no_x11_forwarding {
+                settings.push("X11Forwarding no".to_string());
+            }
+            if self.no_agent_forwarding {
+                settings.push("AllowAgentForwarding no".to_string());
+            }
+        }
+        if self.sftp_only {
+            match isolation {
+                IsolationLevel::FullShell => {
+                    settings.push("ForceCommand internal-sftp".to_string());
+                    settings.push("DisableForwarding yes".to_string());
+                }
+                IsolationLevel::RestrictedShell => settings.push("ForceCommand internal-sftp".to_string()), // No forwarding already
+                IsolationLevel::Chroot => {} // SFTP only already
+                IsolationLevel::ContainerOnly => {
+                    return Err(SshManagerError::InvalidInput("A container-only user cannot be SFTP only".to_string()));
+                }
+            }
+        }
+        if let Some(sessions) = self.max_sessions {
+            if sessions == 0 {
+                return Err(SshManagerError::InvalidInput("MaxSessions must be at least 1".to_string()));
+            }
+            settings.push(format!("MaxSessions {}", sessions));
+        }
+        if self.no_password_auth {
+            settings.push("PasswordAuthentication no".to_string());
+            settings.push("KbdInteractiveAuthentication no".to_string());
+        }
+        Ok(settings)
+    }
+}
+
+/// What a job's user is for
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessMode {
+    #[default]
+    Shell, // Signs in at the node's isolation level
+    SftpOnly, // Data drop: sshd runs internal-sftp, chrooted with files in `CHROOT_WORK_DIR` at the chroot level
+}
+
+impl AccessMode {
+    /// The mode a user isolated at `isolation` ends up with under `policy`
+    pub fn of(isolation: IsolationLevel, policy: &SshPolicy) -> Self {
+        match isolation {
+            IsolationLevel::Chroot => Self::SftpOnly,
+            _ if policy.sftp_only => Self::SftpOnly,
+            _ => Self::Shell,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Shell => "Shell",
+            Self::SftpOnly => "SFTP only (data drop)",
+        }
+    }
+
+    /// Program the client connects with
+    pub fn client_program(&self) -> &'static str {
+        match self {
+            Self::Shell => "ssh",
+            Self::SftpOnly => "sftp",
+        }
+    }
+}
+
+/// Writable directory inside a chrooted home, which itself must belong to root
+pub const CHROOT_WORK_DIR: &str = "work";
+
+/// Tools a restricted shell may run, linked into its ~/bin when installed
+pub const RESTRICTED_COMMANDS: [&str; 10] = ["ls", "cat", "head", "tail", "grep", "wc", "df", "du", "free", "nvidia-smi"];
+
+/// CPU and memory caps on everything a job user runs
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ResourceCaps {
+    pub cpu_quota_percent: Option<u32>, // 100 = one core
+    pub memory_max_mb: Option<u64>,
+    #[serde(default)]
+    pub allowed_cpus: Vec<u32>, // Cores the user's processes may run on, every core when empty
+}
+
+impl ResourceCaps {
+    pub fn is_empty(&self) -> bool {
+        self.cpu_quota_percent.is_none() && self.memory_max_mb.is_none() && self.allowed_cpus.is_empty()
+    }
+
+    /// systemd properties for the user's slice
+    pub fn slice_properties(&self) -> Vec<String> {
+        let mut properties = vec![];
+        if let Some(percent) = self.cpu_quota_percent {
+            properties.push(format!("CPUQuota={}%", percent));
+        }
+        if let Some(mb) = self.memory_max_mb {
+            properties.push(format!("MemoryMax={}M", mb));
+        }
+        if !self.allowed_cpus.is_empty() {
+            let cpus: Vec<String> = self.allowed_cpus.iter().map(u32::to_string).collect();
+            properties.push(format!("AllowedCPUs={}", cpus.join(",")));
+        }
+        properties
+    }
+}
+
+/// Check that `username` is a job user's name, the node's prefix and eight letters or digits
+pub fn validate_username(username: &str) -> Result<(), SshManagerError> {
+    SshManagerConfig::system().check_username(username)
+}
+
+/// Create a job user of job `job_id` with a private home directory, signing in with `credential`
+/// and reaching containers as `docker` allows
+pub fn create_user(
+    username: &str,
+    credential: &Credential,
+    isolation: IsolationLevel,
+    policy: &SshPolicy,
+    job_id: &str,
+    docker: DockerAccess,
+) -> Result<(), SshManagerError> {
+    validate_username(username)?;
+    let credential = validate_credential(credential)?;
+    // Checked before the account exists
+    let match_block = isolation.sshd_match_block(username, job_id, policy)?;
+    docker.check(isolation)?;
+    platform::create_user(username, &credential, isolation, match_block.as_deref(), docker, job_id)?;
+    let signs_in_with = match credential {
+        Credential::Password(_) => "password",
+        Credential::PublicKey(_) => "public key",
+        Credential::Certificate => "certificate",
+    };
+    info!("Created system user '{}' with {} and {}", username, signs_in_with, isolation.label().to_lowercase());
+    Ok(())
+}
+
+/// The credential as it will be installed, or why it cannot be
+fn validate_credential(credential: &Credential) -> Result<Credential, SshManagerError> {
+    match credential {
+        Credential::Password(password) if password.is_empty() || password.contains([':', '\n']) => {
+            Err(SshManagerError::InvalidInput("Password must be non-empty without ':' or line breaks".to_string()))
+        }
+        Credential::Password(password) => Ok(Credential::Password(password.clone())),
+        Credential::PublicKey(key) => crate::validate_public_key(key).map(Credential::PublicKey),
+        Credential::Certificate => Ok(Credential::Certificate),
+    }
+}
+
+/// Rewrite the sshd Match blocks of an existing job user for `policy` and `sources`, and reload sshd
+pub fn set_policy(username: &str, job_id: &str, isolation: IsolationLevel, policy: &SshPolicy, sources: &SourceRestriction) -> Result<(), SshManagerError> {
+    validate_username(username)?;
+    sources.validate()?;
+    let match_block = isolation.sshd_match_block(username, job_id, policy)?;
+    let match_block = match (sources.sshd_match_blocks(username), match_block) {
+        (Some(restriction), Some(block)) => Some(format!("{}{}", restriction, block)),
+        (restriction, block) => restriction.or(block),
+    };
+    platform::write_match_block(username, match_block.as_deref())?;
+    info!("Set the SSH policy of system user '{}' to {:?}", username, policy);
+    if !sources.is_empty() {
+        info!("Restricted system user '{}': {}", username, sources.summary());
+    }
+    Ok(())
+}
+
+/// Stop a job user's processes and delete the user with its home directory
+pub fn delete_user(username: &str) -> Result<(), SshManagerError> {
+    validate_username(username)?;
+    platform::delete_user(username)?;
+    info!("Deleted system user '{}'", username);
+    Ok(())
+}
+
+/// Lock a job user out and end its sessions, keeping its home for when it is resumed
+pub fn suspend_user(username: &str) -> Result<(), SshManagerError> {
+    validate_username(username)?;
+    platform::suspend_user(username)?;
+    info!("Suspended system user '{}'", username);
+    Ok(())
+}
+
+/// End a job user's logins without locking it out
+pub fn end_sessions(username: &str) -> Result<(), SshManagerError> {
+    validate_username(username)?;
+    platform::end_sessions(username)?;
+    info!("Ended the sessions of system user '{}'", username);
+    Ok(())
+}
+
+/// Let a suspended job user sign in again
+pub fn resume_user(username: &str) -> Result<(), SshManagerError> {
+    validate_username(username)?;
+    platform::resume_user(username)?;
+    info!("Resumed system user '{}'", username);
+    Ok(())
+}
+
+/// Cap what a job user's processes may use, lifting the caps when `caps` is empty
+pub fn limit_user(username: &str, caps: &ResourceCaps) -> Result<(), SshManagerError> {
+    validate_username(username)?;
+    platform::limit_user(username, caps)?;
+    info!("Limited system user '{}' to {:?}", username, caps);
+    Ok(())
+}
+
+/// Cap the home of a job user at `limit_mb`; returns how the cap is enforced
+pub fn quota_user(username: &str, limit_mb: u64) -> Result<QuotaMethod, SshManagerError> {
+    validate_username(username)?;
+    if limit_mb == 0 {
+        return Err(SshManagerError::InvalidInput("Disk quota must be at least 1 MB".to_string()));
+    }
+    let method = platform::quota_user(username, limit_mb)?;
+    info!("Capped the home of system user '{}' at {} MB with a {}", username, limit_mb, method.label().to_lowercase());
+    Ok(method)
+}
+
+/// Cap the bandwidth of every job user in `usernames`, forgetting the traffic of any other
+pub fn throttle_users(usernames: &[String], caps: BandwidthCaps) -> Result<(), SshManagerError> {
+    for username in usernames {
+        validate_username(username)?;
+    }
+    platform::throttle_users(usernames, caps)?;
+    info!("Limited the bandwidth of {} system users to {:?}", usernames.len(), caps);
+    Ok(())
+}
+
+/// Bytes each job user has received and sent since it was first throttled
+pub fn user_traffic() -> Result<Vec<UserTraffic>, SshManagerError> {
+    platform::user_traffic()
+}
+
+/// Give `username` the job owner's key and the keys of everyone the owner granted access
+///
+/// Every key is checked again here, since the privileged service passes them on as they came.
+pub fn authorize_keys(username: &str, job_id: &str, owner_key: Option<&str>, grants: &[Grant], sources: &SourceRestriction) -> Result<(), SshManagerError> {
+    validate_username(username)?;
+    let lines = authorized_keys_lines(job_id, owner_key, grants, sources)?;
+    platform::write_authorized_keys(username, &lines.join("\n"))?;
+    info!("Authorized {} keys for system user '{}'", lines.len(), username);
+    Ok(())
+}
+
+/// authorized_keys lines for the owner's key and each grant of job `job_id`, as far as `sources` allows
+pub fn authorized_keys_lines(job_id: &str, owner_key: Option<&str>, grants: &[Grant], sources: &SourceRestriction) -> Result<Vec<String>, SshManagerError> {
+    sources.validate()?;
+    let mut lines = vec![];
+    if let Some(key) = owner_key {
+        let key = crate::validate_public_key(key)?;
+        if sources.allows_key(&key) {
+            lines.push(key);
+        }
+    }
+    for grant in grants {
+        grants::validate_identity(&grant.identity)?;
+        if grant.scope == AccessScope::LogsOnly && !grants::command_safe(job_id) {
+            return Err(SshManagerError::InvalidInput(format!("Job id '{}' cannot be used in a logs-only key", job_id)));
+        }
+        let key = crate::validate_public_key(&grant.public_key)?;
+        if sources.allows_key(&key) {
+            lines.push(grants::authorized_keys_line(&key, grant.scope, &grant.identity, job_id));
+        }
+    }
+    Ok(lines.into_iter().map(|line| sources.restrict_line(line)).collect())
+}
+
+/// Have sshd accept user certificates signed by the CA key at `ca_public_key`
+pub fn trust_user_ca(ca_public_key: &std::path::Path) -> Result<(), SshManagerError> {
+    platform::trust_user_ca(ca_public_key)
+}
+
+/// Have sshd ask the helper at `helper` about every job user login, see `login`
+///
+/// The helper is installed to `login::HELPER_PATH` and runs as `run_as`, reading `state_path`.
+pub fn enable_login_check(helper: &std::path::Path, state_path: &std::path::Path, run_as: &str) -> Result<(), SshManagerError> {
+    login::validate_run_as(run_as)?;
+    // sshd would read a quote or newline as the end of the command, and % as a token
+    let state = state_path.to_string_lossy();
+    if !state_path.is_absolute() || state.contains(['"', '\n', '%']) {
+        return Err(SshManagerError::InvalidInput(format!("'{}' cannot be passed to sshd as the state file", state)));
+    }
+    if !helper.is_file() {
+        return Err(SshManagerError::NotFound(format!("No login check helper at {}", helper.display())));
+    }
+    platform::enable_login_check(helper, &login::sshd_settings(std::path::Path::new(login::HELPER_PATH), state_path, run_as))?;
+    info!("Job user logins are checked by {} as '{}'", login::HELPER_PATH, run_as);
+    Ok(())
+}
+
+/// Certificate from the host CA for `public_key` to sign in as `username` until `expires_at`
+pub fn certify(username: &str, public_key: &str, expires_at: chrono::DateTime<chrono::Utc>, sources: &SourceRestriction) -> Result<String, SshManagerError> {
+    validate_username(username)?;
+    let public_key = crate::validate_public_key(public_key)?;
+    sources.validate()?;
+    if !sources.allows_key(&public_key) {
+        return Err(SshManagerError::PermissionDenied(format!("The key of system user '{}' is not among the keys it is restricted to", username)));
+    }
+    let authority = CertificateAuthority::new(ca::CA_DIR);
+    authority.prepare()?;
+    let certificate = authority.sign(&public_key, username, expires_at, sources.source_address().as_deref())?;
+    info!("Certified a key for system user '{}' until {}", username, expires_at);
+    Ok(certificate)
+}
+
+/// Void a certificate from the host CA before it runs out
+pub fn revoke_certificate(certificate: &str) -> Result<(), SshManagerError> {
+    let valid = certificate.split_whitespace().next().is_some_and(|kind| kind.ends_with("-cert-v01@openssh.com")) && !certificate.contains('\n');
+    if !valid {
+        return Err(SshManagerError::InvalidInput("Not an OpenSSH certificate".to_string()));
+    }
+    let authority = CertificateAuthority::new(ca::CA_DIR);
+    authority.revoke(certificate)?;
+    platform::use_revoked_keys(&authority.revoked_keys_path())?;
+    info!("Revoked a job user certificate");
+    Ok(())
+}
+
+/// Names of every local user, job user or not
+pub fn local_users() -> Result<Vec<String>, SshManagerError> {
+    platform::local_users()
+}
+
+/// User names in /etc/passwd content
+pub fn users_in_passwd(passwd: &str) -> Vec<String> {
+    passwd
+        .lines()
+        .filter_map(|line| line.split(':').next())
+        .filter(|user| !user.is_empty())
+        .map(|user| user.to_string())
+        .collect()
+}
+
+/// The job users among `users`
+pub fn job_users(users: &[String]) -> Vec<String> {
+    users.iter().filter(|user| validate_username(user).is_ok()).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_usernames() {
+        assert!(validate_username("job_1a2b3c4d").is_ok());
+        for username in ["root", "job_", "job_1a2b3c4", "job_1a2b3c4d5", "job_../../x", "job_1a2b 3c4"] {
+            assert!(validate_username(username).is_err(), "{}", username);
+        }
+        let passwd = "root:x:0:0:root:/root:/bin/bash\njob_1a2b3c4d:x:1001:1001::/home/job_1a2b3c4d:/bin/bash\njob_admin:x:1002:1002::/home/job_admin:/bin/bash\n";
+        let users = users_in_passwd(passwd);
+        assert_eq!(users, vec!["root", "job_1a2b3c4d", "job_admin"]);
+        assert_eq!(job_users(&users), vec!["job_1a2b3c4d"]);
+        assert!(validate_credential(&Credential::Password("job_x:hunter2".to_string())).is_err());
+        assert!(validate_credential(&Credential::PublicKey("ssh-ed25519 not-base64!".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_isolation_levels() {
+        let open = SshPolicy::default();
+        assert_eq!(IsolationLevel::FullShell.sshd_match_block("job_1a2b3c4d", "train-1", &open), Ok(None));
+        assert_eq!(
+            IsolationLevel::Chroot.sshd_match_block("job_1a2b3c4d", "train-1", &open).unwrap().unwrap(),
+            "Match User job_1a2b3c4d\n    ChrootDirectory %h\n    ForceCommand internal-sftp -d /work\n    DisableForwarding yes\n"
+        );
+        let container = IsolationLevel::ContainerOnly.sshd_match_block("job_1a2b3c4d", "train-1", &open).unwrap().unwrap();
+        assert!(container.contains("ForceCommand exec docker exec -i$([ -t 0 ] && echo t) eryzaa-job-train-1 sh -c \"${SSH_ORIGINAL_COMMAND:-"));
+        assert!(IsolationLevel::ContainerOnly.sshd_match_block("job_1a2b3c4d", "x; rm -rf /", &open).is_err());
+        assert!(!IsolationLevel::RestrictedShell.docker_access() && IsolationLevel::ContainerOnly.docker_access());
+        assert_eq!(serde_json::to_string(&IsolationLevel::ContainerOnly).unwrap(), "\"container_only\"");
+    }
+
+    #[test]
+    fn test_ssh_policies() {
+        let untrusted = SshPolicy::untrusted();
+        assert_eq!(
+            IsolationLevel::FullShell.sshd_match_block("job_1a2b3c4d", "train-1", &untrusted).unwrap().unwrap(),
+            "Match User job_1a2b3c4d\n    AllowTcpForwarding no\n    AllowStreamLocalForwarding no\n    PermitTunnel no\n    \
+             X11Forwarding no\n    AllowAgentForwarding no\n    MaxSessions 4\n"
+        );
+        // DisableForwarding covers the forwarding settings already
+        let restricted = IsolationLevel::RestrictedShell.sshd_match_block("job_1a2b3c4d", "train-1", &untrusted).unwrap().unwrap();
+        assert_eq!(restricted, "Match User job_1a2b3c4d\n    SetEnv PATH=/home/job_1a2b3c4d/bin\n    DisableForwarding yes\n    MaxSessions 4\n");
+
+        let sftp = SshPolicy { sftp_only: true, ..SshPolicy::default() };
+        assert_eq!(
+            IsolationLevel::FullShell.sshd_match_block("job_1a2b3c4d", "train-1", &sftp).unwrap().unwrap(),
+            "Match User job_1a2b3c4d\n    ForceCommand internal-sftp\n    DisableForwarding yes\n"
+        );
+        let untrusted_sftp = SshPolicy { sftp_only: true, ..untrusted };
+        let block = IsolationLevel::FullShell.sshd_match_block("job_1a2b3c4d", "train-1", &untrusted_sftp).unwrap().unwrap();
+        assert!(block.contains("    ForceCommand internal-sftp\n") && block.contains("    DisableForwarding yes\n"));
+        assert!(!block.contains("AllowTcpForwarding"));
+        assert_eq!(AccessMode::of(IsolationLevel::FullShell, &sftp), AccessMode::SftpOnly);
+        assert_eq!(AccessMode::of(IsolationLevel::FullShell, &untrusted), AccessMode::Shell);
+        assert_eq!(IsolationLevel::Chroot.sshd_match_block("job_1a2b3c4d", "train-1", &sftp), IsolationLevel::Chroot.sshd_match_block("job_1a2b3c4d", "train-1", &SshPolicy::default()));
+        assert!(IsolationLevel::ContainerOnly.sshd_match_block("job_1a2b3c4d", "train-1", &sftp).is_err());
+        assert!(IsolationLevel::FullShell.sshd_match_block("job_1a2b3c4d", "train-1", &SshPolicy { max_sessions: Some(0), ..SshPolicy::default() }).is_err());
+        let keys_only = SshPolicy { no_password_auth: true, ..SshPolicy::default() };
+        assert_eq!(
+            IsolationLevel::FullShell.sshd_match_block("job_1a2b3c4d", "train-1", &keys_only).unwrap().unwrap(),
+            "Match User job_1a2b3c4d\n    PasswordAuthentication no\n    KbdInteractiveAuthentication no\n"
+        );
+        assert_eq!(serde_json::from_str::<SshPolicy>("{}").unwrap(), SshPolicy::default());
+        assert_eq!(AccessMode::of(IsolationLevel::Chroot, &SshPolicy::default()), AccessMode::SftpOnly);
+        assert_eq!(AccessMode::of(IsolationLevel::RestrictedShell, &SshPolicy::default()).client_program(), "ssh");
+    }
+}
@@ -0,0 +1,187 @@
+//! Live session activity of job users
+//! Whether a renter is connected right now, from where and since when. `ss` lists established
+//! connections to sshd and the session processes holding them, whose titles name the user;
+//! `who` adds when interactive sessions started. Combined with the traffic counters this is the
+//! `SessionActivity` each `JobAccess` carries, refreshed by `SshManager::refresh_activity`.
+//! Listing other users' processes takes root, so the manager asks the privileged service.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+use crate::accounts::job_user_prefix;
+use crate::bandwidth::UserTraffic;
+use crate::error::SshManagerError;
+
+/// Port sshd listens on for job users
+const SSH_PORT: u16 = 22;
+
+/// An open SSH connection of a job user
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Connection {
+    pub username: String,
+    pub source: Option<String>,       // Client address
+    pub since: Option<DateTime<Utc>>, // Known for sessions with a terminal
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SessionActivity {
+    pub connected: bool,
+    pub sources: Vec<String>, // Addresses of the open connections
+    pub connected_since: Option<DateTime<Utc>>, // Start of the oldest open connection, or when it was first seen
+    pub connections: usize,
+    pub rx_bytes: Option<u64>, // Received and sent so far, when traffic is counted
+    pub tx_bytes: Option<u64>,
+    pub checked_at: Option<DateTime<Utc>>,
+}
+
+impl SessionActivity {
+    /// Activity of a user with `connections` open and `traffic` counted at `now`
+    ///
+    /// A connection without a known start that was already open at the `previous` check keeps
+    /// its start, so `connected_since` does not move with every refresh.
+    pub fn observe(previous: Option<&SessionActivity>, connections: &[&Connection], traffic: Option<&UserTraffic>, now: DateTime<Utc>) -> Self {
+        let mut sources: Vec<String> = vec![];
+        for source in connections.iter().filter_map(|connection| connection.source.clone()) {
+            if !sources.contains(&source) {
+                sources.push(source);
+            }
+        }
+        let still_connected = previous.filter(|previous| previous.connected).and_then(|previous| previous.connected_since);
+        let unknown_start = connections.iter().any(|connection| connection.since.is_none());
+        let connected_since = connections
+            .iter()
+            .filter_map(|connection| connection.since)
+            .chain(unknown_start.then(|| still_connected.unwrap_or(now)))
+            .min();
+        Self {
+            connected: !connections.is_empty(),
+            sources,
+            connected_since,
+            connections: connections.len(),
+            rx_bytes: traffic.map(|traffic| traffic.rx_bytes),
+            tx_bytes: traffic.map(|traffic| traffic.tx_bytes),
+            checked_at: Some(now),
+        }
+    }
+}
+
+/// Sessions listed by `who`, as (user, client address, start); the start is local time
+pub fn parse_who(output: &str) -> Vec<(String, Option<String>, Option<DateTime<Utc>>)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let username = fields.first()?;
+            let since = fields
+                .get(2..4)
+                .and_then(|time| chrono::NaiveDateTime::parse_from_str(&time.join(" "), "%Y-%m-%d %H:%M").ok())
+                .and_then(|time| time.and_local_timezone(chrono::Local).single())
+                .map(|time| time.with_timezone(&Utc));
+            let source = fields
+                .iter()
+                .find(|field| field.starts_with('(') && field.ends_with(')'))
+                .map(|field| field.trim_matches(['(', ')']).to_string());
+            Some((username.to_string(), source, since))
+        })
+        .collect()
+}
+
+/// Connections listed by `ss -Htnp`, as (client address, pids of the processes holding them)
+pub fn parse_ss(output: &str) -> Vec<(String, Vec<u32>)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (address, _port) = fields.get(3)?.rsplit_once(':')?;
+            let address = address.trim_matches(['[', ']']);
+            let address = address.strip_prefix("::ffff:").unwrap_or(address);
+            let pids = line
+                .split("pid=")
+                .skip(1)
+                .filter_map(|rest| rest.split(|c: char| !c.is_ascii_digit()).next()?.parse().ok())
+                .collect();
+            Some((address.to_string(), pids))
+        })
+        .collect()
+}
+
+/// Job user an sshd process serves, from its title, e.g. "sshd: job_ab12cd34@pts/0"
+///
+/// Connections still authenticating are nobody's yet.
+pub fn session_user(title: &str) -> Option<&str> {
+    let title = title.trim_end_matches('\0');
+    let rest = title.strip_prefix("sshd: ").or_else(|| title.strip_prefix("sshd-session: "))?;
+    if rest.contains("[preauth]") || rest.contains("[net]") {
+        return None;
+    }
+    let username = rest.split(['@', ' ']).next()?;
+    username.starts_with(job_user_prefix()).then_some(username)
+}
+
+/// Open connections of job users on this machine; complete only when run as root
+pub fn read_connections() -> Result<Vec<Connection>, SshManagerError> {
+    let filter = format!("( sport = :{} )", SSH_PORT);
+    let output = Command::new("ss")
+        .args(["-Htnp", "state", "established", &filter])
+        .output()
+        .map_err(|e| SshManagerError::spawn("ss", e))?;
+    if !output.status.success() {
+        return Err(SshManagerError::from_command("ss", &String::from_utf8_lossy(&output.stderr)).context("Failed to list SSH connections"));
+    }
+    let sessions = Command::new("who")
+        .output()
+        .map(|output| parse_who(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or_default();
+
+    let mut connections = vec![];
+    for (source, pids) in parse_ss(&String::from_utf8_lossy(&output.stdout)) {
+        let username = pids.iter().find_map(|pid| {
+            let title = std::fs::read_to_string(format!("/proc/{}/cmdline", pid)).ok()?;
+            session_user(&title).map(str::to_string)
+        });
+        let Some(username) = username else {
+            continue;
+        };
+        let since = sessions
+            .iter()
+            .filter(|(user, from, _)| *user == username && from.as_deref() == Some(source.as_str()))
+            .filter_map(|(_, _, since)| *since)
+            .min();
+        connections.push(Connection { username, source: Some(source), since });
+    }
+    Ok(connections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_activity() {
+        let ss = "0      0      192.168.1.5:22     10.0.0.7:50022 users:((\"sshd\",pid=4242,fd=4),(\"sshd\",pid=4250,fd=4))\n\
+                  0      0      [::ffff:192.168.1.5]:22 [::ffff:10.0.0.8]:61000 users:((\"sshd-session\",pid=77,fd=3))\n";
+        assert_eq!(parse_ss(ss), vec![("10.0.0.7".to_string(), vec![4242, 4250]), ("10.0.0.8".to_string(), vec![77])]);
+        assert_eq!(session_user("sshd: job_ab12cd34@pts/0\0\0"), Some("job_ab12cd34"));
+        assert_eq!(session_user("sshd-session: job_ab12cd34 [priv]"), Some("job_ab12cd34"));
+        assert_eq!(session_user("sshd: job_ab12cd34 [preauth]"), None);
+        assert_eq!(session_user("sshd: alice@pts/1"), None);
+        let who = parse_who("job_ab12cd34 pts/0        2026-10-17 10:02 (10.0.0.7)\nalice    tty1         2026-10-16 08:00\n");
+        assert_eq!(who[0].1.as_deref(), Some("10.0.0.7"));
+        assert!(who[0].2.is_some() && who[1].1.is_none());
+
+        let now = Utc::now();
+        let started = now - chrono::Duration::minutes(30);
+        let shell = Connection { username: "job_ab12cd34".to_string(), source: Some("10.0.0.7".to_string()), since: Some(started) };
+        let sftp = Connection { username: "job_ab12cd34".to_string(), source: Some("10.0.0.8".to_string()), since: None };
+        let traffic = UserTraffic { username: "job_ab12cd34".to_string(), rx_bytes: 1000, tx_bytes: 5000 };
+        let first = SessionActivity::observe(None, &[&sftp], Some(&traffic), now - chrono::Duration::minutes(5));
+        assert_eq!(first.connected_since, Some(now - chrono::Duration::minutes(5)));
+        // The SFTP connection keeps the start it was first seen at; the shell started earlier
+        let second = SessionActivity::observe(Some(&first), &[&shell, &sftp], Some(&traffic), now);
+        assert_eq!((second.connected, second.connections, second.connected_since), (true, 2, Some(started)));
+        assert_eq!(second.sources, vec!["10.0.0.7", "10.0.0.8"]);
+        assert_eq!(second.tx_bytes, Some(5000));
+        assert!(!SessionActivity::observe(Some(&second), &[], None, now).connected);
+    }
+}
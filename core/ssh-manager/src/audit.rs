@@ -0,0 +1,247 @@
+//! SSH session audit log
+//! sshd's journal entries about job users (logins, failed attempts and logouts, each with the
+//! client's address) are filed under the job the user belongs to, so the owner can see who used
+//! a rental and from where. Transcripts of interactive sessions come from the login gate's
+//! recordings when recording is turned on in the rental settings.
+
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::accounts::job_user_prefix;
+use crate::error::SshManagerError;
+
+/// Where the privileged service remembers how far it has read the journal
+pub const SERVICE_CURSOR_PATH: &str = "/var/lib/eryzaa/ssh-audit.cursor";
+
+/// Events returned by one read, so an answer from the service fits in one frame
+const MAX_EVENTS: usize = 200;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditKind {
+    Login,
+    Failed, // Rejected password or key
+    Logout,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditEvent {
+    pub at: u64, // Unix seconds
+    pub username: String,
+    pub kind: AuditKind,
+    #[serde(default)]
+    pub source: Option<String>, // Client address
+    #[serde(default)]
+    pub method: Option<String>, // publickey, password, ...
+    #[serde(default)]
+    pub pid: u32, // sshd process of the connection, pairs a login with its logout
+    #[serde(default)]
+    pub fingerprint: Option<String>, // Of the key a login used
+    #[serde(default)]
+    pub identity: Option<String>, // Owner of that key: the client or someone it granted access
+}
+
+impl AuditEvent {
+    /// Event described by an sshd log message, if it is about a job user
+    pub fn parse(message: &str, at: u64, pid: u32) -> Option<Self> {
+        let words: Vec<&str> = message.split_whitespace().collect();
+        let (kind, method, username, source) = match words.as_slice() {
+            ["Accepted", method, "for", username, "from", source, ..] => (AuditKind::Login, Some(*method), *username, *source),
+            ["Failed", method, "for", username, "from", source, ..] => (AuditKind::Failed, Some(*method), *username, *source),
+            ["Disconnected", "from", "user", username, source, ..] => (AuditKind::Logout, None, *username, *source),
+            _ => return None,
+        };
+        username.starts_with(job_user_prefix()).then(|| Self {
+            at,
+            username: username.to_string(),
+            kind,
+            source: Some(source.to_string()),
+            method: method.map(str::to_string),
+            pid,
+            fingerprint: words.iter().find(|word| word.starts_with("SHA256:")).map(|word| word.to_string()),
+            identity: None,
+        })
+    }
+}
+
+/// A login and, once it has ended, its logout
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditSession {
+    pub username: String,
+    pub identity: Option<String>,
+    pub source: Option<String>,
+    pub method: Option<String>,
+    pub started_at: u64,
+    pub ended_at: Option<u64>,
+}
+
+/// Pair each login with the logout of the same sshd process
+pub fn sessions(events: &[AuditEvent]) -> Vec<AuditSession> {
+    let mut sessions: Vec<(u32, AuditSession)> = vec![];
+    for event in events {
+        match event.kind {
+            AuditKind::Login => sessions.push((
+                event.pid,
+                AuditSession {
+                    username: event.username.clone(),
+                    identity: event.identity.clone(),
+                    source: event.source.clone(),
+                    method: event.method.clone(),
+                    started_at: event.at,
+                    ended_at: None,
+                },
+            )),
+            AuditKind::Logout => {
+                let open = sessions.iter_mut().rfind(|(pid, session)| {
+                    *pid == event.pid && session.username == event.username && session.ended_at.is_none()
+                });
+                if let Some((_, session)) = open {
+                    session.ended_at = Some(event.at);
+                }
+            }
+            AuditKind::Failed => {}
+        }
+    }
+    sessions.into_iter().map(|(_, session)| session).collect()
+}
+
+/// Audit events kept per job, one JSON line per event
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    dir: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, job_id: &str) -> PathBuf {
+        // Job ids come from clients; keep them from naming another directory
+        self.dir.join(format!("{}.jsonl", job_id.replace(['/', '\\'], "_")))
+    }
+
+    pub fn append(&self, job_id: &str, events: &[AuditEvent]) -> Result<(), SshManagerError> {
+        std::fs::create_dir_all(&self.dir).map_err(|e| SshManagerError::io("create", &self.dir, e))?;
+        let path = self.path(job_id);
+        let mut lines = String::new();
+        for event in events {
+            let line = serde_json::to_string(event).map_err(|e| SshManagerError::Io(format!("Failed to serialize audit event: {}", e)))?;
+            lines.push_str(&line);
+            lines.push('\n');
+        }
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(lines.as_bytes()))
+            .map_err(|e| SshManagerError::io("write audit log", &path, e))
+    }
+
+    /// Events of a job, oldest first
+    pub fn events(&self, job_id: &str) -> Vec<AuditEvent> {
+        std::fs::read_to_string(self.path(job_id))
+            .map(|content| content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Jobs with an audit log, including ones that have ended
+    pub fn job_ids(&self) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return vec![];
+        };
+        let mut job_ids: Vec<String> = entries
+            .flatten()
+            .filter_map(|entry| entry.file_name().to_str()?.strip_suffix(".jsonl").map(str::to_string))
+            .collect();
+        job_ids.sort();
+        job_ids
+    }
+}
+
+/// sshd's journal entries about job users since the cursor kept in `cursor_file`, which is advanced
+///
+/// Without a cursor the last day is read. Reading the system journal takes root or membership
+/// of the systemd-journal group.
+#[cfg(unix)]
+pub fn read_journal(cursor_file: &Path) -> Result<Vec<AuditEvent>, SshManagerError> {
+    let mut cmd = std::process::Command::new("journalctl");
+    cmd.args(["--output", "json", "--no-pager", "--identifier", "sshd", "--identifier", "sshd-session"]);
+    match std::fs::read_to_string(cursor_file) {
+        Ok(cursor) => cmd.arg(format!("--after-cursor={}", cursor.trim())),
+        Err(_) => cmd.arg("--since=-24h"),
+    };
+    let output = cmd.output().map_err(|e| SshManagerError::spawn("journalctl", e))?;
+    if !output.status.success() {
+        return Err(SshManagerError::from_command("journalctl", &String::from_utf8_lossy(&output.stderr)).context("journalctl failed"));
+    }
+
+    let mut events = vec![];
+    let mut cursor = None;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Ok(entry) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        let field = |name: &str| entry[name].as_str().map(str::to_string);
+        cursor = field("__CURSOR").or(cursor);
+        let at = field("__REALTIME_TIMESTAMP").and_then(|us| us.parse::<u64>().ok()).unwrap_or(0) / 1_000_000;
+        let pid = field("_PID").and_then(|pid| pid.parse().ok()).unwrap_or(0);
+        if let Some(event) = field("MESSAGE").and_then(|message| AuditEvent::parse(&message, at, pid)) {
+            events.push(event);
+            if events.len() == MAX_EVENTS {
+                break;
+            }
+        }
+    }
+
+    if let Some(cursor) = cursor {
+        if let Some(parent) = cursor_file.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| SshManagerError::io("create", parent, e))?;
+        }
+        std::fs::write(cursor_file, cursor).map_err(|e| SshManagerError::io("write", cursor_file, e))?;
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_pair_sessions() {
+        let messages = [
+            (100, 41, "Failed password for job_ab12cd34 from 10.242.0.7 port 50022 ssh2"),
+            (105, 42, "Accepted publickey for job_ab12cd34 from 10.242.0.7 port 50031 ssh2: ED25519 SHA256:abc"),
+            (110, 43, "Accepted publickey for job_ab12cd34 from 10.242.0.9 port 40100 ssh2: ED25519 SHA256:abc"),
+            (120, 43, "Disconnected from user job_ab12cd34 10.242.0.9 port 40100"),
+            (130, 44, "Accepted password for root from 10.242.0.7 port 50040 ssh2"),
+            (140, 45, "Failed password for invalid user job_ab12cd34 from 10.242.0.8 port 1 ssh2"),
+        ];
+        let events: Vec<AuditEvent> = messages
+            .iter()
+            .filter_map(|(at, pid, message)| AuditEvent::parse(message, *at, *pid))
+            .collect();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0].kind, AuditKind::Failed);
+        assert_eq!(events[1].method.as_deref(), Some("publickey"));
+        assert_eq!(events[1].fingerprint.as_deref(), Some("SHA256:abc"));
+        assert_eq!(events[3].source.as_deref(), Some("10.242.0.9"));
+
+        let sessions = sessions(&events);
+        assert_eq!(sessions.len(), 2);
+        assert_eq!((sessions[0].started_at, sessions[0].ended_at), (105, None));
+        assert_eq!((sessions[1].source.as_deref(), sessions[1].ended_at), (Some("10.242.0.9"), Some(120)));
+
+        let log = AuditLog::new(std::env::temp_dir().join(format!("eryzaa-audit-{}", std::process::id())));
+        log.append("../job-1", &events[..2]).unwrap();
+        log.append("../job-1", &events[2..]).unwrap();
+        assert_eq!(log.events("../job-1"), events);
+        assert_eq!(log.job_ids(), vec![".._job-1"]);
+        std::fs::remove_dir_all(log.dir()).unwrap();
+    }
+}
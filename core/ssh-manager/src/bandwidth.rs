@@ -0,0 +1,141 @@
+//! Bandwidth limits and traffic counters for job users
+//! One nftables table polices what each job user's processes send, matched by uid, and what
+//! they receive, matched by a conntrack mark their outgoing packets set. Dropping traffic over
+//! the rate makes TCP back off, so one renter cannot saturate the owner's uplink. Named
+//! counters per user outlive re-applying the rules and feed `SshManager::get_usage`. This module
+//! writes the ruleset and reads the counters; `accounts` runs nft on platforms that have it.
+
+use serde::{Deserialize, Serialize};
+
+/// nftables table holding the limits and counters
+pub const BANDWIDTH_TABLE: &str = "eryzaa_ssh_bw";
+
+/// Conntrack marks of job users' connections are this plus the uid's low bits
+const MARK_BASE: u32 = 0x4552_0000;
+
+/// Per-user rates, unlimited when unset
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BandwidthCaps {
+    #[serde(default)]
+    pub egress_mbit: Option<u32>, // Upload, what the user sends
+    #[serde(default)]
+    pub ingress_mbit: Option<u32>, // Download, what the user receives
+}
+
+impl BandwidthCaps {
+    pub fn is_empty(&self) -> bool {
+        self.egress_mbit.is_none() && self.ingress_mbit.is_none()
+    }
+}
+
+/// Bytes a job user has received and sent since the counters were created
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UserTraffic {
+    pub username: String,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+/// Traffic of a job's user, as `SshManager::get_usage` reports it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BandwidthUsage {
+    pub job_id: String,
+    pub username: String,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_bits_per_sec: Option<u64>, // Since the previous call; None on the first
+    pub tx_bits_per_sec: Option<u64>,
+    pub caps: BandwidthCaps,
+}
+
+/// `limit` statement for `mbit` per second, with a second's worth of burst
+fn rate_limit(mbit: u32) -> String {
+    let kbytes = u64::from(mbit) * 125;
+    format!("limit rate over {} kbytes/second burst {} kbytes drop", kbytes, kbytes)
+}
+
+/// nft script putting `caps` on each (username, uid) in `users` and counting their traffic
+///
+/// Counters of `stale` users, who are gone, are deleted; the others keep their counts.
+pub fn ruleset(users: &[(String, u32)], caps: BandwidthCaps, stale: &[String]) -> String {
+    let table = format!("inet {}", BANDWIDTH_TABLE);
+    let mut script = format!(
+        "add table {table}\n\
+         add chain {table} output {{ type filter hook output priority 0; policy accept; }}\n\
+         add chain {table} input {{ type filter hook input priority 0; policy accept; }}\n\
+         flush chain {table} output\n\
+         flush chain {table} input\n"
+    );
+    for username in stale {
+        script.push_str(&format!("delete counter {} {}_rx\ndelete counter {} {}_tx\n", table, username, table, username));
+    }
+    for (username, uid) in users {
+        let mark = MARK_BASE | (uid & 0xffff);
+        script.push_str(&format!("add counter {} {}_rx\nadd counter {} {}_tx\n", table, username, table, username));
+        script.push_str(&format!("add rule {} output meta skuid {} ct mark set {:#x}\n", table, uid, mark));
+        if let Some(mbit) = caps.egress_mbit {
+            script.push_str(&format!("add rule {} output meta skuid {} {}\n", table, uid, rate_limit(mbit)));
+        }
+        script.push_str(&format!("add rule {} output meta skuid {} counter name \"{}_tx\"\n", table, uid, username));
+        if let Some(mbit) = caps.ingress_mbit {
+            script.push_str(&format!("add rule {} input ct mark {:#x} {}\n", table, mark, rate_limit(mbit)));
+        }
+        script.push_str(&format!("add rule {} input ct mark {:#x} counter name \"{}_rx\"\n", table, mark, username));
+    }
+    script
+}
+
+/// Per-user traffic from `nft list counters` output
+pub fn parse_counters(listing: &str) -> Vec<UserTraffic> {
+    let mut traffic: Vec<UserTraffic> = vec![];
+    let mut current: Option<String> = None;
+    for line in listing.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix("counter ").and_then(|rest| rest.strip_suffix('{')) {
+            current = Some(name.trim().to_string());
+            continue;
+        }
+        let (Some(name), Some(bytes)) = (current.take(), line.split_whitespace().skip_while(|word| *word != "bytes").nth(1)) else {
+            continue;
+        };
+        let (Some((username, direction)), Ok(bytes)) = (name.rsplit_once('_'), bytes.parse::<u64>()) else {
+            continue;
+        };
+        let index = match traffic.iter().position(|entry| entry.username == username) {
+            Some(index) => index,
+            None => {
+                traffic.push(UserTraffic { username: username.to_string(), ..Default::default() });
+                traffic.len() - 1
+            }
+        };
+        match direction {
+            "rx" => traffic[index].rx_bytes = bytes,
+            "tx" => traffic[index].tx_bytes = bytes,
+            _ => {}
+        }
+    }
+    traffic
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bandwidth_rules_and_counters() {
+        let users = vec![("job_ab12cd34".to_string(), 1001)];
+        let caps = BandwidthCaps { egress_mbit: Some(20), ingress_mbit: None };
+        let script = ruleset(&users, caps, &["job_gone0000".to_string()]);
+        assert!(script.contains("delete counter inet eryzaa_ssh_bw job_gone0000_rx\n"));
+        assert!(script.contains("add rule inet eryzaa_ssh_bw output meta skuid 1001 ct mark set 0x455203e9\n"));
+        assert!(script.contains("output meta skuid 1001 limit rate over 2500 kbytes/second burst 2500 kbytes drop\n"));
+        assert!(script.contains("add rule inet eryzaa_ssh_bw input ct mark 0x455203e9 counter name \"job_ab12cd34_rx\"\n"));
+        assert!(!script.contains("input ct mark 0x455203e9 limit"));
+
+        let listing = "table inet eryzaa_ssh_bw {\n\tcounter job_ab12cd34_rx {\n\t\tpackets 12 bytes 3456\n\t}\n\
+                       \tcounter job_ab12cd34_tx {\n\t\tpackets 3 bytes 789\n\t}\n}\n";
+        assert_eq!(
+            parse_counters(listing),
+            vec![UserTraffic { username: "job_ab12cd34".to_string(), rx_bytes: 3456, tx_bytes: 789 }]
+        );
+    }
+}
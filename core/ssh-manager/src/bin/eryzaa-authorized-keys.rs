@@ -0,0 +1,45 @@
+//! Login check sshd runs for job users
+//! Prints the keys or certificate principals a job user may sign in with, read from the rental
+//! node's SSH state file, and nothing once the job has expired or been revoked; see
+//! `eryzaa_ssh_manager::login`. sshd runs it through the drop-in `eryzaa-ssh-service login-check`
+//! writes. As `pam` it answers pam_exec in the account stack, for password logins.
+
+use std::path::Path;
+
+use eryzaa_ssh_manager::login;
+
+const USAGE: &str = "Usage: eryzaa-authorized-keys keys <state-file> <username>
+       eryzaa-authorized-keys principals <state-file> <username>
+       eryzaa-authorized-keys pam <state-file>   (user from PAM_USER, for pam_exec)";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let now = chrono::Utc::now();
+    let answer = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["keys", state, username] => login::read_state(Path::new(state)).and_then(|jobs| login::authorized_keys(&jobs, username, now)),
+        ["principals", state, username] => login::read_state(Path::new(state)).and_then(|jobs| login::principals(&jobs, username, now)),
+        ["pam", state] => {
+            let username = std::env::var("PAM_USER").unwrap_or_default();
+            // Users other than job users must still get in when the state cannot be read
+            let jobs = login::read_state(Path::new(state)).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                vec![]
+            });
+            if login::pam_allows(&jobs, &username, now) {
+                return;
+            }
+            exit_with(&format!("Job user '{}' has no active job", username));
+        }
+        _ => exit_with(USAGE),
+    };
+    match answer {
+        Ok(lines) => lines.iter().for_each(|line| println!("{}", line)),
+        // sshd logs what the command writes to stderr and lets nobody in
+        Err(e) => exit_with(&e.to_string()),
+    }
+}
+
+fn exit_with(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
@@ -0,0 +1,224 @@
+//! Privileged helper that creates and deletes job users for the rental node
+//! Runs as root and answers the unprivileged rental GUI and server over a Unix socket, see
+//! `eryzaa_ssh_manager::service`. Only root and the users given with --allow may call it.
+
+#[cfg(unix)]
+fn main() {
+    unix::main()
+}
+
+/// Windows hosts have no socket to serve; the rental GUI manages users itself when run as administrator
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("eryzaa-ssh-service is only used on Linux; on Windows run the rental GUI as administrator");
+    std::process::exit(1);
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::io::BufRead;
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixListener;
+    use std::path::{Path, PathBuf};
+
+    use eryzaa_ssh_manager::accounts::{self, Credential};
+    use eryzaa_ssh_manager::activity;
+    use eryzaa_ssh_manager::audit;
+    use eryzaa_ssh_manager::ca::{self, CertificateAuthority};
+    use eryzaa_ssh_manager::docker;
+    use eryzaa_ssh_manager::grants::{GrantQueue, GRANT_QUEUE_DIR};
+    use eryzaa_ssh_manager::login;
+    use eryzaa_ssh_manager::service::{self, Request, Response, SOCKET_PATH};
+
+    const USAGE: &str = "Usage: eryzaa-ssh-service [serve] [--allow <user|uid>]... [--socket PATH]
+       eryzaa-ssh-service list [--socket PATH]
+       eryzaa-ssh-service create <username> [--socket PATH]   (password on stdin)
+       eryzaa-ssh-service remove <username> [--socket PATH]
+       eryzaa-ssh-service suspend <username> [--socket PATH]
+       eryzaa-ssh-service resume <username> [--socket PATH]
+       eryzaa-ssh-service audit [--socket PATH]
+       eryzaa-ssh-service traffic [--socket PATH]
+       eryzaa-ssh-service connections [--socket PATH]
+       eryzaa-ssh-service quota <username> <MB> [--socket PATH]
+       eryzaa-ssh-service login-check <state-file> <run-as-user> [--socket PATH]";
+
+    pub fn main() {
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+        let mut args: Vec<String> = std::env::args().skip(1).collect();
+        let socket = match args.iter().position(|arg| arg == "--socket") {
+            Some(index) if index + 1 < args.len() => {
+                let path = PathBuf::from(args.remove(index + 1));
+                args.remove(index);
+                path
+            }
+            Some(_) => exit_with(USAGE),
+            None => PathBuf::from(SOCKET_PATH),
+        };
+
+        let result = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            [] | ["serve", ..] | ["--allow", ..] => serve(&socket, &args),
+            ["list"] => call(&socket, Request::List),
+            ["create", username] => {
+                let mut password = String::new();
+                let _ = std::io::stdin().lock().read_line(&mut password);
+                let credential = Credential::Password(password.trim_end_matches(['\r', '\n']).to_string());
+                call(
+                    &socket,
+                    Request::Create {
+                        username: username.to_string(),
+                        credential,
+                        isolation: Default::default(),
+                        policy: Default::default(),
+                        job_id: String::new(),
+                        docker: Default::default(),
+                    },
+                )
+            }
+            ["remove", username] => call(&socket, Request::Remove { username: username.to_string() }),
+            ["suspend", username] => call(&socket, Request::Suspend { username: username.to_string() }),
+            ["resume", username] => call(&socket, Request::Resume { username: username.to_string() }),
+            ["audit"] => call(&socket, Request::Audit),
+            ["traffic"] => call(&socket, Request::Traffic),
+            ["connections"] => call(&socket, Request::Connections),
+            ["quota", username, limit_mb] => match limit_mb.parse() {
+                Ok(limit_mb) => call(&socket, Request::Quota { username: username.to_string(), limit_mb }),
+                Err(_) => exit_with(USAGE),
+            },
+            ["login-check", state_path, run_as] => {
+                call(&socket, Request::LoginCheck { state_path: PathBuf::from(state_path), run_as: run_as.to_string() })
+            }
+            _ => exit_with(USAGE),
+        };
+        if let Err(e) = result {
+            exit_with(&format!("[-] {}", e));
+        }
+    }
+
+    fn exit_with(message: &str) -> ! {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    }
+
+    /// Listen on `socket` and handle requests until killed
+    fn serve(socket: &Path, args: &[String]) -> Result<(), String> {
+        if unsafe { libc::geteuid() } != 0 {
+            return Err("eryzaa-ssh-service must run as root".to_string());
+        }
+
+        let passwd = std::fs::read_to_string("/etc/passwd").map_err(|e| format!("Failed to read /etc/passwd: {}", e))?;
+        let mut allowed_uids = vec![];
+        let mut args = args.iter().filter(|arg| *arg != "serve");
+        while let Some(arg) = args.next() {
+            match (arg.as_str(), args.next()) {
+                ("--allow", Some(user)) => allowed_uids.push(resolve_uid(user, &passwd)?),
+                _ => return Err(USAGE.to_string()),
+            }
+        }
+
+        if let Some(parent) = socket.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        // A socket left by a previous run refuses connections, so it is safe to replace
+        if std::os::unix::net::UnixStream::connect(socket).is_ok() {
+            return Err(format!("Another SSH service is already listening on {}", socket.display()));
+        }
+        let _ = std::fs::remove_file(socket);
+        let listener = UnixListener::bind(socket).map_err(|e| format!("Failed to listen on {}: {}", socket.display(), e))?;
+        // Anyone may connect; callers are checked by uid on every request
+        std::fs::set_permissions(socket, std::fs::Permissions::from_mode(0o666))
+            .map_err(|e| format!("Failed to open up {}: {}", socket.display(), e))?;
+
+        // Clients file requests to share their job here, see `eryzaa job grant`
+        if let Err(e) = GrantQueue::new(GRANT_QUEUE_DIR).prepare() {
+            println!("[-] Delegated access is unavailable: {}", e);
+        }
+        // Job users sign in with certificates from this CA
+        if let Err(e) = CertificateAuthority::new(ca::CA_DIR).prepare() {
+            println!("[-] Job users cannot sign in until the SSH CA is set up: {}", e);
+        }
+        // Proxied job users lose docker while the service is down
+        let users = accounts::local_users().map(|users| accounts::job_users(&users)).unwrap_or_default();
+        let proxies = docker::restore_proxies(&users);
+        if proxies > 0 {
+            println!("[+] Restarted the docker proxies of {} job users", proxies);
+        }
+        println!("[+] Managing SSH job users on {}", socket.display());
+        println!("[*] Accepting requests from root and uids {:?}", allowed_uids);
+        service::serve(listener, allowed_uids, |request| {
+            let result = match request {
+                Request::Create { username, credential, isolation, policy, job_id, docker } => {
+                    accounts::create_user(&username, &credential, isolation, &policy, &job_id, docker).map(|_| Response::Done)
+                }
+                Request::Policy { username, job_id, isolation, policy, sources } => {
+                    accounts::set_policy(&username, &job_id, isolation, &policy, &sources).map(|_| Response::Done)
+                }
+                Request::Remove { username } => accounts::delete_user(&username).map(|_| Response::Done),
+                Request::Suspend { username } => accounts::suspend_user(&username).map(|_| Response::Done),
+                Request::Resume { username } => accounts::resume_user(&username).map(|_| Response::Done),
+                Request::EndSessions { username } => accounts::end_sessions(&username).map(|_| Response::Done),
+                Request::Revoke { certificate } => accounts::revoke_certificate(&certificate).map(|_| Response::Done),
+                Request::Limit { username, caps } => accounts::limit_user(&username, &caps).map(|_| Response::Done),
+                Request::List => accounts::local_users().map(|users| Response::Users(accounts::job_users(&users))),
+                Request::Authorize { username, job_id, owner_key, grants, sources } => {
+                    accounts::authorize_keys(&username, &job_id, owner_key.as_deref(), &grants, &sources).map(|_| Response::Done)
+                }
+                Request::Audit => audit::read_journal(Path::new(audit::SERVICE_CURSOR_PATH)).map(Response::Events),
+                Request::Certify { username, public_key, expires_at, sources } => {
+                    accounts::certify(&username, &public_key, expires_at, &sources).map(Response::Certificate)
+                }
+                Request::Throttle { usernames, caps } => accounts::throttle_users(&usernames, caps).map(|_| Response::Done),
+                Request::Traffic => accounts::user_traffic().map(Response::Traffic),
+                Request::Connections => activity::read_connections().map(Response::Connections),
+                Request::Quota { username, limit_mb } => accounts::quota_user(&username, limit_mb).map(Response::Quota),
+                Request::LoginCheck { state_path, run_as } => login::bundled_helper()
+                    .and_then(|helper| accounts::enable_login_check(&helper, &state_path, &run_as))
+                    .map(|_| Response::Done),
+            };
+            result.unwrap_or_else(Response::Error)
+        });
+        Ok(())
+    }
+
+    /// uid of a user given by name or number
+    fn resolve_uid(user: &str, passwd: &str) -> Result<u32, String> {
+        if let Ok(uid) = user.parse() {
+            return Ok(uid);
+        }
+        passwd
+            .lines()
+            .map(|line| line.split(':').collect::<Vec<_>>())
+            .find(|fields| fields.first() == Some(&user))
+            .and_then(|fields| fields.get(2)?.parse().ok())
+            .ok_or_else(|| format!("No local user '{}'", user))
+    }
+
+    /// Send one request to a running service and print the answer
+    fn call(socket: &Path, request: Request) -> Result<(), String> {
+        match service::call(socket, &request).map_err(|e| e.to_string())? {
+            Response::Done => println!("[+] Done"),
+            Response::Users(users) => users.iter().for_each(|user| println!("{}", user)),
+            Response::Events(events) => {
+                for event in events {
+                    let source = event.source.as_deref().unwrap_or("-");
+                    println!("{}  {:?}  {}  {}", event.at, event.kind, event.username, source);
+                }
+            }
+            Response::Certificate(certificate) => println!("{}", certificate),
+            Response::Traffic(traffic) => {
+                for user in traffic {
+                    println!("{}  rx {} B  tx {} B", user.username, user.rx_bytes, user.tx_bytes);
+                }
+            }
+            Response::Connections(connections) => {
+                for connection in connections {
+                    let since = connection.since.map(|since| since.to_rfc3339()).unwrap_or_else(|| "-".to_string());
+                    println!("{}  {}  since {}", connection.username, connection.source.as_deref().unwrap_or("-"), since);
+                }
+            }
+            Response::Quota(method) => println!("[+] Capped with a {}", method.label().to_lowercase()),
+            Response::Error(e) => return Err(e.to_string()),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+//! Host-local SSH certificate authority
+//! Job users sign in with user certificates signed by a CA key kept on this machine instead of
+//! passwords. Each certificate names the job user as its only principal and runs out when the
+//! job does, so sshd enforces the expiry itself; it trusts the CA through TrustedUserCAKeys.
+//! Every certificate has its own serial, so one can be voided early through the key revocation
+//! list sshd reads with RevokedKeys without touching the others issued for the same key.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::error::SshManagerError;
+
+/// Where the CA key lives, readable by root only
+#[cfg(unix)]
+pub const CA_DIR: &str = "/etc/eryzaa/ssh-ca";
+#[cfg(windows)]
+pub const CA_DIR: &str = r"C:\ProgramData\ssh\eryzaa_ca";
+
+/// Certificates are valid from a little before they are issued, for clocks running behind
+const CLOCK_SKEW_MINUTES: i64 = 5;
+
+/// Whether `path` exists, checked with the rights to look into the CA directory
+fn exists(path: &Path) -> bool {
+    #[cfg(unix)]
+    return crate::accounts::privileged("test").arg("-e").arg(path).status().is_ok_and(|status| status.success());
+    #[cfg(windows)]
+    return path.exists();
+}
+
+/// ssh-keygen run with the rights to read the CA key
+fn ssh_keygen() -> Command {
+    #[cfg(unix)]
+    return crate::accounts::privileged("ssh-keygen");
+    #[cfg(windows)]
+    return Command::new("ssh-keygen");
+}
+
+/// Run `command`, returning its stderr as the error
+fn run(mut command: Command, action: &str) -> Result<(), SshManagerError> {
+    let output = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| SshManagerError::spawn("ssh-keygen", e).context(&format!("Failed to {}", action)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(SshManagerError::from_command("ssh-keygen", &stderr).context(&format!("Failed to {}", action)));
+    }
+    Ok(())
+}
+
+/// ssh-keygen's `-V` interval from a few minutes ago until `expires_at`
+pub fn validity(now: chrono::DateTime<chrono::Utc>, expires_at: chrono::DateTime<chrono::Utc>) -> Result<String, SshManagerError> {
+    let seconds = (expires_at - now).num_seconds();
+    if seconds <= 0 {
+        return Err(SshManagerError::InvalidInput("The job has already expired".to_string()));
+    }
+    Ok(format!("-{}m:+{}m", CLOCK_SKEW_MINUTES, (seconds + 59) / 60))
+}
+
+/// A fresh ed25519 key pair for a client that brought no key, as (private, public)
+pub fn generate_key_pair(comment: &str) -> Result<(String, String), SshManagerError> {
+    let dir = std::env::temp_dir().join(format!("eryzaa-key-{}", uuid::Uuid::new_v4().simple()));
+    std::fs::create_dir_all(&dir).map_err(|e| SshManagerError::io("create", &dir, e))?;
+    let key = dir.join("id_ed25519");
+    let mut command = Command::new("ssh-keygen");
+    command.args(["-q", "-t", "ed25519", "-N", "", "-C", comment, "-f"]).arg(&key);
+    let pair = run(command, "generate a key pair").and_then(|_| {
+        let read = |path: &Path| std::fs::read_to_string(path).map_err(|e| SshManagerError::io("read", path, e));
+        Ok((read(&key)?, read(&key.with_extension("pub"))?.trim().to_string()))
+    });
+    let _ = std::fs::remove_dir_all(&dir);
+    pair
+}
+
+/// The CA key pair in one directory
+pub struct CertificateAuthority {
+    dir: PathBuf,
+}
+
+impl CertificateAuthority {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn key_path(&self) -> PathBuf {
+        self.dir.join("ca_key")
+    }
+
+    /// The public half, which sshd trusts
+    pub fn public_key_path(&self) -> PathBuf {
+        self.dir.join("ca_key.pub")
+    }
+
+    /// Key revocation list sshd checks every certificate against
+    pub fn revoked_keys_path(&self) -> PathBuf {
+        self.dir.join("revoked_keys")
+    }
+
+    /// Create the CA key unless there is one already
+    pub fn create_key(&self) -> Result<(), SshManagerError> {
+        if self.public_key_path().exists() {
+            return Ok(());
+        }
+        #[cfg(unix)]
+        {
+            let mut command = crate::accounts::privileged("install");
+            command.args(["-d", "-m", "700"]).arg(&self.dir);
+            run(command, "create the SSH CA directory")?;
+        }
+        #[cfg(windows)]
+        std::fs::create_dir_all(&self.dir).map_err(|e| SshManagerError::io("create", &self.dir, e))?;
+        let mut command = ssh_keygen();
+        command.args(["-q", "-t", "ed25519", "-N", "", "-C", "eryzaa job user CA", "-f"]).arg(self.key_path());
+        run(command, "create the SSH CA key")
+    }
+
+    /// Create the CA key if needed and have sshd trust it
+    pub fn prepare(&self) -> Result<(), SshManagerError> {
+        self.create_key()?;
+        crate::accounts::trust_user_ca(&self.public_key_path())
+    }
+
+    /// Certificate for `public_key` to sign in as `username` until `expires_at`, from `source_address` if given
+    pub fn sign(
+        &self,
+        public_key: &str,
+        username: &str,
+        expires_at: chrono::DateTime<chrono::Utc>,
+        source_address: Option<&str>,
+    ) -> Result<String, SshManagerError> {
+        let validity = validity(chrono::Utc::now(), expires_at)?;
+        let serial = uuid::Uuid::new_v4().as_u64_pair().0.max(1);
+        let dir = std::env::temp_dir().join(format!("eryzaa-cert-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).map_err(|e| SshManagerError::io("create", &dir, e))?;
+        let key = dir.join("key.pub");
+        let signed = std::fs::write(&key, public_key)
+            .map_err(|e| SshManagerError::io("write", &key, e))
+            .and_then(|_| {
+                let mut command = ssh_keygen();
+                command
+                    .args(["-q", "-s"])
+                    .arg(self.key_path())
+                    .args(["-I", &format!("eryzaa-{}-{}", username, serial), "-n", username, "-V", &validity])
+                    .args(["-z", &serial.to_string()]);
+                if let Some(addresses) = source_address {
+                    command.args(["-O", &format!("source-address={}", addresses)]);
+                }
+                command.arg(&key);
+                run(command, "sign the certificate")
+            })
+            .and_then(|_| {
+                let cert = dir.join("key-cert.pub");
+                std::fs::read_to_string(&cert).map_err(|e| SshManagerError::io("read", &cert, e))
+            });
+        let _ = std::fs::remove_dir_all(&dir);
+        signed.map(|cert| cert.trim().to_string())
+    }
+
+    /// Void `certificate` before it runs out, by its serial; the key it certifies may be certified again
+    pub fn revoke(&self, certificate: &str) -> Result<(), SshManagerError> {
+        let revoked_keys = self.revoked_keys_path();
+        // sshd refuses every key while RevokedKeys names a missing file, and -u needs one to update
+        if !exists(&revoked_keys) {
+            let mut command = ssh_keygen();
+            command.args(["-q", "-k", "-f"]).arg(&revoked_keys);
+            run(command, "create the key revocation list")?;
+        }
+        let dir = std::env::temp_dir().join(format!("eryzaa-revoke-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).map_err(|e| SshManagerError::io("create", &dir, e))?;
+        let cert = dir.join("key-cert.pub");
+        let revoked = std::fs::write(&cert, certificate).map_err(|e| SshManagerError::io("write", &cert, e)).and_then(|_| {
+            let mut command = ssh_keygen();
+            command.args(["-q", "-k", "-u", "-f"]).arg(&revoked_keys).arg(&cert);
+            run(command, "revoke the certificate")
+        });
+        let _ = std::fs::remove_dir_all(&dir);
+        revoked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_certificate() {
+        let now = chrono::Utc::now();
+        assert_eq!(validity(now, now + chrono::Duration::seconds(3601)), Ok("-5m:+61m".to_string()));
+        assert!(validity(now, now).is_err());
+
+        // Needs OpenSSH's ssh-keygen, and root or sudo for the CA key
+        let Ok((private_key, public_key)) = generate_key_pair("job_ab12cd34") else {
+            return;
+        };
+        assert!(private_key.contains("OPENSSH PRIVATE KEY"));
+        let ca = CertificateAuthority::new(std::env::temp_dir().join(format!("eryzaa-ca-{}", std::process::id())));
+        if ca.create_key().is_err() {
+            return;
+        }
+        let cert = ca.sign(&public_key, "job_ab12cd34", now + chrono::Duration::hours(2), None).unwrap();
+        assert!(cert.starts_with("ssh-ed25519-cert-v01@openssh.com "));
+        assert!(ca.sign(&public_key, "job_ab12cd34", now + chrono::Duration::hours(2), Some("10.147.17.5,10.147.18.0/24")).is_ok());
+        ca.revoke(&cert).unwrap();
+        assert!(exists(&ca.revoked_keys_path()));
+        std::fs::remove_dir_all(&ca.dir).unwrap();
+    }
+}
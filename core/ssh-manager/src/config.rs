@@ -0,0 +1,284 @@
+//! How the manager names job users and what passwords it accepts
+//! Names come from a template such as "job_{id}" or "job_gpu{id}". The privileged service, the
+//! audit log and the login helper only treat the prefix and eight lowercase letters or digits as
+//! a job user, so an owner's own accounts are never touched; a template picks what goes into
+//! those eight characters and `{id}` fills the rest at random. The prefix is "job_" unless the
+//! node's config file, which only root can change, names another. Job users normally sign in with
+//! certificates, but password credentials are held to the policy here, or refused entirely.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use log::warn;
+
+use crate::accounts::{Credential, JOB_USER_PREFIX};
+use crate::error::SshManagerError;
+
+/// Characters after the prefix of every job user's name
+pub const USERNAME_ID_LEN: usize = 8;
+
+/// Random characters a name needs at least, so two jobs don't get the same user
+const MIN_RANDOM_CHARS: usize = 4;
+
+/// Longest prefix, so names stay within the 32 characters useradd allows
+const MAX_PREFIX_LEN: usize = 16;
+
+/// Characters passwords are drawn from unless configured otherwise
+const DEFAULT_CHARSET: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PasswordPolicy {
+    pub length: usize, // Of generated passwords, and the least a given one may have
+    pub charset: String, // Generated passwords use only these
+    pub min_entropy_bits: f64,
+    #[serde(default)]
+    pub deny: bool, // No password logins at all, only keys and certificates
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self { length: 16, charset: DEFAULT_CHARSET.to_string(), min_entropy_bits: 80.0, deny: false }
+    }
+}
+
+impl PasswordPolicy {
+    fn charset(&self) -> Vec<char> {
+        let mut chars: Vec<char> = self.charset.chars().collect();
+        chars.sort_unstable();
+        chars.dedup();
+        chars
+    }
+
+    /// Entropy of a generated password, in bits
+    pub fn generated_entropy_bits(&self) -> f64 {
+        self.length as f64 * (self.charset().len() as f64).log2()
+    }
+
+    /// Entropy of `password` as guessed from its length and the kinds of characters in it
+    pub fn estimated_entropy_bits(password: &str) -> f64 {
+        let has = |class: fn(&char) -> bool| password.chars().any(|c| class(&c));
+        let pool = [
+            (has(char::is_ascii_lowercase), 26),
+            (has(char::is_ascii_uppercase), 26),
+            (has(char::is_ascii_digit), 10),
+            (has(|c| !c.is_ascii_alphanumeric()), 33), // Punctuation and space, or anything beyond ASCII
+        ];
+        let pool: usize = pool.iter().filter(|(used, _)| *used).map(|(_, size)| size).sum();
+        password.chars().count() as f64 * (pool.max(1) as f64).log2()
+    }
+
+    fn validate(&self) -> Result<(), SshManagerError> {
+        if !(2..=256).contains(&self.charset().len()) || self.charset.contains([':', '\n', '\r', '\0']) {
+            return Err(SshManagerError::InvalidInput("Passwords need 2 to 256 characters to draw from, without ':' or control characters".to_string()));
+        }
+        if self.generated_entropy_bits() < self.min_entropy_bits {
+            return Err(SshManagerError::InvalidInput(format!(
+                "{} characters from a set of {} give {:.0} bits, short of the required {:.0}",
+                self.length,
+                self.charset().len(),
+                self.generated_entropy_bits(),
+                self.min_entropy_bits
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check a password a client or owner chose
+    pub fn check(&self, password: &str) -> Result<(), SshManagerError> {
+        if self.deny {
+            return Err(SshManagerError::PermissionDenied("Password logins are turned off on this node".to_string()));
+        }
+        if password.chars().count() < self.length {
+            return Err(SshManagerError::InvalidInput(format!("Passwords must be at least {} characters", self.length)));
+        }
+        if Self::estimated_entropy_bits(password) < self.min_entropy_bits {
+            return Err(SshManagerError::InvalidInput(format!("Password is too easy to guess; use at least {:.0} bits", self.min_entropy_bits)));
+        }
+        Ok(())
+    }
+
+    /// A random password of `length` characters from the charset
+    pub fn generate(&self) -> Result<String, SshManagerError> {
+        self.validate()?;
+        Ok(random_chars(&self.charset(), self.length))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SshManagerConfig {
+    #[serde(default = "default_prefix")]
+    pub username_prefix: String, // Every job user's name starts with this
+    pub username_template: String, // The prefix, then letters, digits and `{id}`
+    #[serde(default)]
+    pub password: PasswordPolicy,
+}
+
+fn default_prefix() -> String {
+    JOB_USER_PREFIX.to_string()
+}
+
+impl Default for SshManagerConfig {
+    fn default() -> Self {
+        Self {
+            username_prefix: default_prefix(),
+            username_template: format!("{}{{id}}", JOB_USER_PREFIX),
+            password: PasswordPolicy::default(),
+        }
+    }
+}
+
+/// The node's config file, which the privileged service reads too
+pub fn config_path() -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from(r"C:\ProgramData\eryzaa\ssh-manager.json")
+    } else {
+        PathBuf::from("/etc/eryzaa/ssh-manager.json")
+    }
+}
+
+impl SshManagerConfig {
+    /// The config in `path`, or the defaults when there is none
+    pub fn load_from(path: &Path) -> Result<Self, SshManagerError> {
+        let config: Self = match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| SshManagerError::InvalidInput(format!("Malformed {}: {}", path.display(), e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(SshManagerError::Io(format!("Failed to read {}: {}", path.display(), e))),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// The node's config, read once; a broken file leaves the defaults in place
+    pub fn system() -> &'static Self {
+        static CONFIG: OnceLock<SshManagerConfig> = OnceLock::new();
+        CONFIG.get_or_init(|| {
+            Self::load_from(&config_path()).unwrap_or_else(|e| {
+                warn!("Using the default SSH manager config: {}", e);
+                Self::default()
+            })
+        })
+    }
+
+    /// Check that `username` is one of this config's job users: the prefix and eight letters or digits
+    pub fn check_username(&self, username: &str) -> Result<(), SshManagerError> {
+        let valid = username
+            .strip_prefix(self.username_prefix.as_str())
+            .is_some_and(|id| id.len() == USERNAME_ID_LEN && id.bytes().all(|b| b.is_ascii_alphanumeric()));
+        match valid {
+            true => Ok(()),
+            false => Err(SshManagerError::InvalidInput(format!("'{}' is not a job user", username))),
+        }
+    }
+
+    /// Check the prefix, template and password policy before the manager uses them
+    pub fn validate(&self) -> Result<(), SshManagerError> {
+        let prefix = &self.username_prefix;
+        let prefix_valid = prefix.len() <= MAX_PREFIX_LEN
+            && prefix.starts_with(|c: char| c.is_ascii_lowercase())
+            && prefix.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_' || b == b'-');
+        if !prefix_valid {
+            return Err(SshManagerError::InvalidInput(format!(
+                "Username prefix '{}' must start with a lowercase letter and have at most {} lowercase letters, digits, '_' or '-'",
+                prefix, MAX_PREFIX_LEN
+            )));
+        }
+        let fixed = self
+            .username_template
+            .strip_prefix(prefix.as_str())
+            .filter(|rest| rest.matches("{id}").count() == 1)
+            .map(|rest| rest.replace("{id}", ""))
+            .ok_or_else(|| SshManagerError::InvalidInput(format!("Username template must start with '{}' and hold one {{id}}", prefix)))?;
+        if !fixed.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit()) {
+            return Err(SshManagerError::InvalidInput("Username templates may only add lowercase letters and digits".to_string()));
+        }
+        if fixed.len() + MIN_RANDOM_CHARS > USERNAME_ID_LEN {
+            return Err(SshManagerError::InvalidInput(format!(
+                "Username templates may add at most {} characters",
+                USERNAME_ID_LEN - MIN_RANDOM_CHARS
+            )));
+        }
+        self.password.validate()
+    }
+
+    /// A fresh name for a job user, with `{id}` filled at random
+    pub fn username(&self) -> Result<String, SshManagerError> {
+        self.validate()?;
+        let fixed = self.username_template.len() - self.username_prefix.len() - "{id}".len();
+        let id = random_chars(&"abcdef0123456789".chars().collect::<Vec<_>>(), USERNAME_ID_LEN - fixed);
+        let username = self.username_template.replace("{id}", &id);
+        self.check_username(&username)?;
+        Ok(username)
+    }
+
+    /// Check a credential a job user would sign in with
+    pub fn check_credential(&self, credential: &Credential) -> Result<(), SshManagerError> {
+        match credential {
+            Credential::Password(password) => self.password.check(password),
+            Credential::PublicKey(_) | Credential::Certificate => Ok(()),
+        }
+    }
+}
+
+/// `count` characters drawn uniformly from `chars`, of which there are at most 256
+fn random_chars(chars: &[char], count: usize) -> String {
+    // v4 UUIDs come from the OS's random source, apart from the bytes holding the version and
+    // variant; bytes past a multiple of the set's size are dropped, so no character is likelier
+    let limit = 256 - 256 % chars.len();
+    std::iter::repeat_with(|| uuid::Uuid::new_v4().into_bytes())
+        .flat_map(|bytes| bytes.into_iter().enumerate().filter(|(index, _)| *index != 6 && *index != 8).map(|(_, byte)| byte))
+        .filter(|byte| (*byte as usize) < limit)
+        .take(count)
+        .map(|byte| chars[byte as usize % chars.len()])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_username_and_password_policy() {
+        let config = SshManagerConfig { username_template: "job_gpu{id}".to_string(), ..Default::default() };
+        let username = config.username().unwrap();
+        assert!(username.starts_with("job_gpu") && username.len() == JOB_USER_PREFIX.len() + USERNAME_ID_LEN);
+        assert!(crate::accounts::validate_username(&username).is_ok());
+        assert!(SshManagerConfig::default().username().unwrap().starts_with(JOB_USER_PREFIX));
+        for template in ["user_{id}", "job_", "job_{id}{id}", "job_GPU{id}", "job_train{id}", "job_a-b{id}"] {
+            let config = SshManagerConfig { username_template: template.to_string(), ..Default::default() };
+            assert!(config.validate().is_err(), "{}", template);
+        }
+
+        // An owner whose own accounts start with "job_" can have job users named otherwise
+        let custom = SshManagerConfig { username_prefix: "rent-".to_string(), username_template: "rent-gpu{id}".to_string(), ..Default::default() };
+        let username = custom.username().unwrap();
+        assert!(username.starts_with("rent-gpu") && username.len() == "rent-".len() + USERNAME_ID_LEN);
+        assert!(custom.check_username(&username).is_ok());
+        assert!(custom.check_username("job_1a2b3c4d").is_err());
+        for prefix in ["", "Job_", "1job", "job_/", "a_very_long_prefix_"] {
+            let config = SshManagerConfig { username_prefix: prefix.to_string(), username_template: format!("{}{{id}}", prefix), ..Default::default() };
+            assert!(config.validate().is_err(), "{}", prefix);
+        }
+        let path = std::env::temp_dir().join(format!("eryzaa-ssh-config-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{"username_prefix": "rent-", "username_template": "rent-{id}"}"#).unwrap();
+        assert_eq!(SshManagerConfig::load_from(&path).unwrap().username_prefix, "rent-");
+        std::fs::write(&path, r#"{"username_prefix": "root", "username_template": "job_{id}"}"#).unwrap();
+        assert!(SshManagerConfig::load_from(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(SshManagerConfig::load_from(&path).unwrap(), SshManagerConfig::default());
+
+        let policy = PasswordPolicy::default();
+        let password = policy.generate().unwrap();
+        assert_eq!(password.len(), 16);
+        assert!(password.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert!(policy.check("Tr0ub4dor&3xyzQw").is_ok());
+        assert!(policy.check("aaaaaaaaaaaaaaaa").is_err()); // 16 lowercase letters are 75 bits
+        assert!(policy.check("Sh0rt!").is_err());
+        let weak = PasswordPolicy { length: 8, charset: "0123456789".to_string(), ..Default::default() };
+        assert!(weak.generate().is_err());
+        let denied = SshManagerConfig { password: PasswordPolicy { deny: true, ..Default::default() }, ..Default::default() };
+        assert!(matches!(denied.check_credential(&Credential::Password(password)), Err(SshManagerError::PermissionDenied(_))));
+        assert!(denied.check_credential(&Credential::Certificate).is_ok());
+    }
+}
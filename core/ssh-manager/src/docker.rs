@@ -0,0 +1,389 @@
+//! Docker for job users
+//! Members of the docker group can start privileged containers and mount the host's root, so the
+//! group is as good as root on the node. A job can instead give its user a socket proxy in front
+//! of the node's engine, which only lets the user inspect, follow, copy to and exec into the
+//! job's own container; a rootless dockerd of the user's own; or rootless podman. Rootless
+//! engines can't see the job's container, so container-only users need the group or the proxy.
+
+use serde::{Deserialize, Serialize};
+
+use crate::accounts::IsolationLevel;
+use crate::error::SshManagerError;
+use crate::grants;
+
+/// Where the node's engine listens
+pub const DOCKER_SOCKET: &str = "/var/run/docker.sock";
+
+/// Where each proxied user's socket is made, as `<username>.sock`
+pub const PROXY_SOCKET_DIR: &str = "/run/eryzaa/docker";
+
+/// Proxied users and their jobs, kept across reboots so the service can start the proxies again
+pub const PROXY_STATE_DIR: &str = "/var/lib/eryzaa/docker-proxy";
+
+/// Name of the docker CLI context pointing at a user's proxy
+pub const PROXY_CONTEXT: &str = "eryzaa";
+
+/// How a job's user reaches a container engine
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DockerAccess {
+    #[default]
+    Group, // The docker group: everything, root on the host included
+    Proxy,    // The node's engine, through a proxy that allows the job's container only
+    Rootless, // A dockerd of the user's own, running as the user
+    Podman,   // Daemonless and rootless, without the node's containers
+    None,
+}
+
+impl DockerAccess {
+    pub const ALL: [Self; 5] = [Self::Group, Self::Proxy, Self::Rootless, Self::Podman, Self::None];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Group => "docker group (root on the host)",
+            Self::Proxy => "Job container through a proxy",
+            Self::Rootless => "Rootless Docker",
+            Self::Podman => "Rootless Podman",
+            Self::None => "No containers",
+        }
+    }
+
+    /// What a user isolated at `isolation` gets; only full shells and container-only users get docker
+    pub fn for_isolation(self, isolation: IsolationLevel) -> Self {
+        match isolation.docker_access() {
+            true => self,
+            false => Self::None,
+        }
+    }
+
+    /// Check that a user isolated at `isolation` can still work with this
+    pub fn check(&self, isolation: IsolationLevel) -> Result<(), SshManagerError> {
+        match (isolation, self.for_isolation(isolation)) {
+            (IsolationLevel::ContainerOnly, Self::Rootless | Self::Podman | Self::None) => Err(SshManagerError::InvalidInput(format!(
+                "A container-only user needs the docker group or the proxy to reach the job's container, not {}",
+                self.label().to_lowercase()
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A Docker API request as the proxy sees it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiRequest<'a> {
+    pub method: &'a str,
+    pub path: &'a str, // Without the query or API version
+    pub body: Option<&'a [u8]>, // Only for calls whose body is checked
+}
+
+impl<'a> ApiRequest<'a> {
+    /// The request for `method` and request target `target`, e.g. "/v1.43/containers/x/json?size=1"
+    pub fn parse(method: &'a str, target: &'a str) -> Self {
+        let path = target.split(['?', '#']).next().unwrap_or_default();
+        let unversioned = path
+            .strip_prefix("/v")
+            .and_then(|rest| rest.split_once('/'))
+            .filter(|(version, _)| !version.is_empty() && version.bytes().all(|b| b.is_ascii_digit() || b == b'.'))
+            .map(|(_, rest)| &path[path.len() - rest.len() - 1..]);
+        Self { method, path: unversioned.unwrap_or(path), body: None }
+    }
+
+    /// Whether the body has to be read and checked before the request goes on
+    pub fn needs_body(&self) -> bool {
+        self.method == "POST" && self.path.ends_with("/exec") && self.path.starts_with("/containers/")
+    }
+
+    /// Check the request against what a proxied user of job `job_id` may do
+    pub fn check(&self, job_id: &str) -> Result<(), String> {
+        let container = grants::container_name(job_id);
+        let parts: Vec<&str> = self.path.trim_start_matches('/').split('/').collect();
+        let allowed = match (self.method, parts.as_slice()) {
+            ("GET" | "HEAD", ["_ping"]) | ("GET", ["version"]) => true,
+            // Exec ids are long and random; only this user's exec calls know them
+            ("POST", ["exec", id, "start" | "resize"]) | ("GET", ["exec", id, "json"]) => is_id(id),
+            (method, ["containers", name, action]) if *name == container => match (method, *action) {
+                ("GET", "json" | "logs" | "top" | "stats") => true,
+                ("POST", "attach" | "resize" | "wait") => true,
+                ("GET" | "HEAD" | "PUT", "archive") => true,
+                ("POST", "exec") => return self.check_exec(),
+                _ => false,
+            },
+            (_, ["containers", _, ..]) => return Err(format!("Only the job's container, {}, is reachable through this proxy", container)),
+            _ => false,
+        };
+        match allowed {
+            true => Ok(()),
+            false => Err(format!("{} {} is not allowed through this proxy", self.method, self.path)),
+        }
+    }
+
+    /// An exec may not ask for more privileges than the container has
+    fn check_exec(&self) -> Result<(), String> {
+        let body = self.body.ok_or("Exec requests need a body with a Content-Length")?;
+        let config: serde_json::Value = serde_json::from_slice(body).map_err(|e| format!("Malformed exec request: {}", e))?;
+        if config.get("Privileged").and_then(serde_json::Value::as_bool) == Some(true) {
+            return Err("Privileged exec is not allowed through this proxy".to_string());
+        }
+        Ok(())
+    }
+}
+
+fn is_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(unix)]
+pub use proxy::{restore_proxies, start_proxy, stop_proxy};
+
+#[cfg(unix)]
+mod proxy {
+    use std::collections::HashMap;
+    use std::io::{Read, Write};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use log::{debug, info, warn};
+
+    use super::*;
+
+    /// Longest request head the proxy reads
+    const MAX_HEAD: usize = 64 * 1024;
+
+    /// Longest exec request body the proxy reads
+    const MAX_BODY: usize = 64 * 1024;
+
+    /// Proxies running in this process, by username, with the flag that stops each
+    static PROXIES: Mutex<Option<HashMap<String, Arc<AtomicBool>>>> = Mutex::new(None);
+
+    fn socket_path(username: &str) -> PathBuf {
+        Path::new(PROXY_SOCKET_DIR).join(format!("{}.sock", username))
+    }
+
+    /// Start the proxy for `username` on its socket, remembering it for `restore_proxies`
+    ///
+    /// The socket belongs to the user alone; the caller gives it to them with `chown`.
+    pub fn start_proxy(username: &str, job_id: &str) -> Result<PathBuf, SshManagerError> {
+        if !grants::command_safe(job_id) {
+            return Err(SshManagerError::InvalidInput(format!("Job id '{}' cannot name a container", job_id)));
+        }
+        for dir in [PROXY_SOCKET_DIR, PROXY_STATE_DIR] {
+            std::fs::create_dir_all(dir).map_err(|e| SshManagerError::io("create", Path::new(dir), e))?;
+        }
+        let record = Path::new(PROXY_STATE_DIR).join(username);
+        std::fs::write(&record, job_id).map_err(|e| SshManagerError::io("write", &record, e))?;
+        serve(username, job_id)
+    }
+
+    fn serve(username: &str, job_id: &str) -> Result<PathBuf, SshManagerError> {
+        stop_listening(username);
+        let path = socket_path(username);
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).map_err(|e| SshManagerError::io("listen on", &path, e))?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).map_err(|e| SshManagerError::io("secure", &path, e))?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        PROXIES.lock().unwrap().get_or_insert_with(HashMap::new).insert(username.to_string(), stop.clone());
+        let (user, job) = (username.to_string(), job_id.to_string());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let job = job.clone();
+                std::thread::spawn(move || {
+                    if let Err(e) = handle(stream, &job) {
+                        debug!("Docker proxy connection for job '{}' ended: {}", job, e);
+                    }
+                });
+            }
+            debug!("Stopped the docker proxy of '{}'", user);
+        });
+        info!("Started a docker proxy for '{}' on {}", username, path.display());
+        Ok(path)
+    }
+
+    /// Stop the proxy of `username`, if one runs, and forget it
+    pub fn stop_proxy(username: &str) {
+        stop_listening(username);
+        let _ = std::fs::remove_file(socket_path(username));
+        let _ = std::fs::remove_file(Path::new(PROXY_STATE_DIR).join(username));
+    }
+
+    fn stop_listening(username: &str) {
+        let stop = PROXIES.lock().unwrap().as_mut().and_then(|proxies| proxies.remove(username));
+        if let Some(stop) = stop {
+            stop.store(true, Ordering::SeqCst);
+            // Wakes the accept loop so it sees the flag
+            let _ = UnixStream::connect(socket_path(username));
+        }
+    }
+
+    /// Start the proxies of users that still exist, e.g. after the service restarted
+    pub fn restore_proxies(users: &[String]) -> usize {
+        let Ok(entries) = std::fs::read_dir(PROXY_STATE_DIR) else {
+            return 0;
+        };
+        let mut started = 0;
+        for entry in entries.flatten() {
+            let username = entry.file_name().to_string_lossy().to_string();
+            let job_id = std::fs::read_to_string(entry.path()).unwrap_or_default();
+            if !users.contains(&username) {
+                let _ = std::fs::remove_file(entry.path());
+                continue;
+            }
+            let restored = serve(&username, job_id.trim()).and_then(|path| crate::unix::give_to(&username, &path));
+            match restored {
+                Ok(()) => started += 1,
+                Err(e) => warn!("Docker proxy of '{}' is down: {}", username, e),
+            }
+        }
+        started
+    }
+
+    /// Check one request from the user and pass it to the engine, answering 403 when refused
+    fn handle(mut client: UnixStream, job_id: &str) -> Result<(), String> {
+        let (head, mut rest) = read_head(&mut client)?;
+        let head_text = String::from_utf8(head).map_err(|_| "Request head is not UTF-8".to_string())?;
+        let mut lines = head_text.split("\r\n");
+        let request_line = lines.next().unwrap_or_default();
+        let mut fields = request_line.split(' ');
+        let (Some(method), Some(target), Some(version)) = (fields.next(), fields.next(), fields.next()) else {
+            return refuse(&mut client, "Malformed request line");
+        };
+        let headers: Vec<(&str, &str)> = lines
+            .filter(|line| !line.is_empty())
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim(), value.trim()))
+            .collect();
+        let header = |name: &str| headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| *value);
+
+        let mut request = ApiRequest::parse(method, target);
+        if request.needs_body() {
+            // The engine must read the same body the proxy checked
+            let lengths = headers.iter().filter(|(name, _)| name.eq_ignore_ascii_case("Content-Length")).count();
+            let length: usize = match header("Content-Length").map(str::parse) {
+                Some(Ok(length)) if length <= MAX_BODY && lengths == 1 && header("Transfer-Encoding").is_none() => length,
+                _ => return refuse(&mut client, "Exec requests need one Content-Length of at most 64 KiB"),
+            };
+            while rest.len() < length {
+                let mut buffer = [0; 8192];
+                let read = client.read(&mut buffer).map_err(|e| e.to_string())?;
+                if read == 0 {
+                    return Err("Client closed the connection mid-body".to_string());
+                }
+                rest.extend_from_slice(&buffer[..read]);
+            }
+        }
+        let body = rest.clone();
+        if request.needs_body() {
+            request.body = header("Content-Length").and_then(|length| length.parse().ok()).map(|length: usize| &body[..length.min(body.len())]);
+        }
+        if let Err(reason) = request.check(job_id) {
+            return refuse(&mut client, &reason);
+        }
+
+        // One request per connection, so nothing the proxy did not check follows it; the engine
+        // goes by the Upgrade header when it takes an exec or attach over for a raw stream
+        let mut forwarded = format!("{} {} {}\r\n", method, target, version);
+        for (name, value) in headers.iter().filter(|(name, _)| !name.eq_ignore_ascii_case("Connection")) {
+            forwarded.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        forwarded.push_str("Connection: close\r\n\r\n");
+
+        let mut engine = UnixStream::connect(DOCKER_SOCKET).map_err(|e| format!("Failed to reach {}: {}", DOCKER_SOCKET, e))?;
+        engine.write_all(forwarded.as_bytes()).and_then(|_| engine.write_all(&rest)).map_err(|e| e.to_string())?;
+        pipe(client, engine)
+    }
+
+    /// The request head up to its blank line, and whatever was read past it
+    fn read_head(client: &mut UnixStream) -> Result<(Vec<u8>, Vec<u8>), String> {
+        let mut read = vec![];
+        let mut buffer = [0; 8192];
+        loop {
+            if let Some(end) = read.windows(4).position(|window| window == b"\r\n\r\n") {
+                let rest = read.split_off(end + 4);
+                return Ok((read, rest));
+            }
+            if read.len() > MAX_HEAD {
+                return Err("Request head is too large".to_string());
+            }
+            let count = client.read(&mut buffer).map_err(|e| e.to_string())?;
+            if count == 0 {
+                return Err("Client closed the connection".to_string());
+            }
+            read.extend_from_slice(&buffer[..count]);
+        }
+    }
+
+    fn refuse(client: &mut UnixStream, reason: &str) -> Result<(), String> {
+        let body = serde_json::json!({ "message": reason }).to_string();
+        let response = format!(
+            "HTTP/1.1 403 Forbidden\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let _ = client.write_all(response.as_bytes());
+        Err(reason.to_string())
+    }
+
+    /// Copy both ways until either side is done
+    fn pipe(client: UnixStream, engine: UnixStream) -> Result<(), String> {
+        let (mut client_read, mut engine_write) = (client.try_clone().map_err(|e| e.to_string())?, engine.try_clone().map_err(|e| e.to_string())?);
+        let upstream = std::thread::spawn(move || {
+            let _ = std::io::copy(&mut client_read, &mut engine_write);
+            let _ = engine_write.shutdown(std::net::Shutdown::Write);
+        });
+        let (mut engine_read, mut client_write) = (engine, client);
+        let _ = std::io::copy(&mut engine_read, &mut client_write);
+        let _ = client_write.shutdown(std::net::Shutdown::Both);
+        let _ = upstream.join();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_docker_access() {
+        assert_eq!(DockerAccess::Proxy.for_isolation(IsolationLevel::Chroot), DockerAccess::None);
+        assert!(DockerAccess::Proxy.check(IsolationLevel::ContainerOnly).is_ok());
+        assert!(DockerAccess::Rootless.check(IsolationLevel::ContainerOnly).is_err());
+        assert!(DockerAccess::None.check(IsolationLevel::RestrictedShell).is_ok());
+
+        let request = ApiRequest::parse("GET", "/v1.43/containers/eryzaa-job-train-1/logs?follow=1");
+        assert_eq!(request.path, "/containers/eryzaa-job-train-1/logs");
+        assert!(request.check("train-1").is_ok());
+        assert!(request.check("other").unwrap_err().contains("eryzaa-job-other"));
+        assert!(ApiRequest::parse("GET", "/_ping").check("train-1").is_ok());
+        for (method, target) in [
+            ("POST", "/v1.43/containers/create"),
+            ("GET", "/containers/json"),
+            ("DELETE", "/containers/eryzaa-job-train-1"),
+            ("POST", "/containers/eryzaa-job-train-1/stop"),
+            ("POST", "/images/create?fromImage=alpine"),
+            ("POST", "/exec/../containers/create/start"),
+            ("GET", "/v1.43/../containers/json"),
+        ] {
+            assert!(ApiRequest::parse(method, target).check("train-1").is_err(), "{} {}", method, target);
+        }
+
+        let mut exec = ApiRequest::parse("POST", "/v1.43/containers/eryzaa-job-train-1/exec");
+        assert!(exec.needs_body());
+        assert!(exec.check("train-1").is_err());
+        exec.body = Some(br#"{"Cmd": ["bash"], "Tty": true}"#);
+        assert!(exec.check("train-1").is_ok());
+        exec.body = Some(br#"{"Cmd": ["bash"], "Privileged": true}"#);
+        assert!(exec.check("train-1").is_err());
+        let target = format!("/v1.43/exec/{}/start", "ab".repeat(32));
+        assert!(ApiRequest::parse("POST", &target).check("train-1").is_ok());
+        assert!(ApiRequest::parse("POST", "/exec/abc/start").check("train-1").is_err());
+    }
+}
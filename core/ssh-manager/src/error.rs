@@ -0,0 +1,118 @@
+//! Errors of the SSH manager
+//! Each variant is a class of failure callers can act on: a user or job that already exists or
+//! is missing, a full node, bad input from a client, missing rights, the privileged service not
+//! answering, or a system tool failing. Variants cross the service socket unchanged, so the GUI
+//! sees the class of a failure inside the service too.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum SshManagerError {
+    #[error("{0}")]
+    AlreadyExists(String), // The job already has a user, or the user name is taken
+    #[error("{0}")]
+    NotFound(String), // No such job, user or grant
+    #[error("All {0} SSH session slots on this rental node are in use")]
+    NoFreeSlots(usize),
+    #[error("{0}")]
+    InvalidInput(String), // A user name, key, identity or job id that was refused
+    #[error("{0}")]
+    Unauthorized(String), // A caller or token without the right to ask
+    #[error("{0}")]
+    PermissionDenied(String), // We lack root, sudo or file permissions
+    #[error("{0}")]
+    ServiceUnavailable(String), // The privileged service is unreachable or busy
+    #[error("{0}")]
+    Unsupported(String), // Not available on this platform or setup
+    #[error("{0}")]
+    Io(String), // Reading or writing our own files
+    #[error("{0}")]
+    Command(String), // A system tool failed
+    #[error("{0}")]
+    Protocol(String), // A malformed or unexpected message on the service socket
+}
+
+impl SshManagerError {
+    /// The same error with `context` put in front of its message
+    pub fn context(self, context: &str) -> Self {
+        let prefix = |message: String| format!("{}: {}", context, message);
+        match self {
+            Self::AlreadyExists(message) => Self::AlreadyExists(prefix(message)),
+            Self::NotFound(message) => Self::NotFound(prefix(message)),
+            Self::NoFreeSlots(max_sessions) => Self::NoFreeSlots(max_sessions),
+            Self::InvalidInput(message) => Self::InvalidInput(prefix(message)),
+            Self::Unauthorized(message) => Self::Unauthorized(prefix(message)),
+            Self::PermissionDenied(message) => Self::PermissionDenied(prefix(message)),
+            Self::ServiceUnavailable(message) => Self::ServiceUnavailable(prefix(message)),
+            Self::Unsupported(message) => Self::Unsupported(prefix(message)),
+            Self::Io(message) => Self::Io(prefix(message)),
+            Self::Command(message) => Self::Command(prefix(message)),
+            Self::Protocol(message) => Self::Protocol(prefix(message)),
+        }
+    }
+
+    /// A failed system tool, classified by what it printed
+    pub fn from_command(program: &str, stderr: &str) -> Self {
+        let message = match stderr.trim() {
+            "" => format!("{} failed", program),
+            stderr => stderr.to_string(),
+        };
+        let lower = message.to_lowercase();
+        if ["permission denied", "password is required", "terminal is required", "not in the sudoers", "access is denied"]
+            .iter()
+            .any(|needle| lower.contains(needle))
+        {
+            Self::PermissionDenied(message)
+        } else if lower.contains("already exists") {
+            Self::AlreadyExists(message)
+        } else if lower.contains("does not exist") || lower.contains("was not found") {
+            Self::NotFound(message)
+        } else {
+            Self::Command(message)
+        }
+    }
+
+    /// A system tool that would not start, as unsupported when it is not installed
+    pub fn spawn(program: &str, error: std::io::Error) -> Self {
+        let message = format!("Failed to execute {}: {}", program, error);
+        match error.kind() {
+            std::io::ErrorKind::NotFound => Self::Unsupported(message),
+            std::io::ErrorKind::PermissionDenied => Self::PermissionDenied(message),
+            _ => Self::Command(message),
+        }
+    }
+
+    /// A file we could not read or write, as permission denied when that is why
+    pub fn io(action: &str, path: &std::path::Path, error: std::io::Error) -> Self {
+        let message = format!("Failed to {} {}: {}", action, path.display(), error);
+        match error.kind() {
+            std::io::ErrorKind::PermissionDenied => Self::PermissionDenied(message),
+            std::io::ErrorKind::NotFound => Self::NotFound(message),
+            _ => Self::Io(message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_and_carry_errors() {
+        let denied = SshManagerError::from_command("useradd", "sudo: a terminal is required to read the password\n");
+        assert!(matches!(denied, SshManagerError::PermissionDenied(_)));
+        let taken = SshManagerError::from_command("useradd", "useradd: user 'job_1a2b3c4d' already exists");
+        assert_eq!(
+            taken.context("Failed to create user").to_string(),
+            "Failed to create user: useradd: user 'job_1a2b3c4d' already exists"
+        );
+        assert!(matches!(SshManagerError::from_command("chpasswd", ""), SshManagerError::Command(e) if e == "chpasswd failed"));
+
+        // As the service sends it
+        let json = serde_json::to_string(&SshManagerError::NoFreeSlots(4)).unwrap();
+        assert_eq!(json, r#"{"kind":"no_free_slots","message":4}"#);
+        assert_eq!(serde_json::from_str::<SshManagerError>(&json).unwrap(), SshManagerError::NoFreeSlots(4));
+    }
+}
@@ -0,0 +1,117 @@
+//! SSH lifecycle events
+//! Subscribers hear when a job user is created, signs in, signs out and expires, so billing and
+//! notifications can follow what a renter actually does rather than when the account was made.
+//! Logins and logouts come from sshd's journal as `SshManager::collect_audit` files them, so they
+//! arrive as often as that is called and only for managers with a state file.
+
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+use crate::audit::{AuditEvent, AuditKind};
+
+/// Events a channel subscriber lagging this far behind misses
+const EVENT_BACKLOG: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionEvent {
+    UserCreated { job_id: String, client_id: String, username: String },
+    Login { job_id: String, username: String, at: u64, source: Option<String>, identity: Option<String> },
+    Logout { job_id: String, username: String, at: u64, source: Option<String> },
+    Expired { job_id: String, username: String }, // The user is gone with its job
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEventKind {
+    UserCreated,
+    Login,
+    Logout,
+    Expired,
+}
+
+impl SessionEvent {
+    pub fn kind(&self) -> SessionEventKind {
+        match self {
+            Self::UserCreated { .. } => SessionEventKind::UserCreated,
+            Self::Login { .. } => SessionEventKind::Login,
+            Self::Logout { .. } => SessionEventKind::Logout,
+            Self::Expired { .. } => SessionEventKind::Expired,
+        }
+    }
+
+    pub fn job_id(&self) -> &str {
+        match self {
+            Self::UserCreated { job_id, .. } | Self::Login { job_id, .. } | Self::Logout { job_id, .. } | Self::Expired { job_id, .. } => job_id,
+        }
+    }
+
+    /// The event an audited sshd entry of `job_id` raises; failed attempts raise none
+    pub fn from_audit(job_id: &str, event: &AuditEvent) -> Option<Self> {
+        let (job_id, username, at, source) = (job_id.to_string(), event.username.clone(), event.at, event.source.clone());
+        match event.kind {
+            AuditKind::Login => Some(Self::Login { job_id, username, at, source, identity: event.identity.clone() }),
+            AuditKind::Logout => Some(Self::Logout { job_id, username, at, source }),
+            AuditKind::Failed => None,
+        }
+    }
+}
+
+type Callback = Box<dyn Fn(&SessionEvent) + Send + Sync>;
+
+/// Callbacks by event kind, and a channel carrying every event
+pub struct EventBus {
+    channel: broadcast::Sender<SessionEvent>,
+    callbacks: Mutex<Vec<(SessionEventKind, Callback)>>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self { channel: broadcast::channel(EVENT_BACKLOG).0, callbacks: Mutex::new(vec![]) }
+    }
+}
+
+impl EventBus {
+    /// Every event from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<SessionEvent> {
+        self.channel.subscribe()
+    }
+
+    /// Run `callback` on every event of `kind`, on the thread that raises it
+    pub fn on(&self, kind: SessionEventKind, callback: impl Fn(&SessionEvent) + Send + Sync + 'static) {
+        self.callbacks.lock().unwrap().push((kind, Box::new(callback)));
+    }
+
+    pub fn publish(&self, event: SessionEvent) {
+        for (kind, callback) in self.callbacks.lock().unwrap().iter() {
+            if *kind == event.kind() {
+                callback(&event);
+            }
+        }
+        // Nobody listening is fine
+        let _ = self.channel.send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_event_bus() {
+        let bus = EventBus::default();
+        let logins = Arc::new(Mutex::new(vec![]));
+        let seen = Arc::clone(&logins);
+        bus.on(SessionEventKind::Login, move |event| seen.lock().unwrap().push(event.job_id().to_string()));
+        let mut channel = bus.subscribe();
+
+        let entry = AuditEvent::parse("Accepted publickey for job_ab12cd34 from 10.0.0.7 port 5022 ssh2", 100, 42).unwrap();
+        bus.publish(SessionEvent::from_audit("job-1", &entry).unwrap());
+        bus.publish(SessionEvent::Expired { job_id: "job-1".to_string(), username: "job_ab12cd34".to_string() });
+        let failed = AuditEvent::parse("Failed password for job_ab12cd34 from 10.0.0.7 port 5022 ssh2", 101, 43).unwrap();
+        assert_eq!(SessionEvent::from_audit("job-1", &failed), None);
+
+        assert_eq!(*logins.lock().unwrap(), vec!["job-1"]);
+        assert!(matches!(channel.try_recv(), Ok(SessionEvent::Login { at: 100, source: Some(source), .. }) if source == "10.0.0.7"));
+        assert_eq!(channel.try_recv().unwrap().kind(), SessionEventKind::Expired);
+    }
+}
@@ -0,0 +1,235 @@
+//! Delegated access to a running job
+//! The paying client can let teammates into its job. Each grant names an identity, the key it
+//! signs in with and a scope, and is written to the job user's authorized_keys with options that
+//! hold the key to that scope. Key fingerprints tie audit entries back to identities.
+//!
+//! Clients file grant requests over their own SSH session, into a queue the rental GUI works
+//! through; a request counts only with the job's access token or a full-control grantee's.
+
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::SshManagerError;
+use crate::sources::SourceRestriction;
+
+/// Where clients file grant requests; job users may add files but not remove anyone else's
+pub const GRANT_QUEUE_DIR: &str = "/var/lib/eryzaa/grants";
+
+/// Logs-only keys run this with the job's container name appended
+const LOGS_COMMAND: &str = "docker logs --follow --tail 500";
+
+/// Name of a job's container, as `eryzaa_jobs::container_name_for` gives it
+pub(crate) fn container_name(job_id: &str) -> String {
+    format!("eryzaa-job-{}", job_id)
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessScope {
+    Ssh,      // A shell, without forwarding
+    LogsOnly, // Follows the job's container logs and nothing else
+    Full,     // Everything the client can do, including granting access
+}
+
+impl AccessScope {
+    pub fn parse(scope: &str) -> Result<Self, SshManagerError> {
+        match scope {
+            "ssh" => Ok(Self::Ssh),
+            "logs" | "logs-only" => Ok(Self::LogsOnly),
+            "full" => Ok(Self::Full),
+            _ => Err(SshManagerError::InvalidInput(format!("Unknown scope '{}'; use ssh, logs or full", scope))),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Ssh => "SSH",
+            Self::LogsOnly => "Logs only",
+            Self::Full => "Full control",
+        }
+    }
+}
+
+/// A teammate let into a job
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Grant {
+    pub identity: String, // Who the client says the key belongs to, e.g. an email address
+    pub scope: AccessScope,
+    pub public_key: String,
+    pub fingerprint: String, // As sshd logs it, "SHA256:..."
+    pub granted_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub token_hash: Option<String>, // Full control only: SHA-256 of the token the grantee files requests with
+}
+
+/// Check an identity given by a client: letters, digits and @ . _ + -
+pub fn validate_identity(identity: &str) -> Result<(), SshManagerError> {
+    let valid = !identity.is_empty()
+        && identity.len() <= 64
+        && identity.bytes().all(|b| b.is_ascii_alphanumeric() || b"@._+-".contains(&b));
+    match valid {
+        true => Ok(()),
+        false => Err(SshManagerError::InvalidInput(format!("'{}' is not a valid identity", identity))),
+    }
+}
+
+/// Fingerprint of an authorized_keys line, as sshd logs it
+pub fn fingerprint(key: &str) -> Option<String> {
+    let body = key.split_whitespace().nth(1)?;
+    let blob = STANDARD.decode(body).ok()?;
+    Some(format!("SHA256:{}", STANDARD_NO_PAD.encode(Sha256::digest(blob))))
+}
+
+/// A fresh token for a full-control grantee, handed over by the client
+pub fn new_token() -> String {
+    format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+}
+
+/// Hex SHA-256 of a token, as grants keep it
+pub fn token_hash(token: &str) -> String {
+    Sha256::digest(token.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// authorized_keys line for a grant of job `job_id`, labelled with the identity
+///
+/// `key` must have passed `validate_public_key`, which refuses options of its own.
+pub fn authorized_keys_line(key: &str, scope: AccessScope, identity: &str, job_id: &str) -> String {
+    let mut fields = key.split_whitespace();
+    let key = format!("{} {} eryzaa:{}", fields.next().unwrap_or_default(), fields.next().unwrap_or_default(), identity);
+    match scope {
+        AccessScope::Full => key,
+        AccessScope::Ssh => format!("no-port-forwarding,no-agent-forwarding,no-X11-forwarding {}", key),
+        AccessScope::LogsOnly => format!("restrict,command=\"{} {}\" {}", LOGS_COMMAND, container_name(job_id), key),
+    }
+}
+
+/// Whether a job id can go into a forced command as it is
+pub fn command_safe(job_id: &str) -> bool {
+    !job_id.is_empty() && job_id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// A client's request to grant or revoke access, filed on the node
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GrantRequest {
+    pub id: String,
+    pub job_id: String,
+    pub identity: String,
+    pub token: String, // The job's access token, or a full-control grantee's
+    #[serde(default)]
+    pub scope: Option<AccessScope>, // None revokes the identity's grant
+    #[serde(default)]
+    pub public_key: Option<String>,
+    #[serde(default)]
+    pub grantee_token_hash: Option<String>, // For full control; the client hands the token over itself
+    #[serde(default)]
+    pub sources: Option<SourceRestriction>, // Restricts where the job's user signs in from instead of granting
+    pub requested_at: u64, // Unix seconds
+}
+
+impl GrantRequest {
+    pub fn new(job_id: &str, identity: &str, token: &str) -> Self {
+        let requested_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        Self {
+            id: format!("{}-{}", requested_at, uuid::Uuid::new_v4().simple()),
+            job_id: job_id.to_string(),
+            identity: identity.to_string(),
+            token: token.to_string(),
+            scope: None,
+            public_key: None,
+            grantee_token_hash: None,
+            sources: None,
+            requested_at,
+        }
+    }
+}
+
+/// Grant requests as files in one directory, each marked once it has been handled
+pub struct GrantQueue {
+    dir: PathBuf,
+}
+
+impl GrantQueue {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Create the directory so job users can add requests but not remove anyone else's
+    pub fn prepare(&self) -> Result<(), SshManagerError> {
+        std::fs::create_dir_all(&self.dir).map_err(|e| SshManagerError::io("create", &self.dir, e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&self.dir, std::fs::Permissions::from_mode(0o1777))
+                .map_err(|e| SshManagerError::io("set permissions on", &self.dir, e))?;
+        }
+        Ok(())
+    }
+
+    fn request_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    fn handled_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.handled", id))
+    }
+
+    /// Path a request is filed under, for clients writing it over SSH
+    pub fn path_for(dir: &Path, request: &GrantRequest) -> PathBuf {
+        Self::new(dir).request_path(&request.id)
+    }
+
+    /// Requests not handled yet, oldest first
+    pub fn pending(&self) -> Vec<GrantRequest> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return vec![];
+        };
+        let mut requests: Vec<GrantRequest> = entries
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|e| e == "json"))
+            .filter_map(|entry| serde_json::from_str::<GrantRequest>(&std::fs::read_to_string(entry.path()).ok()?).ok())
+            .filter(|request| !self.handled_path(&request.id).exists())
+            .collect();
+        requests.sort_by_key(|request| request.requested_at);
+        requests
+    }
+
+    /// Mark a request handled, noting the outcome for the client
+    pub fn mark_handled(&self, id: &str, outcome: &str) -> Result<(), SshManagerError> {
+        let path = self.handled_path(id);
+        std::fs::write(&path, outcome).map_err(|e| SshManagerError::io("write", &path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grant_lines_and_queue() {
+        let key = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHuY0m1wZ8Wq7u2Yt4b5vE3s1nZkq2jF8p9dLxR0aB7c bob@laptop";
+        assert_eq!(
+            authorized_keys_line(key, AccessScope::LogsOnly, "bob@example.com", "train-1"),
+            "restrict,command=\"docker logs --follow --tail 500 eryzaa-job-train-1\" ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHuY0m1wZ8Wq7u2Yt4b5vE3s1nZkq2jF8p9dLxR0aB7c eryzaa:bob@example.com"
+        );
+        assert!(authorized_keys_line(key, AccessScope::Full, "bob", "train-1").ends_with(" eryzaa:bob"));
+        assert!(fingerprint(key).unwrap().starts_with("SHA256:"));
+        assert_eq!(fingerprint(key).unwrap().len(), 7 + 43);
+        assert!(!command_safe("x\" ; rm -rf ~"));
+        assert!(validate_identity("bob@example.com").is_ok());
+        assert!(validate_identity("bob example").is_err());
+
+        let queue = GrantQueue::new(std::env::temp_dir().join(format!("eryzaa-grants-{}", std::process::id())));
+        queue.prepare().unwrap();
+        let mut request = GrantRequest::new("train-1", "bob", "secret");
+        request.scope = Some(AccessScope::Ssh);
+        std::fs::write(GrantQueue::path_for(&queue.dir, &request), serde_json::to_string(&request).unwrap()).unwrap();
+        assert_eq!(queue.pending(), vec![request.clone()]);
+        queue.mark_handled(&request.id, "granted").unwrap();
+        assert!(queue.pending().is_empty());
+        std::fs::remove_dir_all(&queue.dir).unwrap();
+    }
+}
@@ -1,9 +1,46 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::process::Command;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use uuid::Uuid;
-use log::{info, warn, error};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use log::{debug, info, warn, error};
+
+pub mod accounts;
+pub mod activity;
+pub mod audit;
+pub mod bandwidth;
+pub mod ca;
+pub mod config;
+pub mod docker;
+pub mod error;
+pub mod events;
+pub mod grants;
+pub mod login;
+pub mod quota;
+pub mod reaper;
+#[cfg(unix)]
+pub mod service;
+pub mod sources;
+#[cfg(unix)]
+mod unix;
+#[cfg(unix)]
+pub mod userdb;
+#[cfg(windows)]
+mod windows;
+
+use accounts::{AccessMode, Credential, IsolationLevel, ResourceCaps, SshPolicy};
+use activity::{Connection, SessionActivity};
+use audit::{AuditEvent, AuditLog};
+use bandwidth::{BandwidthCaps, BandwidthUsage, UserTraffic};
+pub use config::SshManagerConfig;
+pub use docker::DockerAccess;
+pub use error::SshManagerError;
+pub use events::{EventBus, SessionEvent, SessionEventKind};
+use grants::{AccessScope, Grant, GrantQueue, GrantRequest};
+use quota::{DiskQuota, QuotaMethod};
+pub use reaper::{Reaper, ReaperEvent};
+pub use sources::SourceRestriction;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SshUser {
@@ -12,6 +49,16 @@ pub struct SshUser {
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub is_active: bool,
     pub ssh_key: Option<String>,
+    #[serde(default)]
+    pub certificate: Option<String>, // From the host CA for ssh_key, valid until the job expires
+    #[serde(default)]
+    pub isolation: IsolationLevel,
+    #[serde(default)]
+    pub policy: SshPolicy, // Forwarding, SFTP only and sessions, in the user's sshd Match block
+    #[serde(default)]
+    pub sources: SourceRestriction, // Addresses and keys the client limited the user to
+    #[serde(default)]
+    pub docker: DockerAccess, // Users from before the choice existed are in the docker group
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,45 +67,756 @@ pub struct JobAccess {
     pub client_id: String,
     pub ssh_user: SshUser,
     pub expires_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub access_token: Option<String>, // The job's access token; the client proves itself with it when sharing the job
+    #[serde(default)]
+    pub grants: Vec<Grant>, // Teammates the client let in
+    #[serde(default)]
+    pub mode: AccessMode,
+    #[serde(default)]
+    pub disk_quota: Option<DiskQuota>, // Cap on the user's home, set when the user was made
+    #[serde(skip)]
+    pub activity: Option<SessionActivity>, // Live, as of the last `SshManager::refresh_activity`
+    #[serde(skip)]
+    pub private_key: Option<String>, // Made here for a client without a key; handed over once, never saved
+}
+
+impl JobAccess {
+    /// Whether `token` may change who has access: the job's own, or a full-control grantee's
+    pub fn may_delegate(&self, token: &str) -> bool {
+        if self.access_token.as_deref() == Some(token) {
+            return true;
+        }
+        let hash = grants::token_hash(token);
+        self.grants
+            .iter()
+            .any(|grant| grant.scope == AccessScope::Full && grant.token_hash.as_deref() == Some(hash.as_str()))
+    }
+
+    /// Who a key belongs to, by the fingerprint sshd logged for it
+    pub fn identity_for(&self, fingerprint: &str) -> Option<String> {
+        if self.ssh_user.ssh_key.as_deref().and_then(grants::fingerprint).as_deref() == Some(fingerprint) {
+            return Some(self.client_id.clone());
+        }
+        self.grants
+            .iter()
+            .find(|grant| grant.fingerprint == fingerprint)
+            .map(|grant| grant.identity.clone())
+    }
+}
+
+/// Key types sshd accepts in authorized_keys
+pub(crate) const KEY_TYPES: [&str; 6] = [
+    "ssh-ed25519",
+    "ssh-rsa",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+    "sk-ssh-ed25519@openssh.com",
+];
+
+/// Check a client-supplied public key and return it as one authorized_keys line
+///
+/// Options (`command=...` and the like) are refused so the client cannot change how the
+/// account is used; only "type base64 [comment]" is accepted.
+pub fn validate_public_key(key: &str) -> Result<String, SshManagerError> {
+    let key = key.trim();
+    if key.lines().count() != 1 {
+        return Err(SshManagerError::InvalidInput("Public key must be a single line".to_string()));
+    }
+    let mut fields = key.split_whitespace();
+    let (Some(key_type), Some(body)) = (fields.next(), fields.next()) else {
+        return Err(SshManagerError::InvalidInput("Public key must be '<type> <base64> [comment]'".to_string()));
+    };
+    if !KEY_TYPES.contains(&key_type) {
+        return Err(SshManagerError::InvalidInput(format!("Unsupported key type '{}'", key_type)));
+    }
+    let base64 = body.len() >= 16
+        && body.len() % 4 == 0
+        && body.trim_end_matches('=').bytes().all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/');
+    if !base64 {
+        return Err(SshManagerError::InvalidInput("Public key data is not valid base64".to_string()));
+    }
+    let comment: String = fields
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .filter(|c| !c.is_control() && *c != '|')
+        .collect();
+    Ok(if comment.is_empty() { format!("{} {}", key_type, body) } else { format!("{} {} {}", key_type, body, comment) })
+}
+
+/// How many job users may be signed up at once and what each may use
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SessionLimits {
+    pub max_sessions: usize,
+    #[serde(default)]
+    pub cpu_quota_percent: Option<u32>, // Per user, 100 = one core
+    #[serde(default)]
+    pub memory_max_mb: Option<u64>, // Per user
+    #[serde(default = "default_machine_share")]
+    pub machine_cpu_percent: Option<f32>, // Share of all cores any one user may use, from the rental settings
+    #[serde(default = "default_machine_share")]
+    pub machine_memory_percent: Option<f32>, // Share of the memory any one user may use
+    #[serde(default)]
+    pub reserved_cpus: Vec<u32>, // Kept for the owner; job users run on the other cores
+    #[serde(default)]
+    pub reserved_memory_mb: u64, // Kept for the owner; shares are of the memory left
+    #[serde(default)]
+    pub isolation: IsolationLevel, // What new job users get unless a job asks for something else
+    #[serde(default)]
+    pub policy: SshPolicy, // Likewise
+    #[serde(default)]
+    pub docker: DockerAccess, // Likewise, for full shells and container-only users
+    #[serde(default)]
+    pub egress_mbit: Option<u32>, // Per user, what their processes may send
+    #[serde(default)]
+    pub ingress_mbit: Option<u32>, // Per user, what they may receive
+    #[serde(default)]
+    pub disk_quota_mb: Option<u64>, // Per user, the size of their home; applies to users made after it is set
+}
+
+fn default_machine_share() -> Option<f32> {
+    Some(80.0)
+}
+
+impl Default for SessionLimits {
+    fn default() -> Self {
+        // Same as the node's default job limit
+        Self {
+            max_sessions: 4,
+            cpu_quota_percent: None,
+            memory_max_mb: None,
+            machine_cpu_percent: default_machine_share(),
+            machine_memory_percent: default_machine_share(),
+            reserved_cpus: vec![],
+            reserved_memory_mb: 0,
+            isolation: IsolationLevel::default(),
+            policy: SshPolicy::default(),
+            docker: DockerAccess::default(),
+            egress_mbit: None,
+            ingress_mbit: None,
+            disk_quota_mb: None,
+        }
+    }
+}
+
+impl SessionLimits {
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), SshManagerError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| SshManagerError::io("create", parent, e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| SshManagerError::Io(format!("Failed to serialize session limits: {}", e)))?;
+        std::fs::write(path, content)
+            .map_err(|e| SshManagerError::io("write session limits", path, e))
+    }
+
+    /// Caps for each job user on a machine with `cpus` cores and `memory_mb` of memory
+    ///
+    /// The tighter of the per-user and machine share limits wins; the memory share is skipped
+    /// when the machine's memory is unknown. Shares are of what the owner has not reserved, and
+    /// nobody gets the reserved cores or memory.
+    pub fn user_caps(&self, cpus: usize, memory_mb: Option<u64>) -> ResourceCaps {
+        let tenant_cpus: Vec<u32> = (0..cpus as u32).filter(|cpu| !self.reserved_cpus.contains(cpu)).collect();
+        let cpu_share = self.machine_cpu_percent.map(|percent| (percent * tenant_cpus.len().max(1) as f32).round() as u32);
+        let tenant_memory = memory_mb.map(|total| total.saturating_sub(self.reserved_memory_mb));
+        let memory_share = self
+            .machine_memory_percent
+            .zip(tenant_memory)
+            .map(|(percent, total)| (total as f64 * f64::from(percent) / 100.0) as u64);
+        let memory_left = tenant_memory.filter(|_| self.reserved_memory_mb > 0);
+        ResourceCaps {
+            cpu_quota_percent: self.cpu_quota_percent.into_iter().chain(cpu_share).min(),
+            memory_max_mb: self.memory_max_mb.into_iter().chain(memory_share).chain(memory_left).min(),
+            allowed_cpus: if self.reserved_cpus.is_empty() || tenant_cpus.is_empty() { vec![] } else { tenant_cpus },
+        }
+    }
+
+    /// Bandwidth each job user may use
+    pub fn bandwidth_caps(&self) -> BandwidthCaps {
+        BandwidthCaps { egress_mbit: self.egress_mbit, ingress_mbit: self.ingress_mbit }
+    }
+}
+
+/// Memory of this machine in MB, from /proc/meminfo
+fn machine_memory_mb() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let total = meminfo.lines().find_map(|line| line.strip_prefix("MemTotal:"))?;
+    let kb: u64 = total.trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kb / 1024)
+}
+
+/// What `SshManager::reconcile` changed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Reconciliation {
+    pub forgotten: Vec<String>, // Tracked jobs whose user no longer exists
+    pub removed: Vec<String>,   // Job users nobody tracked, deleted
+}
+
+/// Tracked users that are missing from the `existing` local users, and job users among them that are not tracked
+pub fn compare_with_local_users(tracked: &[&str], existing: &[String]) -> (Vec<String>, Vec<String>) {
+    let missing = tracked
+        .iter()
+        .filter(|user| !existing.iter().any(|existing| existing == *user))
+        .map(|user| user.to_string())
+        .collect();
+    let untracked = existing
+        .iter()
+        .filter(|user| accounts::validate_username(user).is_ok() && !tracked.contains(&user.as_str()))
+        .cloned()
+        .collect();
+    (missing, untracked)
 }
 
+/// How often the expiry cleanup looks again with no job to wait for
+const EXPIRY_IDLE: Duration = Duration::from_secs(3600);
+
+/// How soon the expiry cleanup retries users it failed to remove
+const EXPIRY_RETRY: Duration = Duration::from_secs(60);
+
+/// Tracks job users and sets them up and tears them down
+///
+/// The state behind the mutexes is only looked up or changed in place, never held across an
+/// await, so the GUIs can read it from their own threads. Work that waits on a subprocess or
+/// the privileged service runs on tokio's blocking threads or over async sockets instead.
+#[derive(Clone)]
 pub struct SshManager {
     active_users: Arc<Mutex<HashMap<String, JobAccess>>>,
-    current_user: Arc<Mutex<Option<String>>>, // Only one user at a time
+    creating: Arc<Mutex<usize>>, // Users being set up, which already hold a slot
+    trying: Arc<Mutex<Option<String>>>, // Throwaway user of a self-test under way, which reconcile leaves alone
+    limits: Arc<Mutex<SessionLimits>>,
+    state_path: Option<PathBuf>, // Active jobs are kept here across restarts
+    audit: Option<AuditLog>,     // Next to the state file
+    expiry_changed: Arc<Notify>, // Wakes the expiry cleanup when a job is added or extended
+    traffic_samples: Arc<Mutex<HashMap<String, (Instant, UserTraffic)>>>, // Last read per user, for rates
+    events: Arc<EventBus>,
+    config: Arc<SshManagerConfig>,
+    #[cfg(unix)]
+    service_socket: PathBuf, // Where the privileged service listens
 }
 
 impl SshManager {
     pub fn new() -> Self {
+        Self::with_limits(SessionLimits::default())
+    }
+
+    pub fn with_limits(limits: SessionLimits) -> Self {
         Self {
             active_users: Arc::new(Mutex::new(HashMap::new())),
-            current_user: Arc::new(Mutex::new(None)),
+            creating: Arc::new(Mutex::new(0)),
+            trying: Arc::new(Mutex::new(None)),
+            limits: Arc::new(Mutex::new(limits)),
+            state_path: None,
+            audit: None,
+            expiry_changed: Arc::new(Notify::new()),
+            traffic_samples: Arc::new(Mutex::new(HashMap::new())),
+            events: Arc::new(EventBus::default()),
+            config: Arc::new(SshManagerConfig::system().clone()),
+            #[cfg(unix)]
+            service_socket: PathBuf::from(service::SOCKET_PATH),
+        }
+    }
+
+    /// The same manager, naming job users and checking passwords by `config`
+    ///
+    /// The prefix must be the node's, see `SshManagerConfig::system`, as the privileged service
+    /// and the login helper refuse users named otherwise.
+    pub fn with_config(self, config: SshManagerConfig) -> Result<Self, SshManagerError> {
+        config.validate()?;
+        if config.username_prefix != accounts::job_user_prefix() {
+            return Err(SshManagerError::InvalidInput(format!(
+                "Username prefix '{}' is not the node's '{}'; set it in {}",
+                config.username_prefix,
+                accounts::job_user_prefix(),
+                config::config_path().display()
+            )));
+        }
+        Ok(Self { config: Arc::new(config), ..self })
+    }
+
+    /// The same manager, reaching the privileged service at `socket` instead of its usual place
+    #[cfg(unix)]
+    pub fn with_service_socket(self, socket: impl Into<PathBuf>) -> Self {
+        Self { service_socket: socket.into(), ..self }
+    }
+
+    pub fn config(&self) -> &SshManagerConfig {
+        &self.config
+    }
+
+    /// `policy` with what the config enforces for every job user on top
+    fn enforced(&self, policy: SshPolicy) -> SshPolicy {
+        // Windows' sshd gets no per-user settings; its job users have passwords nobody knows
+        let deny = cfg!(unix) && self.config.password.deny;
+        SshPolicy { no_password_auth: policy.no_password_auth || deny, ..policy }
+    }
+
+    /// Manager that keeps its active jobs in `path`, starting with the ones saved there
+    ///
+    /// Call `reconcile` afterwards so the saved jobs match the users on the system.
+    pub fn with_state(limits: SessionLimits, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let saved: Vec<JobAccess> = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        let audit = Some(AuditLog::new(path.with_file_name("ssh_audit")));
+        let manager = Self { state_path: Some(path), audit, ..Self::with_limits(limits) };
+        manager
+            .active_users
+            .lock()
+            .unwrap()
+            .extend(saved.into_iter().map(|access| (access.job_id.clone(), access)));
+        manager
+    }
+
+    /// Write the active jobs to the state file, if there is one
+    fn save_state(&self) {
+        let Some(path) = &self.state_path else {
+            return;
+        };
+        let mut jobs = self.get_active_jobs();
+        jobs.sort_by_key(|access| access.ssh_user.created_at);
+        let saved = serde_json::to_string_pretty(&jobs)
+            .map_err(|e| SshManagerError::Io(format!("Failed to serialize SSH manager state: {}", e)))
+            .and_then(|content| {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)
+                        .map_err(|e| SshManagerError::io("create", parent, e))?;
+                }
+                std::fs::write(path, content)
+                    .map_err(|e| SshManagerError::io("write SSH manager state", path, e))
+            });
+        if let Err(e) = saved {
+            error!("{}", e);
+        }
+    }
+
+    /// Match the tracked jobs with the local users after a restart
+    ///
+    /// Jobs whose user is gone are forgotten, and job users left behind by a run that never
+    /// saved them are deleted so they do not outlive their job.
+    pub async fn reconcile(&self) -> Result<Reconciliation, SshManagerError> {
+        let existing = self.unblocked(|_| accounts::local_users()).await?;
+        let (missing, untracked) = {
+            let active_users = self.active_users.lock().unwrap();
+            let trying = self.trying.lock().unwrap();
+            let tracked: Vec<&str> = active_users
+                .values()
+                .map(|access| access.ssh_user.username.as_str())
+                .chain(trying.as_deref())
+                .collect();
+            compare_with_local_users(&tracked, &existing)
+        };
+
+        let mut reconciliation = Reconciliation::default();
+        if !missing.is_empty() {
+            self.active_users.lock().unwrap().retain(|job_id, access| {
+                let gone = missing.contains(&access.ssh_user.username);
+                if gone {
+                    warn!("SSH user '{}' for job '{}' no longer exists", access.ssh_user.username, job_id);
+                    reconciliation.forgotten.push(job_id.clone());
+                }
+                !gone
+            });
+            self.unblocked(|manager| manager.save_state()).await;
+        }
+        // Caps only last until reboot
+        self.unblocked(|manager| manager.apply_limits_to_active_users()).await;
+        for username in untracked {
+            match self.delete_system_user(&username).await {
+                Ok(_) => {
+                    info!("Removed untracked SSH user '{}'", username);
+                    reconciliation.removed.push(username);
+                }
+                Err(e) => error!("Failed to remove untracked SSH user '{}': {}", username, e),
+            }
+        }
+        Ok(reconciliation)
+    }
+
+    pub fn limits(&self) -> SessionLimits {
+        self.limits.lock().unwrap().clone()
+    }
+
+    /// Change the limits; a lower session cap does not end existing sessions
+    pub fn set_limits(&self, limits: SessionLimits) {
+        *self.limits.lock().unwrap() = limits;
+    }
+
+    /// Cap every tracked job user with the current limits again
+    pub fn apply_limits_to_active_users(&self) {
+        let limits = self.limits();
+        for username in self.get_current_users() {
+            if let Err(e) = self.apply_resource_limits(&username, &limits) {
+                warn!("SSH user '{}' runs without resource limits: {}", username, e);
+            }
+        }
+        self.apply_bandwidth_limits(&limits);
+        // A loopback home is only mounted until reboot
+        let quotas: Vec<(String, u64)> = self
+            .active_users
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|access| Some((access.ssh_user.username.clone(), access.disk_quota.as_ref()?.limit_mb)))
+            .collect();
+        for (username, limit_mb) in quotas {
+            if let Err(e) = self.apply_disk_quota(&username, limit_mb) {
+                warn!("SSH user '{}' runs without its disk quota: {}", username, e);
+            }
+        }
+    }
+
+    /// Traffic of the job's user so far and its rate since the previous call
+    pub fn get_usage(&self, job_id: &str) -> Result<BandwidthUsage, SshManagerError> {
+        let username = self
+            .active_users
+            .lock()
+            .unwrap()
+            .get(job_id)
+            .map(|access| access.ssh_user.username.clone())
+            .ok_or_else(|| SshManagerError::NotFound(format!("No SSH user found for job '{}'", job_id)))?;
+        let traffic = self
+            .read_traffic()?
+            .into_iter()
+            .find(|traffic| traffic.username == username)
+            .ok_or_else(|| SshManagerError::NotFound(format!("No traffic counted for SSH user '{}'", username)))?;
+
+        let now = Instant::now();
+        let previous = self.traffic_samples.lock().unwrap().insert(username.clone(), (now, traffic.clone()));
+        let rate = |bytes: u64, before: u64, since: Instant| {
+            let seconds = now.duration_since(since).as_secs_f64();
+            (seconds > 0.0 && bytes >= before).then(|| ((bytes - before) as f64 * 8.0 / seconds) as u64)
+        };
+        Ok(BandwidthUsage {
+            job_id: job_id.to_string(),
+            username,
+            rx_bytes: traffic.rx_bytes,
+            tx_bytes: traffic.tx_bytes,
+            rx_bits_per_sec: previous.as_ref().and_then(|(at, before)| rate(traffic.rx_bytes, before.rx_bytes, *at)),
+            tx_bits_per_sec: previous.as_ref().and_then(|(at, before)| rate(traffic.tx_bytes, before.tx_bytes, *at)),
+            caps: self.limits().bandwidth_caps(),
+        })
+    }
+
+    /// Check who of the job users is connected right now, for `JobAccess::activity`
+    ///
+    /// Traffic is included where it is counted, see `get_usage`.
+    pub fn refresh_activity(&self) -> Result<(), SshManagerError> {
+        let connections = self.read_connections()?;
+        let traffic = self.read_traffic().unwrap_or_default();
+        let now = chrono::Utc::now();
+        for access in self.active_users.lock().unwrap().values_mut() {
+            let username = &access.ssh_user.username;
+            let open: Vec<&Connection> = connections.iter().filter(|connection| &connection.username == username).collect();
+            let counted = traffic.iter().find(|traffic| &traffic.username == username);
+            access.activity = Some(SessionActivity::observe(access.activity.as_ref(), &open, counted, now));
+        }
+        Ok(())
+    }
+
+    /// Per-job log of logins and logouts, kept by managers with a state file
+    pub fn audit_log(&self) -> Option<&AuditLog> {
+        self.audit.as_ref()
+    }
+
+    /// File sshd's new entries about tracked job users under their jobs, returning how many
+    ///
+    /// Entries about users no longer tracked are dropped, so collect before removing a user.
+    pub fn collect_audit(&self) -> Result<usize, SshManagerError> {
+        let Some(log) = &self.audit else {
+            return Ok(0);
+        };
+        let events = self.read_audit_events(log)?;
+        let jobs: HashMap<String, JobAccess> = self
+            .get_active_jobs()
+            .into_iter()
+            .map(|access| (access.ssh_user.username.clone(), access))
+            .collect();
+
+        let mut per_job: HashMap<&str, Vec<AuditEvent>> = HashMap::new();
+        for mut event in events {
+            if let Some(access) = jobs.get(&event.username) {
+                event.identity = event.fingerprint.as_deref().and_then(|fingerprint| access.identity_for(fingerprint));
+                per_job.entry(&access.job_id).or_default().push(event);
+            }
+        }
+        let mut filed = 0;
+        for (job_id, events) in per_job {
+            log.append(job_id, &events)?;
+            filed += events.len();
+            for event in &events {
+                if let Some(event) = SessionEvent::from_audit(job_id, event) {
+                    self.events.publish(event);
+                }
+            }
+        }
+        Ok(filed)
+    }
+
+    /// Every lifecycle event from now on, see `events`
+    pub fn subscribe_events(&self) -> tokio::sync::broadcast::Receiver<SessionEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn on_user_created(&self, callback: impl Fn(&SessionEvent) + Send + Sync + 'static) {
+        self.events.on(SessionEventKind::UserCreated, callback);
+    }
+
+    /// Run `callback` on each login of a job user, as `collect_audit` finds it
+    pub fn on_login(&self, callback: impl Fn(&SessionEvent) + Send + Sync + 'static) {
+        self.events.on(SessionEventKind::Login, callback);
+    }
+
+    /// Run `callback` on each logout of a job user, as `collect_audit` finds it
+    pub fn on_logout(&self, callback: impl Fn(&SessionEvent) + Send + Sync + 'static) {
+        self.events.on(SessionEventKind::Logout, callback);
+    }
+
+    pub fn on_expired(&self, callback: impl Fn(&SessionEvent) + Send + Sync + 'static) {
+        self.events.on(SessionEventKind::Expired, callback);
+    }
+
+    /// New sshd events, from the privileged service when it runs
+    fn read_audit_events(&self, log: &AuditLog) -> Result<Vec<AuditEvent>, SshManagerError> {
+        #[cfg(unix)]
+        {
+            let socket_path = self.service_socket.as_path();
+            if socket_path.exists() {
+                return match service::call(socket_path, &service::Request::Audit)? {
+                    service::Response::Events(events) => Ok(events),
+                    service::Response::Error(e) => Err(e.context("Service error")),
+                    response => Err(SshManagerError::Protocol(format!("Unexpected answer from SSH service: {:?}", response))),
+                };
+            }
+            audit::read_journal(&log.dir().join(".cursor"))
+        }
+        // OpenSSH on Windows logs to the event log, which is not read yet
+        #[cfg(windows)]
+        {
+            let _ = log;
+            Ok(vec![])
+        }
+    }
+
+    /// Take the job's access token from its record, so the client can share the job
+    pub fn set_access_token(&self, job_id: &str, token: &str) {
+        let mut users = self.active_users.lock().unwrap();
+        let Some(access) = users.get_mut(job_id) else {
+            return;
+        };
+        if access.access_token.as_deref() == Some(token) {
+            return;
+        }
+        access.access_token = Some(token.to_string());
+        drop(users);
+        self.save_state();
+    }
+
+    /// Let `identity` into a job with `scope`, signing in with `public_key`
+    ///
+    /// A grant for the same identity is replaced. `token_hash` lets a full-control grantee file
+    /// requests of its own.
+    pub fn grant_access(
+        &self,
+        job_id: &str,
+        identity: &str,
+        scope: AccessScope,
+        public_key: &str,
+        token_hash: Option<String>,
+    ) -> Result<Grant, SshManagerError> {
+        grants::validate_identity(identity)?;
+        let public_key = validate_public_key(public_key)?;
+        let mut access = self
+            .active_users
+            .lock()
+            .unwrap()
+            .get(job_id)
+            .cloned()
+            .ok_or_else(|| SshManagerError::NotFound(format!("No SSH user found for job '{}'", job_id)))?;
+
+        let grant = Grant {
+            identity: identity.to_string(),
+            scope,
+            fingerprint: grants::fingerprint(&public_key)
+                .ok_or_else(|| SshManagerError::InvalidInput("Public key data is not valid base64".to_string()))?,
+            public_key,
+            granted_at: chrono::Utc::now(),
+            token_hash: token_hash.filter(|_| scope == AccessScope::Full),
+        };
+        access.grants.retain(|existing| existing.identity != identity);
+        access.grants.push(grant.clone());
+        self.authorize_keys(&access)?;
+
+        self.active_users.lock().unwrap().insert(job_id.to_string(), access);
+        self.save_state();
+        info!("Granted {} access to job '{}' to {}", scope.label(), job_id, identity);
+        Ok(grant)
+    }
+
+    /// Take away the access granted to `identity`
+    pub fn revoke_access(&self, job_id: &str, identity: &str) -> Result<(), SshManagerError> {
+        let mut access = self
+            .active_users
+            .lock()
+            .unwrap()
+            .get(job_id)
+            .cloned()
+            .ok_or_else(|| SshManagerError::NotFound(format!("No SSH user found for job '{}'", job_id)))?;
+        let before = access.grants.len();
+        access.grants.retain(|grant| grant.identity != identity);
+        if access.grants.len() == before {
+            return Err(SshManagerError::NotFound(format!("{} has no access to job '{}'", identity, job_id)));
+        }
+        self.authorize_keys(&access)?;
+
+        self.active_users.lock().unwrap().insert(job_id.to_string(), access);
+        self.save_state();
+        info!("Revoked the access of {} to job '{}'", identity, job_id);
+        Ok(())
+    }
+
+    /// Carry out the grant requests clients filed in `queue`, returning each with its outcome
+    pub fn handle_grant_requests(&self, queue: &GrantQueue) -> Vec<(GrantRequest, Result<(), SshManagerError>)> {
+        let mut handled = vec![];
+        for request in queue.pending() {
+            let allowed = self
+                .active_users
+                .lock()
+                .unwrap()
+                .get(&request.job_id)
+                .is_some_and(|access| access.may_delegate(&request.token));
+            let result = match (&request.scope, &request.public_key) {
+                _ if !allowed => Err(SshManagerError::Unauthorized(format!("Request for job '{}' is not authorized", request.job_id))),
+                _ if request.sources.is_some() => {
+                    let sources = request.sources.clone().unwrap_or_default();
+                    self.restrict_sources(&request.job_id, sources).map(|_| ())
+                }
+                (Some(scope), Some(key)) => self
+                    .grant_access(&request.job_id, &request.identity, *scope, key, request.grantee_token_hash.clone())
+                    .map(|_| ()),
+                (Some(_), None) => Err(SshManagerError::InvalidInput("Grant request has no public key".to_string())),
+                (None, _) => self.revoke_access(&request.job_id, &request.identity),
+            };
+            let outcome = match &result {
+                Ok(()) => "done".to_string(),
+                Err(e) => e.to_string(),
+            };
+            if let Err(e) = queue.mark_handled(&request.id, &outcome) {
+                warn!("{}", e);
+            }
+            handled.push((request, result));
         }
+        handled
+    }
+
+    /// Write the owner's and grantees' keys for a job's user, through the service when it runs
+    ///
+    /// A suspended user gets an empty file.
+    fn authorize_keys(&self, access: &JobAccess) -> Result<(), SshManagerError> {
+        let username = &access.ssh_user.username;
+        let active = access.ssh_user.is_active;
+        // A certified owner key must not also work on its own, past the certificate's expiry
+        let owner_key = access.ssh_user.ssh_key.clone().filter(|_| active && access.ssh_user.certificate.is_none());
+        let grants = if active { access.grants.clone() } else { vec![] };
+        #[cfg(unix)]
+        {
+            let request = service::Request::Authorize {
+                username: username.clone(),
+                job_id: access.job_id.clone(),
+                owner_key: owner_key.clone(),
+                grants: grants.clone(),
+                sources: access.ssh_user.sources.clone(),
+            };
+            if let Some(result) = self.call_service(&request) {
+                return result;
+            }
+        }
+        accounts::authorize_keys(username, &access.job_id, owner_key.as_deref(), &grants, &access.ssh_user.sources)
     }
 
     /// Create a new SSH user for a job
-    pub async fn create_job_user(&self, job_id: &str, client_id: &str, duration_hours: u64) -> Result<JobAccess, String> {
-        let mut current_user = self.current_user.lock().unwrap();
+    ///
+    /// The user has no password and signs in with a certificate for the client's `ssh_key`, valid
+    /// until the job expires. Without a key one is made here, its private half returned once in
+    /// `private_key`. `isolation` decides how much of the machine the user gets, `policy` what
+    /// it may do over SSH on top of that and `docker` how it reaches containers.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_job_user(
+        &self,
+        job_id: &str,
+        client_id: &str,
+        duration_hours: u64,
+        ssh_key: Option<&str>,
+        isolation: IsolationLevel,
+        policy: SshPolicy,
+        docker: DockerAccess,
+    ) -> Result<JobAccess, SshManagerError> {
+        let ssh_key = ssh_key.map(validate_public_key).transpose()?;
+        let username = self.config.username()?;
+        let policy = self.enforced(policy);
+        let docker = docker.for_isolation(isolation);
+        docker.check(isolation)?;
+        let limits = self.limits();
         
-        // Check if there's already an active user
-        if current_user.is_some() {
-            return Err("Another user is currently accessing this rental node".to_string());
+        // Take a session slot
+        {
+            let active_users = self.active_users.lock().unwrap();
+            let mut creating = self.creating.lock().unwrap();
+            if active_users.contains_key(job_id) {
+                return Err(SshManagerError::AlreadyExists(format!("Job '{}' already has an SSH user", job_id)));
+            }
+            if active_users.len() + *creating >= limits.max_sessions {
+                return Err(SshManagerError::NoFreeSlots(limits.max_sessions));
+            }
+            *creating += 1;
         }
 
-        let uuid_str = Uuid::new_v4().to_string().replace("-", "");
-        let username = format!("job_{}", &uuid_str[..8]);
-        let password = self.generate_secure_password();
+        let expires_at = chrono::Utc::now() + chrono::Duration::hours(duration_hours as i64);
         
         // Create the system user
-        match self.create_system_user(&username, &password).await {
-            Ok(_) => {
-                let expires_at = chrono::Utc::now() + chrono::Duration::hours(duration_hours as i64);
-                
+        let created = self.provision_user(&username, job_id, ssh_key.as_deref(), isolation, policy, docker, expires_at).await;
+        *self.creating.lock().unwrap() -= 1;
+        match created {
+            Ok((public_key, certificate, private_key)) => {
+                let (user, caps) = (username.clone(), limits.clone());
+                let disk_quota = self
+                    .unblocked(move |manager| {
+                        if let Err(e) = manager.apply_resource_limits(&user, &caps) {
+                            warn!("SSH user '{}' runs without resource limits: {}", user, e);
+                        }
+                        caps.disk_quota_mb.and_then(|limit_mb| match manager.apply_disk_quota(&user, limit_mb) {
+                            Ok(method) => Some(DiskQuota { limit_mb, method, used_mb: None }),
+                            Err(e) => {
+                                warn!("SSH user '{}' has no disk quota: {}", user, e);
+                                None
+                            }
+                        })
+                    })
+                    .await;
+
                 let ssh_user = SshUser {
                     username: username.clone(),
                     job_id: job_id.to_string(),
                     created_at: chrono::Utc::now(),
                     is_active: true,
-                    ssh_key: None, // Could add SSH key support later
+                    ssh_key: Some(public_key),
+                    certificate: Some(certificate),
+                    isolation,
+                    policy,
+                    sources: SourceRestriction::default(),
+                    docker,
                 };
 
                 let job_access = JobAccess {
@@ -66,17 +824,30 @@ impl SshManager {
                     client_id: client_id.to_string(),
                     ssh_user: ssh_user.clone(),
                     expires_at,
+                    access_token: None,
+                    grants: vec![],
+                    mode: AccessMode::of(isolation, &policy),
+                    disk_quota,
+                    activity: None,
+                    private_key: None,
                 };
 
-                // Set as current user
-                *current_user = Some(username.clone());
-                
                 // Store in active users
-                let mut active_users = self.active_users.lock().unwrap();
-                active_users.insert(job_id.to_string(), job_access.clone());
+                self.active_users.lock().unwrap().insert(job_id.to_string(), job_access.clone());
+                self.unblocked(move |manager| {
+                    manager.save_state();
+                    manager.apply_bandwidth_limits(&limits);
+                })
+                .await;
+                self.expiry_changed.notify_one();
 
-                info!("Created SSH user '{}' for job '{}' (client: {})", username, job_id, client_id);
-                Ok(job_access)
+                info!("Created SSH user '{}' for job '{}' (client: {}, {})", username, job_id, client_id, isolation.label().to_lowercase());
+                self.events.publish(SessionEvent::UserCreated {
+                    job_id: job_id.to_string(),
+                    client_id: client_id.to_string(),
+                    username: username.clone(),
+                });
+                Ok(JobAccess { private_key, ..job_access })
             }
             Err(e) => {
                 error!("Failed to create SSH user for job '{}': {}", job_id, e);
@@ -85,61 +856,415 @@ impl SshManager {
         }
     }
 
+    /// Create a throwaway job user and delete it again, as jobs have theirs, for the node's
+    /// startup self-test; the name it had
+    ///
+    /// Both go through the privileged service where it runs, so the unprivileged GUI tests the
+    /// same path its jobs take.
+    pub async fn try_job_user(&self) -> Result<String, SshManagerError> {
+        let username = self.config.username()?;
+        *self.trying.lock().unwrap() = Some(username.clone());
+        let tried = async {
+            let policy = self.enforced(SshPolicy::default());
+            self.create_system_user(&username, Credential::Certificate, IsolationLevel::FullShell, policy, DockerAccess::None, "selftest")
+                .await
+                .map_err(|e| e.context("Cannot create users"))?;
+            self.delete_system_user(&username)
+                .await
+                .map_err(|e| e.context(&format!("Created {} but cannot delete it", username)))
+        }
+        .await;
+        // Left behind if deleting failed, for the next reconcile to remove
+        *self.trying.lock().unwrap() = None;
+        tried.map(|_| username)
+    }
+
+    /// Create a data-drop user for a job: SFTP only, chrooted to its home, files in its upload directory
+    ///
+    /// The node's isolation level does not apply; the owner's session limit does.
+    pub async fn create_sftp_user(
+        &self,
+        job_id: &str,
+        client_id: &str,
+        duration_hours: u64,
+        ssh_key: Option<&str>,
+    ) -> Result<JobAccess, SshManagerError> {
+        let policy = SshPolicy { sftp_only: true, ..self.limits().policy };
+        self.create_job_user(job_id, client_id, duration_hours, ssh_key, IsolationLevel::Chroot, policy, DockerAccess::None).await
+    }
+
     /// Remove SSH user when job ends
-    pub async fn remove_job_user(&self, job_id: &str) -> Result<(), String> {
-        let mut active_users = self.active_users.lock().unwrap();
-        let mut current_user = self.current_user.lock().unwrap();
+    pub async fn remove_job_user(&self, job_id: &str) -> Result<(), SshManagerError> {
+        // The user's last logout can only be filed while the job still knows it
+        if let Err(e) = self.unblocked(|manager| manager.collect_audit()).await {
+            warn!("Failed to update the SSH audit log: {}", e);
+        }
+        let removed = self.active_users.lock().unwrap().remove(job_id);
 
-        if let Some(job_access) = active_users.remove(job_id) {
+        if let Some(job_access) = removed {
             let username = &job_access.ssh_user.username;
-            
-            // Remove from current user if it matches
-            if let Some(ref current) = *current_user {
-                if current == username {
-                    *current_user = None;
-                }
-            }
 
             // Delete the system user
             match self.delete_system_user(username).await {
                 Ok(_) => {
                     info!("Removed SSH user '{}' for job '{}'", username, job_id);
+                    self.traffic_samples.lock().unwrap().remove(username);
+                    self.unblocked(|manager| {
+                        manager.save_state();
+                        manager.apply_bandwidth_limits(&manager.limits());
+                    })
+                    .await;
                     Ok(())
                 }
                 Err(e) => {
                     error!("Failed to delete SSH user '{}': {}", username, e);
+                    // Keep tracking the user so removing it can be retried
+                    self.active_users.lock().unwrap().insert(job_id.to_string(), job_access);
                     Err(e)
                 }
             }
         } else {
             warn!("No SSH user found for job '{}'", job_id);
-            Err(format!("No SSH user found for job '{}'", job_id))
+            Err(SshManagerError::NotFound(format!("No SSH user found for job '{}'", job_id)))
+        }
+    }
+
+    /// Lock a job's user out and end its sessions, as when the client's payment lapses
+    ///
+    /// A locked account still takes keys and certificates, so its keys file is emptied and its
+    /// certificate revoked too. The user keeps its home and the job keeps running;
+    /// `resume_job_user` lets it back in.
+    pub async fn suspend_job_user(&self, job_id: &str) -> Result<JobAccess, SshManagerError> {
+        let username = self.set_job_user_active(job_id, false)?;
+        // The login helper refuses an inactive user, even while the account is still unlocked
+        self.unblocked(|manager| manager.save_state()).await;
+        let suspended = self.job_access(job_id)?;
+        let emptied = {
+            let access = suspended.clone();
+            self.unblocked(move |manager| manager.authorize_keys(&access)).await
+        };
+        if let Err(e) = emptied.and(self.lock_system_user(&username, true).await) {
+            error!("Failed to suspend SSH user '{}': {}", username, e);
+            self.set_job_user_active(job_id, true)?;
+            let access = self.job_access(job_id)?;
+            self.unblocked(move |manager| {
+                manager.save_state();
+                manager.authorize_keys(&access)
+            })
+            .await?;
+            return Err(e);
+        }
+
+        // Last, as a revoked certificate cannot come back; sessions it opened since the lock end too
+        if let Some(certificate) = suspended.ssh_user.certificate.clone() {
+            self.unblocked(move |manager| {
+                manager.revoke_certificate(&certificate)?;
+                manager.end_sessions(&username)
+            })
+            .await?;
+        }
+        info!("Suspended SSH user '{}' for job '{}'", suspended.ssh_user.username, job_id);
+        self.job_access(job_id)
+    }
+
+    /// Let a suspended job's user sign in again, with the access it had
+    ///
+    /// A certified key gets a new certificate in place of the one revoked on suspension.
+    pub async fn resume_job_user(&self, job_id: &str) -> Result<JobAccess, SshManagerError> {
+        let access = self.job_access(job_id)?;
+        if access.expires_at <= chrono::Utc::now() {
+            return Err(SshManagerError::InvalidInput(format!("Access to job '{}' has already expired", job_id)));
+        }
+        if access.ssh_user.is_active {
+            return Err(SshManagerError::InvalidInput(format!("Access to job '{}' is not suspended", job_id)));
+        }
+        let mut resumed = access.clone();
+        resumed.ssh_user.is_active = true;
+        if let (Some(key), Some(_)) = (&access.ssh_user.ssh_key, &access.ssh_user.certificate) {
+            let (username, key, sources) = (access.ssh_user.username.clone(), key.clone(), access.ssh_user.sources.clone());
+            let certificate = self.unblocked(move |manager| manager.certify(&username, &key, access.expires_at, &sources)).await?;
+            resumed.ssh_user.certificate = Some(certificate);
+        }
+        self.lock_system_user(&resumed.ssh_user.username, false).await?;
+        let authorized = resumed.clone();
+        self.unblocked(move |manager| manager.authorize_keys(&authorized)).await?;
+
+        let updated = {
+            let mut active_users = self.active_users.lock().unwrap();
+            let current = active_users
+                .get_mut(job_id)
+                .ok_or_else(|| SshManagerError::NotFound(format!("No SSH user found for job '{}'", job_id)))?;
+            current.ssh_user.is_active = true;
+            current.ssh_user.certificate = resumed.ssh_user.certificate;
+            current.clone()
+        };
+        self.unblocked(|manager| manager.save_state()).await;
+        info!("Resumed SSH user '{}' for job '{}'", updated.ssh_user.username, job_id);
+        Ok(updated)
+    }
+
+    /// The access of `job_id`
+    fn job_access(&self, job_id: &str) -> Result<JobAccess, SshManagerError> {
+        self.active_users
+            .lock()
+            .unwrap()
+            .get(job_id)
+            .cloned()
+            .ok_or_else(|| SshManagerError::NotFound(format!("No SSH user found for job '{}'", job_id)))
+    }
+
+    /// Mark the user of `job_id` active or suspended; returns its username
+    fn set_job_user_active(&self, job_id: &str, active: bool) -> Result<String, SshManagerError> {
+        let mut active_users = self.active_users.lock().unwrap();
+        let current = active_users
+            .get_mut(job_id)
+            .ok_or_else(|| SshManagerError::NotFound(format!("No SSH user found for job '{}'", job_id)))?;
+        current.ssh_user.is_active = active;
+        Ok(current.ssh_user.username.clone())
+    }
+
+    /// Prolong a job's access by `additional_hours`, certifying the client's key until the new expiry
+    ///
+    /// Returns the access with the new certificate, which the client needs once the old one runs out.
+    pub fn extend_job_access(&self, job_id: &str, additional_hours: u64) -> Result<JobAccess, SshManagerError> {
+        if additional_hours == 0 {
+            return Err(SshManagerError::InvalidInput("Access can only be extended by at least one hour".to_string()));
+        }
+        let access = self
+            .active_users
+            .lock()
+            .unwrap()
+            .get(job_id)
+            .cloned()
+            .ok_or_else(|| SshManagerError::NotFound(format!("No SSH user found for job '{}'", job_id)))?;
+        if access.expires_at <= chrono::Utc::now() {
+            return Err(SshManagerError::InvalidInput(format!("Access to job '{}' has already expired", job_id)));
+        }
+
+        let expires_at = access.expires_at + chrono::Duration::hours(additional_hours as i64);
+        // A suspended user is certified again when it resumes
+        let certificate = match &access.ssh_user.ssh_key {
+            Some(key) if access.ssh_user.is_active => Some(self.certify(&access.ssh_user.username, key, expires_at, &access.ssh_user.sources)?),
+            _ => access.ssh_user.certificate.clone(),
+        };
+        let extended = {
+            let mut active_users = self.active_users.lock().unwrap();
+            let current = active_users
+                .get_mut(job_id)
+                .ok_or_else(|| SshManagerError::NotFound(format!("No SSH user found for job '{}'", job_id)))?;
+            current.expires_at = expires_at;
+            current.ssh_user.certificate = certificate;
+            current.clone()
+        };
+        self.save_state();
+        self.expiry_changed.notify_one();
+
+        info!("Extended access to job '{}' by {} h, until {}", job_id, additional_hours, expires_at);
+        Ok(extended)
+    }
+
+    /// Change what a job's user may do over SSH; sshd applies it to connections made after this
+    pub fn set_job_policy(&self, job_id: &str, policy: SshPolicy) -> Result<JobAccess, SshManagerError> {
+        let access = self
+            .active_users
+            .lock()
+            .unwrap()
+            .get(job_id)
+            .cloned()
+            .ok_or_else(|| SshManagerError::NotFound(format!("No SSH user found for job '{}'", job_id)))?;
+        let policy = self.enforced(policy);
+        self.write_policy(&access.ssh_user.username, job_id, access.ssh_user.isolation, policy, &access.ssh_user.sources)?;
+
+        let updated = {
+            let mut active_users = self.active_users.lock().unwrap();
+            let current = active_users
+                .get_mut(job_id)
+                .ok_or_else(|| SshManagerError::NotFound(format!("No SSH user found for job '{}'", job_id)))?;
+            current.ssh_user.policy = policy;
+            current.mode = AccessMode::of(current.ssh_user.isolation, &policy);
+            current.clone()
+        };
+        self.save_state();
+        info!("Set the SSH policy of job '{}' to {:?}", job_id, policy);
+        Ok(updated)
+    }
+
+    /// Limit a job's user to the client's addresses and keys, or lift the limit with an empty restriction
+    ///
+    /// sshd refuses the user from anywhere else once this returns, whatever it signs in with; the
+    /// returned access holds a certificate that carries the addresses too. With fingerprints, the
+    /// client's own key must be among them.
+    pub fn restrict_sources(&self, job_id: &str, sources: SourceRestriction) -> Result<JobAccess, SshManagerError> {
+        sources.validate()?;
+        let mut access = self
+            .active_users
+            .lock()
+            .unwrap()
+            .get(job_id)
+            .cloned()
+            .ok_or_else(|| SshManagerError::NotFound(format!("No SSH user found for job '{}'", job_id)))?;
+        if access.ssh_user.ssh_key.as_deref().is_some_and(|key| !sources.allows_key(key)) {
+            return Err(SshManagerError::InvalidInput(format!("The key of job '{}' must be one of the allowed keys", job_id)));
+        }
+        let user = &access.ssh_user;
+        self.write_policy(&user.username, job_id, user.isolation, user.policy, &sources)?;
+        if let (Some(key), Some(_), true) = (&user.ssh_key, &user.certificate, user.is_active) {
+            access.ssh_user.certificate = Some(self.certify(&user.username, key, access.expires_at, &sources)?);
+        }
+        access.ssh_user.sources = sources;
+        self.authorize_keys(&access)?;
+
+        let updated = {
+            let mut active_users = self.active_users.lock().unwrap();
+            let current = active_users
+                .get_mut(job_id)
+                .ok_or_else(|| SshManagerError::NotFound(format!("No SSH user found for job '{}'", job_id)))?;
+            current.ssh_user.sources = access.ssh_user.sources;
+            current.ssh_user.certificate = access.ssh_user.certificate;
+            current.clone()
+        };
+        self.save_state();
+        info!("Restricted job '{}': {}", job_id, updated.ssh_user.sources.summary());
+        Ok(updated)
+    }
+
+    /// Give a job's user a new key and certificate, voiding the old certificate
+    ///
+    /// With `ssh_key` the client's new public key is certified; without, a key pair is made here
+    /// and its private half returned once in `private_key`. authorized_keys and the login check
+    /// switch to the new key in one step. Sessions opened with the old key stay up unless
+    /// `end_sessions`; a restriction to the old key's fingerprint moves to the new one.
+    pub fn rotate_credentials(&self, job_id: &str, ssh_key: Option<&str>, end_sessions: bool) -> Result<JobAccess, SshManagerError> {
+        let access = self.job_access(job_id)?;
+        if access.expires_at <= chrono::Utc::now() {
+            return Err(SshManagerError::InvalidInput(format!("Access to job '{}' has already expired", job_id)));
+        }
+        if !access.ssh_user.is_active {
+            return Err(SshManagerError::InvalidInput(format!("Access to job '{}' is suspended", job_id)));
+        }
+        let user = &access.ssh_user;
+        let (public_key, private_key) = match ssh_key {
+            Some(key) => (validate_public_key(key)?, None),
+            None => {
+                let (private_key, public_key) = ca::generate_key_pair(&user.username)?;
+                (public_key, Some(private_key))
+            }
+        };
+        if user.ssh_key.as_deref() == Some(public_key.as_str()) {
+            return Err(SshManagerError::InvalidInput(format!("The new key of job '{}' is the one it has", job_id)));
         }
+
+        let mut sources = user.sources.clone();
+        if let (Some(old), Some(new)) = (user.ssh_key.as_deref().and_then(grants::fingerprint), grants::fingerprint(&public_key)) {
+            for fingerprint in sources.fingerprints.iter_mut().filter(|fingerprint| **fingerprint == old) {
+                *fingerprint = new.clone();
+            }
+        }
+        let certificate = self.certify(&user.username, &public_key, access.expires_at, &sources)?;
+        let mut rotated = access.clone();
+        rotated.ssh_user.ssh_key = Some(public_key);
+        rotated.ssh_user.certificate = Some(certificate);
+        rotated.ssh_user.sources = sources;
+        self.authorize_keys(&rotated)?;
+
+        let updated = {
+            let mut active_users = self.active_users.lock().unwrap();
+            let current = active_users
+                .get_mut(job_id)
+                .ok_or_else(|| SshManagerError::NotFound(format!("No SSH user found for job '{}'", job_id)))?;
+            current.ssh_user.ssh_key = rotated.ssh_user.ssh_key;
+            current.ssh_user.certificate = rotated.ssh_user.certificate;
+            current.ssh_user.sources = rotated.ssh_user.sources;
+            current.clone()
+        };
+        self.save_state();
+
+        // The new credentials are in place, so the client is not left without any if this fails
+        if let Some(old) = &user.certificate {
+            if let Err(e) = self.revoke_certificate(old) {
+                error!("The old certificate of job '{}' stays valid until {}: {}", job_id, access.expires_at, e);
+            }
+        }
+        if end_sessions {
+            if let Err(e) = self.end_sessions(&user.username) {
+                warn!("Failed to end the sessions of SSH user '{}': {}", user.username, e);
+            }
+        }
+        info!("Rotated the credentials of job '{}'", job_id);
+        Ok(JobAccess { private_key, ..updated })
+    }
+
+    /// Get the usernames of all active job users
+    pub fn get_current_users(&self) -> Vec<String> {
+        let mut users: Vec<String> = self
+            .active_users
+            .lock()
+            .unwrap()
+            .values()
+            .map(|access| access.ssh_user.username.clone())
+            .collect();
+        users.sort();
+        users
     }
 
-    /// Get current active user
-    pub fn get_current_user(&self) -> Option<String> {
-        self.current_user.lock().unwrap().clone()
+    /// Session slots still free
+    pub fn free_sessions(&self) -> usize {
+        let used = self.active_users.lock().unwrap().len() + *self.creating.lock().unwrap();
+        self.limits.lock().unwrap().max_sessions.saturating_sub(used)
     }
 
-    /// Get all active job accesses
+    /// Get all active job accesses, with how much of its disk quota each user has used
     pub fn get_active_jobs(&self) -> Vec<JobAccess> {
-        self.active_users.lock().unwrap().values().cloned().collect()
+        let mut jobs: Vec<JobAccess> = self.active_users.lock().unwrap().values().cloned().collect();
+        for job in &mut jobs {
+            if let Some(disk_quota) = &mut job.disk_quota {
+                disk_quota.used_mb = quota::used_mb(Path::new(&format!("/home/{}", job.ssh_user.username)));
+            }
+        }
+        jobs
     }
 
     /// Check if a user can access (for SSH login validation)
+    ///
+    /// sshd asks the same of the state file through `enable_login_check`.
     pub fn validate_user_access(&self, username: &str) -> bool {
-        let active_users = self.active_users.lock().unwrap();
-        active_users.values().any(|access| {
-            access.ssh_user.username == username && 
-            access.ssh_user.is_active && 
-            access.expires_at > chrono::Utc::now()
-        })
+        login::check_access(&self.get_active_jobs(), username, chrono::Utc::now()).is_ok()
+    }
+
+    /// Have sshd check every job user login against this manager's state file, see `login`
+    ///
+    /// The helper runs as `run_as`, which must be able to read the state file.
+    pub fn enable_login_check(&self, run_as: &str) -> Result<(), SshManagerError> {
+        let Some(state_path) = &self.state_path else {
+            return Err(SshManagerError::Unsupported("The login check needs a manager with a state file".to_string()));
+        };
+        #[cfg(unix)]
+        {
+            let request = service::Request::LoginCheck { state_path: state_path.clone(), run_as: run_as.to_string() };
+            if let Some(result) = self.call_service(&request) {
+                return result;
+            }
+        }
+        accounts::enable_login_check(&login::bundled_helper()?, state_path, run_as)
     }
 
     /// Clean up expired users
-    pub async fn cleanup_expired_users(&self) -> Result<Vec<String>, String> {
-        let mut removed_jobs = Vec::new();
+    pub async fn cleanup_expired_users(&self) -> Result<Vec<String>, SshManagerError> {
+        let removed_jobs = self
+            .reap_expired()
+            .await
+            .into_iter()
+            .filter_map(|event| match event {
+                ReaperEvent::Expired { job_id, .. } => Some(job_id),
+                ReaperEvent::CleanupFailed { .. } => None,
+            })
+            .collect();
+        Ok(removed_jobs)
+    }
+
+    /// Remove the users of every expired job, reporting each one
+    async fn reap_expired(&self) -> Vec<ReaperEvent> {
+        let mut events = Vec::new();
         let active_users = {
             let users = self.active_users.lock().unwrap();
             users.clone()
@@ -148,207 +1273,356 @@ impl SshManager {
         for (job_id, access) in active_users {
             if access.expires_at <= chrono::Utc::now() {
                 info!("Cleaning up expired user for job '{}'", job_id);
-                if let Err(e) = self.remove_job_user(&job_id).await {
-                    error!("Failed to cleanup expired user for job '{}': {}", job_id, e);
-                } else {
-                    removed_jobs.push(job_id);
+                let username = access.ssh_user.username;
+                match self.remove_job_user(&job_id).await {
+                    Ok(()) => {
+                        self.events.publish(SessionEvent::Expired { job_id: job_id.clone(), username: username.clone() });
+                        events.push(ReaperEvent::Expired { job_id, username });
+                    }
+                    Err(e) => {
+                        error!("Failed to cleanup expired user for job '{}': {}", job_id, e);
+                        events.push(ReaperEvent::CleanupFailed { job_id, username, error: e.to_string() });
+                    }
                 }
             }
         }
+        events
+    }
 
-        Ok(removed_jobs)
+    /// How long the expiry cleanup may sleep: until the earliest expiry, at most `cap`, and at
+    /// most `EXPIRY_RETRY` while an expired user is still waiting to be removed
+    fn expiry_wait(&self, cap: Duration) -> Duration {
+        let now = chrono::Utc::now();
+        let (overdue, next) = {
+            let active_users = self.active_users.lock().unwrap();
+            let overdue = active_users.values().any(|access| access.expires_at <= now);
+            (overdue, active_users.values().map(|access| access.expires_at).filter(|at| *at > now).min())
+        };
+        let mut wait = next.and_then(|at| (at - now).to_std().ok()).unwrap_or(cap).min(cap);
+        if overdue {
+            wait = wait.min(EXPIRY_RETRY);
+        }
+        wait
     }
 
-    /// Generate a secure random password
-    fn generate_secure_password(&self) -> String {
-        use rand::Rng;
-        const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789!@#$%^&*";
-        let mut rng = rand::thread_rng();
-        
-        (0..16)
-            .map(|_| {
-                let idx = rng.gen_range(0..CHARSET.len());
-                CHARSET[idx] as char
-            })
-            .collect()
+    /// Remove job users as their jobs expire, for as long as the manager lives
+    ///
+    /// Sleeps until the earliest expiry, waking early when a job is added or extended; users
+    /// that could not be removed are retried every `EXPIRY_RETRY`.
+    pub async fn run_expiry_cleanup(&self) {
+        loop {
+            self.reap_expired().await;
+            tokio::select! {
+                _ = tokio::time::sleep(self.expiry_wait(EXPIRY_IDLE)) => {}
+                _ = self.expiry_changed.notified() => {}
+            }
+        }
     }
 
-    /// Create a system user with sudo privileges for job access
-    async fn create_system_user(&self, username: &str, password: &str) -> Result<(), String> {
-        // Try to use the privileged service first
-        if let Ok(()) = self.create_user_via_service(username, password).await {
-            return Ok(());
+    /// Create the system user and certify the client's key, or one made here, as
+    /// (public key, certificate, private key made here)
+    #[allow(clippy::too_many_arguments)]
+    async fn provision_user(
+        &self,
+        username: &str,
+        job_id: &str,
+        ssh_key: Option<&str>,
+        isolation: IsolationLevel,
+        policy: SshPolicy,
+        docker: DockerAccess,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(String, String, Option<String>), SshManagerError> {
+        let (public_key, private_key) = match ssh_key {
+            Some(key) => (key.to_string(), None),
+            None => {
+                let user = username.to_string();
+                let (private_key, public_key) = self.unblocked(move |_| ca::generate_key_pair(&user)).await?;
+                (public_key, Some(private_key))
+            }
+        };
+        self.create_system_user(username, Credential::Certificate, isolation, policy, docker, job_id).await?;
+        let (user, key) = (username.to_string(), public_key.clone());
+        match self.unblocked(move |manager| manager.certify(&user, &key, expires_at, &SourceRestriction::default())).await {
+            Ok(certificate) => Ok((public_key, certificate, private_key)),
+            Err(e) => {
+                let _ = self.delete_system_user(username).await;
+                Err(e)
+            }
         }
-        
-        // Fallback to direct sudo (will fail in GUI without proper setup)
-        warn!("Service unavailable, trying direct sudo (may fail in GUI)");
-        self.create_user_direct(username, password).await
     }
-    
-    /// Create user via privileged service (recommended)
-    async fn create_user_via_service(&self, username: &str, password: &str) -> Result<(), String> {
-        use std::fs::OpenOptions;
-        use std::io::Write;
-        
-        let socket_path = "/tmp/eryzaa_ssh_service.sock";
-        let response_path = "/tmp/eryzaa_ssh_service.sock.response";
-        
-        // Check if service is running
-        if !std::path::Path::new(socket_path).exists() {
-            return Err("SSH management service not running".to_string());
+
+    /// Void a certificate from the host CA, through the privileged service when it runs
+    fn revoke_certificate(&self, certificate: &str) -> Result<(), SshManagerError> {
+        #[cfg(unix)]
+        {
+            let request = service::Request::Revoke { certificate: certificate.to_string() };
+            if let Some(result) = self.call_service(&request) {
+                return result;
+            }
         }
-        
-        // Send request to service
-        let request = format!("create|{}|{}", username, password);
-        
-        match OpenOptions::new().write(true).open(socket_path) {
-            Ok(mut file) => {
-                if let Err(e) = writeln!(file, "{}", request) {
-                    return Err(format!("Failed to write to service socket: {}", e));
-                }
+        accounts::revoke_certificate(certificate)
+    }
+
+    /// End a job user's logins, through the privileged service when it runs
+    fn end_sessions(&self, username: &str) -> Result<(), SshManagerError> {
+        #[cfg(unix)]
+        {
+            let request = service::Request::EndSessions { username: username.to_string() };
+            if let Some(result) = self.call_service(&request) {
+                return result;
             }
-            Err(e) => return Err(format!("Failed to open service socket: {}", e)),
         }
-        
-        // Wait for response (with timeout)
-        for _ in 0..50 { // 5 second timeout
-            if std::path::Path::new(response_path).exists() {
-                match std::fs::read_to_string(response_path) {
-                    Ok(response) => {
-                        std::fs::remove_file(response_path).ok(); // Cleanup
-                        if response.trim() == "SUCCESS" {
-                            info!("Created system user '{}' via service", username);
-                            return Ok(());
-                        } else {
-                            return Err(format!("Service error: {}", response.trim()));
-                        }
-                    }
-                    Err(_) => continue,
-                }
+        accounts::end_sessions(username)
+    }
+
+    /// Certificate from the host CA, through the privileged service when it runs
+    fn certify(
+        &self,
+        username: &str,
+        public_key: &str,
+        expires_at: chrono::DateTime<chrono::Utc>,
+        sources: &SourceRestriction,
+    ) -> Result<String, SshManagerError> {
+        #[cfg(unix)]
+        {
+            let socket_path = self.service_socket.as_path();
+            if socket_path.exists() {
+                let request = service::Request::Certify {
+                    username: username.to_string(),
+                    public_key: public_key.to_string(),
+                    expires_at,
+                    sources: sources.clone(),
+                };
+                return match service::call(socket_path, &request)? {
+                    service::Response::Certificate(certificate) => Ok(certificate),
+                    service::Response::Error(e) => Err(e.context("Service error")),
+                    response => Err(SshManagerError::Protocol(format!("Unexpected answer from SSH service: {:?}", response))),
+                };
             }
-            std::thread::sleep(std::time::Duration::from_millis(100));
         }
-        
-        Err("Service timeout".to_string())
+        accounts::certify(username, public_key, expires_at, sources)
+    }
+
+    /// Create a system user for job access, through the privileged service when it runs
+    async fn create_system_user(
+        &self,
+        username: &str,
+        credential: Credential,
+        isolation: IsolationLevel,
+        policy: SshPolicy,
+        docker: DockerAccess,
+        job_id: &str,
+    ) -> Result<(), SshManagerError> {
+        self.config.check_credential(&credential)?;
+        #[cfg(unix)]
+        {
+            let request = service::Request::Create {
+                username: username.to_string(),
+                credential: credential.clone(),
+                isolation,
+                policy,
+                job_id: job_id.to_string(),
+                docker,
+            };
+            if let Some(result) = self.call_service_async(&request).await {
+                return result.inspect(|_| info!("Created system user '{}' via service", username));
+            }
+            
+            // Fallback to direct sudo (will fail in GUI without proper setup)
+            warn!("Service unavailable, trying direct sudo (may fail in GUI)");
+        }
+        let (username, job_id) = (username.to_string(), job_id.to_string());
+        self.unblocked(move |_| accounts::create_user(&username, &credential, isolation, &policy, &job_id, docker)).await
     }
     
-    /// Direct sudo method (fallback)
-    async fn create_user_direct(&self, username: &str, password: &str) -> Result<(), String> {
-    /// Direct sudo method (fallback)
-    async fn create_user_direct(&self, username: &str, password: &str) -> Result<(), String> {
-        // Create user
-        let create_output = Command::new("sudo")
-            .args(&["useradd", "-m", "-s", "/bin/bash", username])
-            .output()
-            .map_err(|e| format!("Failed to execute useradd: {}", e))?;
+    /// Send `request` to the privileged service; None when it is not installed
+    #[cfg(unix)]
+    fn call_service(&self, request: &service::Request) -> Option<Result<(), SshManagerError>> {
+        let socket_path = self.service_socket.as_path();
+        if !socket_path.exists() {
+            return None;
+        }
+        Some(service_done(service::call(socket_path, request)))
+    }
 
-        if !create_output.status.success() {
-            return Err(format!("Failed to create user: {}", String::from_utf8_lossy(&create_output.stderr)));
+    /// `call_service` for the async methods, which must not block the runtime on the socket
+    #[cfg(unix)]
+    async fn call_service_async(&self, request: &service::Request) -> Option<Result<(), SshManagerError>> {
+        let socket_path = self.service_socket.as_path();
+        if !socket_path.exists() {
+            return None;
         }
+        Some(service_done(service::call_async(socket_path, request).await))
+    }
 
-        // Set password
-        let passwd_output = Command::new("sudo")
-            .args(&["chpasswd"])
-            .arg(format!("{}:{}", username, password))
-            .output()
-            .map_err(|e| format!("Failed to execute chpasswd: {}", e))?;
+    /// Run `work` on tokio's blocking threads, for the async methods' subprocesses and file writes
+    async fn unblocked<T: Send + 'static>(&self, work: impl FnOnce(&SshManager) -> T + Send + 'static) -> T {
+        let manager = self.clone();
+        match tokio::task::spawn_blocking(move || work(&manager)).await {
+            Ok(result) => result,
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        }
+    }
 
-        if !passwd_output.status.success() {
-            return Err(format!("Failed to set password: {}", String::from_utf8_lossy(&passwd_output.stderr)));
+    /// Cap the user's processes to the share of the machine the renter allows
+    fn apply_resource_limits(&self, username: &str, limits: &SessionLimits) -> Result<(), SshManagerError> {
+        let cpus = std::thread::available_parallelism().map(|cpus| cpus.get()).unwrap_or(1);
+        let caps = limits.user_caps(cpus, machine_memory_mb());
+        if caps.is_empty() {
+            return Ok(());
         }
+        #[cfg(unix)]
+        {
+            let request = service::Request::Limit { username: username.to_string(), caps: caps.clone() };
+            if let Some(result) = self.call_service(&request) {
+                return result;
+            }
+        }
+        accounts::limit_user(username, &caps)
+    }
 
-        // Add to docker group for container access
-        let docker_output = Command::new("sudo")
-            .args(&["usermod", "-aG", "docker", username])
-            .output()
-            .map_err(|e| format!("Failed to add user to docker group: {}", e))?;
+    /// Cap the user's home, through the privileged service when it runs
+    fn apply_disk_quota(&self, username: &str, limit_mb: u64) -> Result<QuotaMethod, SshManagerError> {
+        #[cfg(unix)]
+        {
+            let socket_path = self.service_socket.as_path();
+            if socket_path.exists() {
+                let request = service::Request::Quota { username: username.to_string(), limit_mb };
+                return match service::call(socket_path, &request)? {
+                    service::Response::Quota(method) => Ok(method),
+                    service::Response::Error(e) => Err(e.context("Service error")),
+                    response => Err(SshManagerError::Protocol(format!("Unexpected answer from SSH service: {:?}", response))),
+                };
+            }
+        }
+        accounts::quota_user(username, limit_mb)
+    }
 
-        if !docker_output.status.success() {
-            warn!("Failed to add user to docker group: {}", String::from_utf8_lossy(&docker_output.stderr));
+    /// Cap the bandwidth of every tracked job user, which also counts their traffic
+    fn apply_bandwidth_limits(&self, limits: &SessionLimits) {
+        let caps = limits.bandwidth_caps();
+        match self.throttle_users(&self.get_current_users(), caps) {
+            Err(e) if !caps.is_empty() => warn!("SSH users' bandwidth is not limited: {}", e),
+            Err(e) => debug!("SSH users' traffic is not counted: {}", e),
+            Ok(_) => {}
         }
+    }
 
-        info!("Created system user '{}' with password", username);
-        Ok(())
+    /// Rewrite a job user's sshd Match blocks, through the privileged service when it runs
+    fn write_policy(
+        &self,
+        username: &str,
+        job_id: &str,
+        isolation: IsolationLevel,
+        policy: SshPolicy,
+        sources: &SourceRestriction,
+    ) -> Result<(), SshManagerError> {
+        #[cfg(unix)]
+        {
+            let request = service::Request::Policy {
+                username: username.to_string(),
+                job_id: job_id.to_string(),
+                isolation,
+                policy,
+                sources: sources.clone(),
+            };
+            if let Some(result) = self.call_service(&request) {
+                return result;
+            }
+        }
+        accounts::set_policy(username, job_id, isolation, &policy, sources)
     }
 
-    /// Delete a system user
-    async fn delete_system_user(&self, username: &str) -> Result<(), String> {
-        // Try service first
-        if let Ok(()) = self.delete_user_via_service(username).await {
-            return Ok(());
+    /// Throttle `usernames`, through the privileged service when it runs
+    fn throttle_users(&self, usernames: &[String], caps: BandwidthCaps) -> Result<(), SshManagerError> {
+        #[cfg(unix)]
+        {
+            let request = service::Request::Throttle { usernames: usernames.to_vec(), caps };
+            if let Some(result) = self.call_service(&request) {
+                return result;
+            }
         }
-        
-        // Fallback to direct sudo
-        warn!("Service unavailable, trying direct sudo (may fail in GUI)");
-        self.delete_user_direct(username).await
+        accounts::throttle_users(usernames, caps)
     }
-    
-    /// Delete user via privileged service
-    async fn delete_user_via_service(&self, username: &str) -> Result<(), String> {
-        use std::fs::OpenOptions;
-        use std::io::Write;
-        
-        let socket_path = "/tmp/eryzaa_ssh_service.sock";
-        let response_path = "/tmp/eryzaa_ssh_service.sock.response";
-        
-        if !std::path::Path::new(socket_path).exists() {
-            return Err("SSH management service not running".to_string());
+
+    /// Traffic counted per job user, through the privileged service when it runs
+    fn read_traffic(&self) -> Result<Vec<UserTraffic>, SshManagerError> {
+        #[cfg(unix)]
+        {
+            let socket_path = self.service_socket.as_path();
+            if socket_path.exists() {
+                return match service::call(socket_path, &service::Request::Traffic)? {
+                    service::Response::Traffic(traffic) => Ok(traffic),
+                    service::Response::Error(e) => Err(e.context("Service error")),
+                    response => Err(SshManagerError::Protocol(format!("Unexpected answer from SSH service: {:?}", response))),
+                };
+            }
         }
-        
-        let request = format!("remove|{}", username);
-        
-        match OpenOptions::new().write(true).open(socket_path) {
-            Ok(mut file) => {
-                if let Err(e) = writeln!(file, "{}", request) {
-                    return Err(format!("Failed to write to service socket: {}", e));
-                }
+        accounts::user_traffic()
+    }
+
+    /// Open connections of job users, through the privileged service when it runs
+    fn read_connections(&self) -> Result<Vec<Connection>, SshManagerError> {
+        #[cfg(unix)]
+        {
+            let socket_path = self.service_socket.as_path();
+            if socket_path.exists() {
+                return match service::call(socket_path, &service::Request::Connections)? {
+                    service::Response::Connections(connections) => Ok(connections),
+                    service::Response::Error(e) => Err(e.context("Service error")),
+                    response => Err(SshManagerError::Protocol(format!("Unexpected answer from SSH service: {:?}", response))),
+                };
             }
-            Err(e) => return Err(format!("Failed to open service socket: {}", e)),
         }
-        
-        // Wait for response
-        for _ in 0..50 {
-            if std::path::Path::new(response_path).exists() {
-                match std::fs::read_to_string(response_path) {
-                    Ok(response) => {
-                        std::fs::remove_file(response_path).ok();
-                        if response.trim() == "SUCCESS" {
-                            info!("Deleted system user '{}' via service", username);
-                            return Ok(());
-                        } else {
-                            return Err(format!("Service error: {}", response.trim()));
-                        }
-                    }
-                    Err(_) => continue,
-                }
+        activity::read_connections()
+    }
+
+    /// Delete a system user
+    async fn delete_system_user(&self, username: &str) -> Result<(), SshManagerError> {
+        #[cfg(unix)]
+        {
+            let request = service::Request::Remove { username: username.to_string() };
+            if let Some(result) = self.call_service_async(&request).await {
+                return result.inspect(|_| info!("Deleted system user '{}' via service", username));
             }
-            std::thread::sleep(std::time::Duration::from_millis(100));
+            
+            // Fallback to direct sudo
+            warn!("Service unavailable, trying direct sudo (may fail in GUI)");
         }
-        
-        Err("Service timeout".to_string())
+        let username = username.to_string();
+        self.unblocked(move |_| accounts::delete_user(&username)).await
     }
-    
-    /// Direct sudo method for deletion
-    async fn delete_user_direct(&self, username: &str) -> Result<(), String> {
-    /// Direct sudo method for deletion
-    async fn delete_user_direct(&self, username: &str) -> Result<(), String> {
-        // Kill any processes owned by the user
-        let _ = Command::new("sudo")
-            .args(&["pkill", "-u", username])
-            .output();
-
-        // Remove user and home directory
-        let delete_output = Command::new("sudo")
-            .args(&["userdel", "-r", username])
-            .output()
-            .map_err(|e| format!("Failed to execute userdel: {}", e))?;
-
-        if !delete_output.status.success() {
-            return Err(format!("Failed to delete user: {}", String::from_utf8_lossy(&delete_output.stderr)));
-        }
-
-        info!("Deleted system user '{}'", username);
-        Ok(())
+
+    async fn lock_system_user(&self, username: &str, locked: bool) -> Result<(), SshManagerError> {
+        #[cfg(unix)]
+        {
+            let request = match locked {
+                true => service::Request::Suspend { username: username.to_string() },
+                false => service::Request::Resume { username: username.to_string() },
+            };
+            if let Some(result) = self.call_service_async(&request).await {
+                return result;
+            }
+
+            warn!("Service unavailable, trying direct sudo (may fail in GUI)");
+        }
+        let username = username.to_string();
+        self.unblocked(move |_| match locked {
+            true => accounts::suspend_user(&username),
+            false => accounts::resume_user(&username),
+        })
+        .await
+    }
+}
+
+/// What the service answered to a request that only reports success
+#[cfg(unix)]
+fn service_done(response: Result<service::Response, SshManagerError>) -> Result<(), SshManagerError> {
+    match response {
+        Ok(service::Response::Done) => Ok(()),
+        Ok(service::Response::Error(e)) => Err(e.context("Service error")),
+        Ok(response) => Err(SshManagerError::Protocol(format!("Unexpected answer from SSH service: {:?}", response))),
+        Err(e) => Err(e),
     }
 }
 
@@ -365,14 +1639,421 @@ mod tests {
     #[tokio::test]
     async fn test_ssh_manager_creation() {
         let manager = SshManager::new();
-        assert!(manager.get_current_user().is_none());
+        assert!(manager.get_current_users().is_empty());
         assert!(manager.get_active_jobs().is_empty());
+        assert_eq!(manager.free_sessions(), 4);
     }
 
     #[test]
-    fn test_password_generation() {
-        let manager = SshManager::new();
-        let password = manager.generate_secure_password();
-        assert_eq!(password.len(), 16);
+    fn test_state_and_reconciliation() {
+        let path = std::env::temp_dir().join(format!("eryzaa-ssh-state-{}.json", std::process::id()));
+        let access = |job_id: &str, username: &str| JobAccess {
+            job_id: job_id.to_string(),
+            client_id: "client-1".to_string(),
+            ssh_user: SshUser {
+                username: username.to_string(),
+                job_id: job_id.to_string(),
+                created_at: chrono::Utc::now(),
+                is_active: true,
+                ssh_key: None,
+                certificate: None,
+                isolation: IsolationLevel::default(),
+                policy: SshPolicy::default(),
+                sources: SourceRestriction::default(),
+                docker: DockerAccess::default(),
+            },
+            expires_at: chrono::Utc::now() + chrono::Duration::hours(1),
+            access_token: Some(format!("token-{}", job_id)),
+            grants: vec![],
+            mode: AccessMode::Shell,
+            disk_quota: None,
+            activity: None,
+            private_key: None,
+        };
+        let manager = SshManager::with_state(SessionLimits::default(), &path);
+        manager.active_users.lock().unwrap().insert("job-a".to_string(), access("job-a", "job_aaaaaaaa"));
+        manager.active_users.lock().unwrap().insert("job-b".to_string(), access("job-b", "job_bbbbbbbb"));
+        manager.save_state();
+
+        let key = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHuY0m1wZ8Wq7u2Yt4b5vE3s1nZkq2jF8p9dLxR0aB7c";
+        let mut shared = access("job-c", "job_cccccccc");
+        shared.grants.push(Grant {
+            identity: "bob@example.com".to_string(),
+            scope: AccessScope::Full,
+            public_key: key.to_string(),
+            fingerprint: grants::fingerprint(key).unwrap(),
+            granted_at: chrono::Utc::now(),
+            token_hash: Some(grants::token_hash("bob-token")),
+        });
+        assert!(shared.may_delegate("token-job-c") && shared.may_delegate("bob-token"));
+        assert!(!shared.may_delegate("token-job-a"));
+        assert_eq!(shared.identity_for(&grants::fingerprint(key).unwrap()).as_deref(), Some("bob@example.com"));
+
+        let restarted = SshManager::with_state(SessionLimits::default(), &path);
+        assert_eq!(restarted.get_current_users(), vec!["job_aaaaaaaa", "job_bbbbbbbb"]);
+        assert!(restarted.validate_user_access("job_bbbbbbbb"));
+        let before = restarted.get_active_jobs().into_iter().find(|access| access.job_id == "job-a").unwrap().expires_at;
+        let extended = restarted.extend_job_access("job-a", 2).unwrap();
+        assert_eq!(extended.expires_at, before + chrono::Duration::hours(2));
+        assert!(matches!(restarted.extend_job_access("job-z", 1), Err(SshManagerError::NotFound(_))));
+        let saved = SshManager::with_state(SessionLimits::default(), &path);
+        assert!(saved.get_active_jobs().iter().any(|access| access.expires_at == extended.expires_at));
+        std::fs::remove_file(&path).unwrap();
+
+        let passwd = "root:x:0:0:root:/root:/bin/bash\njob_aaaaaaaa:x:1001:1001::/home/job_aaaaaaaa:/bin/bash\njob_cccccccc:x:1002:1002::/home/job_cccccccc:/bin/bash\n";
+        let (missing, untracked) = compare_with_local_users(&["job_aaaaaaaa", "job_bbbbbbbb"], &accounts::users_in_passwd(passwd));
+        assert_eq!(missing, vec!["job_bbbbbbbb"]);
+        assert_eq!(untracked, vec!["job_cccccccc"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extend_job_access() {
+        let socket = std::env::temp_dir().join(format!("eryzaa-ssh-extend-{}.sock", std::process::id()));
+        let state = std::env::temp_dir().join(format!("eryzaa-ssh-extend-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&socket);
+        let listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let seen = Arc::clone(&requests);
+        let own_uid = unsafe { libc::geteuid() };
+        std::thread::spawn(move || {
+            service::serve(listener, vec![own_uid], move |request| {
+                seen.lock().unwrap().push(request.clone());
+                match request {
+                    service::Request::Certify { expires_at, .. } => service::Response::Certificate(format!("cert until {}", expires_at.timestamp())),
+                    _ => service::Response::Done,
+                }
+            })
+        });
+
+        let key = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHuY0m1wZ8Wq7u2Yt4b5vE3s1nZkq2jF8p9dLxR0aB7c";
+        let access = |job_id: &str, username: &str, expires_at| JobAccess {
+            job_id: job_id.to_string(),
+            client_id: "client-1".to_string(),
+            ssh_user: SshUser {
+                username: username.to_string(),
+                job_id: job_id.to_string(),
+                created_at: chrono::Utc::now(),
+                is_active: true,
+                ssh_key: Some(key.to_string()),
+                certificate: Some("first certificate".to_string()),
+                isolation: IsolationLevel::default(),
+                policy: SshPolicy::default(),
+                sources: SourceRestriction::default(),
+                docker: DockerAccess::default(),
+            },
+            expires_at,
+            access_token: None,
+            grants: vec![],
+            mode: AccessMode::Shell,
+            disk_quota: None,
+            activity: None,
+            private_key: None,
+        };
+        let expires_at = chrono::Utc::now() + chrono::Duration::hours(1);
+        let manager = SshManager::with_state(SessionLimits::default(), &state).with_service_socket(&socket);
+        manager.active_users.lock().unwrap().insert("job-a".to_string(), access("job-a", "job_aaaaaaaa", expires_at));
+        let expired = chrono::Utc::now() - chrono::Duration::minutes(1);
+        manager.active_users.lock().unwrap().insert("job-b".to_string(), access("job-b", "job_bbbbbbbb", expired));
+
+        assert!(matches!(manager.extend_job_access("job-a", 0), Err(SshManagerError::InvalidInput(_))));
+        assert!(matches!(manager.extend_job_access("job-b", 1), Err(SshManagerError::InvalidInput(_))));
+        assert!(requests.lock().unwrap().is_empty());
+        assert_eq!(manager.job_access("job-b").unwrap().expires_at, expired);
+
+        let extended = manager.extend_job_access("job-a", 3).unwrap();
+        let new_expiry = expires_at + chrono::Duration::hours(3);
+        let certificate = format!("cert until {}", new_expiry.timestamp());
+        assert_eq!(extended.expires_at, new_expiry);
+        assert_eq!(extended.ssh_user.certificate.as_deref(), Some(certificate.as_str()));
+        assert!(matches!(&requests.lock().unwrap()[..], [service::Request::Certify { username, expires_at, .. }] if username == "job_aaaaaaaa" && *expires_at == new_expiry));
+
+        let saved = SshManager::with_state(SessionLimits::default(), &state).job_access("job-a").unwrap();
+        assert_eq!(saved.expires_at, new_expiry);
+        assert_eq!(saved.ssh_user.certificate, Some(certificate));
+        std::fs::remove_file(&state).unwrap();
+        std::fs::remove_file(&socket).unwrap();
+    }
+
+    #[test]
+    fn test_session_limits() {
+        let limits = SessionLimits {
+            max_sessions: 2,
+            cpu_quota_percent: Some(150),
+            memory_max_mb: Some(2048),
+            machine_cpu_percent: None,
+            machine_memory_percent: None,
+            reserved_cpus: vec![],
+            reserved_memory_mb: 0,
+            isolation: IsolationLevel::Chroot,
+            policy: SshPolicy::untrusted(),
+            docker: DockerAccess::Proxy,
+            egress_mbit: Some(20),
+            ingress_mbit: None,
+            disk_quota_mb: Some(10240),
+        };
+        assert_eq!(limits.bandwidth_caps(), BandwidthCaps { egress_mbit: Some(20), ingress_mbit: None });
+        assert_eq!(limits.user_caps(8, Some(16384)).slice_properties(), vec!["CPUQuota=150%", "MemoryMax=2048M"]);
+        let shared = SessionLimits { cpu_quota_percent: Some(400), ..SessionLimits::default() };
+        assert_eq!(shared.user_caps(4, Some(16384)), ResourceCaps { cpu_quota_percent: Some(320), memory_max_mb: Some(13107), allowed_cpus: vec![] });
+        assert_eq!(shared.user_caps(8, None), ResourceCaps { cpu_quota_percent: Some(400), memory_max_mb: None, allowed_cpus: vec![] });
+        let unlimited = SessionLimits { machine_cpu_percent: None, machine_memory_percent: None, ..Default::default() };
+        assert!(unlimited.user_caps(8, Some(16384)).is_empty());
+        // The owner keeps two cores and 8 GB of a 4-core, 16 GB desktop
+        let carved = SessionLimits { reserved_cpus: vec![2, 3], reserved_memory_mb: 8192, ..unlimited };
+        assert_eq!(carved.user_caps(4, Some(16384)).slice_properties(), vec!["MemoryMax=8192M", "AllowedCPUs=0,1"]);
+        let saved: SessionLimits = serde_json::from_str(r#"{"max_sessions": 3}"#).unwrap();
+        assert_eq!(saved.machine_cpu_percent, Some(80.0));
+        assert!(saved.bandwidth_caps().is_empty());
+
+        let manager = SshManager::with_limits(limits);
+        assert_eq!(manager.free_sessions(), 2);
+        manager.set_limits(SessionLimits { max_sessions: 0, ..Default::default() });
+        assert_eq!(manager.free_sessions(), 0);
+    }
+
+    #[test]
+    fn test_slice_properties() {
+        assert!(ResourceCaps::default().slice_properties().is_empty());
+        let caps = ResourceCaps { cpu_quota_percent: Some(250), memory_max_mb: Some(1024), allowed_cpus: vec![0, 2] };
+        assert_eq!(caps.slice_properties(), vec!["CPUQuota=250%", "MemoryMax=1024M", "AllowedCPUs=0,2"]);
+
+        // Core 0 and 2 GB kept back on a 4-core, 16 GB machine: 80% of three cores, the tighter memory cap
+        let limits = SessionLimits { memory_max_mb: Some(4096), reserved_cpus: vec![0], reserved_memory_mb: 2048, ..SessionLimits::default() };
+        assert_eq!(limits.user_caps(4, Some(16384)).slice_properties(), vec!["CPUQuota=240%", "MemoryMax=4096M", "AllowedCPUs=1,2,3"]);
+        // Without a per-user memory cap the machine share of what is left applies
+        let limits = SessionLimits { memory_max_mb: None, ..limits };
+        assert_eq!(limits.user_caps(4, Some(16384)).slice_properties(), vec!["CPUQuota=240%", "MemoryMax=11468M", "AllowedCPUs=1,2,3"]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_try_job_user() {
+        let socket = std::env::temp_dir().join(format!("eryzaa-ssh-try-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket);
+        let listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let seen = Arc::clone(&requests);
+        let own_uid = unsafe { libc::geteuid() };
+        std::thread::spawn(move || {
+            service::serve(listener, vec![own_uid], move |request| {
+                seen.lock().unwrap().push(request.clone());
+                match request {
+                    service::Request::Remove { .. } if seen.lock().unwrap().len() > 2 => {
+                        service::Response::Error(SshManagerError::PermissionDenied("userdel".to_string()))
+                    }
+                    _ => service::Response::Done,
+                }
+            })
+        });
+
+        let manager = SshManager::new().with_service_socket(&socket);
+        let username = manager.try_job_user().await.unwrap();
+        assert!(accounts::validate_username(&username).is_ok());
+        {
+            let requests = requests.lock().unwrap();
+            assert!(matches!(&requests[0], service::Request::Create { username: created, credential: Credential::Certificate, .. } if *created == username));
+            assert_eq!(requests[1], service::Request::Remove { username: username.clone() });
+        }
+        assert!(manager.trying.lock().unwrap().is_none());
+
+        let failed = manager.try_job_user().await.unwrap_err().to_string();
+        assert!(failed.contains("cannot delete it"), "{}", failed);
+        std::fs::remove_file(&socket).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rotate_credentials() {
+        let socket = std::env::temp_dir().join(format!("eryzaa-ssh-rotate-{}.sock", std::process::id()));
+        let state = std::env::temp_dir().join(format!("eryzaa-ssh-rotate-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&socket);
+        let listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let seen = Arc::clone(&requests);
+        let own_uid = unsafe { libc::geteuid() };
+        std::thread::spawn(move || {
+            service::serve(listener, vec![own_uid], move |request| {
+                seen.lock().unwrap().push(request.clone());
+                match request {
+                    service::Request::Certify { public_key, .. } => service::Response::Certificate(format!("cert for {}", public_key)),
+                    _ => service::Response::Done,
+                }
+            })
+        });
+
+        let old_key = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHuY0m1wZ8Wq7u2Yt4b5vE3s1nZkq2jF8p9dLxR0aB7c";
+        let new_key = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8g";
+        let (old_fingerprint, new_fingerprint) = (grants::fingerprint(old_key).unwrap(), grants::fingerprint(new_key).unwrap());
+        let other_fingerprint = "SHA256:someoneelse".to_string();
+        let manager = SshManager::with_state(SessionLimits::default(), &state).with_service_socket(&socket);
+        let access = JobAccess {
+            job_id: "job-a".to_string(),
+            client_id: "client-1".to_string(),
+            ssh_user: SshUser {
+                username: "job_aaaaaaaa".to_string(),
+                job_id: "job-a".to_string(),
+                created_at: chrono::Utc::now(),
+                is_active: true,
+                ssh_key: Some(old_key.to_string()),
+                certificate: Some("old certificate".to_string()),
+                isolation: IsolationLevel::default(),
+                policy: SshPolicy::default(),
+                sources: SourceRestriction { addresses: vec![], fingerprints: vec![old_fingerprint.clone(), other_fingerprint.clone()] },
+                docker: DockerAccess::default(),
+            },
+            expires_at: chrono::Utc::now() + chrono::Duration::hours(1),
+            access_token: None,
+            grants: vec![],
+            mode: AccessMode::Shell,
+            disk_quota: None,
+            activity: None,
+            private_key: None,
+        };
+        manager.active_users.lock().unwrap().insert("job-a".to_string(), access);
+
+        let rotated = manager.rotate_credentials("job-a", Some(new_key), true).unwrap();
+        assert_eq!(rotated.ssh_user.ssh_key.as_deref(), Some(new_key));
+        assert_eq!(rotated.ssh_user.certificate, Some(format!("cert for {}", new_key)));
+        assert!(rotated.private_key.is_none());
+        // The restriction to the old key now names the new one; others are left as they were
+        assert_eq!(rotated.ssh_user.sources.fingerprints, vec![new_fingerprint.clone(), other_fingerprint]);
+
+        // Certified for the new key before anything is voided, then the old certificate goes
+        let requests = requests.lock().unwrap().clone();
+        let actions: Vec<&str> = requests
+            .iter()
+            .map(|request| match request {
+                service::Request::Certify { .. } => "certify",
+                service::Request::Authorize { .. } => "authorize",
+                service::Request::Revoke { .. } => "revoke",
+                service::Request::EndSessions { .. } => "end sessions",
+                _ => "other",
+            })
+            .collect();
+        assert_eq!(actions, vec!["certify", "authorize", "revoke", "end sessions"]);
+        assert!(matches!(&requests[0], service::Request::Certify { public_key, sources, .. } if public_key == new_key && sources.fingerprints[0] == new_fingerprint));
+        assert!(matches!(&requests[2], service::Request::Revoke { certificate } if certificate == "old certificate"));
+
+        assert!(manager.rotate_credentials("job-a", Some(new_key), false).is_err());
+        let restarted = SshManager::with_state(SessionLimits::default(), &state);
+        assert_eq!(restarted.get_active_jobs()[0].ssh_user.ssh_key.as_deref(), Some(new_key));
+        std::fs::remove_file(&state).unwrap();
+        std::fs::remove_file(&socket).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_suspend_and_resume() {
+        let socket = std::env::temp_dir().join(format!("eryzaa-ssh-suspend-{}.sock", std::process::id()));
+        let state = std::env::temp_dir().join(format!("eryzaa-ssh-suspend-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&socket);
+        let listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let seen = Arc::clone(&requests);
+        let own_uid = unsafe { libc::geteuid() };
+        std::thread::spawn(move || {
+            let lock_fails = std::sync::atomic::AtomicBool::new(true);
+            service::serve(listener, vec![own_uid], move |request| {
+                seen.lock().unwrap().push(request.clone());
+                match request {
+                    service::Request::Suspend { .. } if lock_fails.swap(false, std::sync::atomic::Ordering::SeqCst) => {
+                        service::Response::Error(SshManagerError::PermissionDenied("usermod".to_string()))
+                    }
+                    service::Request::Certify { .. } => service::Response::Certificate("new certificate".to_string()),
+                    _ => service::Response::Done,
+                }
+            })
+        });
+
+        let key = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHuY0m1wZ8Wq7u2Yt4b5vE3s1nZkq2jF8p9dLxR0aB7c";
+        let grantee_key = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8g";
+        let manager = SshManager::with_state(SessionLimits::default(), &state).with_service_socket(&socket);
+        let access = JobAccess {
+            job_id: "job-a".to_string(),
+            client_id: "client-1".to_string(),
+            ssh_user: SshUser {
+                username: "job_aaaaaaaa".to_string(),
+                job_id: "job-a".to_string(),
+                created_at: chrono::Utc::now(),
+                is_active: true,
+                ssh_key: Some(key.to_string()),
+                certificate: Some("old certificate".to_string()),
+                isolation: IsolationLevel::default(),
+                policy: SshPolicy::default(),
+                sources: SourceRestriction::default(),
+                docker: DockerAccess::default(),
+            },
+            expires_at: chrono::Utc::now() + chrono::Duration::hours(1),
+            access_token: None,
+            grants: vec![Grant {
+                identity: "colleague@example.com".to_string(),
+                scope: AccessScope::Full,
+                public_key: grantee_key.to_string(),
+                fingerprint: grants::fingerprint(grantee_key).unwrap(),
+                granted_at: chrono::Utc::now(),
+                token_hash: None,
+            }],
+            mode: AccessMode::Shell,
+            disk_quota: None,
+            activity: None,
+            private_key: None,
+        };
+        manager.active_users.lock().unwrap().insert("job-a".to_string(), access);
+        let authorized = |request: &service::Request| match request {
+            service::Request::Authorize { owner_key, grants, .. } => Some((owner_key.is_some(), grants.len())),
+            _ => None,
+        };
+
+        // The lock fails: the keys come back and nothing is revoked
+        assert!(manager.suspend_job_user("job-a").await.is_err());
+        {
+            let requests = std::mem::take(&mut *requests.lock().unwrap());
+            assert_eq!(requests.len(), 3);
+            assert_eq!(authorized(&requests[0]), Some((false, 0)));
+            assert!(matches!(&requests[1], service::Request::Suspend { .. }));
+            assert_eq!(authorized(&requests[2]), Some((false, 1)));
+        }
+        assert!(manager.job_access("job-a").unwrap().ssh_user.is_active);
+        assert!(SshManager::with_state(SessionLimits::default(), &state).get_active_jobs()[0].ssh_user.is_active);
+
+        // No keys and no certificate while suspended, and a grant made meanwhile waits for the resume
+        let suspended = manager.suspend_job_user("job-a").await.unwrap();
+        assert!(!suspended.ssh_user.is_active);
+        {
+            let requests = std::mem::take(&mut *requests.lock().unwrap());
+            assert_eq!(authorized(&requests[0]), Some((false, 0)));
+            assert_eq!(requests[1], service::Request::Suspend { username: "job_aaaaaaaa".to_string() });
+            assert_eq!(requests[2], service::Request::Revoke { certificate: "old certificate".to_string() });
+            assert!(matches!(&requests[3], service::Request::EndSessions { .. }));
+        }
+        assert!(!SshManager::with_state(SessionLimits::default(), &state).get_active_jobs()[0].ssh_user.is_active);
+        assert!(manager.rotate_credentials("job-a", Some(grantee_key), false).is_err());
+        manager.grant_access("job-a", "auditor@example.com", AccessScope::Ssh, key, None).unwrap();
+        assert_eq!(authorized(&std::mem::take(&mut *requests.lock().unwrap())[0]), Some((false, 0)));
+
+        let resumed = manager.resume_job_user("job-a").await.unwrap();
+        assert!(resumed.ssh_user.is_active);
+        assert_eq!(resumed.ssh_user.certificate.as_deref(), Some("new certificate"));
+        {
+            let requests = std::mem::take(&mut *requests.lock().unwrap());
+            assert!(matches!(&requests[0], service::Request::Certify { public_key, .. } if public_key == key));
+            assert_eq!(requests[1], service::Request::Resume { username: "job_aaaaaaaa".to_string() });
+            assert_eq!(authorized(&requests[2]), Some((false, 2)));
+        }
+        let restarted = SshManager::with_state(SessionLimits::default(), &state);
+        assert_eq!(restarted.get_active_jobs()[0].ssh_user.certificate.as_deref(), Some("new certificate"));
+        assert!(manager.resume_job_user("job-a").await.is_err());
+        std::fs::remove_file(&state).unwrap();
+        std::fs::remove_file(&socket).unwrap();
+    }
+
+    #[test]
+    fn test_public_key_validation() {
+        let key = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHuY0m1wZ8Wq7u2Yt4b5vE3s1nZkq2jF8p9dLxR0aB7c alice@laptop";
+        assert_eq!(validate_public_key(&format!("  {}\n", key)).unwrap(), key);
+        assert!(validate_public_key("command=\"sh\" ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAA").is_err());
+        assert!(validate_public_key("ssh-ed25519 not-base64!").is_err());
+        assert!(validate_public_key(&format!("{}\n{}", key, key)).is_err());
     }
 }
@@ -0,0 +1,149 @@
+//! Access checks sshd runs at login
+//! A job user whose job ran out or was revoked may still exist until the reaper gets to it, and
+//! its authorized_keys stay valid until then. With the login check enabled sshd reads no key
+//! files for job users; it asks the `eryzaa-authorized-keys` helper, which reads the manager's
+//! state file and answers only for active jobs that have not expired. Certificates are checked
+//! the same way through their principals, and password logins through pam_exec.
+
+use std::path::{Path, PathBuf};
+
+use crate::accounts::{self, job_user_prefix};
+use crate::error::SshManagerError;
+use crate::JobAccess;
+
+/// Where the helper is installed; sshd only runs a command that root owns along its whole path
+pub const HELPER_PATH: &str = "/usr/local/libexec/eryzaa-authorized-keys";
+
+/// The helper built next to the running binary, which gets installed to `HELPER_PATH`
+pub fn bundled_helper() -> Result<PathBuf, SshManagerError> {
+    let exe = std::env::current_exe().map_err(|e| SshManagerError::Io(format!("Failed to find the running binary: {}", e)))?;
+    Ok(exe.with_file_name(format!("eryzaa-authorized-keys{}", std::env::consts::EXE_SUFFIX)))
+}
+
+/// The jobs saved in the state file at `path`; unlike `SshManager::with_state`, a file that cannot be read is an error
+pub fn read_state(path: &Path) -> Result<Vec<JobAccess>, SshManagerError> {
+    let content = std::fs::read_to_string(path).map_err(|e| SshManagerError::io("read SSH manager state", path, e))?;
+    serde_json::from_str(&content).map_err(|e| SshManagerError::Io(format!("Failed to parse SSH manager state {}: {}", path.display(), e)))
+}
+
+/// The job `username` signs in for, if it may sign in at `now`
+pub fn check_access<'a>(jobs: &'a [JobAccess], username: &str, now: chrono::DateTime<chrono::Utc>) -> Result<&'a JobAccess, SshManagerError> {
+    let access = jobs
+        .iter()
+        .find(|access| access.ssh_user.username == username)
+        .ok_or_else(|| SshManagerError::NotFound(format!("No job for '{}'", username)))?;
+    if !access.ssh_user.is_active {
+        return Err(SshManagerError::Unauthorized(format!("Access of '{}' to job '{}' was revoked", username, access.job_id)));
+    }
+    if access.expires_at <= now {
+        return Err(SshManagerError::Unauthorized(format!("Job '{}' of '{}' expired at {}", access.job_id, username, access.expires_at)));
+    }
+    Ok(access)
+}
+
+/// authorized_keys lines sshd may accept for `username` at `now`
+pub fn authorized_keys(jobs: &[JobAccess], username: &str, now: chrono::DateTime<chrono::Utc>) -> Result<Vec<String>, SshManagerError> {
+    let access = check_access(jobs, username, now)?;
+    // As when the keys are written: a certified owner key only works with its certificate
+    let owner_key = access.ssh_user.ssh_key.as_deref().filter(|_| access.ssh_user.certificate.is_none());
+    accounts::authorized_keys_lines(&access.job_id, owner_key, &access.grants, &access.ssh_user.sources)
+}
+
+/// Principals a certificate may name to sign in as `username` at `now`
+pub fn principals(jobs: &[JobAccess], username: &str, now: chrono::DateTime<chrono::Utc>) -> Result<Vec<String>, SshManagerError> {
+    check_access(jobs, username, now).map(|_| vec![username.to_string()])
+}
+
+/// Whether PAM may let `username` in at `now`; only job users are checked
+pub fn pam_allows(jobs: &[JobAccess], username: &str, now: chrono::DateTime<chrono::Utc>) -> bool {
+    !username.starts_with(job_user_prefix()) || check_access(jobs, username, now).is_ok()
+}
+
+/// sshd settings that send every job user's login through the helper, run as `run_as`
+///
+/// `run_as` must be able to read `state_path`, so it is normally the user the rental node runs as.
+pub fn sshd_settings(helper: &Path, state_path: &Path, run_as: &str) -> String {
+    let command = |mode: &str| format!("\"{}\" {} \"{}\" %u", helper.display(), mode, state_path.display());
+    format!(
+        "Match User {}*\n    AuthorizedKeysFile none\n    AuthorizedKeysCommand {}\n    AuthorizedKeysCommandUser {}\n    AuthorizedPrincipalsCommand {}\n    AuthorizedPrincipalsCommandUser {}\n",
+        job_user_prefix(),
+        command("keys"),
+        run_as,
+        command("principals"),
+        run_as
+    )
+}
+
+/// Check that `run_as` is a plain user name sshd can run the helper as
+pub fn validate_run_as(run_as: &str) -> Result<(), SshManagerError> {
+    let valid = !run_as.is_empty()
+        && run_as.len() <= 32
+        && !run_as.starts_with('-')
+        && run_as.bytes().all(|b| b.is_ascii_alphanumeric() || b"._-".contains(&b));
+    match valid {
+        true => Ok(()),
+        false => Err(SshManagerError::InvalidInput(format!("'{}' is not a user the login check can run as", run_as))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::{AccessMode, IsolationLevel, SshPolicy};
+    use crate::docker::DockerAccess;
+    use crate::sources::SourceRestriction;
+    use crate::SshUser;
+
+    #[test]
+    fn test_login_check() {
+        let now = chrono::Utc::now();
+        let key = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHuY0m1wZ8Wq7u2Yt4b5vE3s1nZkq2jF8p9dLxR0aB7c";
+        let access = |username: &str, expires_at, certificate: Option<&str>| JobAccess {
+            job_id: format!("job-{}", username),
+            client_id: "client-1".to_string(),
+            ssh_user: SshUser {
+                username: username.to_string(),
+                job_id: format!("job-{}", username),
+                created_at: now,
+                is_active: true,
+                ssh_key: Some(key.to_string()),
+                certificate: certificate.map(str::to_string),
+                isolation: IsolationLevel::default(),
+                policy: SshPolicy::default(),
+                sources: SourceRestriction::default(),
+                docker: DockerAccess::default(),
+            },
+            expires_at,
+            access_token: None,
+            grants: vec![],
+            mode: AccessMode::Shell,
+            disk_quota: None,
+            activity: None,
+            private_key: None,
+        };
+        let jobs = vec![
+            access("job_aaaaaaaa", now + chrono::Duration::hours(1), None),
+            access("job_bbbbbbbb", now - chrono::Duration::minutes(1), None),
+            access("job_cccccccc", now + chrono::Duration::hours(1), Some("ssh-ed25519-cert-v01@openssh.com AAAA")),
+        ];
+
+        assert_eq!(authorized_keys(&jobs, "job_aaaaaaaa", now).unwrap(), vec![key]);
+        assert!(matches!(authorized_keys(&jobs, "job_bbbbbbbb", now), Err(SshManagerError::Unauthorized(_))));
+        assert!(matches!(authorized_keys(&jobs, "job_zzzzzzzz", now), Err(SshManagerError::NotFound(_))));
+        // A certified key signs in through its certificate alone
+        assert!(authorized_keys(&jobs, "job_cccccccc", now).unwrap().is_empty());
+        let mut restricted = jobs.clone();
+        restricted[0].ssh_user.sources.addresses = vec!["10.147.17.5".to_string()];
+        assert_eq!(authorized_keys(&restricted, "job_aaaaaaaa", now).unwrap(), vec![format!("from=\"10.147.17.5\" {}", key)]);
+        restricted[0].ssh_user.sources.fingerprints = vec![format!("SHA256:{}", "A".repeat(43))];
+        assert!(authorized_keys(&restricted, "job_aaaaaaaa", now).unwrap().is_empty());
+        assert_eq!(principals(&jobs, "job_cccccccc", now).unwrap(), vec!["job_cccccccc"]);
+        assert!(principals(&jobs, "job_bbbbbbbb", now).is_err());
+        assert!(pam_allows(&jobs, "alice", now) && !pam_allows(&jobs, "job_bbbbbbbb", now));
+
+        let settings = sshd_settings(Path::new(HELPER_PATH), Path::new("/home/jo doe/.eryzaa/ssh_state.json"), "jo");
+        assert!(settings.starts_with("Match User job_*\n    AuthorizedKeysFile none\n"));
+        assert!(settings.contains("AuthorizedKeysCommand \"/usr/local/libexec/eryzaa-authorized-keys\" keys \"/home/jo doe/.eryzaa/ssh_state.json\" %u\n"));
+        assert!(validate_run_as("jo").is_ok() && validate_run_as("jo\nMatch all").is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! Disk quotas for job users
+//! A job user's home is capped so one renter cannot fill the owner's disk. Where the filesystem
+//! holding the home keeps project quotas, xfs or ext4 mounted with prjquota, the home becomes a
+//! project numbered after the user's uid with a hard block limit. Anywhere else the home is a
+//! loopback ext4 volume of the quota's size, its image kept in `VOLUME_DIR`. Either way statvfs
+//! on the home reports the quota, which is how usage is read. This module picks the method and
+//! reads usage; `accounts` sets quotas up on platforms that have them.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Where the images of loopback homes are kept
+pub const VOLUME_DIR: &str = "/var/lib/eryzaa/homes";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaMethod {
+    XfsProject,
+    Ext4Project,
+    LoopbackVolume,
+}
+
+impl QuotaMethod {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::XfsProject => "XFS project quota",
+            Self::Ext4Project => "ext4 project quota",
+            Self::LoopbackVolume => "Loopback volume",
+        }
+    }
+}
+
+/// A job user's disk quota, as `SshManager::get_active_jobs` reports it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DiskQuota {
+    pub limit_mb: u64,
+    pub method: QuotaMethod,
+    #[serde(default)]
+    pub used_mb: Option<u64>, // As of the last report; None when the home could not be read
+}
+
+/// A line of /proc/mounts
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mount {
+    pub point: PathBuf,
+    pub fs_type: String,
+    pub options: Vec<String>,
+}
+
+/// The mounts listed in `mounts`, the content of /proc/mounts
+pub fn parse_mounts(mounts: &str) -> Vec<Mount> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [_, point, fs_type, options, ..] = fields[..] else {
+                return None;
+            };
+            // Spaces in mount points are written as octal escapes
+            Some(Mount {
+                point: PathBuf::from(point.replace("\\040", " ").replace("\\011", "\t")),
+                fs_type: fs_type.to_string(),
+                options: options.split(',').map(str::to_string).collect(),
+            })
+        })
+        .collect()
+}
+
+/// The mount holding `path`; the last of several mounts on one point is the visible one
+pub fn mount_of<'a>(mounts: &'a [Mount], path: &Path) -> Option<&'a Mount> {
+    mounts
+        .iter()
+        .enumerate()
+        .filter(|(_, mount)| path.starts_with(&mount.point))
+        .max_by_key(|(index, mount)| (mount.point.components().count(), *index))
+        .map(|(_, mount)| mount)
+}
+
+/// How to cap the home at `home`, given `mounts`
+pub fn method_for(mounts: &[Mount], home: &Path) -> QuotaMethod {
+    let Some(mount) = mount_of(mounts, home) else {
+        return QuotaMethod::LoopbackVolume;
+    };
+    let has = |option: &str| mount.options.iter().any(|set| set == option);
+    match mount.fs_type.as_str() {
+        "xfs" if has("prjquota") || has("pquota") => QuotaMethod::XfsProject,
+        "ext4" if has("prjquota") => QuotaMethod::Ext4Project,
+        _ => QuotaMethod::LoopbackVolume,
+    }
+}
+
+/// Whether a volume is mounted on `home` itself
+pub fn is_mounted(mounts: &[Mount], home: &Path) -> bool {
+    mounts.iter().any(|mount| mount.point == home)
+}
+
+/// Image of `username`'s loopback home
+pub fn volume_image(username: &str) -> PathBuf {
+    Path::new(VOLUME_DIR).join(format!("{}.img", username))
+}
+
+/// MB in use on the filesystem holding `home`, which is the home's own usage under a quota
+#[cfg(unix)]
+pub fn used_mb(home: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(home.as_os_str().as_bytes()).ok()?;
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 {
+        return None;
+    }
+    let used_blocks = (stats.f_blocks as u64).saturating_sub(stats.f_bfree as u64);
+    Some(used_blocks * stats.f_frsize as u64 / (1024 * 1024))
+}
+
+#[cfg(not(unix))]
+pub fn used_mb(_home: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quota_method() {
+        let mounts = parse_mounts(
+            "/dev/sda1 / ext4 rw,relatime 0 0\n\
+             /dev/sdb1 /home xfs rw,relatime,attr2,inode64,prjquota 0 0\n\
+             /dev/sdc1 /srv/home\\040dirs ext4 rw,relatime,prjquota 0 0\n\
+             /dev/loop3 /home/job_ab12cd34 ext4 rw,nosuid,nodev,relatime 0 0\n",
+        );
+        assert_eq!(mounts[2].point, Path::new("/srv/home dirs"));
+        assert_eq!(method_for(&mounts, Path::new("/home/job_00000000")), QuotaMethod::XfsProject);
+        assert_eq!(method_for(&mounts, Path::new("/srv/home dirs/job_00000000")), QuotaMethod::Ext4Project);
+        assert_eq!(method_for(&mounts, Path::new("/var/home/job_00000000")), QuotaMethod::LoopbackVolume);
+
+        // A home already on its own volume keeps it
+        assert_eq!(method_for(&mounts, Path::new("/home/job_ab12cd34")), QuotaMethod::LoopbackVolume);
+        assert!(is_mounted(&mounts, Path::new("/home/job_ab12cd34")));
+        assert!(!is_mounted(&mounts, Path::new("/home/job_00000000")));
+        assert_eq!(volume_image("job_ab12cd34"), Path::new("/var/lib/eryzaa/homes/job_ab12cd34.img"));
+    }
+}
@@ -0,0 +1,89 @@
+//! Expiry reaper
+//! A background task that removes job users as their jobs expire and tells subscribers which
+//! jobs went, so no front end has to remember to call `cleanup_expired_users` itself. It wakes
+//! at the earliest expiry or every `interval`, whichever comes first, and early when a job is
+//! added or extended.
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, oneshot};
+use tokio::task::JoinHandle;
+use log::info;
+
+use crate::SshManager;
+
+/// Events a subscriber lagging this far behind misses
+const EVENT_BACKLOG: usize = 64;
+
+/// What the reaper did with an expired job
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReaperEvent {
+    Expired { job_id: String, username: String },
+    CleanupFailed { job_id: String, username: String, error: String }, // Retried on the next pass
+}
+
+/// Handle on a running reaper; the task stops once this is shut down or dropped
+pub struct Reaper {
+    events: broadcast::Sender<ReaperEvent>,
+    stop: Option<oneshot::Sender<()>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl Reaper {
+    /// Events from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<ReaperEvent> {
+        self.events.subscribe()
+    }
+
+    /// Stop the task, letting a cleanup pass in progress finish first
+    pub async fn shutdown(mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+    }
+}
+
+impl SshManager {
+    /// Spawn the expiry reaper on the current tokio runtime
+    pub fn start_reaper(self: &Arc<Self>, interval: Duration) -> Reaper {
+        let (events, _) = broadcast::channel(EVENT_BACKLOG);
+        let (stop, mut stopped) = oneshot::channel();
+        let manager = Arc::clone(self);
+        let sender = events.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                for event in manager.reap_expired().await {
+                    // Nobody listening is fine
+                    let _ = sender.send(event);
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(manager.expiry_wait(interval)) => {}
+                    _ = manager.expiry_changed.notified() => {}
+                    _ = &mut stopped => break,
+                }
+            }
+            info!("Expiry reaper stopped");
+        });
+        Reaper { events, stop: Some(stop), task: Some(task) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reaper_shutdown() {
+        let manager = Arc::new(SshManager::new());
+        assert_eq!(manager.expiry_wait(Duration::from_secs(30)), Duration::from_secs(30));
+
+        let reaper = manager.start_reaper(Duration::from_secs(3600));
+        let mut events = reaper.subscribe();
+        tokio::time::timeout(Duration::from_secs(5), reaper.shutdown()).await.unwrap();
+        // The sender went with the task, and nothing had expired
+        assert!(matches!(events.recv().await, Err(broadcast::error::RecvError::Closed)));
+    }
+}
@@ -0,0 +1,327 @@
+//! Privileged SSH service protocol
+//! The rental GUI runs unprivileged, so job users are created by `eryzaa-ssh-service`, which
+//! runs as root and listens on a Unix socket. Each connection carries one request and one
+//! response, both JSON behind a 4-byte big-endian length. The service checks the caller's uid
+//! through the socket's peer credentials and handles connections concurrently.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::accounts::{Credential, IsolationLevel, ResourceCaps, SshPolicy};
+use crate::activity::Connection;
+use crate::audit::AuditEvent;
+use crate::docker::DockerAccess;
+use crate::bandwidth::{BandwidthCaps, UserTraffic};
+use crate::error::SshManagerError;
+use crate::grants::Grant;
+use crate::quota::QuotaMethod;
+use crate::sources::SourceRestriction;
+
+/// Where the service listens; /run keeps other users from planting a socket first
+pub const SOCKET_PATH: &str = "/run/eryzaa/ssh-service.sock";
+
+/// Largest request or response; a public key is at most a few KiB
+const MAX_FRAME: u32 = 64 * 1024;
+
+/// Requests handled at once, beyond which callers are told to retry
+const MAX_CONNECTIONS: usize = 16;
+
+/// Reading a request from a connected caller
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Waiting for the service, which runs useradd and friends
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Request {
+    Create {
+        username: String,
+        credential: Credential,
+        #[serde(default)]
+        isolation: IsolationLevel,
+        #[serde(default)]
+        policy: SshPolicy,
+        #[serde(default)]
+        job_id: String, // Names the container of a container-only or proxied user
+        #[serde(default)]
+        docker: DockerAccess,
+    },
+    Policy {
+        username: String,
+        job_id: String,
+        isolation: IsolationLevel,
+        policy: SshPolicy,
+        #[serde(default)]
+        sources: SourceRestriction,
+    },
+    Remove { username: String },
+    Suspend { username: String }, // Lock out and end the sessions, keeping the home
+    Resume { username: String },
+    EndSessions { username: String }, // Close the user's logins, which stay allowed
+    Revoke { certificate: String }, // Void a certificate from the host CA before it runs out
+    Limit { username: String, caps: ResourceCaps },
+    List,
+    Audit, // sshd events about job users since the last audit request
+    Authorize {
+        username: String,
+        job_id: String,
+        owner_key: Option<String>,
+        grants: Vec<Grant>,
+        #[serde(default)]
+        sources: SourceRestriction,
+    },
+    Certify {
+        username: String,
+        public_key: String,
+        expires_at: chrono::DateTime<chrono::Utc>,
+        #[serde(default)]
+        sources: SourceRestriction,
+    },
+    Throttle { usernames: Vec<String>, caps: BandwidthCaps }, // Every job user, so the others' counters go
+    Traffic,
+    Connections, // Job users' open SSH connections
+    Quota { username: String, limit_mb: u64 },
+    LoginCheck { state_path: PathBuf, run_as: String }, // Have sshd ask the login helper about job users
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    Done,
+    Users(Vec<String>),
+    Events(Vec<AuditEvent>),
+    Certificate(String),
+    Traffic(Vec<UserTraffic>),
+    Connections(Vec<Connection>),
+    Quota(QuotaMethod),
+    Error(SshManagerError),
+}
+
+/// `message` as one length-prefixed frame
+fn encode_frame<T: Serialize>(message: &T) -> Result<Vec<u8>, SshManagerError> {
+    let body = serde_json::to_vec(message).map_err(|e| SshManagerError::Protocol(format!("Failed to serialize message: {}", e)))?;
+    let length = u32::try_from(body.len()).ok().filter(|length| *length <= MAX_FRAME);
+    let length = length.ok_or_else(|| SshManagerError::Protocol(format!("Message of {} bytes is too large", body.len())))?;
+    Ok([length.to_be_bytes().as_slice(), &body].concat())
+}
+
+/// Length of the frame whose header is `length`, if it is not too large
+fn frame_length(length: [u8; 4]) -> Result<usize, SshManagerError> {
+    let length = u32::from_be_bytes(length);
+    if length > MAX_FRAME {
+        return Err(SshManagerError::Protocol(format!("Message of {} bytes is too large", length)));
+    }
+    Ok(length as usize)
+}
+
+fn decode_body<T: DeserializeOwned>(body: &[u8]) -> Result<T, SshManagerError> {
+    serde_json::from_slice(body).map_err(|e| SshManagerError::Protocol(format!("Malformed message: {}", e)))
+}
+
+/// Write `message` as one length-prefixed frame
+pub fn write_frame<T: Serialize>(writer: &mut impl Write, message: &T) -> Result<(), SshManagerError> {
+    let frame = encode_frame(message)?;
+    writer
+        .write_all(&frame)
+        .and_then(|_| writer.flush())
+        .map_err(|e| SshManagerError::Protocol(format!("Failed to send message: {}", e)))
+}
+
+/// Read one length-prefixed frame
+pub fn read_frame<T: DeserializeOwned>(reader: &mut impl Read) -> Result<T, SshManagerError> {
+    let error = |e: std::io::Error| SshManagerError::Protocol(format!("Failed to read message: {}", e));
+    let mut length = [0; 4];
+    reader.read_exact(&mut length).map_err(error)?;
+    let mut body = vec![0; frame_length(length)?];
+    reader.read_exact(&mut body).map_err(error)?;
+    decode_body(&body)
+}
+
+/// Send `request` to the service listening at `socket_path`
+pub fn call(socket_path: &Path, request: &Request) -> Result<Response, SshManagerError> {
+    let mut stream = UnixStream::connect(socket_path)
+        .map_err(|e| SshManagerError::ServiceUnavailable(format!("SSH service at {} unreachable: {}", socket_path.display(), e)))?;
+    stream
+        .set_read_timeout(Some(RESPONSE_TIMEOUT))
+        .map_err(|e| SshManagerError::ServiceUnavailable(format!("Failed to set timeout: {}", e)))?;
+    write_frame(&mut stream, request)?;
+    read_frame(&mut stream)
+}
+
+/// Send `request` to the service listening at `socket_path` without blocking the runtime
+pub async fn call_async(socket_path: &Path, request: &Request) -> Result<Response, SshManagerError> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let exchange = async {
+        let mut stream = tokio::net::UnixStream::connect(socket_path)
+            .await
+            .map_err(|e| SshManagerError::ServiceUnavailable(format!("SSH service at {} unreachable: {}", socket_path.display(), e)))?;
+        let frame = encode_frame(request)?;
+        stream
+            .write_all(&frame)
+            .await
+            .map_err(|e| SshManagerError::Protocol(format!("Failed to send message: {}", e)))?;
+        let error = |e: std::io::Error| SshManagerError::Protocol(format!("Failed to read message: {}", e));
+        let mut length = [0; 4];
+        stream.read_exact(&mut length).await.map_err(error)?;
+        let mut body = vec![0; frame_length(length)?];
+        stream.read_exact(&mut body).await.map_err(error)?;
+        decode_body(&body)
+    };
+    tokio::time::timeout(RESPONSE_TIMEOUT, exchange)
+        .await
+        .unwrap_or_else(|_| Err(SshManagerError::ServiceUnavailable("SSH service did not answer in time".to_string())))
+}
+
+/// uid of the process at the other end of `stream`
+#[cfg(target_os = "linux")]
+pub fn peer_uid(stream: &UnixStream) -> Result<u32, SshManagerError> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut credentials = libc::ucred { pid: 0, uid: 0, gid: 0 };
+    let mut length = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut credentials as *mut _ as *mut libc::c_void,
+            &mut length,
+        )
+    };
+    if result != 0 {
+        return Err(SshManagerError::Protocol(format!(
+            "Failed to read peer credentials: {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+    Ok(credentials.uid)
+}
+
+/// uid of the process at the other end of `stream`
+#[cfg(not(target_os = "linux"))]
+pub fn peer_uid(stream: &UnixStream) -> Result<u32, SshManagerError> {
+    use std::os::unix::io::AsRawFd;
+
+    let (mut uid, mut gid) = (0, 0);
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+        return Err(SshManagerError::Protocol(format!(
+            "Failed to read peer credentials: {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+    Ok(uid)
+}
+
+/// Answer connections on `listener` from root and `allowed_uids`, each on its own thread
+pub fn serve<H>(listener: UnixListener, allowed_uids: Vec<u32>, handler: H)
+where
+    H: Fn(Request) -> Response + Send + Sync + 'static,
+{
+    let handler = Arc::new(handler);
+    let allowed_uids = Arc::new(allowed_uids);
+    let connections = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming().flatten() {
+        if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            connections.fetch_sub(1, Ordering::SeqCst);
+            let mut stream = stream;
+            let _ = write_frame(&mut stream, &Response::Error(SshManagerError::ServiceUnavailable("SSH service is busy, try again".to_string())));
+            continue;
+        }
+
+        let (handler, allowed_uids, connections) = (Arc::clone(&handler), Arc::clone(&allowed_uids), Arc::clone(&connections));
+        std::thread::spawn(move || {
+            if let Err(e) = respond(stream, &allowed_uids, handler.as_ref()) {
+                log::warn!("SSH service request failed: {}", e);
+            }
+            connections.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+}
+
+fn respond(mut stream: UnixStream, allowed_uids: &[u32], handler: &impl Fn(Request) -> Response) -> Result<(), SshManagerError> {
+    let uid = peer_uid(&stream)?;
+    if uid != 0 && !allowed_uids.contains(&uid) {
+        write_frame(&mut stream, &Response::Error(SshManagerError::Unauthorized(format!("uid {} may not manage SSH users", uid))))?;
+        return Err(SshManagerError::Unauthorized(format!("Refused a request from uid {}", uid)));
+    }
+
+    stream
+        .set_read_timeout(Some(REQUEST_TIMEOUT))
+        .map_err(|e| SshManagerError::Protocol(format!("Failed to set timeout: {}", e)))?;
+    let response = match read_frame(&mut stream) {
+        Ok(request) => handler(request),
+        Err(e) => Response::Error(e),
+    };
+    write_frame(&mut stream, &response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_socket_protocol() {
+        let path = std::env::temp_dir().join(format!("eryzaa-ssh-service-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let own_uid = unsafe { libc::geteuid() };
+        std::thread::spawn(move || {
+            serve(listener, vec![own_uid], |request| match request {
+                Request::List => Response::Users(vec!["job_1a2b3c4d".to_string()]),
+                Request::Remove { username } => {
+                    // Slow enough that the callers below overlap
+                    std::thread::sleep(Duration::from_millis(200));
+                    Response::Error(SshManagerError::NotFound(format!("No user {}", username)))
+                }
+                Request::Create { .. } | Request::Policy { .. } | Request::Suspend { .. } | Request::Resume { .. } | Request::EndSessions { .. } | Request::Revoke { .. } | Request::Limit { .. } | Request::Authorize { .. } | Request::Throttle { .. } | Request::LoginCheck { .. } => {
+                    Response::Done
+                }
+                Request::Traffic => Response::Traffic(vec![]),
+                Request::Connections => Response::Connections(vec![]),
+                Request::Quota { .. } => Response::Quota(QuotaMethod::LoopbackVolume),
+                Request::Audit => Response::Events(vec![]),
+                Request::Certify { username, .. } => Response::Certificate(format!("cert for {}", username)),
+            })
+        });
+
+        let started = std::time::Instant::now();
+        let callers: Vec<_> = (0..4)
+            .map(|_| {
+                let path = path.clone();
+                std::thread::spawn(move || call(&path, &Request::Remove { username: "job_00000000".to_string() }))
+            })
+            .collect();
+        for caller in callers {
+            assert_eq!(caller.join().unwrap(), Ok(Response::Error(SshManagerError::NotFound("No user job_00000000".to_string()))));
+        }
+        assert!(started.elapsed() < Duration::from_millis(700));
+        assert_eq!(call(&path, &Request::List), Ok(Response::Users(vec!["job_1a2b3c4d".to_string()])));
+
+        // A single-threaded runtime keeps ticking while the async call waits on the slow removal
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let (response, ticked) = runtime.block_on(async {
+            let ticker = tokio::spawn(async {
+                for _ in 0..5 {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            });
+            let response = call_async(&path, &Request::Remove { username: "job_00000000".to_string() }).await;
+            (response, ticker.is_finished())
+        });
+        assert_eq!(response, Ok(Response::Error(SshManagerError::NotFound("No user job_00000000".to_string()))));
+        assert!(ticked);
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream.write_all(&(MAX_FRAME + 1).to_be_bytes()).unwrap();
+        assert!(matches!(read_frame(&mut stream), Ok(Response::Error(SshManagerError::Protocol(e))) if e.contains("too large")));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
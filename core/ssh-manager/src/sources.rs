@@ -0,0 +1,166 @@
+//! Where a job's user may sign in from
+//! A client may pin their rental to the overlay addresses they connect from and to the keys they
+//! sign in with. Addresses go three ways, so a leaked password, key or certificate is no use
+//! from anywhere else: into a Match block of the user's sshd drop-in that turns every
+//! authentication method off for other addresses, into a `from=` option on each authorized key,
+//! and into the `source-address` option of the user's certificate. Fingerprints narrow the keys
+//! sshd accepts, which also rules out passwords.
+
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+use crate::error::SshManagerError;
+use crate::grants;
+
+/// Most addresses or fingerprints one restriction may list
+const MAX_ENTRIES: usize = 32;
+
+/// Addresses and keys a job's user is limited to; empty lists leave that side open
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SourceRestriction {
+    #[serde(default)]
+    pub addresses: Vec<String>, // IPs or CIDR ranges, as the overlay network assigns them
+    #[serde(default)]
+    pub fingerprints: Vec<String>, // "SHA256:...", as `ssh-keygen -l` prints them
+}
+
+impl SourceRestriction {
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty() && self.fingerprints.is_empty()
+    }
+
+    /// Check every entry, so none can break out of the sshd or authorized_keys line it goes in
+    pub fn validate(&self) -> Result<(), SshManagerError> {
+        if self.addresses.len() > MAX_ENTRIES || self.fingerprints.len() > MAX_ENTRIES {
+            return Err(SshManagerError::InvalidInput(format!("A restriction may list at most {} addresses and {} keys", MAX_ENTRIES, MAX_ENTRIES)));
+        }
+        for address in &self.addresses {
+            validate_address(address)?;
+        }
+        for fingerprint in &self.fingerprints {
+            validate_fingerprint(fingerprint)?;
+        }
+        Ok(())
+    }
+
+    /// Whether sshd may accept `key`, an authorized_keys line
+    pub fn allows_key(&self, key: &str) -> bool {
+        self.fingerprints.is_empty() || grants::fingerprint(key).is_some_and(|fingerprint| self.fingerprints.contains(&fingerprint))
+    }
+
+    /// `line` from authorized_keys, limited to the allowed addresses
+    pub fn restrict_line(&self, line: String) -> String {
+        if self.addresses.is_empty() {
+            return line;
+        }
+        let from = format!("from=\"{}\"", self.addresses.join(","));
+        // Lines with options of their own start with them rather than the key type
+        match crate::KEY_TYPES.iter().any(|key_type| line.starts_with(&format!("{} ", key_type))) {
+            true => format!("{} {}", from, line),
+            false => format!("{},{}", from, line),
+        }
+    }
+
+    /// The `source-address` list for the user's certificate, if addresses are restricted
+    pub fn source_address(&self) -> Option<String> {
+        (!self.addresses.is_empty()).then(|| self.addresses.join(","))
+    }
+
+    /// sshd Match blocks for `username`, to go before the user's own
+    ///
+    /// sshd takes the first value it finds for each keyword, so these win over the policy's.
+    pub fn sshd_match_blocks(&self, username: &str) -> Option<String> {
+        let mut blocks = String::new();
+        if !self.addresses.is_empty() {
+            let others: Vec<String> = self.addresses.iter().map(|address| format!("!{}", address)).collect();
+            blocks.push_str(&format!(
+                "Match User {} Address *,{}\n    PubkeyAuthentication no\n    PasswordAuthentication no\n    KbdInteractiveAuthentication no\n    HostbasedAuthentication no\n",
+                username,
+                others.join(",")
+            ));
+        }
+        if !self.fingerprints.is_empty() {
+            blocks.push_str(&format!("Match User {}\n    PasswordAuthentication no\n    KbdInteractiveAuthentication no\n", username));
+        }
+        (!blocks.is_empty()).then_some(blocks)
+    }
+
+    /// One line for the job's connection info
+    pub fn summary(&self) -> String {
+        match (self.addresses.is_empty(), self.fingerprints.is_empty()) {
+            (true, true) => "From anywhere, with any authorized key".to_string(),
+            (false, true) => format!("Only from {}", self.addresses.join(", ")),
+            (true, false) => format!("Only with keys {}", self.fingerprints.join(", ")),
+            (false, false) => format!("Only from {}, with keys {}", self.addresses.join(", "), self.fingerprints.join(", ")),
+        }
+    }
+}
+
+/// Check an IP address or CIDR range, which must not have host bits past its prefix
+fn validate_address(address: &str) -> Result<(), SshManagerError> {
+    let invalid = || SshManagerError::InvalidInput(format!("'{}' is not an IP address or CIDR range", address));
+    let (ip, prefix) = match address.split_once('/') {
+        Some((ip, prefix)) if prefix.bytes().all(|b| b.is_ascii_digit()) => (ip, Some(prefix.parse::<u32>().map_err(|_| invalid())?)),
+        Some(_) => return Err(invalid()),
+        None => (address, None),
+    };
+    let ip: IpAddr = ip.parse().map_err(|_| invalid())?;
+    let bits: u32 = if ip.is_ipv4() { 32 } else { 128 };
+    let Some(prefix) = prefix else {
+        return Ok(());
+    };
+    // sshd refuses ranges like 10.0.0.1/8 outright, which would lock the user out
+    let value = match ip {
+        IpAddr::V4(ip) => u32::from(ip) as u128,
+        IpAddr::V6(ip) => u128::from(ip),
+    };
+    if prefix > bits || (prefix < bits && value & (u128::MAX >> (128 - bits + prefix)) != 0) {
+        return Err(invalid());
+    }
+    Ok(())
+}
+
+/// Check a key fingerprint: "SHA256:" and 43 characters of unpadded base64
+fn validate_fingerprint(fingerprint: &str) -> Result<(), SshManagerError> {
+    let valid = fingerprint
+        .strip_prefix("SHA256:")
+        .is_some_and(|hash| hash.len() == 43 && hash.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/'));
+    match valid {
+        true => Ok(()),
+        false => Err(SshManagerError::InvalidInput(format!("'{}' is not a SHA256 key fingerprint", fingerprint))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_restriction() {
+        let key = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHuY0m1wZ8Wq7u2Yt4b5vE3s1nZkq2jF8p9dLxR0aB7c";
+        let fingerprint = grants::fingerprint(key).unwrap();
+        let restriction = SourceRestriction {
+            addresses: vec!["10.147.17.5".to_string(), "10.147.18.0/24".to_string(), "fd00::/8".to_string()],
+            fingerprints: vec![fingerprint.clone()],
+        };
+        assert!(restriction.validate().is_ok());
+        for address in ["10.147.17.5/33", "10.147.17.5/24", "host.example", "10.0.0.1,*", "*", "fd00::1/8", "10.0.0.0/+8"] {
+            let bad = SourceRestriction { addresses: vec![address.to_string()], ..Default::default() };
+            assert!(bad.validate().is_err(), "{}", address);
+        }
+        let bad = SourceRestriction { fingerprints: vec!["SHA256:abc\nMatch all".to_string()], ..Default::default() };
+        assert!(bad.validate().is_err());
+
+        assert!(restriction.allows_key(key));
+        assert!(!restriction.allows_key("ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIA8p9dLxR0aB7cHuY0m1wZ8Wq7u2Yt4b5vE3s1nZkq2j"));
+        assert!(SourceRestriction::default().allows_key(key));
+        assert_eq!(restriction.restrict_line(key.to_string()), format!("from=\"10.147.17.5,10.147.18.0/24,fd00::/8\" {}", key));
+        assert!(restriction.restrict_line(format!("restrict {}", key)).starts_with("from=\"10.147.17.5,10.147.18.0/24,fd00::/8\",restrict "));
+        assert_eq!(restriction.source_address().as_deref(), Some("10.147.17.5,10.147.18.0/24,fd00::/8"));
+
+        let blocks = restriction.sshd_match_blocks("job_ab12cd34").unwrap();
+        assert!(blocks.starts_with("Match User job_ab12cd34 Address *,!10.147.17.5,!10.147.18.0/24,!fd00::/8\n    PubkeyAuthentication no\n"));
+        assert!(blocks.ends_with("Match User job_ab12cd34\n    PasswordAuthentication no\n    KbdInteractiveAuthentication no\n"));
+        assert_eq!(SourceRestriction::default().sshd_match_blocks("job_ab12cd34"), None);
+    }
+}
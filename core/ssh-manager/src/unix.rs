@@ -0,0 +1,580 @@
+//! Job users on Linux and other Unix systems
+//! Accounts are written to the account files directly, see `userdb`, which takes root as the
+//! privileged SSH service has; the remaining system tools run through sudo unless we already run
+//! as root. Resource caps go on the systemd slice logind runs each of
+//! the user's sessions in, `user-<uid>.slice`, so they hold however the user signs in. Disk
+//! quotas are project quotas or loopback homes, see `quota`.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use sha2::{Digest, Sha256};
+
+use crate::accounts::{Credential, IsolationLevel, ResourceCaps, CHROOT_WORK_DIR, RESTRICTED_COMMANDS};
+use crate::bandwidth::{self, BandwidthCaps, UserTraffic, BANDWIDTH_TABLE};
+use crate::docker::{self, DockerAccess};
+use crate::error::SshManagerError;
+use crate::login;
+use crate::quota::{self, QuotaMethod, VOLUME_DIR};
+use crate::userdb::{self, AccountBackend, FileDatabase, UserEntry};
+
+/// Included by the stock sshd_config of current distributions
+const SSHD_DROP_IN_DIR: &str = "/etc/ssh/sshd_config.d";
+
+/// Where sshd finds job users' keys, owned by root so nothing a job user does can redirect writes
+const AUTHORIZED_KEYS_DIR: &str = "/etc/ssh/eryzaa-keys";
+
+/// `program` run as root: through sudo, unless we are root already
+pub fn privileged(program: &str) -> Command {
+    if is_root() {
+        return Command::new(program);
+    }
+    let mut command = Command::new("sudo");
+    command.arg(program);
+    command
+}
+
+fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// Run a privileged command, with `input` on its stdin
+fn run(program: &str, args: &[&str], input: Option<&str>) -> Result<(), SshManagerError> {
+    let mut child = privileged(program)
+        .args(args)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| SshManagerError::spawn(program, e))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        writeln!(stdin, "{}", input).map_err(|e| SshManagerError::Command(format!("Failed to write to {}: {}", program, e)))?;
+    }
+    let output = child.wait_with_output().map_err(|e| SshManagerError::spawn(program, e))?;
+    if !output.status.success() {
+        return Err(SshManagerError::from_command(program, &String::from_utf8_lossy(&output.stderr)));
+    }
+    Ok(())
+}
+
+/// Create a job user with a private home directory, signing in with `credential`
+///
+/// `match_block` holds the sshd settings that enforce `isolation` and the job's `SshPolicy`;
+/// `docker` is how the user of job `job_id` reaches a container engine.
+pub fn create_user(
+    username: &str,
+    credential: &Credential,
+    isolation: IsolationLevel,
+    match_block: Option<&str>,
+    docker: DockerAccess,
+    job_id: &str,
+) -> Result<(), SshManagerError> {
+    let shell = isolation.shell();
+    if !Path::new(shell).exists() {
+        return Err(SshManagerError::Unsupported(format!("{} is not installed, needed for {}", shell, isolation.label().to_lowercase())));
+    }
+    if match_block.is_some() && !Path::new(SSHD_DROP_IN_DIR).is_dir() {
+        return Err(SshManagerError::Unsupported(format!("sshd has no {} to restrict job users in", SSHD_DROP_IN_DIR)));
+    }
+    // Before the account exists, so no login ever reads keys from its home
+    restrict_key_files()?;
+    let entry = userdb::create_account(&FileDatabase::system(), username, credential, isolation, docker)
+        .map_err(|e| e.context("Failed to create user"))?;
+
+    let set_up = match credential {
+        Credential::PublicKey(key) => write_authorized_keys(username, key),
+        Credential::Password(_) | Credential::Certificate => Ok(()),
+    };
+    let set_up = set_up
+        .and_then(|_| isolate_user(username, isolation, match_block))
+        .and_then(|_| set_up_docker(&entry, job_id, docker.for_isolation(isolation)));
+    if set_up.is_err() {
+        let _ = delete_user(username);
+    }
+    set_up
+}
+
+/// sshd drop-in holding a job user's Match block
+fn user_drop_in(username: &str) -> String {
+    format!("{}/eryzaa-{}.conf", SSHD_DROP_IN_DIR, username)
+}
+
+/// Prepare the home directory for `isolation` and have sshd enforce it
+fn isolate_user(username: &str, isolation: IsolationLevel, match_block: Option<&str>) -> Result<(), SshManagerError> {
+    let home = format!("/home/{}", username);
+    let mut layout = home_layout(username, isolation).into_iter();
+    if let Some((_, owner, mode)) = layout.next() {
+        // Nothing the user owns outside the work directory, not even what useradd copied in
+        run("chown", &["-R", &format!("{}:{}", owner, owner), &home], None)
+            .and_then(|_| run("chmod", &[mode, &home], None))
+            .map_err(|e| e.context(&format!("Failed to hand {} to root", home)))?;
+    }
+    for (dir, owner, mode) in layout {
+        run("install", &["-d", "-m", mode, "-o", owner, "-g", owner, &dir], None).map_err(|e| e.context(&format!("Failed to create {}", dir)))?;
+    }
+    if isolation == IsolationLevel::RestrictedShell {
+        // rbash refuses to change PATH, so the tools linked here are all it can run
+        let bin = format!("{}/bin", home);
+        for command in RESTRICTED_COMMANDS {
+            if let Some(path) = ["/usr/bin", "/bin"].iter().map(|dir| format!("{}/{}", dir, command)).find(|path| Path::new(path).exists()) {
+                run("ln", &["-sf", &path, &format!("{}/{}", bin, command)], None)
+                    .map_err(|e| e.context(&format!("Failed to link {}", command)))?;
+            }
+        }
+        let profile = format!("{}/.bash_profile", home);
+        run("tee", &[&profile], Some("readonly PATH=$HOME/bin\nexport PATH"))
+            .map_err(|e| e.context(&format!("Failed to write {}", profile)))?;
+    }
+
+    match match_block {
+        Some(_) => write_match_block(username, match_block),
+        None => Ok(()),
+    }
+}
+
+/// Directories in the home of `username` for `isolation`, with owner and mode, the home itself first
+///
+/// A home the user could write to would let them swap the restricted shell's tools and profile,
+/// or break sshd's chroot, so they only get the work directory.
+fn home_layout(username: &str, isolation: IsolationLevel) -> Vec<(String, &str, &'static str)> {
+    let home = format!("/home/{}", username);
+    let mut layout = match isolation {
+        IsolationLevel::FullShell | IsolationLevel::ContainerOnly => return vec![],
+        IsolationLevel::RestrictedShell => vec![(home.clone(), "root", "755"), (format!("{}/bin", home), "root", "755")],
+        IsolationLevel::Chroot => vec![(home.clone(), "root", "755")],
+    };
+    layout.push((format!("{}/{}", home, CHROOT_WORK_DIR), username, "700"));
+    layout
+}
+
+/// Give the user the container engine `docker` stands for; the docker group is `userdb`'s
+fn set_up_docker(user: &UserEntry, job_id: &str, docker: DockerAccess) -> Result<(), SshManagerError> {
+    match docker {
+        DockerAccess::Group | DockerAccess::None => Ok(()),
+        DockerAccess::Proxy => {
+            let socket = docker::start_proxy(&user.name, job_id)?;
+            give_to(&user.name, &socket).and_then(|_| write_docker_context(&user.name, &socket))
+        }
+        DockerAccess::Rootless => {
+            let tool = find_program("dockerd-rootless-setuptool.sh", "rootless Docker")?;
+            add_subordinate_ids(user)?;
+            // The user's own systemd runs the engine, also while they are signed out
+            let runtime_dir = format!("/run/user/{}", user.uid);
+            run("loginctl", &["enable-linger", &user.name], None)
+                .and_then(|_| run("systemctl", &["start", &format!("user@{}.service", user.uid)], None))
+                .and_then(|_| run("runuser", &["-l", &user.name, "-c", &format!("XDG_RUNTIME_DIR={} {} install", runtime_dir, tool)], None))
+                .map_err(|e| e.context("Failed to set up rootless Docker"))
+        }
+        DockerAccess::Podman => {
+            find_program("podman", "rootless Podman")?;
+            add_subordinate_ids(user)
+        }
+    }
+}
+
+/// Path of `program` in the usual places, for `purpose`
+fn find_program(program: &str, purpose: &str) -> Result<String, SshManagerError> {
+    ["/usr/bin", "/usr/local/bin", "/bin"]
+        .iter()
+        .map(|dir| format!("{}/{}", dir, program))
+        .find(|path| Path::new(path).exists())
+        .ok_or_else(|| SshManagerError::Unsupported(format!("{} is not installed, needed for {}", program, purpose)))
+}
+
+/// Give the user 65536 subordinate uids and gids of its own, which rootless engines map containers to
+///
+/// Ranges follow from the uid, so no two users share one; userdel takes them away again.
+fn add_subordinate_ids(user: &UserEntry) -> Result<(), SshManagerError> {
+    if user.uid >= 65536 {
+        return Err(SshManagerError::Unsupported(format!("uid {} has no room for subordinate ids", user.uid)));
+    }
+    let start = user.uid as u64 * 65536;
+    let range = format!("{}-{}", start, start + 65535);
+    run("usermod", &["--add-subuids", &range, "--add-subgids", &range, &user.name], None)
+        .map_err(|e| e.context("Failed to add subordinate ids"))
+}
+
+/// Make `path` the user's
+pub(crate) fn give_to(username: &str, path: &Path) -> Result<(), SshManagerError> {
+    let path = path.to_string_lossy();
+    run("chown", &[&format!("{}:{}", username, username), &path], None).map_err(|e| e.context(&format!("Failed to hand {} over", path)))
+}
+
+/// Point the user's docker CLI at their proxy socket through a context of its own
+fn write_docker_context(username: &str, socket: &Path) -> Result<(), SshManagerError> {
+    let docker_dir = format!("/home/{}/.docker", username);
+    let hash: String = Sha256::digest(docker::PROXY_CONTEXT.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect();
+    let meta_dir = format!("{}/contexts/meta/{}", docker_dir, hash);
+    let meta = serde_json::json!({
+        "Name": docker::PROXY_CONTEXT,
+        "Metadata": {},
+        "Endpoints": { "docker": { "Host": format!("unix://{}", socket.display()), "SkipTLSVerify": false } },
+    });
+    let config = serde_json::json!({ "currentContext": docker::PROXY_CONTEXT });
+    for dir in [docker_dir.clone(), format!("{}/contexts", docker_dir), format!("{}/contexts/meta", docker_dir), meta_dir.clone()] {
+        run("install", &["-d", "-m", "700", "-o", username, "-g", username, &dir], None)?;
+    }
+    for (path, content) in [(format!("{}/meta.json", meta_dir), meta), (format!("{}/config.json", docker_dir), config)] {
+        run("tee", &[&path], Some(&content.to_string()))
+            .and_then(|_| give_to(username, Path::new(&path)))
+            .map_err(|e| e.context(&format!("Failed to write {}", path)))?;
+    }
+    Ok(())
+}
+
+/// Put `match_block` in the user's sshd drop-in, or remove the drop-in without one, and reload sshd
+pub fn write_match_block(username: &str, match_block: Option<&str>) -> Result<(), SshManagerError> {
+    let drop_in = user_drop_in(username);
+    match match_block {
+        Some(match_block) => {
+            if !Path::new(SSHD_DROP_IN_DIR).is_dir() {
+                return Err(SshManagerError::Unsupported(format!("sshd has no {} to restrict job users in", SSHD_DROP_IN_DIR)));
+            }
+            run("tee", &[&drop_in], Some(match_block)).map_err(|e| e.context(&format!("Failed to write {}", drop_in)))?;
+        }
+        None if Path::new(&drop_in).exists() => {
+            run("rm", &["-f", &drop_in], None).map_err(|e| e.context(&format!("Failed to remove {}", drop_in)))?;
+        }
+        None => return Ok(()),
+    }
+    reload_sshd()
+}
+
+/// Debian names the unit ssh, most others sshd
+fn reload_sshd() -> Result<(), SshManagerError> {
+    run("systemctl", &["reload", "ssh"], None)
+        .or_else(|_| run("systemctl", &["reload", "sshd"], None))
+        .map_err(|e| e.context("Failed to reload sshd"))
+}
+
+/// Replace the user's authorized_keys with `content`
+///
+/// The keys live in a root-owned directory rather than the user's home, where a symlink the user
+/// planted would have root write through it. The new file is written next to the old one and
+/// renamed over it, so sshd reads either the old keys or the new ones, never a mix or a
+/// half-written file.
+pub fn write_authorized_keys(username: &str, content: &str) -> Result<(), SshManagerError> {
+    let authorized_keys = format!("{}/{}", AUTHORIZED_KEYS_DIR, username);
+    let staged = format!("{}.new", authorized_keys);
+
+    let steps: [(&str, &str, Vec<&str>, Option<&str>); 4] = [
+        ("create the keys directory", "install", vec!["-d", "-m", "755", "-o", "root", "-g", "root", AUTHORIZED_KEYS_DIR], None),
+        ("write authorized_keys", "tee", vec![&staged], Some(content)),
+        ("secure authorized_keys", "chmod", vec!["644", &staged], None),
+        ("replace authorized_keys", "mv", vec!["-f", &staged, &authorized_keys], None),
+    ];
+    for (step, program, args, input) in steps {
+        run(program, &args, input).map_err(|e| e.context(&format!("Failed to {}", step)))?;
+    }
+    restrict_key_files()
+}
+
+/// Have sshd read job users' keys from their root-owned file alone
+///
+/// A job user can write ~/.ssh/authorized_keys, and a key put there would outlast rotations,
+/// source restrictions and revoked grants. Other users keep the distribution's setting.
+fn restrict_key_files() -> Result<(), SshManagerError> {
+    write_sshd_setting("eryzaa-keys.conf", &keys_drop_in(crate::accounts::job_user_prefix()))
+}
+
+/// sshd drop-in pointing users named with `prefix` at their file in `AUTHORIZED_KEYS_DIR`
+fn keys_drop_in(prefix: &str) -> String {
+    format!("Match User {}*\n    AuthorizedKeysFile {}/%u", prefix, AUTHORIZED_KEYS_DIR)
+}
+
+/// Have sshd trust the CA key at `ca_public_key` through a drop-in, reloading it when that changes
+pub fn trust_user_ca(ca_public_key: &Path) -> Result<(), SshManagerError> {
+    write_sshd_setting("eryzaa-ca.conf", &format!("TrustedUserCAKeys {}", ca_public_key.display()))
+}
+
+/// Have sshd refuse the keys and certificates in the revocation list at `revoked_keys`
+pub fn use_revoked_keys(revoked_keys: &Path) -> Result<(), SshManagerError> {
+    write_sshd_setting("eryzaa-revoked.conf", &format!("RevokedKeys {}", revoked_keys.display()))
+}
+
+/// Put `setting` alone in the drop-in `file`, reloading sshd when that changes
+fn write_sshd_setting(file: &str, setting: &str) -> Result<(), SshManagerError> {
+    if !Path::new(SSHD_DROP_IN_DIR).is_dir() {
+        return Err(SshManagerError::Unsupported(format!(
+            "sshd has no {}; add '{}' to /etc/ssh/sshd_config",
+            SSHD_DROP_IN_DIR, setting
+        )));
+    }
+    let drop_in = format!("{}/{}", SSHD_DROP_IN_DIR, file);
+    if std::fs::read_to_string(&drop_in).is_ok_and(|content| content.trim() == setting) {
+        return Ok(());
+    }
+    run("tee", &[&drop_in], Some(setting)).map_err(|e| e.context(&format!("Failed to write {}", drop_in)))?;
+    reload_sshd()
+}
+
+/// Install `helper` where sshd runs it from and put `settings` in the login check drop-in
+pub fn enable_login_check(helper: &Path, settings: &str) -> Result<(), SshManagerError> {
+    if !Path::new(SSHD_DROP_IN_DIR).is_dir() {
+        return Err(SshManagerError::Unsupported(format!("sshd has no {}; add the login check to /etc/ssh/sshd_config by hand", SSHD_DROP_IN_DIR)));
+    }
+    let helper = helper.to_string_lossy();
+    run("install", &["-D", "-m", "755", "-o", "root", "-g", "root", &helper, login::HELPER_PATH], None)
+        .map_err(|e| e.context(&format!("Failed to install {}", login::HELPER_PATH)))?;
+    let drop_in = format!("{}/eryzaa-login.conf", SSHD_DROP_IN_DIR);
+    if std::fs::read_to_string(&drop_in).is_ok_and(|content| content.trim() == settings.trim()) {
+        return Ok(());
+    }
+    run("tee", &[&drop_in], Some(settings.trim_end())).map_err(|e| e.context(&format!("Failed to write {}", drop_in)))?;
+    reload_sshd()
+}
+
+/// The uid of `username`
+fn uid(username: &str) -> Result<u32, SshManagerError> {
+    FileDatabase::system()
+        .lookup(username)?
+        .map(|entry| entry.uid)
+        .ok_or_else(|| SshManagerError::NotFound(format!("No local user '{}'", username)))
+}
+
+/// The slice holding every session of `username`
+fn user_slice(username: &str) -> Result<String, SshManagerError> {
+    Ok(format!("user-{}.slice", uid(username)?))
+}
+
+/// Cap the user's slice; the caps last until reboot, when reconciling applies them again
+pub fn limit_user(username: &str, caps: &ResourceCaps) -> Result<(), SshManagerError> {
+    let slice = user_slice(username)?;
+    if caps.is_empty() {
+        return run("systemctl", &["revert", &slice], None).map_err(|e| e.context(&format!("Failed to lift limits on {}", slice)));
+    }
+    let properties = caps.slice_properties();
+    let mut args = vec!["set-property", "--runtime", slice.as_str()];
+    args.extend(properties.iter().map(String::as_str));
+    run("systemctl", &args, None).map_err(|e| e.context(&format!("Failed to limit {}", slice)))
+}
+
+/// Cap the home of `username` at `limit_mb`, by whichever means its filesystem allows
+///
+/// A loopback home keeps the size it was made with; applying again only mounts it after a reboot.
+pub fn quota_user(username: &str, limit_mb: u64) -> Result<QuotaMethod, SshManagerError> {
+    let home = format!("/home/{}", username);
+    let mounts = read_mounts()?;
+    let method = quota::method_for(&mounts, Path::new(&home));
+    let mount_point = quota::mount_of(&mounts, Path::new(&home)).map(|mount| mount.point.to_string_lossy().to_string());
+    let project = uid(username)?.to_string();
+    match (method, mount_point) {
+        (QuotaMethod::XfsProject, Some(mount_point)) => {
+            let project_dir = format!("project -s -p {} {}", home, project);
+            let limit = format!("limit -p bhard={}m {}", limit_mb, project);
+            run("xfs_quota", &["-x", "-c", &project_dir, &mount_point], None)
+                .and_then(|_| run("xfs_quota", &["-x", "-c", &limit, &mount_point], None))
+                .map_err(|e| e.context(&format!("Failed to set the quota of {}", home)))?;
+        }
+        (QuotaMethod::Ext4Project, Some(mount_point)) => {
+            let kb = (limit_mb * 1024).to_string();
+            run("chattr", &["-R", "-p", &project, "+P", &home], None)
+                .and_then(|_| run("setquota", &["-P", &project, "0", &kb, "0", "0", &mount_point], None))
+                .map_err(|e| e.context(&format!("Failed to set the quota of {}", home)))?;
+        }
+        _ if quota::is_mounted(&mounts, Path::new(&home)) => {}
+        _ => mount_volume(username, &home, limit_mb)?,
+    }
+    Ok(method)
+}
+
+fn read_mounts() -> Result<Vec<quota::Mount>, SshManagerError> {
+    let mounts = std::fs::read_to_string("/proc/mounts").map_err(|e| SshManagerError::io("read", Path::new("/proc/mounts"), e))?;
+    Ok(quota::parse_mounts(&mounts))
+}
+
+/// Mount the user's loopback volume on its home, first making it from the home as it is
+fn mount_volume(username: &str, home: &str, limit_mb: u64) -> Result<(), SshManagerError> {
+    let image = quota::volume_image(username).to_string_lossy().to_string();
+    if !Path::new(&image).exists() {
+        run("install", &["-d", "-m", "700", VOLUME_DIR], None).map_err(|e| e.context(&format!("Failed to create {}", VOLUME_DIR)))?;
+        // Built under another name, so a volume left half made is made again
+        let staging = format!("{}.new", image);
+        let filling = format!("{}/{}.fill", VOLUME_DIR, username);
+        let made = make_volume(home, &staging, &filling, limit_mb);
+        if made.is_err() {
+            let _ = run("umount", &[&filling], None);
+            let _ = run("rm", &["-rf", &staging, &filling], None);
+        }
+        made.and_then(|_| run("rmdir", &[&filling], None))
+            .and_then(|_| run("mv", &[&staging, &image], None))
+            .map_err(|e| e.context(&format!("Failed to make the volume of {}", home)))?;
+    }
+    run("mount", &["-o", "loop,nosuid,nodev", &image, home], None).map_err(|e| e.context(&format!("Failed to mount {}", image)))
+}
+
+/// Format a sparse `image` of `limit_mb` and copy `home` into it through `filling`
+fn make_volume(home: &str, image: &str, filling: &str, limit_mb: u64) -> Result<(), SshManagerError> {
+    run("truncate", &["-s", &format!("{}M", limit_mb), image], None)?;
+    // Nothing reserved for root, the user gets all of it
+    run("mkfs.ext4", &["-q", "-F", "-m", "0", image], None)?;
+    run("install", &["-d", "-m", "700", filling], None)?;
+    run("mount", &["-o", "loop", image, filling], None)?;
+    // The copy takes the home's owner and mode along, which a chrooted home depends on
+    let copied = run("rm", &["-rf", &format!("{}/lost+found", filling)], None)
+        .and_then(|_| run("cp", &["-a", &format!("{}/.", home), filling], None));
+    run("umount", &[filling], None).and(copied)
+}
+
+/// Lift the user's disk quota, unmounting and deleting a loopback home
+fn release_quota(username: &str) {
+    let home = format!("/home/{}", username);
+    let Ok(mounts) = read_mounts() else {
+        return;
+    };
+    if quota::is_mounted(&mounts, Path::new(&home)) {
+        let _ = run("umount", &[&home], None);
+    }
+    let image = quota::volume_image(username);
+    if image.exists() {
+        let _ = run("rm", &["-f", &image.to_string_lossy()], None);
+    }
+    // The next user given this uid, which numbers the project, must not inherit the limit
+    let (Ok(project), Some(mount)) = (uid(username), quota::mount_of(&mounts, Path::new(&home))) else {
+        return;
+    };
+    let (project, mount_point) = (project.to_string(), mount.point.to_string_lossy().to_string());
+    let _ = match quota::method_for(&mounts, Path::new(&home)) {
+        QuotaMethod::XfsProject => run("xfs_quota", &["-x", "-c", &format!("limit -p bhard=0 {}", project), &mount_point], None),
+        QuotaMethod::Ext4Project => run("setquota", &["-P", &project, "0", "0", "0", "0", &mount_point], None),
+        QuotaMethod::LoopbackVolume => Ok(()),
+    };
+}
+
+/// Put `caps` on the traffic of each of `usernames` and count it, see `bandwidth`
+pub fn throttle_users(usernames: &[String], caps: BandwidthCaps) -> Result<(), SshManagerError> {
+    let mut users = vec![];
+    for username in usernames {
+        users.push((username.clone(), uid(username)?));
+    }
+    // Before the first run there is no table to list
+    let stale: Vec<String> = user_traffic()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|traffic| traffic.username)
+        .filter(|username| !usernames.contains(username))
+        .collect();
+    run("nft", &["-f", "-"], Some(&bandwidth::ruleset(&users, caps, &stale))).map_err(|e| e.context("Failed to limit bandwidth"))
+}
+
+/// Traffic counted for each job user so far
+pub fn user_traffic() -> Result<Vec<UserTraffic>, SshManagerError> {
+    let output = privileged("nft")
+        .args(["list", "counters", "table", "inet", BANDWIDTH_TABLE])
+        .output()
+        .map_err(|e| SshManagerError::spawn("nft", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(SshManagerError::from_command("nft", &stderr).context("Failed to read traffic counters"));
+    }
+    Ok(bandwidth::parse_counters(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Whether logind keeps the user's systemd running without a session, as for a rootless engine
+fn lingering(username: &str) -> bool {
+    Path::new(&format!("/var/lib/systemd/linger/{}", username)).exists()
+}
+
+/// Lock a job user out and end its sessions, keeping its home and what it runs for the job
+///
+/// A lingering user's systemd runs the job's rootless engine, so only its logind sessions and
+/// sshd processes go; any other user loses all its processes, as when deleted.
+pub fn suspend_user(username: &str) -> Result<(), SshManagerError> {
+    FileDatabase::system().set_locked(username, true).map_err(|e| e.context("Failed to lock user"))?;
+    end_sessions(username)
+}
+
+/// End a job user's logins, leaving a lingering user's rootless engine running; see `suspend_user`
+pub fn end_sessions(username: &str) -> Result<(), SshManagerError> {
+    if !lingering(username) {
+        let _ = run("loginctl", &["terminate-user", username], None);
+        let _ = run("pkill", &["-KILL", "-u", username], None);
+        return Ok(());
+    }
+    if let Ok(output) = privileged("loginctl").args(["list-sessions", "--no-legend"]).output() {
+        // SESSION UID USER SEAT TTY
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() >= 3 && fields[2] == username {
+                let _ = run("loginctl", &["terminate-session", fields[0]], None);
+            }
+        }
+    }
+    let _ = run("pkill", &["-KILL", "-u", username, "^sshd"], None);
+    Ok(())
+}
+
+/// Let a suspended job user sign in again
+pub fn resume_user(username: &str) -> Result<(), SshManagerError> {
+    FileDatabase::system().set_locked(username, false).map_err(|e| e.context("Failed to unlock user"))
+}
+
+/// Stop a job user's processes and delete the user with its home directory
+pub fn delete_user(username: &str) -> Result<(), SshManagerError> {
+    // A rootless engine keeps the user's systemd running, which would start it again
+    if lingering(username) {
+        let _ = run("loginctl", &["disable-linger", username], None);
+        let _ = run("loginctl", &["terminate-user", username], None);
+    }
+    docker::stop_proxy(username);
+    // Kill any processes owned by the user
+    let _ = run("pkill", &["-u", username], None);
+    // The next user given this uid must not inherit the caps
+    if let Ok(slice) = user_slice(username) {
+        let _ = run("systemctl", &["revert", &slice], None);
+    }
+    // Restrictions must not carry over to a later user of the same name
+    let drop_in = user_drop_in(username);
+    if Path::new(&drop_in).exists() {
+        let _ = run("rm", &["-f", &drop_in], None).and_then(|_| reload_sshd());
+    }
+    // Nor may its keys
+    let _ = run("rm", &["-f", &format!("{}/{}", AUTHORIZED_KEYS_DIR, username)], None);
+    // A loopback home is unmounted before the home under it can go
+    release_quota(username);
+    FileDatabase::system().remove_user(username).map_err(|e| e.context("Failed to delete user"))
+}
+
+/// Every user in /etc/passwd
+pub fn local_users() -> Result<Vec<String>, SshManagerError> {
+    FileDatabase::system().users()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::SshPolicy;
+
+    #[test]
+    fn test_job_users_take_keys_from_root_owned_files_only() {
+        let drop_in = keys_drop_in("job_");
+        assert_eq!(drop_in, "Match User job_*\n    AuthorizedKeysFile /etc/ssh/eryzaa-keys/%u");
+        // Only ever inside the Match block, and never the user's own file
+        assert!(drop_in.starts_with("Match User job_*\n"));
+        assert!(!drop_in.contains(".ssh/authorized_keys"));
+    }
+
+    #[test]
+    fn test_isolated_homes_are_root_owned() {
+        assert!(home_layout("job_1a2b3c4d", IsolationLevel::FullShell).is_empty());
+        assert_eq!(
+            home_layout("job_1a2b3c4d", IsolationLevel::RestrictedShell),
+            vec![
+                ("/home/job_1a2b3c4d".to_string(), "root", "755"),
+                ("/home/job_1a2b3c4d/bin".to_string(), "root", "755"),
+                ("/home/job_1a2b3c4d/work".to_string(), "job_1a2b3c4d", "700"),
+            ]
+        );
+        for isolation in [IsolationLevel::RestrictedShell, IsolationLevel::Chroot] {
+            let layout = home_layout("job_1a2b3c4d", isolation);
+            assert_eq!(layout[0], ("/home/job_1a2b3c4d".to_string(), "root", "755"));
+            // The user's only writable place is the work directory
+            let owned: Vec<_> = layout.iter().filter(|(_, owner, _)| *owner != "root").map(|(dir, _, _)| dir.as_str()).collect();
+            assert_eq!(owned, ["/home/job_1a2b3c4d/work"]);
+        }
+
+        let block = IsolationLevel::RestrictedShell.sshd_match_block("job_1a2b3c4d", "train-1", &SshPolicy::default()).unwrap().unwrap();
+        assert_eq!(block, "Match User job_1a2b3c4d\n    SetEnv PATH=/home/job_1a2b3c4d/bin\n    DisableForwarding yes\n");
+    }
+}
@@ -0,0 +1,610 @@
+//! Local account database
+//! Job users are written to /etc/passwd, /etc/shadow and /etc/group directly, under the shadow
+//! suite's lock, instead of through useradd, chpasswd, usermod and userdel. Failures come back as
+//! typed errors rather than parsed stderr, nothing depends on PATH or sudo, and account setup
+//! goes through `AccountBackend`, so it can be exercised against a mock.
+
+use std::ffi::{CStr, CString};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use log::warn;
+
+use crate::accounts::{users_in_passwd, Credential, IsolationLevel};
+use crate::docker::DockerAccess;
+use crate::error::SshManagerError;
+
+/// Range of uids given to job users, login.defs' defaults
+const UID_MIN: u32 = 1000;
+const UID_MAX: u32 = 60000;
+
+/// Shadow password that matches no password but, unlike a locked account, still lets sshd accept keys
+pub const NO_PASSWORD: &str = "*";
+
+/// Keeps our own threads from interleaving edits; `lckpwdf` keeps other tools out
+static EDIT_LOCK: Mutex<()> = Mutex::new(());
+
+/// crypt(3) hashes into a static buffer
+static CRYPT_LOCK: Mutex<()> = Mutex::new(());
+
+#[cfg(target_os = "linux")]
+extern "C" {
+    fn lckpwdf() -> libc::c_int;
+    fn ulckpwdf() -> libc::c_int;
+}
+
+#[cfg_attr(target_os = "linux", link(name = "crypt"))]
+extern "C" {
+    fn crypt(key: *const libc::c_char, salt: *const libc::c_char) -> *mut libc::c_char;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserEntry {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub home: PathBuf,
+    pub shell: String,
+}
+
+impl UserEntry {
+    /// A passwd line: name:password:uid:gid:gecos:home:shell
+    fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields.len() != 7 {
+            return None;
+        }
+        Some(Self {
+            name: fields[0].to_string(),
+            uid: fields[2].parse().ok()?,
+            gid: fields[3].parse().ok()?,
+            home: PathBuf::from(fields[5]),
+            shell: fields[6].to_string(),
+        })
+    }
+}
+
+/// What managing job users needs of the account database
+pub trait AccountBackend {
+    fn lookup(&self, username: &str) -> Result<Option<UserEntry>, SshManagerError>;
+
+    fn users(&self) -> Result<Vec<String>, SshManagerError>;
+
+    /// Add `username` with a private group of the same name and a home only it can enter
+    fn add_user(&self, username: &str, shell: &str) -> Result<UserEntry, SshManagerError>;
+
+    /// Set the shadow password: a crypt hash, or `NO_PASSWORD`
+    fn set_password_hash(&self, username: &str, hash: &str) -> Result<(), SshManagerError>;
+
+    fn add_to_group(&self, username: &str, group: &str) -> Result<(), SshManagerError>;
+
+    /// Lock or unlock the account, keeping its home; see `lock_shadow_entry`
+    fn set_locked(&self, username: &str, locked: bool) -> Result<(), SshManagerError>;
+
+    /// Remove the user, its private group, its memberships and its home
+    fn remove_user(&self, username: &str) -> Result<(), SshManagerError>;
+}
+
+/// Add a job user signing in with `credential`, taking the account away again if that fails
+///
+/// Keys and certificates need no password, so those accounts get `NO_PASSWORD`. Joining the
+/// docker group, for users with `DockerAccess::Group`, is best effort: a node without Docker
+/// still takes SSH jobs.
+pub fn create_account(
+    backend: &dyn AccountBackend,
+    username: &str,
+    credential: &Credential,
+    isolation: IsolationLevel,
+    docker: DockerAccess,
+) -> Result<UserEntry, SshManagerError> {
+    if backend.lookup(username)?.is_some() {
+        return Err(SshManagerError::AlreadyExists(format!("User '{}' already exists", username)));
+    }
+    let entry = backend.add_user(username, isolation.shell())?;
+
+    let hash = match credential {
+        Credential::Password(password) => hash_password(password),
+        Credential::PublicKey(_) | Credential::Certificate => Ok(NO_PASSWORD.to_string()),
+    };
+    if let Err(e) = hash.and_then(|hash| backend.set_password_hash(username, &hash)) {
+        let _ = backend.remove_user(username);
+        return Err(e.context("Failed to set password"));
+    }
+
+    if docker.for_isolation(isolation) == DockerAccess::Group {
+        if let Err(e) = backend.add_to_group(username, "docker") {
+            warn!("Failed to add user to docker group: {}", e);
+        }
+    }
+    Ok(entry)
+}
+
+/// SHA-512 crypt hash of `password`, the scheme chpasswd uses by default
+pub fn hash_password(password: &str) -> Result<String, SshManagerError> {
+    const SALT_CHARS: &[u8] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+    let salt: String = uuid::Uuid::new_v4()
+        .as_bytes()
+        .iter()
+        .map(|byte| SALT_CHARS[(*byte % 64) as usize] as char)
+        .collect();
+    let key = CString::new(password).map_err(|_| SshManagerError::InvalidInput("Password contains a NUL byte".to_string()))?;
+    let setting = CString::new(format!("$6${}$", salt)).expect("salt has no NUL bytes");
+
+    let hash = {
+        let _guard = CRYPT_LOCK.lock().unwrap();
+        // SAFETY: both arguments are NUL-terminated; the result is copied out before the lock is released
+        unsafe {
+            let hash = crypt(key.as_ptr(), setting.as_ptr());
+            (!hash.is_null()).then(|| CStr::from_ptr(hash).to_string_lossy().into_owned())
+        }
+    };
+    match hash {
+        Some(hash) if hash.starts_with("$6$") => Ok(hash),
+        _ => Err(SshManagerError::Unsupported("crypt(3) cannot make SHA-512 password hashes here".to_string())),
+    }
+}
+
+/// `fields` of a shadow entry, locked the way `usermod -L --expiredate 1` locks it, or unlocked
+///
+/// A `!` before the hash only stops passwords; the expiry date in the past makes PAM's account
+/// check, and sshd without PAM, refuse keys and certificates as well. An account left with no
+/// password by unlocking gets `NO_PASSWORD` rather than an empty one, which would let anyone in.
+fn lock_shadow_entry(fields: &mut Vec<String>, locked: bool) {
+    fields.resize(fields.len().max(9), String::new());
+    match locked {
+        true if !fields[1].starts_with('!') => fields[1].insert(0, '!'),
+        true => {}
+        false => {
+            let unlocked = fields[1].strip_prefix('!').unwrap_or(&fields[1]).to_string();
+            fields[1] = if unlocked.is_empty() { NO_PASSWORD.to_string() } else { unlocked };
+        }
+    }
+    fields[7] = if locked { "1".to_string() } else { String::new() };
+}
+
+/// Days since the epoch, the unit of shadow's date fields
+fn days_since_epoch() -> i64 {
+    chrono::Utc::now().timestamp() / 86400
+}
+
+/// The passwd, shadow and group files as lines, while they are being edited
+struct Tables {
+    passwd: Vec<String>,
+    shadow: Vec<String>,
+    group: Vec<String>,
+}
+
+impl Tables {
+    fn find(lines: &[String], name: &str) -> Option<usize> {
+        lines.iter().position(|line| line.split(':').next() == Some(name))
+    }
+
+    fn used_ids(lines: &[String]) -> Vec<u32> {
+        lines.iter().filter_map(|line| line.split(':').nth(2)?.parse().ok()).collect()
+    }
+}
+
+/// Held while the account files are edited; keeps out shadow tools and other editors of ours
+struct EditLock {
+    _guard: std::sync::MutexGuard<'static, ()>,
+    shadow_locked: bool,
+}
+
+impl EditLock {
+    fn take(system: bool) -> Result<Self, SshManagerError> {
+        let guard = EDIT_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let shadow_locked = system && lock_shadow()?;
+        Ok(Self { _guard: guard, shadow_locked })
+    }
+}
+
+impl Drop for EditLock {
+    fn drop(&mut self) {
+        #[cfg(target_os = "linux")]
+        if self.shadow_locked {
+            // SAFETY: releases the lock `lckpwdf` took in `take`
+            unsafe {
+                ulckpwdf();
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn lock_shadow() -> Result<bool, SshManagerError> {
+    // SAFETY: lckpwdf takes no arguments; it waits up to 15 seconds for the lock
+    if unsafe { lckpwdf() } != 0 {
+        return Err(SshManagerError::PermissionDenied(
+            "Cannot lock the account database; run as root or install eryzaa-ssh-service".to_string(),
+        ));
+    }
+    Ok(true)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lock_shadow() -> Result<bool, SshManagerError> {
+    Ok(false)
+}
+
+/// Account files under a root directory: the machine's own, or a scratch copy
+pub struct FileDatabase {
+    root: PathBuf,
+    system: bool, // Lock with lckpwdf and give homes to their users
+}
+
+impl FileDatabase {
+    /// This machine's accounts; changing them takes root
+    pub fn system() -> Self {
+        Self { root: PathBuf::from("/"), system: true }
+    }
+
+    /// Accounts under `root`, e.g. a scratch directory; homes keep the caller as owner
+    pub fn at(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into(), system: false }
+    }
+
+    fn file(&self, name: &str) -> PathBuf {
+        self.root.join("etc").join(name)
+    }
+
+    /// Where `home` is on disk
+    fn home_path(&self, home: &Path) -> PathBuf {
+        self.root.join(home.strip_prefix("/").unwrap_or(home))
+    }
+
+    fn read_lines(&self, name: &str) -> Result<Vec<String>, SshManagerError> {
+        let path = self.file(name);
+        match std::fs::read_to_string(&path) {
+            Ok(content) => Ok(content.lines().map(str::to_string).collect()),
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => Err(SshManagerError::PermissionDenied(format!(
+                "Cannot read {}; run as root or install eryzaa-ssh-service",
+                path.display()
+            ))),
+            Err(e) => Err(SshManagerError::io("read", &path, e)),
+        }
+    }
+
+    /// Swap in new content for an account file, keeping its mode and owner
+    fn write_lines(&self, name: &str, lines: &[String]) -> Result<(), SshManagerError> {
+        let path = self.file(name);
+        let staged = self.file(&format!("{}+", name));
+        let mut content = lines.join("\n");
+        content.push('\n');
+        std::fs::write(&staged, content).map_err(|e| SshManagerError::io("write", &staged, e))?;
+        if let Ok(metadata) = std::fs::metadata(&path) {
+            std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(metadata.mode() & 0o7777))
+                .map_err(|e| SshManagerError::io("set permissions of", &staged, e))?;
+            if self.system {
+                std::os::unix::fs::chown(&staged, Some(metadata.uid()), Some(metadata.gid()))
+                    .map_err(|e| SshManagerError::io("change owner of", &staged, e))?;
+            }
+        }
+        std::fs::rename(&staged, &path).map_err(|e| SshManagerError::io("replace", &path, e))
+    }
+
+    /// Change the account files under the lock, writing them back if `change` succeeds
+    fn edit<T>(&self, change: impl FnOnce(&mut Tables) -> Result<T, SshManagerError>) -> Result<T, SshManagerError> {
+        let _lock = EditLock::take(self.system)?;
+        let mut tables = Tables {
+            passwd: self.read_lines("passwd")?,
+            shadow: self.read_lines("shadow")?,
+            group: self.read_lines("group")?,
+        };
+        let result = change(&mut tables)?;
+        // A user never shows up before its group and shadow entry
+        self.write_lines("group", &tables.group)?;
+        self.write_lines("shadow", &tables.shadow)?;
+        self.write_lines("passwd", &tables.passwd)?;
+        Ok(result)
+    }
+
+    /// Create `entry`'s home from /etc/skel, private to it
+    fn make_home(&self, entry: &UserEntry) -> Result<(), SshManagerError> {
+        let home = self.home_path(&entry.home);
+        copy_tree(&self.file("skel"), &home)?;
+        std::fs::set_permissions(&home, std::fs::Permissions::from_mode(0o700))
+            .map_err(|e| SshManagerError::io("make private", &home, e))?;
+        if self.system {
+            chown_tree(&home, entry.uid, entry.gid)?;
+        }
+        Ok(())
+    }
+}
+
+impl AccountBackend for FileDatabase {
+    fn lookup(&self, username: &str) -> Result<Option<UserEntry>, SshManagerError> {
+        Ok(self
+            .read_lines("passwd")?
+            .iter()
+            .filter_map(|line| UserEntry::parse(line))
+            .find(|entry| entry.name == username))
+    }
+
+    fn users(&self) -> Result<Vec<String>, SshManagerError> {
+        Ok(users_in_passwd(&self.read_lines("passwd")?.join("\n")))
+    }
+
+    fn add_user(&self, username: &str, shell: &str) -> Result<UserEntry, SshManagerError> {
+        let entry = self.edit(|tables| {
+            if Tables::find(&tables.passwd, username).is_some() || Tables::find(&tables.group, username).is_some() {
+                return Err(SshManagerError::AlreadyExists(format!("User or group '{}' already exists", username)));
+            }
+            // The private group shares the uid's number
+            let taken: Vec<u32> = Tables::used_ids(&tables.passwd)
+                .into_iter()
+                .chain(Tables::used_ids(&tables.group))
+                .collect();
+            let id = (UID_MIN..=UID_MAX)
+                .find(|id| !taken.contains(id))
+                .ok_or_else(|| SshManagerError::Command(format!("No free uid between {} and {}", UID_MIN, UID_MAX)))?;
+
+            let entry = UserEntry {
+                name: username.to_string(),
+                uid: id,
+                gid: id,
+                home: PathBuf::from(format!("/home/{}", username)),
+                shell: shell.to_string(),
+            };
+            tables.passwd.push(format!("{}:x:{}:{}::{}:{}", username, id, id, entry.home.display(), shell));
+            tables.shadow.push(format!("{}:!:{}:0:99999:7:::", username, days_since_epoch()));
+            tables.group.push(format!("{}:x:{}:", username, id));
+            Ok(entry)
+        })?;
+
+        if let Err(e) = self.make_home(&entry) {
+            let _ = self.remove_user(username);
+            return Err(e.context("Failed to create home directory"));
+        }
+        Ok(entry)
+    }
+
+    fn set_password_hash(&self, username: &str, hash: &str) -> Result<(), SshManagerError> {
+        self.edit(|tables| {
+            let index = Tables::find(&tables.shadow, username)
+                .ok_or_else(|| SshManagerError::NotFound(format!("No shadow entry for '{}'", username)))?;
+            let mut fields: Vec<String> = tables.shadow[index].split(':').map(str::to_string).collect();
+            fields.resize(fields.len().max(9), String::new());
+            fields[1] = hash.to_string();
+            fields[2] = days_since_epoch().to_string();
+            tables.shadow[index] = fields.join(":");
+            Ok(())
+        })
+    }
+
+    fn add_to_group(&self, username: &str, group: &str) -> Result<(), SshManagerError> {
+        self.edit(|tables| {
+            let index = Tables::find(&tables.group, group)
+                .ok_or_else(|| SshManagerError::NotFound(format!("No group '{}'", group)))?;
+            let line = &mut tables.group[index];
+            let members = line.rsplit(':').next().unwrap_or("");
+            if members.split(',').any(|member| member == username) {
+                return Ok(());
+            }
+            if !members.is_empty() {
+                line.push(',');
+            }
+            line.push_str(username);
+            Ok(())
+        })
+    }
+
+    fn set_locked(&self, username: &str, locked: bool) -> Result<(), SshManagerError> {
+        self.edit(|tables| {
+            let index = Tables::find(&tables.shadow, username)
+                .ok_or_else(|| SshManagerError::NotFound(format!("No shadow entry for '{}'", username)))?;
+            let mut fields: Vec<String> = tables.shadow[index].split(':').map(str::to_string).collect();
+            lock_shadow_entry(&mut fields, locked);
+            tables.shadow[index] = fields.join(":");
+            Ok(())
+        })
+    }
+
+    fn remove_user(&self, username: &str) -> Result<(), SshManagerError> {
+        let entry = self.edit(|tables| {
+            let index = Tables::find(&tables.passwd, username)
+                .ok_or_else(|| SshManagerError::NotFound(format!("No local user '{}'", username)))?;
+            let entry = UserEntry::parse(&tables.passwd.remove(index));
+            if let Some(index) = Tables::find(&tables.shadow, username) {
+                tables.shadow.remove(index);
+            }
+            if let Some(index) = Tables::find(&tables.group, username) {
+                tables.group.remove(index);
+            }
+            for line in &mut tables.group {
+                let Some((head, members)) = line.rsplit_once(':') else {
+                    continue;
+                };
+                if members.split(',').any(|member| member == username) {
+                    let kept: Vec<&str> = members.split(',').filter(|member| *member != username).collect();
+                    *line = format!("{}:{}", head, kept.join(","));
+                }
+            }
+            Ok(entry)
+        })?;
+
+        let Some(entry) = entry else {
+            return Ok(());
+        };
+        let home = self.home_path(&entry.home);
+        match std::fs::remove_dir_all(&home) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(SshManagerError::io("remove", &home, e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Copy `from` to `to`, creating `to` even when there is nothing to copy
+fn copy_tree(from: &Path, to: &Path) -> Result<(), SshManagerError> {
+    std::fs::create_dir_all(to).map_err(|e| SshManagerError::io("create", to, e))?;
+    let Ok(entries) = std::fs::read_dir(from) else {
+        return Ok(());
+    };
+    for entry in entries.flatten() {
+        let target = to.join(entry.file_name());
+        let file_type = entry.file_type().map_err(|e| SshManagerError::io("inspect", &entry.path(), e))?;
+        if file_type.is_dir() {
+            copy_tree(&entry.path(), &target)?;
+        } else if file_type.is_file() {
+            std::fs::copy(entry.path(), &target).map_err(|e| SshManagerError::io("copy", &entry.path(), e))?;
+        }
+    }
+    Ok(())
+}
+
+fn chown_tree(path: &Path, uid: u32, gid: u32) -> Result<(), SshManagerError> {
+    std::os::unix::fs::lchown(path, Some(uid), Some(gid)).map_err(|e| SshManagerError::io("change owner of", path, e))?;
+    if path.is_dir() && !path.is_symlink() {
+        let entries = std::fs::read_dir(path).map_err(|e| SshManagerError::io("read", path, e))?;
+        for entry in entries.flatten() {
+            chown_tree(&entry.path(), uid, gid)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Accounts in memory, failing whatever step is named in `fail`
+    #[derive(Default)]
+    struct MockBackend {
+        users: RefCell<Vec<(UserEntry, String, Vec<String>)>>, // Entry, password hash, groups
+        fail: Option<&'static str>,
+    }
+
+    impl MockBackend {
+        fn check(&self, step: &str) -> Result<(), SshManagerError> {
+            match self.fail == Some(step) {
+                true => Err(SshManagerError::Command(format!("{} failed", step))),
+                false => Ok(()),
+            }
+        }
+    }
+
+    impl AccountBackend for MockBackend {
+        fn lookup(&self, username: &str) -> Result<Option<UserEntry>, SshManagerError> {
+            Ok(self.users.borrow().iter().map(|(entry, _, _)| entry.clone()).find(|entry| entry.name == username))
+        }
+
+        fn users(&self) -> Result<Vec<String>, SshManagerError> {
+            Ok(self.users.borrow().iter().map(|(entry, _, _)| entry.name.clone()).collect())
+        }
+
+        fn add_user(&self, username: &str, shell: &str) -> Result<UserEntry, SshManagerError> {
+            self.check("add_user")?;
+            let id = UID_MIN + self.users.borrow().len() as u32;
+            let entry = UserEntry { name: username.to_string(), uid: id, gid: id, home: PathBuf::from(format!("/home/{}", username)), shell: shell.to_string() };
+            self.users.borrow_mut().push((entry.clone(), "!".to_string(), vec![]));
+            Ok(entry)
+        }
+
+        fn set_password_hash(&self, username: &str, hash: &str) -> Result<(), SshManagerError> {
+            self.check("set_password_hash")?;
+            let mut users = self.users.borrow_mut();
+            let user = users.iter_mut().find(|(entry, _, _)| entry.name == username).unwrap();
+            user.1 = hash.to_string();
+            Ok(())
+        }
+
+        fn add_to_group(&self, username: &str, group: &str) -> Result<(), SshManagerError> {
+            self.check("add_to_group")?;
+            let mut users = self.users.borrow_mut();
+            let user = users.iter_mut().find(|(entry, _, _)| entry.name == username).unwrap();
+            user.2.push(group.to_string());
+            Ok(())
+        }
+
+        fn set_locked(&self, username: &str, locked: bool) -> Result<(), SshManagerError> {
+            let mut users = self.users.borrow_mut();
+            let user = users.iter_mut().find(|(entry, _, _)| entry.name == username).unwrap();
+            let mut fields = vec![username.to_string(), user.1.clone()];
+            lock_shadow_entry(&mut fields, locked);
+            user.1 = fields[1].clone();
+            Ok(())
+        }
+
+        fn remove_user(&self, username: &str) -> Result<(), SshManagerError> {
+            self.users.borrow_mut().retain(|(entry, _, _)| entry.name != username);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_create_account() {
+        let mock = MockBackend::default();
+        let entry = create_account(&mock, "job_aaaaaaaa", &Credential::Certificate, IsolationLevel::FullShell, DockerAccess::Group).unwrap();
+        assert_eq!((entry.uid, entry.shell.as_str()), (1000, "/bin/bash"));
+        assert_eq!(mock.users.borrow()[0].1, NO_PASSWORD);
+        assert_eq!(mock.users.borrow()[0].2, vec!["docker"]);
+        assert!(matches!(
+            create_account(&mock, "job_aaaaaaaa", &Credential::Certificate, IsolationLevel::FullShell, DockerAccess::Group),
+            Err(SshManagerError::AlreadyExists(_))
+        ));
+
+        let password = Credential::Password("hunter22".to_string());
+        create_account(&mock, "job_bbbbbbbb", &password, IsolationLevel::Chroot, DockerAccess::Group).unwrap();
+        assert!(mock.users.borrow()[1].1.starts_with("$6$"));
+        assert!(mock.users.borrow()[1].2.is_empty());
+
+        // An account whose password cannot be set is taken away again; docker is best effort
+        let failing = MockBackend { fail: Some("set_password_hash"), ..Default::default() };
+        assert!(create_account(&failing, "job_cccccccc", &password, IsolationLevel::FullShell, DockerAccess::Group).is_err());
+        assert!(failing.users().unwrap().is_empty());
+        let no_docker = MockBackend { fail: Some("add_to_group"), ..Default::default() };
+        assert!(create_account(&no_docker, "job_cccccccc", &password, IsolationLevel::FullShell, DockerAccess::Group).is_ok());
+        create_account(&mock, "job_dddddddd", &password, IsolationLevel::FullShell, DockerAccess::Proxy).unwrap();
+        assert!(mock.users.borrow()[2].2.is_empty());
+    }
+
+    #[test]
+    fn test_file_database() {
+        let root = std::env::temp_dir().join(format!("eryzaa-userdb-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("etc/skel")).unwrap();
+        std::fs::write(root.join("etc/skel/.bashrc"), "# skel\n").unwrap();
+        std::fs::write(root.join("etc/passwd"), "root:x:0:0:root:/root:/bin/bash\nalice:x:1000:1000::/home/alice:/bin/bash\n").unwrap();
+        std::fs::write(root.join("etc/shadow"), "root:*:19000:0:99999:7:::\nalice:!:19000:0:99999:7:::\n").unwrap();
+        std::fs::write(root.join("etc/group"), "root:x:0:\nalice:x:1000:\ndocker:x:998:alice\nstaff:x:1001:\n").unwrap();
+        let database = FileDatabase::at(&root);
+
+        // 1000 is alice's and 1001 a group's, so the user and its group get 1002
+        let entry = database.add_user("job_aaaaaaaa", "/bin/rbash").unwrap();
+        assert_eq!((entry.uid, entry.gid), (1002, 1002));
+        assert_eq!(database.lookup("job_aaaaaaaa").unwrap(), Some(entry));
+        assert!(root.join("home/job_aaaaaaaa/.bashrc").exists());
+        assert_eq!(std::fs::metadata(root.join("home/job_aaaaaaaa")).unwrap().mode() & 0o777, 0o700);
+        assert!(matches!(database.add_user("alice", "/bin/bash"), Err(SshManagerError::AlreadyExists(_))));
+
+        database.set_password_hash("job_aaaaaaaa", NO_PASSWORD).unwrap();
+        database.add_to_group("job_aaaaaaaa", "docker").unwrap();
+        let shadow = std::fs::read_to_string(root.join("etc/shadow")).unwrap();
+        assert!(shadow.lines().any(|line| line.starts_with("job_aaaaaaaa:*:")));
+        assert!(std::fs::read_to_string(root.join("etc/group")).unwrap().contains("docker:x:998:alice,job_aaaaaaaa\n"));
+        assert!(matches!(database.add_to_group("job_aaaaaaaa", "wheel"), Err(SshManagerError::NotFound(_))));
+
+        // Suspending keeps the home and restores the same key-only login
+        database.set_locked("job_aaaaaaaa", true).unwrap();
+        database.set_locked("job_aaaaaaaa", true).unwrap();
+        let shadow = std::fs::read_to_string(root.join("etc/shadow")).unwrap();
+        let locked = shadow.lines().find(|line| line.starts_with("job_aaaaaaaa:")).unwrap();
+        assert!(locked.starts_with("job_aaaaaaaa:!*:") && locked.ends_with(":1:"), "{}", locked);
+        database.set_locked("job_aaaaaaaa", false).unwrap();
+        let shadow = std::fs::read_to_string(root.join("etc/shadow")).unwrap();
+        let unlocked = shadow.lines().find(|line| line.starts_with("job_aaaaaaaa:")).unwrap();
+        assert!(unlocked.starts_with("job_aaaaaaaa:*:") && unlocked.ends_with(":::"), "{}", unlocked);
+        assert!(root.join("home/job_aaaaaaaa/.bashrc").exists());
+        let mut fresh = vec!["job_bbbbbbbb".to_string(), "!".to_string()];
+        lock_shadow_entry(&mut fresh, false);
+        assert_eq!(fresh[1], NO_PASSWORD);
+
+        database.remove_user("job_aaaaaaaa").unwrap();
+        assert_eq!(database.users().unwrap(), vec!["root", "alice"]);
+        let group = std::fs::read_to_string(root.join("etc/group")).unwrap();
+        assert!(group.contains("docker:x:998:alice\n") && !group.contains("job_aaaaaaaa"));
+        assert!(!root.join("home/job_aaaaaaaa").exists());
+        assert!(matches!(database.remove_user("job_aaaaaaaa"), Err(SshManagerError::NotFound(_))));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
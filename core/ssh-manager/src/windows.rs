@@ -0,0 +1,295 @@
+//! Job users on Windows
+//! Accounts are managed with the LocalAccounts PowerShell module and signed in to through the
+//! OpenSSH Server feature, so the rental GUI has to run elevated. A profile only exists after
+//! the first sign-in, so client keys live under ProgramData and sshd finds them through a
+//! Match block added to its config once.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use log::{info, warn};
+
+use crate::accounts::{Credential, IsolationLevel, ResourceCaps};
+use crate::bandwidth::{BandwidthCaps, UserTraffic};
+use crate::docker::DockerAccess;
+use crate::error::SshManagerError;
+use crate::quota::QuotaMethod;
+
+const SSHD_CONFIG: &str = r"C:\ProgramData\ssh\sshd_config";
+const KEYS_DIR: &str = r"C:\ProgramData\ssh\eryzaa_keys";
+const SSHD_MISSING: &str = "OpenSSH Server is not installed; add it under Settings > Apps > Optional features";
+
+/// Appended to sshd_config; Match blocks run to the end of the file, so it goes last
+const MATCH_BLOCK: &str = "\n# Eryzaa job users sign in with the key their client supplied\n\
+                           Match User job_*\n    AuthorizedKeysFile __PROGRAMDATA__/ssh/eryzaa_keys/%u\n";
+
+/// sshd on Windows reads no drop-ins, and per-user blocks would have to go before ours in sshd_config
+const SSH_POLICY_UNSUPPORTED: &str = "Per-job SSH policies are not available on Windows";
+
+/// Well-known SIDs, since group names are translated on localized Windows
+const USERS_SID: &str = "S-1-5-32-545";
+const ADMINISTRATORS_SID: &str = "S-1-5-32-544";
+const SYSTEM_SID: &str = "S-1-5-18";
+
+/// Run a PowerShell `script` that finds the user in $env:ERYZAA_USER and secrets on stdin
+fn powershell(script: &str, username: &str, input: Option<&str>) -> Result<String, SshManagerError> {
+    let mut child = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-ExecutionPolicy", "Bypass", "-Command", script])
+        .env("ERYZAA_USER", username)
+        .env("ERYZAA_KEYS", KEYS_DIR)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| SshManagerError::spawn("PowerShell", e))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        writeln!(stdin, "{}", input).map_err(|e| SshManagerError::Command(format!("Failed to write to PowerShell: {}", e)))?;
+    }
+    let output = child.wait_with_output().map_err(|e| SshManagerError::spawn("PowerShell", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(SshManagerError::from_command("PowerShell", stderr.lines().find(|line| !line.trim().is_empty()).unwrap_or_default()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Create a local job user in the Users group, signing in with `credential`
+///
+/// Only full shells are offered; Windows has no rbash or chroot, and Docker Desktop's
+/// containers cannot be entered through a forced command the same way.
+pub fn create_user(
+    username: &str,
+    credential: &Credential,
+    isolation: IsolationLevel,
+    match_block: Option<&str>,
+    docker: DockerAccess,
+    _job_id: &str,
+) -> Result<(), SshManagerError> {
+    if isolation != IsolationLevel::FullShell {
+        return Err(SshManagerError::Unsupported(format!("{} is not available on Windows", isolation.label())));
+    }
+    if !matches!(docker, DockerAccess::Group | DockerAccess::None) {
+        return Err(SshManagerError::Unsupported(format!("{} is not available on Windows", docker.label())));
+    }
+    if match_block.is_some() {
+        return Err(SshManagerError::Unsupported(SSH_POLICY_UNSUPPORTED.to_string()));
+    }
+    // Key-only users get a password nobody knows, since Windows refuses blank ones over the network
+    let password = match credential {
+        Credential::Password(password) => password.clone(),
+        Credential::PublicKey(_) | Credential::Certificate => format!("{}!Aa1", uuid::Uuid::new_v4().simple()),
+    };
+    let create = "$ErrorActionPreference = 'Stop'
+        $password = ConvertTo-SecureString ([Console]::In.ReadLine()) -AsPlainText -Force
+        New-LocalUser -Name $env:ERYZAA_USER -Password $password -PasswordNeverExpires -UserMayNotChangePassword -Description 'Eryzaa job user' | Out-Null";
+    powershell(create, username, Some(&password)).map_err(|e| e.context("Failed to create user"))?;
+
+    let set_up = set_up_user(username, credential);
+    if set_up.is_err() {
+        let _ = delete_user(username);
+    }
+    set_up?;
+
+    // Docker Desktop only lets members of docker-users talk to the engine
+    if docker == DockerAccess::Group {
+        let script = "Add-LocalGroupMember -Group 'docker-users' -Member $env:ERYZAA_USER -ErrorAction Stop";
+        if let Err(e) = powershell(script, username, None) {
+            warn!("Failed to add user to docker-users group: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn set_up_user(username: &str, credential: &Credential) -> Result<(), SshManagerError> {
+    let users = format!("$ErrorActionPreference = 'Stop'; Add-LocalGroupMember -SID '{}' -Member $env:ERYZAA_USER", USERS_SID);
+    powershell(&users, username, None).map_err(|e| e.context("Failed to add user to Users group"))?;
+
+    match credential {
+        Credential::Password(_) => Ok(()),
+        Credential::PublicKey(key) => install_public_key(username, key),
+        Credential::Certificate => Ok(()),
+    }
+}
+
+/// Authorize `key` for `username`
+fn install_public_key(username: &str, key: &str) -> Result<(), SshManagerError> {
+    write_authorized_keys(username, key)
+}
+
+/// Replace the user's authorized keys with `content`, readable only by sshd, administrators and the user
+pub fn write_authorized_keys(username: &str, content: &str) -> Result<(), SshManagerError> {
+    enable_job_user_keys()?;
+
+    // sshd ignores key files anyone else can write to
+    let script = format!(
+        "$ErrorActionPreference = 'Stop'
+        New-Item -ItemType Directory -Force -Path $env:ERYZAA_KEYS | Out-Null
+        $path = Join-Path $env:ERYZAA_KEYS $env:ERYZAA_USER
+        $staged = \"$path.new\"
+        Set-Content -Path $staged -Value ([Console]::In.ReadToEnd()) -Encoding ascii
+        icacls $staged /inheritance:r /grant '*{}:F' /grant '*{}:F' /grant \"$($env:ERYZAA_USER):R\" | Out-Null
+        if ($LASTEXITCODE -ne 0) {{ throw 'icacls could not restrict the key file' }}
+        Move-Item -Force -Path $staged -Destination $path",
+        SYSTEM_SID, ADMINISTRATORS_SID
+    );
+    powershell(&script, username, Some(content)).map(|_| ()).map_err(|e| e.context("Failed to write authorized keys"))
+}
+
+/// Point sshd at the key directory for job users, once
+fn enable_job_user_keys() -> Result<(), SshManagerError> {
+    let config_path = Path::new(SSHD_CONFIG);
+    let config = std::fs::read_to_string(config_path)
+        .map_err(|_| SshManagerError::Unsupported(SSHD_MISSING.to_string()))?;
+    let Some(updated) = with_job_user_keys(&config) else {
+        return Ok(());
+    };
+
+    std::fs::write(config_path, updated).map_err(|e| SshManagerError::io("write", config_path, e))?;
+    powershell("Restart-Service sshd -ErrorAction Stop", "", None).map_err(|e| e.context("Failed to restart sshd"))?;
+    info!("Enabled key sign-in for job users in {}", SSHD_CONFIG);
+    Ok(())
+}
+
+/// `config` with the job user Match block appended, or None when it has one already
+fn with_job_user_keys(config: &str) -> Option<String> {
+    if config.lines().any(|line| line.trim() == "Match User job_*") {
+        return None;
+    }
+    Some(format!("{}{}", config.trim_end_matches(['\r', '\n']), MATCH_BLOCK))
+}
+
+/// Have sshd trust the CA key at `ca_public_key`, restarting it when that changes
+pub fn trust_user_ca(ca_public_key: &Path) -> Result<(), SshManagerError> {
+    add_sshd_setting(&format!("TrustedUserCAKeys {}", ca_public_key.display()))
+}
+
+/// Have sshd refuse the keys and certificates in the revocation list at `revoked_keys`
+pub fn use_revoked_keys(revoked_keys: &Path) -> Result<(), SshManagerError> {
+    add_sshd_setting(&format!("RevokedKeys {}", revoked_keys.display()))
+}
+
+/// Add `setting` to sshd_config, restarting sshd when that changes it
+fn add_sshd_setting(setting: &str) -> Result<(), SshManagerError> {
+    let config_path = Path::new(SSHD_CONFIG);
+    let config = std::fs::read_to_string(config_path)
+        .map_err(|_| SshManagerError::Unsupported(SSHD_MISSING.to_string()))?;
+    let Some(updated) = with_trusted_ca(&config, setting) else {
+        return Ok(());
+    };
+
+    std::fs::write(config_path, updated).map_err(|e| SshManagerError::io("write", config_path, e))?;
+    powershell("Restart-Service sshd -ErrorAction Stop", "", None).map_err(|e| e.context("Failed to restart sshd"))?;
+    info!("Added '{}' to {}", setting, SSHD_CONFIG);
+    Ok(())
+}
+
+/// `config` with `setting` ahead of its Match blocks, or None when it has it already
+fn with_trusted_ca(config: &str, setting: &str) -> Option<String> {
+    if config.lines().any(|line| line.trim() == setting) {
+        return None;
+    }
+    let lines: Vec<&str> = config.lines().collect();
+    let first_match = lines.iter().position(|line| line.trim_start().starts_with("Match ")).unwrap_or(lines.len());
+    let mut updated = lines[..first_match].to_vec();
+    updated.push(setting);
+    updated.extend(&lines[first_match..]);
+    Some(format!("{}\n", updated.join("\n")))
+}
+
+/// Windows has no per-user slice to cap, so caps are refused rather than ignored quietly
+pub fn limit_user(_username: &str, caps: &ResourceCaps) -> Result<(), SshManagerError> {
+    match caps.is_empty() {
+        true => Ok(()),
+        false => Err(SshManagerError::Unsupported("CPU and memory limits per user are not supported on Windows".to_string())),
+    }
+}
+
+/// Windows Firewall cannot rate-limit per user, so caps are refused like resource caps
+pub fn throttle_users(_usernames: &[String], caps: BandwidthCaps) -> Result<(), SshManagerError> {
+    match caps.is_empty() {
+        true => Ok(()),
+        false => Err(SshManagerError::Unsupported("Bandwidth limits per user are not supported on Windows".to_string())),
+    }
+}
+
+/// OpenSSH on Windows runs no AuthorizedKeysCommand as another user; expired keys go when the reaper removes them
+pub fn enable_login_check(_helper: &Path, _settings: &str) -> Result<(), SshManagerError> {
+    Err(SshManagerError::Unsupported("The login check is only available on Linux".to_string()))
+}
+
+/// NTFS quotas count per volume and owner rather than per directory, so none is set
+pub fn quota_user(_username: &str, _limit_mb: u64) -> Result<QuotaMethod, SshManagerError> {
+    Err(SshManagerError::Unsupported("Disk quotas per user are not supported on Windows".to_string()))
+}
+
+/// Traffic is not counted per user on Windows
+pub fn user_traffic() -> Result<Vec<UserTraffic>, SshManagerError> {
+    Err(SshManagerError::Unsupported("Traffic per user is not counted on Windows".to_string()))
+}
+
+/// Only an empty policy, which needs no Match block, can be set
+pub fn write_match_block(_username: &str, match_block: Option<&str>) -> Result<(), SshManagerError> {
+    match match_block {
+        Some(_) => Err(SshManagerError::Unsupported(SSH_POLICY_UNSUPPORTED.to_string())),
+        None => Ok(()),
+    }
+}
+
+/// Stop a job user's processes and delete the user with its profile and key
+pub fn delete_user(username: &str) -> Result<(), SshManagerError> {
+    let script = "$user = Get-LocalUser -Name $env:ERYZAA_USER -ErrorAction Stop
+        Get-CimInstance Win32_Process | Where-Object { (Invoke-CimMethod -InputObject $_ -MethodName GetOwner).User -eq $env:ERYZAA_USER } |
+            ForEach-Object { Stop-Process -Id $_.ProcessId -Force -ErrorAction SilentlyContinue }
+        Remove-LocalUser -Name $env:ERYZAA_USER -ErrorAction Stop
+        Get-CimInstance Win32_UserProfile | Where-Object { $_.SID -eq $user.SID.Value } | Remove-CimInstance
+        Remove-Item -Force -ErrorAction SilentlyContinue (Join-Path $env:ERYZAA_KEYS $env:ERYZAA_USER)";
+    powershell(script, username, None).map(|_| ()).map_err(|e| e.context("Failed to delete user"))
+}
+
+/// Disable a job user and stop its processes, keeping its profile
+pub fn suspend_user(username: &str) -> Result<(), SshManagerError> {
+    powershell("Disable-LocalUser -Name $env:ERYZAA_USER -ErrorAction Stop", username, None)
+        .map_err(|e| e.context("Failed to suspend user"))?;
+    end_sessions(username)
+}
+
+/// Stop a job user's processes, its sessions among them
+pub fn end_sessions(username: &str) -> Result<(), SshManagerError> {
+    let script = "Get-CimInstance Win32_Process | Where-Object { (Invoke-CimMethod -InputObject $_ -MethodName GetOwner).User -eq $env:ERYZAA_USER } |
+            ForEach-Object { Stop-Process -Id $_.ProcessId -Force -ErrorAction SilentlyContinue }";
+    powershell(script, username, None).map(|_| ()).map_err(|e| e.context("Failed to end sessions"))
+}
+
+/// Let a suspended job user sign in again
+pub fn resume_user(username: &str) -> Result<(), SshManagerError> {
+    powershell("Enable-LocalUser -Name $env:ERYZAA_USER -ErrorAction Stop", username, None)
+        .map(|_| ())
+        .map_err(|e| e.context("Failed to resume user"))
+}
+
+/// Every local account
+pub fn local_users() -> Result<Vec<String>, SshManagerError> {
+    let output = powershell("Get-LocalUser | ForEach-Object { $_.Name }", "", None)
+        .map_err(|e| e.context("Failed to list local users"))?;
+    Ok(output.lines().map(str::trim).filter(|user| !user.is_empty()).map(|user| user.to_string()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sshd_match_block() {
+        let config = "PubkeyAuthentication yes\r\n\r\nMatch Group administrators\r\n       AuthorizedKeysFile __PROGRAMDATA__/ssh/administrators_authorized_keys\r\n";
+        let updated = with_job_user_keys(config).unwrap();
+        assert!(updated.starts_with(config.trim_end()));
+        assert!(updated.ends_with("Match User job_*\n    AuthorizedKeysFile __PROGRAMDATA__/ssh/eryzaa_keys/%u\n"));
+        assert_eq!(with_job_user_keys(&updated), None);
+
+        let setting = r"TrustedUserCAKeys C:\ProgramData\ssh\eryzaa_ca\ca_key.pub";
+        let trusted = with_trusted_ca(&updated, setting).unwrap();
+        assert!(trusted.contains(&format!("{}\nMatch Group administrators", setting)));
+        assert_eq!(with_trusted_ca(&trusted, setting), None);
+    }
+}
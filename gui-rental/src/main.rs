@@ -1,14 +1,26 @@
 use eframe::egui;
 use std::process::Command;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, SystemTime};
 use sysinfo::System;
+use tokio::sync::broadcast;
+use eryzaa_discovery::overlay::{self, NetworkMode};
+use eryzaa_discovery::rendezvous;
 use eryzaa_discovery::{
-    DiscoveryService, NodeAdvertisement, NodeCapabilities, NodeStatus, NodeType,
+    AdvertisementPrivacy, AdvertisementTiming, Check, DiscoveryService, NodeAdvertisement, NodeCapabilities, NodeDetails, NodeIdentity, NodeStatus, NodeType, TrialOffer,
     create_rental_advertisement,
 };
-use eryzaa_ssh_manager::{SshManager, JobAccess};
+use eryzaa_jobs::pinning::{self, CpuTopology, PinningSettings};
+use eryzaa_jobs::reservation::{self, OwnerReservation};
+use eryzaa_jobs::terminal::{self, Platform};
+use eryzaa_node::{readiness, recording, selftest, ConcurrencyLimit, HardwareWatcher, Recording};
+use eryzaa_ssh_manager::audit::{self, AuditKind};
+use eryzaa_ssh_manager::bandwidth::BandwidthUsage;
+use eryzaa_ssh_manager::grants::{self, GrantQueue};
+use eryzaa_ssh_manager::{DockerAccess, SshManager, SshManagerError, JobAccess, ReaperEvent, SessionEvent, SessionLimits};
+use eryzaa_ssh_manager::accounts::{AccessMode, IsolationLevel, SshPolicy, CHROOT_WORK_DIR};
 use uuid::Uuid;
 
 pub struct EryzaaRentalApp {
@@ -24,6 +36,9 @@ pub struct EryzaaRentalApp {
     
     // SSH management
     ssh_manager: Arc<SshManager>,
+    session_limits: SessionLimits, // Being edited; applied on save
+    audit_job: Option<String>,     // Job whose SSH audit log is shown
+    job_usage: Arc<Mutex<HashMap<String, BandwidthUsage>>>, // Traffic per job, sampled with the audit
     
     // Rental state
     is_renting_active: bool,
@@ -45,19 +60,33 @@ pub struct EryzaaRentalApp {
 
 impl Default for EryzaaRentalApp {
     fn default() -> Self {
+        let session_limits = with_owner_reservation(SessionLimits::load_from(&ssh_sessions_path()));
+        let ssh_manager = Arc::new(SshManager::with_state(session_limits.clone(), ssh_state_path()));
+        let job_usage = Arc::new(Mutex::new(HashMap::new()));
+        watch_ssh_audit(ssh_manager.clone(), job_usage.clone());
         Self {
             system: Arc::new(Mutex::new(System::new_all())),
             setup_status: Arc::new(Mutex::new(SetupStatus::default())),
             server_info: Arc::new(Mutex::new(ServerInfo::default())),
             discovery_service: None,
-            node_id: Uuid::new_v4().to_string(),
+            // Coordinators list the node beyond the LAN under the id it registered and was claimed with
+            node_id: registered_node_id().unwrap_or_else(|| Uuid::new_v4().to_string()),
             connected_clients: Arc::new(Mutex::new(Vec::new())),
-            ssh_manager: Arc::new(SshManager::new()),
+            ssh_manager,
+            settings: RentalSettings {
+                max_cpu_usage: session_limits.machine_cpu_percent.unwrap_or(100.0),
+                max_memory_usage: session_limits.machine_memory_percent.unwrap_or(100.0),
+                max_upload_mbit: session_limits.egress_mbit,
+                max_download_mbit: session_limits.ingress_mbit,
+                ..Default::default()
+            },
+            session_limits,
+            audit_job: None,
+            job_usage,
             is_renting_active: false,
             selected_tab: Tab::default(),
             show_setup_wizard: false,
             setup_step: 0,
-            settings: RentalSettings::default(),
             setup_config: SetupConfig::default(),
             last_update: SystemTime::now(),
         }
@@ -124,6 +153,8 @@ pub struct RentalSettings {
     max_memory_usage: f32,
     allowed_clients: Vec<String>,
     pricing_per_hour: f32,
+    max_upload_mbit: Option<u32>, // Per job user, unlimited when None
+    max_download_mbit: Option<u32>,
 }
 
 impl Default for RentalSettings {
@@ -135,6 +166,8 @@ impl Default for RentalSettings {
             max_memory_usage: 80.0,
             allowed_clients: vec![],
             pricing_per_hour: 5.0,
+            max_upload_mbit: None,
+            max_download_mbit: None,
         }
     }
 }
@@ -144,6 +177,7 @@ pub struct SetupConfig {
     enable_gpu: bool,
     enable_ssh: bool,
     custom_network_id: String,
+    network_mode: NetworkMode, // LAN direct skips ZeroTier altogether
     install_dev_tools: bool,
     setup_monitoring: bool,
 }
@@ -154,6 +188,7 @@ impl Default for SetupConfig {
             enable_gpu: true,
             enable_ssh: true,
             custom_network_id: "363c67c55ad2489d".to_string(),
+            network_mode: NetworkMode::load_from(&network_mode_path()),
             install_dev_tools: true,
             setup_monitoring: true,
         }
@@ -173,6 +208,45 @@ impl EryzaaRentalApp {
         // Initialize discovery service
         app.initialize_discovery_service();
         
+        // Pick up job users from before a restart and drop the ones that went away, then those that expire
+        let ssh_manager = app.ssh_manager.clone();
+        thread::spawn(move || {
+            let Ok(runtime) = tokio::runtime::Builder::new_current_thread().enable_all().build() else {
+                return;
+            };
+            match runtime.block_on(ssh_manager.reconcile()) {
+                Ok(reconciliation) => {
+                    if !reconciliation.forgotten.is_empty() || !reconciliation.removed.is_empty() {
+                        println!("Reconciled SSH users: forgot jobs {:?}, removed users {:?}", reconciliation.forgotten, reconciliation.removed);
+                    }
+                }
+                Err(e) => eprintln!("Failed to reconcile SSH users: {}", e),
+            }
+            // Sessions, as the audit collection finds them
+            ssh_manager.on_login(|event| {
+                if let SessionEvent::Login { job_id, username, source, .. } = event {
+                    println!("{} signed in to job {} from {}", username, job_id, source.as_deref().unwrap_or("an unknown address"));
+                }
+            });
+            ssh_manager.on_logout(|event| {
+                if let SessionEvent::Logout { job_id, username, .. } = event {
+                    println!("{} signed out of job {}", username, job_id);
+                }
+            });
+            runtime.block_on(async {
+                let reaper = ssh_manager.start_reaper(Duration::from_secs(60));
+                let mut events = reaper.subscribe();
+                loop {
+                    match events.recv().await {
+                        Ok(ReaperEvent::Expired { job_id, username }) => println!("Job {} expired; removed SSH user {}", job_id, username),
+                        Ok(ReaperEvent::CleanupFailed { job_id, username, error }) => eprintln!("Failed to remove SSH user {} of expired job {}: {}", username, job_id, error),
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+        });
+        
         app
     }
     
@@ -188,36 +262,62 @@ impl EryzaaRentalApp {
             network_speed_mbps: 1000, // Placeholder
             supports_docker: self.check_docker_support(),
             supports_gpu: self.detect_gpu_count() > 0,
-            max_concurrent_jobs: 4,
+            max_concurrent_jobs: ConcurrencyLimit::load_from(&concurrency_limit_path()).max_concurrent_jobs,
         };
         drop(sys);
+        let capabilities = without_owner_reservation(&capabilities);
         
         // Get network information
         let (local_ip, zerotier_ip) = self.get_network_info();
         
         // Create node advertisement
-        let advertisement = create_rental_advertisement(
+        let mut advertisement = create_rental_advertisement(
             self.node_id.clone(),
             local_ip,
             zerotier_ip,
             capabilities,
             "363c67c55ad2489d".to_string(), // Default ZeroTier network
         );
+        advertisement.details = NodeDetails::load_from(&node_details_path());
+        advertisement.trial = TrialOffer::load_from(&trial_offer_path());
+        // Not Available before the startup self-test passes
+        advertisement.status = NodeStatus::Maintenance;
         
         // Initialize discovery service
-        match DiscoveryService::new(advertisement) {
-            Ok(service) => {
+        let timing = AdvertisementTiming::load_from(&advertisement_timing_path());
+        match DiscoveryService::with_timing(advertisement, timing) {
+            Ok(mut service) => {
+                // Hidden from the very first broadcast
+                service.update_privacy(AdvertisementPrivacy::load_from(&advertisement_privacy_path()));
+                // Clients pin the key a node signs with, so it has to survive restarts
+                match NodeIdentity::load_or_create(&node_identity_path()) {
+                    Ok(identity) => service.set_identity(identity),
+                    Err(e) => println!("⚠️ Signing with a key for this run only: {}", e),
+                }
+                service.keep_node_keys(&node_keys_path());
+                // Listed at the coordinators too, for clients outside this network
+                for backend in rendezvous::backends_from_env() {
+                    println!("🛰️ Also advertising through {}", backend.name());
+                    service.add_backend(backend);
+                }
                 let service_arc = Arc::new(Mutex::new(service));
                 
-                // Start the discovery service
-                if let Ok(mut service) = service_arc.lock() {
-                    if service.start().is_ok() {
+                // Start the discovery service, which fails rather than panics off a tokio runtime
+                let started = match service_arc.lock() {
+                    Ok(service) => service.start().map_err(|e| e.to_string()),
+                    Err(_) => Err("the service is poisoned".to_string()),
+                };
+                match started {
+                    Err(e) => println!("❌ Failed to start discovery service: {}", e),
+                    Ok(()) => {
                         println!("🌐 Discovery service started - advertising rental node");
                         println!("📡 Node ID: {}", self.node_id);
+                        println!("⏱️ Re-advertising every {} s ± {}%, changes pushed at once", timing.interval_secs, timing.jitter_percent);
+                        Self::watch_hardware(Arc::clone(&service_arc));
+                        Self::watch_readiness(Arc::clone(&service_arc), Arc::clone(&self.ssh_manager));
+                        Self::watch_addresses(Arc::clone(&service_arc));
                         self.discovery_service = Some(service_arc);
                     }
-                } else {
-                    println!("❌ Failed to start discovery service");
                 }
             }
             Err(e) => {
@@ -226,6 +326,93 @@ impl EryzaaRentalApp {
         }
     }
     
+    /// Republish capabilities when GPUs, memory, disks or the driver change
+    fn watch_hardware(service_arc: Arc<Mutex<DiscoveryService>>) {
+        HardwareWatcher::new(Duration::from_secs(300)).spawn(move |current, changes| {
+            for change in &changes {
+                println!("🔧 Hardware change: {}", change);
+            }
+            if let Ok(mut service) = service_arc.lock() {
+                let capabilities = current.capabilities(&service.local_advertisement().capabilities);
+                service.update_capabilities(without_owner_reservation(&capabilities));
+                println!("📡 Capabilities republished");
+            }
+        });
+    }
+    
+    /// Check the overlay, sshd, Docker, the GPU runtime and payouts every minute and advertise the result
+    fn watch_readiness(service_arc: Arc<Mutex<DiscoveryService>>, ssh_manager: Arc<SshManager>) {
+        thread::spawn(move || {
+            // Once, before the node may advertise itself Available
+            let advertised = match service_arc.lock() {
+                Ok(service) => service.local_advertisement(),
+                Err(_) => return,
+            };
+            let mode = NetworkMode::load_from(&network_mode_path());
+            // Job users come from the privileged service, so the self-test asks it for one too
+            let users = || selftest::try_users(&ssh_manager);
+            let self_test = selftest::run(mode, advertised.api_port, advertised.zerotier_ip.as_deref(), users, readiness::run_command);
+            match self_test.passed() {
+                true => println!("✅ Startup self-test passed"),
+                false => println!("❌ Startup self-test failed, advertising Maintenance until a restart passes it: {}", self_test.failures().join("; ")),
+            }
+            
+            let mut reported = vec![];
+            loop {
+                let expects_gpu = match service_arc.lock() {
+                    Ok(service) => service.local_advertisement().capabilities.gpu_count > 0,
+                    Err(_) => return,
+                };
+                let payments_configured = eryzaa_jobs::ProfileStore::load()
+                    .map(|profiles| profiles.active().wallet_address.is_some())
+                    .unwrap_or(false);
+                let mode = NetworkMode::load_from(&network_mode_path());
+                let mut checked = readiness::probe(mode, expects_gpu, payments_configured, readiness::run_command);
+                checked.self_test = self_test.check();
+                let problems = checked.problems();
+                if problems != reported {
+                    match problems.is_empty() {
+                        true => println!("✅ Rental stack ready"),
+                        false => println!("⚠️ Not ready: {}", problems.join("; ")),
+                    }
+                    reported = problems;
+                }
+                if let Ok(mut service) = service_arc.lock() {
+                    service.update_readiness(checked);
+                }
+                thread::sleep(Duration::from_secs(60));
+            }
+        });
+    }
+    
+    /// Re-advertise at once when ZeroTier reassigns our address or the machine changes networks
+    fn watch_addresses(service_arc: Arc<Mutex<DiscoveryService>>) {
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_secs(10));
+            let advertised = match service_arc.lock() {
+                Ok(service) => service.local_advertisement(),
+                Err(_) => return,
+            };
+            let mode = NetworkMode::load_from(&network_mode_path());
+            let (local_ip, zerotier_ip) = overlay::local_addresses_in(mode, &advertised.network_id);
+            if local_ip == advertised.ip_address && zerotier_ip == advertised.zerotier_ip {
+                continue;
+            }
+            
+            let describe = |ip: &Option<String>| ip.clone().unwrap_or_else(|| "none".to_string());
+            println!(
+                "🌐 Address changed: LAN {} → {}, ZeroTier {} → {}; notifying clients",
+                advertised.ip_address,
+                local_ip,
+                describe(&advertised.zerotier_ip),
+                describe(&zerotier_ip)
+            );
+            if let Ok(mut service) = service_arc.lock() {
+                service.update_address(local_ip, zerotier_ip);
+            }
+        });
+    }
+    
     fn detect_gpu_count(&self) -> u32 {
         // Try to detect GPUs using nvidia-smi
         if let Ok(output) = Command::new("nvidia-smi").arg("-L").output() {
@@ -276,41 +463,7 @@ impl EryzaaRentalApp {
     }
     
     fn get_network_info(&self) -> (String, Option<String>) {
-        let mut local_ip = "127.0.0.1".to_string();
-        let mut zerotier_ip = None;
-        
-        // Get local IP (try to get non-loopback interface)
-        if let Ok(output) = Command::new("hostname").arg("-I").output() {
-            if output.status.success() {
-                let output_str = String::from_utf8_lossy(&output.stdout);
-                if let Some(ip) = output_str.split_whitespace().next() {
-                    if ip != "127.0.0.1" && !ip.is_empty() {
-                        local_ip = ip.to_string();
-                    }
-                }
-            }
-        }
-        
-        // Get ZeroTier IP
-        if let Ok(output) = Command::new("zerotier-cli").args(&["listnetworks"]).output() {
-            if output.status.success() {
-                let output_str = String::from_utf8_lossy(&output.stdout);
-                for line in output_str.lines() {
-                    if line.contains("363c67c55ad2489d") {
-                        let parts: Vec<&str> = line.split_whitespace().collect();
-                        if parts.len() > 6 {
-                            let ip = parts[6].split('/').next().unwrap_or("");
-                            if !ip.is_empty() && ip != "-" {
-                                zerotier_ip = Some(ip.to_string());
-                                break;
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        
-        (local_ip, zerotier_ip)
+        overlay::local_addresses_in(self.setup_config.network_mode, &self.setup_config.custom_network_id)
     }
     
     fn update_discovery_service(&mut self) {
@@ -318,13 +471,32 @@ impl EryzaaRentalApp {
             if let Ok(mut service) = service_arc.lock() {
                 // Update status based on current state
                 let status = match self.setup_status.lock().unwrap().clone() {
-                    SetupStatus::Running => NodeStatus::Available,
+                    SetupStatus::Running => available_status(&service),
                     SetupStatus::Installing(_) => NodeStatus::Maintenance,
                     _ => NodeStatus::Offline,
                 };
                 
                 service.update_status(status);
                 
+                // Pick up description and notes saved from the settings page
+                let details = NodeDetails::load_from(&node_details_path());
+                if details.validate().is_ok() {
+                    service.update_details(details);
+                }
+                
+                // Free trial offered from the settings page, if any
+                service.update_trial(TrialOffer::load_from(&trial_offer_path()));
+                
+                // What the owner keeps out of the advertisement, and whether it is broadcast at all
+                service.update_privacy(AdvertisementPrivacy::load_from(&advertisement_privacy_path()));
+                
+                // Partial availability: free slots out of the owner's limit
+                let limit = ConcurrencyLimit::load_from(&concurrency_limit_path());
+                let running = eryzaa_jobs::JobManager::load_from(&eryzaa_jobs::default_registry_path())
+                    .map(|manager| manager.running_count())
+                    .unwrap_or(0);
+                service.update_slots(limit.free_slots(running), limit.max_concurrent_jobs);
+                
                 // Get connected clients
                 let clients = service.get_nodes_by_type(NodeType::Client);
                 *self.connected_clients.lock().unwrap() = clients;
@@ -466,7 +638,11 @@ impl EryzaaRentalApp {
         Ok(())
     }
     
-    fn install_zerotier(_config: &SetupConfig) -> Result<(), String> {
+    fn install_zerotier(config: &SetupConfig) -> Result<(), String> {
+        if !config.network_mode.uses_overlay() {
+            return Ok(());
+        }
+        
         // Check if ZeroTier is already installed
         if Command::new("zerotier-cli").arg("info").output().is_ok() {
             return Ok(());
@@ -494,6 +670,12 @@ impl EryzaaRentalApp {
     }
     
     fn setup_network(config: &SetupConfig) -> Result<(), String> {
+        // The rental server reads the mode too
+        config.network_mode.save_to(&network_mode_path())?;
+        if !config.network_mode.uses_overlay() {
+            return Ok(());
+        }
+        
         // Join ZeroTier network
         let output = Command::new("zerotier-cli")
             .args(&["join", &config.custom_network_id])
@@ -590,7 +772,8 @@ impl EryzaaRentalApp {
         // Update discovery service to show as available
         if let Some(ref service_arc) = self.discovery_service {
             if let Ok(mut service) = service_arc.lock() {
-                service.update_status(NodeStatus::Available);
+                let status = available_status(&service);
+                service.update_status(status);
             }
         }
         
@@ -610,10 +793,10 @@ impl EryzaaRentalApp {
             let ssh_manager_clone = ssh_manager.clone();
             
             tokio::spawn(async move {
-                if let Err(e) = ssh_manager_clone.remove_job_user(&job_id).await {
-                    eprintln!("Failed to remove SSH user for job {}: {}", job_id, e);
-                } else {
-                    println!("Removed SSH user for job: {}", job_id);
+                match ssh_manager_clone.remove_job_user(&job_id).await {
+                    Ok(()) => println!("Removed SSH user for job: {}", job_id),
+                    Err(SshManagerError::NotFound(_)) => {}
+                    Err(e) => eprintln!("Failed to remove SSH user for job {}: {}", job_id, e),
                 }
             });
         }
@@ -869,7 +1052,7 @@ impl EryzaaRentalApp {
                         let test_client_id = "dashboard_test".to_string();
                         
                         tokio::spawn(async move {
-                            match ssh_manager.create_job_user(&test_job_id, &test_client_id, 1).await {
+                            match ssh_manager.create_job_user(&test_job_id, &test_client_id, 1, None, ssh_manager.limits().isolation, ssh_manager.limits().policy, ssh_manager.limits().docker).await {
                                 Ok(job_access) => {
                                     println!("Created test SSH user: {}", job_access.ssh_user.username);
                                 }
@@ -988,9 +1171,19 @@ impl EryzaaRentalApp {
             ui.add_space(10.0);
             
             ui.horizontal(|ui| {
-                ui.label("ZeroTier Network ID:");
-                ui.text_edit_singleline(&mut self.setup_config.custom_network_id);
+                ui.label("Network:");
+                for mode in [NetworkMode::Overlay, NetworkMode::LanDirect] {
+                    ui.radio_value(&mut self.setup_config.network_mode, mode, mode.label());
+                }
             });
+            if self.setup_config.network_mode.uses_overlay() {
+                ui.horizontal(|ui| {
+                    ui.label("ZeroTier Network ID:");
+                    ui.text_edit_singleline(&mut self.setup_config.custom_network_id);
+                });
+            } else {
+                ui.small("Clients on this LAN find the node by multicast and connect to its LAN address; SSH keeps its certificate login and the firewall only admits the LAN subnet.");
+            }
         });
         
         let status = self.setup_status.lock().unwrap().clone();
@@ -1103,8 +1296,10 @@ impl EryzaaRentalApp {
                                     if ui.button("🔗 Connect SSH").clicked() {
                                         let ip = client.zerotier_ip.as_ref()
                                             .unwrap_or(&client.ip_address);
-                                        let ssh_cmd = format!("gnome-terminal -- ssh rental@{}", ip);
-                                        let _ = Command::new("sh").arg("-c").arg(&ssh_cmd).spawn();
+                                        let line = Platform::current().ssh_snippet("rental", ip, &[]);
+                                        if let Err(e) = terminal::launch(&format!("Connecting to {}...", ip), &line) {
+                                            eprintln!("Cannot open terminal: {}", e);
+                                        }
                                     }
                                 });
                             });
@@ -1227,36 +1422,189 @@ impl EryzaaRentalApp {
         });
     }
     
+    /// Logins, failed attempts and logouts of one job's user, with recordings where there are any
+    fn show_ssh_audit(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("📜 SSH Audit Log");
+            let Some(log) = self.ssh_manager.audit_log() else {
+                ui.label("Audit logging is off for this SSH manager");
+                return;
+            };
+            let job_ids = log.job_ids();
+            if job_ids.is_empty() {
+                ui.label("📋 No SSH activity recorded yet");
+                return;
+            }
+            let selected = self.audit_job.clone().filter(|job_id| job_ids.contains(job_id)).unwrap_or_else(|| job_ids[0].clone());
+            egui::ComboBox::from_label("Job")
+                .selected_text(selected.as_str())
+                .show_ui(ui, |ui| {
+                    for job_id in &job_ids {
+                        ui.selectable_value(&mut self.audit_job, Some(job_id.clone()), job_id.as_str());
+                    }
+                });
+            
+            let events = log.events(&selected);
+            let recordings = Recording::list(std::path::Path::new(recording::RECORDINGS_DIR));
+            let time = |at: u64| {
+                chrono::DateTime::from_timestamp(at as i64, 0)
+                    .map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                    .unwrap_or_default()
+            };
+            egui::ScrollArea::vertical().id_source("ssh_audit").max_height(240.0).show(ui, |ui| {
+                for session in audit::sessions(&events) {
+                    let source = session.source.as_deref().unwrap_or("unknown");
+                    let method = session.method.as_deref().unwrap_or("?");
+                    let ended = match session.ended_at {
+                        Some(end) => format!("{} min", (end.saturating_sub(session.started_at) + 59) / 60),
+                        None => "still connected".to_string(),
+                    };
+                    let who = session.identity.as_deref().unwrap_or(&session.username);
+                    ui.label(format!("🔑 {}  {} from {} ({}), {}", time(session.started_at), who, source, method, ended));
+                    let recorded = recordings.iter().find(|r| {
+                        r.meta.username == session.username
+                            && r.meta.started_at + 5 >= session.started_at
+                            && session.ended_at.map_or(true, |end| r.meta.started_at <= end)
+                    });
+                    if let Some(recorded) = recorded {
+                        ui.small(format!("    🎞 Transcript recorded as {}", recorded.meta.id));
+                    }
+                }
+                for event in events.iter().filter(|event| event.kind == AuditKind::Failed) {
+                    ui.colored_label(
+                        egui::Color32::ORANGE,
+                        format!("⚠️ {}  failed {} login from {}", time(event.at), event.method.as_deref().unwrap_or("?"),
+                                event.source.as_deref().unwrap_or("unknown")),
+                    );
+                }
+            });
+            ui.small("Turn on session recording in the rental settings to keep transcripts of interactive logins.");
+        });
+    }
+    
     fn show_ssh_users(&mut self, ui: &mut egui::Ui) {
         ui.heading("🔐 SSH User Management");
         ui.separator();
         
-        // Current active user
+        // Session slots
         ui.group(|ui| {
-            ui.heading("Current Active User");
-            if let Some(current_user) = self.ssh_manager.get_current_user() {
-                ui.label(format!("👤 Active SSH User: {}", current_user));
-                ui.label("🔒 Status: ONE USER ONLY - No other SSH access allowed");
-                
-                // Show terminate button
-                ui.horizontal(|ui| {
-                    if ui.button("🛑 Terminate Access").clicked() {
-                        // Find job ID for this user
-                        let active_jobs = self.ssh_manager.get_active_jobs();
-                        if let Some(job) = active_jobs.iter().find(|j| j.ssh_user.username == current_user) {
-                            let ssh_manager = self.ssh_manager.clone();
-                            let job_id = job.job_id.clone();
-                            tokio::spawn(async move {
-                                if let Err(e) = ssh_manager.remove_job_user(&job_id).await {
-                                    eprintln!("Failed to remove SSH user: {}", e);
-                                }
-                            });
-                        }
-                    }
-                });
+            ui.heading("Session Slots");
+            let current_users = self.ssh_manager.get_current_users();
+            let max_sessions = self.ssh_manager.limits().max_sessions;
+            ui.label(format!("👥 {} of {} SSH sessions in use", current_users.len(), max_sessions));
+            if current_users.is_empty() {
+                ui.label("🟢 No active SSH users - Rental node available");
             } else {
-                ui.label("🟢 No active SSH user - Rental node available");
+                ui.label(format!("👤 Active SSH Users: {}", current_users.join(", ")));
+            }
+            if self.ssh_manager.free_sessions() > 0 {
                 ui.label("✅ Ready to accept new job assignments");
+            } else {
+                ui.label("🔒 All session slots in use - new jobs wait for one to end");
+            }
+            ui.label("🏠 Each job gets its own user and private home directory");
+            
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Max concurrent sessions:");
+                ui.add(egui::DragValue::new(&mut self.session_limits.max_sessions).clamp_range(1..=64));
+            });
+            ui.horizontal(|ui| {
+                let mut capped = self.session_limits.cpu_quota_percent.is_some();
+                if ui.checkbox(&mut capped, "Limit CPU per user").changed() {
+                    self.session_limits.cpu_quota_percent = capped.then_some(100);
+                }
+                if let Some(percent) = &mut self.session_limits.cpu_quota_percent {
+                    ui.add(egui::DragValue::new(percent).clamp_range(10..=6400).suffix(" %"));
+                    ui.label("(100% = one core)");
+                }
+            });
+            ui.horizontal(|ui| {
+                let mut capped = self.session_limits.memory_max_mb.is_some();
+                if ui.checkbox(&mut capped, "Limit memory per user").changed() {
+                    self.session_limits.memory_max_mb = capped.then_some(4096);
+                }
+                if let Some(mb) = &mut self.session_limits.memory_max_mb {
+                    ui.add(egui::DragValue::new(mb).clamp_range(256..=1_048_576).suffix(" MB"));
+                }
+            });
+            ui.horizontal(|ui| {
+                let mut capped = self.session_limits.disk_quota_mb.is_some();
+                if ui.checkbox(&mut capped, "Limit disk per user").on_hover_text("Applies to users made from now on").changed() {
+                    self.session_limits.disk_quota_mb = capped.then_some(20480);
+                }
+                if let Some(mb) = &mut self.session_limits.disk_quota_mb {
+                    ui.add(egui::DragValue::new(mb).clamp_range(1024..=16_777_216).suffix(" MB"));
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Job users get:");
+                egui::ComboBox::from_id_source("ssh_isolation")
+                    .selected_text(self.session_limits.isolation.label())
+                    .show_ui(ui, |ui| {
+                        for level in IsolationLevel::ALL {
+                            ui.selectable_value(&mut self.session_limits.isolation, level, level.label());
+                        }
+                    });
+            });
+            if self.session_limits.isolation.docker_access() {
+                ui.horizontal(|ui| {
+                    ui.label("Containers:");
+                    egui::ComboBox::from_id_source("ssh_docker")
+                        .selected_text(self.session_limits.docker.label())
+                        .show_ui(ui, |ui| {
+                            for access in DockerAccess::ALL {
+                                ui.selectable_value(&mut self.session_limits.docker, access, access.label());
+                            }
+                        });
+                });
+                if self.session_limits.docker == DockerAccess::Group {
+                    ui.colored_label(egui::Color32::YELLOW, "⚠️ The docker group is as good as root on this machine");
+                }
+                if let Err(e) = self.session_limits.docker.check(self.session_limits.isolation) {
+                    ui.colored_label(egui::Color32::RED, format!("⚠️ {}", e));
+                }
+            }
+            ui.horizontal(|ui| {
+                let policy = &mut self.session_limits.policy;
+                ui.checkbox(&mut policy.no_tcp_forwarding, "No TCP forwarding");
+                ui.checkbox(&mut policy.no_x11_forwarding, "No X11");
+                ui.checkbox(&mut policy.no_agent_forwarding, "No agent forwarding");
+                ui.checkbox(&mut policy.sftp_only, "SFTP only");
+            });
+            ui.horizontal(|ui| {
+                let mut capped = self.session_limits.policy.max_sessions.is_some();
+                if ui.checkbox(&mut capped, "Limit sessions per connection").changed() {
+                    self.session_limits.policy.max_sessions = capped.then_some(4);
+                }
+                if let Some(sessions) = &mut self.session_limits.policy.max_sessions {
+                    ui.add(egui::DragValue::new(sessions).clamp_range(1..=64));
+                }
+                if ui.button("🔒 Untrusted preset").clicked() {
+                    self.session_limits.policy = SshPolicy::untrusted();
+                }
+            });
+            if ui.button("💾 Save Limits").clicked() {
+                self.session_limits = with_owner_reservation(self.session_limits.clone());
+                self.ssh_manager.set_limits(self.session_limits.clone());
+                if let Err(e) = self.session_limits.save_to(&ssh_sessions_path()) {
+                    eprintln!("Failed to save session limits: {}", e);
+                }
+                let ssh_manager = self.ssh_manager.clone();
+                thread::spawn(move || ssh_manager.apply_limits_to_active_users());
+            }
+            ui.small("CPU and memory limits apply to every job user once saved; the access level and SSH policy to users created after that.");
+            ui.separator();
+            if ui
+                .button("🛂 Check job logins live")
+                .on_hover_text("sshd asks this node's job list at every login, so expired or revoked jobs are refused before their user is removed")
+                .clicked()
+            {
+                let run_as = std::env::var("USER").unwrap_or_default();
+                match self.ssh_manager.enable_login_check(&run_as) {
+                    Ok(()) => println!("✅ sshd now checks job user logins as '{}'", run_as),
+                    Err(e) => eprintln!("Failed to enable the login check: {}", e),
+                }
             }
         });
         
@@ -1277,8 +1625,41 @@ impl EryzaaRentalApp {
                             ui.horizontal(|ui| {
                                 ui.vertical(|ui| {
                                     ui.strong(format!("Job: {}", job.job_id));
-                                    ui.label(format!("👤 SSH User: {}", job.ssh_user.username));
+                                    ui.label(format!("👤 SSH User: {} ({})", job.ssh_user.username, job.ssh_user.isolation.label()));
                                     ui.label(format!("👨‍💻 Client: {}", job.client_id));
+                                    if !job.ssh_user.is_active {
+                                        ui.colored_label(egui::Color32::YELLOW, "⏸ Suspended: the client cannot sign in");
+                                    }
+                                    match &job.activity {
+                                        Some(activity) if activity.connected => {
+                                            let since = activity
+                                                .connected_since
+                                                .map(|since| format!(" since {}", since.with_timezone(&chrono::Local).format("%H:%M")))
+                                                .unwrap_or_default();
+                                            ui.colored_label(
+                                                egui::Color32::GREEN,
+                                                format!("🟢 Connected from {}{} ({} connections)", activity.sources.join(", "), since, activity.connections),
+                                            );
+                                        }
+                                        Some(_) => {
+                                            ui.label("⚪ Not connected");
+                                        }
+                                        None => {}
+                                    }
+                                    if let Some(usage) = self.job_usage.lock().unwrap().get(&job.job_id) {
+                                        let rate = |bits: Option<u64>| bits.map(|bits| format!(" ({:.1} Mbit/s)", bits as f64 / 1e6)).unwrap_or_default();
+                                        ui.label(format!(
+                                            "📶 ↓ {:.1} MB{}  ↑ {:.1} MB{}",
+                                            usage.rx_bytes as f64 / 1e6,
+                                            rate(usage.rx_bits_per_sec),
+                                            usage.tx_bytes as f64 / 1e6,
+                                            rate(usage.tx_bits_per_sec)
+                                        ));
+                                    }
+                                    if let Some(disk_quota) = &job.disk_quota {
+                                        let used = disk_quota.used_mb.map(|mb| format!("{} of ", mb)).unwrap_or_default();
+                                        ui.label(format!("💾 Disk: {}{} MB ({})", used, disk_quota.limit_mb, disk_quota.method.label()));
+                                    }
                                 });
                                 
                                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
@@ -1291,6 +1672,46 @@ impl EryzaaRentalApp {
                                             }
                                         });
                                     }
+                                    let suspended = !job.ssh_user.is_active;
+                                    let label = if suspended { "▶ Resume Access" } else { "⏸ Suspend Access" };
+                                    if ui.button(label).on_hover_text("Keeps the job and the user's files").clicked() {
+                                        let ssh_manager = self.ssh_manager.clone();
+                                        let job_id = job.job_id.clone();
+                                        tokio::spawn(async move {
+                                            let result = match suspended {
+                                                true => ssh_manager.resume_job_user(&job_id).await,
+                                                false => ssh_manager.suspend_job_user(&job_id).await,
+                                            };
+                                            if let Err(e) = result {
+                                                eprintln!("Failed to change the access of job {}: {}", job_id, e);
+                                            }
+                                        });
+                                    }
+                                    let untrusted = job.ssh_user.policy == SshPolicy::untrusted();
+                                    let (label, policy) = match untrusted {
+                                        true => ("🔓 Allow forwarding", SshPolicy::default()),
+                                        false => ("🔒 Restrict SSH", SshPolicy::untrusted()),
+                                    };
+                                    if ui.button(label).on_hover_text("Applies to connections made from now on").clicked() {
+                                        let ssh_manager = self.ssh_manager.clone();
+                                        let job_id = job.job_id.clone();
+                                        // Reloads sshd
+                                        thread::spawn(move || {
+                                            if let Err(e) = ssh_manager.set_job_policy(&job_id, policy) {
+                                                eprintln!("Failed to set the SSH policy of job {}: {}", job_id, e);
+                                            }
+                                        });
+                                    }
+                                    if ui.button("⏩ Extend 1 h").clicked() {
+                                        let ssh_manager = self.ssh_manager.clone();
+                                        let job_id = job.job_id.clone();
+                                        // Re-signing the certificate may wait on ssh-keygen
+                                        thread::spawn(move || {
+                                            if let Err(e) = ssh_manager.extend_job_access(&job_id, 1) {
+                                                eprintln!("Failed to extend job {}: {}", job_id, e);
+                                            }
+                                        });
+                                    }
                                 });
                             });
                             
@@ -1309,7 +1730,8 @@ impl EryzaaRentalApp {
                             ui.group(|ui| {
                                 ui.heading("SSH Connection Info");
                                 let server_info = self.server_info.lock().unwrap();
-                                let ssh_cmd = format!("ssh {}@{}", 
+                                let ssh_cmd = format!("{} {}@{}", 
+                                    job.mode.client_program(),
                                     job.ssh_user.username, 
                                     server_info.ip_address
                                 );
@@ -1322,9 +1744,42 @@ impl EryzaaRentalApp {
                                     }
                                 });
                                 
-                                ui.label("🔐 User has system access with docker privileges");
+                                if job.ssh_user.certificate.is_some() {
+                                    ui.label("🪪 Signs in with a certificate from this node's CA; sshd refuses it once the job expires");
+                                }
+                                // Set by the client with `eryzaa job restrict`
+                                if !job.ssh_user.sources.is_empty() {
+                                    ui.label(format!("📍 {}", job.ssh_user.sources.summary()));
+                                }
+                                match (job.mode, job.ssh_user.docker) {
+                                    (AccessMode::SftpOnly, _) => ui.label(format!("📦 Data drop: SFTP only, files go in ~/{}", CHROOT_WORK_DIR)),
+                                    (AccessMode::Shell, DockerAccess::Group) => ui.label("🔐 User has system access with docker privileges"),
+                                    (AccessMode::Shell, docker) => ui.label(format!("🐳 Containers: {}", docker.label())),
+                                };
                                 ui.label("⚠️ Access will be automatically revoked when job ends");
                             });
+
+                            // Teammates the client let in with `eryzaa job grant`
+                            if !job.grants.is_empty() {
+                                ui.group(|ui| {
+                                    ui.heading("Shared With");
+                                    for grant in &job.grants {
+                                        ui.horizontal(|ui| {
+                                            ui.label(format!("🤝 {} ({})", grant.identity, grant.scope.label()));
+                                            ui.small(format!("{} since {}", grant.fingerprint, grant.granted_at.format("%Y-%m-%d %H:%M UTC")));
+                                            if ui.button("🚫 Revoke").clicked() {
+                                                let ssh_manager = self.ssh_manager.clone();
+                                                let (job_id, identity) = (job.job_id.clone(), grant.identity.clone());
+                                                thread::spawn(move || {
+                                                    if let Err(e) = ssh_manager.revoke_access(&job_id, &identity) {
+                                                        eprintln!("Failed to revoke access: {}", e);
+                                                    }
+                                                });
+                                            }
+                                        });
+                                    }
+                                });
+                            }
                         });
                         ui.add_space(5.0);
                     }
@@ -1334,6 +1789,10 @@ impl EryzaaRentalApp {
         
         ui.add_space(10.0);
         
+        self.show_ssh_audit(ui);
+        
+        ui.add_space(10.0);
+        
         // Management actions
         ui.group(|ui| {
             ui.heading("🛠️ Management Actions");
@@ -1363,16 +1822,31 @@ impl EryzaaRentalApp {
                     let test_client_id = "test_client_123".to_string();
                     
                     tokio::spawn(async move {
-                        match ssh_manager.create_job_user(&test_job_id, &test_client_id, 1).await {
+                        match ssh_manager.create_job_user(&test_job_id, &test_client_id, 1, None, ssh_manager.limits().isolation, ssh_manager.limits().policy, ssh_manager.limits().docker).await {
                             Ok(job_access) => {
                                 println!("Created test SSH user: {}", job_access.ssh_user.username);
                             }
+                            Err(SshManagerError::PermissionDenied(e)) => {
+                                eprintln!("Failed to create test SSH user: {}; install eryzaa-ssh-service or run as root", e);
+                            }
                             Err(e) => {
                                 eprintln!("Failed to create test SSH user: {}", e);
                             }
                         }
                     });
                 }
+                
+                if ui.button("📦 Test Data Drop").on_hover_text("Create an SFTP-only user chrooted to its home").clicked() {
+                    let ssh_manager = self.ssh_manager.clone();
+                    let test_job_id = format!("test_drop_{}", uuid::Uuid::new_v4());
+                    
+                    tokio::spawn(async move {
+                        match ssh_manager.create_sftp_user(&test_job_id, "test_client_123", 1, None).await {
+                            Ok(job_access) => println!("Created test SFTP user: {}", job_access.ssh_user.username),
+                            Err(e) => eprintln!("Failed to create test SFTP user: {}", e),
+                        }
+                    });
+                }
             });
             
             ui.add_space(5.0);
@@ -1402,6 +1876,25 @@ impl EryzaaRentalApp {
                 ui.label("Max Memory Usage:");
                 ui.add(egui::Slider::new(&mut self.settings.max_memory_usage, 10.0..=100.0).suffix("%"));
             });
+            ui.horizontal(|ui| {
+                let mut capped = self.settings.max_upload_mbit.is_some();
+                if ui.checkbox(&mut capped, "Limit upload per user").changed() {
+                    self.settings.max_upload_mbit = capped.then_some(20);
+                }
+                if let Some(mbit) = &mut self.settings.max_upload_mbit {
+                    ui.add(egui::DragValue::new(mbit).clamp_range(1..=10_000).suffix(" Mbit/s"));
+                }
+            });
+            ui.horizontal(|ui| {
+                let mut capped = self.settings.max_download_mbit.is_some();
+                if ui.checkbox(&mut capped, "Limit download per user").changed() {
+                    self.settings.max_download_mbit = capped.then_some(50);
+                }
+                if let Some(mbit) = &mut self.settings.max_download_mbit {
+                    ui.add(egui::DragValue::new(mbit).clamp_range(1..=10_000).suffix(" Mbit/s"));
+                }
+            });
+            ui.small("Each job user is held to these shares of the machine's cores and memory, and to these rates so one renter cannot fill your uplink.");
         });
         
         ui.add_space(10.0);
@@ -1418,17 +1911,183 @@ impl EryzaaRentalApp {
         
         ui.horizontal(|ui| {
             if ui.button("💾 Save Settings").clicked() {
-                // Save settings
+                self.save_settings();
             }
             if ui.button("🔄 Reset to Defaults").clicked() {
                 self.settings = RentalSettings::default();
             }
         });
     }
+    
+    /// Apply the machine shares and bandwidth caps to job users, new ones and those already signed up
+    fn save_settings(&mut self) {
+        let mut limits = with_owner_reservation(self.ssh_manager.limits());
+        limits.machine_cpu_percent = Some(self.settings.max_cpu_usage);
+        limits.machine_memory_percent = Some(self.settings.max_memory_usage);
+        limits.egress_mbit = self.settings.max_upload_mbit;
+        limits.ingress_mbit = self.settings.max_download_mbit;
+        self.session_limits.machine_cpu_percent = limits.machine_cpu_percent;
+        self.session_limits.machine_memory_percent = limits.machine_memory_percent;
+        self.session_limits.egress_mbit = limits.egress_mbit;
+        self.session_limits.ingress_mbit = limits.ingress_mbit;
+        self.ssh_manager.set_limits(limits.clone());
+        if let Err(e) = limits.save_to(&ssh_sessions_path()) {
+            eprintln!("Failed to save session limits: {}", e);
+        }
+        let ssh_manager = self.ssh_manager.clone();
+        thread::spawn(move || ssh_manager.apply_limits_to_active_users());
+    }
+}
+
+/// Overlay or LAN direct, shared with the rental server
+fn network_mode_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("network.json")
+}
+
+/// Job concurrency limit shared with the rental server
+fn concurrency_limit_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("concurrency.json")
+}
+
+/// Carry out clients' access requests, file sshd's logins and logouts under their jobs and
+/// sample each job's traffic into `job_usage` every few seconds
+fn watch_ssh_audit(ssh_manager: Arc<SshManager>, job_usage: Arc<Mutex<HashMap<String, BandwidthUsage>>>) {
+    let grant_queue = GrantQueue::new(grants::GRANT_QUEUE_DIR);
+    thread::spawn(move || loop {
+        // Clients share a job with its access token, so the SSH manager needs each job's
+        if let Ok(jobs) = eryzaa_jobs::JobManager::load_from(&eryzaa_jobs::default_registry_path()) {
+            for job in jobs.list_jobs() {
+                if let Some(token) = &job.access_token {
+                    ssh_manager.set_access_token(&job.job_id, token);
+                }
+            }
+        }
+        // The owner may change what they keep from the rental settings at any time
+        let limits = with_owner_reservation(ssh_manager.limits());
+        if limits != ssh_manager.limits() {
+            ssh_manager.set_limits(limits);
+            ssh_manager.apply_limits_to_active_users();
+        }
+        for (request, result) in ssh_manager.handle_grant_requests(&grant_queue) {
+            match result {
+                Ok(()) => println!("Handled the access request for {} on job {}", request.identity, request.job_id),
+                Err(e) => eprintln!("Refused the access request for {} on job {}: {}", request.identity, request.job_id, e),
+            }
+        }
+
+        let usage: HashMap<String, BandwidthUsage> = ssh_manager
+            .get_active_jobs()
+            .iter()
+            .filter_map(|job| ssh_manager.get_usage(&job.job_id).ok())
+            .map(|usage| (usage.job_id.clone(), usage))
+            .collect();
+        *job_usage.lock().unwrap() = usage;
+        if let Err(e) = ssh_manager.refresh_activity() {
+            eprintln!("Failed to check who is connected over SSH: {}", e);
+        }
+
+        if let Err(e) = ssh_manager.collect_audit() {
+            eprintln!("Failed to update the SSH audit log: {}", e);
+            thread::sleep(Duration::from_secs(60));
+        }
+        thread::sleep(Duration::from_secs(10));
+    });
+}
+
+/// `limits` keeping job users off the cores and memory the owner reserved
+fn with_owner_reservation(mut limits: SessionLimits) -> SessionLimits {
+    let reservation = OwnerReservation::load_from(&reservation::default_reservation_path());
+    limits.reserved_cpus = match reservation.is_empty() {
+        true => vec![],
+        false => {
+            let kept = PinningSettings::load_from(&pinning::default_pinning_path()).reserved_cores;
+            reservation.reserved_cpus(&CpuTopology::detect(), &kept)
+        }
+    };
+    limits.reserved_memory_mb = u64::from(reservation.memory_gb) * 1024;
+    limits
+}
+
+/// `capabilities` without what the owner reserved, as advertised
+fn without_owner_reservation(capabilities: &NodeCapabilities) -> NodeCapabilities {
+    let reservation = OwnerReservation::load_from(&reservation::default_reservation_path());
+    let kept = PinningSettings::load_from(&pinning::default_pinning_path()).reserved_cores;
+    let reserved_cpus = match reservation.is_empty() {
+        true => 0,
+        false => reservation.reserved_cpus(&CpuTopology::detect(), &kept).len(),
+    };
+    reservation.advertised(capabilities, reserved_cpus)
+}
+
+/// SSH session limits set on the SSH users tab
+fn ssh_sessions_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("ssh_sessions.json")
+}
+
+/// Job users the SSH manager has created, kept across restarts
+fn ssh_state_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("ssh_users.json")
+}
+
+/// Advertisement details saved from the rental settings
+fn node_details_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("advertisement.json")
+}
+
+/// Free trial the node offers, shared with the rental server
+fn trial_offer_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("trial.json")
+}
+
+/// Fields the node keeps out of its advertisement, shared with the rental server
+fn advertisement_privacy_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("privacy.json")
+}
+
+/// Key the node signs its advertisements with
+fn node_identity_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("node_identity.key")
+}
+
+/// Id this node registered with at the coordinators, once onboarding has been run
+fn registered_node_id() -> Option<String> {
+    let onboarding = eryzaa_node::Onboarding::load_from(&eryzaa_jobs::default_registry_path().with_file_name("onboarding.json"));
+    Some(onboarding.node_id).filter(|node_id| !node_id.is_empty())
+}
+
+/// Keys pinned for the other nodes this machine has heard from
+fn node_keys_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("node_keys.json")
+}
+
+/// Available once the startup self-test has passed, Maintenance before then or if it failed
+fn available_status(service: &DiscoveryService) -> NodeStatus {
+    match service.local_advertisement().readiness.self_test {
+        Check::Ok => NodeStatus::Available,
+        _ => NodeStatus::Maintenance,
+    }
+}
+
+/// How often and with how much jitter the node re-advertises
+fn advertisement_timing_path() -> std::path::PathBuf {
+    eryzaa_jobs::default_registry_path().with_file_name("advertising.json")
 }
 
 fn main() -> Result<(), eframe::Error> {
     env_logger::init();
+    if let Err(e) = eryzaa_jobs::schema::prepare() {
+        eprintln!("Cannot open the saved state: {}", e);
+        std::process::exit(1);
+    }
+    // Discovery and SSH user clean-up run as tasks on this runtime
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Cannot start the async runtime: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let _runtime = runtime.enter();
     
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
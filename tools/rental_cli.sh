@@ -4,7 +4,8 @@
 # Simple command-line interface for renting out your PC via SSH
 
 SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
-SSH_SERVICE_SCRIPT="$SC        echo -e "${YELLOW}💡 Run: node $SCRIPT_DIR/blockchain_integration.cjs register${NC}"IPT_DIR/../tools/ssh_service.sh"
+SSH_SERVICE_SCRIPT="$SCRIPT_DIR/../tools/ssh_service.sh"
+SSH_SERVICE="$(command -v eryzaa-ssh-service || echo "$SCRIPT_DIR/../core/target/release/eryzaa-ssh-service")"
 RENTAL_STATE_FILE="/tmp/eryzaa_rental_state"
 LOG_FILE="/var/log/eryzaa_rental.log"
 
@@ -28,7 +29,7 @@ print_banner() {
 }
 
 check_ssh_service() {
-    if [ ! -p "/tmp/eryzaa_ssh_service.sock" ]; then
+    if [ ! -S "/run/eryzaa/ssh-service.sock" ]; then
         echo -e "${RED}❌ SSH management service not running!${NC}"
         echo -e "${YELLOW}Start it with: sudo $SSH_SERVICE_SCRIPT daemon${NC}"
         return 1
@@ -59,41 +60,26 @@ get_zerotier_ip() {
 }
 
 list_active_users() {
-    echo "list" > /tmp/eryzaa_ssh_service.sock 2>/dev/null
-    sleep 0.2
-    if [ -f "/tmp/eryzaa_ssh_service.sock.response" ]; then
-        cat "/tmp/eryzaa_ssh_service.sock.response" 2>/dev/null
-        rm -f "/tmp/eryzaa_ssh_service.sock.response" 2>/dev/null
-    fi
+    "$SSH_SERVICE" list 2>/dev/null
 }
 
 create_test_user() {
-    local username="eryzaa_job_$(openssl rand -hex 4)"
+    local username="job_$(openssl rand -hex 4)"
     local password="$(openssl rand -base64 12)"
     
-    echo "create $username $password" > /tmp/eryzaa_ssh_service.sock
-    sleep 0.5
-    
-    if [ -f "/tmp/eryzaa_ssh_service.sock.response" ]; then
-        local response=$(cat "/tmp/eryzaa_ssh_service.sock.response")
-        rm -f "/tmp/eryzaa_ssh_service.sock.response" 2>/dev/null
+    local response
+    if response=$(echo "$password" | "$SSH_SERVICE" create "$username" 2>&1); then
+        echo -e "${GREEN}✅ Created SSH user: $username${NC}"
+        echo -e "${BLUE}🔐 SSH Command: ssh $username@$(get_system_ip)${NC}"
+        echo -e "${YELLOW}🔑 Password: $password${NC}"
+        echo -e "${RED}⚠️  Only ONE user can access at a time!${NC}"
         
-        if [ "$response" = "SUCCESS" ]; then
-            echo -e "${GREEN}✅ Created SSH user: $username${NC}"
-            echo -e "${BLUE}🔐 SSH Command: ssh $username@$(get_system_ip)${NC}"
-            echo -e "${YELLOW}🔑 Password: $password${NC}"
-            echo -e "${RED}⚠️  Only ONE user can access at a time!${NC}"
-            
-            # Log the user creation
-            log_message "Created SSH user: $username for IP: $(get_system_ip)"
-            
-            return 0
-        else
-            echo -e "${RED}❌ Failed to create user: $response${NC}"
-            return 1
-        fi
+        # Log the user creation
+        log_message "Created SSH user: $username for IP: $(get_system_ip)"
+        
+        return 0
     else
-        echo -e "${RED}❌ No response from SSH service${NC}"
+        echo -e "${RED}❌ Failed to create user: $response${NC}"
         return 1
     fi
 }
@@ -106,23 +92,13 @@ remove_user() {
         return 1
     fi
     
-    echo "remove $username" > /tmp/eryzaa_ssh_service.sock
-    sleep 0.5
-    
-    if [ -f "/tmp/eryzaa_ssh_service.sock.response" ]; then
-        local response=$(cat "/tmp/eryzaa_ssh_service.sock.response")
-        rm -f "/tmp/eryzaa_ssh_service.sock.response" 2>/dev/null
-        
-        if [ "$response" = "SUCCESS" ]; then
-            echo -e "${GREEN}✅ Removed SSH user: $username${NC}"
-            log_message "Removed SSH user: $username"
-            return 0
-        else
-            echo -e "${RED}❌ Failed to remove user: $response${NC}"
-            return 1
-        fi
+    local response
+    if response=$("$SSH_SERVICE" remove "$username" 2>&1); then
+        echo -e "${GREEN}✅ Removed SSH user: $username${NC}"
+        log_message "Removed SSH user: $username"
+        return 0
     else
-        echo -e "${RED}❌ No response from SSH service${NC}"
+        echo -e "${RED}❌ Failed to remove user: $response${NC}"
         return 1
     fi
 }
@@ -3,7 +3,6 @@
 # This script should be run as root to manage SSH users
 
 USER_PREFIX="eryzaa_job_"
-SOCKET_PATH="/tmp/eryzaa_ssh_service.sock"
 
 log_message() {
     echo "[$(date '+%Y-%m-%d %H:%M:%S')] $1" | tee -a /var/log/eryzaa_ssh_service.log
@@ -26,6 +25,8 @@ create_ssh_user() {
     # Create user inside the container
     if docker exec eryzaa-ubuntu-ssh useradd -m -s /bin/bash "$username"; then
         log_message "User $username created successfully in container"
+        # Keep other job users out of the home directory
+        docker exec eryzaa-ubuntu-ssh chmod 700 "/home/$username"
     else
         log_message "Failed to create user $username in container"
         return 1
@@ -54,6 +55,42 @@ create_ssh_user() {
     return 0
 }
 
+create_ssh_user_with_key() {
+    local username="$1"
+    local public_key="$2"
+    
+    log_message "Creating key-only SSH user in Ubuntu container: $username"
+    
+    if ! docker ps | grep -q eryzaa-ubuntu-ssh; then
+        log_message "Starting eryzaa-ubuntu-ssh container..."
+        cd /home/aloo/eryzaa/infrastructure/docker
+        docker-compose -f docker-compose.ssh.yml up -d
+        sleep 5
+    fi
+    
+    if ! docker exec eryzaa-ubuntu-ssh useradd -m -s /bin/bash "$username"; then
+        log_message "Failed to create user $username in container"
+        return 1
+    fi
+    
+    docker exec eryzaa-ubuntu-ssh chmod 700 "/home/$username"
+    
+    # No password matches "*", so only the key can sign in
+    docker exec eryzaa-ubuntu-ssh usermod -p '*' "$username"
+    docker exec eryzaa-ubuntu-ssh install -d -m 700 -o "$username" -g "$username" "/home/$username/.ssh"
+    if ! printf '%s\n' "$public_key" | docker exec -i eryzaa-ubuntu-ssh tee "/home/$username/.ssh/authorized_keys" > /dev/null; then
+        log_message "Failed to install public key for $username in container"
+        docker exec eryzaa-ubuntu-ssh userdel -r "$username" 2>/dev/null
+        return 1
+    fi
+    docker exec eryzaa-ubuntu-ssh chmod 600 "/home/$username/.ssh/authorized_keys"
+    docker exec eryzaa-ubuntu-ssh chown "$username:$username" "/home/$username/.ssh/authorized_keys"
+    
+    log_message "SSH user $username ready (public key only) - connect via: ssh $username@localhost -p 2222"
+    
+    return 0
+}
+
 remove_ssh_user() {
     local username="$1"
     
@@ -86,74 +123,28 @@ list_eryzaa_users() {
     fi
 }
 
-# Socket server to handle requests
-handle_request() {
-    local request="$1"
-    local action=$(echo "$request" | cut -d'|' -f1)
-    local username=$(echo "$request" | cut -d'|' -f2)
-    local password=$(echo "$request" | cut -d'|' -f3)
-    
-    case "$action" in
-        "create")
-            if [[ "$username" =~ ^${USER_PREFIX}[a-zA-Z0-9_]{8}$ ]]; then
-                create_ssh_user "$username" "$password"
-                echo "SUCCESS"
-            else
-                echo "ERROR: Invalid username format"
-            fi
-            ;;
-        "remove")
-            if [[ "$username" =~ ^${USER_PREFIX}[a-zA-Z0-9_]{8}$ ]]; then
-                remove_ssh_user "$username"
-                echo "SUCCESS"
-            else
-                echo "ERROR: Invalid username format"
-            fi
-            ;;
-        "list")
-            list_eryzaa_users
-            ;;
-        *)
-            echo "ERROR: Unknown action"
-            ;;
-    esac
-}
-
-# Main service loop
+# The daemon is the eryzaa-ssh-service binary from core/ssh-manager, which listens on a
+# Unix socket under /run and checks who is calling. The user running sudo may call it.
 main() {
     if [[ $EUID -ne 0 ]]; then
         echo "This script must be run as root"
         exit 1
     fi
     
-    log_message "Starting Eryzaa SSH management service"
-    
-    # Remove old socket if exists
-    rm -f "$SOCKET_PATH"
-    
-    # Create named pipe for communication
-    mkfifo "$SOCKET_PATH"
-    chmod 666 "$SOCKET_PATH"
-    
-    log_message "Service listening on $SOCKET_PATH"
+    local service
+    service="$(command -v eryzaa-ssh-service || echo "$(dirname "$0")/../core/target/release/eryzaa-ssh-service")"
+    if [[ ! -x "$service" ]]; then
+        echo "eryzaa-ssh-service not found; build it with: cargo build --release -p eryzaa-ssh-manager"
+        exit 1
+    fi
     
-    while true; do
-        if read -r request < "$SOCKET_PATH"; then
-            response=$(handle_request "$request")
-            echo "$response" > "${SOCKET_PATH}.response"
-        fi
-    done
-}
-
-# Handle cleanup on exit
-cleanup() {
-    log_message "Shutting down Eryzaa SSH management service"
-    rm -f "$SOCKET_PATH" "${SOCKET_PATH}.response"
-    exit 0
+    log_message "Starting Eryzaa SSH management service"
+    if [[ -n "${SUDO_USER:-}" ]]; then
+        exec "$service" serve --allow "$SUDO_USER"
+    fi
+    exec "$service" serve
 }
 
-trap cleanup SIGTERM SIGINT
-
 # Check command line arguments
 case "${1:-daemon}" in
     "daemon")
@@ -162,6 +153,9 @@ case "${1:-daemon}" in
     "create")
         create_ssh_user "$2" "$3"
         ;;
+    "createkey")
+        create_ssh_user_with_key "$2" "$3"
+        ;;
     "remove")
         remove_ssh_user "$2"
         ;;
@@ -169,7 +163,7 @@ case "${1:-daemon}" in
         list_eryzaa_users
         ;;
     *)
-        echo "Usage: $0 [daemon|create|remove|list] [username] [password]"
+        echo "Usage: $0 [daemon|create|createkey|remove|list] [username] [password|public-key]"
         exit 1
         ;;
 esac